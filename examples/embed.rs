@@ -0,0 +1,27 @@
+//! Run nbuild from another application, with the surface of the prelude only:
+//!
+//! ```text
+//! cargo run --example embed -- --no-tui --release
+//! ```
+
+use std::process::ExitCode;
+
+use cargo_nbuild::prelude::*;
+
+fn main() -> ExitCode {
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let options = match AppOptions::parse_from(&args) {
+    Ok(options) => options,
+    Err(e) => {
+      eprintln!("invalid arguments: {}", e);
+      return ExitCode::FAILURE;
+    }
+  };
+  match App::new(options).run() {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("{}", e);
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,37 @@
+//! Prepare a build log without the terminal UI, with the surface of the prelude only:
+//!
+//! ```text
+//! cargo build 2>&1 | cargo run --example headless
+//! ```
+
+use std::{
+  io::{stdin, BufRead},
+  process::ExitCode,
+};
+
+use cargo_nbuild::prelude::*;
+
+fn main() -> ExitCode {
+  set_active_rule(&DEFAULT_RULES[0].aliases[0]);
+  let mut build = BuildOutput::default();
+  for line in stdin().lock().lines() {
+    match line {
+      Ok(line) => build.push(BuildEntry::new(line, Origin::Stdin)),
+      Err(e) => {
+        eprintln!("failed to read stdin, {}", e);
+        return ExitCode::FAILURE;
+      }
+    }
+  }
+  build.prepare();
+  for entry in build
+    .errors()
+    .iter()
+    .filter_map(|id| build.entries().get(*id))
+  {
+    println!("{}", entry.message());
+  }
+  let summary = BuildSummary::new(&build, None);
+  println!("{}", summary.line(ExitCodeMode::Errors));
+  ExitCode::from(ExitCodeMode::Errors.exit_code(&summary))
+}
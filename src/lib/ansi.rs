@@ -0,0 +1,430 @@
+use std::{cell::Cell, ops::Range};
+
+use ratatui::{
+  style::{Color, Modifier, Style},
+  text::{Line, Span},
+};
+
+/// The escape character starting every ANSI sequence
+const ESC: char = '\x1b';
+
+/// A line whose ANSI escape sequences were translated into [`Style`]s.
+///
+/// The [`AnsiLine::text`] is free of escape bytes, the styles are expressed
+/// as byte ranges of that text.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnsiLine {
+  text: String,
+  styles: Vec<(Range<usize>, Style)>,
+}
+
+impl AnsiLine {
+  /// Parse a raw line: SGR sequences are translated, the other sequences dropped
+  pub fn parse<S: AsRef<str>>(raw: S) -> Self {
+    let mut ret = Self::default();
+    let mut style = Style::default();
+    let mut start = 0;
+    let mut chars = raw.as_ref().chars().peekable();
+    while let Some(ch) = chars.next() {
+      if ch != ESC {
+        ret.text.push(ch);
+        continue;
+      }
+      match chars.next() {
+        // CSI: parameters then a final byte in the 0x40..=0x7E range
+        Some('[') => {
+          let mut params = String::new();
+          let mut final_byte = None;
+          for ch in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&ch) {
+              final_byte = Some(ch);
+              break;
+            }
+            params.push(ch);
+          }
+          if final_byte == Some('m') {
+            let new_style = Self::apply_sgr(style, &params);
+            if new_style != style {
+              ret.push_style(start..ret.text.len(), style);
+              start = ret.text.len();
+              style = new_style;
+            }
+          }
+        }
+        // OSC (hyperlinks...): terminated by BEL or ST
+        Some(']') => {
+          while let Some(ch) = chars.next() {
+            if ch == '\x07' {
+              break;
+            }
+            if ch == ESC && chars.peek() == Some(&'\\') {
+              chars.next();
+              break;
+            }
+          }
+        }
+        _ => {}
+      }
+    }
+    ret.push_style(start..ret.text.len(), style);
+    ret
+  }
+
+  /// Remove every escape sequence from a raw line
+  pub fn strip<S: AsRef<str>>(raw: S) -> String {
+    let mut ret = String::with_capacity(raw.as_ref().len());
+    Self::strip_into(raw, &mut ret);
+    ret
+  }
+
+  /// Append the [`Self::strip`]ped `raw` line to `buf`, the styles are not parsed
+  pub fn strip_into<S: AsRef<str>>(raw: S, buf: &mut String) {
+    let raw = raw.as_ref();
+    if !raw.contains(ESC) {
+      buf.push_str(raw);
+      return;
+    }
+    let mut chars = raw.chars().peekable();
+    while let Some(ch) = chars.next() {
+      if ch != ESC {
+        buf.push(ch);
+        continue;
+      }
+      match chars.next() {
+        Some('[') => {
+          for ch in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&ch) {
+              break;
+            }
+          }
+        }
+        Some(']') => {
+          while let Some(ch) = chars.next() {
+            if ch == '\x07' {
+              break;
+            }
+            if ch == ESC && chars.peek() == Some(&'\\') {
+              chars.next();
+              break;
+            }
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// Run `f` on the [`Self::strip`]ped `raw` line: borrowed when free of escape bytes,
+  /// or else stripped into a scratch buffer reused by the thread
+  pub fn with_stripped<R, F: FnOnce(&str) -> R>(raw: &str, f: F) -> R {
+    thread_local! {
+      static SCRATCH: Cell<String> = const { Cell::new(String::new()) };
+    }
+    if !raw.contains(ESC) {
+      return f(raw);
+    }
+    // taken out, `f` may strip another line
+    let mut buf = SCRATCH.take();
+    buf.clear();
+    Self::strip_into(raw, &mut buf);
+    let ret = f(&buf);
+    SCRATCH.set(buf);
+    ret
+  }
+
+  /// Retrieve the text, without escape sequences
+  pub fn text(&self) -> &String {
+    &self.text
+  }
+
+  /// Retrieve the styled byte ranges of the text
+  pub fn styles(&self) -> &Vec<(Range<usize>, Style)> {
+    &self.styles
+  }
+
+  /// Check if any part of the text is styled
+  pub fn is_styled(&self) -> bool {
+    !self.styles.is_empty()
+  }
+
+  /// Retrieve the style applied at a byte position
+  pub fn style_at(&self, pos: usize) -> Style {
+    self
+      .styles
+      .iter()
+      .find(|(range, _)| range.contains(&pos))
+      .map(|(_, style)| *style)
+      .unwrap_or_default()
+  }
+
+  /// Build the [`Span`]s covering `range`, unstyled parts included
+  pub fn spans(&self, range: Range<usize>) -> Vec<Span<'static>> {
+    let mut ret = vec![];
+    let mut cursor = range.start;
+    for (styled, style) in &self.styles {
+      let start = styled.start.max(range.start);
+      let end = styled.end.min(range.end);
+      if start >= end {
+        continue;
+      }
+      if cursor < start {
+        ret.push(Span::raw(self.text[cursor..start].to_string()));
+      }
+      ret.push(Span::styled(self.text[start..end].to_string(), *style));
+      cursor = end;
+    }
+    if cursor < range.end {
+      ret.push(Span::raw(self.text[cursor..range.end].to_string()));
+    }
+    ret
+  }
+
+  /// Write a styled line back as a raw line, the inverse of [`Self::parse`]
+  pub fn render(line: &Line) -> String {
+    let mut ret = String::new();
+    for span in &line.spans {
+      let style = line.style.patch(span.style);
+      let params = Self::sgr_params(style);
+      match params.is_empty() || span.content.is_empty() {
+        true => ret.push_str(&span.content),
+        false => ret.push_str(&format!("{}[{}m{}{}[0m", ESC, params, span.content, ESC)),
+      }
+    }
+    ret
+  }
+
+  /// Build the parameters of the SGR sequence applying `style`
+  fn sgr_params(style: Style) -> String {
+    let mut params = [
+      (Modifier::BOLD, 1),
+      (Modifier::DIM, 2),
+      (Modifier::ITALIC, 3),
+      (Modifier::UNDERLINED, 4),
+      (Modifier::SLOW_BLINK, 5),
+      (Modifier::RAPID_BLINK, 6),
+      (Modifier::REVERSED, 7),
+      (Modifier::HIDDEN, 8),
+      (Modifier::CROSSED_OUT, 9),
+    ]
+    .into_iter()
+    .filter(|(modifier, _code)| style.add_modifier.contains(*modifier))
+    .map(|(_modifier, code)| code.to_string())
+    .collect::<Vec<_>>();
+    params.extend(style.fg.and_then(|color| Self::sgr_color(color, 30)));
+    params.extend(style.bg.and_then(|color| Self::sgr_color(color, 40)));
+    params.join(";")
+  }
+
+  /// Build the parameters of a foreground (`base` 30) or background (`base` 40) color
+  fn sgr_color(color: Color, base: u16) -> Option<String> {
+    let code = match color {
+      Color::Reset => return None,
+      Color::Black => 0,
+      Color::Red => 1,
+      Color::Green => 2,
+      Color::Yellow => 3,
+      Color::Blue => 4,
+      Color::Magenta => 5,
+      Color::Cyan => 6,
+      Color::Gray => 7,
+      Color::DarkGray => 8,
+      Color::LightRed => 9,
+      Color::LightGreen => 10,
+      Color::LightYellow => 11,
+      Color::LightBlue => 12,
+      Color::LightMagenta => 13,
+      Color::LightCyan => 14,
+      Color::White => 15,
+      Color::Indexed(i) => return Some(format!("{};5;{}", base + 8, i)),
+      Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    };
+    Some(match code {
+      0..=7 => (base + code).to_string(),
+      _ => (base + 60 + code - 8).to_string(),
+    })
+  }
+
+  fn push_style(&mut self, range: Range<usize>, style: Style) {
+    if !range.is_empty() && style != Style::default() {
+      self.styles.push((range, style));
+    }
+  }
+
+  fn ansi_color(code: u16) -> Color {
+    match code {
+      0 => Color::Black,
+      1 => Color::Red,
+      2 => Color::Green,
+      3 => Color::Yellow,
+      4 => Color::Blue,
+      5 => Color::Magenta,
+      6 => Color::Cyan,
+      7 => Color::Gray,
+      8 => Color::DarkGray,
+      9 => Color::LightRed,
+      10 => Color::LightGreen,
+      11 => Color::LightYellow,
+      12 => Color::LightBlue,
+      13 => Color::LightMagenta,
+      14 => Color::LightCyan,
+      _ => Color::White,
+    }
+  }
+
+  /// Parse the `5;n` or `2;r;g;b` extended color parameters
+  fn extended_color<I: Iterator<Item = u16>>(codes: &mut I) -> Option<Color> {
+    match codes.next()? {
+      5 => Some(Color::Indexed(codes.next()? as u8)),
+      2 => Some(Color::Rgb(
+        codes.next()? as u8,
+        codes.next()? as u8,
+        codes.next()? as u8,
+      )),
+      _ => None,
+    }
+  }
+
+  /// Apply the parameters of a SGR sequence (`ESC [ params m`) to a style
+  fn apply_sgr(mut style: Style, params: &str) -> Style {
+    let mut codes = params
+      .split(';')
+      .map(|code| code.parse::<u16>().unwrap_or(0))
+      .collect::<Vec<_>>()
+      .into_iter();
+    while let Some(code) = codes.next() {
+      style = match code {
+        0 => Style::default(),
+        1 => style.add_modifier(Modifier::BOLD),
+        2 => style.add_modifier(Modifier::DIM),
+        3 => style.add_modifier(Modifier::ITALIC),
+        4 => style.add_modifier(Modifier::UNDERLINED),
+        5 => style.add_modifier(Modifier::SLOW_BLINK),
+        6 => style.add_modifier(Modifier::RAPID_BLINK),
+        7 => style.add_modifier(Modifier::REVERSED),
+        8 => style.add_modifier(Modifier::HIDDEN),
+        9 => style.add_modifier(Modifier::CROSSED_OUT),
+        22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+        23 => style.remove_modifier(Modifier::ITALIC),
+        24 => style.remove_modifier(Modifier::UNDERLINED),
+        25 => style.remove_modifier(Modifier::SLOW_BLINK | Modifier::RAPID_BLINK),
+        27 => style.remove_modifier(Modifier::REVERSED),
+        28 => style.remove_modifier(Modifier::HIDDEN),
+        29 => style.remove_modifier(Modifier::CROSSED_OUT),
+        30..=37 => style.fg(Self::ansi_color(code - 30)),
+        38 => match Self::extended_color(&mut codes) {
+          Some(color) => style.fg(color),
+          None => style,
+        },
+        39 => Style { fg: None, ..style },
+        40..=47 => style.bg(Self::ansi_color(code - 40)),
+        48 => match Self::extended_color(&mut codes) {
+          Some(color) => style.bg(color),
+          None => style,
+        },
+        49 => Style { bg: None, ..style },
+        90..=97 => style.fg(Self::ansi_color(code - 90 + 8)),
+        100..=107 => style.bg(Self::ansi_color(code - 100 + 8)),
+        _ => style,
+      };
+    }
+    style
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+  };
+
+  use super::AnsiLine;
+
+  #[test]
+  fn parse_sgr() {
+    let line = AnsiLine::parse(
+      "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0425]\x1b[0m\x1b[0m\x1b[1m: cannot find value\x1b[0m",
+    );
+    assert_eq!(line.text(), "error[E0425]: cannot find value");
+    assert_eq!(
+      line.styles(),
+      &vec![
+        (0..12, Style::default().bold().fg(Color::Indexed(9))),
+        (12..31, Style::default().bold()),
+      ]
+    );
+  }
+
+  #[test]
+  fn parse_colors() {
+    let line = AnsiLine::parse("\x1b[31;44mred\x1b[39mblue\x1b[0m \x1b[38;2;255;128;0morange");
+    assert_eq!(line.text(), "redblue orange");
+    assert_eq!(
+      line.styles(),
+      &vec![
+        (0..3, Style::default().red().on_blue()),
+        (3..7, Style::default().on_blue()),
+        (8..14, Style::default().fg(Color::Rgb(255, 128, 0))),
+      ]
+    );
+  }
+
+  #[test]
+  fn strip() {
+    assert_eq!(AnsiLine::strip("plain"), "plain");
+    assert_eq!(
+      AnsiLine::strip("\x1b[1mbold\x1b[0m \x1b]8;;file:///a\x1b\\link\x1b]8;;\x07\x1b[2K"),
+      "bold link"
+    );
+    // the same text as the parsed line, reentrant
+    let raw = "\x1b[1m\x1b[38;5;12m --> \x1b[0msrc/lib.rs:4:9\x1b[K";
+    assert_eq!(&AnsiLine::strip(raw), AnsiLine::parse(raw).text());
+    let nested = AnsiLine::with_stripped(raw, |outer| {
+      AnsiLine::with_stripped("\x1b[1minner", |inner| format!("{outer}|{inner}"))
+    });
+    assert_eq!(nested, " --> src/lib.rs:4:9|inner");
+    assert_eq!(AnsiLine::with_stripped(raw, str::len), 19);
+  }
+
+  #[test]
+  fn spans() {
+    let line = AnsiLine::parse("ab\x1b[1mcd\x1b[0mef");
+    assert_eq!(
+      line.spans(1..5),
+      vec![
+        Span::raw("b"),
+        Span::styled("cd", Style::default().bold()),
+        Span::raw("e")
+      ]
+    );
+    assert_eq!(line.spans(4..6), vec![Span::raw("ef")]);
+  }
+
+  #[test]
+  fn render() {
+    let line = Line::default().spans([
+      Span::styled("error", Style::default().bold().light_red()),
+      Span::raw(": "),
+      Span::styled("boom", Style::default().fg(Color::Indexed(9)).on_blue()),
+      Span::styled("!", Style::default().fg(Color::Rgb(255, 128, 0)).dim()),
+    ]);
+    let raw = AnsiLine::render(&line);
+    assert_eq!(
+      raw,
+      "\x1b[1;91merror\x1b[0m: \x1b[38;5;9;44mboom\x1b[0m\x1b[2;38;2;255;128;0m!\x1b[0m"
+    );
+    // parsed back to the same styles
+    let parsed = AnsiLine::parse(&raw);
+    assert_eq!(parsed.text(), "error: boom!");
+    assert_eq!(parsed.spans(0..parsed.text().len()), line.spans);
+    assert_eq!(AnsiLine::render(&Line::raw("plain")), "plain");
+    // the style of the line applies to every span
+    let line = Line::default()
+      .spans([Span::raw("a"), Span::raw("b").bold()])
+      .red();
+    assert_eq!(
+      AnsiLine::render(&line),
+      "\x1b[31ma\x1b[0m\x1b[1;31mb\x1b[0m"
+    );
+  }
+}
@@ -1,32 +1,48 @@
 use crate::{
-  default_system_location, init_rules, load_rules, save_rules, set_active_rule, BuildEntry,
-  BuildEvent, Debug, Origin, Rule, DEFAULT_RULES,
+  active_rule, check_rules, default_system_location, err, init_rules, load_rules,
+  localize_active_rule, rule_files, save_rules, set_active_rule, set_manifest_path,
+  set_thousands_separator, BuildEntry, BuildEvent, BuildOutput, BuildSnapshot, BuildSummary,
+  ConfigExplanation, Debug, ErrorKind, Excerpt, ExplainFormat, History, IntakeFilter, Origin, Rule,
+  DEFAULT_RULES,
 };
 
 use std::{
-  collections::VecDeque,
-  io::stdout,
+  fs::File,
+  io::{self, stdout, IsTerminal, Write},
   path::PathBuf,
-  process::exit,
-  sync::mpsc::channel,
+  process::ExitCode,
+  sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, SyncSender},
   thread::{spawn, JoinHandle},
+  time::{Duration, Instant, SystemTime},
 };
 
-use ratatui::{
-  crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
-    execute,
-  },
-  restore,
+use ratatui::crossterm::{event::DisableMouseCapture, execute};
+
+use super::{
+  AppOptions, BuildManager, BuildRequest, Builder, DiffViewer, Doctor, DoctorEnv, IpcServer,
+  PrepareCommand, Preparer, Renderer, RunContext, Scanner, SessionInfo, Supervisor, TerminalGuard,
+  TerminalInit,
 };
 
-use super::{AppOptions, Builder, Renderer, Scanner};
+/// How the build gets displayed, see [`App::frontend`]
+enum Frontend {
+  /// The entries get echoed to stdout, see `--no-tui`
+  Plain,
+  /// The terminal UI, restored once the guard is dropped
+  Terminal(TerminalGuard, ratatui::DefaultTerminal),
+}
 
 /// Represent the application data
 pub struct App {
   options: AppOptions,
   rules: Vec<Rule>,
-  threads: VecDeque<JoinHandle<()>>,
+  threads: Supervisor,
+  /// The summary of the build, once finished
+  summary: Option<BuildSummary>,
+  /// The files of the session, shown by the renderer
+  session: SessionInfo,
+  /// Set up the terminal of the UI
+  terminal_init: TerminalInit,
 }
 
 impl App {
@@ -34,23 +50,342 @@ impl App {
   pub fn new(options: AppOptions) -> Self {
     Self {
       options,
-      threads: VecDeque::new(),
+      threads: Supervisor::default(),
       rules: DEFAULT_RULES.clone(),
+      summary: None,
+      session: SessionInfo::default(),
+      terminal_init: TerminalGuard::init_terminal,
     }
   }
 
+  /// Set up the terminal of the UI with `init` rather than [`TerminalGuard::init_terminal`]
+  pub fn with_terminal_init(mut self, init: TerminalInit) -> Self {
+    self.terminal_init = init;
+    self
+  }
+
   /// Define the panic hook to restore the terminal to it's default state after panics
   fn set_panic_hook() {
     let hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
-      Renderer::restore_terminal();
+      TerminalGuard::restore_terminal();
       Debug::log(format!("Panic {:?}", panic_info));
+      Debug::flush();
       hook(panic_info);
     }));
   }
 
+  /// Spawn the build thread: either scan the build log (the `input` file or stdin)
+  /// or run the [`Builder`]
+  fn spawn_build(
+    options: AppOptions,
+    input: Option<File>,
+    tx_build_output: SyncSender<BuildEntry>,
+    tx_build_events: Sender<BuildEvent>,
+  ) -> JoinHandle<crate::Result<()>> {
+    spawn(move || match (options.reads_log(), input) {
+      (_, Some(file)) => Scanner::new(Origin::Stdin, tx_build_output, tx_build_events)
+        .with_file(file)
+        .with_intake_filter(IntakeFilter::from_options(&options))
+        .run(),
+      (true, None) => Scanner::new(Origin::Stdin, tx_build_output, tx_build_events)
+        .with_intake_filter(IntakeFilter::from_options(&options))
+        .run(),
+      (false, None) => Builder::new(options, tx_build_output, tx_build_events).run(),
+    })
+  }
+
+  /// Open the build log given on the command line, before the UI starts
+  fn open_input(&self) -> crate::Result<Option<File>> {
+    let Some(path) = self.options.input.as_ref() else {
+      return Ok(None);
+    };
+    File::open(path).map(Some).map_err(|e| {
+      err!(
+        ErrorKind::IO,
+        "failed to read build log '{}', {}",
+        path.display(),
+        e
+      )
+    })
+  }
+
+  /// Run the build without the terminal UI, echoing the entries to `out`.
+  /// When reading `stdin`, a hint is printed if nothing gets piped
+  fn run_headless<W: Write>(
+    stdin: bool,
+    build_output: Receiver<BuildEntry>,
+    build_events: Receiver<BuildEvent>,
+    out: &mut W,
+  ) -> BuildSummary {
+    let mut build = BuildOutput::default().with_noise_removed(false);
+    let mut exit_status = None;
+    let started_at = Instant::now();
+    let mut hinted = !stdin;
+    loop {
+      let done = match build_events.recv_timeout(Duration::from_millis(50)) {
+        Ok(BuildEvent::BuildFinished(status)) => {
+          exit_status = Some(status);
+          true
+        }
+        Ok(BuildEvent::BuildFailed(failure)) => {
+          eprintln!("\x1b[90mnbuild:\x1b[0m {}", failure);
+          false
+        }
+        Ok(_) | Err(RecvTimeoutError::Timeout) => false,
+        Err(RecvTimeoutError::Disconnected) => true,
+      };
+      build.pull(&build_output);
+      if !hinted && !build.has_any_entries() && started_at.elapsed() >= Scanner::IDLE_HINT_DELAY {
+        hinted = true;
+        eprintln!("\x1b[90mnbuild:\x1b[0m {}", Scanner::IDLE_HINT);
+      }
+      let start = build.cursor();
+      if build.prepare() {
+        for entry in &build.entries()[start..build.cursor()] {
+          let _ = writeln!(out, "{}", entry.message());
+        }
+      }
+      if done {
+        break;
+      }
+    }
+    BuildSummary::new(&build, exit_status).with_duration(started_at.elapsed())
+  }
+
+  /// Print the summary line, the produced artifacts and compute the process exit code.
+  /// The summary is kept for the line printed to scripts, see [`App::run`]
+  fn finish(&mut self, summary: BuildSummary) -> ExitCode {
+    eprintln!(
+      "\x1b[90mnbuild:\x1b[0m {}",
+      summary.line(self.options.exit_code)
+    );
+    if let Some(hint) = summary.hint_line() {
+      eprintln!("\x1b[90mnbuild:\x1b[0m \x1b[35m{}\x1b[0m", hint);
+    }
+    for line in summary.target_lines() {
+      eprintln!("\x1b[90mnbuild:\x1b[0m {}", line);
+    }
+    for line in summary.artifact_lines() {
+      eprintln!("\x1b[90mnbuild:\x1b[0m {}", line);
+    }
+    let exit_code = self.options.exit_code.exit_code(&summary);
+    self.summary = Some(summary);
+    ExitCode::from(exit_code)
+  }
+
+  /// Compare the logs given to `--diff`, exiting with 1 if there are regressions
+  fn run_diff(&self) -> crate::Result<ExitCode> {
+    let [old, new] = match self.options.diff.as_slice() {
+      [old, new] => [old, new],
+      _ => {
+        return Err(err!(
+          ErrorKind::Parsing,
+          "--diff expects two build logs: --diff <OLD> <NEW>"
+        ))
+      }
+    };
+    let viewer = DiffViewer::new(old, new)?;
+    match self.try_terminal() {
+      Some((guard, terminal)) => {
+        // the viewer does not handle the mouse, the terminal keeps its selection
+        let _ = execute!(stdout(), DisableMouseCapture);
+        App::set_panic_hook();
+        let result = viewer.run(terminal);
+        drop(guard);
+        result.map_err(|e| err!(ErrorKind::IO, "failed to show the diff, {}", e))?;
+      }
+      None => viewer.print(),
+    }
+    Ok(match viewer.diff().regressions().count() {
+      0 => ExitCode::SUCCESS,
+      _ => ExitCode::FAILURE,
+    })
+  }
+
+  /// Print the excerpt of the build log given to `--render`, exiting with 1 if it does not exist
+  fn run_render(&self, excerpt: &Excerpt, input: Option<File>) -> crate::Result<ExitCode> {
+    if !self.options.reads_log() {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--render prints an excerpt of a build log: --render {} <LOG>",
+        excerpt
+      ));
+    }
+    let (tx_entries, rx_entries) = Builder::entries_channel(&self.options);
+    let (tx_events, _rx_events) = channel::<BuildEvent>();
+    let scanner = Scanner::new(Origin::Stdin, tx_entries, tx_events);
+    // the queue is bounded, the log gets pulled while scanned
+    let scanner = spawn(move || match input {
+      Some(file) => scanner.with_file(file).run(),
+      None => scanner.run(),
+    });
+    let mut build = BuildOutput::default()
+      .with_inline_markers(self.options.inline_markers)
+      .with_ansi_colors(self.options.respect_cargo_colors)
+      .with_tab_width(self.options.tab_width)
+      .with_max_line_length(self.options.max_line_length);
+    for entry in rx_entries.iter() {
+      build.push(entry);
+    }
+    scanner
+      .join()
+      .map_err(|_| err!(ErrorKind::Thread, "the scanner thread panicked"))??;
+    build.prepare();
+    let colored = self.options.color.is_colored(stdout().is_terminal());
+    match excerpt.render(&mut build, colored) {
+      Ok(lines) => {
+        for line in lines {
+          println!("{}", line);
+        }
+        Ok(ExitCode::SUCCESS)
+      }
+      Err(e) => {
+        eprintln!("\x1b[0;31merror\x1b[0m: {}", e);
+        Ok(ExitCode::FAILURE)
+      }
+    }
+  }
+
+  /// Load and validate the config, see `--check-config`
+  fn check_config(&self) -> ExitCode {
+    match check_rules(self.options.config_path.clone()) {
+      Ok((path, rules)) => {
+        println!("{}: {} rule(s), ok", path.display(), rules.len());
+        ExitCode::SUCCESS
+      }
+      Err(e) => {
+        eprintln!("\x1b[0;31merror\x1b[0m: {}", e);
+        if let Some(snippet) = e.snippet() {
+          eprintln!("{}", snippet);
+        }
+        ExitCode::FAILURE
+      }
+    }
+  }
+
+  /// Print the effective settings and where they come from, see `--explain-config`
+  fn explain_config(&self, format: ExplainFormat) -> crate::Result<ExitCode> {
+    let explanation = ConfigExplanation::new(&self.options, &active_rule());
+    match format {
+      ExplainFormat::Text => {
+        for line in explanation.lines() {
+          println!("{}", line);
+        }
+      }
+      ExplainFormat::Json => println!("{}", explanation.to_json()?),
+    }
+    Ok(ExitCode::SUCCESS)
+  }
+
+  /// Print the run directories, see `--runs`
+  fn list_runs() -> ExitCode {
+    let root = RunContext::runs_dir();
+    match RunContext::list(&root) {
+      Ok(runs) => {
+        for run in runs {
+          println!("{}", run.line());
+        }
+        ExitCode::SUCCESS
+      }
+      Err(e) if e.kind() == io::ErrorKind::NotFound => ExitCode::SUCCESS,
+      Err(e) => {
+        eprintln!(
+          "\x1b[0;31merror\x1b[0m: failed to list {}, {}",
+          root.display(),
+          e
+        );
+        ExitCode::FAILURE
+      }
+    }
+  }
+
+  /// Diagnose the environment, see `--doctor`
+  fn doctor(&self) -> ExitCode {
+    let checks = Doctor::checks(&DoctorEnv::detect(&self.options));
+    for line in Doctor::report(&checks, stdout().is_terminal()) {
+      println!("{}", line);
+    }
+    Doctor::exit_code(&checks)
+  }
+
+  /// Prune the stale run directories and create the one of this run, capturing the
+  /// debug log. The run goes on without it if the cache directory is not writable
+  fn start_run(keep: bool) -> Option<RunContext> {
+    let root = RunContext::runs_dir();
+    if let Ok(pruned) = RunContext::prune(&root, RunContext::MAX_AGE, SystemTime::now()) {
+      for path in pruned {
+        Debug::log(format!("Pruned run directory {}", path.display()));
+      }
+    }
+    match RunContext::create_in(&root) {
+      Ok(run) => {
+        let mut run = run.with_keep(keep);
+        if let Err(e) = run.capture_debug_log() {
+          Debug::log(format!("failed to create the debug log, {}", e));
+        }
+        Some(run)
+      }
+      Err(e) => {
+        Debug::log(format!(
+          "failed to create a run directory in {}, {}",
+          root.display(),
+          e
+        ));
+        None
+      }
+    }
+  }
+
   /// Run the whole application
-  pub fn run(&mut self) -> crate::Result<()> {
+  pub fn run(&mut self) -> crate::Result<ExitCode> {
+    set_thousands_separator(self.options.thousands_separator);
+    if self.options.check_config {
+      return Ok(self.check_config());
+    }
+    if self.options.list_runs {
+      return Ok(Self::list_runs());
+    }
+    if self.options.doctor {
+      return Ok(self.doctor());
+    }
+    let builds =
+      !self.options.reads_log() && self.options.diff.is_empty() && self.options.render.is_none();
+    for check in Doctor::startup_checks(&DoctorEnv::detect(&self.options), builds) {
+      eprintln!("{}", check.warning_line());
+    }
+    if let Some(path) = self.options.manifest_path.as_ref() {
+      set_manifest_path(path);
+    }
+    let mut run = Self::start_run(self.options.keep_artifacts);
+    self.session = SessionInfo::default().with_run(run.as_ref());
+    let result = self.run_in_context();
+    if let Some(run) = run.as_mut() {
+      // the debug log of a failed run is worth a look
+      if result.is_err() {
+        run.keep();
+      }
+      if run.is_kept() {
+        eprintln!(
+          "\x1b[90mnbuild:\x1b[0m run artifacts kept in {}",
+          run.dir().display()
+        );
+      }
+    }
+    // the trailing line, for scripts
+    if let Some(summary) = self.summary.as_ref() {
+      if self.options.prints_summary() {
+        eprintln!(
+          "{}",
+          summary.script_line(self.options.exit_code.exit_code(summary))
+        );
+      }
+    }
+    result
+  }
+
+  /// Run the application once the run directory is created
+  fn run_in_context(&mut self) -> crate::Result<ExitCode> {
     if let Some(path) = self.options.config_path.as_ref() {
       if self.options.eject_config {
         self.rules = init_rules(Some(path.clone()))?;
@@ -70,7 +405,13 @@ impl App {
       }
     }
 
+    self.session.config_files = rule_files();
     set_active_rule(&self.options.active_rule);
+    localize_active_rule(self.options.keep_locale);
+
+    if let Some(format) = self.options.explain_config {
+      return self.explain_config(format);
+    }
 
     if self.options.dump_rules {
       for r in &self.rules {
@@ -85,49 +426,244 @@ impl App {
             .join("\n")
         );
       }
-      exit(0);
+      return Ok(ExitCode::SUCCESS);
+    }
+
+    if !self.options.diff.is_empty() {
+      return self.run_diff();
+    }
+
+    let input = self.open_input()?;
+    if let Some(excerpt) = self.options.render.as_ref() {
+      return self.run_render(excerpt, input);
+    }
+    match self.frontend() {
+      Frontend::Plain => self.run_plain(input, &mut stdout()),
+      Frontend::Terminal(guard, terminal) => self.run_terminal(input, guard, terminal),
+    }
+  }
+
+  /// Choose how the build gets displayed: the terminal UI unless `--no-tui` is given.
+  /// The plain output is used as well, with a notice, if the terminal cannot be set up,
+  /// e.g. stdout is not a terminal or `TERM` is unset
+  fn frontend(&self) -> Frontend {
+    match self.try_terminal() {
+      Some((guard, terminal)) => Frontend::Terminal(guard, terminal),
+      None => Frontend::Plain,
+    }
+  }
+
+  /// Set up the terminal of the UI unless `--no-tui` is given, with a notice if it fails
+  fn try_terminal(&self) -> Option<(TerminalGuard, ratatui::DefaultTerminal)> {
+    if self.options.no_tui {
+      return None;
+    }
+    TerminalGuard::try_init(self.terminal_init)
+      .inspect_err(|e| {
+        eprintln!(
+          "\x1b[90mnbuild:\x1b[0m the terminal UI is unavailable ({}), falling back to --no-tui",
+          e
+        )
+      })
+      .ok()
+  }
+
+  /// Run the build without the terminal UI, the entries echoed to `out`
+  fn run_plain<W: Write>(&mut self, input: Option<File>, out: &mut W) -> crate::Result<ExitCode> {
+    let (tx_build_output, rx_build_output) = Builder::entries_channel(&self.options);
+    let (tx_build_events, rx_build_events) = channel::<BuildEvent>();
+    if self.options.pty_fallback() {
+      eprintln!(
+        "\x1b[90mnbuild:\x1b[0m --pty is not supported on this platform, the build output is piped"
+      );
     }
+    let build = Self::spawn_build(
+      self.options.clone(),
+      input,
+      tx_build_output,
+      tx_build_events,
+    );
+    let summary = Self::run_headless(self.options.stdin, rx_build_output, rx_build_events, out);
+    if !self.options.reads_log() {
+      History::new(History::default_path()).record(&self.options, &summary);
+    }
+    self.threads.add("build", build);
+    self.threads.join()?;
+    Ok(self.finish(summary))
+  }
+
+  /// Run the build in the terminal UI, set up by [`App::frontend`]
+  fn run_terminal(
+    &mut self,
+    input: Option<File>,
+    terminal_guard: TerminalGuard,
+    terminal: ratatui::DefaultTerminal,
+  ) -> crate::Result<ExitCode> {
+    let (tx_build_output, rx_build_output) = Builder::entries_channel(&self.options);
+    let (tx_build_events, rx_build_events) = channel::<BuildEvent>();
+    let ipc = match self.options.ipc.as_ref() {
+      Some(path) => IpcServer::bind(path)?,
+      None => IpcServer::default(),
+    };
+    let render_ipc = ipc.clone();
 
-    let mut terminal = ratatui::init();
-    let _ = terminal.clear();
-    let _ = execute!(stdout(), EnableMouseCapture);
+    // the guard restores the terminal once the renderer stopped, or while unwinding
     App::set_panic_hook();
 
     let (tx_user_quit, _rx_user_quit) = channel::<bool>();
-    let (tx_build_output, rx_build_output) = channel::<Vec<BuildEntry>>();
-    let (tx_build_events, rx_build_events) = channel::<BuildEvent>();
+    let (tx_summary, rx_summary) = channel::<BuildSummary>();
+    let (tx_snapshots, rx_snapshots) = channel::<BuildSnapshot>();
+    let (tx_commands, rx_commands) = channel::<PrepareCommand>();
     let render_options = self.options.clone();
+    let session = self.session.clone();
+    let prepare_options = self.options.clone();
     let build_options = self.options.clone();
 
-    let th_tx_events = tx_build_events.clone();
-    self.threads = VecDeque::from([
-      // render
-      spawn(move || {
-        Renderer::new(
-          render_options,
-          terminal,
-          tx_user_quit,
-          rx_build_output,
-          th_tx_events,
-          rx_build_events,
-        )
-        .run()
-      }),
-      // build
-      spawn(move || match build_options.stdin {
-        true => Scanner::new(Origin::Stdin, tx_build_output, tx_build_events).run(),
-        false => Builder::new(build_options, tx_build_output, tx_build_events).run(),
-      }),
-    ]);
-    let mut th_id = 0;
-    while let Some(th) = self.threads.pop_front() {
-      Debug::log(format!("Waiting for thread {}", th_id));
-      if let Err(e) = th.join() {
-        Debug::log(format!("failed to join thread #{}, {:?}", th_id, e))
-      }
-      th_id += 1
-    }
-    Debug::log(format!("Done with this shit..."));
-    Ok(())
+    // the build manager runs the builds, at most one at a time, until the renderer quits
+    let (tx_builds, rx_builds) = channel::<BuildRequest>();
+    let scanner = match self.options.reads_log() {
+      true => Some(Self::spawn_build(
+        self.options.clone(),
+        input,
+        tx_build_output,
+        tx_build_events.clone(),
+      )),
+      false => {
+        let _ = tx_builds.send(BuildRequest::Run(tx_build_output));
+        None
+      }
+    };
+    let manager_tx_events = tx_build_events.clone();
+    let prepare_tx_events = tx_build_events;
+    let render = spawn(move || {
+      Renderer::new(
+        render_options,
+        terminal,
+        tx_user_quit,
+        rx_snapshots,
+        tx_commands,
+        tx_builds,
+        rx_build_events,
+        tx_summary,
+      )
+      .with_ipc(render_ipc)
+      .with_session(session)
+      .run()
+    });
+    self.threads.spawn("prepare", move || {
+      Preparer::new(
+        prepare_options,
+        rx_build_output,
+        prepare_tx_events,
+        rx_commands,
+        tx_snapshots,
+      )
+      .with_ipc(ipc)
+      .run()
+    });
+    self.threads.spawn("build manager", move || {
+      BuildManager::new(build_options, rx_builds, manager_tx_events).run()
+    });
+    if let Some(scanner) = scanner {
+      self.threads.add("scanner", scanner);
+    }
+    Debug::log("Waiting for render thread");
+    let rendered = Supervisor::collect([("render", render.join())]);
+    // the errors get printed once the terminal is restored
+    drop(terminal_guard);
+    let joined = self.threads.join();
+    rendered.and(joined)?;
+    let summary = rx_summary.try_recv().unwrap_or_default();
+    Ok(self.finish(summary))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io,
+    path::Path,
+    process::ExitStatus,
+    sync::mpsc::{channel, sync_channel},
+    time::Duration,
+  };
+
+  use crate::{AppOptions, BuildEntry, BuildEvent, BuildSummary, ExitCodeMode, Origin};
+
+  use super::{App, Frontend};
+
+  /// Run the headless path over a build log, as if the build exited with `status`
+  fn headless(log: &str, status: ExitStatus) -> BuildSummary {
+    let lines = log.lines().collect::<Vec<_>>();
+    let (tx_entries, rx_entries) = sync_channel(lines.len());
+    let (tx_events, rx_events) = channel();
+    for line in lines {
+      tx_entries
+        .send(BuildEntry::new(line, Origin::Stdout))
+        .unwrap();
+    }
+    tx_events.send(BuildEvent::BuildFinished(status)).unwrap();
+    App::run_headless(false, rx_entries, rx_events, &mut Vec::new())
+      .with_duration(Duration::from_millis(1250))
+  }
+
+  #[test]
+  fn terminal_fallback() {
+    let log = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rust/rust-targets.log");
+    let options = AppOptions::builder().input(&log).finish().unwrap();
+    let mut app = App::new(options).with_terminal_init(|| Err(io::Error::other("TERM is not set")));
+    assert!(matches!(app.frontend(), Frontend::Plain));
+    let mut out = Vec::new();
+    let input = app.open_input().unwrap();
+    app.run_plain(input, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 29);
+    assert!(out.contains("warning: `demo` (lib test) generated"));
+    let summary = app.summary.as_ref().unwrap();
+    assert_eq!(summary.num_warnings, 2);
+  }
+
+  #[test]
+  fn diff_terminal_fallback() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rust");
+    let options = AppOptions::builder()
+      .diff_log(fixtures.join("rust-targets.log"))
+      .diff_log(fixtures.join("rust-macro-backtrace.log"))
+      .finish()
+      .unwrap();
+    let app = App::new(options).with_terminal_init(|| Err(io::Error::other("TERM is not set")));
+    // printed rather than panicking without a terminal
+    assert!(app.run_diff().is_ok());
+  }
+
+  #[test]
+  fn script_line() {
+    let summary = headless(
+      include_str!("../../../tests/fixtures/rust/rust-macro-backtrace.log"),
+      ExitStatus::default(),
+    );
+    assert_eq!(
+      summary.script_line(ExitCodeMode::Build.exit_code(&summary)),
+      "nbuild: exit=0 errors=1 warnings=1 notes=2 duration=1.25"
+    );
+    assert_eq!(
+      summary.script_line(ExitCodeMode::Errors.exit_code(&summary)),
+      "nbuild: exit=1 errors=1 warnings=1 notes=2 duration=1.25"
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn script_line_failed_build() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let summary = headless(
+      include_str!("../../../tests/fixtures/c/gcc-fr.log"),
+      ExitStatus::from_raw(2 << 8),
+    );
+    assert_eq!(
+      summary.script_line(ExitCodeMode::Build.exit_code(&summary)),
+      "nbuild: exit=2 errors=0 warnings=0 notes=0 duration=1.25"
+    );
   }
 }
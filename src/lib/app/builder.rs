@@ -1,76 +1,578 @@
 use std::{
-  io::{BufRead, BufReader},
-  sync::mpsc::Sender,
-  thread::spawn,
+  io::{self, BufReader, ErrorKind, Read, Write},
+  process::Stdio,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError},
+    Arc,
+  },
+  thread::{sleep, spawn},
+  time::{Duration, Instant},
 };
 
-use crate::{active_rule, BuildCommand, BuildEntry, BuildEvent, Debug, Origin};
+use chrono::Local;
 
-use super::AppOptions;
+use crate::{
+  active_rule, build_progress, err, overwritten_line, read_line_timed, BuildCommand, BuildEntry,
+  BuildEvent, BuildFailure, Debug, IntakeFilter, IntakeStats, Origin,
+};
+
+use super::{AppOptions, RotatingWriter};
 
 pub struct Builder {
   options: AppOptions,
-  tx_entries: Sender<Vec<BuildEntry>>,
+  tx_entries: SyncSender<BuildEntry>,
   tx_events: Sender<BuildEvent>,
+  cancel: Arc<AtomicBool>,
+  input: Option<Receiver<String>>,
+  intake_filter: Option<IntakeFilter>,
 }
 
 impl Builder {
+  /// Time spent waiting for output lines between two child status polls
+  pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+  /// Maximum time spent draining the pipes once the child exited
+  pub const DRAIN_DEADLINE: Duration = Duration::from_secs(2);
+
+  /// The entries queued ahead of the renderer, unless defined with `--queue-capacity`
+  pub const QUEUE_CAPACITY: usize = 4096;
+
+  /// Time waited before sending again to a full queue
+  pub const QUEUE_RETRY: Duration = Duration::from_millis(2);
+
+  /// Create the channel of the entries sent to the renderer, bounded by `--queue-capacity`
+  pub fn entries_channel(options: &AppOptions) -> (SyncSender<BuildEntry>, Receiver<BuildEntry>) {
+    sync_channel(options.queue_capacity)
+  }
+
   pub fn new(
     options: AppOptions,
-    tx_entries: Sender<Vec<BuildEntry>>,
+    tx_entries: SyncSender<BuildEntry>,
     tx_events: Sender<BuildEvent>,
   ) -> Self {
     Self {
       options,
       tx_entries,
       tx_events,
+      cancel: Default::default(),
+      input: None,
+      intake_filter: None,
     }
   }
+
+  /// Kill the child once `cancel` is set, see [`super::BuildManager`]
+  pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+    self.cancel = cancel;
+    self
+  }
+
+  /// Write the lines received from `input` to the stdin of the child, if piped
+  pub fn with_input(mut self, input: Receiver<String>) -> Self {
+    self.input = Some(input);
+    self
+  }
+
   /// The `cargo build` thread. It will run the [`BuildCommand`]
-  /// and push output lines to [`BuildOutput`]
-  pub fn run(self) {
-    let args = self.options.build_args;
+  /// and push output lines to [`BuildOutput`]. Fails if the command cannot be spawned
+  pub fn run(mut self) -> crate::Result<()> {
     crate::dbg!("build thread started: {:#?}", active_rule());
-    match BuildCommand::spawn(args) {
-      Ok(mut build) => {
-        let _ = self.tx_events.send(BuildEvent::BuildStarted);
-        Debug::log("spawned cargo process");
-        let out_buf = BufReader::new(build.stdout.take().unwrap());
-        let err_buf = BufReader::new(build.stderr.take().unwrap());
-
-        let stderr_events = self.tx_entries.clone();
-        let stdout_events = self.tx_entries.clone();
-        let stdout_thread = spawn(move || {
-          for line in out_buf.lines() {
-            let line = line.expect("invalid output line");
-            // Debug::log(format!("[stdout] {}", line));
-            let _ = stdout_events.send(vec![BuildEntry::new(line, Origin::Stdout)]);
+    self.intake_filter = IntakeFilter::from_options(&self.options);
+    let build = Self::spawn_build(&self.options)
+      .map_err(|e| err!(crate::ErrorKind::IO, "failed to spawn cargo build, {}", e))?;
+    self.run_command(build);
+    Debug::log("build thread stopped");
+    Ok(())
+  }
+
+  /// Spawn the `cargo build` command of the options, inside a pseudo-terminal with `--pty`.
+  ///
+  /// Its stdin is piped with `--interactive`, see [`Self::with_input`]
+  pub fn spawn_build(options: &AppOptions) -> io::Result<BuildCommand> {
+    let mut command = BuildCommand::command(
+      options.build_args.clone(),
+      options.manifest_path.as_deref(),
+      options.keep_locale,
+    );
+    if options.interactive {
+      command.stdin(Stdio::piped());
+    }
+    match options.pty {
+      true => BuildCommand::spawn_pty(command),
+      false => BuildCommand::spawn_command(command),
+    }
+  }
+
+  /// Drain the output of an already spawned [`BuildCommand`].
+  ///
+  /// The pipes are drained until both reach EOF, even after the child exited.
+  /// If they stay open longer than [`Self::DRAIN_DEADLINE`] after the exit,
+  /// the remaining lines are sent as a final batch and the readers are abandoned.
+  ///
+  /// The readers and the renderer queue at most `--queue-capacity` entries each: past them
+  /// the readers stop reading, cargo waits on its full pipes until the renderer catches up
+  pub fn run_command(self, mut build: BuildCommand) {
+    let _ = self.tx_events.send(BuildEvent::BuildStarted);
+    Debug::log("spawned cargo process");
+    let capacity = self.options.queue_capacity;
+    let (tx_lines, rx_lines) = sync_channel::<BuildEntry>(capacity);
+    if let Some(out) = build.stdout.take() {
+      Self::spawn_reader(
+        out,
+        Origin::Stdout,
+        false,
+        self.intake_filter.clone(),
+        tx_lines.clone(),
+        self.tx_events.clone(),
+      );
+    }
+    if let Some(err) = build.stderr.take() {
+      Self::spawn_reader(
+        err,
+        Origin::Stderr,
+        false,
+        self.intake_filter.clone(),
+        tx_lines.clone(),
+        self.tx_events.clone(),
+      );
+    }
+    // both streams share the terminal, cargo writes its messages on stderr
+    if let Some(pty) = build.take_pty() {
+      Self::spawn_reader(
+        pty,
+        Origin::Stderr,
+        true,
+        self.intake_filter.clone(),
+        tx_lines.clone(),
+        self.tx_events.clone(),
+      );
+    }
+    drop(tx_lines);
+    let mut stdin = build.take_stdin();
+    let mut output = RotatingWriter::from_options(&self.options);
+    if self.options.output_file_header {
+      if let Some(writer) = output.as_mut() {
+        let header = writer.write_header(&self.options.command_line(), Local::now());
+        Self::check_output(&mut output, header);
+      }
+    }
+
+    let mut exit_status = None;
+    let mut deadline = None;
+    loop {
+      match rx_lines.recv_timeout(Self::POLL_INTERVAL) {
+        Ok(entry) => {
+          let mut batch = vec![entry];
+          batch.extend(rx_lines.try_iter().take(capacity));
+          Self::write_output(&mut output, &batch);
+          // the renderer being late is no reason to abandon the pipes
+          let waited = self.send_entries(batch);
+          deadline = deadline.map(|deadline| deadline + waited);
+        }
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+      if let (Some(pipe), Some(input)) = (stdin.as_mut(), self.input.as_ref()) {
+        let written = input
+          .try_iter()
+          .try_for_each(|line| writeln!(pipe, "{}", line).and_then(|_| pipe.flush()));
+        if let Err(e) = written {
+          Debug::log(format!("error: failed to write to cargo's stdin, {}", e));
+          stdin = None;
+        }
+      }
+      if exit_status.is_none() && self.cancel.swap(false, Ordering::SeqCst) {
+        Debug::log("build cancelled, killing cargo");
+        if let Err(e) = build.kill() {
+          Debug::log(format!("error: failed to kill cargo, {}", e));
+        }
+      }
+      if exit_status.is_none() {
+        match build.try_wait() {
+          Ok(Some(status)) => {
+            exit_status = Some(status);
+            deadline = Some(Instant::now() + Self::DRAIN_DEADLINE);
           }
-        });
-        let stderr_thread = spawn(move || {
-          for line in err_buf.lines() {
-            let line = line.expect("invalid error line");
-            // Debug::log(format!("[stderr] {}", line));
-            let t = vec![BuildEntry::new(line, Origin::Stderr)];
-            let _ = stderr_events.send(t);
+          Ok(None) => {}
+          Err(e) => Debug::log(format!("error: failed to poll cargo status, {}", e)),
+        }
+      }
+      if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        Debug::log("pipes still opened after the drain deadline, giving up");
+        break;
+      }
+    }
+    let remaining = rx_lines.try_iter().collect::<Vec<_>>();
+    if !remaining.is_empty() {
+      Self::write_output(&mut output, &remaining);
+      self.send_entries(remaining);
+    }
+
+    let exit_status = match exit_status.map(Ok).unwrap_or_else(|| build.wait()) {
+      Ok(status) => status,
+      Err(e) => {
+        Debug::log(format!("error: failed to wait for cargo, {}", e));
+        let _ = self
+          .tx_events
+          .send(BuildEvent::BuildFailed(BuildFailure::Lost));
+        return;
+      }
+    };
+    let _ = self.tx_events.send(BuildEvent::BuildFinished(exit_status));
+    Debug::log(format!("Exit status: {}", exit_status));
+  }
+
+  /// Send entries to the renderer, waiting while its queue is full. The entries get dropped
+  /// once the build is cancelled or the renderer hung up.
+  ///
+  /// Returns the time spent waiting
+  fn send_entries(&self, entries: Vec<BuildEntry>) -> Duration {
+    let start = Instant::now();
+    for mut entry in entries {
+      loop {
+        match self.tx_entries.try_send(entry) {
+          Ok(_) => break,
+          Err(TrySendError::Full(full)) if !self.cancel.load(Ordering::SeqCst) => {
+            entry = full;
+            sleep(Self::QUEUE_RETRY);
           }
-        });
-        // Debug::log("Waiting for stdout/err threads");
-        stdout_thread
-          .join()
-          .expect("failed to join process reader thread");
-        stderr_thread
-          .join()
-          .expect("failed to join process reader thread");
-        // Debug::log("Done waiting for stdout/err threads");
-
-        let exit_status = build.wait().expect("failed to wait for cargo");
-
-        let _ = self.tx_events.send(BuildEvent::BuildFinished(exit_status));
-        Debug::log(format!("Exit status: {}", exit_status));
+          Err(_) => return start.elapsed(),
+        }
       }
-      Err(e) => Debug::log(format!("error: failed to spawn cargo build, {}", e)),
     }
-    Debug::log("build thread stopped");
+    start.elapsed()
+  }
+
+  /// Append a batch to the `--output-file`, see [`Self::check_output`]
+  fn write_output(output: &mut Option<RotatingWriter>, batch: &[BuildEntry]) {
+    if let Some(writer) = output.as_mut() {
+      let written = writer.write_entries(batch);
+      Self::check_output(output, written);
+    }
+  }
+
+  /// Stop writing to the `--output-file` after a failure, the build goes on
+  fn check_output(output: &mut Option<RotatingWriter>, result: io::Result<()>) {
+    if let Err(e) = result {
+      Debug::log(format!("error: failed to write the output file, {}", e));
+      *output = None;
+    }
+  }
+
+  /// Spawn a thread reading lines from a child's pipe until EOF, then sending
+  /// its [`IntakeStats`] to `events`.
+  ///
+  /// The `terminal` lines keep only their text displayed after the carriage returns,
+  /// see [`overwritten_line`], the lines fully overwritten are dropped. Their progress bars
+  /// are sent as [`BuildEvent::BuildProgress`]. So are the lines
+  /// dropped by the `filter`, counted by [`IntakeStats::dropped`]
+  fn spawn_reader<R: Read + Send + 'static>(
+    pipe: R,
+    origin: Origin,
+    terminal: bool,
+    mut filter: Option<IntakeFilter>,
+    lines: SyncSender<BuildEntry>,
+    events: Sender<BuildEvent>,
+  ) {
+    spawn(move || {
+      let mut reader = BufReader::new(pipe);
+      let mut stats = IntakeStats::new(origin);
+      loop {
+        let read_at = Instant::now();
+        let read = read_line_timed(&mut reader);
+        stats.blocked += read_at.elapsed();
+        let (at, mut line) = match read {
+          Ok(Some(line)) => line,
+          Ok(None) => break,
+          Err(e) if terminal && BuildCommand::is_pty_closed(&e) => break,
+          Err(e) if e.kind() == ErrorKind::InvalidData => {
+            Debug::log(format!("skipped an invalid line from {:?}, {}", origin, e));
+            continue;
+          }
+          Err(e) => {
+            Debug::log(format!("error: failed to read {:?}, {}", origin, e));
+            break;
+          }
+        };
+        stats.record_line(&line, at);
+        if terminal && line.contains('\r') {
+          if let Some((done, total)) = build_progress(&line) {
+            let _ = events.send(BuildEvent::BuildProgress(done, total));
+          }
+          line = overwritten_line(&line).to_string();
+          if line.is_empty() {
+            stats.processing += at.elapsed();
+            continue;
+          }
+        }
+        if filter.as_mut().is_some_and(|filter| !filter.keep(&line)) {
+          stats.dropped += 1;
+          stats.processing += at.elapsed();
+          continue;
+        }
+        let sent = lines.send(BuildEntry::with_timestamp(line, origin, at));
+        stats.processing += at.elapsed();
+        if sent.is_err() {
+          break;
+        }
+      }
+      Debug::log(format!("intake {}", stats));
+      let _ = events.send(BuildEvent::IntakeStats(stats));
+    });
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use std::{
+    process::{Command, Stdio},
+    sync::mpsc::{channel, sync_channel},
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+  };
+
+  use crate::{AppOptions, BuildCommand, BuildEvent, IntakeFilter, Origin, DEFAULT_RULES};
+
+  use super::Builder;
+
+  #[test]
+  fn capture_timestamp() {
+    let (tx_lines, rx_lines) = sync_channel(16);
+    let mut child = Command::new("sh")
+      .arg("-c")
+      .arg("echo first; echo second")
+      .stdout(Stdio::piped())
+      .spawn()
+      .expect("failed to spawn command");
+    Builder::spawn_reader(
+      child.stdout.take().unwrap(),
+      Origin::Stdout,
+      false,
+      None,
+      tx_lines,
+      channel().0,
+    );
+    let _ = child.wait();
+    // the consumer pulls late, after the lines were read
+    sleep(Duration::from_millis(200));
+    let pulled_at = Instant::now();
+    let entries = rx_lines.iter().collect::<Vec<_>>();
+    assert_eq!(
+      entries
+        .iter()
+        .map(|entry| entry.message().as_str())
+        .collect::<Vec<_>>(),
+      vec!["first", "second"]
+    );
+    for entry in entries {
+      assert!(pulled_at.duration_since(*entry.created_at()) >= Duration::from_millis(150));
+    }
+  }
+
+  #[test]
+  fn intake_stats() {
+    let (tx_lines, rx_lines) = sync_channel(16);
+    let (tx_events, rx_events) = channel();
+    // a slow build: the reader waits, the lines get handled at once
+    let mut child = Command::new("sh")
+      .arg("-c")
+      .arg("echo first; sleep 0.3; echo second")
+      .stdout(Stdio::piped())
+      .spawn()
+      .expect("failed to spawn command");
+    Builder::spawn_reader(
+      child.stdout.take().unwrap(),
+      Origin::Stdout,
+      false,
+      None,
+      tx_lines,
+      tx_events,
+    );
+    let _ = child.wait();
+    assert_eq!(rx_lines.iter().count(), 2);
+    let stats = match rx_events.recv_timeout(Duration::from_secs(5)) {
+      Ok(BuildEvent::IntakeStats(stats)) => stats,
+      other => panic!("unexpected event {:?}", other),
+    };
+    assert_eq!(stats.origin, Origin::Stdout);
+    assert_eq!((stats.lines, stats.bytes), (2, 13));
+    assert!(stats.blocked >= Duration::from_millis(250), "{}", stats);
+    assert!(stats.max_gap >= Duration::from_millis(250), "{}", stats);
+    assert!(stats.processing < stats.blocked / 10, "{}", stats);
+  }
+
+  #[test]
+  fn skip_invalid_line() {
+    let (tx_lines, rx_lines) = sync_channel(16);
+    let mut child = Command::new("sh")
+      .arg("-c")
+      .arg("echo first; printf '\\377\\n'; echo second")
+      .stdout(Stdio::piped())
+      .spawn()
+      .expect("failed to spawn command");
+    Builder::spawn_reader(
+      child.stdout.take().unwrap(),
+      Origin::Stdout,
+      false,
+      None,
+      tx_lines,
+      channel().0,
+    );
+    let _ = child.wait();
+    let lines = rx_lines
+      .iter()
+      .map(|entry| entry.message().clone())
+      .collect::<Vec<_>>();
+    assert_eq!(lines, vec!["first".to_string(), "second".to_string()]);
+  }
+
+  #[test]
+  fn drop_noise() {
+    let (tx_lines, rx_lines) = sync_channel(16);
+    let (tx_events, rx_events) = channel();
+    let mut child = Command::new("sh")
+      .arg("-c")
+      .arg("printf 'a\\n\\n  \\n⠋\\nerror: oops\\n  |\\n\\n\\n|\\nb\\n'")
+      .stdout(Stdio::piped())
+      .spawn()
+      .expect("failed to spawn command");
+    Builder::spawn_reader(
+      child.stdout.take().unwrap(),
+      Origin::Stdout,
+      false,
+      Some(IntakeFilter::new(&DEFAULT_RULES[0])),
+      tx_lines,
+      tx_events,
+    );
+    let _ = child.wait();
+    let lines = rx_lines
+      .iter()
+      .map(|entry| entry.message().clone())
+      .collect::<Vec<_>>();
+    assert_eq!(lines, vec!["a", "error: oops", "  |", "", "b"]);
+    let stats = match rx_events.recv_timeout(Duration::from_secs(5)) {
+      Ok(BuildEvent::IntakeStats(stats)) => stats,
+      other => panic!("unexpected event {:?}", other),
+    };
+    assert_eq!((stats.lines, stats.dropped), (10, 5));
+  }
+
+  #[test]
+  fn drain_after_exit() {
+    let (tx_entries, rx_entries) = sync_channel(16);
+    let (tx_events, rx_events) = channel();
+    let mut command = Command::new("sh");
+    command
+      .arg("-c")
+      .arg("echo early; (sleep 0.3; echo late >&2) & exit 1");
+    let build = BuildCommand::spawn_command(command).expect("failed to spawn command");
+    let builder =
+      spawn(move || Builder::new(AppOptions::default(), tx_entries, tx_events).run_command(build));
+    let mut finished = false;
+    while let Ok(event) = rx_events.recv() {
+      if let BuildEvent::BuildFinished(status) = event {
+        assert!(!status.success());
+        finished = true;
+        break;
+      }
+    }
+    assert!(finished);
+    // every line was sent before the finished event
+    let lines = rx_entries
+      .try_iter()
+      .map(|entry| entry.message().clone())
+      .collect::<Vec<_>>();
+    assert_eq!(lines, vec!["early".to_string(), "late".to_string()]);
+    builder.join().expect("failed to join builder");
+  }
+
+  #[test]
+  fn backpressure() {
+    const CAPACITY: usize = 50;
+    const LINES: usize = 20000;
+    let options = AppOptions::builder()
+      .queue_capacity(CAPACITY)
+      .finish()
+      .unwrap();
+    let (tx_entries, rx_entries) = Builder::entries_channel(&options);
+    let (tx_events, _rx_events) = channel();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(format!("seq 1 {}", LINES));
+    let build = BuildCommand::spawn_command(command).expect("failed to spawn command");
+    let builder = spawn(move || Builder::new(options, tx_entries, tx_events).run_command(build));
+    // the renderer is late, cargo must wait instead of the entries piling up
+    sleep(Duration::from_millis(300));
+    let received = rx_entries
+      .iter()
+      .map(|entry| (Instant::now(), entry))
+      .collect::<Vec<_>>();
+    builder.join().expect("failed to join builder");
+    assert_eq!(received.len(), LINES);
+    assert_eq!(received[LINES - 1].1.message(), &LINES.to_string());
+    // the queues of the readers and the renderer, plus the batch in flight
+    let in_flight = 3 * CAPACITY + 2;
+    for k in in_flight..LINES {
+      assert!(
+        *received[k].1.created_at() >= received[k - in_flight].0,
+        "line {} read before line {} was received",
+        k,
+        k - in_flight
+      );
+    }
+  }
+
+  #[test]
+  fn forward_input() {
+    let (tx_entries, rx_entries) = sync_channel(16);
+    let (tx_events, _rx_events) = channel();
+    let (tx_input, rx_input) = channel();
+    let mut command = Command::new("sh");
+    command
+      .arg("-c")
+      .arg("echo 'Continue?'; read answer; echo \"got $answer\"")
+      .stdin(Stdio::piped());
+    let build = BuildCommand::spawn_command(command).expect("failed to spawn command");
+    tx_input.send("yes".to_string()).unwrap();
+    Builder::new(AppOptions::default(), tx_entries, tx_events)
+      .with_input(rx_input)
+      .run_command(build);
+    let lines = rx_entries
+      .try_iter()
+      .map(|entry| entry.message().clone())
+      .collect::<Vec<_>>();
+    assert_eq!(lines, vec!["Continue?".to_string(), "got yes".to_string()]);
+  }
+
+  #[test]
+  fn pty_colors() {
+    let (tx_entries, rx_entries) = sync_channel(16);
+    let (tx_events, rx_events) = channel();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(
+      "[ -t 2 ] && printf '\\033[1;31merror\\033[0m: colored\\n' >&2; \
+       printf 'Building [=> ] 1/2\\r\\033[K   Compiling demo\\n'; \
+       printf 'Building [==>] 2/2\\r'",
+    );
+    let build = BuildCommand::spawn_pty(command).expect("failed to spawn command");
+    Builder::new(AppOptions::default(), tx_entries, tx_events).run_command(build);
+    let entries = rx_entries.try_iter().collect::<Vec<_>>();
+    assert!(entries.iter().all(|entry| entry.origin() == Origin::Stderr));
+    assert_eq!(
+      entries
+        .iter()
+        .map(|entry| entry.message().as_str())
+        .collect::<Vec<_>>(),
+      vec!["\x1b[1;31merror\x1b[0m: colored", "\x1b[K   Compiling demo"]
+    );
+    // the progress bars dropped from the lines, but the last one: still unterminated
+    // when the pty closed, it got lost with its line
+    let progress = rx_events
+      .try_iter()
+      .filter_map(|event| match event {
+        BuildEvent::BuildProgress(done, total) => Some((done, total)),
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(progress, vec![(1, 2)]);
   }
 }
@@ -0,0 +1,257 @@
+use crate::{DisplayMap, EntryId, Viewport};
+
+/// The line cursor of the log, moved by j/k instead of the scroll, see `--cursor`.
+///
+/// The cursor stands on an entry rather than a row, to stay on its line when the filters
+/// or the folds map the rows again. It starts on the top row shown, the line-targeted actions
+/// operate from it and the marker navigation moves it to the selected entry,
+/// see [`CursorState::follow`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CursorState {
+  enabled: bool,
+  entry_id: Option<EntryId>,
+  /// The selected entry the cursor last moved to
+  followed: Option<EntryId>,
+}
+
+impl CursorState {
+  /// Construct the cursor, shown or not
+  pub fn new(enabled: bool) -> Self {
+    Self {
+      enabled,
+      ..Default::default()
+    }
+  }
+
+  /// Check if the cursor is shown
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Show or hide the cursor, shown again it starts over from the top row
+  pub fn toggle(&mut self) {
+    self.enabled = !self.enabled;
+    self.entry_id = None;
+  }
+
+  /// Retrieve the entry of the cursor, `None` when hidden or not moved yet
+  #[cfg(test)]
+  pub fn entry_id(&self) -> Option<EntryId> {
+    self.entry_id.filter(|_| self.enabled)
+  }
+
+  /// Retrieve the row of the cursor in `view`, until moved the top row of `scroll` after the
+  /// margin of `viewport`. A hidden entry gives the row of the next displayed one, or the last row
+  pub fn row(&self, view: &DisplayMap, scroll: usize, viewport: Viewport) -> Option<usize> {
+    let last = view.len().checked_sub(1).filter(|_| self.enabled)?;
+    let row = match (self.entry_id, scroll) {
+      (Some(entry_id), _) => view.row(entry_id.0),
+      (None, 0) => 0,
+      (None, scroll) => scroll + viewport.margin(),
+    };
+    Some(row.min(last))
+  }
+
+  /// Move the cursor to `row`, kept within the rows of `view`.
+  /// Returns the scroll revealing it with the margin of `viewport`
+  pub fn move_to(
+    &mut self,
+    row: usize,
+    view: &DisplayMap,
+    scroll: usize,
+    viewport: Viewport,
+  ) -> usize {
+    let Some(last) = view.len().checked_sub(1).filter(|_| self.enabled) else {
+      return scroll;
+    };
+    let row = row.min(last);
+    self.entry_id = view.entry_at(row).map(EntryId);
+    viewport.reveal(row, scroll, view.len())
+  }
+
+  /// Move the cursor `rows` down, see [`CursorState::move_to`]
+  pub fn move_down(
+    &mut self,
+    rows: usize,
+    view: &DisplayMap,
+    scroll: usize,
+    viewport: Viewport,
+  ) -> usize {
+    match self.row(view, scroll, viewport) {
+      Some(row) => self.move_to(row.saturating_add(rows), view, scroll, viewport),
+      None => scroll,
+    }
+  }
+
+  /// Move the cursor `rows` up, see [`CursorState::move_to`]
+  pub fn move_up(
+    &mut self,
+    rows: usize,
+    view: &DisplayMap,
+    scroll: usize,
+    viewport: Viewport,
+  ) -> usize {
+    match self.row(view, scroll, viewport) {
+      Some(row) => self.move_to(row.saturating_sub(rows), view, scroll, viewport),
+      None => scroll,
+    }
+  }
+
+  /// Bring the cursor back in the rows shown from `scroll`, e.g. after paging,
+  /// with the margin of `viewport` unless at the first or the last row
+  pub fn keep_in_view(&mut self, view: &DisplayMap, scroll: usize, viewport: Viewport) {
+    let Some(row) = self.row(view, scroll, viewport) else {
+      return;
+    };
+    let (height, margin) = (viewport.height(), viewport.margin());
+    let low = match scroll {
+      0 => 0,
+      _ => scroll + margin,
+    };
+    let high = match scroll + height >= view.len() {
+      true => view.len() - 1,
+      false => (scroll + height - 1).saturating_sub(margin),
+    };
+    let kept = row.clamp(low.min(high), high);
+    if kept != row {
+      self.entry_id = view.entry_at(kept).map(EntryId);
+    }
+  }
+
+  /// Move the cursor to the selected entry when the selection changed,
+  /// e.g. with the marker navigation or a search
+  pub fn follow(&mut self, selected: Option<EntryId>) {
+    if selected == self.followed {
+      return;
+    }
+    self.followed = selected;
+    if selected.is_some() {
+      self.entry_id = selected;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    BuildTagKind, BuildTagKinds, DisplayLayers, DisplayMap, DisplayedEntry, EntryId, LogEntry,
+    Viewport,
+  };
+
+  use super::CursorState;
+
+  /// The rows of 30 entries, every third one an error
+  fn view(kind: Option<BuildTagKind>) -> DisplayMap {
+    let lines = (0..30)
+      .map(|i| {
+        let kinds = match i % 3 {
+          0 => BuildTagKinds::from_iter([BuildTagKind::Error]),
+          _ => Default::default(),
+        };
+        DisplayedEntry::from(LogEntry::new(format!("line {}", i).into(), kinds))
+      })
+      .collect::<Vec<_>>();
+    let mut display = DisplayMap::new(DisplayLayers::default());
+    display.set_kind(kind);
+    display.update(&lines);
+    display
+  }
+
+  #[test]
+  fn movement() {
+    let (view, viewport) = (view(None), Viewport::new(10).with_scrolloff(2));
+    let mut cursor = CursorState::default();
+    // hidden, the keys keep scrolling
+    assert_eq!(cursor.row(&view, 5, viewport), None);
+    assert_eq!(cursor.move_down(1, &view, 5, viewport), 5);
+    assert_eq!(cursor.entry_id(), None);
+    cursor.toggle();
+    // from the top row after the margin, the view scrolls once the cursor enters the other one
+    assert_eq!(cursor.row(&view, 5, viewport), Some(7));
+    assert_eq!(cursor.row(&view, 0, viewport), Some(0));
+    assert_eq!(cursor.move_down(1, &view, 5, viewport), 5);
+    assert_eq!(cursor.entry_id(), Some(EntryId(8)));
+    assert_eq!(cursor.move_down(4, &view, 5, viewport), 5);
+    assert_eq!(cursor.move_down(1, &view, 5, viewport), 6);
+    assert_eq!(cursor.entry_id(), Some(EntryId(13)));
+    // above the view, a page up
+    assert_eq!(cursor.move_up(8, &view, 6, viewport), 0);
+    assert_eq!(cursor.entry_id(), Some(EntryId(5)));
+    // hidden again, it starts over from the top row
+    cursor.toggle();
+    assert_eq!(cursor.entry_id(), None);
+    cursor.toggle();
+    assert_eq!(cursor.row(&view, 3, viewport), Some(5));
+  }
+
+  #[test]
+  fn edges() {
+    let (view, viewport) = (view(None), Viewport::new(10).with_scrolloff(2));
+    let mut cursor = CursorState::new(true);
+    assert_eq!(cursor.move_up(1, &view, 0, viewport), 0);
+    assert_eq!(cursor.entry_id(), Some(EntryId(0)));
+    // stopped on the last row, the view not scrolled past it
+    assert_eq!(cursor.move_down(100, &view, 0, viewport), 20);
+    assert_eq!(cursor.entry_id(), Some(EntryId(29)));
+    assert_eq!(cursor.move_down(1, &view, 20, viewport), 20);
+    assert_eq!(cursor.entry_id(), Some(EntryId(29)));
+    // nothing displayed
+    let empty = DisplayMap::default();
+    assert_eq!(cursor.row(&empty, 0, viewport), None);
+    assert_eq!(cursor.move_down(1, &empty, 0, viewport), 0);
+  }
+
+  #[test]
+  fn filtered() {
+    let viewport = Viewport::new(4).with_scrolloff(1);
+    let mut cursor = CursorState::new(true);
+    cursor.move_to(4, &view(None), 0, viewport);
+    assert_eq!(cursor.entry_id(), Some(EntryId(4)));
+    // the errors only: the cursor on a hidden line stands on the next error
+    let errors = view(Some(BuildTagKind::Error));
+    assert_eq!(errors.len(), 10);
+    assert_eq!(cursor.row(&errors, 0, viewport), Some(2));
+    assert_eq!(cursor.move_down(1, &errors, 0, viewport), 1);
+    assert_eq!(cursor.entry_id(), Some(EntryId(9)));
+    assert_eq!(cursor.move_up(1, &errors, 1, viewport), 1);
+    assert_eq!(cursor.entry_id(), Some(EntryId(6)));
+    assert_eq!(cursor.move_down(100, &errors, 1, viewport), 6);
+    assert_eq!(cursor.entry_id(), Some(EntryId(27)));
+    // past the last error, on the last row
+    cursor.follow(Some(EntryId(29)));
+    assert_eq!(cursor.row(&errors, 0, viewport), Some(9));
+  }
+
+  #[test]
+  fn keep_in_view() {
+    let (view, viewport) = (view(None), Viewport::new(10).with_scrolloff(2));
+    let mut cursor = CursorState::new(true);
+    cursor.move_to(3, &view, 0, viewport);
+    // paged down, on the first row after the margin
+    cursor.keep_in_view(&view, 10, viewport);
+    assert_eq!(cursor.entry_id(), Some(EntryId(12)));
+    // paged up, on the last row before the margin
+    cursor.keep_in_view(&view, 0, viewport);
+    assert_eq!(cursor.entry_id(), Some(EntryId(7)));
+    // at the bottom the last row is reachable
+    cursor.move_to(29, &view, 20, viewport);
+    cursor.keep_in_view(&view, 20, viewport);
+    assert_eq!(cursor.entry_id(), Some(EntryId(29)));
+  }
+
+  #[test]
+  fn follow() {
+    let mut cursor = CursorState::new(true);
+    cursor.follow(Some(EntryId(12)));
+    assert_eq!(cursor.entry_id(), Some(EntryId(12)));
+    // moved away, the same selection leaves it there
+    cursor.move_to(3, &view(None), 0, Viewport::new(10));
+    cursor.follow(Some(EntryId(12)));
+    assert_eq!(cursor.entry_id(), Some(EntryId(3)));
+    // unselected, it stays
+    cursor.follow(None);
+    assert_eq!(cursor.entry_id(), Some(EntryId(3)));
+    cursor.follow(Some(EntryId(15)));
+    assert_eq!(cursor.entry_id(), Some(EntryId(15)));
+  }
+}
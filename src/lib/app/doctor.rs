@@ -0,0 +1,601 @@
+use std::{
+  collections::HashMap,
+  env,
+  ffi::{OsStr, OsString},
+  fmt::Display,
+  fs::{self, create_dir_all},
+  io::{stdout, IsTerminal},
+  path::{Path, PathBuf},
+  process::{Command, ExitCode},
+};
+
+use crate::{check_rules, display_path, search_locations, Rule, DEFAULT_RULES};
+
+use super::{AppOptions, History, RunContext};
+
+/// The outcome of a [`Check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+  Pass,
+  /// The application runs, with a degraded experience
+  Warn,
+  /// The build cannot run, `--doctor` exits with 1
+  Fail,
+}
+
+impl CheckStatus {
+  /// The ANSI color of the status in the report
+  fn color(&self) -> &'static str {
+    match self {
+      Self::Pass => "\x1b[0;32m",
+      Self::Warn => "\x1b[0;33m",
+      Self::Fail => "\x1b[0;31m",
+    }
+  }
+}
+
+impl Display for CheckStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Self::Pass => "PASS",
+        Self::Warn => "WARN",
+        Self::Fail => "FAIL",
+      }
+    )
+  }
+}
+
+/// The result of one of the `--doctor` checks: what was found, and how to fix it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+  pub name: &'static str,
+  pub status: CheckStatus,
+  pub detail: String,
+  /// The remediation of a warning or a failure
+  pub hint: Option<String>,
+}
+
+impl Check {
+  pub fn new<D: AsRef<str>>(name: &'static str, status: CheckStatus, detail: D) -> Self {
+    Self {
+      name,
+      status,
+      detail: detail.as_ref().to_string(),
+      hint: None,
+    }
+  }
+
+  pub fn pass<D: AsRef<str>>(name: &'static str, detail: D) -> Self {
+    Self::new(name, CheckStatus::Pass, detail)
+  }
+
+  pub fn warn<D: AsRef<str>>(name: &'static str, detail: D) -> Self {
+    Self::new(name, CheckStatus::Warn, detail)
+  }
+
+  pub fn fail<D: AsRef<str>>(name: &'static str, detail: D) -> Self {
+    Self::new(name, CheckStatus::Fail, detail)
+  }
+
+  pub fn with_hint<H: AsRef<str>>(mut self, hint: H) -> Self {
+    self.hint = Some(hint.as_ref().to_string());
+    self
+  }
+
+  /// Format the one-line warning printed at startup, see [`Doctor::startup_checks`]
+  pub fn warning_line(&self) -> String {
+    format!(
+      "\x1b[0;33mwarning\x1b[0m: {}: {} (see --doctor)",
+      self.name, self.detail
+    )
+  }
+}
+
+/// What the checks inspect, detected from the process with [`DoctorEnv::detect`]
+/// or built by hand in tests
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DoctorEnv {
+  /// The directories searched for cargo, `PATH`
+  pub path: Option<OsString>,
+  /// The variables describing the terminal: `TERM`, `COLORTERM`
+  pub vars: HashMap<String, String>,
+  /// The size of the terminal, none when stdout is not a terminal
+  pub terminal_size: Option<(u16, u16)>,
+  /// The config file of `--config`
+  pub config_path: Option<PathBuf>,
+  /// The config files searched, in precedence order, see [`crate::search_locations`]
+  pub config_locations: Vec<PathBuf>,
+  /// The active rule, `--rule`
+  pub rule: String,
+  /// The directories written during the runs, with their name
+  pub dirs: Vec<(&'static str, PathBuf)>,
+}
+
+impl DoctorEnv {
+  /// The variables of the terminal kept in [`Self::vars`]
+  const TERM_VARS: [&'static str; 2] = ["TERM", "COLORTERM"];
+
+  /// Inspect the current process, with the config and rule of `options`
+  pub fn detect(options: &AppOptions) -> Self {
+    let history = History::default_path();
+    Self {
+      path: env::var_os("PATH"),
+      vars: Self::TERM_VARS
+        .iter()
+        .filter_map(|name| Some((name.to_string(), env::var(name).ok()?)))
+        .collect(),
+      terminal_size: stdout()
+        .is_terminal()
+        .then(ratatui::crossterm::terminal::size)
+        .and_then(Result::ok),
+      config_path: options.config_path.clone(),
+      config_locations: search_locations(),
+      rule: options.active_rule.clone(),
+      dirs: vec![
+        (
+          "state directory",
+          history.parent().map(Path::to_path_buf).unwrap_or(history),
+        ),
+        ("runs directory", RunContext::runs_dir()),
+      ],
+    }
+  }
+
+  fn var(&self, name: &str) -> &str {
+    self.vars.get(name).map(String::as_str).unwrap_or_default()
+  }
+}
+
+/// Diagnose the environment before running the build, see `--doctor`
+pub struct Doctor;
+
+impl Doctor {
+  /// The smallest terminal showing the whole UI
+  pub const MIN_SIZE: (u16, u16) = (80, 24);
+  /// The width of the names column of the report
+  const NAME_WIDTH: usize = 16;
+
+  /// Run all the checks
+  pub fn checks(env: &DoctorEnv) -> Vec<Check> {
+    let mut checks = vec![
+      Self::check_cargo(env),
+      Self::check_config(env),
+      Self::check_rule(env),
+      Self::check_colors(env),
+      Self::check_terminal_size(env),
+      Self::check_mouse(env),
+    ];
+    checks.extend(
+      env
+        .dirs
+        .iter()
+        .map(|(name, dir)| Self::check_writable(name, dir)),
+    );
+    checks
+  }
+
+  /// The cheap checks run on every start, only the problems get returned:
+  /// cargo is found when `builds`, and the directories are writable
+  pub fn startup_checks(env: &DoctorEnv, builds: bool) -> Vec<Check> {
+    let cargo = match builds {
+      true => Self::find_cargo(env).err(),
+      false => None,
+    };
+    cargo
+      .into_iter()
+      .chain(
+        env
+          .dirs
+          .iter()
+          .map(|(name, dir)| Self::check_writable(name, dir)),
+      )
+      .filter(|check| check.status != CheckStatus::Pass)
+      .collect()
+  }
+
+  /// Find an executable in the directories of `path`
+  pub fn find_program(name: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    let name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    env::split_paths(path?)
+      .map(|dir| dir.join(&name))
+      .find(|path| path.is_file())
+  }
+
+  fn find_cargo(env: &DoctorEnv) -> Result<PathBuf, Check> {
+    Self::find_program("cargo", env.path.as_deref()).ok_or_else(|| {
+      Check::fail("cargo", "not found in PATH")
+        .with_hint("install Rust with rustup (https://rustup.rs), or add ~/.cargo/bin to PATH")
+    })
+  }
+
+  /// Check that cargo is found and runs `cargo --version`
+  pub fn check_cargo(env: &DoctorEnv) -> Check {
+    let cargo = match Self::find_cargo(env) {
+      Ok(cargo) => cargo,
+      Err(check) => return check,
+    };
+    match Command::new(&cargo).arg("--version").output() {
+      Ok(output) if output.status.success() => {
+        Check::pass("cargo", String::from_utf8_lossy(&output.stdout).trim())
+      }
+      Ok(output) => Check::fail(
+        "cargo",
+        format!(
+          "{} --version exited with {}",
+          cargo.display(),
+          output.status
+        ),
+      )
+      .with_hint("repair the toolchain with `rustup update`"),
+      Err(e) => Check::fail("cargo", format!("failed to run {}, {}", cargo.display(), e))
+        .with_hint("repair the toolchain with `rustup update`"),
+    }
+  }
+
+  /// Retrieve the config file loaded: `--config`, else the first existing search location
+  pub fn config_file(env: &DoctorEnv) -> Option<PathBuf> {
+    env.config_path.clone().or_else(|| {
+      env
+        .config_locations
+        .iter()
+        .find(|path| path.is_file())
+        .cloned()
+    })
+  }
+
+  /// Check that the config file parses and validates, telling which file wins
+  pub fn check_config(env: &DoctorEnv) -> Check {
+    let Some(path) = Self::config_file(env) else {
+      return Check::pass("config", "no config file, the built-in rules are used");
+    };
+    let shadowed = env
+      .config_locations
+      .iter()
+      .filter(|other| other.is_file() && **other != path)
+      .count();
+    match check_rules(Some(path.clone())) {
+      Ok((_, rules)) if shadowed > 0 => Check::warn(
+        "config",
+        format!(
+          "{}: {} rule(s), wins over {} other file(s)",
+          display_path(&path),
+          rules.len(),
+          shadowed
+        ),
+      )
+      .with_hint("remove the files not used, see the search locations of --help"),
+      Ok((_, rules)) => Check::pass(
+        "config",
+        format!("{}: {} rule(s)", display_path(&path), rules.len()),
+      ),
+      Err(e) => Check::fail("config", e.to_string())
+        .with_hint("fix the config, or start over with --eject-config"),
+    }
+  }
+
+  /// Check that the active rule is known, from the built-in rules or the config file
+  pub fn check_rule(env: &DoctorEnv) -> Check {
+    let config = Self::config_file(env)
+      .and_then(|path| check_rules(Some(path)).ok())
+      .map(|(_, rules)| rules)
+      .unwrap_or_default();
+    let rules = DEFAULT_RULES
+      .iter()
+      .chain(config.iter())
+      .collect::<Vec<_>>();
+    Self::check_rule_in(&env.rule, &rules)
+  }
+
+  /// Check that the rule `name` is found in `rules`, with enabled markers
+  pub fn check_rule_in(name: &str, rules: &[&Rule]) -> Check {
+    let Some(rule) = rules.iter().find(|rule| {
+      rule
+        .aliases
+        .iter()
+        .any(|alias| alias.eq_ignore_ascii_case(name))
+    }) else {
+      return Check::fail("rule", format!("unknown rule '{}'", name))
+        .with_hint("list the known rules with --dump-rules");
+    };
+    let enabled = rule
+      .markers
+      .iter()
+      .filter(|marker| !marker.disabled)
+      .count();
+    match enabled {
+      0 => Check::warn("rule", format!("'{}' has no enabled marker", name))
+        .with_hint("enable its markers in the rule editor (R)"),
+      _ => Check::pass(
+        "rule",
+        format!("'{}': {} ({} marker(s))", name, rule.command, enabled),
+      ),
+    }
+  }
+
+  /// Check the colors of the terminal, from `COLORTERM` and `TERM`
+  pub fn check_colors(env: &DoctorEnv) -> Check {
+    let term = env.var("TERM");
+    if matches!(env.var("COLORTERM"), "truecolor" | "24bit") {
+      return Check::pass("colors", "24-bit colors");
+    }
+    match term {
+      "" | "dumb" => Check::warn("colors", format!("no colors (TERM={})", term))
+        .with_hint("set TERM to your terminal, e.g. TERM=xterm-256color"),
+      term if term.contains("256color") => Check::pass("colors", "256 colors"),
+      term => Check::pass("colors", format!("16 colors (TERM={})", term)),
+    }
+  }
+
+  /// Check that the terminal shows the whole UI, see [`Self::MIN_SIZE`]
+  pub fn check_terminal_size(env: &DoctorEnv) -> Check {
+    let (min_width, min_height) = Self::MIN_SIZE;
+    match env.terminal_size {
+      None => Check::warn("terminal size", "stdout is not a terminal")
+        .with_hint("run in a terminal, or use --no-tui"),
+      Some((width, height)) if width < min_width || height < min_height => Check::warn(
+        "terminal size",
+        format!(
+          "{}x{}, the UI needs at least {}x{}",
+          width, height, min_width, min_height
+        ),
+      )
+      .with_hint("enlarge the terminal, or use --zen to save rows"),
+      Some((width, height)) => Check::pass("terminal size", format!("{}x{}", width, height)),
+    }
+  }
+
+  /// Check that the terminal reports the mouse, the consoles without a terminal emulator don't
+  pub fn check_mouse(env: &DoctorEnv) -> Check {
+    let term = env.var("TERM");
+    match (env.terminal_size, term) {
+      (None, _) => Check::warn("mouse", "no terminal to report the mouse"),
+      (_, "" | "dumb" | "linux") => {
+        Check::warn("mouse", format!("TERM={} does not report the mouse", term))
+          .with_hint("use the keyboard shortcuts, listed with ?")
+      }
+      (_, term) => Check::pass("mouse", format!("reported (TERM={})", term)),
+    }
+  }
+
+  /// Check that `dir` gets created and written
+  pub fn check_writable(name: &'static str, dir: &Path) -> Check {
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    let written = create_dir_all(dir)
+      .and_then(|_| fs::write(&probe, b""))
+      .and_then(|_| fs::remove_file(&probe));
+    match written {
+      Ok(()) => Check::pass(name, display_path(dir)),
+      Err(e) => Check::warn(
+        name,
+        format!("{} is not writable, {}", display_path(dir), e),
+      )
+      .with_hint(format!("fix the permissions of {}", dir.display())),
+    }
+  }
+
+  /// Format the report: a row per check with its status, its hint below.
+  /// The statuses get colored when `colored`
+  pub fn report(checks: &[Check], colored: bool) -> Vec<String> {
+    let mut lines = vec![];
+    for check in checks {
+      let status = match colored {
+        true => format!("{}{}\x1b[0m", check.status.color(), check.status),
+        false => check.status.to_string(),
+      };
+      lines.push(format!(
+        "{}  {:<width$}{}",
+        status,
+        check.name,
+        check.detail,
+        width = Self::NAME_WIDTH
+      ));
+      if let Some(hint) = check.hint.as_ref() {
+        lines.push(format!(
+          "      {:<width$}hint: {}",
+          "",
+          hint,
+          width = Self::NAME_WIDTH
+        ));
+      }
+    }
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    lines.push(format!(
+      "{} passed, {} warning(s), {} failure(s)",
+      count(CheckStatus::Pass),
+      count(CheckStatus::Warn),
+      count(CheckStatus::Fail)
+    ));
+    lines
+  }
+
+  /// The exit code of `--doctor`: 1 if any check failed
+  pub fn exit_code(checks: &[Check]) -> ExitCode {
+    match checks.iter().any(|check| check.status == CheckStatus::Fail) {
+      true => ExitCode::FAILURE,
+      false => ExitCode::SUCCESS,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use crate::{save_rules, DEFAULT_RULES};
+
+  use super::{Check, CheckStatus, Doctor, DoctorEnv};
+
+  fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nbuild-doctor-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn terminal(term: &str, colorterm: Option<&str>, size: Option<(u16, u16)>) -> DoctorEnv {
+    let mut env = DoctorEnv {
+      terminal_size: size,
+      ..Default::default()
+    };
+    env.vars.insert("TERM".to_string(), term.to_string());
+    if let Some(colorterm) = colorterm {
+      env
+        .vars
+        .insert("COLORTERM".to_string(), colorterm.to_string());
+    }
+    env
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn cargo() {
+    let bin = dir("cargo");
+    let env = DoctorEnv {
+      path: Some(bin.clone().into_os_string()),
+      ..Default::default()
+    };
+    let check = Doctor::check_cargo(&env);
+    assert_eq!(check.status, CheckStatus::Fail);
+    assert!(check.hint.is_some());
+    assert_eq!(Doctor::startup_checks(&env, true), vec![check]);
+    assert!(Doctor::startup_checks(&env, false).is_empty());
+    // a program printing its version, then a failing one
+    std::os::unix::fs::symlink("/bin/echo", bin.join("cargo")).unwrap();
+    let check = Doctor::check_cargo(&env);
+    assert_eq!(check.status, CheckStatus::Pass, "{:?}", check);
+    assert!(!check.detail.is_empty());
+    assert!(Doctor::startup_checks(&env, true).is_empty());
+    fs::remove_file(bin.join("cargo")).unwrap();
+    std::os::unix::fs::symlink("/bin/false", bin.join("cargo")).unwrap();
+    assert_eq!(Doctor::check_cargo(&env).status, CheckStatus::Fail);
+    fs::remove_dir_all(bin).unwrap();
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn config() {
+    let dir = dir("config");
+    let env = DoctorEnv {
+      config_locations: vec![dir.join("nbuild.json"), dir.join("nbuild.yaml")],
+      rule: "rust".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(Doctor::check_config(&env).status, CheckStatus::Pass);
+    assert_eq!(Doctor::check_rule(&env).status, CheckStatus::Pass);
+
+    save_rules(&DEFAULT_RULES, Some(dir.join("nbuild.json"))).unwrap();
+    assert_eq!(Doctor::config_file(&env), Some(dir.join("nbuild.json")));
+    let check = Doctor::check_config(&env);
+    assert_eq!(check.status, CheckStatus::Pass, "{:?}", check);
+    // a config with an alias used twice, and a rule missing from the config
+    let twice = vec![DEFAULT_RULES[0].clone(), DEFAULT_RULES[0].clone()];
+    save_rules(&twice, Some(dir.join("bad.json"))).unwrap();
+    let env = DoctorEnv {
+      config_path: Some(dir.join("bad.json")),
+      rule: "make".to_string(),
+      ..env
+    };
+    let check = Doctor::check_config(&env);
+    assert_eq!(check.status, CheckStatus::Fail, "{:?}", check);
+    assert!(check.hint.is_some());
+    assert_eq!(Doctor::check_rule(&env).status, CheckStatus::Fail);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn rule() {
+    let mut rule = DEFAULT_RULES[0].clone();
+    assert_eq!(
+      Doctor::check_rule_in("RUST", &[&rule]).status,
+      CheckStatus::Pass
+    );
+    for marker in rule.markers.iter_mut() {
+      marker.disabled = true;
+    }
+    assert_eq!(
+      Doctor::check_rule_in("rust", &[&rule]).status,
+      CheckStatus::Warn
+    );
+    assert_eq!(
+      Doctor::check_rule_in("gradle", &[&rule]).status,
+      CheckStatus::Fail
+    );
+  }
+
+  #[test]
+  fn terminal_capabilities() {
+    let status = |env: &DoctorEnv| {
+      [
+        Doctor::check_colors(env).status,
+        Doctor::check_terminal_size(env).status,
+        Doctor::check_mouse(env).status,
+      ]
+    };
+    use CheckStatus::{Pass, Warn};
+    assert_eq!(
+      status(&terminal("xterm-256color", None, Some((120, 40)))),
+      [Pass, Pass, Pass]
+    );
+    assert_eq!(
+      Doctor::check_colors(&terminal("xterm", Some("truecolor"), None)).detail,
+      "24-bit colors"
+    );
+    assert_eq!(
+      status(&terminal("linux", None, Some((60, 20)))),
+      [Pass, Warn, Warn]
+    );
+    assert_eq!(status(&terminal("dumb", None, None)), [Warn, Warn, Warn]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn writable() {
+    let dir = dir("writable");
+    let check = Doctor::check_writable("runs directory", &dir.join("runs"));
+    assert_eq!(check.status, CheckStatus::Pass);
+    // a file in place of the parent directory
+    fs::write(dir.join("state"), "").unwrap();
+    let env = DoctorEnv {
+      dirs: vec![
+        ("state directory", dir.join("state").join("nbuild")),
+        ("runs directory", dir.join("runs")),
+      ],
+      ..Default::default()
+    };
+    let checks = Doctor::startup_checks(&env, false);
+    assert_eq!(checks.len(), 1);
+    assert_eq!(
+      (checks[0].name, checks[0].status),
+      ("state directory", CheckStatus::Warn)
+    );
+    assert!(checks[0].warning_line().contains("state directory: "));
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn report() {
+    let checks = [
+      Check::pass("cargo", "cargo 1.80.0"),
+      Check::warn("terminal size", "60x20, the UI needs at least 80x24")
+        .with_hint("enlarge the terminal"),
+    ];
+    assert_eq!(
+      Doctor::report(&checks, false),
+      vec![
+        "PASS  cargo           cargo 1.80.0",
+        "WARN  terminal size   60x20, the UI needs at least 80x24",
+        "                      hint: enlarge the terminal",
+        "1 passed, 1 warning(s), 0 failure(s)",
+      ]
+    );
+    assert!(Doctor::report(&checks, true)[0].starts_with("\x1b[0;32mPASS\x1b[0m"));
+    assert_eq!(Doctor::exit_code(&checks), std::process::ExitCode::SUCCESS);
+    assert_eq!(
+      Doctor::exit_code(&[Check::fail("rule", "unknown rule 'make'")]),
+      std::process::ExitCode::FAILURE
+    );
+  }
+}
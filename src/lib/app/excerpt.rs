@@ -0,0 +1,259 @@
+use std::{fmt::Display, ops::Range, str::FromStr};
+
+use crate::{err, AnsiLine, BuildOutput, ErrorKind};
+
+/// When the printed output gets styled, from cargo's `--color <WHEN>`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+  /// Style the output if stdout is a terminal
+  #[default]
+  Auto,
+  Always,
+  Never,
+}
+
+impl FromStr for ColorChoice {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "auto" => Ok(Self::Auto),
+      "always" => Ok(Self::Always),
+      "never" => Ok(Self::Never),
+      _ => Err(err!(
+        ErrorKind::Parsing,
+        "invalid color '{}', expected one of: auto, always, never",
+        s
+      )),
+    }
+  }
+}
+
+impl Display for ColorChoice {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Self::Auto => "auto",
+        Self::Always => "always",
+        Self::Never => "never",
+      }
+    )
+  }
+}
+
+impl ColorChoice {
+  /// Check if the output gets styled, `terminal` telling if stdout is a terminal
+  pub fn is_colored(&self, terminal: bool) -> bool {
+    match self {
+      Self::Auto => terminal,
+      Self::Always => true,
+      Self::Never => false,
+    }
+  }
+}
+
+/// The part of a build log printed by `--render`: `block-<N>` or `lines-<A>..<B>`,
+/// both counted from 1 like in the UI
+#[derive(Debug, Clone, PartialEq)]
+pub enum Excerpt {
+  /// The nth block, opened by a primary marker
+  Block(usize),
+  /// The lines from `start` to `end`, included
+  Lines { start: usize, end: usize },
+}
+
+impl FromStr for Excerpt {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = |reason: String| {
+      err!(
+        ErrorKind::Parsing,
+        "invalid range '{}', {}, expected block-<N> or lines-<A>..<B>",
+        s,
+        reason
+      )
+    };
+    let number = |n: &str| match n.parse::<usize>() {
+      Ok(0) => Err(invalid("the numbers start at 1".to_string())),
+      Ok(n) => Ok(n),
+      Err(e) => Err(invalid(format!("'{}' {}", n, e))),
+    };
+    if let Some(n) = s.strip_prefix("block-") {
+      return Ok(Self::Block(number(n)?));
+    }
+    let Some(lines) = s.strip_prefix("lines-") else {
+      return Err(invalid("unknown kind".to_string()));
+    };
+    let Some((start, end)) = lines.split_once("..") else {
+      return Err(invalid("the lines are given as <A>..<B>".to_string()));
+    };
+    let (start, end) = (number(start)?, number(end)?);
+    if start > end {
+      return Err(invalid(format!("line {} comes after line {}", start, end)));
+    }
+    Ok(Self::Lines { start, end })
+  }
+}
+
+impl Display for Excerpt {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Block(n) => write!(f, "block-{}", n),
+      Self::Lines { start, end } => write!(f, "lines-{}..{}", start, end),
+    }
+  }
+}
+
+impl Excerpt {
+  /// Retrieve the entries of the excerpt in the prepared `build`
+  pub fn range(&self, build: &BuildOutput) -> crate::Result<Range<usize>> {
+    match self {
+      Self::Block(n) => {
+        let blocks = build.blocks();
+        match blocks.get(n - 1) {
+          Some(block) => Ok(block.range()),
+          None => Err(err!(
+            ErrorKind::Parsing,
+            "no block #{}, the build log has {} block(s)",
+            n,
+            blocks.len()
+          )),
+        }
+      }
+      Self::Lines { start, end } => {
+        let len = build.entries().len();
+        match *end <= len {
+          true => Ok(start - 1..*end),
+          false => Err(err!(
+            ErrorKind::Parsing,
+            "no line {}, the build log has {} line(s)",
+            end,
+            len
+          )),
+        }
+      }
+    }
+  }
+
+  /// Render the excerpt of the prepared `build` like the UI does,
+  /// with escape sequences if `colored`. The selected block is not highlighted
+  pub fn render(&self, build: &mut BuildOutput, colored: bool) -> crate::Result<Vec<String>> {
+    let range = self.range(build)?;
+    build.markers_mut().set_selection(None);
+    Ok(
+      build
+        .display_range(range)
+        .iter()
+        .map(|entry| match colored {
+          true => AnsiLine::render(entry.line()),
+          false => entry.line().to_string(),
+        })
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BuildOutput, ColorChoice};
+
+  use super::Excerpt;
+
+  fn prepared(log: &str) -> BuildOutput<'static> {
+    let mut build = BuildOutput::from(log.lines());
+    build.prepare();
+    build
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!("block-3".parse::<Excerpt>().unwrap(), Excerpt::Block(3));
+    assert_eq!(
+      "lines-2..12".parse::<Excerpt>().unwrap(),
+      Excerpt::Lines { start: 2, end: 12 }
+    );
+    assert_eq!(
+      Excerpt::Lines { start: 4, end: 4 }.to_string(),
+      "lines-4..4"
+    );
+    for (range, error) in [
+      ("block-0", "the numbers start at 1"),
+      ("block-x", "'x' invalid digit found in string"),
+      ("lines-3", "the lines are given as <A>..<B>"),
+      ("lines-5..2", "line 5 comes after line 2"),
+      ("errors-1", "unknown kind"),
+    ] {
+      let e = range.parse::<Excerpt>().unwrap_err();
+      assert!(e.to_string().contains(error), "{}: {}", range, e);
+    }
+    assert_eq!("Never".parse::<ColorChoice>().unwrap(), ColorChoice::Never);
+    assert!(!ColorChoice::Auto.is_colored(false));
+    assert!("sometimes".parse::<ColorChoice>().is_err());
+  }
+
+  #[test]
+  fn render_block() {
+    let mut build = prepared(include_str!(
+      "../../../tests/fixtures/rust/rust-macro-backtrace.log"
+    ));
+    let lines = Excerpt::Block(2).render(&mut build, false).unwrap();
+    assert_eq!(
+      lines,
+      vec![
+        "warning:  unused variable: `x`",
+        "          --> src/main.rs:4:9",
+        "         |",
+        "       4 |     let x = 1;",
+        "         |         ^ help: if this is intentional, prefix it with an underscore: `_x`",
+        "         |",
+        "note:  `#[warn(unused_variables)]` on by default",
+        "       ",
+        "error:  could not compile `demo` (bin \"demo\") due to 1 previous error; 1 warning emitted",
+      ]
+    );
+    let colored = Excerpt::Block(2).render(&mut build, true).unwrap();
+    assert_eq!(colored.len(), lines.len());
+    assert!(colored[0].starts_with("\x1b["));
+    assert_eq!(crate::AnsiLine::strip(&colored[0]), lines[0]);
+
+    let e = Excerpt::Block(9).render(&mut build, false).unwrap_err();
+    assert_eq!(
+      e.to_string(),
+      "parsing failed: no block #9, the build log has 2 block(s)"
+    );
+  }
+
+  #[test]
+  fn render_lines() {
+    let mut build = prepared(include_str!("../../../tests/fixtures/rust/rust-panic.log"));
+    let lines = Excerpt::Lines { start: 2, end: 4 }
+      .render(&mut build, false)
+      .unwrap();
+    assert_eq!(
+      lines,
+      vec![
+        "error:  this build will fail",
+        "  --> src/main.rs:2:3",
+        "   |"
+      ]
+    );
+    let colored = Excerpt::Lines { start: 2, end: 2 }
+      .render(&mut build, true)
+      .unwrap();
+    assert_eq!(
+      colored,
+      vec!["\x1b[1;31merror:\x1b[0m  this build will fail"]
+    );
+
+    let e = Excerpt::Lines { start: 6, end: 9 }
+      .render(&mut build, false)
+      .unwrap_err();
+    assert_eq!(
+      e.to_string(),
+      "parsing failed: no line 9, the build log has 8 line(s)"
+    );
+  }
+}
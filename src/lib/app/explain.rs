@@ -0,0 +1,461 @@
+use std::{fmt::Display, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+  rule_files, rule_provenance, rule_source, BuildOutput, NoiseLine, Rule, SelectionTheme,
+};
+
+use super::{AppOptions, HELP_SECTIONS};
+
+/// Where an effective setting comes from, see [`ConfigExplanation`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "from", rename_all = "lowercase")]
+pub enum Provenance {
+  /// The built-in default
+  Default,
+  /// A config file
+  File(PathBuf),
+  /// A command line flag, as typed
+  Flag(String),
+}
+
+impl Display for Provenance {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Default => write!(f, "default"),
+      Self::File(path) => write!(f, "file {}", path.display()),
+      Self::Flag(flag) => write!(f, "flag {}", flag),
+    }
+  }
+}
+
+/// How `--explain-config` prints the settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+  /// Indented text, one setting per line
+  Text,
+  /// A JSON document, see [`ConfigExplanation::to_json`]
+  Json,
+}
+
+/// An effective setting and where it comes from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Setting {
+  pub key: String,
+  pub value: String,
+  pub provenance: Provenance,
+}
+
+impl Setting {
+  pub fn new<K: AsRef<str>, V: Display>(key: K, value: V, provenance: Provenance) -> Self {
+    Self {
+      key: key.as_ref().to_string(),
+      value: value.to_string(),
+      provenance,
+    }
+  }
+}
+
+/// The settings of a section of [`ConfigExplanation`], e.g. `intake`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingsSection {
+  pub name: &'static str,
+  pub settings: Vec<Setting>,
+}
+
+/// The effective settings of a run with their [`Provenance`], printed by
+/// `--explain-config`: the defaults, overridden by the config file (the rules),
+/// overridden by the command line flags
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigExplanation {
+  pub sections: Vec<SettingsSection>,
+}
+
+impl ConfigExplanation {
+  /// Explain the settings of `options` and of the active `rule`, once the rules are loaded
+  pub fn new(options: &AppOptions, rule: &Rule) -> Self {
+    let mut explanation = Self { sections: vec![] };
+    let option = |key: &str, value: &dyn Display| Setting::new(key, value, options.provenance(key));
+    let name = rule.aliases.first().cloned().unwrap_or_default();
+    let rule_provenance = rule_provenance(&name);
+
+    let config = match options.config_path.as_ref() {
+      Some(path) => Setting::new("config", path.display(), options.provenance("config")),
+      None => match rule_files().first() {
+        Some(path) => Setting::new("config", path.display(), Provenance::Default),
+        None => Setting::new("config", "none", Provenance::Default),
+      },
+    };
+    let mut settings = vec![
+      Setting::new(
+        "active",
+        &options.active_rule,
+        options.provenance("active-rule"),
+      ),
+      config,
+      Setting::new("command", &rule.command, rule_provenance.clone()),
+      Setting::new(
+        "locale",
+        rule.locale.as_deref().unwrap_or("none"),
+        rule_provenance.clone(),
+      ),
+      option("keep-locale", &options.keep_locale),
+    ];
+    // the rule registered first wins over its declaration in the config file
+    if let Some(path) = rule_source(&name) {
+      let kept = match rule_provenance {
+        Provenance::Default => "the built-in rule",
+        _ => "the rule registered first",
+      };
+      if rule_provenance != Provenance::File(path.clone()) {
+        settings.push(Setting::new(
+          "ignored",
+          format!("'{}' declared again, {} is kept", name, kept),
+          Provenance::File(path),
+        ));
+      }
+    }
+    explanation.push("rule", settings);
+
+    explanation.push(
+      "markers",
+      rule
+        .markers
+        .iter()
+        .map(|marker| {
+          let value = match marker.disabled {
+            true => format!("{} (disabled)", marker.regex.as_str()),
+            false => marker.regex.as_str().to_string(),
+          };
+          Setting::new(
+            marker.tag.to_string().to_lowercase(),
+            value,
+            rule_provenance.clone(),
+          )
+        })
+        .collect(),
+    );
+
+    let theme = SelectionTheme::default();
+    explanation.push(
+      "theme",
+      vec![
+        Setting::new(
+          "selection-bg",
+          format!("{:?}", theme.bg),
+          Provenance::Default,
+        ),
+        Setting::new("selection-contrast", theme.contrast, Provenance::Default),
+        option("color", &options.color),
+        option("respect-cargo-colors", &options.respect_cargo_colors),
+        option("hyperlinks", &options.hyperlinks),
+        option(
+          "hyperlink-format",
+          &options.hyperlink_format.as_deref().unwrap_or("auto"),
+        ),
+      ],
+    );
+
+    explanation.push(
+      "keybindings",
+      HELP_SECTIONS
+        .iter()
+        .flat_map(|(_title, keys)| keys.iter())
+        .map(|(key, desc)| Setting::new(key, desc, Provenance::Default))
+        .collect(),
+    );
+
+    let noise_provenance = match NoiseLine::are_defaults(&rule.noise) {
+      true => Provenance::Default,
+      false => rule_provenance.clone(),
+    };
+    let mut settings = vec![
+      option("keep-blank-lines", &options.keep_blank_lines),
+      option("queue-capacity", &options.queue_capacity),
+      option("pty", &options.pty),
+    ];
+    settings.extend(
+      rule
+        .noise
+        .iter()
+        .map(|line| Setting::new("noise", line.regex.as_str(), noise_provenance.clone())),
+    );
+    explanation.push("intake", settings);
+
+    let marker_budget = match options.marker_budget {
+      Some(budget) => format!("{}ms", budget.as_millis()),
+      None => "none".to_string(),
+    };
+    explanation.push(
+      "workers",
+      vec![
+        Setting::new("prepare", BuildOutput::WORKERS, Provenance::Default),
+        option("prepare-min-pending", &options.prepare_min_pending),
+        option(
+          "prepare-max-delay",
+          &format!("{}ms", options.prepare_max_delay.as_millis()),
+        ),
+        option("prepare-max-batch", &options.prepare_max_batch),
+        option("marker-budget", &marker_budget),
+      ],
+    );
+
+    let summary = match options.summary {
+      Some(summary) => summary.to_string(),
+      None => "auto".to_string(),
+    };
+    let summary_provenance = match options.provenance("no-summary") {
+      Provenance::Default => options.provenance("summary"),
+      flag => flag,
+    };
+    explanation.push(
+      "display",
+      vec![
+        option("only-errors", &options.show_only_errors),
+        option("inline-markers", &options.inline_markers),
+        option("compact-width", &options.compact_width),
+        option("zen", &options.zen),
+        option("cursor", &options.cursor),
+        option("tab-width", &options.tab_width),
+        option("max-line-length", &options.max_line_length),
+        option("scrolloff", &options.scrolloff),
+        option("pin-lines", &options.pin_lines),
+        option("split-view", &options.split_view),
+        option("fold-repeats", &options.fold_repeats),
+        option("dedup-targets", &options.dedup_targets),
+        option("thousands-separator", &options.thousands_separator),
+        option("no-title", &options.no_title),
+        option("no-term-progress", &options.no_term_progress),
+        option("perf-overlay", &options.perf_overlay),
+        option("exit-code", &options.exit_code),
+        Setting::new("summary", summary, summary_provenance),
+      ],
+    );
+    explanation
+  }
+
+  fn push(&mut self, name: &'static str, settings: Vec<Setting>) {
+    self.sections.push(SettingsSection { name, settings });
+  }
+
+  /// Retrieve the setting `key` of the section `section`, the first one when repeated
+  #[cfg(test)]
+  pub fn setting<S: AsRef<str>, K: AsRef<str>>(&self, section: S, key: K) -> Option<&Setting> {
+    self
+      .sections
+      .iter()
+      .find(|s| s.name == section.as_ref())
+      .and_then(|s| {
+        s.settings
+          .iter()
+          .find(|setting| setting.key == key.as_ref())
+      })
+  }
+
+  /// Format the sections as indented text: the section names, then their settings
+  /// as `key = value  <- provenance`
+  pub fn lines(&self) -> Vec<String> {
+    let mut lines = vec![];
+    for section in &self.sections {
+      lines.push(format!("{}:", section.name));
+      let width = section
+        .settings
+        .iter()
+        .map(|setting| setting.key.len())
+        .max()
+        .unwrap_or_default();
+      for setting in &section.settings {
+        lines.push(format!(
+          "  {:<width$} = {}  <- {}",
+          setting.key,
+          setting.value,
+          setting.provenance,
+          width = width
+        ));
+      }
+    }
+    lines
+  }
+
+  /// Format the sections as a JSON document
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> crate::Result<String> {
+    serde_json::to_string_pretty(self).map_err(|e| {
+      crate::err!(
+        crate::ErrorKind::Parsing,
+        "failed to encode the settings, {}",
+        e
+      )
+    })
+  }
+
+  /// Format the sections as a JSON document, never available without the `json` feature
+  #[cfg(not(feature = "json"))]
+  pub fn to_json(&self) -> crate::Result<String> {
+    Err(crate::err!(
+      crate::ErrorKind::Parsing,
+      "--explain-config-json needs the `json` feature"
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use crate::{load_rules, rule, save_rules, AppOptions, Rule, DEFAULT_RULES};
+
+  use super::{ConfigExplanation, Provenance};
+
+  /// A fresh config file `nbuild.json` in a directory of its own, declaring `rules`
+  fn config_file(test: &str, rules: &Vec<Rule>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nbuild-explain-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("nbuild.json");
+    save_rules(rules, Some(path.clone())).unwrap();
+    path
+  }
+
+  fn provenance(explanation: &ConfigExplanation, section: &str, key: &str) -> Provenance {
+    explanation
+      .setting(section, key)
+      .unwrap_or_else(|| panic!("no setting {}.{}", section, key))
+      .provenance
+      .clone()
+  }
+
+  #[test]
+  fn options() {
+    let options = AppOptions::parse_from([
+      "--tab-width",
+      "8",
+      "-E",
+      "--no-summary",
+      "--",
+      "--color=never",
+    ])
+    .unwrap();
+    let explanation = ConfigExplanation::new(&options, &DEFAULT_RULES[0]);
+    let tab_width = explanation.setting("display", "tab-width").unwrap();
+    assert_eq!(tab_width.value, "8");
+    assert_eq!(tab_width.provenance, Provenance::Flag("--tab-width".into()));
+    assert_eq!(
+      provenance(&explanation, "display", "only-errors"),
+      Provenance::Flag("-E".into())
+    );
+    assert_eq!(
+      provenance(&explanation, "display", "summary"),
+      Provenance::Flag("--no-summary".into())
+    );
+    // the color of the build arguments is the one of the display
+    assert_eq!(
+      provenance(&explanation, "theme", "color"),
+      Provenance::Flag("--color".into())
+    );
+    // not overridden
+    let max_line_length = explanation.setting("display", "max-line-length").unwrap();
+    assert_eq!(
+      max_line_length.value,
+      crate::BuildOutput::DEFAULT_MAX_LINE_LENGTH.to_string()
+    );
+    assert_eq!(max_line_length.provenance, Provenance::Default);
+    assert_eq!(
+      provenance(&explanation, "intake", "keep-blank-lines"),
+      Provenance::Default
+    );
+    assert_eq!(
+      provenance(&explanation, "rule", "active"),
+      Provenance::Default
+    );
+    assert!(explanation
+      .lines()
+      .contains(&"  tab-width           = 8  <- flag --tab-width".to_string()));
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn layered_rules() {
+    let mut layered = DEFAULT_RULES[0].clone();
+    layered.aliases = vec!["layered".to_string()];
+    layered.command = "cargo check".to_string();
+    let path = config_file("layered", &vec![layered.clone()]);
+    load_rules(Some(path.clone())).unwrap();
+    // declared again by another config file, with another command
+    layered.command = "make".to_string();
+    let other = config_file("layered-again", &vec![layered]);
+    load_rules(Some(other.clone())).unwrap();
+
+    let options =
+      AppOptions::parse_from(["--config", path.to_str().unwrap(), "--rule", "layered"]).unwrap();
+    let explanation = ConfigExplanation::new(&options, &rule("layered").unwrap());
+    assert_eq!(
+      provenance(&explanation, "rule", "active"),
+      Provenance::Flag("--rule".into())
+    );
+    assert_eq!(
+      explanation.setting("rule", "config").unwrap().provenance,
+      Provenance::Flag("--config".into())
+    );
+    let command = explanation.setting("rule", "command").unwrap();
+    assert_eq!(command.value, "cargo check");
+    assert_eq!(command.provenance, Provenance::File(path.clone()));
+    assert_eq!(
+      provenance(&explanation, "markers", "error"),
+      Provenance::File(path.clone())
+    );
+    // the noise lines were not declared, the defaults got deserialized
+    assert_eq!(
+      provenance(&explanation, "intake", "noise"),
+      Provenance::Default
+    );
+    assert_eq!(
+      provenance(&explanation, "rule", "ignored"),
+      Provenance::File(other)
+    );
+    assert_eq!(
+      provenance(&explanation, "keybindings", "?"),
+      Provenance::Default
+    );
+
+    // the built-in rule
+    let explanation = ConfigExplanation::new(&options, &DEFAULT_RULES[0]);
+    let command = explanation.setting("rule", "command").unwrap();
+    assert_eq!(command.value, DEFAULT_RULES[0].command);
+    assert_eq!(command.provenance, Provenance::Default);
+    assert_eq!(explanation.setting("rule", "ignored"), None);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn json() {
+    let options = AppOptions::parse_from(["--pty"]).unwrap();
+    let json = ConfigExplanation::new(&options, &DEFAULT_RULES[0])
+      .to_json()
+      .unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let intake = &value["sections"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|section| section["name"] == "intake")
+      .unwrap()["settings"];
+    let pty = intake
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|setting| setting["key"] == "pty")
+      .unwrap();
+    assert_eq!(pty["value"], "true");
+    assert_eq!(pty["provenance"]["kind"], "flag");
+    assert_eq!(pty["provenance"]["from"], "--pty");
+    let queue = intake
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|setting| setting["key"] == "queue-capacity")
+      .unwrap();
+    assert_eq!(queue["provenance"]["kind"], "default");
+  }
+}
@@ -0,0 +1,243 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{err, BuildTagKind, EntryId, ErrorKind};
+
+/// The markers that stop following the output, see [`FollowPolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FollowStop {
+  /// Stop on the first error
+  #[default]
+  Error,
+  /// Stop on the first error or warning
+  Warning,
+  /// Keep following until the end of the build
+  Never,
+}
+
+impl FromStr for FollowStop {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "error" => Ok(Self::Error),
+      "warning" => Ok(Self::Warning),
+      "never" => Ok(Self::Never),
+      _ => Err(err!(
+        ErrorKind::Parsing,
+        "invalid follow stop '{}', expected one of: error, warning, never",
+        s
+      )),
+    }
+  }
+}
+
+impl Display for FollowStop {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Self::Error => "error",
+        Self::Warning => "warning",
+        Self::Never => "never",
+      }
+    )
+  }
+}
+
+impl FollowStop {
+  /// Check if a marker of `kind` stops following the output
+  pub fn stops_on(&self, kind: BuildTagKind) -> bool {
+    match self {
+      Self::Error => kind == BuildTagKind::Error,
+      Self::Warning => matches!(kind, BuildTagKind::Error | BuildTagKind::Warning),
+      Self::Never => false,
+    }
+  }
+}
+
+/// Where the log view stands while the output arrives
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FollowState {
+  /// The view stays where the user left it
+  #[default]
+  Off,
+  /// The view sticks to the last line, `at` is the scroll it was last moved to
+  Following { at: Option<usize> },
+  /// The view stopped on the marker of the entry, until resumed
+  Pinned(EntryId, BuildTagKind),
+}
+
+/// Follow the output like `tail -f`, until a marker matching the [`FollowStop`] shows up.
+///
+/// Only the first matching marker of a build pins the view, once resumed it keeps
+/// following until the build starts over, see [`FollowPolicy::reset`]
+#[derive(Debug, Clone, Default)]
+pub struct FollowPolicy {
+  stop_on: FollowStop,
+  state: FollowState,
+  stopped: bool,
+}
+
+impl FollowPolicy {
+  pub fn new(stop_on: FollowStop) -> Self {
+    Self {
+      stop_on,
+      ..Default::default()
+    }
+  }
+
+  /// Start following the output
+  pub fn with_following(mut self, v: bool) -> Self {
+    self.state = match v {
+      true => FollowState::Following { at: None },
+      false => FollowState::Off,
+    };
+    self
+  }
+
+  /// Retrieve the current state
+  #[cfg(test)]
+  pub fn state(&self) -> FollowState {
+    self.state
+  }
+
+  /// Check if the view sticks to the last line
+  pub fn is_following(&self) -> bool {
+    matches!(self.state, FollowState::Following { .. })
+  }
+
+  /// Evaluate the markers newly detected by a prepare, in entry order.
+  ///
+  /// Returns the marker pinning the view, if it stopped following
+  pub fn observe<'m, I: IntoIterator<Item = &'m (EntryId, BuildTagKind)>>(
+    &mut self,
+    detected: I,
+  ) -> Option<(EntryId, BuildTagKind)> {
+    if self.stopped || !self.is_following() {
+      return None;
+    }
+    let (entry_id, kind) = detected
+      .into_iter()
+      .find(|(_entry_id, kind)| self.stop_on.stops_on(*kind))?;
+    self.stopped = true;
+    self.state = FollowState::Pinned(*entry_id, *kind);
+    Some((*entry_id, *kind))
+  }
+
+  /// Compute the scroll of the view, `bottom` being the scroll showing the last line.
+  ///
+  /// Scrolling up stops following, otherwise the view sticks to the bottom
+  pub fn scroll(&mut self, scroll: usize, bottom: usize) -> usize {
+    let FollowState::Following { at } = self.state else {
+      return scroll;
+    };
+    if at.is_some_and(|at| scroll < at) {
+      self.state = FollowState::Off;
+      return scroll;
+    }
+    self.state = FollowState::Following { at: Some(bottom) };
+    bottom
+  }
+
+  /// Switch following on or off, a pinned view resumes following.
+  ///
+  /// Returns true if the view follows the output again
+  pub fn toggle(&mut self) -> bool {
+    self.state = match self.state {
+      FollowState::Following { .. } => FollowState::Off,
+      FollowState::Off | FollowState::Pinned(..) => FollowState::Following { at: None },
+    };
+    self.is_following()
+  }
+
+  /// Start over with a new build: a pinned view follows again, up to its first marker
+  pub fn reset(&mut self) {
+    self.stopped = false;
+    if let FollowState::Pinned(..) = self.state {
+      self.state = FollowState::Following { at: None };
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BuildTagKind, EntryId};
+
+  use super::{FollowPolicy, FollowState, FollowStop};
+
+  #[test]
+  fn warning_then_error() {
+    let detected = [
+      (EntryId(3), BuildTagKind::Warning),
+      (EntryId(8), BuildTagKind::Note),
+      (EntryId(12), BuildTagKind::Error),
+    ];
+    let mut policy = FollowPolicy::new(FollowStop::Error).with_following(true);
+    assert_eq!(policy.observe(&detected[..2]), None);
+    assert!(policy.is_following());
+    assert_eq!(policy.scroll(0, 20), 20);
+    assert_eq!(
+      policy.observe(&detected[2..]),
+      Some((EntryId(12), BuildTagKind::Error))
+    );
+    assert_eq!(
+      policy.state(),
+      FollowState::Pinned(EntryId(12), BuildTagKind::Error)
+    );
+    // the pinned view does not move anymore
+    assert_eq!(policy.scroll(5, 40), 5);
+
+    let mut policy = FollowPolicy::new(FollowStop::Warning).with_following(true);
+    assert_eq!(
+      policy.observe(&detected),
+      Some((EntryId(3), BuildTagKind::Warning))
+    );
+
+    let mut policy = FollowPolicy::new(FollowStop::Never).with_following(true);
+    assert_eq!(policy.observe(&detected), None);
+    assert_eq!(policy.scroll(0, 20), 20);
+  }
+
+  #[test]
+  fn error_then_more_output() {
+    let mut policy = FollowPolicy::new(FollowStop::Error).with_following(true);
+    assert_eq!(
+      policy.observe(&[(EntryId(1), BuildTagKind::Error)]),
+      Some((EntryId(1), BuildTagKind::Error))
+    );
+    // the errors that follow keep the view on the first one
+    assert_eq!(policy.observe(&[(EntryId(9), BuildTagKind::Error)]), None);
+    assert_eq!(policy.scroll(0, 30), 0);
+    assert_eq!(
+      policy.state(),
+      FollowState::Pinned(EntryId(1), BuildTagKind::Error)
+    );
+    // resuming jumps back to the bottom and keeps following
+    assert!(policy.toggle());
+    assert_eq!(policy.scroll(0, 30), 30);
+    assert_eq!(policy.observe(&[(EntryId(40), BuildTagKind::Error)]), None);
+    assert_eq!(policy.scroll(30, 35), 35);
+    // scrolling up stops following
+    assert_eq!(policy.scroll(20, 40), 20);
+    assert_eq!(policy.state(), FollowState::Off);
+    assert_eq!(policy.observe(&[(EntryId(50), BuildTagKind::Error)]), None);
+    // a new build pins again
+    assert!(policy.toggle());
+    policy.reset();
+    assert_eq!(
+      policy.observe(&[(EntryId(2), BuildTagKind::Error)]),
+      Some((EntryId(2), BuildTagKind::Error))
+    );
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      "Warning".parse::<FollowStop>().unwrap(),
+      FollowStop::Warning
+    );
+    assert_eq!(FollowStop::Never.to_string(), "never");
+    assert!("sometimes".parse::<FollowStop>().is_err());
+  }
+}
@@ -0,0 +1,311 @@
+use std::{
+  fs::{create_dir_all, rename, File, OpenOptions},
+  io::{self, BufRead, BufReader, Write},
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime},
+};
+
+use dirs::{data_local_dir, state_dir};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppOptions, BuildSummary, ExitCause};
+
+/// A completed run, appended to the [`History`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+  /// The end of the run, in seconds since the unix epoch
+  pub timestamp: u64,
+  /// The build command, e.g. `cargo build --release`
+  pub command: String,
+  /// The time the build took, in seconds
+  pub duration: f64,
+  /// Whether the build succeeded
+  pub success: bool,
+  /// The exit status, e.g. `exit status: 101` or `killed by SIGKILL`
+  pub exit: String,
+  /// The number of detected errors
+  pub errors: usize,
+  /// The number of detected warnings
+  pub warnings: usize,
+  /// The directory the build ran in
+  pub workspace: PathBuf,
+}
+
+impl HistoryRecord {
+  /// Construct the record of a run ending at `now`
+  pub fn new<C: AsRef<str>, P: AsRef<Path>>(
+    summary: &BuildSummary,
+    command: C,
+    workspace: P,
+    now: SystemTime,
+  ) -> Self {
+    Self {
+      timestamp: now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs(),
+      command: command.as_ref().to_string(),
+      duration: summary.duration.as_secs_f64(),
+      success: summary
+        .exit_status
+        .map(|status| status.success())
+        .unwrap_or_default(),
+      exit: match summary.exit_status {
+        Some(status) => match ExitCause::from_status(status) {
+          Some(cause) => cause.to_string(),
+          None => status.to_string(),
+        },
+        None => "not finished".to_string(),
+      },
+      errors: summary.num_errors,
+      warnings: summary.num_warnings,
+      workspace: workspace.as_ref().to_path_buf(),
+    }
+  }
+
+  /// Retrieve the end of the run
+  pub fn time(&self) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp)
+  }
+
+  /// Encode the record as a JSON line, without the trailing newline
+  #[cfg(feature = "json")]
+  pub fn encode(&self) -> Option<String> {
+    serde_json::to_string(self).ok()
+  }
+
+  /// Encode the record as a JSON line, never available without the `json` feature
+  #[cfg(not(feature = "json"))]
+  pub fn encode(&self) -> Option<String> {
+    None
+  }
+
+  /// Decode a JSON line, `None` if malformed
+  #[cfg(feature = "json")]
+  pub fn decode<L: AsRef<str>>(line: L) -> Option<Self> {
+    serde_json::from_str(line.as_ref()).ok()
+  }
+
+  /// Decode a JSON line, never available without the `json` feature
+  #[cfg(not(feature = "json"))]
+  pub fn decode<L: AsRef<str>>(_line: L) -> Option<Self> {
+    None
+  }
+}
+
+/// The runs recorded across sessions, one JSON record per line.
+///
+/// Once the file grows past its maximum size, it gets rotated to `<file>.1`,
+/// replacing the previous rotation, so that the recent runs stay available
+#[derive(Debug, Clone)]
+pub struct History {
+  path: PathBuf,
+  max_size: u64,
+}
+
+impl History {
+  /// The size past which the file gets rotated
+  pub const MAX_SIZE: u64 = 256 * 1024;
+
+  /// The number of runs listed by the history popup
+  pub const SHOWN: usize = 20;
+
+  /// Construct the history stored in `path`
+  pub fn new<P: AsRef<Path>>(path: P) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+      max_size: Self::MAX_SIZE,
+    }
+  }
+
+  /// Retrieve the default file, in the user's state directory
+  pub fn default_path() -> PathBuf {
+    state_dir()
+      .or_else(data_local_dir)
+      .unwrap_or_else(std::env::temp_dir)
+      .join(env!("CARGO_PKG_NAME"))
+      .join("history.jsonl")
+  }
+
+  /// Define the size past which the file gets rotated
+  #[cfg(test)]
+  pub fn with_max_size(mut self, v: u64) -> Self {
+    self.max_size = v;
+    self
+  }
+
+  /// Retrieve the file of the history
+  #[cfg(test)]
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Retrieve the file the history gets rotated to
+  pub fn rotated_path(&self) -> PathBuf {
+    let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    self.path.with_file_name(name)
+  }
+
+  /// Append a record, rotating the file first if it would grow past its maximum size
+  pub fn append(&self, record: &HistoryRecord) -> io::Result<()> {
+    let Some(line) = record.encode() else {
+      return Ok(());
+    };
+    if let Some(dir) = self.path.parent() {
+      create_dir_all(dir)?;
+    }
+    let size = self.path.metadata().map(|meta| meta.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 + 1 > self.max_size {
+      rename(&self.path, self.rotated_path())?;
+    }
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)?;
+    writeln!(file, "{}", line)
+  }
+
+  /// Load the records, the oldest first. The malformed lines are skipped,
+  /// e.g. a line cut by a crash or written by another version
+  pub fn load(&self) -> Vec<HistoryRecord> {
+    let mut ret = vec![];
+    for path in [self.rotated_path(), self.path.clone()] {
+      let Ok(file) = File::open(&path) else {
+        continue;
+      };
+      for (i, line) in BufReader::new(file).lines().enumerate() {
+        let Ok(line) = line else {
+          break;
+        };
+        match HistoryRecord::decode(&line) {
+          Some(record) => ret.push(record),
+          None if line.trim().is_empty() => {}
+          None => crate::dbg!("Skipped history line {} of {}", i + 1, path.display()),
+        }
+      }
+    }
+    ret
+  }
+
+  /// Append the record of a build run with `options`, ending now
+  pub fn record(&self, options: &AppOptions, summary: &BuildSummary) -> HistoryRecord {
+    let record = HistoryRecord::new(
+      summary,
+      options.command_line(),
+      options.project_dir().unwrap_or_default(),
+      SystemTime::now(),
+    );
+    if let Err(e) = self.append(&record) {
+      crate::Debug::log(format!(
+        "failed to append to the history {}, {}",
+        self.path.display(),
+        e
+      ));
+    }
+    record
+  }
+
+  /// Load the `n` most recent records, the oldest first
+  pub fn last(&self, n: usize) -> Vec<HistoryRecord> {
+    let mut records = self.load();
+    records.drain(..records.len().saturating_sub(n));
+    records
+  }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+  use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+  };
+
+  use crate::BuildSummary;
+
+  use super::{History, HistoryRecord};
+
+  fn history(test: &str) -> History {
+    let dir = std::env::temp_dir().join(format!("nbuild-history-{}-{}", test, std::process::id()));
+    let _ = remove_dir_all(&dir);
+    History::new(dir.join("state").join("history.jsonl"))
+  }
+
+  fn record(warnings: usize) -> HistoryRecord {
+    let summary = BuildSummary {
+      num_warnings: warnings,
+      duration: Duration::from_millis(1500),
+      ..Default::default()
+    };
+    HistoryRecord::new(
+      &summary,
+      "cargo build",
+      "/home/user/demo",
+      SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + warnings as u64),
+    )
+  }
+
+  #[test]
+  fn append_load() {
+    let history = history("append");
+    assert!(history.load().is_empty());
+    for warnings in 0..3 {
+      history.append(&record(warnings)).unwrap();
+    }
+    let records = history.load();
+    assert_eq!(records, vec![record(0), record(1), record(2)]);
+    assert_eq!(records[1].timestamp, 1_700_000_001);
+    assert_eq!(records[1].duration, 1.5);
+    assert_eq!(records[1].exit, "not finished");
+    assert!(!records[1].success);
+    assert_eq!(records[1].workspace, PathBuf::from("/home/user/demo"));
+    assert_eq!(history.last(2), vec![record(1), record(2)]);
+    assert_eq!(history.last(10).len(), 3);
+    let _ = remove_dir_all(history.path().parent().unwrap());
+  }
+
+  #[test]
+  fn rotate() {
+    let line_len = record(0).encode().unwrap().len() as u64 + 1;
+    let history = history("rotate").with_max_size(line_len * 2);
+    for warnings in 0..5 {
+      history.append(&record(warnings)).unwrap();
+    }
+    assert_eq!(
+      history.rotated_path().file_name().unwrap(),
+      "history.jsonl.1"
+    );
+    assert_eq!(read_to_string(history.path()).unwrap().lines().count(), 1);
+    assert_eq!(
+      read_to_string(history.rotated_path())
+        .unwrap()
+        .lines()
+        .count(),
+      2
+    );
+    // the previous rotation gets replaced
+    assert_eq!(history.load(), vec![record(2), record(3), record(4)]);
+    let _ = remove_dir_all(history.path().parent().unwrap());
+  }
+
+  #[test]
+  fn load_corrupted() {
+    let history = history("corrupted");
+    create_dir_all(history.path().parent().unwrap()).unwrap();
+    write(
+      history.path(),
+      format!(
+        "{}\n{{\"timestamp\":12,\"comm\n\nnot json\n{{\"timestamp\":\"x\"}}\n{}\n",
+        record(1).encode().unwrap(),
+        record(2).encode().unwrap()
+      ),
+    )
+    .unwrap();
+    assert_eq!(history.load(), vec![record(1), record(2)]);
+    // the malformed lines stay, the records keep being appended
+    history.append(&record(3)).unwrap();
+    assert_eq!(history.last(1), vec![record(3)]);
+    let _ = remove_dir_all(history.path().parent().unwrap());
+  }
+}
@@ -0,0 +1,458 @@
+#[cfg(all(unix, feature = "json"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{
+  collections::VecDeque,
+  io::Write,
+  path::{Path, PathBuf},
+  process::ExitStatus,
+  sync::{Arc, Condvar, Mutex},
+  thread::spawn,
+};
+
+use serde::Serialize;
+
+use crate::{err, BuildEvent, BuildTagKind, ErrorKind, HookContext, MarkerSelection};
+
+/// An event streamed to the [`IpcServer`] clients, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+  /// The build process was spawned
+  BuildStarted,
+  /// The build process exited, `code` is missing when it was killed by a signal
+  BuildFinished { success: bool, code: Option<i32> },
+  /// A marker was detected, with the location of its block when known
+  Marker {
+    entry_id: usize,
+    kind: BuildTagKind,
+    message: String,
+    file: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>,
+  },
+  /// The selection changed in the UI
+  Selection {
+    marker_id: Option<usize>,
+    entry_id: Option<usize>,
+  },
+}
+
+impl IpcEvent {
+  /// Construct the event of a finished build
+  pub fn finished(status: ExitStatus) -> Self {
+    Self::BuildFinished {
+      success: status.success(),
+      code: status.code(),
+    }
+  }
+
+  /// Construct the event of a detected marker
+  pub fn marker(entry_id: usize, context: HookContext) -> Self {
+    Self::Marker {
+      entry_id,
+      kind: context.kind,
+      message: context.message,
+      file: context.file,
+      line: context.line,
+      column: context.column,
+    }
+  }
+
+  /// Construct the event of a selection change
+  pub fn selection(selection: Option<&MarkerSelection>) -> Self {
+    Self::Selection {
+      marker_id: selection.map(|selection| selection.marker_id.0),
+      entry_id: selection.map(|selection| selection.entry_id.0),
+    }
+  }
+
+  /// Translate a [`BuildEvent`], the errors are streamed as [`IpcEvent::Marker`] instead
+  pub fn from_build_event(event: &BuildEvent) -> Option<Self> {
+    match event {
+      BuildEvent::BuildStarted => Some(Self::BuildStarted),
+      BuildEvent::BuildFinished(status) => Some(Self::finished(*status)),
+      BuildEvent::FirstErrorDetected(_)
+      | BuildEvent::BuildFailed(_)
+      | BuildEvent::IntakeStats(_)
+      | BuildEvent::BuildProgress(..) => None,
+    }
+  }
+
+  /// Encode the event as a JSON line, without the trailing newline
+  #[cfg(feature = "json")]
+  pub fn encode(&self) -> Option<String> {
+    serde_json::to_string(self).ok()
+  }
+
+  /// Encode the event as a JSON line, never available without the `json` feature
+  #[cfg(not(feature = "json"))]
+  pub fn encode(&self) -> Option<String> {
+    None
+  }
+}
+
+/// The lines waiting to be written to one client
+#[derive(Debug, Default)]
+struct IpcQueue {
+  lines: VecDeque<String>,
+  dropped: usize,
+  closed: bool,
+}
+
+/// A connected client, written to by its own thread so that a slow reader
+/// never blocks the others
+#[derive(Debug, Default)]
+struct IpcClient {
+  queue: Mutex<IpcQueue>,
+  ready: Condvar,
+}
+
+impl IpcClient {
+  /// Queue a line, dropping the oldest one when the client does not keep up
+  fn push(&self, line: &str) -> bool {
+    let Ok(mut queue) = self.queue.lock() else {
+      return false;
+    };
+    if queue.closed {
+      return false;
+    }
+    if queue.lines.len() >= IpcServer::MAX_QUEUED {
+      queue.lines.pop_front();
+      queue.dropped += 1;
+    }
+    queue.lines.push_back(line.to_string());
+    self.ready.notify_one();
+    true
+  }
+
+  fn close(&self) {
+    if let Ok(mut queue) = self.queue.lock() {
+      queue.closed = true;
+    }
+    self.ready.notify_one();
+  }
+
+  /// The writing loop, stops when the client hangs up or the server is dropped
+  fn write_loop<W: Write>(&self, mut writer: W) {
+    loop {
+      let line = {
+        let Ok(mut queue) = self.queue.lock() else {
+          return;
+        };
+        while queue.lines.is_empty() && !queue.closed {
+          queue = match self.ready.wait(queue) {
+            Ok(queue) => queue,
+            Err(_) => return,
+          };
+        }
+        if queue.dropped > 0 {
+          crate::dbg!("IPC client too slow, dropped {} events", queue.dropped);
+          queue.dropped = 0;
+        }
+        match queue.lines.pop_front() {
+          Some(line) => line,
+          None => return,
+        }
+      };
+      if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+        crate::dbg!("IPC client disconnected, {}", e);
+        self.close();
+        return;
+      }
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct IpcShared {
+  clients: Mutex<Vec<Arc<IpcClient>>>,
+  /// The socket file, removed when the server is dropped
+  path: Option<PathBuf>,
+  /// Whether the accept thread still listens on the socket
+  #[cfg(all(unix, feature = "json"))]
+  accepting: Arc<AtomicBool>,
+}
+
+impl Drop for IpcShared {
+  fn drop(&mut self) {
+    if let Ok(clients) = self.clients.lock() {
+      for client in clients.iter() {
+        client.close();
+      }
+    }
+    if let Some(path) = self.path.as_ref() {
+      // wake the accept thread up, it finds the server gone and stops listening
+      #[cfg(unix)]
+      let _ = std::os::unix::net::UnixStream::connect(path);
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
+
+/// Stream [`IpcEvent`]s to the editors listening on `--ipc <PATH>`.
+///
+/// Clients are accepted in the background and each one gets its own queue:
+/// [`IpcServer::send`] never blocks, the oldest events of a client that falls
+/// more than [`IpcServer::MAX_QUEUED`] events behind are dropped.
+///
+/// The default server has no socket, sending to it does nothing.
+#[derive(Debug, Clone, Default)]
+pub struct IpcServer {
+  shared: Arc<IpcShared>,
+}
+
+impl IpcServer {
+  /// The number of events queued per client before the oldest get dropped
+  pub const MAX_QUEUED: usize = 1024;
+
+  /// Listen on a unix domain socket, replacing a stale socket file.
+  ///
+  /// Fails when `path` is not a socket, or when another server still listens on it
+  #[cfg(all(unix, feature = "json"))]
+  pub fn bind<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    use std::os::unix::net::UnixListener;
+
+    let path = path.as_ref();
+    Self::remove_stale(path)?;
+    let listener = UnixListener::bind(path).map_err(|e| {
+      err!(
+        ErrorKind::IO,
+        "failed to listen on {}, {}",
+        path.display(),
+        e
+      )
+    })?;
+    let server = Self {
+      shared: Arc::new(IpcShared {
+        clients: Default::default(),
+        path: Some(path.to_path_buf()),
+        accepting: Arc::new(AtomicBool::new(true)),
+      }),
+    };
+    let weak = Arc::downgrade(&server.shared);
+    let accepting = server.shared.accepting.clone();
+    spawn(move || {
+      crate::Debug::log("IPC accept thread started");
+      for stream in listener.incoming() {
+        let Some(shared) = weak.upgrade() else {
+          break;
+        };
+        match stream {
+          Ok(stream) => Self { shared }.connect(stream),
+          Err(e) => crate::dbg!("failed to accept IPC client, {}", e),
+        }
+      }
+      accepting.store(false, Ordering::Release);
+      crate::Debug::log("IPC accept thread stopped");
+    });
+    Ok(server)
+  }
+
+  /// Remove the socket file left by a server that is gone, refusing to touch
+  /// anything else: a regular file, or the socket of another running instance
+  #[cfg(all(unix, feature = "json"))]
+  fn remove_stale(path: &Path) -> crate::Result<()> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+
+    let Ok(metadata) = path.symlink_metadata() else {
+      return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+      return Err(err!(
+        ErrorKind::IO,
+        "cannot listen on {}, the file exists and is not a socket",
+        path.display()
+      ));
+    }
+    if UnixStream::connect(path).is_ok() {
+      return Err(err!(
+        ErrorKind::IO,
+        "cannot listen on {}, another server is listening there",
+        path.display()
+      ));
+    }
+    std::fs::remove_file(path).map_err(|e| {
+      err!(
+        ErrorKind::IO,
+        "failed to remove stale socket {}, {}",
+        path.display(),
+        e
+      )
+    })
+  }
+
+  /// Named pipes are not supported yet, `--ipc` needs a unix domain socket and the `json` feature
+  #[cfg(not(all(unix, feature = "json")))]
+  pub fn bind<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    Err(err!(
+      ErrorKind::IO,
+      "cannot listen on {}, --ipc needs unix domain sockets and the json feature",
+      path.as_ref().display()
+    ))
+  }
+
+  /// Stream the events to `writer` from now on
+  pub fn connect<W: Write + Send + 'static>(&self, writer: W) {
+    let client = Arc::new(IpcClient::default());
+    if let Ok(mut clients) = self.shared.clients.lock() {
+      clients.push(client.clone());
+    }
+    spawn(move || client.write_loop(writer));
+  }
+
+  /// Retrieve the number of connected clients
+  pub fn num_clients(&self) -> usize {
+    self
+      .shared
+      .clients
+      .lock()
+      .map(|clients| clients.len())
+      .unwrap_or(0)
+  }
+
+  /// Queue an event for every client, the disconnected ones are forgotten
+  pub fn send(&self, event: IpcEvent) {
+    let Ok(mut clients) = self.shared.clients.lock() else {
+      return;
+    };
+    if clients.is_empty() {
+      return;
+    }
+    let Some(line) = event.encode() else {
+      return;
+    };
+    clients.retain(|client| client.push(&line));
+  }
+}
+
+#[cfg(all(test, unix, feature = "json"))]
+mod tests {
+  use std::{
+    io::{BufRead, BufReader},
+    os::unix::{
+      net::{UnixListener, UnixStream},
+      process::ExitStatusExt,
+    },
+    process::ExitStatus,
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, Instant},
+  };
+
+  use crate::{BuildEntry, BuildEvent, BuildOutput, EntryId, MarkerId, MarkerSelection, Origin};
+
+  use super::{IpcClient, IpcEvent, IpcServer};
+
+  fn wait_for_clients(server: &IpcServer, n: usize) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.num_clients() < n && Instant::now() < deadline {
+      sleep(Duration::from_millis(5));
+    }
+    assert_eq!(server.num_clients(), n);
+  }
+
+  #[test]
+  fn stream_build() {
+    let path = std::env::temp_dir().join(format!("nbuild-ipc-{}.sock", std::process::id()));
+    let server = IpcServer::bind(&path).unwrap();
+    let clients = [
+      UnixStream::connect(&path).unwrap(),
+      UnixStream::connect(&path).unwrap(),
+    ];
+    wait_for_clients(&server, 2);
+
+    server.send(IpcEvent::from_build_event(&BuildEvent::BuildStarted).unwrap());
+    let mut build = BuildOutput::default().with_ipc(server.clone());
+    for line in [
+      "   Compiling demo v0.1.0",
+      "error[E0308]: mismatched types",
+      " --> src/main.rs:2:7",
+      "warning: unused variable: `x`",
+      " --> src/main.rs:4:9",
+    ] {
+      build.push(BuildEntry::new(line, Origin::Stderr));
+    }
+    while build.prepare() {}
+    server.send(IpcEvent::selection(Some(&MarkerSelection::new(
+      MarkerId(1),
+      EntryId(3),
+      None,
+    ))));
+    let status = ExitStatus::from_raw(101 << 8);
+    server.send(IpcEvent::from_build_event(&BuildEvent::BuildFinished(status)).unwrap());
+
+    let expected = [
+      r#"{"event":"build_started"}"#,
+      r#"{"event":"marker","entry_id":1,"kind":"Error","message":"error[E0308]: mismatched types","file":"src/main.rs","line":2,"column":7}"#,
+      r#"{"event":"marker","entry_id":3,"kind":"Warning","message":"warning: unused variable: `x`","file":"src/main.rs","line":4,"column":9}"#,
+      r#"{"event":"selection","marker_id":1,"entry_id":3}"#,
+      r#"{"event":"build_finished","success":false,"code":101}"#,
+    ];
+    for client in clients {
+      let lines = BufReader::new(client)
+        .lines()
+        .take(expected.len())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+      assert_eq!(lines, expected);
+    }
+
+    drop(build);
+    drop(server);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn keep_regular_file() {
+    let path = std::env::temp_dir().join(format!("nbuild-ipc-{}.txt", std::process::id()));
+    std::fs::write(&path, "notes").unwrap();
+    assert!(IpcServer::bind(&path).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "notes");
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn keep_live_socket() {
+    let path = std::env::temp_dir().join(format!("nbuild-ipc-live-{}.sock", std::process::id()));
+    let _server = IpcServer::bind(&path).unwrap();
+    assert!(IpcServer::bind(&path).is_err());
+    assert!(UnixStream::connect(&path).is_ok());
+  }
+
+  #[test]
+  fn replace_stale_socket() {
+    let path = std::env::temp_dir().join(format!("nbuild-ipc-stale-{}.sock", std::process::id()));
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+    let server = IpcServer::bind(&path).unwrap();
+    let _client = UnixStream::connect(&path).unwrap();
+    wait_for_clients(&server, 1);
+  }
+
+  #[test]
+  fn stop_accepting() {
+    let path = std::env::temp_dir().join(format!("nbuild-ipc-stop-{}.sock", std::process::id()));
+    let server = IpcServer::bind(&path).unwrap();
+    let accepting = server.shared.accepting.clone();
+    assert!(accepting.load(Ordering::Acquire));
+    drop(server);
+    assert!(!path.exists());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while accepting.load(Ordering::Acquire) && Instant::now() < deadline {
+      sleep(Duration::from_millis(5));
+    }
+    assert!(!accepting.load(Ordering::Acquire));
+  }
+
+  #[test]
+  fn drop_oldest() {
+    let client = IpcClient::default();
+    for i in 0..IpcServer::MAX_QUEUED + 2 {
+      assert!(client.push(&i.to_string()));
+    }
+    let queue = client.queue.lock().unwrap();
+    assert_eq!(queue.lines.len(), IpcServer::MAX_QUEUED);
+    assert_eq!(queue.lines.front().map(String::as_str), Some("2"));
+    assert_eq!(queue.dropped, 2);
+  }
+}
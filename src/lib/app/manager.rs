@@ -0,0 +1,288 @@
+use std::{
+  io,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver, Sender, SyncSender},
+    Arc,
+  },
+  thread::{spawn, JoinHandle},
+};
+
+use crate::{err, BuildCommand, BuildEntry, BuildEvent, Debug, ErrorKind};
+
+use super::{AppOptions, Builder};
+
+/// The requests handled by the [`BuildManager`]
+pub enum BuildRequest {
+  /// Start a new build sending its entries to the given channel,
+  /// the build in flight (if any) gets killed first
+  Run(SyncSender<BuildEntry>),
+  /// Kill the build in flight
+  Stop,
+  /// Write a line to the stdin of the build in flight, see `--interactive`
+  Input(String),
+}
+
+/// Spawn the command of a build, replaceable for tests
+pub type SpawnBuild = Box<dyn Fn(&AppOptions) -> io::Result<BuildCommand> + Send>;
+
+/// Spawn the [`Builder`] threads on demand, keeping at most one build in flight
+pub struct BuildManager {
+  options: AppOptions,
+  requests: Receiver<BuildRequest>,
+  tx_events: Sender<BuildEvent>,
+  spawn_build: SpawnBuild,
+  active: Option<(JoinHandle<()>, Arc<AtomicBool>, Sender<String>)>,
+  runs: usize,
+  failure: Option<crate::Error>,
+}
+
+impl BuildManager {
+  pub fn new(
+    options: AppOptions,
+    requests: Receiver<BuildRequest>,
+    tx_events: Sender<BuildEvent>,
+  ) -> Self {
+    Self {
+      options,
+      requests,
+      tx_events,
+      spawn_build: Box::new(Builder::spawn_build),
+      active: None,
+      runs: 0,
+      failure: None,
+    }
+  }
+
+  /// Replace the spawned command, `cargo build` by default
+  #[cfg(test)]
+  pub fn with_spawn_build(mut self, spawn_build: SpawnBuild) -> Self {
+    self.spawn_build = spawn_build;
+    self
+  }
+
+  /// The supervisor thread: handle the requests until all the senders are dropped,
+  /// then wait for the last build. Fails with the first build that could not be spawned
+  pub fn run(mut self) -> crate::Result<()> {
+    while let Ok(request) = self.requests.recv() {
+      match request {
+        BuildRequest::Run(tx_entries) => {
+          self.stop();
+          self.start(tx_entries);
+        }
+        BuildRequest::Stop => self.stop(),
+        BuildRequest::Input(line) => self.input(line),
+      }
+    }
+    if let Some((builder, ..)) = self.active.take() {
+      let _ = builder.join();
+    }
+    Debug::log(format!("build manager stopped after {} run(s)", self.runs));
+    match self.failure {
+      Some(e) => Err(e),
+      None => Ok(()),
+    }
+  }
+
+  /// Kill the build in flight and wait for its [`BuildEvent::BuildFinished`]
+  fn stop(&mut self) {
+    if let Some((builder, cancel, _input)) = self.active.take() {
+      cancel.store(true, Ordering::SeqCst);
+      if let Err(e) = builder.join() {
+        Debug::log(format!("failed to join build thread, {:?}", e));
+      }
+    }
+  }
+
+  /// Forward a line to the build in flight, dropped if there is none
+  fn input(&self, line: String) {
+    match self.active.as_ref() {
+      Some((_builder, _cancel, input)) => {
+        let _ = input.send(line);
+      }
+      None => Debug::log("no build in flight, dropped the input line"),
+    }
+  }
+
+  fn start(&mut self, tx_entries: SyncSender<BuildEntry>) {
+    let build = match (self.spawn_build)(&self.options) {
+      Ok(build) => build,
+      Err(e) => {
+        Debug::log(format!("error: failed to spawn cargo build, {}", e));
+        self
+          .failure
+          .get_or_insert(err!(ErrorKind::IO, "failed to spawn cargo build, {}", e));
+        return;
+      }
+    };
+    self.runs += 1;
+    crate::dbg!("Starting run #{}", self.runs);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (tx_input, rx_input) = channel();
+    let builder = Builder::new(self.options.clone(), tx_entries, self.tx_events.clone())
+      .with_cancel(cancel.clone())
+      .with_input(rx_input);
+    self.active = Some((spawn(move || builder.run_command(build)), cancel, tx_input));
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use std::{
+    process::{Command, Stdio},
+    sync::mpsc::{channel, sync_channel},
+    thread::spawn,
+    time::{Duration, Instant},
+  };
+
+  use crate::{AppOptions, BuildCommand, BuildEvent};
+
+  use super::{BuildManager, BuildRequest};
+
+  #[test]
+  fn rerun_kills_build_in_flight() {
+    let (tx_requests, rx_requests) = channel();
+    let (tx_events, rx_events) = channel();
+    let manager = BuildManager::new(AppOptions::default(), rx_requests, tx_events)
+      .with_spawn_build(Box::new(|_options| {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo started; exec sleep 10");
+        BuildCommand::spawn_command(command)
+      }));
+    let supervisor = spawn(move || manager.run());
+    let start = Instant::now();
+    let (tx_first, rx_first) = sync_channel(16);
+    tx_requests.send(BuildRequest::Run(tx_first)).unwrap();
+    assert_eq!(
+      rx_first
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .message(),
+      "started"
+    );
+    let (tx_second, rx_second) = sync_channel(16);
+    tx_requests.send(BuildRequest::Run(tx_second)).unwrap();
+    assert!(rx_second.recv_timeout(Duration::from_secs(5)).is_ok());
+    tx_requests.send(BuildRequest::Stop).unwrap();
+    drop(tx_requests);
+    supervisor.join().unwrap().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(4));
+    // the first build finished before the second one started
+    let events = rx_events
+      .try_iter()
+      .filter(|event| !matches!(event, BuildEvent::IntakeStats(_)))
+      .collect::<Vec<_>>();
+    assert!(matches!(
+      events.as_slice(),
+      [
+        BuildEvent::BuildStarted,
+        BuildEvent::BuildFinished(first),
+        BuildEvent::BuildStarted,
+        BuildEvent::BuildFinished(second),
+      ] if !first.success() && !second.success()
+    ));
+  }
+
+  #[test]
+  fn input_reaches_build_in_flight() {
+    let (tx_requests, rx_requests) = channel();
+    let (tx_events, _rx_events) = channel();
+    let manager = BuildManager::new(AppOptions::default(), rx_requests, tx_events)
+      .with_spawn_build(Box::new(|_options| {
+        let mut command = Command::new("sh");
+        command
+          .arg("-c")
+          .arg("echo 'Continue?'; read answer; echo \"got $answer\"")
+          .stdin(Stdio::piped());
+        BuildCommand::spawn_command(command)
+      }));
+    let supervisor = spawn(move || manager.run());
+    let (tx_entries, rx_entries) = sync_channel(16);
+    tx_requests.send(BuildRequest::Run(tx_entries)).unwrap();
+    assert_eq!(
+      rx_entries
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .message(),
+      "Continue?"
+    );
+    tx_requests
+      .send(BuildRequest::Input("yes".to_string()))
+      .unwrap();
+    assert_eq!(
+      rx_entries
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .message(),
+      "got yes"
+    );
+    drop(tx_requests);
+    supervisor.join().unwrap().unwrap();
+  }
+
+  #[test]
+  fn output_file_across_runs() {
+    let dir = std::env::temp_dir().join(format!("nbuild-manager-output-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = AppOptions::builder()
+      .build_args(["--release"])
+      .output_file(dir.join("build.log"))
+      .output_file_header(true)
+      .output_file_max_size(64)
+      .output_file_keep(1)
+      .finish()
+      .unwrap();
+    let (tx_requests, rx_requests) = channel();
+    let (tx_events, rx_events) = channel();
+    let manager =
+      BuildManager::new(options, rx_requests, tx_events).with_spawn_build(Box::new(|_options| {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo 'error: boom'");
+        BuildCommand::spawn_command(command)
+      }));
+    let supervisor = spawn(move || manager.run());
+    for _ in 0..2 {
+      let (tx_entries, _rx_entries) = sync_channel(16);
+      tx_requests.send(BuildRequest::Run(tx_entries)).unwrap();
+      // the runs must not overlap
+      while !matches!(
+        rx_events.recv_timeout(Duration::from_secs(5)).unwrap(),
+        BuildEvent::BuildFinished(_)
+      ) {}
+    }
+    drop(tx_requests);
+    supervisor.join().unwrap().unwrap();
+    // each run takes 56 bytes, the second one rotated the first one
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+    for name in ["build.log", "build.log.1"] {
+      let content = read(name);
+      let lines = content.lines().collect::<Vec<_>>();
+      assert_eq!(lines.len(), 2, "{}: {:?}", name, lines);
+      assert!(lines[0].starts_with("# "), "{:?}", lines);
+      assert!(lines[0].ends_with(" cargo build --release"), "{:?}", lines);
+      assert_eq!(lines[1], "error: boom");
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn spawn_failure() {
+    let (tx_requests, rx_requests) = channel();
+    let (tx_events, _rx_events) = channel();
+    let manager = BuildManager::new(AppOptions::default(), rx_requests, tx_events)
+      .with_spawn_build(Box::new(|_options| {
+        BuildCommand::spawn_command(Command::new("/nonexistent/cargo"))
+      }));
+    let supervisor = spawn(move || manager.run());
+    for _ in 0..2 {
+      let (tx_entries, _rx_entries) = sync_channel(16);
+      tx_requests.send(BuildRequest::Run(tx_entries)).unwrap();
+    }
+    drop(tx_requests);
+    let e = supervisor.join().unwrap().unwrap_err();
+    assert!(e
+      .to_string()
+      .starts_with("i/o: failed to spawn cargo build, "));
+  }
+}
@@ -1,11 +1,52 @@
-pub mod app;
-pub mod builder;
-pub mod opt;
-pub mod renderer;
-pub mod scanner;
+#[allow(clippy::module_inception)]
+mod app;
+mod builder;
+mod cursor;
+mod doctor;
+mod excerpt;
+mod explain;
+mod follow;
+mod history;
+mod ipc;
+mod manager;
+mod opt;
+mod perf;
+mod preparer;
+mod quit;
+mod renderer;
+mod run;
+mod scanner;
+mod scheduler;
+mod session;
+mod summary;
+mod supervisor;
+mod terminal;
+mod viewer;
+mod viewport;
+mod writer;
 
 pub use app::*;
 pub use builder::*;
+pub use cursor::*;
+pub use doctor::*;
+pub use excerpt::*;
+pub use explain::*;
+pub use follow::*;
+pub use history::*;
+pub use ipc::*;
+pub use manager::*;
 pub use opt::*;
+pub use perf::*;
+pub use preparer::*;
+pub use quit::*;
 pub use renderer::*;
+pub use run::*;
 pub use scanner::*;
+pub use scheduler::*;
+pub use session::*;
+pub use summary::*;
+pub use supervisor::*;
+pub use terminal::*;
+pub use viewer::*;
+pub use viewport::*;
+pub use writer::*;
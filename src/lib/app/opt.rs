@@ -1,13 +1,18 @@
 use std::{
-  collections::HashMap,
-  io::{stdin, IsTerminal as _},
-  path::PathBuf,
+  collections::{HashMap, VecDeque},
+  io::{stderr, stdin, IsTerminal},
+  path::{Path, PathBuf},
   process::exit,
+  time::Duration,
 };
 
 use lazy_static::lazy_static;
 
-use crate::active_rule_name;
+use crate::{
+  active_rule_name, err, BuildSummary, ColorChoice, ErrorKind, Excerpt, ExitCodeMode,
+  ExplainFormat, FollowStop, MarkerBudget, PrepareScheduler, Provenance, Scanner,
+  ThousandsSeparator,
+};
 
 struct KnownOption {
   name: String,
@@ -15,7 +20,8 @@ struct KnownOption {
   short: Option<char>,
   desc: Option<String>,
   needs_value: bool,
-  activate: Option<Box<dyn Fn(&mut AppOptions, Option<String>)>>,
+  activate:
+    Option<Box<dyn Fn(AppOptionsBuilder, Option<String>) -> crate::Result<AppOptionsBuilder>>>,
 }
 
 unsafe impl Send for KnownOption {}
@@ -53,7 +59,12 @@ impl KnownOption {
     self
   }
 
-  pub fn with_activate<F: Fn(&mut AppOptions, Option<String>) + 'static>(mut self, f: F) -> Self {
+  pub fn with_activate<
+    F: Fn(AppOptionsBuilder, Option<String>) -> crate::Result<AppOptionsBuilder> + 'static,
+  >(
+    mut self,
+    f: F,
+  ) -> Self {
     self.activate = Some(Box::new(f));
     self
   }
@@ -64,83 +75,826 @@ lazy_static! {
     KnownOption::new("help")
       .with_long("--help")
       .with_short('h')
-      .with_activate(|opts, arg| opts.show_help = true)
+      .with_activate(|opts, _arg| Ok(opts.show_help(true)))
       .with_desc("Show this help screen"),
+    KnownOption::new("stdin")
+      .with_long("--stdin")
+      .with_activate(|opts, _arg| Ok(opts.stdin(true)))
+      .with_desc(
+        "Read the build output from stdin instead of running the build, like `-` (a file also works)"
+      ),
     KnownOption::new("only-errors")
       .with_long("--only-errors")
       .with_short('E')
-      .with_activate(|opts, arg| opts.show_only_errors = true)
+      .with_activate(|opts, _arg| Ok(opts.show_only_errors(true)))
       .with_desc("Filter logs: show only errors"),
+    KnownOption::new("inline-markers")
+      .with_long("--inline-markers")
+      .with_activate(|opts, _arg| Ok(opts.inline_markers(true)))
+      .with_desc(
+        "Always use the compact gutter: badges in the margin, markers styled inside the line"
+      ),
+    KnownOption::new("compact-width")
+      .with_long("--compact-width")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<u16>() {
+        Ok(width) => Ok(opts.compact_width(width)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid compact width, {}", e)),
+      })
+      .with_desc("Use the compact gutter below this terminal width (default: 100, toggle with c)"),
+    KnownOption::new("zen")
+      .with_long("--zen")
+      .with_activate(|opts, _arg| Ok(opts.zen(true)))
+      .with_desc("Start in zen mode: no command and shortcuts rows, no border (toggle with z)"),
+    KnownOption::new("cursor")
+      .with_long("--cursor")
+      .with_activate(|opts, _arg| Ok(opts.cursor(true)))
+      .with_desc("Start with the line cursor moved by j/k instead of the scroll (toggle with v)"),
+    KnownOption::new("tab-width")
+      .with_long("--tab-width")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<usize>() {
+        Ok(0) => Err(err!(ErrorKind::Parsing, "invalid tab width, expected at least 1")),
+        Ok(width) => Ok(opts.tab_width(width)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid tab width, {}", e)),
+      })
+      .with_desc("Expand the tabs of the displayed lines to this many columns (default: 4)"),
+    KnownOption::new("max-line-length")
+      .with_long("--max-line-length")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<usize>() {
+        Ok(0) => Err(err!(ErrorKind::Parsing, "invalid line length, expected at least 1 byte")),
+        Ok(len) => Ok(opts.max_line_length(len)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid line length, {}", e)),
+      })
+      .with_desc(
+        "Cut the displayed lines longer than this many bytes, x shows them whole (default: 4096)"
+      ),
+    KnownOption::new("scrolloff")
+      .with_long("--scrolloff")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<usize>() {
+        Ok(rows) => Ok(opts.scrolloff(rows)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid scrolloff, {}", e)),
+      })
+      .with_desc("Keep this many lines around the selected marker when scrolling (default: 3)"),
+    KnownOption::new("pin-lines")
+      .with_long("--pin-lines")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<usize>() {
+        Ok(0) => Err(err!(ErrorKind::Parsing, "invalid pin lines, at least 1 line")),
+        Ok(lines) => Ok(opts.pin_lines(lines)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid pin lines, {}", e)),
+      })
+      .with_desc("Show this many lines of the pinned blocks above the log (default: 1)"),
+    KnownOption::new("respect-cargo-colors")
+      .with_long("--respect-cargo-colors")
+      .with_activate(|opts, _arg| Ok(opts.respect_cargo_colors(true)))
+      .with_desc("Keep cargo's own colors (--color) instead of styling the markers"),
+    KnownOption::new("keep-locale")
+      .with_long("--keep-locale")
+      .with_activate(|opts, _arg| Ok(opts.keep_locale(true)))
+      .with_desc("Keep the user's locale for the build instead of forcing LC_ALL=C"),
+    KnownOption::new("keep-blank-lines")
+      .with_long("--keep-blank-lines")
+      .with_activate(|opts, _arg| Ok(opts.keep_blank_lines(true)))
+      .with_desc("Keep the blank and spinner-only lines instead of dropping them on intake"),
+    KnownOption::new("pty")
+      .with_long("--pty")
+      .with_activate(|opts, _arg| Ok(opts.pty(true)))
+      .with_desc("Run the build inside a pseudo-terminal, for cargo's progress bars and colors"),
+    KnownOption::new("interactive")
+      .with_long("--interactive")
+      .with_activate(|opts, _arg| Ok(opts.interactive(true)))
+      .with_desc("Pipe the build's stdin, I types a line for its prompts"),
+    KnownOption::new("hyperlinks")
+      .with_long("--hyperlinks")
+      .with_activate(|opts, _arg| Ok(opts.hyperlinks(true)))
+      .with_desc("Make the locations clickable even if the terminal is not known to support it"),
+    KnownOption::new("hyperlink-format")
+      .with_long("--hyperlink-format")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(opts.hyperlink_format(arg.unwrap_or_default())))
+      .with_desc("The URL opened by the locations, e.g. vscode://file/{path}:{line}:{column}"),
+    KnownOption::new("diff")
+      .with_long("--diff")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(opts.diff_log(arg.unwrap_or_default())))
+      .with_desc("Compare two build logs: --diff <OLD> <NEW>, exits with 1 on regressions"),
+    KnownOption::new("split-view")
+      .with_long("--split-view")
+      .with_activate(|opts, _arg| Ok(opts.split_view(true)))
+      .with_desc("Show stdout and stderr side by side (toggle with |)"),
+    KnownOption::new("fold-repeats")
+      .with_long("--fold-repeats")
+      .with_activate(|opts, _arg| Ok(opts.fold_repeats(true)))
+      .with_desc("Show the identical consecutive lines once, with their count (toggle with u)"),
+    KnownOption::new("dedup-targets")
+      .with_long("--dedup-targets")
+      .with_activate(|opts, _arg| Ok(opts.dedup_targets(true)))
+      .with_desc(
+        "Fold the diagnostics repeated by another target of their package, e.g. the lib warnings of its tests (toggle with D)"
+      ),
+    KnownOption::new("follow")
+      .with_long("--follow")
+      .with_activate(|opts, _arg| Ok(opts.follow(true)))
+      .with_desc("Keep the last line in view while the output arrives (toggle with F)"),
+    KnownOption::new("follow-stop-on")
+      .with_long("--follow-stop-on")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(
+        opts.follow_stop_on(arg.unwrap_or_default().parse::<FollowStop>()?)
+      ))
+      .with_desc("Stop following on the first: error (default), warning or never"),
+    KnownOption::new("thousands-separator")
+      .with_long("--thousands-separator")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(
+        opts.thousands_separator(arg.unwrap_or_default().parse::<ThousandsSeparator>()?)
+      ))
+      .with_desc("Separate the thousands of the counts with: comma (default), space or none"),
+    KnownOption::new("ipc")
+      .with_long("--ipc")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(opts.ipc(arg.unwrap_or_default())))
+      .with_desc(
+        "Stream the build events as JSON lines on this unix socket, for editor integration"
+      ),
+    KnownOption::new("output-file")
+      .with_long("--output-file")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(opts.output_file(arg.unwrap_or_default())))
+      .with_desc("Append the build output to this file"),
+    KnownOption::new("output-file-max-size")
+      .with_long("--output-file-max-size")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<u64>() {
+        Ok(0) => Err(err!(ErrorKind::Parsing, "invalid output file size, expected at least 1 byte")),
+        Ok(size) => Ok(opts.output_file_max_size(size)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid output file size, {}", e)),
+      })
+      .with_desc(
+        "Rotate the output file once it grows past this many bytes: build.log becomes build.log.1, ..."
+      ),
+    KnownOption::new("output-file-keep")
+      .with_long("--output-file-keep")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<usize>() {
+        Ok(keep) => Ok(opts.output_file_keep(keep)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid number of output files, {}", e)),
+      })
+      .with_desc("Keep this many rotated output files (default: 3)"),
+    KnownOption::new("output-file-header")
+      .with_long("--output-file-header")
+      .with_activate(|opts, _arg| Ok(opts.output_file_header(true)))
+      .with_desc("Start each run in the output file with its date and command"),
+    KnownOption::new("queue-capacity")
+      .with_long("--queue-capacity")
+      .with_value_required(true)
+      .with_activate(|opts, arg| match arg.unwrap_or_default().parse::<usize>() {
+        Ok(0) => Err(err!(ErrorKind::Parsing, "invalid queue capacity, expected at least 1 entry")),
+        Ok(capacity) => Ok(opts.queue_capacity(capacity)),
+        Err(e) => Err(err!(ErrorKind::Parsing, "invalid queue capacity, {}", e)),
+      })
+      .with_desc(
+        "The output lines read ahead of the UI, the build waits past them (default: 4096)"
+      ),
+    KnownOption::new("no-tui")
+      .with_long("--no-tui")
+      .with_activate(|opts, _arg| Ok(opts.no_tui(true)))
+      .with_desc("Run headless: print the build output and a summary line, without the UI, also used when the terminal cannot be set up"),
+    KnownOption::new("no-title")
+      .with_long("--no-title")
+      .with_activate(|opts, _arg| Ok(opts.no_title(true)))
+      .with_desc("Never set the terminal title to the build state"),
+    KnownOption::new("no-term-progress")
+      .with_long("--no-term-progress")
+      .with_activate(|opts, _arg| Ok(opts.no_term_progress(true)))
+      .with_desc("Never report the build progress to the terminal, e.g. on the taskbar"),
+    KnownOption::new("render")
+      .with_long("--render")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(opts.render(arg.unwrap_or_default().parse::<Excerpt>()?)))
+      .with_desc(
+        "Print a block or lines of a build log and exit: --render block-<N>|lines-<A>..<B> <LOG>, plain with --color never"
+      ),
+    KnownOption::new("summary")
+      .with_long("--summary")
+      .with_activate(|opts, _arg| Ok(opts.summary(true)))
+      .with_desc(
+        "Print a summary line for scripts on exit (default when stderr is not a terminal)"
+      ),
+    KnownOption::new("no-summary")
+      .with_long("--no-summary")
+      .with_activate(|opts, _arg| Ok(opts.summary(false)))
+      .with_desc("Never print the summary line for scripts"),
+    KnownOption::new("perf-overlay")
+      .with_long("--perf-overlay")
+      .with_activate(|opts, _arg| Ok(opts.perf_overlay(true)))
+      .with_desc("Show the frame timings overlay (toggle with F10)"),
+    KnownOption::new("exit-code")
+      .with_long("--exit-code")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(
+        opts.exit_code(arg.unwrap_or_default().parse::<ExitCodeMode>()?)
+      ))
+      .with_desc("Exit code mode: build (default), errors or warnings"),
+    KnownOption::new("manifest-path")
+      .with_long("--manifest-path")
+      .with_value_required(true)
+      .with_activate(|opts, arg| Ok(opts.manifest_path(arg.unwrap_or_default())))
+      .with_desc(
+        "Build the project of this Cargo.toml, its locations are resolved from its directory"
+      ),
     KnownOption::new("config")
       .with_long("--config")
       .with_short('c')
       .with_value_required(true)
-      .with_activate(|opts, arg| opts.config_path = Some(PathBuf::from(arg.unwrap())))
+      .with_activate(|opts, arg| Ok(opts.config_path(arg.unwrap_or_default())))
       .with_desc("Use custom config path"),
     KnownOption::new("eject-config")
       .with_long("--eject-config")
-      .with_activate(|opts, arg| opts.eject_config = true)
+      .with_activate(|opts, _arg| Ok(opts.eject_config(true)))
       .with_desc("Eject (write) the config in the current directory"),
+    KnownOption::new("check-config")
+      .with_long("--check-config")
+      .with_activate(|opts, _arg| Ok(opts.check_config(true)))
+      .with_desc(
+        "Load and validate the config without running the build, exits with 1 on problems"
+      ),
+    KnownOption::new("explain-config")
+      .with_long("--explain-config")
+      .with_activate(|opts, _arg| Ok(opts.explain_config(Some(ExplainFormat::Text))))
+      .with_desc(
+        "Print the effective settings and where each comes from (default, config file or flag), then exit"
+      ),
+    KnownOption::new("explain-config-json")
+      .with_long("--explain-config-json")
+      .with_activate(|opts, _arg| Ok(opts.explain_config(Some(ExplainFormat::Json))))
+      .with_desc("Like --explain-config, printed as JSON"),
     KnownOption::new("active-rule")
       .with_short('r')
       .with_long("--rule")
-      .with_activate(|opts, arg| opts.active_rule = arg.unwrap())
+      .with_activate(|opts, arg| Ok(opts.rule(arg.unwrap_or_default())))
       .with_value_required(true)
       .with_desc("Define the active rule"),
     KnownOption::new("dump-rules")
       .with_long("--dump-rules")
-      .with_activate(|opts, arg| opts.dump_rules = true)
+      .with_activate(|opts, _arg| Ok(opts.dump_rules(true)))
       .with_desc("Dump known rules"),
+    KnownOption::new("keep-artifacts")
+      .with_long("--keep-artifacts")
+      .with_activate(|opts, _arg| Ok(opts.keep_artifacts(true)))
+      .with_desc("Keep the run directory (debug log, ...) instead of removing it on exit"),
+    KnownOption::new("runs")
+      .with_long("--runs")
+      .with_activate(|opts, _arg| Ok(opts.list_runs(true)))
+      .with_desc("List the recent run directories with their size"),
+    KnownOption::new("doctor")
+      .with_long("--doctor")
+      .with_activate(|opts, _arg| Ok(opts.doctor(true)))
+      .with_desc(
+        "Check cargo, the config, the terminal and the directories written, exits with 1 on failures"
+      ),
   ];
 }
 
-/// Represent the application options
+/// Represent the application options.
+///
+/// Construct them with [`AppOptions::builder`], or [`AppOptions::parse`] for the command line.
+/// New options may be added, hence `#[non_exhaustive]`
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct AppOptions {
+  /// Read the build output from stdin instead of running the build (default: false)
   pub stdin: bool,
+  /// The build log read instead of running the build (default: none)
+  pub input: Option<PathBuf>,
+  /// Print the usage and exit (default: false)
   pub show_help: bool,
+  /// Start with the errors filter (default: false)
   pub show_only_errors: bool,
+  /// Always use the compact gutter (default: false)
+  pub inline_markers: bool,
+  /// The terminal width below which the compact gutter is used, see [`AppOptions::compact_gutter`]
+  /// (default: [`AppOptions::COMPACT_WIDTH`])
+  pub compact_width: u16,
+  /// Start in zen mode, without the command and shortcuts rows (default: false)
+  pub zen: bool,
+  /// Start with the line cursor, see [`crate::CursorState`] (default: false)
+  pub cursor: bool,
+  /// The columns between two tab stops of the displayed lines
+  /// (default: [`crate::BuildOutput::DEFAULT_TAB_WIDTH`])
+  pub tab_width: usize,
+  /// The bytes of a displayed line before it gets cut
+  /// (default: [`crate::BuildOutput::DEFAULT_MAX_LINE_LENGTH`])
+  pub max_line_length: usize,
+  /// The lines of context kept around the selected marker
+  /// (default: [`crate::Viewport::DEFAULT_SCROLLOFF`])
+  pub scrolloff: usize,
+  /// The first lines of the pinned blocks shown above the log, see [`crate::PinnedArea`] (default: 1)
+  pub pin_lines: usize,
+  /// Keep cargo's `--color` arguments (default: false)
+  pub respect_cargo_colors: bool,
+  /// Show stdout and stderr side by side (default: false)
+  pub split_view: bool,
+  /// Group the identical consecutive lines, see [`crate::Fold::repeats`] (default: false)
+  pub fold_repeats: bool,
+  /// Fold the blocks repeated by another target, see [`crate::Fold::duplicate`] (default: false)
+  pub dedup_targets: bool,
+  /// Follow the output, see [`crate::FollowPolicy`] (default: false)
+  pub follow: bool,
+  /// The markers that stop following the output (default: [`FollowStop::Error`])
+  pub follow_stop_on: FollowStop,
+  /// The separator of the thousands in the counts, see [`crate::format_count`] (default: [`ThousandsSeparator::Comma`])
+  pub thousands_separator: ThousandsSeparator,
+  /// Keep the user's locale for the build (default: false)
+  pub keep_locale: bool,
+  /// Keep the lines dropped by the [`crate::IntakeFilter`] (default: false)
+  pub keep_blank_lines: bool,
+  /// Run the build inside a pseudo-terminal, see [`crate::BuildCommand::spawn_pty`] (default: false)
+  pub pty: bool,
+  /// Pipe the stdin of the build, written from [`crate::InputPrompt`] (default: false)
+  pub interactive: bool,
+  /// Force the clickable locations (default: false, detected from the terminal)
+  pub hyperlinks: bool,
+  /// The URL opened by the locations (default: none, `file://` URLs)
+  pub hyperlink_format: Option<String>,
+  /// The old and new logs to compare, see [`crate::DiffViewer`] (default: none)
+  pub diff: Vec<PathBuf>,
+  /// The color arguments removed from the build arguments, see [`AppOptions::rewrite_color_args`]
+  pub stripped_color_args: Vec<String>,
+  /// The socket the events get streamed on, see [`crate::IpcServer`] (default: none)
+  pub ipc: Option<PathBuf>,
+  /// The file the build output gets appended to, see [`crate::RotatingWriter`] (default: none)
+  pub output_file: Option<PathBuf>,
+  /// The size past which the output file gets rotated (default: none, never)
+  pub output_file_max_size: Option<u64>,
+  /// The rotated output files kept (default: [`crate::RotatingWriter::DEFAULT_KEEP`])
+  pub output_file_keep: usize,
+  /// Write the date and command of each run to the output file (default: false)
+  pub output_file_header: bool,
+  /// The entries queued by the reader threads and by the build thread each, the build waits
+  /// once they are full, see [`crate::Builder`] (default: [`crate::Builder::QUEUE_CAPACITY`])
+  pub queue_capacity: usize,
+  /// Run without the UI (default: false)
+  pub no_tui: bool,
+  /// Leave the terminal title untouched (default: false, detected from the terminal)
+  pub no_title: bool,
+  /// Never report the progress to the terminal, see [`crate::ProgressReporter`]
+  /// (default: false, detected from the terminal)
+  pub no_term_progress: bool,
+  /// Print the summary line for scripts, see [`AppOptions::prints_summary`]
+  /// (default: none, when stderr is not a terminal)
+  pub summary: Option<bool>,
+  /// The excerpt of the build log printed instead of running the UI, see [`Excerpt`] (default: none)
+  pub render: Option<Excerpt>,
+  /// When the printed excerpts get styled, from cargo's `--color` argument (default: [`ColorChoice::Auto`])
+  pub color: ColorChoice,
+  /// Show the frame timings overlay (default: false)
+  pub perf_overlay: bool,
+  /// The pending entries prepared without waiting, see [`crate::PrepareScheduler`]
+  /// (default: [`crate::PrepareScheduler::MIN_PENDING`])
+  pub prepare_min_pending: usize,
+  /// The longest time the pending entries wait to be prepared
+  /// (default: [`crate::PrepareScheduler::MAX_DELAY`])
+  pub prepare_max_delay: Duration,
+  /// The entries prepared at once, unless the lines arrive faster
+  /// (default: [`crate::PrepareScheduler::MAX_BATCH`])
+  pub prepare_max_batch: usize,
+  /// The time a marker regex may spend per prepared batch before getting disabled,
+  /// none to never disable them (default: [`crate::MarkerBudget::DEFAULT`])
+  pub marker_budget: Option<Duration>,
+  /// How the process exit code gets computed (default: [`ExitCodeMode::Build`])
+  pub exit_code: ExitCodeMode,
+  /// The manifest of the built project, forwarded to cargo. The locations get resolved from
+  /// its directory, see [`crate::set_manifest_path`] (default: none, the current directory)
+  pub manifest_path: Option<PathBuf>,
+  /// The config file (default: none, the user's config directory)
   pub config_path: Option<PathBuf>,
+  /// The name of the active rule (default: the current active rule)
   pub active_rule: String,
+  /// Print the known rules and exit (default: false)
   pub dump_rules: bool,
+  /// Write the config and exit (default: false)
   pub eject_config: bool,
+  /// Validate the config and exit (default: false)
+  pub check_config: bool,
+  /// Print the effective settings with their provenance and exit, see
+  /// [`crate::ConfigExplanation`] (default: none)
+  pub explain_config: Option<ExplainFormat>,
+  /// Keep the run directory on exit, see [`crate::RunContext`] (default: false)
+  pub keep_artifacts: bool,
+  /// Print the run directories and exit (default: false)
+  pub list_runs: bool,
+  /// Diagnose the environment and exit, see [`crate::Doctor`] (default: false)
+  pub doctor: bool,
+  /// The arguments given to the build command (default: none)
   pub build_args: Vec<String>,
+  /// The command line flag that set each option, by option name, see
+  /// [`AppOptions::provenance`] (default: none)
+  pub flags: HashMap<String, String>,
 }
 
 impl Default for AppOptions {
   fn default() -> Self {
     Self {
       stdin: Default::default(),
+      input: Default::default(),
       show_help: Default::default(),
       show_only_errors: Default::default(),
+      inline_markers: Default::default(),
+      compact_width: Self::COMPACT_WIDTH,
+      zen: Default::default(),
+      cursor: Default::default(),
+      tab_width: crate::BuildOutput::DEFAULT_TAB_WIDTH,
+      max_line_length: crate::BuildOutput::DEFAULT_MAX_LINE_LENGTH,
+      scrolloff: crate::Viewport::DEFAULT_SCROLLOFF,
+      pin_lines: 1,
+      respect_cargo_colors: Default::default(),
+      split_view: Default::default(),
+      fold_repeats: Default::default(),
+      dedup_targets: Default::default(),
+      follow: Default::default(),
+      follow_stop_on: Default::default(),
+      thousands_separator: Default::default(),
+      keep_locale: Default::default(),
+      keep_blank_lines: Default::default(),
+      pty: Default::default(),
+      interactive: Default::default(),
+      hyperlinks: Default::default(),
+      hyperlink_format: Default::default(),
+      diff: Default::default(),
+      stripped_color_args: Default::default(),
+      ipc: Default::default(),
+      output_file: Default::default(),
+      output_file_max_size: Default::default(),
+      output_file_keep: crate::RotatingWriter::DEFAULT_KEEP,
+      output_file_header: Default::default(),
+      queue_capacity: crate::Builder::QUEUE_CAPACITY,
+      no_tui: Default::default(),
+      no_title: Default::default(),
+      no_term_progress: Default::default(),
+      summary: Default::default(),
+      render: Default::default(),
+      color: Default::default(),
+      perf_overlay: Default::default(),
+      prepare_min_pending: PrepareScheduler::MIN_PENDING,
+      prepare_max_delay: PrepareScheduler::MAX_DELAY,
+      prepare_max_batch: PrepareScheduler::MAX_BATCH,
+      marker_budget: Some(MarkerBudget::DEFAULT),
+      exit_code: Default::default(),
+      manifest_path: Default::default(),
       config_path: Default::default(),
       active_rule: active_rule_name(),
       dump_rules: false,
       eject_config: Default::default(),
+      check_config: Default::default(),
+      explain_config: Default::default(),
+      keep_artifacts: Default::default(),
+      list_runs: Default::default(),
+      doctor: Default::default(),
       build_args: Default::default(),
+      flags: Default::default(),
     }
   }
 }
 
-impl AppOptions {
-  /// Parse command line to extract options
-  pub fn parse(mut self) -> Self {
-    if !stdin().is_terminal() {
-      self.stdin = true
-    }
+/// Construct [`AppOptions`] programmatically, e.g. to embed the application or in tests:
+///
+/// ```
+/// use cargo_nbuild::AppOptions;
+///
+/// let options = AppOptions::builder()
+///   .build_args(["--release"])
+///   .rule("cargo")
+///   .no_tui(true)
+///   .finish()
+///   .unwrap();
+/// assert_eq!(options.build_args, vec!["--release".to_string()]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AppOptionsBuilder {
+  options: AppOptions,
+}
+
+impl From<AppOptions> for AppOptionsBuilder {
+  fn from(options: AppOptions) -> Self {
+    Self { options }
+  }
+}
+
+impl AppOptionsBuilder {
+  pub fn stdin(mut self, v: bool) -> Self {
+    self.options.stdin = v;
+    self
+  }
+
+  /// Read the build log `path` instead of running the build
+  pub fn input<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.options.input = Some(path.as_ref().to_path_buf());
+    self
+  }
+
+  pub fn show_help(mut self, v: bool) -> Self {
+    self.options.show_help = v;
+    self
+  }
+
+  pub fn show_only_errors(mut self, v: bool) -> Self {
+    self.options.show_only_errors = v;
+    self
+  }
+
+  pub fn inline_markers(mut self, v: bool) -> Self {
+    self.options.inline_markers = v;
+    self
+  }
+
+  pub fn compact_width(mut self, v: u16) -> Self {
+    self.options.compact_width = v;
+    self
+  }
+
+  pub fn zen(mut self, v: bool) -> Self {
+    self.options.zen = v;
+    self
+  }
+
+  pub fn cursor(mut self, v: bool) -> Self {
+    self.options.cursor = v;
+    self
+  }
+
+  pub fn tab_width(mut self, v: usize) -> Self {
+    self.options.tab_width = v;
+    self
+  }
+
+  pub fn max_line_length(mut self, v: usize) -> Self {
+    self.options.max_line_length = v;
+    self
+  }
+
+  pub fn scrolloff(mut self, v: usize) -> Self {
+    self.options.scrolloff = v;
+    self
+  }
+
+  pub fn pin_lines(mut self, v: usize) -> Self {
+    self.options.pin_lines = v;
+    self
+  }
+
+  pub fn respect_cargo_colors(mut self, v: bool) -> Self {
+    self.options.respect_cargo_colors = v;
+    self
+  }
+
+  pub fn split_view(mut self, v: bool) -> Self {
+    self.options.split_view = v;
+    self
+  }
+
+  pub fn fold_repeats(mut self, v: bool) -> Self {
+    self.options.fold_repeats = v;
+    self
+  }
+
+  pub fn dedup_targets(mut self, v: bool) -> Self {
+    self.options.dedup_targets = v;
+    self
+  }
+
+  pub fn follow(mut self, v: bool) -> Self {
+    self.options.follow = v;
+    self
+  }
+
+  pub fn follow_stop_on(mut self, v: FollowStop) -> Self {
+    self.options.follow_stop_on = v;
+    self
+  }
+
+  pub fn thousands_separator(mut self, v: ThousandsSeparator) -> Self {
+    self.options.thousands_separator = v;
+    self
+  }
+
+  pub fn keep_locale(mut self, v: bool) -> Self {
+    self.options.keep_locale = v;
+    self
+  }
+
+  pub fn keep_blank_lines(mut self, v: bool) -> Self {
+    self.options.keep_blank_lines = v;
+    self
+  }
+
+  pub fn pty(mut self, v: bool) -> Self {
+    self.options.pty = v;
+    self
+  }
+
+  pub fn interactive(mut self, v: bool) -> Self {
+    self.options.interactive = v;
+    self
+  }
+
+  pub fn hyperlinks(mut self, v: bool) -> Self {
+    self.options.hyperlinks = v;
+    self
+  }
+
+  pub fn hyperlink_format<F: AsRef<str>>(mut self, format: F) -> Self {
+    self.options.hyperlink_format = Some(format.as_ref().to_string());
+    self
+  }
+
+  /// Compare the `old` and `new` logs instead of running the build
+  pub fn diff<O: AsRef<Path>, N: AsRef<Path>>(mut self, old: O, new: N) -> Self {
+    self.options.diff = vec![old.as_ref().to_path_buf(), new.as_ref().to_path_buf()];
+    self
+  }
+
+  /// Add a log to compare, see [`Self::diff`]
+  pub fn diff_log<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.options.diff.push(path.as_ref().to_path_buf());
+    self
+  }
+
+  pub fn ipc<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.options.ipc = Some(path.as_ref().to_path_buf());
+    self
+  }
+
+  pub fn output_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.options.output_file = Some(path.as_ref().to_path_buf());
+    self
+  }
+
+  pub fn output_file_max_size(mut self, v: u64) -> Self {
+    self.options.output_file_max_size = Some(v);
+    self
+  }
+
+  pub fn output_file_keep(mut self, v: usize) -> Self {
+    self.options.output_file_keep = v;
+    self
+  }
+
+  pub fn output_file_header(mut self, v: bool) -> Self {
+    self.options.output_file_header = v;
+    self
+  }
+
+  pub fn queue_capacity(mut self, v: usize) -> Self {
+    self.options.queue_capacity = v;
+    self
+  }
+
+  pub fn no_tui(mut self, v: bool) -> Self {
+    self.options.no_tui = v;
+    self
+  }
+
+  pub fn no_title(mut self, v: bool) -> Self {
+    self.options.no_title = v;
+    self
+  }
+
+  pub fn no_term_progress(mut self, v: bool) -> Self {
+    self.options.no_term_progress = v;
+    self
+  }
+
+  pub fn render(mut self, v: Excerpt) -> Self {
+    self.options.render = Some(v);
+    self
+  }
+
+  pub fn color(mut self, v: ColorChoice) -> Self {
+    self.options.color = v;
+    self
+  }
+
+  pub fn summary(mut self, v: bool) -> Self {
+    self.options.summary = Some(v);
+    self
+  }
+
+  pub fn perf_overlay(mut self, v: bool) -> Self {
+    self.options.perf_overlay = v;
+    self
+  }
+
+  pub fn prepare_min_pending(mut self, v: usize) -> Self {
+    self.options.prepare_min_pending = v;
+    self
+  }
+
+  pub fn prepare_max_delay(mut self, v: Duration) -> Self {
+    self.options.prepare_max_delay = v;
+    self
+  }
+
+  pub fn prepare_max_batch(mut self, v: usize) -> Self {
+    self.options.prepare_max_batch = v;
+    self
+  }
+
+  pub fn marker_budget(mut self, v: Option<Duration>) -> Self {
+    self.options.marker_budget = v;
+    self
+  }
+
+  pub fn exit_code(mut self, mode: ExitCodeMode) -> Self {
+    self.options.exit_code = mode;
+    self
+  }
+
+  pub fn manifest_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.options.manifest_path = Some(path.as_ref().to_path_buf());
+    self
+  }
+
+  pub fn config_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self.options.config_path = Some(path.as_ref().to_path_buf());
+    self
+  }
+
+  /// Define the active rule by name
+  pub fn rule<N: AsRef<str>>(mut self, name: N) -> Self {
+    self.options.active_rule = name.as_ref().to_string();
+    self
+  }
+
+  pub fn dump_rules(mut self, v: bool) -> Self {
+    self.options.dump_rules = v;
+    self
+  }
+
+  pub fn eject_config(mut self, v: bool) -> Self {
+    self.options.eject_config = v;
+    self
+  }
+
+  pub fn check_config(mut self, v: bool) -> Self {
+    self.options.check_config = v;
+    self
+  }
+
+  pub fn explain_config(mut self, v: Option<ExplainFormat>) -> Self {
+    self.options.explain_config = v;
+    self
+  }
+
+  pub fn keep_artifacts(mut self, v: bool) -> Self {
+    self.options.keep_artifacts = v;
+    self
+  }
+
+  pub fn list_runs(mut self, v: bool) -> Self {
+    self.options.list_runs = v;
+    self
+  }
+
+  pub fn doctor(mut self, v: bool) -> Self {
+    self.options.doctor = v;
+    self
+  }
+
+  /// Replace the arguments given to the build command
+  pub fn build_args<A: AsRef<str>, I: IntoIterator<Item = A>>(mut self, args: I) -> Self {
+    self.options.build_args = args
+      .into_iter()
+      .map(|arg| arg.as_ref().to_string())
+      .collect::<Vec<_>>();
+    self
+  }
+
+  /// Apply the command line arguments (without the program name), the unknown ones are
+  /// left to the build command, as well as everything after `--`
+  pub fn parse_from<A: AsRef<str>, I: IntoIterator<Item = A>>(
+    mut self,
+    args: I,
+  ) -> crate::Result<AppOptions> {
     let pkg_name = env!("CARGO_PKG_NAME").replace("cargo-", "");
-    self.build_args = std::env::args().skip(1).collect::<Vec<_>>();
-    if !self.build_args.is_empty() && self.build_args[0].eq(&pkg_name) {
-      self.build_args.remove(0);
+    let mut args = args
+      .into_iter()
+      .map(|arg| arg.as_ref().to_string())
+      .collect::<VecDeque<_>>();
+    // invoked as `cargo nbuild`
+    if args.front().is_some_and(|arg| arg.eq(&pkg_name)) {
+      args.pop_front();
     }
-    let mut i: isize = 0;
-    while i < self.build_args.len() as isize {
-      let arg = self.build_args[i as usize].clone();
+    let mut build_args = vec![];
+    while let Some(arg) = args.pop_front() {
       if arg.eq("--") {
-        self.build_args.remove(i as usize);
+        build_args.extend(args.drain(..));
         break;
       }
-      if let Some(known_opt) = KNOWN_OPTIONS.iter().find(|opt| {
+      let Some(known_opt) = KNOWN_OPTIONS.iter().find(|opt| {
         if opt.long.is_some() && opt.long.as_ref().unwrap().eq_ignore_ascii_case(&arg) {
           return true;
         }
@@ -148,23 +902,275 @@ impl AppOptions {
           return true;
         }
         return false;
-      }) {
-        self.build_args.remove(i as usize);
-        let mut arg = None;
-        if known_opt.needs_value {
-          arg = Some(self.build_args[i as usize].clone());
-          self.build_args.remove(i as usize);
+      }) else {
+        // `nbuild build.log` or `nbuild -`: cargo build takes no positional argument
+        let positional = arg == "-" || !arg.starts_with('-');
+        if positional && build_args.is_empty() && self.options.diff.is_empty() {
+          self = match arg.as_str() {
+            "-" => self.stdin(true),
+            path => self.input(path),
+          };
+          let name = if arg == "-" { "stdin" } else { "input" };
+          self.options.flags.insert(name.to_string(), arg);
+          continue;
         }
-        known_opt.activate.as_ref().unwrap()(&mut self, arg);
-        i -= 1;
+        build_args.push(arg);
+        continue;
+      };
+      let mut value = None;
+      if known_opt.needs_value {
+        value = Some(
+          args
+            .pop_front()
+            .ok_or_else(|| err!(ErrorKind::Parsing, "{} expects a value", arg))?,
+        );
       }
-      i += 1;
+      self = known_opt.activate.as_ref().unwrap()(self, value)?;
+      self.options.flags.insert(known_opt.name.clone(), arg);
+    }
+    // --diff <OLD> <NEW>: the new log is left in the build arguments
+    if self.options.diff.len() == 1 && !build_args.is_empty() {
+      self = self.diff_log(build_args.remove(0));
+    }
+    self.build_args(build_args).finish()
+  }
+
+  /// Validate the options, rejecting the conflicting ones, and rewrite the color arguments
+  /// (see [`AppOptions::rewrite_color_args`])
+  pub fn finish(mut self) -> crate::Result<AppOptions> {
+    let options = &mut self.options;
+    if options.active_rule.is_empty() {
+      return Err(err!(ErrorKind::Parsing, "--rule expects a rule name"));
+    }
+    if !matches!(options.diff.len(), 0 | 2) {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--diff expects two build logs: --diff <OLD> <NEW>"
+      ));
+    }
+    if options.reads_log() && !options.diff.is_empty() {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--diff compares build logs, it cannot read another build output"
+      ));
+    }
+    if options.stdin && options.input.is_some() {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "the build output is read either from stdin or from a file, not both"
+      ));
+    }
+    if options.no_tui && options.ipc.is_some() {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--ipc streams the events of the UI, it cannot be used with --no-tui"
+      ));
+    }
+    if options.interactive && (options.reads_log() || !options.diff.is_empty()) {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--interactive answers the prompts of the build, it cannot read a build log"
+      ));
+    }
+    if options.interactive && options.no_tui {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--interactive types the answers in the UI, it cannot be used with --no-tui"
+      ));
+    }
+    if options.output_file.is_none()
+      && (options.output_file_max_size.is_some()
+        || options.output_file_keep != crate::RotatingWriter::DEFAULT_KEEP
+        || options.output_file_header)
+    {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "the --output-file-* options apply to the --output-file, it is missing"
+      ));
     }
-    if self.show_help {
+    if options.output_file.is_some() && (options.reads_log() || !options.diff.is_empty()) {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--output-file records the build output, it cannot read a build log"
+      ));
+    }
+    if options.render.is_some() && !options.diff.is_empty() {
+      return Err(err!(
+        ErrorKind::Parsing,
+        "--render prints an excerpt of a build log, it cannot be used with --diff"
+      ));
+    }
+    if let Some(path) = options.manifest_path.as_ref() {
+      if path.file_name().is_none_or(|name| name != "Cargo.toml") {
+        return Err(err!(
+          ErrorKind::Parsing,
+          "--manifest-path expects the path of a Cargo.toml, got {}",
+          path.display()
+        ));
+      }
+      if !path.is_file() {
+        return Err(err!(
+          ErrorKind::Parsing,
+          "--manifest-path {} does not exist",
+          path.display()
+        ));
+      }
+    }
+    if let Some(color) = AppOptions::color_choice(&options.build_args)? {
+      options.color = color;
+      options
+        .flags
+        .insert("color".to_string(), "--color".to_string());
+    }
+    if !options.respect_cargo_colors {
+      let stripped = AppOptions::rewrite_color_args(&mut options.build_args);
+      options.stripped_color_args.extend(stripped);
+    }
+    Ok(self.options)
+  }
+}
+
+impl AppOptions {
+  /// The default of `--compact-width`
+  pub const COMPACT_WIDTH: u16 = 100;
+  /// How long a piped stdin may stay silent before the build gets run instead
+  pub const PIPE_PEEK_TIMEOUT: Duration = Duration::from_millis(500);
+
+  /// Construct the options programmatically, see [`AppOptionsBuilder`]
+  pub fn builder() -> AppOptionsBuilder {
+    AppOptionsBuilder::default()
+  }
+
+  /// Check if the compact gutter is used in a terminal `width` cells wide:
+  /// always with `--inline-markers`, otherwise below `--compact-width`
+  pub fn compact_gutter(&self, width: u16) -> bool {
+    self.inline_markers || width < self.compact_width
+  }
+
+  /// Format the build command, e.g. `cargo build --release`
+  pub fn command_line(&self) -> String {
+    std::iter::once("cargo build".to_string())
+      .chain(
+        self
+          .manifest_path
+          .iter()
+          .map(|path| format!("--manifest-path {}", path.display())),
+      )
+      .chain(self.build_args.iter().cloned())
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  /// Retrieve the directory of the built project: the one containing `--manifest-path`,
+  /// else the current directory
+  pub fn project_dir(&self) -> Option<PathBuf> {
+    crate::PathDisplay::project_dir(self.manifest_path.as_deref())
+  }
+
+  /// Retrieve where the option `name` (e.g. `tab-width`) comes from: the command line
+  /// flag that set it, else its default
+  pub fn provenance<N: AsRef<str>>(&self, name: N) -> Provenance {
+    match self.flags.get(name.as_ref()) {
+      Some(flag) => Provenance::Flag(flag.clone()),
+      None => Provenance::Default,
+    }
+  }
+
+  /// Check if a build log is read (stdin or a file) instead of running the build
+  pub fn reads_log(&self) -> bool {
+    self.stdin || self.input.is_some()
+  }
+
+  /// Check if the summary line for scripts gets printed on exit,
+  /// see [`crate::BuildSummary::script_line`]
+  pub fn prints_summary(&self) -> bool {
+    self.summary.unwrap_or_else(|| !stderr().is_terminal())
+  }
+
+  /// Check if `--pty` is not supported on this platform, the build then runs with pipes
+  pub fn pty_fallback(&self) -> bool {
+    self.pty && !self.reads_log() && !crate::BuildCommand::PTY_SUPPORTED
+  }
+
+  /// Parse command line arguments (without the program name) on top of the defaults
+  pub fn parse_from<A: AsRef<str>, I: IntoIterator<Item = A>>(args: I) -> crate::Result<Self> {
+    Self::builder().parse_from(args)
+  }
+
+  /// Parse the process command line to extract options, printing the
+  /// usage with `--help` and exiting on errors
+  pub fn parse(self) -> Self {
+    let mut options = match AppOptionsBuilder::from(self).parse_from(std::env::args().skip(1)) {
+      Ok(options) => options,
+      Err(e) => {
+        eprintln!("\x1b[0;31merror\x1b[0m: {}", e);
+        exit(1);
+      }
+    };
+    // `nbuild < build.log` or `cargo build | nbuild`, the logs given to --diff are read instead
+    if !options.reads_log()
+      && options.diff.is_empty()
+      && !options.interactive
+      && Scanner::has_input(&stdin(), Self::PIPE_PEEK_TIMEOUT)
+    {
+      options.stdin = true;
+    }
+    if options.show_help {
       Self::usage();
     }
-    crate::dbg!("{:#?}", self);
-    self
+    crate::dbg!("{:#?}", options);
+    options
+  }
+
+  /// Retrieve the last `--color <WHEN>` / `--color=<WHEN>` of the build arguments
+  pub fn color_choice(args: &[String]) -> crate::Result<Option<ColorChoice>> {
+    let mut color = None;
+    for (i, arg) in args.iter().enumerate() {
+      let value = match arg.strip_prefix("--color=") {
+        Some(value) => Some(value),
+        None if arg == "--color" => args.get(i + 1).map(|value| value.as_str()),
+        None => None,
+      };
+      if let Some(value) = value {
+        color = Some(value.parse::<ColorChoice>()?);
+      }
+    }
+    Ok(color)
+  }
+
+  /// Replace the `--color <WHEN>` / `--color=<WHEN>` build arguments by `--color=never`
+  /// so cargo's escape codes don't fight with the marker styles.
+  ///
+  /// Returns the removed arguments
+  pub fn rewrite_color_args(args: &mut Vec<String>) -> Vec<String> {
+    let mut stripped = vec![];
+    let mut values = vec![];
+    let mut position = None;
+    let mut i = 0;
+    while i < args.len() {
+      if args[i] == "--color" {
+        stripped.push(args.remove(i));
+        if i < args.len() && !args[i].starts_with('-') {
+          values.push(args[i].clone());
+          stripped.push(args.remove(i));
+        }
+      } else if let Some(value) = args[i].strip_prefix("--color=") {
+        values.push(value.to_string());
+        stripped.push(args.remove(i));
+      } else {
+        i += 1;
+        continue;
+      }
+      position.get_or_insert(i);
+    }
+    if let Some(position) = position {
+      args.insert(position, "--color=never".to_string());
+    }
+    // colors were already disabled, nothing to report
+    if values.iter().all(|value| value == "never") && !values.is_empty() {
+      stripped.clear();
+    }
+    stripped
   }
 
   fn usage() {
@@ -207,6 +1213,11 @@ impl AppOptions {
       eprintln!("  {:width$}: {}", opt[0], opt[1], width = widths[0])
     }
     eprintln!();
+    eprintln!("\x1b[1m* Summary line\x1b[0m");
+    eprintln!();
+    eprintln!("  With --summary, the last line written to stderr is:");
+    eprintln!("  {}", BuildSummary::SCRIPT_LINE_FORMAT);
+    eprintln!();
     eprintln!("\x1b[1m* Author\x1b[0m");
     eprintln!();
     for author in env!("CARGO_PKG_AUTHORS").split(":") {
@@ -215,3 +1226,321 @@ impl AppOptions {
     exit(0);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use crate::{
+    ColorChoice, ErrorKind, Excerpt, ExitCodeMode, ExplainFormat, FollowStop, Provenance,
+    ThousandsSeparator,
+  };
+
+  use super::AppOptions;
+
+  fn rewrite(args: &[&str]) -> (Vec<String>, Vec<String>) {
+    let mut args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let stripped = AppOptions::rewrite_color_args(&mut args);
+    (args, stripped)
+  }
+
+  #[test]
+  fn compact_gutter() {
+    let options = AppOptions::default();
+    assert!(options.compact_gutter(80));
+    assert!(!options.compact_gutter(200));
+    let options = AppOptions {
+      inline_markers: true,
+      ..Default::default()
+    };
+    assert!(options.compact_gutter(200));
+  }
+
+  #[test]
+  fn rewrite_color_args() {
+    assert_eq!(
+      rewrite(&["--release", "--color", "always", "-p", "demo"]),
+      (
+        vec!["--release", "--color=never", "-p", "demo"]
+          .into_iter()
+          .map(String::from)
+          .collect(),
+        vec!["--color".to_string(), "always".to_string()]
+      )
+    );
+    assert_eq!(
+      rewrite(&["--color=always", "--color", "auto"]),
+      (
+        vec!["--color=never".to_string()],
+        vec![
+          "--color=always".to_string(),
+          "--color".to_string(),
+          "auto".to_string()
+        ]
+      )
+    );
+  }
+
+  #[test]
+  fn keep_other_args() {
+    assert_eq!(
+      rewrite(&["--release", "--features", "colors"]),
+      (
+        vec!["--release", "--features", "colors"]
+          .into_iter()
+          .map(String::from)
+          .collect(),
+        vec![]
+      )
+    );
+    // already disabled, nothing to report
+    assert_eq!(
+      rewrite(&["--color=never"]),
+      (vec!["--color=never".to_string()], vec![])
+    );
+  }
+
+  #[test]
+  fn builder() {
+    let options = AppOptions::builder()
+      .stdin(true)
+      .build_args(["--release", "--color", "always"])
+      .rule("cargo")
+      .exit_code(ExitCodeMode::Errors)
+      .finish()
+      .unwrap();
+    assert!(options.stdin);
+    assert_eq!(options.active_rule, "cargo");
+    assert_eq!(options.exit_code, ExitCodeMode::Errors);
+    assert_eq!(options.build_args, vec!["--release", "--color=never"]);
+    assert_eq!(options.stripped_color_args, vec!["--color", "always"]);
+    assert_eq!(options.color, ColorChoice::Always);
+    assert_eq!(options.compact_width, AppOptions::COMPACT_WIDTH);
+  }
+
+  #[test]
+  fn builder_conflicts() {
+    for builder in [
+      AppOptions::builder().stdin(true).diff("old.log", "new.log"),
+      AppOptions::builder().no_tui(true).ipc("/tmp/nbuild.sock"),
+      AppOptions::builder().diff_log("old.log"),
+      AppOptions::builder().rule(""),
+      AppOptions::builder()
+        .render(Excerpt::Block(1))
+        .diff("old.log", "new.log"),
+      AppOptions::builder().interactive(true).input("build.log"),
+      AppOptions::builder().interactive(true).no_tui(true),
+    ] {
+      let e = builder.finish().unwrap_err();
+      assert_eq!(e.kind(), ErrorKind::Parsing, "{}", e);
+    }
+    assert!(AppOptions::builder()
+      .diff("old.log", "new.log")
+      .finish()
+      .is_ok());
+    assert!(AppOptions::builder()
+      .ipc("/tmp/nbuild.sock")
+      .finish()
+      .is_ok());
+    assert!(AppOptions::builder()
+      .input("build.log")
+      .diff("old.log", "new.log")
+      .finish()
+      .is_err());
+    assert!(AppOptions::builder()
+      .stdin(true)
+      .input("build.log")
+      .finish()
+      .is_err());
+  }
+
+  #[test]
+  fn parse_intake() {
+    // the build runs
+    for args in [&[][..], &["--release"][..], &["-p", "demo"][..]] {
+      let options = AppOptions::parse_from(args).unwrap();
+      assert!(!options.reads_log(), "{:?}", args);
+    }
+    let options = AppOptions::parse_from(["-p", "demo"]).unwrap();
+    assert_eq!(options.build_args, vec!["-p", "demo"]);
+    // stdin is read
+    for args in [&["-"][..], &["--stdin"][..], &["-r", "cargo", "-"][..]] {
+      let options = AppOptions::parse_from(args).unwrap();
+      assert!(options.stdin, "{:?}", args);
+      assert_eq!(options.input, None);
+      assert!(options.build_args.is_empty());
+    }
+    // a file is read
+    for args in [
+      &["build.log"][..],
+      &["nbuild", "build.log"][..],
+      &["-E", "build.log"][..],
+    ] {
+      let options = AppOptions::parse_from(args).unwrap();
+      assert!(!options.stdin, "{:?}", args);
+      assert_eq!(options.input, Some(PathBuf::from("build.log")));
+      assert!(options.build_args.is_empty());
+    }
+    for args in [
+      &["--stdin", "build.log"][..],
+      &["-", "--diff", "old.log", "new.log"][..],
+    ] {
+      let e = AppOptions::parse_from(args).unwrap_err();
+      assert_eq!(e.kind(), ErrorKind::Parsing, "{:?}: {}", args, e);
+    }
+  }
+
+  #[test]
+  fn manifest_path() {
+    let dir = std::env::temp_dir().join(format!("nbuild-manifest-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let manifest = dir.join("Cargo.toml");
+    fs::write(&manifest, "[package]\nname = \"demo\"\n").unwrap();
+    fs::write(dir.join("Other.toml"), "").unwrap();
+
+    let options =
+      AppOptions::parse_from(["--manifest-path", &manifest.to_string_lossy(), "--release"])
+        .unwrap();
+    assert_eq!(options.manifest_path.as_ref(), Some(&manifest));
+    assert_eq!(options.build_args, vec!["--release"]);
+    assert_eq!(options.project_dir(), Some(dir.clone()));
+    assert_eq!(
+      options.command_line(),
+      format!(
+        "cargo build --manifest-path {} --release",
+        manifest.display()
+      )
+    );
+    // a missing manifest, or a file not named Cargo.toml
+    for path in [
+      dir.join("missing").join("Cargo.toml"),
+      dir.join("Other.toml"),
+    ] {
+      let e = AppOptions::builder()
+        .manifest_path(&path)
+        .finish()
+        .unwrap_err();
+      assert_eq!(e.kind(), ErrorKind::Parsing, "{}", e);
+    }
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn parse_render() {
+    let options =
+      AppOptions::parse_from(["--render", "block-3", "build.log", "--color", "never"]).unwrap();
+    assert_eq!(options.render, Some(Excerpt::Block(3)));
+    assert_eq!(options.input, Some(PathBuf::from("build.log")));
+    assert_eq!(options.color, ColorChoice::Never);
+    let options = AppOptions::parse_from(["--render", "lines-2..8", "-"]).unwrap();
+    assert_eq!(options.render, Some(Excerpt::Lines { start: 2, end: 8 }));
+    assert_eq!(options.color, ColorChoice::Auto);
+    for args in [
+      &["--render", "lines-8..2", "build.log"][..],
+      &["--render", "block-1", "build.log", "--color=sometimes"][..],
+    ] {
+      let e = AppOptions::parse_from(args).unwrap_err();
+      assert_eq!(e.kind(), ErrorKind::Parsing, "{:?}: {}", args, e);
+    }
+  }
+
+  #[test]
+  fn parse_from() {
+    let options = AppOptions::parse_from([
+      "nbuild",
+      "--no-tui",
+      "--pty",
+      "--no-summary",
+      "--no-title",
+      "--no-term-progress",
+      "--keep-artifacts",
+      "--follow-stop-on",
+      "warning",
+      "-r",
+      "cargo",
+      "--release",
+      "--",
+      "--split-view",
+    ])
+    .unwrap();
+    assert!(options.no_tui);
+    assert!(options.pty);
+    assert!(!options.interactive);
+    assert!(!options.prints_summary());
+    assert!(options.keep_artifacts);
+    assert!(options.no_title);
+    assert!(options.no_term_progress);
+    assert_eq!(options.follow_stop_on, FollowStop::Warning);
+    assert!(!options.split_view);
+    assert_eq!(options.active_rule, "cargo");
+    assert_eq!(options.build_args, vec!["--release", "--split-view"]);
+
+    let options = AppOptions::parse_from([
+      "--interactive",
+      "--tab-width",
+      "8",
+      "--max-line-length",
+      "65536",
+      "--dedup-targets",
+      "--keep-blank-lines",
+      "--thousands-separator",
+      "space",
+      "--scrolloff",
+      "0",
+      "--pin-lines",
+      "3",
+      "--cursor",
+      "--queue-capacity",
+      "100",
+      "--release",
+    ])
+    .unwrap();
+    assert!(options.interactive);
+    assert_eq!(options.queue_capacity, 100);
+    assert_eq!(options.tab_width, 8);
+    assert_eq!(options.max_line_length, 65536);
+    assert!(options.dedup_targets);
+    assert!(options.keep_blank_lines);
+    assert_eq!(options.thousands_separator, ThousandsSeparator::Space);
+    assert_eq!(options.scrolloff, 0);
+    assert_eq!(options.pin_lines, 3);
+    assert!(options.cursor);
+    assert!(AppOptions::parse_from(["--pin-lines", "0"]).is_err());
+    assert_eq!(options.build_args, vec!["--release"]);
+
+    let options = AppOptions::parse_from(["--explain-config-json", "build.log"]).unwrap();
+    assert_eq!(options.explain_config, Some(ExplainFormat::Json));
+    assert_eq!(
+      options.provenance("input"),
+      Provenance::Flag("build.log".into())
+    );
+    assert_eq!(options.provenance("tab-width"), Provenance::Default);
+
+    let options = AppOptions::parse_from(["--diff", "old.log", "new.log"]).unwrap();
+    assert_eq!(
+      options.diff,
+      vec![PathBuf::from("old.log"), PathBuf::from("new.log")]
+    );
+    assert!(options.build_args.is_empty());
+
+    for args in [
+      &["--compact-width", "wide"][..],
+      &["--tab-width", "0"][..],
+      &["--tab-width", "wide"][..],
+      &["--max-line-length", "0"][..],
+      &["--exit-code", "sometimes"][..],
+      &["--follow-stop-on", "sometimes"][..],
+      &["--thousands-separator", "dot"][..],
+      &["--config"][..],
+      &["--no-tui", "--ipc", "/tmp/nbuild.sock"][..],
+      &["--output-file-max-size", "1024"][..],
+      &["--output-file", "build.log", "--output-file-max-size", "0"][..],
+      &["--output-file", "build.log", "--output-file-keep", "many"][..],
+      &["--output-file", "out.log", "build.log"][..],
+      &["--queue-capacity", "0"][..],
+    ] {
+      let e = AppOptions::parse_from(args).unwrap_err();
+      assert_eq!(e.kind(), ErrorKind::Parsing, "{:?}: {}", args, e);
+    }
+  }
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+/// The time spent in each step of a single render loop iteration
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTiming {
+  /// Receiving the build snapshots from the prepare thread
+  pub pull: Duration,
+  /// Building the displayed lines from the latest snapshot
+  pub prepare: Duration,
+  /// Drawing the terminal UI
+  pub draw: Duration,
+  /// Polling and handling the terminal events
+  pub events: Duration,
+  /// The longest time spent by a received entry between its capture and its pull
+  /// by the prepare thread, not part of [`Self::total`]
+  pub queue_latency: Duration,
+}
+
+impl FrameTiming {
+  /// The whole iteration duration
+  pub fn total(&self) -> Duration {
+    self.pull + self.prepare + self.draw + self.events
+  }
+
+  /// Check if the frame took longer than [`FrameTimings::SLOW_FRAME`]
+  pub fn is_slow(&self) -> bool {
+    self.total() > FrameTimings::SLOW_FRAME
+  }
+}
+
+/// A fixed-size ring buffer of the latest [`FrameTiming`]s
+#[derive(Debug, Clone)]
+pub struct FrameTimings {
+  frames: Vec<FrameTiming>,
+  capacity: usize,
+  /// The slot written by the next [`Self::push`]
+  head: usize,
+}
+
+impl Default for FrameTimings {
+  fn default() -> Self {
+    Self::new(Self::CAPACITY)
+  }
+}
+
+impl FrameTimings {
+  /// The default number of recorded frames
+  pub const CAPACITY: usize = 120;
+
+  /// Frames lasting longer than this are highlighted
+  pub const SLOW_FRAME: Duration = Duration::from_millis(50);
+
+  /// Construct a collector keeping the last `capacity` frames
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      frames: Vec::with_capacity(capacity),
+      capacity: capacity.max(1),
+      head: 0,
+    }
+  }
+
+  /// Record a frame, overwriting the oldest one when full
+  pub fn push(&mut self, frame: FrameTiming) {
+    if self.frames.len() < self.capacity {
+      self.frames.push(frame);
+    } else {
+      self.frames[self.head] = frame;
+    }
+    self.head = (self.head + 1) % self.capacity;
+  }
+
+  /// Retrieve the number of recorded frames
+  pub fn len(&self) -> usize {
+    self.frames.len()
+  }
+
+  /// Check if no frame was recorded yet
+  #[cfg(test)]
+  pub fn is_empty(&self) -> bool {
+    self.frames.is_empty()
+  }
+
+  /// Retrieve the maximum number of recorded frames
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  /// Retrieve the latest frame
+  pub fn last(&self) -> Option<&FrameTiming> {
+    if self.frames.is_empty() {
+      return None;
+    }
+    self
+      .frames
+      .get((self.head + self.capacity - 1) % self.capacity)
+  }
+
+  /// Iterate over the recorded frames, from the oldest to the latest
+  pub fn iter(&self) -> impl Iterator<Item = &FrameTiming> {
+    let split = match self.frames.len() < self.capacity {
+      true => 0,
+      false => self.head,
+    };
+    self.frames[split..]
+      .iter()
+      .chain(self.frames[..split].iter())
+  }
+
+  /// Retrieve the longest queue latency of the recorded frames
+  pub fn max_queue_latency(&self) -> Duration {
+    self
+      .frames
+      .iter()
+      .map(|frame| frame.queue_latency)
+      .max()
+      .unwrap_or_default()
+  }
+
+  /// Count the frames lasting longer than [`Self::SLOW_FRAME`]
+  pub fn num_slow(&self) -> usize {
+    self.frames.iter().filter(|frame| frame.is_slow()).count()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::{FrameTiming, FrameTimings};
+
+  fn frame(draw_ms: u64) -> FrameTiming {
+    FrameTiming {
+      draw: Duration::from_millis(draw_ms),
+      ..Default::default()
+    }
+  }
+
+  fn draws(timings: &FrameTimings) -> Vec<u128> {
+    timings
+      .iter()
+      .map(|frame| frame.draw.as_millis())
+      .collect::<Vec<_>>()
+  }
+
+  #[test]
+  fn partially_filled() {
+    let mut timings = FrameTimings::new(4);
+    assert!(timings.is_empty());
+    assert_eq!(timings.last(), None);
+    timings.push(frame(1));
+    timings.push(frame(2));
+    assert_eq!(timings.len(), 2);
+    assert_eq!(timings.last(), Some(&frame(2)));
+    assert_eq!(draws(&timings), vec![1, 2]);
+  }
+
+  #[test]
+  fn wrap_around() {
+    let mut timings = FrameTimings::new(3);
+    for draw in 1..=3 {
+      timings.push(frame(draw));
+    }
+    assert_eq!(draws(&timings), vec![1, 2, 3]);
+    assert_eq!(timings.last(), Some(&frame(3)));
+    timings.push(frame(4));
+    assert_eq!(timings.len(), 3);
+    assert_eq!(timings.last(), Some(&frame(4)));
+    assert_eq!(draws(&timings), vec![2, 3, 4]);
+    for draw in 5..=8 {
+      timings.push(frame(draw));
+    }
+    assert_eq!(draws(&timings), vec![6, 7, 8]);
+  }
+
+  #[test]
+  fn slow_frames() {
+    let mut timings = FrameTimings::new(3);
+    timings.push(frame(60));
+    timings.push(frame(10));
+    assert_eq!(timings.num_slow(), 1);
+    timings.push(FrameTiming {
+      pull: Duration::from_millis(30),
+      events: Duration::from_millis(30),
+      ..Default::default()
+    });
+    assert_eq!(timings.num_slow(), 2);
+    // the 60ms frame gets overwritten
+    timings.push(frame(0));
+    assert_eq!(timings.num_slow(), 1);
+  }
+
+  #[test]
+  fn queue_latency() {
+    let mut timings = FrameTimings::new(2);
+    assert_eq!(timings.max_queue_latency(), Duration::ZERO);
+    for latency in [300, 20, 10] {
+      timings.push(FrameTiming {
+        queue_latency: Duration::from_millis(latency),
+        ..Default::default()
+      });
+    }
+    // the 300ms latency got overwritten
+    assert_eq!(timings.max_queue_latency(), Duration::from_millis(20));
+    assert!(!timings.last().unwrap().is_slow());
+  }
+}
@@ -0,0 +1,270 @@
+use std::{
+  sync::mpsc::{Receiver, Sender, TryRecvError},
+  thread::sleep,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  BuildEntry, BuildEvent, BuildOutput, BuildSnapshot, Debug, IpcServer, MarkerSelection, Rule,
+};
+
+use super::{AppOptions, PrepareScheduler};
+
+/// The commands sent by the [`super::Renderer`] to the [`Preparer`]
+pub enum PrepareCommand {
+  /// Mirror the renderer's selection, the first error only gets selected when there is none
+  Select(Option<MarkerSelection>),
+  /// Replace the [`Rule`], all entries get prepared again
+  SetRule(Rule),
+  /// Start over with the output of a new build
+  Reset(Receiver<BuildEntry>),
+  /// Send a snapshot once every entry received so far is prepared
+  Flush,
+  /// Switch the compact gutter, all entries get rendered again
+  SetInlineMarkers(bool),
+}
+
+/// The preparation thread: it owns the [`BuildOutput`], prepares the build entries
+/// as they arrive and sends a [`BuildSnapshot`] to the renderer after each prepare.
+///
+/// The [`PrepareScheduler`] groups the lines of a trickle and spreads a burst over several prepares
+pub struct Preparer {
+  options: AppOptions,
+  build_output: Receiver<BuildEntry>,
+  tx_build_events: Sender<BuildEvent>,
+  commands: Receiver<PrepareCommand>,
+  snapshots: Sender<BuildSnapshot>,
+  ipc: IpcServer,
+}
+
+impl Preparer {
+  /// The time to wait for new entries when there was nothing to prepare
+  pub const IDLE_DELAY: Duration = Duration::from_millis(5);
+
+  pub fn new(
+    options: AppOptions,
+    build_output: Receiver<BuildEntry>,
+    tx_build_events: Sender<BuildEvent>,
+    commands: Receiver<PrepareCommand>,
+    snapshots: Sender<BuildSnapshot>,
+  ) -> Self {
+    Self {
+      options,
+      build_output,
+      tx_build_events,
+      commands,
+      snapshots,
+      ipc: Default::default(),
+    }
+  }
+
+  /// Stream the detected markers to the clients of an [`IpcServer`]
+  pub fn with_ipc(mut self, ipc: IpcServer) -> Self {
+    self.ipc = ipc;
+    self
+  }
+
+  /// Construct the [`BuildOutput`] matching the options
+  pub fn new_build_output(
+    options: &AppOptions,
+    tx_build_events: &Sender<BuildEvent>,
+    ipc: &IpcServer,
+  ) -> BuildOutput<'static> {
+    BuildOutput::default()
+      .with_noise_removed(false)
+      .with_inline_markers(options.inline_markers)
+      .with_ansi_colors(options.respect_cargo_colors)
+      .with_marker_budget(options.marker_budget)
+      .with_tab_width(options.tab_width)
+      .with_max_line_length(options.max_line_length)
+      .with_build_events(tx_build_events.clone())
+      .with_ipc(ipc.clone())
+  }
+
+  /// The preparation loop, stops when the renderer hangs up
+  pub fn run(mut self) -> crate::Result<()> {
+    Debug::log("prepare thread started");
+    let mut build = Self::new_build_output(&self.options, &self.tx_build_events, &self.ipc);
+    let mut generation = 0;
+    let mut inline_markers = self.options.inline_markers;
+    let mut scheduler = PrepareScheduler::from_options(&self.options);
+    loop {
+      let mut changed = false;
+      let mut flush = false;
+      loop {
+        match self.commands.try_recv() {
+          Ok(PrepareCommand::Select(selection)) => {
+            build.markers_mut().set_selection(selection);
+          }
+          Ok(PrepareCommand::SetRule(rule)) => {
+            build.set_rule(rule);
+            generation += 1;
+            changed = true;
+          }
+          Ok(PrepareCommand::Reset(build_output)) => {
+            self.build_output = build_output;
+            build = Self::new_build_output(&self.options, &self.tx_build_events, &self.ipc);
+            build.set_inline_markers(inline_markers);
+            generation += 1;
+            changed = true;
+          }
+          Ok(PrepareCommand::Flush) => flush = true,
+          Ok(PrepareCommand::SetInlineMarkers(v)) => {
+            inline_markers = v;
+            build.set_inline_markers(v);
+            changed = true;
+          }
+          Err(TryRecvError::Empty) => break,
+          Err(TryRecvError::Disconnected) => {
+            Debug::log("prepare thread stopped");
+            return Ok(());
+          }
+        }
+      }
+      let received = build.num_entries();
+      // past the capacity, the entries wait in the queue and the build waits on them.
+      // A flush takes them all, the build already sent its last ones
+      match flush {
+        true => build.pull(&self.build_output),
+        false => build.pull_up_to(
+          &self.build_output,
+          self
+            .options
+            .queue_capacity
+            .saturating_sub(build.num_unprepared()),
+        ),
+      }
+      let now = Instant::now();
+      scheduler.arrived(build.num_entries() - received, now);
+      // a flush waits for every entry, otherwise the scheduler picks the batch
+      let limit = match flush {
+        true => Some(None),
+        false => scheduler.plan(build.num_unprepared(), now).map(Some),
+      };
+      if let Some(limit) = limit {
+        build.set_prepare_limit(limit);
+        if build.prepare() {
+          changed = true;
+        }
+      }
+      if changed || flush {
+        let snapshot = build
+          .snapshot()
+          .with_generation(generation)
+          .with_flushed(flush);
+        if self.snapshots.send(snapshot).is_err() {
+          Debug::log("prepare thread stopped");
+          return Ok(());
+        }
+      } else {
+        sleep(Self::IDLE_DELAY);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::mpsc::{channel, sync_channel},
+    thread::spawn,
+    time::{Duration, Instant},
+  };
+
+  use crate::{AppOptions, BuildEntry, BuildEvent, BuildSnapshot, BuildTagKind, Markers, Origin};
+
+  use super::{PrepareCommand, Preparer};
+
+  const NUM_BLOCKS: usize = 400;
+
+  fn expected_message(entry_id: usize) -> String {
+    match entry_id % 2 {
+      0 => format!("error: failure #{}", entry_id / 2),
+      _ => format!(" --> src/main.rs:{}:1", entry_id / 2 + 1),
+    }
+  }
+
+  /// Check that a snapshot is consistent with itself
+  fn assert_consistent(snapshot: &BuildSnapshot) {
+    assert!(snapshot.len() <= snapshot.num_entries());
+    assert_eq!(snapshot.display(&Markers::default()).len(), snapshot.len());
+    let mut num_errors = 0;
+    for (entry_id, entry) in snapshot.entries().enumerate() {
+      assert_eq!(entry.message(), &expected_message(entry_id));
+      if entry.tag(BuildTagKind::Error).is_some() {
+        num_errors += 1;
+      }
+    }
+    assert_eq!(snapshot.num_errors(), num_errors);
+    assert_eq!(snapshot.markers().tags().len(), num_errors);
+    for (entry_id, _kind) in snapshot.markers().tags() {
+      assert!(entry_id.0 < snapshot.len());
+    }
+  }
+
+  #[test]
+  fn snapshots_while_preparing() {
+    let (tx_entries, rx_entries) = sync_channel::<BuildEntry>(16);
+    let (tx_events, _rx_events) = channel::<BuildEvent>();
+    let (tx_commands, rx_commands) = channel::<PrepareCommand>();
+    let (tx_snapshots, rx_snapshots) = channel::<BuildSnapshot>();
+    let preparer = spawn(move || {
+      Preparer::new(
+        AppOptions::default(),
+        rx_entries,
+        tx_events,
+        rx_commands,
+        tx_snapshots,
+      )
+      .run()
+    });
+    let mut producer = Some(spawn(move || {
+      for entry_id in 0..NUM_BLOCKS * 2 {
+        let entry = BuildEntry::new(expected_message(entry_id), Origin::Stdout);
+        if tx_entries.send(entry).is_err() {
+          break;
+        }
+      }
+    }));
+    // consume the snapshots while new entries keep getting prepared
+    let mut num_snapshots = 0;
+    let mut previous: Option<BuildSnapshot> = None;
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let mut flushed = false;
+    while Instant::now() < deadline {
+      if producer
+        .as_ref()
+        .is_some_and(|producer| producer.is_finished())
+      {
+        let _ = producer.take().unwrap().join();
+        tx_commands.send(PrepareCommand::Flush).unwrap();
+        flushed = true;
+      }
+      let Ok(snapshot) = rx_snapshots.recv_timeout(Duration::from_millis(100)) else {
+        continue;
+      };
+      num_snapshots += 1;
+      assert_consistent(&snapshot);
+      // the previous snapshot must not be altered by the later prepares
+      if let Some(previous) = previous.as_ref() {
+        assert_consistent(previous);
+        assert!(previous.len() <= snapshot.len());
+      }
+      if snapshot.is_flushed() {
+        assert_eq!(snapshot.len(), NUM_BLOCKS * 2);
+        assert_eq!(snapshot.num_errors(), NUM_BLOCKS);
+        assert!(snapshot
+          .entries()
+          .skip(1)
+          .step_by(2)
+          .all(|entry| entry.locations().next().is_some()));
+        break;
+      }
+      previous = Some(snapshot);
+    }
+    assert!(flushed, "the producer did not finish in time");
+    assert!(num_snapshots > 0);
+    drop(tx_commands);
+    let _ = preparer.join();
+  }
+}
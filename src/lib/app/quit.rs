@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+/// A transition of the [`QuitState`], handled by the renderer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuitStep {
+  /// Ask for a confirmation, the build is still running
+  Prompt,
+  /// The quit was dismissed, the build keeps running
+  Stay,
+  /// Confirmed: kill the build in flight, then quit
+  Cancel,
+}
+
+/// The two-phase quit: `q` quits at once unless a build is running,
+/// which asks to press `q` again within [`QuitState::TIMEOUT`]
+#[derive(Debug, Clone, Default)]
+pub struct QuitState {
+  /// When the confirmation was asked
+  pending: Option<Instant>,
+  step: Option<QuitStep>,
+}
+
+impl QuitState {
+  /// The confirmation is forgotten after this delay
+  pub const TIMEOUT: Duration = Duration::from_secs(3);
+
+  /// Handle `q` pressed at `now`, while a build is `building` or not.
+  ///
+  /// Returns true if the app quits
+  pub fn press(&mut self, building: bool, now: Instant) -> bool {
+    self.tick(now);
+    if !building {
+      self.pending = None;
+      return true;
+    }
+    if self.pending.take().is_some() {
+      self.step = Some(QuitStep::Cancel);
+      return true;
+    }
+    self.pending = Some(now);
+    self.step = Some(QuitStep::Prompt);
+    false
+  }
+
+  /// Dismiss the pending confirmation, e.g. `Esc`.
+  ///
+  /// Returns false if there was none
+  pub fn dismiss(&mut self) -> bool {
+    if self.pending.take().is_none() {
+      return false;
+    }
+    self.step = Some(QuitStep::Stay);
+    true
+  }
+
+  /// Forget the confirmation asked more than [`QuitState::TIMEOUT`] before `now`
+  pub fn tick(&mut self, now: Instant) {
+    if self
+      .pending
+      .is_some_and(|since| now.saturating_duration_since(since) >= Self::TIMEOUT)
+    {
+      self.pending = None;
+    }
+  }
+
+  /// Check if a confirmation is pending
+  #[cfg(test)]
+  pub fn is_pending(&self) -> bool {
+    self.pending.is_some()
+  }
+
+  /// Retrieve the last transition, once
+  pub fn take_step(&mut self) -> Option<QuitStep> {
+    self.step.take()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use super::{QuitState, QuitStep};
+
+  #[test]
+  fn quit_when_idle() {
+    let mut quit = QuitState::default();
+    assert!(quit.press(false, Instant::now()));
+    assert_eq!(quit.take_step(), None);
+    assert!(!quit.is_pending());
+  }
+
+  #[test]
+  fn confirm() {
+    let now = Instant::now();
+    let mut quit = QuitState::default();
+    assert!(!quit.press(true, now));
+    assert!(quit.is_pending());
+    assert_eq!(quit.take_step(), Some(QuitStep::Prompt));
+    assert_eq!(quit.take_step(), None);
+    assert!(quit.press(true, now + Duration::from_secs(1)));
+    assert_eq!(quit.take_step(), Some(QuitStep::Cancel));
+    assert!(!quit.is_pending());
+  }
+
+  #[test]
+  fn dismiss() {
+    let now = Instant::now();
+    let mut quit = QuitState::default();
+    assert!(!quit.dismiss());
+    assert!(!quit.press(true, now));
+    quit.take_step();
+    assert!(quit.dismiss());
+    assert_eq!(quit.take_step(), Some(QuitStep::Stay));
+    // asked again
+    assert!(!quit.press(true, now));
+    assert_eq!(quit.take_step(), Some(QuitStep::Prompt));
+  }
+
+  #[test]
+  fn timeout() {
+    let now = Instant::now();
+    let mut quit = QuitState::default();
+    assert!(!quit.press(true, now));
+    quit.tick(now + QuitState::TIMEOUT - Duration::from_millis(1));
+    assert!(quit.is_pending());
+    quit.tick(now + QuitState::TIMEOUT);
+    assert!(!quit.is_pending());
+    // back to the first phase
+    quit.take_step();
+    assert!(!quit.press(true, now + QuitState::TIMEOUT));
+    assert_eq!(quit.take_step(), Some(QuitStep::Prompt));
+    // pressed again too late, without a tick in between
+    assert!(!quit.press(true, now + 2 * QuitState::TIMEOUT));
+    assert_eq!(quit.take_step(), Some(QuitStep::Prompt));
+    // the build finished in the meantime
+    assert!(quit.press(false, now + 2 * QuitState::TIMEOUT));
+    assert!(!quit.is_pending());
+  }
+}
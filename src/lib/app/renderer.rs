@@ -1,100 +1,414 @@
 use std::{
   cell::RefCell,
-  io::{self, stdout},
+  collections::{BTreeSet, VecDeque},
+  io::{self, stdout, Write},
+  process::ExitStatus,
   rc::Rc,
   sync::mpsc::{channel, Receiver, Sender},
-  time::Duration,
+  thread::{sleep, spawn},
+  time::{Duration, Instant},
 };
 
+use chrono::Local;
 use ratatui::{
-  crossterm::{
-    event::{self, DisableMouseCapture, KeyCode, KeyEvent, KeyEventKind, MouseEventKind},
-    execute,
-  },
+  backend::Backend,
+  crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEventKind},
   layout::{Constraint, Layout, Rect},
   style::{Style, Stylize},
   text::{Line, Span},
   widgets::{Block, Clear, Paragraph, ScrollbarState, Widget},
-  DefaultTerminal,
+  DefaultTerminal, Terminal,
 };
 
 use crate::{
-  BuildEntry, BuildEvent, BuildOutput, BuildTagKind, Debug, HelpMenu, LogEntry, LogView,
-  MarkedBlock, MarkerSelection, Markers, SearchBar, SearchState, StatusBar, StatusMessage,
+  active_rule, active_rule_name, add_active_rule_marker, apply_suggestions, display_path, err,
+  format_count, full_paths, osc52, rule_provenance, rules, save_rules, score_rules,
+  set_active_rule, set_full_paths, set_title, supports_title, update_rule, window_title, AnsiLine,
+  ArtifactAction, ArtifactList, ArtifactsState, BlockFingerprint, BookmarkList, BookmarksState,
+  BuildEvent, BuildSnapshot, BuildSummary, BuildTagKind, CommandRunner, CursorState, Debug,
+  DeclaredMarker, Direction, DisplayMap, EntryId, ErrorKind, FilePreview, FoldMap, FollowPolicy,
+  FrameTiming, FrameTimings, HelpMenu, HelpState, History, HistoryList, HistoryState, Hyperlink,
+  Hyperlinks, InfoPopup, InfoState, InputPrompt, InputPromptState, IntakeStats, JumpDialog,
+  JumpState, LogView, LongLinePopup, LongLineState, MarkerSelection, MarkerToggles, Markers,
+  Origin, PerfOverlay, Pin, PinList, PinnedArea, PinsState, PreviewState, ProcessRunner,
+  ProgressReporter, Provenance, RegexTester, Rule, RuleCandidate, RuleEditor, RuleEditorAction,
+  RuleEditorState, RulePicker, RulePickerState, SearchBar, SearchQuery, SearchState, SplitLogView,
+  SplitState, StatusBar, StatusHistoryAction, StatusHistoryList, StatusHistoryState, StatusMessage,
+  StatusPriority, StatusRecord, TermCapabilities, TesterAction, TesterState, Timeline,
+  TimelineState, TogglesState, Viewport,
 };
 
-use super::AppOptions;
-
-/// The key bindings to be displayed on the help menu
-const HELP_MENU: &'static [(&'static str, &'static str)] = &[
-  ("k", "previous output row"),
-  ("j", "next output row"),
-  ("PageUp", "previous output row"),
-  ("PageDn", "next output row"),
-  ("Home", "go to the first output row"),
-  ("End", "go to the last output row"),
-  ("Up", "go to the previous marker (error/warning/note)"),
-  ("Down", "go to the next marker (error/warning/note)"),
-  ("/", "enter search mode"),
-  ("Esc", "exit search mode"),
-  ("e", "show first error"),
-  ("w", "show first warning"),
-  ("n", "show first note"),
-  ("f", "filter entries: show only errors"),
+use super::{
+  AppOptions, BuildRequest, Builder, IpcEvent, IpcServer, PrepareCommand, QuitState, QuitStep,
+  RunContext, Scanner, SessionInfo,
+};
+
+/// The minimum delay between two updates of the status bar counts
+const STATUS_COUNTS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The number of output lines a build without markers needs to suggest another rule
+const RULE_SUGGESTION_MIN_LINES: usize = 50;
+
+/// The key bindings to be displayed on the help menu, grouped by sections
+pub(crate) const HELP_SECTIONS: &[(&str, &[(&str, &str)])] = &[
+  (
+    "Navigation",
+    &[
+      ("k", "previous output row"),
+      ("j", "next output row"),
+      (
+        "v",
+        "show/hide the line cursor moved by j/k, the line actions start from it",
+      ),
+      ("PageUp", "previous output row"),
+      ("PageDn", "next output row"),
+      ("Home", "go to the first output row"),
+      ("End", "go to the last output row"),
+      ("Up", "go to the previous block (error/warning)"),
+      ("Down", "go to the next block (error/warning)"),
+      (
+        "ge",
+        "quick-jump to a numbered error (gw: warning, gn: note)",
+      ),
+      (":", "quick-jump to a numbered error"),
+      (
+        "m",
+        "bookmark the current line: m then a letter, ' then the letter jumps back",
+      ),
+      ("B", "list the bookmarks"),
+      (
+        "!",
+        "pin/unpin the selected block above the log (p: list the pins)",
+      ),
+      (
+        "Tab",
+        "switch focus between log and preview, or between the split panes",
+      ),
+    ],
+  ),
+  (
+    "Markers",
+    &[
+      ("e", "show first error"),
+      ("w", "show first warning"),
+      ("n", "show first note"),
+      ("K", "enable/disable marker kinds"),
+      (
+        "R",
+        "switch the active rule, scored against the build output (E: reorder/disable its markers)",
+      ),
+      ("F2", "test a marker regex and add it to the active rule"),
+    ],
+  ),
+  (
+    "Search",
+    &[
+      ("/", "search forward from the selection"),
+      ("?", "search backward from the selection"),
+      ("n", "repeat the last search, until dismissed with Esc"),
+      ("N", "repeat the last search backward"),
+      ("Esc", "exit search mode, or dismiss the last search"),
+    ],
+  ),
+  (
+    "View",
+    &[
+      ("f", "filter entries: show only errors"),
+      ("b", "filter entries: show only the build scripts output"),
+      (
+        "F",
+        "follow the output, or resume after pausing on the first error",
+      ),
+      ("P", "preview the source location of the selected block"),
+      (
+        "X",
+        "apply the machine-applicable fix of the selected block to its file (--message-format=json)",
+      ),
+      (
+        "y",
+        "copy the attribute allowing the lint of the selected warning, e.g. #[allow(dead_code)]",
+      ),
+      ("|", "show stdout and stderr side by side"),
+      ("A", "show full/shortened paths"),
+      (
+        "c",
+        "switch between the compact gutter and the full markers",
+      ),
+      (
+        "t",
+        "show the timeline, Left/Right go back in time, Esc returns to the live view",
+      ),
+      (
+        "x",
+        "show the whole selected line when cut, otherwise expand/fold the macro backtrace of the selected block",
+      ),
+      ("u", "group/ungroup the identical consecutive lines"),
+      (
+        "D",
+        "fold/unfold the diagnostics repeated by another target, e.g. the tests of a lib",
+      ),
+      (
+        "Enter",
+        "expand/group the repeated lines of the selected block",
+      ),
+      (
+        "#",
+        "show/hide the column ruler and the indentation guides of the snippets",
+      ),
+      (
+        "z/F11",
+        "zen mode: hide the command and shortcuts rows and the border of the log",
+      ),
+    ],
+  ),
+  (
+    "Build",
+    &[
+      (
+        "r",
+        "re-run the build (killing the one in flight), keeping the selected block",
+      ),
+      (
+        "I",
+        "type a line for the build's prompts, sent to its stdin (--interactive)",
+      ),
+      (
+        "a",
+        "list the produced artifacts, Enter copies the path, o reveals it",
+      ),
+      (
+        "H",
+        "list the last runs with their error and warning counts",
+      ),
+      (
+        "S",
+        "list the status messages and build events, d deletes one, s saves them to the run directory",
+      ),
+    ],
+  ),
+  (
+    "Misc",
+    &[
+      ("h", "show/hide this help, / filters it"),
+      (
+        "F1",
+        "show/hide the active rule, the config files, the debug log and the run directory",
+      ),
+      ("F10", "show/hide the frame timings overlay"),
+      (
+        "q",
+        "quit, press it again within 3s to stop the running build and quit (Esc: stay)",
+      ),
+    ],
+  ),
 ];
 
+/// Where the render loop reads the terminal events
+pub enum RenderInput {
+  /// The events of the terminal, until the user quits
+  Terminal,
+  /// The events of a headless run, see `testing::Harness`
+  #[cfg_attr(not(any(test, feature = "test-util")), allow(dead_code))]
+  Scripted(ScriptedInput),
+}
+
+/// The events and the stop condition of a headless render loop
+#[derive(Default)]
+pub struct ScriptedInput {
+  /// The events of the user, delivered once the build finished and its last entries
+  /// got prepared
+  pub events: VecDeque<Event>,
+  /// Stop after this number of frames
+  pub frames: Option<usize>,
+  /// Stop once the build finished, the events got handled and drawn
+  pub until_finished: bool,
+  /// Stop anyway past this duration
+  pub timeout: Duration,
+  /// The frames drawn since the build finished without pending event
+  pub(crate) idle_frames: usize,
+}
+
+impl RenderInput {
+  /// Wait up to `timeout` for the next event, the scripted ones wait for the build to be `settled`
+  fn next(&mut self, timeout: Duration, settled: bool) -> io::Result<Option<Event>> {
+    match self {
+      Self::Terminal => match event::poll(timeout)? {
+        true => event::read().map(Some),
+        false => Ok(None),
+      },
+      Self::Scripted(input) => match settled {
+        true if !input.events.is_empty() => Ok(input.events.pop_front()),
+        _ => {
+          sleep(timeout);
+          Ok(None)
+        }
+      },
+    }
+  }
+
+  /// Check if a headless loop is over after `frames` frames, see [`ScriptedInput`]
+  fn is_over(&mut self, frames: usize, settled: bool, started_at: Instant) -> bool {
+    match self {
+      Self::Terminal => false,
+      Self::Scripted(input) => {
+        if settled && input.events.is_empty() {
+          input.idle_frames += 1;
+        }
+        // the frame after the last event shows its effects
+        input.frames.is_some_and(|max| frames >= max)
+          || (input.until_finished && input.idle_frames >= 2)
+          || started_at.elapsed() >= input.timeout
+      }
+    }
+  }
+}
+
+/// The state of the render loop once stopped, see `testing::Harness`
+#[cfg_attr(not(any(test, feature = "test-util")), allow(dead_code))]
+pub struct RenderState {
+  pub snapshot: BuildSnapshot,
+  pub markers: Markers,
+  pub status_bar: StatusBar,
+  pub scroll: usize,
+  pub exit_status: Option<ExitStatus>,
+  /// The number of frames drawn
+  pub frames: usize,
+}
+
+/// The widget, popup and toggle states changed by the keys
+#[derive(Default)]
+struct UiState {
+  help: HelpState,
+  search: Option<SearchState>,
+  /// Repeated with n/N until dismissed with Esc
+  last_search: Option<SearchQuery>,
+  input_prompt: InputPromptState,
+  filter: Option<BuildTagKind>,
+  preview: PreviewState,
+  toggles: TogglesState,
+  tester: TesterState,
+  jump: JumpState,
+  artifacts: ArtifactsState,
+  info: InfoState,
+  history: HistoryState,
+  status_history: StatusHistoryState,
+  long_line: LongLineState,
+  bookmarks: BookmarksState,
+  pins: PinsState,
+  rule_picker: RulePickerState,
+  rule_editor: RuleEditorState,
+  show_perf: bool,
+  show_ruler: bool,
+  /// Hides the command and shortcuts row, and the border of the log
+  zen: bool,
+  follow: FollowPolicy,
+  rerun: bool,
+  fix: bool,
+  copy_allow: bool,
+  split: SplitState,
+  timeline: TimelineState,
+  /// The gutter follows the terminal width until toggled with 'c'
+  compact_toggled: Option<bool>,
+  cursor: CursorState,
+  /// The folds expanded with 'x'
+  expanded_folds: BTreeSet<usize>,
+  fold_repeats: bool,
+  dedup_targets: bool,
+  /// 'q' asks for a confirmation while the build runs
+  quit: QuitState,
+}
+
+/// What the keys read from the render loop, and where they send their requests
+struct KeyContext<'a> {
+  user_quit: Sender<bool>,
+  viewport: Viewport,
+  snapshot: &'a BuildSnapshot,
+  display: &'a DisplayMap,
+  search_query: Sender<SearchQuery>,
+  status_records: &'a [StatusRecord],
+  tester_action: Sender<TesterAction>,
+  elapsed: Duration,
+  compact: bool,
+  building: bool,
+}
+
 pub struct Renderer {
   options: AppOptions,
   terminal: DefaultTerminal,
   user_quit: Sender<bool>,
-  build_output: Receiver<Vec<BuildEntry>>,
-  tx_build_events: Sender<BuildEvent>,
+  snapshots: Receiver<BuildSnapshot>,
+  commands: Sender<PrepareCommand>,
+  builds: Sender<BuildRequest>,
   build_events: Receiver<BuildEvent>,
+  summary: Sender<BuildSummary>,
+  ipc: IpcServer,
+  history: History,
+  session: SessionInfo,
 }
 
 impl Renderer {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     options: AppOptions,
     terminal: DefaultTerminal,
     user_quit: Sender<bool>,
-    build_output: Receiver<Vec<BuildEntry>>,
-    tx_build_events: Sender<BuildEvent>,
+    snapshots: Receiver<BuildSnapshot>,
+    commands: Sender<PrepareCommand>,
+    builds: Sender<BuildRequest>,
     build_events: Receiver<BuildEvent>,
+    summary: Sender<BuildSummary>,
   ) -> Self {
     Self {
       options,
       terminal,
       user_quit,
-      build_output,
-      tx_build_events,
+      snapshots,
+      commands,
+      builds,
       build_events,
+      summary,
+      ipc: Default::default(),
+      history: History::new(History::default_path()),
+      session: SessionInfo::default(),
     }
   }
 
-  /// The rendering thread, draws the terminal UI
-  pub fn run(self) {
+  /// Define the files of the session, shown by the info popup (`F1`)
+  pub fn with_session(mut self, session: SessionInfo) -> Self {
+    self.session = session;
+    self
+  }
+
+  /// Stream the build events and the selection changes to the clients of an [`IpcServer`]
+  pub fn with_ipc(mut self, ipc: IpcServer) -> Self {
+    self.ipc = ipc;
+    self
+  }
+
+  /// The rendering thread, draws the terminal UI until the user quits
+  pub fn run(mut self) -> crate::Result<()> {
     Debug::log("render thread started");
     let app_result = Self::render_loop(
       self.options,
-      self.terminal,
+      &mut self.terminal,
+      RenderInput::Terminal,
       self.user_quit,
-      self.build_output,
-      self.tx_build_events,
+      self.snapshots,
+      self.commands,
+      self.builds,
       self.build_events,
+      self.summary,
+      self.ipc,
+      self.history,
+      self.session,
     );
-    Self::restore_terminal();
-    if let Err(e) = app_result {
-      Debug::log(format!("failed to run app, {}", e));
-    }
     Debug::log("render thread stopped");
+    app_result
+      .map(|_| ())
+      .map_err(|e| err!(ErrorKind::IO, "failed to render the UI, {}", e))
   }
 
-  pub fn restore_terminal() {
-    ratatui::restore();
-    let _ = execute!(stdout(), DisableMouseCapture);
-  }
-
-  fn set_cursor_visible(terminal: &mut DefaultTerminal, v: bool) {
+  fn set_cursor_visible<B: Backend>(terminal: &mut Terminal<B>, v: bool) {
     if v {
       if let Err(e) = terminal.show_cursor() {
         crate::dbg!("failed to show cursor: {}", e);
@@ -109,57 +423,448 @@ impl Renderer {
     }
   }
 
-  /// The rendering loop
-  fn render_loop(
+  /// The rendering loop, headless with [`RenderInput::Scripted`]
+  #[allow(clippy::too_many_arguments)]
+  pub(crate) fn render_loop<B: Backend>(
     options: AppOptions,
-    mut terminal: DefaultTerminal,
+    terminal: &mut Terminal<B>,
+    mut input: RenderInput,
     user_quit: Sender<bool>,
-    build_output: Receiver<Vec<BuildEntry>>,
-    tx_build_events: Sender<BuildEvent>,
+    snapshots: Receiver<BuildSnapshot>,
+    commands: Sender<PrepareCommand>,
+    builds: Sender<BuildRequest>,
     build_events: Receiver<BuildEvent>,
-  ) -> io::Result<()> {
-    let mut build = BuildOutput::default()
-      .with_noise_removed(false)
-      .with_build_events(tx_build_events.clone());
+    summary: Sender<BuildSummary>,
+    ipc: IpcServer,
+    history: History,
+    session: SessionInfo,
+  ) -> io::Result<RenderState> {
+    let loop_started_at = Instant::now();
+    let mut num_frames = 0;
+    let mut snapshot = BuildSnapshot::default();
+    let mut generation = 0;
+    let mut flushed = false;
+    let mut sent_selection: Option<MarkerSelection> = None;
     let mut vertical_scroll_state = ScrollbarState::default();
     let mut vertical_scroll: usize = 0;
-    let mut help_vertical_scroll_state = ScrollbarState::default();
-    let mut help_vertical_scroll: usize = 0;
     let [mut command_area, mut log_area] = [Rect::default(), Rect::default()];
+    // the log rows, known once the log area is drawn
+    let mut viewport = Viewport::default().with_scrolloff(options.scrolloff);
     let mut main_pane = Rect::default();
     let mut shortcuts_area = Rect::default();
     let mut top_area = Rect::default();
     let mut bottom_area = Rect::default();
     let mut search_area = Rect::default();
     let mut status_area = Rect::default();
-    let mut status_entry: Option<StatusMessage> = None;
-    let mut build_status_entry: Option<BuildEvent> = None;
-    let mut show_help = false;
+    let mut preview_area = Rect::default();
+    let mut timeline_area = Rect::default();
+    let mut pins_area = Rect::default();
+    // the rows displayed by the last frame
+    let mut display = DisplayMap::default();
+    let mut display_generation = generation;
+    // the gutter follows the terminal width until toggled with 'c'
+    let mut compact = options.inline_markers;
+    let mut started_at: Option<Instant> = None;
+    let mut finished_at: Option<Instant> = None;
+    // the runs get recorded once their last entries are prepared
+    let mut history_pending = false;
+    // the rule gets checked once, against the first finished build
+    let mut rule_checked = false;
+    // a headless loop writes nothing to stdout
+    let headless = matches!(input, RenderInput::Scripted(_));
+    let mut hyperlinks = match headless {
+      true => Hyperlinks::new(false),
+      false => Hyperlinks::detect(options.hyperlinks),
+    };
+    if let Some(format) = options.hyperlink_format.as_ref() {
+      hyperlinks = hyperlinks.with_format(format);
+    }
+    let mut links: Vec<Hyperlink> = vec![];
+    let (tx_tester_action, rx_tester_action) = channel::<TesterAction>();
+    let mut status_entry: Option<StatusMessage> = match options.stripped_color_args.is_empty() {
+      true => None,
+      false => Some(StatusMessage::new([
+        (" ! ".to_string(), Style::default().bold().yellow()),
+        (
+          format!(
+            "ignored '{}', use --respect-cargo-colors to keep cargo's colors",
+            options.stripped_color_args.join(" ")
+          ),
+          Style::default(),
+        ),
+      ])),
+    };
+    // the streams of the build read so far, shown by the frame timings overlay
+    let mut intake: Vec<IntakeStats> = vec![];
+    // the events received since the last frame, applied to the status bar in order
+    let mut build_status_events: Vec<BuildEvent> = vec![];
+    let mut counts_updated_at = Instant::now();
+    let mut counts_settled = false;
+    let mut exit_status = None;
+    let mut awaiting_start = false;
+    let mut anchor: Option<BlockFingerprint> = None;
+    // the terminal title, written again when the state or the elapsed seconds change
+    let titled = !options.no_title && !headless && supports_title();
+    let title_name = match (&options.input, options.stdin) {
+      (Some(path), _) => display_path(path),
+      (None, true) => "stdin".to_string(),
+      (None, false) => options
+        .project_dir()
+        .and_then(|dir| Some(dir.file_name()?.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "cargo".to_string()),
+    };
+    let mut title = String::new();
+    // the taskbar or tab progress, written once a frame is drawn
+    let mut progress = ProgressReporter::new(match options.no_term_progress || headless {
+      true => TermCapabilities::default(),
+      false => TermCapabilities::detect(),
+    });
+    let mut pinned: Option<(EntryId, BuildTagKind)> = None;
+    let mut frame_timings = FrameTimings::default();
+    // the slow markers already reported in the status bar
+    let mut reported_slow_markers = 0;
     let mut markers = Markers::default();
     let _frame_area: Rect = terminal.get_frame().area();
     let status_bar = Rc::new(RefCell::new(StatusBar::default()));
-    let mut search_state: Option<SearchState> = None;
-    let (tx_search_query, rx_search_query) = channel::<String>();
-    let mut _last_search_result: Option<(MarkedBlock<'_>, MarkerSelection)> = None;
-    let mut filter: Option<BuildTagKind> = match options.show_only_errors {
-      true => {
-        crate::dbg!("Default filter is {:?}", BuildTagKind::Error);
-        Some(BuildTagKind::Error)
-      }
+    if options.pty_fallback() {
+      status_bar.borrow_mut().push_message(StatusMessage::new([
+        (" ! ".to_string(), Style::default().bold().yellow()),
+        (
+          "--pty is not supported on this platform, the build output is piped".to_string(),
+          Style::default(),
+        ),
+      ]));
+    }
+    // nothing piped yet, the user may have meant to run a build
+    let mut input_hint_at = match options.stdin {
+      true => Some(Instant::now() + Scanner::IDLE_HINT_DELAY),
       false => None,
     };
+    // the number of entries when the output last changed, and the one the prompt opened at
+    let mut last_output = (0, Instant::now());
+    let mut prompted_at: Option<usize> = None;
+    let (tx_search_query, rx_search_query) = channel::<SearchQuery>();
+    let mut ui = UiState {
+      input_prompt: InputPromptState::new(options.interactive && !options.reads_log()),
+      filter: match options.show_only_errors {
+        true => {
+          crate::dbg!("Default filter is {:?}", BuildTagKind::Error);
+          Some(BuildTagKind::Error)
+        }
+        false => None,
+      },
+      history: HistoryState::new(history.last(History::SHOWN)),
+      show_perf: options.perf_overlay,
+      zen: options.zen,
+      follow: FollowPolicy::new(options.follow_stop_on).with_following(options.follow),
+      split: SplitState::new(options.split_view),
+      cursor: CursorState::new(options.cursor),
+      fold_repeats: options.fold_repeats,
+      dedup_targets: options.dedup_targets,
+      ..UiState::default()
+    };
     let mut stop = false;
     crate::dbg!("Started rendering");
     while !stop {
-      build.pull(&build_output);
-      if build.prepare() {
-        markers.set_selection(build.markers_mut().selection().cloned());
-      }
-      *markers.tags_mut() = build.markers().tags().clone();
-      let mut search_selection = None;
-      if let Ok(query) = rx_search_query.try_recv() {
-        crate::dbg!("Searching for '{}'", query);
-        search_selection = if let Some((block, selection)) = build.search(&query) {
+      let mut frame_timing = FrameTiming::default();
+      let mut step_start = Instant::now();
+      while let Ok(new_snapshot) = snapshots.try_recv() {
+        // prepared before the last reset
+        if new_snapshot.generation() != generation {
+          continue;
+        }
+        frame_timing.queue_latency = frame_timing.queue_latency.max(new_snapshot.queue_latency());
+        if markers.selection().is_none() {
+          markers.set_selection(new_snapshot.markers().selection().cloned());
+        }
+        flushed |= new_snapshot.is_flushed();
+        if let Some(marker) = ui.follow.observe(new_snapshot.detected()) {
+          pinned = Some(marker);
+        }
+        snapshot = new_snapshot;
+      }
+      frame_timing.pull = step_start.elapsed();
+      reported_slow_markers = reported_slow_markers.min(snapshot.slow_markers().len());
+      for slow in &snapshot.slow_markers()[reported_slow_markers..] {
+        status_bar.borrow_mut().push_message(
+          StatusMessage::new([
+            (" ! ".to_string(), Style::default().bold().yellow()),
+            (slow.to_string(), Style::default()),
+          ])
+          .with_priority(StatusPriority::High),
+        );
+      }
+      reported_slow_markers = snapshot.slow_markers().len();
+      if let Some(at) = input_hint_at {
+        if snapshot.num_entries() > 0 {
+          input_hint_at = None;
+        } else if Instant::now() >= at {
+          input_hint_at = None;
+          status_bar.borrow_mut().push_message(StatusMessage::new([
+            (" ! ".to_string(), Style::default().bold().yellow()),
+            (Scanner::IDLE_HINT.to_string(), Style::default()),
+          ]));
+        }
+      }
+      if snapshot.num_entries() != last_output.0 {
+        last_output = (snapshot.num_entries(), Instant::now());
+      }
+      let waiting = last_output.0 > 0
+        && exit_status.is_none()
+        && prompted_at != Some(last_output.0)
+        && !ui.input_prompt.is_shown()
+        && ui.search.is_none()
+        && snapshot
+          .entry(EntryId(last_output.0 - 1))
+          .is_some_and(|entry| {
+            InputPrompt::is_waiting(&AnsiLine::strip(entry.message()), last_output.1.elapsed())
+          });
+      if waiting {
+        prompted_at = Some(last_output.0);
+        ui.input_prompt.open();
+      }
+      if let Some(line) = ui.input_prompt.take_line() {
+        crate::dbg!("Sending {} byte(s) to the build's stdin", line.len() + 1);
+        let _ = builds.send(BuildRequest::Input(line));
+        status_entry = Some(StatusMessage::new([(
+          "Sent the line to the build's stdin".to_string(),
+          Style::default(),
+        )]));
+      }
+      *markers.tags_mut() = snapshot.markers().tags().clone();
+      *markers.secondary_mut() = snapshot.markers().secondary().clone();
+      if let Some((entry_id, kind)) = pinned.take() {
+        crate::dbg!("Paused on the {} of entry #{}", kind, entry_id.0);
+        let marker_id = markers
+          .enabled()
+          .find(|(_marker_id, (id, _kind))| *id == entry_id)
+          .map(|(marker_id, _)| marker_id);
+        if let Some(marker_id) = marker_id {
+          markers.select(marker_id, None);
+        }
+        // the view stays where it is unless the marker scrolled out of it
+        vertical_scroll = vertical_scroll.min(ui.split.view(&display).row(entry_id.0));
+        vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
+        status_entry = Some(
+          StatusMessage::new([
+            (" ! ".to_string(), Style::default().bold().yellow()),
+            (
+              format!(
+                "paused on first {} — press F to resume",
+                kind.to_string().to_lowercase()
+              ),
+              Style::default(),
+            ),
+          ])
+          .with_priority(StatusPriority::High),
+        );
+      }
+      if ui.fix {
+        ui.fix = false;
+        status_entry = Some(Self::apply_fix(&markers, &snapshot));
+      }
+      if ui.copy_allow {
+        ui.copy_allow = false;
+        status_entry = Some(Self::copy_allow_attribute(&markers, &snapshot));
+      }
+      if ui.rerun {
+        ui.rerun = false;
+        if options.reads_log() {
+          status_entry = Some(
+            StatusMessage::new([
+              (" ✗ ".to_string(), Style::default().bold().red()),
+              (
+                "cannot re-run a build read from a log".to_string(),
+                Style::default(),
+              ),
+            ])
+            .with_priority(StatusPriority::High),
+          );
+        } else {
+          anchor = markers
+            .selected_entry()
+            .and_then(|entry_id| snapshot.block_at(entry_id))
+            .map(|block| BlockFingerprint::from_block(&block));
+          crate::dbg!("Re-running the build, anchored on {:?}", anchor);
+          ui.bookmarks.detach(|entry_id| {
+            let block = snapshot.block_at(entry_id)?;
+            Some((
+              BlockFingerprint::from_block(&block),
+              entry_id.0 - block.range().start,
+            ))
+          });
+          ui.pins
+            .detach(|entry_id| Some(BlockFingerprint::from_block(&snapshot.block_at(entry_id)?)));
+          let (tx_entries, rx_entries) = Builder::entries_channel(&options);
+          let _ = commands.send(PrepareCommand::Reset(rx_entries));
+          generation += 1;
+          flushed = false;
+          snapshot = BuildSnapshot::default();
+          let disabled = markers.disabled().clone();
+          markers = Markers::default();
+          *markers.disabled_mut() = disabled;
+          exit_status = None;
+          (started_at, finished_at) = (None, None);
+          history_pending = false;
+          ui.timeline = TimelineState::default();
+          ui.expanded_folds.clear();
+          ui.follow.reset();
+          vertical_scroll = 0;
+          vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
+          // the build in flight gets killed, its last events arrive before the new start
+          awaiting_start = true;
+          let _ = builds.send(BuildRequest::Run(tx_entries));
+        }
+      }
+      if anchor.is_some() && exit_status.is_some() && flushed {
+        let anchor = anchor.take().unwrap();
+        let blocks = snapshot.blocks();
+        let fingerprints = blocks
+          .iter()
+          .map(BlockFingerprint::from_block)
+          .collect::<Vec<_>>();
+        let selection = match anchor.best_match(&fingerprints) {
+          Some(id) => Some(MarkerSelection::new(
+            blocks[id].marker_id(),
+            EntryId(blocks[id].range().start),
+            None,
+          )),
+          None => Self::find_first_marker(&markers, BuildTagKind::Error),
+        };
+        crate::dbg!("Restoring selection {:?}", selection);
+        if let Some(selection) = selection {
+          Self::select_marker(
+            &selection,
+            &mut markers,
+            &mut vertical_scroll,
+            &mut vertical_scroll_state,
+            viewport,
+            &ui.split.view(&display),
+          );
+        }
+      }
+      if ui.bookmarks.is_detached() && exit_status.is_some() && flushed {
+        ui.bookmarks.reattach(&snapshot.blocks());
+        crate::dbg!("Restored {} bookmark(s)", ui.bookmarks.marks().len());
+      }
+      if ui.pins.is_detached() && exit_status.is_some() && flushed {
+        ui.pins.reattach(&snapshot.blocks());
+        crate::dbg!("Restored {} pin(s)", ui.pins.pins().len());
+      }
+      if !rule_checked && exit_status.is_some() && flushed {
+        rule_checked = true;
+        let candidates = Self::score_rules(&snapshot);
+        if let Some(reason) = Self::suggest_rule(&snapshot, &candidates) {
+          crate::dbg!("{}", reason);
+          status_entry = Some(
+            StatusMessage::new([
+              (" ! ".to_string(), Style::default().bold().yellow()),
+              (reason.clone(), Style::default()),
+            ])
+            .with_priority(StatusPriority::High),
+          );
+          ui.rule_picker
+            .open(candidates, Self::rule_name(snapshot.rule()), Some(reason));
+        }
+      }
+      if history_pending && exit_status.is_some() && flushed {
+        history_pending = false;
+        let summary = BuildSummary::from_snapshot(&snapshot, exit_status)
+          .with_duration(Self::duration(started_at, finished_at, &snapshot));
+        ui.history.push_current(history.record(&options, &summary));
+      }
+      if let Some(name) = ui.rule_picker.take_pick() {
+        crate::dbg!("Switching to rule {:?}", name);
+        set_active_rule(name.to_lowercase());
+        let _ = commands.send(PrepareCommand::SetRule(active_rule()));
+        generation += 1;
+        markers.set_selection(None);
+        status_entry = Some(StatusMessage::new([(
+          format!("Switched to rule '{}'", name),
+          Style::default(),
+        )]));
+      }
+      if let Some(name) = ui.rule_picker.take_edit() {
+        let rule = rules().into_iter().find(|rule| {
+          rule
+            .aliases
+            .iter()
+            .any(|alias| alias.eq_ignore_ascii_case(&name))
+        });
+        if let Some(rule) = rule {
+          ui.rule_editor.open(rule);
+        }
+      }
+      if let Some(action) = ui.rule_editor.take_action() {
+        status_entry = Some(Self::apply_rule_editor_action(
+          action,
+          &snapshot,
+          &commands,
+          &mut markers,
+          &mut generation,
+        ));
+      }
+      if let Some(outcome) = ui.pins.take_outcome() {
+        status_entry = Some(match outcome {
+          Ok(true) => StatusMessage::new([(
+            "Pinned the block above the log, p lists the pins".to_string(),
+            Style::default(),
+          )]),
+          Ok(false) => StatusMessage::new([("Unpinned the block".to_string(), Style::default())]),
+          Err(max) => StatusMessage::new([
+            (" ✗ ".to_string(), Style::default().bold().red()),
+            (
+              format!("at most {} pins, unpin one first (p lists them)", max),
+              Style::default(),
+            ),
+          ])
+          .with_priority(StatusPriority::High),
+        });
+      }
+      if let Some(action) = ui.status_history.take_action() {
+        if let Some(message) = Self::apply_status_history_action(action, &status_bar, &session) {
+          status_entry = Some(message);
+        }
+      }
+      if let Some(action) = ui.artifacts.take_action() {
+        status_entry = Some(Self::apply_artifact_action(action, &snapshot));
+      }
+      if let Ok(action) = rx_tester_action.try_recv() {
+        status_entry = Some(Self::apply_tester_action(
+          action,
+          &options,
+          &snapshot,
+          &commands,
+          &mut markers,
+          &mut generation,
+        ));
+      }
+      if let Ok(search) = rx_search_query.try_recv() {
+        crate::dbg!("Searching for {:?}", search);
+        let query = search.query.clone();
+        // from the entry after the cursor or the selection in the direction of the search,
+        // or the top row
+        let view = ui.split.view(&display);
+        let cursor_entry = ui
+          .cursor
+          .row(&view, vertical_scroll, viewport)
+          .and_then(|row| view.entry_at(row))
+          .map(EntryId);
+        let start = match cursor_entry.or(markers.selected_entry()) {
+          Some(EntryId(entry_id)) => match search.direction {
+            Direction::Forward => entry_id + 1,
+            Direction::Backward => entry_id.checked_sub(1).unwrap_or(usize::MAX),
+          },
+          None => view.entry_at(vertical_scroll).unwrap_or(0),
+        };
+        let found = match query.is_empty() {
+          true => None,
+          false => snapshot.search_from(start, &query, search.direction),
+        };
+        ui.last_search = Some(search.clone()).filter(|_| !query.is_empty());
+        if query.is_empty() {
+          ui.search = None;
+        } else if let Some((block, selection)) = found {
           crate::dbg!(
             "Found in block #{} -> {:?}\n{}",
             block.marker_id(),
@@ -171,58 +876,301 @@ impl Renderer {
               .collect::<Vec<_>>()
               .join("\n")
           );
-          _last_search_result = Some((block.clone(), selection.clone()));
-          search_state = None;
+          ui.search = None;
+          let wrapped = match search.direction {
+            Direction::Forward => selection.entry_id.0 < start,
+            Direction::Backward => start == usize::MAX || selection.entry_id.0 > start,
+          };
           status_entry = Some(StatusMessage::new([(
-            format!("Show search result {}/{}", block.marker_id(), markers.len()),
+            format!(
+              "Show search result {}/{}{}",
+              block.marker_id(),
+              markers.len(),
+              match wrapped {
+                true => ", search wrapped",
+                false => "",
+              }
+            ),
             Style::default(),
           )]));
+          let entry_id = selection.entry_id.0;
+          // a match past the display cap only shows in the whole line
+          let cut = snapshot
+            .line(selection.entry_id)
+            .and_then(|line| line.raw_layout().truncated);
+          if let Some(region) = selection
+            .region
+            .clone()
+            .filter(|region| cut.is_some_and(|cut| region.end > cut))
+          {
+            ui.long_line.open(selection.entry_id, Some(region));
+          }
           markers.set_selection(Some(selection));
-          markers.selection()
+          match ui.split.is_enabled() {
+            true => ui.split.reveal(
+              entry_id,
+              &display,
+              &mut vertical_scroll,
+              &mut vertical_scroll_state,
+              viewport.height(),
+            ),
+            false => {
+              vertical_scroll =
+                viewport.reveal(display.row(entry_id), vertical_scroll, display.len());
+              vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
+            }
+          }
         } else {
-          status_entry = Some(StatusMessage::new([
-            (" ✗ ".to_string(), Style::default().bold().red()),
-            (format!("'{}' not found", query), Style::default()),
-          ]));
-          None
-        };
+          status_entry = Some(
+            StatusMessage::new([
+              (" ✗ ".to_string(), Style::default().bold().red()),
+              (format!("'{}' not found", query), Style::default()),
+            ])
+            .with_priority(StatusPriority::High),
+          );
+        }
+      }
+      let width = terminal.size().map(|size| size.width).unwrap_or(u16::MAX);
+      let wanted = ui
+        .compact_toggled
+        .unwrap_or_else(|| options.compact_gutter(width));
+      if wanted != compact {
+        compact = wanted;
+        let _ = commands.send(PrepareCommand::SetInlineMarkers(compact));
       }
-      build
-        .markers_mut()
-        .set_selection(markers.selection().cloned());
-      if let Some(search_sel) = search_selection {
-        build.select_entry(search_sel.entry_id, search_sel.region.clone());
+      ui.cursor.follow(markers.selected_entry());
+      if markers.selection() != sent_selection.as_ref() {
+        sent_selection = markers.selection().cloned();
+        let _ = commands.send(PrepareCommand::Select(sent_selection.clone()));
+        ipc.send(IpcEvent::selection(sent_selection.as_ref()));
+      }
+      step_start = Instant::now();
+      // the first entry may be captured before the start event gets received
+      let build_start = [started_at, snapshot.started_at()]
+        .into_iter()
+        .flatten()
+        .min();
+      let elapsed = build_start
+        .map(|start| {
+          finished_at
+            .unwrap_or_else(Instant::now)
+            .saturating_duration_since(start)
+        })
+        .unwrap_or_default();
+      let lines = snapshot.display(&markers);
+      // back in time, the entries created after the cursor are hidden
+      display.set_limit(
+        build_start
+          .and_then(|start| ui.timeline.at(start))
+          .map(|at| snapshot.entries_before(at)),
+      );
+      let repeats = match ui.fold_repeats {
+        true => snapshot.repeats(),
+        false => vec![],
+      };
+      let duplicates = match ui.dedup_targets {
+        true => snapshot.duplicates(),
+        false => vec![],
+      };
+      display.set_folds(FoldMap::new(
+        snapshot
+          .folds()
+          .into_iter()
+          .chain(repeats)
+          .chain(duplicates)
+          .filter(|fold| !ui.expanded_folds.contains(&fold.range.start)),
+      ));
+      display.set_kind(ui.filter);
+      // prepared again, e.g. with another rule
+      if display_generation != generation {
+        display_generation = generation;
+        display.invalidate();
       }
-      let build_lines = build.display();
-      if let Ok(e) = build_events.try_recv() {
+      display.update(&lines);
+      let view = ui.split.view(&display);
+      vertical_scroll = ui
+        .follow
+        .scroll(vertical_scroll, viewport.bottom(view.len()));
+      vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
+      frame_timing.prepare = step_start.elapsed();
+      // all the pending events, the finished one must not wait behind a backlog
+      while let Ok(e) = build_events.try_recv() {
         crate::dbg!("Received {:?}", e);
-        build_status_entry = Some(e);
+        if awaiting_start && !matches!(e, BuildEvent::BuildStarted) {
+          crate::dbg!("Ignoring {:?} from the previous run", e);
+        } else {
+          awaiting_start = false;
+          if let Some(event) = IpcEvent::from_build_event(&e) {
+            ipc.send(event);
+          }
+          if let BuildEvent::BuildStarted = e {
+            started_at = Some(Instant::now());
+            intake.clear();
+            // indeterminate until cargo draws its progress bar
+            progress.running(0, 0);
+          }
+          if let BuildEvent::BuildProgress(done, total) = e {
+            progress.running(done, total);
+          }
+          if let BuildEvent::IntakeStats(stats) = e {
+            intake.push(stats);
+            if stats.dropped > 0 {
+              let dropped = intake.iter().map(|stats| stats.dropped).sum::<usize>();
+              status_bar.borrow_mut().push_message(
+                StatusMessage::new([(
+                  format!("dropped {} blank/spinner lines", format_count(dropped)),
+                  Style::default().dim(),
+                )])
+                .with_priority(StatusPriority::Low),
+              );
+            }
+          }
+          if let BuildEvent::BuildFinished(status) = e {
+            exit_status = Some(status);
+            finished_at = Some(Instant::now());
+            let outcome = match status.success() {
+              true => "succeeded",
+              false => "failed",
+            };
+            progress.finished(
+              status.success(),
+              format!("the build of {} {}", title_name, outcome),
+              Instant::now(),
+            );
+            if status.success() && !snapshot.artifacts().is_empty() {
+              status_entry = Some(StatusMessage::new([(
+                format!(
+                  "{} artifact(s) produced, press a to list them",
+                  snapshot.artifacts().len()
+                ),
+                Style::default(),
+              )]));
+            }
+            history_pending = !options.reads_log();
+            if anchor.is_some()
+              || ui.bookmarks.is_detached()
+              || ui.pins.is_detached()
+              || !rule_checked
+              || history_pending
+            {
+              // the last entries were sent before this event, wait for them to be prepared
+              let _ = commands.send(PrepareCommand::Flush);
+            }
+          }
+          build_status_events.push(e);
+        }
       }
       // if first_render || output_changed || key_event {
       let (num_errs, num_warns, num_notes) = (
-        build.errors().len(),
-        build.warnings().len(),
-        build.notes().len(),
+        match markers.is_enabled(BuildTagKind::Error) {
+          true => snapshot.num_errors(),
+          false => 0,
+        },
+        match markers.is_enabled(BuildTagKind::Warning) {
+          true => snapshot.num_warnings(),
+          false => 0,
+        },
+        match markers.is_enabled(BuildTagKind::Note) {
+          true => snapshot.num_notes(),
+          false => 0,
+        },
       );
-      Self::set_cursor_visible(&mut terminal, search_state.is_some());
-      terminal.draw(|frame| {
+      if titled {
+        let text = window_title(&title_name, elapsed, exit_status, num_errs, num_warns);
+        if text != title {
+          let _ = set_title(&mut stdout(), &text);
+          title = text;
+        }
+      }
+      Self::set_cursor_visible(
+        terminal,
+        ui.search.is_some()
+          || ui.input_prompt.is_shown()
+          || ui.tester.is_shown()
+          || ui.help.is_editing(),
+      );
+      // the selected line keeps its highlight under the cursor
+      let cursor_row = ui
+        .cursor
+        .row(&view, vertical_scroll, viewport)
+        .filter(|row| view.entry_at(*row).map(EntryId) != markers.selected_entry());
+      step_start = Instant::now();
+      let completed = terminal.draw(|frame| {
+        let top_height = match ui.zen {
+          true => 0,
+          false => 3,
+        };
         [top_area, main_pane] =
-          Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(frame.area());
+          Layout::vertical([Constraint::Length(top_height), Constraint::Fill(1)])
+            .areas(frame.area());
         [command_area, shortcuts_area] =
           Layout::horizontal([Constraint::Percentage(50), Constraint::Fill(1)]).areas(top_area);
-        [log_area, bottom_area] =
-          Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(main_pane);
-        [search_area, status_area] = match search_state {
-          Some(_) => {
+        [log_area, preview_area, timeline_area, bottom_area] = Layout::vertical([
+          Constraint::Fill(1),
+          Constraint::Length(match ui.preview.is_open() {
+            true => FilePreview::HEIGHT,
+            false => 0,
+          }),
+          Constraint::Length(ui.timeline.is_shown() as u16),
+          Constraint::Length(1),
+        ])
+        .areas(main_pane);
+        // the pinned blocks take the top of the log, their entry ids are stale during a re-run
+        let pins = match ui.pins.is_detached() {
+          true => vec![],
+          false => ui
+            .pins
+            .visible()
+            .iter()
+            .filter_map(|pin| {
+              let block = snapshot.block_at(pin.entry_id)?;
+              let end = (pin.entry_id.0 + options.pin_lines).min(block.range().end);
+              let lines = snapshot
+                .display_range(pin.entry_id.0..end, &markers)
+                .iter()
+                .map(|entry| entry.line().clone())
+                .collect::<Vec<_>>();
+              Some((block.marker_id(), lines))
+            })
+            .collect::<Vec<_>>(),
+        };
+        let pinned = PinnedArea::new(pins, ui.pins.scroll(), ui.pins.pins().len());
+        [pins_area, log_area] =
+          Layout::vertical([Constraint::Length(pinned.height()), Constraint::Fill(1)])
+            .areas(log_area);
+        frame.render_widget(pinned, pins_area);
+        // the split panes keep their borders to stay apart
+        let borderless = ui.zen && !ui.split.is_enabled();
+        let previous_height = viewport.height();
+        viewport = Viewport::new(
+          LogView::default()
+            .with_ruler(ui.show_ruler)
+            .with_borderless(borderless)
+            .num_rows(log_area),
+        )
+        .with_scrolloff(options.scrolloff);
+        // resized, e.g. entering the zen mode: no blank rows below the last line,
+        // the selected block stays in view
+        if viewport.height() != previous_height {
+          vertical_scroll = vertical_scroll.min(viewport.bottom(view.len()));
+          if let Some(entry_id) = markers.selected_entry() {
+            vertical_scroll = viewport.reveal(view.row(entry_id.0), vertical_scroll, view.len());
+          }
+          vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
+        }
+        [search_area, status_area] = match ui.search.is_some() || ui.input_prompt.is_shown() {
+          true => {
             Layout::horizontal([Constraint::Percentage(50), Constraint::Fill(1)]).areas(bottom_area)
           }
-          None => {
+          false => {
             Layout::horizontal([Constraint::Length(0), Constraint::Fill(1)]).areas(bottom_area)
           }
         };
 
         let mut args = vec!["cmd".bold(), ":".into(), " ".into()];
-        if options.stdin {
+        if let Some(path) = options.input.as_ref() {
+          args.extend_from_slice(&[path.display().to_string().dim()]);
+        } else if options.stdin {
           args.extend_from_slice(&["stdin".dim()]);
         } else {
           args.extend_from_slice(&["cargo".dim(), " ".into(), "build".dim()]);
@@ -234,116 +1182,352 @@ impl Renderer {
             .flat_map(|arg| vec![" ".into(), arg.into()].into_iter())
             .collect::<Vec<_>>(),
         );
-        frame.render_widget(Clear, command_area);
-        let command = Paragraph::new(Line::default().spans(args)).block(Block::bordered());
-        frame.render_widget(command, command_area);
-
-        let shortcuts =
-          Paragraph::new(Line::default().spans(["H: Show help"])).block(Block::bordered());
-        frame.render_widget(shortcuts, shortcuts_area);
-
-        if status_entry.is_some() || build_status_entry.is_some() {
-          let mut new_status = *status_bar.borrow();
-          match (status_entry.as_ref(), build_status_entry.as_ref()) {
-            (Some(status_msg), Some(build_event)) => {
-              new_status = new_status
-                .with_event(*build_event)
-                .with_message(*status_msg);
-            }
-            (Some(status_msg), None) => {
-              new_status = new_status.with_message(*status_msg);
-            }
-            (None, Some(build_event)) => {
-              new_status = new_status.with_event(*build_event);
-            }
-            (None, None) => {}
-          }
-          *status_bar.borrow_mut() = new_status
-            .with_num_prepared_lines(build.cursor())
-            .with_num_output_lines(build.entries().len())
+        args.extend_from_slice(&[
+          " | ".dark_gray(),
+          "rule".bold(),
+          ":".into(),
+          " ".into(),
+          Self::rule_name(snapshot.rule()).dim(),
+        ]);
+        if !ui.zen {
+          frame.render_widget(Clear, command_area);
+          let command = Paragraph::new(Line::default().spans(args)).block(Block::bordered());
+          frame.render_widget(command, command_area);
+
+          let shortcuts =
+            Paragraph::new(Line::default().spans(["h: Show help"])).block(Block::bordered());
+          frame.render_widget(shortcuts, shortcuts_area);
+        }
+
+        let now = Instant::now();
+        let mut new_status = *status_bar.borrow();
+        // the counts merge into the build state, throttled unless the state changes
+        // or the last entries of the finished build got prepared
+        let settled = exit_status.is_some() && flushed;
+        if !build_status_events.is_empty()
+          || settled != counts_settled
+          || now.duration_since(counts_updated_at) >= STATUS_COUNTS_INTERVAL
+        {
+          counts_updated_at = now;
+          counts_settled = settled;
+          new_status = new_status
+            .with_elapsed(build_start.map(|_| elapsed))
+            .with_compact_counts(options.compact_gutter(width))
+            .with_num_prepared_lines(snapshot.len())
+            .with_num_output_lines(snapshot.num_entries())
             .with_num_notes(num_notes)
             .with_num_errors(num_errs)
-            .with_num_warnings(num_warns);
-          status_entry = None;
-          build_status_entry = None;
+            .with_num_warnings(num_warns)
+            .with_num_script_warnings(snapshot.num_script_warnings())
+            .with_failed_crates(BuildSummary::failed_crates_line(snapshot.failed_crates()));
+        }
+        for build_event in build_status_events.drain(..) {
+          new_status = new_status.with_event(build_event);
+        }
+        if let Some(status_msg) = status_entry.take() {
+          new_status.push_message_at(status_msg, now);
         }
+        new_status.tick(now);
+        *status_bar.borrow_mut() = new_status;
         frame.render_widget(*status_bar.borrow(), status_area);
-        let mut log_view = LogView::default()
-          .with_content(build_lines.clone())
-          .with_scroll(vertical_scroll);
-        log_view.set_filter(filter);
-        frame.render_stateful_widget(log_view, log_area, &mut vertical_scroll_state);
+        if ui.split.is_enabled() {
+          let panes =
+            [Origin::Stdout, Origin::Stderr].map(|pane| display.pane(pane).apply(lines.clone()));
+          let mut split_view = SplitLogView::new(panes, &ui.split, vertical_scroll)
+            .with_ruler(ui.show_ruler)
+            .with_cursor(cursor_row);
+          split_view.set_filter(ui.filter);
+          links = split_view.hyperlinks(log_area);
+          frame.render_widget(split_view, log_area);
+        } else {
+          let mut log_view = LogView::default()
+            .with_content(display.apply(lines))
+            .with_ruler(ui.show_ruler)
+            .with_borderless(borderless)
+            .with_scroll(vertical_scroll)
+            .with_cursor(cursor_row)
+            .with_total(snapshot.len());
+          log_view.set_filter(ui.filter);
+          links = log_view.hyperlinks(log_area);
+          frame.render_stateful_widget(log_view, log_area, &mut vertical_scroll_state);
+        }
         // frame.render_stateful_widget(log_view, log_area, &mut list_state);
-        if search_state.is_some() {
-          frame.render_stateful_widget(SearchBar, search_area, &mut search_state);
-          let mut cursor_pos = (search_area.x, search_area.y);
-          if let Some(state) = search_state.as_ref() {
-            cursor_pos.0 += state.cursor_position() as u16;
+        if ui.preview.is_open() {
+          frame.render_stateful_widget(FilePreview, preview_area, &mut ui.preview);
+        }
+        if ui.timeline.is_shown() {
+          let plotted = markers
+            .enabled()
+            .filter_map(|(_marker_id, (entry_id, kind))| {
+              let offset = snapshot
+                .entry(*entry_id)?
+                .created_at()
+                .saturating_duration_since(build_start?);
+              let style = snapshot
+                .rule()
+                .markers
+                .iter()
+                .find(|marker| marker.tag == *kind)
+                .map(|marker| marker.style)
+                .unwrap_or_default();
+              Some((offset, *kind, style))
+            })
+            .collect::<Vec<_>>();
+          let view = Timeline::new(elapsed).with_markers(plotted);
+          frame.render_stateful_widget(view, timeline_area, &mut ui.timeline);
+        }
+        if ui.search.is_some() {
+          frame.render_stateful_widget(SearchBar, search_area, &mut ui.search);
+          if let Some(state) = ui.search.as_ref() {
+            frame.set_cursor_position(state.cursor_at(search_area));
+          }
+        } else if ui.input_prompt.is_shown() {
+          frame.render_stateful_widget(InputPrompt, search_area, &mut ui.input_prompt);
+          if let Some(state) = ui.input_prompt.input() {
+            frame.set_cursor_position(state.cursor_at(search_area));
+          }
+        }
+        if ui.toggles.is_shown() {
+          let toggles = MarkerToggles::new(&snapshot.rule().markers, &markers);
+          frame.render_stateful_widget(toggles, frame.area(), &mut ui.toggles);
+        }
+        if ui.tester.is_shown() {
+          frame.render_stateful_widget(RegexTester, frame.area(), &mut ui.tester);
+          if let Some(cursor_pos) = ui.tester.cursor_position() {
+            frame.set_cursor_position(cursor_pos);
+          }
+        }
+        if ui.jump.is_shown() {
+          let style = snapshot
+            .rule()
+            .markers
+            .iter()
+            .find(|marker| marker.tag == ui.jump.kind())
+            .map(|marker| marker.style)
+            .unwrap_or_default();
+          let jump = JumpDialog::new(snapshot.jump_targets(ui.jump.kind())).with_style(style);
+          frame.render_stateful_widget(jump, frame.area(), &mut ui.jump);
+        }
+        if ui.artifacts.is_shown() {
+          let artifacts = ArtifactList::new(snapshot.artifacts());
+          frame.render_stateful_widget(artifacts, frame.area(), &mut ui.artifacts);
+        }
+        if ui.info.is_shown() {
+          let info = InfoPopup::new(snapshot.rule(), &session);
+          frame.render_stateful_widget(info, frame.area(), &mut ui.info);
+        }
+        if ui.history.is_shown() {
+          frame.render_stateful_widget(HistoryList, frame.area(), &mut ui.history);
+        }
+        if ui.status_history.is_shown() {
+          let records = status_bar.borrow().records().copied().collect::<Vec<_>>();
+          let list = StatusHistoryList::new(&records);
+          frame.render_stateful_widget(list, frame.area(), &mut ui.status_history);
+        }
+        if ui.long_line.is_shown() {
+          let message = snapshot
+            .entry(ui.long_line.entry_id())
+            .map(|entry| entry.message().as_str())
+            .unwrap_or_default();
+          frame.render_stateful_widget(
+            LongLinePopup::new(message),
+            frame.area(),
+            &mut ui.long_line,
+          );
+        }
+        if ui.bookmarks.is_shown() {
+          frame.render_stateful_widget(BookmarkList, frame.area(), &mut ui.bookmarks);
+        }
+        if ui.pins.is_shown() {
+          frame.render_stateful_widget(PinList, frame.area(), &mut ui.pins);
+        }
+        if ui.rule_picker.is_shown() {
+          frame.render_stateful_widget(RulePicker, frame.area(), &mut ui.rule_picker);
+        }
+        if ui.rule_editor.is_shown() {
+          frame.render_stateful_widget(RuleEditor, frame.area(), &mut ui.rule_editor);
+        }
+        if ui.show_perf {
+          frame.render_widget(
+            PerfOverlay::new(&frame_timings)
+              .with_slow_markers(snapshot.slow_markers().len())
+              .with_intake(&intake),
+            frame.area(),
+          );
+        }
+        if ui.help.is_shown() {
+          let help = HelpMenu::new().with_sections(HELP_SECTIONS);
+          frame.render_stateful_widget(help, frame.area(), &mut ui.help);
+          if let Some(cursor_pos) = ui.help.cursor_position() {
+            frame.set_cursor_position(cursor_pos);
           }
-          frame.set_cursor_position(cursor_pos);
         }
-        if show_help {
-          let help = HelpMenu::new()
-            .with_keys(HELP_MENU)
-            .with_scroll(help_vertical_scroll);
-          frame.render_stateful_widget(help, frame.area(), &mut help_vertical_scroll_state);
+        // the overlays cover the log
+        if ui.help.is_shown()
+          || ui.show_perf
+          || ui.toggles.is_shown()
+          || ui.tester.is_shown()
+          || ui.jump.is_shown()
+          || ui.artifacts.is_shown()
+          || ui.info.is_shown()
+          || ui.history.is_shown()
+          || ui.status_history.is_shown()
+          || ui.long_line.is_shown()
+          || ui.bookmarks.is_shown()
+          || ui.pins.is_shown()
+          || ui.rule_picker.is_shown()
+          || ui.rule_editor.is_shown()
+        {
+          links.clear();
         }
       })?;
+      hyperlinks.write(&mut stdout().lock(), completed.buffer, &links)?;
+      progress.tick(Instant::now());
+      progress.write(&mut stdout().lock(), Instant::now())?;
+      frame_timing.draw = step_start.elapsed();
       // }
 
-      if event::poll(Duration::from_micros(100))? {
-        match event::read()? {
+      step_start = Instant::now();
+      if let Some(event) =
+        input.next(Duration::from_micros(100), exit_status.is_some() && flushed)?
+      {
+        match event {
           event::Event::Mouse(mouse) => match mouse.kind {
             MouseEventKind::ScrollDown => {
-              vertical_scroll = vertical_scroll.saturating_add(1);
+              vertical_scroll =
+                viewport.scroll_down(vertical_scroll, 1, ui.split.view(&display).len());
               vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
             }
             MouseEventKind::ScrollUp => {
               vertical_scroll = vertical_scroll.saturating_sub(1);
               vertical_scroll_state = vertical_scroll_state.position(vertical_scroll);
             }
+            // the counts of the status bar, see StatusBar::segments
+            MouseEventKind::Moved => {
+              let bar = *status_bar.borrow();
+              let hovered = bar.segment_at(status_area, mouse.column, mouse.row);
+              *status_bar.borrow_mut() = bar.with_hovered(hovered);
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+              let clicked = status_bar
+                .borrow()
+                .segment_at(status_area, mouse.column, mouse.row);
+              // same as 'e', 'w' and 'n'
+              if let Some(sel) = clicked.and_then(|kind| Self::find_first_marker(&markers, kind)) {
+                Self::select_marker(
+                  &sel,
+                  &mut markers,
+                  &mut vertical_scroll,
+                  &mut vertical_scroll_state,
+                  viewport,
+                  &ui.split.view(&display),
+                );
+              }
+            }
             _ => {}
           },
           event::Event::Key(key) => {
             if key.kind == KeyEventKind::Press {
+              let status_records = status_bar.borrow().records().copied().collect::<Vec<_>>();
+              let context = KeyContext {
+                user_quit: user_quit.clone(),
+                viewport,
+                snapshot: &snapshot,
+                display: &display,
+                search_query: tx_search_query.clone(),
+                status_records: &status_records,
+                tester_action: tx_tester_action.clone(),
+                elapsed,
+                compact,
+                building: !options.reads_log() && exit_status.is_none(),
+              };
               Self::handle_key_press(
                 key,
+                &mut ui,
+                context,
                 &mut vertical_scroll,
                 &mut vertical_scroll_state,
-                &mut help_vertical_scroll,
-                &mut help_vertical_scroll_state,
                 &mut markers,
                 &mut stop,
-                user_quit.clone(),
-                &log_area,
-                &build,
-                &build_lines,
-                &mut search_state,
-                tx_search_query.clone(),
-                &mut filter,
-                &mut show_help,
               );
             }
           }
           _ => {}
         }
       }
+      ui.quit.tick(Instant::now());
+      match ui.quit.take_step() {
+        Some(QuitStep::Prompt) => {
+          status_entry = Some(
+            StatusMessage::new([
+              (" ? ".to_string(), Style::default().bold().yellow()),
+              (
+                "build running — press q again to cancel and quit, Esc to stay".to_string(),
+                Style::default(),
+              ),
+            ])
+            .with_priority(StatusPriority::High)
+            .with_ttl(QuitState::TIMEOUT),
+          )
+        }
+        Some(QuitStep::Stay) => {
+          status_entry = Some(
+            StatusMessage::new([("The build keeps running".to_string(), Style::default())])
+              .with_priority(StatusPriority::High),
+          )
+        }
+        Some(QuitStep::Cancel) => {
+          crate::dbg!("Stopping the build before quitting");
+          let _ = builds.send(BuildRequest::Stop);
+        }
+        None => {}
+      }
+      frame_timing.events = step_start.elapsed();
+      frame_timings.push(frame_timing);
+      num_frames += 1;
+      stop |= input.is_over(
+        num_frames,
+        exit_status.is_some() && flushed,
+        loop_started_at,
+      );
     }
-    Ok(())
+    progress.clear();
+    let _ = progress.write(&mut stdout().lock(), Instant::now());
+    let duration = Self::duration(started_at, finished_at, &snapshot);
+    let _ =
+      summary.send(BuildSummary::from_snapshot(&snapshot, exit_status).with_duration(duration));
+    let status_bar = *status_bar.borrow();
+    Ok(RenderState {
+      snapshot,
+      markers,
+      status_bar,
+      scroll: vertical_scroll,
+      exit_status,
+      frames: num_frames,
+    })
   }
 
-  fn scroll_to_element(index: usize, scroll: &mut usize, log_area: &Rect) {
-    if index < *scroll {
-      *scroll = index.saturating_sub(log_area.height as usize);
-    } else if index >= *scroll + log_area.height as usize {
-      *scroll = index;
-    }
+  /// Compute the time the build took, or was followed for
+  fn duration(
+    started_at: Option<Instant>,
+    finished_at: Option<Instant>,
+    snapshot: &BuildSnapshot,
+  ) -> Duration {
+    [started_at, snapshot.started_at()]
+      .into_iter()
+      .flatten()
+      .min()
+      .map(|start| {
+        finished_at
+          .unwrap_or_else(Instant::now)
+          .saturating_duration_since(start)
+      })
+      .unwrap_or_default()
   }
 
   fn find_first_marker(markers: &Markers, kind: BuildTagKind) -> Option<MarkerSelection> {
     if let Some((marker_id, (entry_id, _tag))) = markers
-      .iter()
-      .enumerate()
+      .enabled()
       .find(|(_marker_id, (_entry_id, tag))| *tag == kind)
     {
       return Some(MarkerSelection::new(marker_id, *entry_id, None));
@@ -351,37 +1535,550 @@ impl Renderer {
     return None;
   }
 
+  /// The name of a rule, its first alias
+  fn rule_name(rule: &Rule) -> String {
+    rule.aliases.first().cloned().unwrap_or_default()
+  }
+
+  /// Score the known rules against the output of the snapshot, see [`score_rules`]
+  fn score_rules(snapshot: &BuildSnapshot) -> Vec<RuleCandidate> {
+    let lines = snapshot
+      .entries()
+      .map(|entry| entry.message().as_str())
+      .collect::<Vec<_>>();
+    score_rules(&rules(), &lines)
+  }
+
+  /// Explain why another rule should be picked: a finished build of more than
+  /// [`RULE_SUGGESTION_MIN_LINES`] lines where the active rule matched nothing.
+  ///
+  /// The 3 best `candidates` get named
+  fn suggest_rule(snapshot: &BuildSnapshot, candidates: &[RuleCandidate]) -> Option<String> {
+    if snapshot.num_entries() <= RULE_SUGGESTION_MIN_LINES || !snapshot.markers().is_empty() {
+      return None;
+    }
+    let best = candidates
+      .iter()
+      .filter(|candidate| candidate.score > 0)
+      .take(3)
+      .map(|candidate| candidate.to_string())
+      .collect::<Vec<_>>();
+    let mut reason = format!(
+      "no diagnostics matched rule '{}'; press R to switch rules",
+      Self::rule_name(snapshot.rule())
+    );
+    if !best.is_empty() {
+      reason.push_str(&format!(" (best matches: {})", best.join(", ")));
+    }
+    Some(reason)
+  }
+
+  /// Apply a [`TesterAction`] and describe its outcome
+  fn apply_tester_action(
+    action: TesterAction,
+    options: &AppOptions,
+    snapshot: &BuildSnapshot,
+    commands: &Sender<PrepareCommand>,
+    markers: &mut Markers,
+    generation: &mut usize,
+  ) -> StatusMessage {
+    match action {
+      TesterAction::AddMarker(kind, regex) => {
+        let style = snapshot
+          .rule()
+          .markers
+          .iter()
+          .find(|marker| marker.tag == kind)
+          .map(|marker| marker.style)
+          .unwrap_or_default();
+        let rule = add_active_rule_marker(DeclaredMarker::new(kind, regex, style));
+        let _ = commands.send(PrepareCommand::SetRule(rule));
+        *generation += 1;
+        markers.set_selection(None);
+        StatusMessage::new([(
+          format!("Added {} marker to rule '{}'", kind, active_rule_name()),
+          Style::default(),
+        )])
+      }
+      TesterAction::SaveRules => match save_rules(&rules(), options.config_path.clone()) {
+        Ok(path) => StatusMessage::new([(
+          format!("Saved rules to {}", path.display()),
+          Style::default(),
+        )]),
+        Err(e) => StatusMessage::new([
+          (" ✗ ".to_string(), Style::default().bold().red()),
+          (format!("failed to save rules, {}", e), Style::default()),
+        ])
+        .with_priority(StatusPriority::High),
+      },
+    }
+  }
+
+  /// Apply a [`RuleEditorAction`] and describe its outcome,
+  /// the output gets prepared again if the edited rule is the active one.
+  ///
+  /// A rule gets saved to the config file it was registered from, the built-in rules
+  /// are never saved: they would not be reloaded from there
+  fn apply_rule_editor_action(
+    action: RuleEditorAction,
+    snapshot: &BuildSnapshot,
+    commands: &Sender<PrepareCommand>,
+    markers: &mut Markers,
+    generation: &mut usize,
+  ) -> StatusMessage {
+    let (RuleEditorAction::Apply(rule) | RuleEditorAction::Save(rule)) = &action;
+    let name = Self::rule_name(rule);
+    update_rule(rule.clone());
+    if Self::rule_name(snapshot.rule()) == name {
+      let _ = commands.send(PrepareCommand::SetRule(rule.clone()));
+      *generation += 1;
+      markers.set_selection(None);
+    }
+    if let RuleEditorAction::Apply(_) = action {
+      return StatusMessage::new([(format!("Updated rule '{}'", name), Style::default())]);
+    }
+    // the definition in use is the one reloaded next time, a built-in rule wins over
+    // its declaration in the config files
+    let path = match rule_provenance(&name) {
+      Provenance::File(path) => path,
+      _ => {
+        return StatusMessage::new([
+          (" ✗ ".to_string(), Style::default().bold().red()),
+          (
+            format!("'{}' is a built-in rule, edits last until exit", name),
+            Style::default(),
+          ),
+        ])
+        .with_priority(StatusPriority::High)
+      }
+    };
+    match save_rules(&rules(), Some(path)) {
+      Ok(path) => StatusMessage::new([(
+        format!("Saved rule '{}' to {}", name, path.display()),
+        Style::default(),
+      )]),
+      Err(e) => StatusMessage::new([
+        (" ✗ ".to_string(), Style::default().bold().red()),
+        (format!("failed to save rules, {}", e), Style::default()),
+      ])
+      .with_priority(StatusPriority::High),
+    }
+  }
+
+  /// Apply a [`StatusHistoryAction`], describing where the history got saved.
+  /// The run directory holding a saved history outlives the run.
+  ///
+  /// The deletions go unreported, they would make it back to the history
+  fn apply_status_history_action(
+    action: StatusHistoryAction,
+    status_bar: &RefCell<StatusBar>,
+    session: &SessionInfo,
+  ) -> Option<StatusMessage> {
+    let failed = |reason: String| {
+      StatusMessage::new([
+        (" ✗ ".to_string(), Style::default().bold().red()),
+        (reason, Style::default()),
+      ])
+      .with_priority(StatusPriority::High)
+    };
+    match action {
+      StatusHistoryAction::Delete(id) => {
+        status_bar.borrow_mut().delete(id);
+        None
+      }
+      StatusHistoryAction::Save => Some(match session.run_dir.as_ref() {
+        None => failed("no run directory to save the status history to".to_string()),
+        Some(dir) => match status_bar
+          .borrow()
+          .save_history(dir, Local::now())
+          .and_then(|path| RunContext::keep_dir(dir).map(|_| path))
+        {
+          Ok(path) => StatusMessage::new([(
+            format!("Saved the status history to {}", path.display()),
+            Style::default(),
+          )]),
+          Err(e) => failed(format!("failed to save the status history, {}", e)),
+        },
+      }),
+    }
+  }
+
+  /// Apply an [`ArtifactAction`] and describe its outcome
+  fn apply_artifact_action(action: ArtifactAction, snapshot: &BuildSnapshot) -> StatusMessage {
+    let (ArtifactAction::Copy(i) | ArtifactAction::Reveal(i)) = action;
+    let Some(artifact) = snapshot.artifacts().get(i) else {
+      return StatusMessage::new([(format!("no artifact #{}", i + 1), Style::default())]);
+    };
+    match action {
+      ArtifactAction::Copy(_) => {
+        let mut out = stdout().lock();
+        match write!(out, "{}", osc52(artifact.path.display().to_string()))
+          .and_then(|_| out.flush())
+        {
+          Ok(_) => StatusMessage::new([(
+            format!("Copied {}", artifact.path.display()),
+            Style::default(),
+          )]),
+          Err(e) => StatusMessage::new([
+            (" ✗ ".to_string(), Style::default().bold().red()),
+            (format!("failed to copy the path, {}", e), Style::default()),
+          ])
+          .with_priority(StatusPriority::High),
+        }
+      }
+      ArtifactAction::Reveal(_) => {
+        let command = artifact.reveal_command();
+        spawn(move || {
+          if let Err(e) = ProcessRunner.run(&command) {
+            crate::dbg!("failed to reveal artifact, {}", e);
+          }
+        });
+        StatusMessage::new([(
+          format!("Revealing {}", artifact.path.display()),
+          Style::default(),
+        )])
+      }
+    }
+  }
+
+  /// Apply the machine-applicable suggestions of the selected block and describe the outcome
+  fn apply_fix(markers: &Markers, snapshot: &BuildSnapshot) -> StatusMessage {
+    let Some(block) = markers
+      .selected_entry()
+      .and_then(|entry_id| snapshot.block_at(entry_id))
+    else {
+      return StatusMessage::new([(
+        "select a block to apply its fix".to_string(),
+        Style::default(),
+      )]);
+    };
+    let suggestions = block.suggestions();
+    match apply_suggestions(&suggestions) {
+      Ok(count) => StatusMessage::new([
+        (" ✓ ".to_string(), Style::default().bold().green()),
+        (
+          format!(
+            "Applied {} replacement{} to {}",
+            count,
+            if count > 1 { "s" } else { "" },
+            suggestions
+              .iter()
+              .filter(|suggestion| suggestion.is_machine_applicable())
+              .map(|suggestion| display_path(&suggestion.path))
+              .collect::<BTreeSet<_>>()
+              .into_iter()
+              .collect::<Vec<_>>()
+              .join(", ")
+          ),
+          Style::default(),
+        ),
+      ]),
+      Err(e) => StatusMessage::new([
+        (" ✗ ".to_string(), Style::default().bold().red()),
+        (
+          format!(
+            "cannot apply the fix, {}",
+            e.message().map(String::as_str).unwrap_or_default()
+          ),
+          Style::default(),
+        ),
+      ])
+      .with_priority(StatusPriority::High),
+    }
+  }
+
+  /// Copy the attribute allowing the lint of the selected warning and describe the outcome
+  fn copy_allow_attribute(markers: &Markers, snapshot: &BuildSnapshot) -> StatusMessage {
+    let Some(block) = markers
+      .selected_entry()
+      .and_then(|entry_id| snapshot.block_at(entry_id))
+      .filter(|block| block.marker().kind() == BuildTagKind::Warning)
+    else {
+      return StatusMessage::new([(
+        "select a warning to copy its allow attribute".to_string(),
+        Style::default(),
+      )]);
+    };
+    let attribute = block.allow_attribute();
+    let mut out = stdout().lock();
+    match write!(out, "{}", osc52(&attribute)).and_then(|_| out.flush()) {
+      Ok(_) => StatusMessage::new([(format!("Copied {}", attribute), Style::default())]),
+      Err(e) => StatusMessage::new([
+        (" ✗ ".to_string(), Style::default().bold().red()),
+        (
+          format!("failed to copy the attribute, {}", e),
+          Style::default(),
+        ),
+      ])
+      .with_priority(StatusPriority::High),
+    }
+  }
+
   /// Handle user keypresses
   fn handle_key_press(
     key: KeyEvent,
+    ui: &mut UiState,
+    context: KeyContext,
     log_scroll: &mut usize,
     log_scroll_state: &mut ScrollbarState,
-    help_scroll: &mut usize,
-    help_scroll_state: &mut ScrollbarState,
     markers: &mut Markers,
     stop: &mut bool,
-    user_quit: Sender<bool>,
-    log_area: &Rect,
-    build_output: &BuildOutput,
-    build_lines: &Vec<LogEntry<'_>>,
-    search_value: &mut Option<SearchState>,
-    search_query: Sender<String>,
-    filter: &mut Option<BuildTagKind>,
-    show_help: &mut bool,
   ) {
-    if SearchBar::handle_key(key, search_value, search_query) {
+    let UiState {
+      help,
+      search: search_value,
+      last_search,
+      input_prompt,
+      filter,
+      preview,
+      toggles,
+      tester,
+      jump,
+      artifacts,
+      info,
+      history,
+      status_history,
+      long_line,
+      bookmarks,
+      pins,
+      rule_picker,
+      rule_editor,
+      show_perf,
+      show_ruler,
+      zen,
+      follow,
+      rerun,
+      fix,
+      copy_allow,
+      split,
+      timeline,
+      compact_toggled,
+      cursor,
+      expanded_folds,
+      fold_repeats,
+      dedup_targets,
+      quit,
+    } = ui;
+    let KeyContext {
+      user_quit,
+      viewport,
+      snapshot,
+      display,
+      search_query,
+      status_records,
+      tester_action,
+      elapsed,
+      compact,
+      building,
+    } = context;
+    // the rows of the focused pane when split
+    let view = split.view(display);
+    if key.code == KeyCode::F(10) {
+      *show_perf = !*show_perf;
+      return;
+    }
+    // the help filter gets the keys of the search bar
+    if help.is_shown() {
+      HelpMenu::handle_key(key, help, &HelpMenu::new().with_sections(HELP_SECTIONS));
       return;
     }
-    if HelpMenu::handle_key(
-      key,
-      show_help,
-      HELP_MENU.len(),
-      help_scroll,
-      help_scroll_state,
-    ) {
+    if search_value.is_none() && InputPrompt::handle_key(key, input_prompt) {
       return;
     }
-    if key.code == KeyCode::Char('q') {
+    if SearchBar::handle_key(key, search_value, last_search, search_query) {
+      return;
+    }
+    // the line of the cursor, or the selected one
+    let targeted = cursor
+      .row(&view, *log_scroll, viewport)
+      .and_then(|row| view.entry_at(row))
+      .map(EntryId)
+      .or(markers.selected_entry());
+    let sample = targeted
+      .and_then(|entry_id| snapshot.entry(entry_id))
+      .map(|entry| entry.message());
+    if RegexTester::handle_key(key, tester, sample, tester_action) {
+      return;
+    }
+    if HelpMenu::handle_key(key, help, &HelpMenu::new().with_sections(HELP_SECTIONS)) {
+      return;
+    }
+    if JumpDialog::handle_key(key, jump, |kind| snapshot.jump_targets(kind).len()) {
+      let target = jump
+        .take_jump()
+        .and_then(|i| snapshot.jump_targets(jump.kind()).into_iter().nth(i));
+      if let Some((marker_id, ..)) = target {
+        let (entry_id, _kind) = snapshot.markers()[marker_id];
+        let selection = MarkerSelection::new(marker_id, entry_id, None);
+        Self::select_marker(
+          &selection,
+          markers,
+          log_scroll,
+          log_scroll_state,
+          viewport,
+          &view,
+        );
+      }
+      return;
+    }
+    if ArtifactList::handle_key(key, artifacts, snapshot.artifacts().len()) {
+      return;
+    }
+    if InfoPopup::handle_key(key, info) {
+      return;
+    }
+    if HistoryList::handle_key(key, history) {
+      return;
+    }
+    if StatusHistoryList::handle_key(key, status_history, status_records) {
+      return;
+    }
+    if LongLinePopup::handle_key(key, long_line) {
+      return;
+    }
+    if RuleEditor::handle_key(key, rule_editor) {
+      return;
+    }
+    if RulePicker::handle_key(key, rule_picker, || {
+      (
+        Self::score_rules(snapshot),
+        Self::rule_name(snapshot.rule()),
+      )
+    }) {
+      return;
+    }
+    if key.code == KeyCode::Esc && quit.dismiss() {
+      return;
+    }
+    // the line of the cursor, the selected block, or the top row
+    let current_entry = targeted
+      .or(view.entry_at(*log_scroll).map(EntryId))
+      .unwrap_or_default();
+    let current = || {
+      snapshot
+        .entry(current_entry)
+        .map(|entry| (current_entry, entry.message().clone()))
+    };
+    if BookmarkList::handle_key(key, bookmarks, Instant::now(), current) {
+      if let Some(entry_id) = bookmarks.take_jump() {
+        if let Some(block) = snapshot.block_at(entry_id) {
+          markers.select(block.marker_id(), None);
+        }
+        *log_scroll = view.row(entry_id.0);
+        *log_scroll_state = log_scroll_state.position(*log_scroll);
+      }
+      return;
+    }
+    let selected_block = || {
+      let block = snapshot.block_at(targeted?)?;
+      Some(Pin::new(
+        EntryId(block.range().start),
+        block.marker_id(),
+        block.first_line().unwrap_or_default(),
+      ))
+    };
+    if PinList::handle_key(key, pins, selected_block) {
+      return;
+    }
+    let kinds = MarkerToggles::kinds(&snapshot.rule().markers);
+    if MarkerToggles::handle_key(key, toggles, &kinds, markers) {
+      return;
+    }
+    if FilePreview::handle_key(key, preview) {
+      return;
+    }
+    if TimelineState::handle_key(key, timeline, elapsed) {
+      return;
+    }
+    if SplitState::handle_key(key, split, log_scroll, log_scroll_state) {
+      return;
+    }
+    let (selected, scroll_before) = (markers.selected_entry(), *log_scroll);
+    if key.code == KeyCode::Char('P') {
+      match targeted
+        .and_then(|entry_id| snapshot.entry(entry_id))
+        .and_then(|entry| entry.locations().next())
+      {
+        Some(location) => preview.open(location.clone()),
+        None => crate::dbg!("No location to preview for {:?}", markers.selection()),
+      }
+    } else if key.code == KeyCode::Char('r') {
+      *rerun = true;
+    } else if key.code == KeyCode::Char('X') {
+      *fix = true;
+    } else if key.code == KeyCode::Char('y') {
+      *copy_allow = true;
+    } else if key.code == KeyCode::Char('c') {
+      *compact_toggled = Some(!compact);
+    } else if key.code == KeyCode::Char('#') {
+      *show_ruler = !*show_ruler;
+    } else if key.code == KeyCode::Char('z') || key.code == KeyCode::F(11) {
+      *zen = !*zen;
+    } else if key.code == KeyCode::Char('x') {
+      // the targeted line when cut at the display cap, or the first cut line shown
+      let is_cut = |entry_id: &EntryId| {
+        snapshot
+          .line(*entry_id)
+          .is_some_and(|line| line.is_truncated())
+      };
+      let cut = match targeted {
+        Some(entry_id) => Some(entry_id).filter(is_cut),
+        None => (*log_scroll..*log_scroll + viewport.height())
+          .filter_map(|row| view.entry_at(row).map(EntryId))
+          .find(is_cut),
+      };
+      if let Some(entry_id) = cut {
+        long_line.open(entry_id, None);
+        return;
+      }
+      // the fold of the targeted block, or the first one from the top row
+      let entry_id = targeted
+        .or(view.entry_at(*log_scroll).map(EntryId))
+        .unwrap_or_default();
+      let block = snapshot.block_at(entry_id).map(|block| block.range());
+      let fold = snapshot
+        .folds()
+        .into_iter()
+        .find(|fold| match block.as_ref() {
+          Some(block) => block.contains(&fold.range.start),
+          None => fold.range.start >= entry_id.0,
+        });
+      if let Some(fold) = fold {
+        if !expanded_folds.remove(&fold.range.start) {
+          expanded_folds.insert(fold.range.start);
+        }
+      }
+    } else if key.code == KeyCode::Char('u') {
+      *fold_repeats = !*fold_repeats;
+      crate::dbg!("Grouping the repeated lines: {}", *fold_repeats);
+    } else if key.code == KeyCode::Char('D') {
+      *dedup_targets = !*dedup_targets;
+      crate::dbg!(
+        "Folding the blocks repeated by another target: {}",
+        *dedup_targets
+      );
+    } else if key.code == KeyCode::Enter && *fold_repeats {
+      // the run of the targeted entry, or the first one from the top row
+      let selected = targeted;
+      let entry_id = selected
+        .or(view.entry_at(*log_scroll).map(EntryId))
+        .unwrap_or_default();
+      let run = snapshot.repeats().into_iter().find(|run| match selected {
+        Some(_) => run.range.contains(&entry_id.0),
+        None => run.range.end > entry_id.0,
+      });
+      if let Some(run) = run {
+        if !expanded_folds.remove(&run.range.start) {
+          expanded_folds.insert(run.range.start);
+        }
+      }
+    } else if key.code == KeyCode::Char('A') {
+      set_full_paths(!full_paths());
+      crate::dbg!("Full paths: {}", full_paths());
+    } else if key.code == KeyCode::Char('q') {
+      if !quit.press(building, Instant::now()) {
+        return;
+      }
       if let Err(e) = user_quit.send(true) {
         Debug::log(format!("failed to quit app, {}", e));
       }
@@ -393,25 +2090,42 @@ impl Renderer {
         *filter = Some(BuildTagKind::Error);
         crate::dbg!("Filtering log entries with {:?}", filter.as_ref().unwrap());
       }
+    } else if key.code == KeyCode::Char('b') {
+      *filter = match *filter {
+        Some(BuildTagKind::BuildScript) => None,
+        _ => Some(BuildTagKind::BuildScript),
+      };
+      crate::dbg!("Filtering log entries with {:?}", filter);
+    } else if key.code == KeyCode::Char('F') {
+      // resuming jumps back to the bottom, see FollowPolicy::scroll
+      follow.toggle();
+      crate::dbg!("Following the output: {}", follow.is_following());
     } else if key.code == KeyCode::Char('e') {
       if let Some(sel) = Self::find_first_marker(markers, BuildTagKind::Error) {
-        Self::select_marker(&sel, markers, log_scroll, log_scroll_state, log_area);
+        Self::select_marker(&sel, markers, log_scroll, log_scroll_state, viewport, &view);
       }
     } else if key.code == KeyCode::Char('w') {
       if let Some(sel) = Self::find_first_marker(markers, BuildTagKind::Warning) {
-        Self::select_marker(&sel, markers, log_scroll, log_scroll_state, log_area);
+        Self::select_marker(&sel, markers, log_scroll, log_scroll_state, viewport, &view);
       }
     } else if key.code == KeyCode::Char('n') {
       if let Some(sel) = Self::find_first_marker(markers, BuildTagKind::Note) {
-        Self::select_marker(&sel, markers, log_scroll, log_scroll_state, log_area);
+        Self::select_marker(&sel, markers, log_scroll, log_scroll_state, viewport, &view);
       }
+    } else if key.code == KeyCode::Char('v') {
+      cursor.toggle();
+      crate::dbg!("Line cursor: {}", cursor.is_enabled());
     } else if key.code == KeyCode::Char('j') {
-      if *log_scroll < build_lines.len().saturating_sub(log_area.height as usize) {
-        *log_scroll = log_scroll.saturating_add(1);
-        *log_scroll_state = log_scroll_state.position(*log_scroll);
-      }
+      *log_scroll = match cursor.is_enabled() {
+        true => cursor.move_down(1, &view, *log_scroll, viewport),
+        false => viewport.scroll_down(*log_scroll, 1, view.len()),
+      };
+      *log_scroll_state = log_scroll_state.position(*log_scroll);
     } else if key.code == KeyCode::Char('k') {
-      *log_scroll = log_scroll.saturating_sub(1);
+      *log_scroll = match cursor.is_enabled() {
+        true => cursor.move_up(1, &view, *log_scroll, viewport),
+        false => log_scroll.saturating_sub(1),
+      };
       *log_scroll_state = log_scroll_state.position(*log_scroll);
     } else if key.code == KeyCode::End {
       crate::dbg!("goto end");
@@ -424,9 +2138,9 @@ impl Renderer {
           markers
         );
         let entry_id = markers.selected_entry().unwrap_or_default();
-        Self::scroll_to_element(entry_id, log_scroll, log_area);
+        *log_scroll = viewport.reveal(view.row(entry_id.0), *log_scroll, view.len());
       } else {
-        *log_scroll = build_lines.len().saturating_sub(log_area.height as usize);
+        *log_scroll = viewport.bottom(view.len());
       }
       crate::dbg!("scroll to line {}", *log_scroll);
       *log_scroll_state = log_scroll_state.position(*log_scroll);
@@ -440,27 +2154,54 @@ impl Renderer {
           markers.selected_entry()
         );
         let entry_id = markers.selected_entry().unwrap_or_default();
-        Self::scroll_to_element(entry_id, log_scroll, log_area);
+        *log_scroll = viewport.reveal(view.row(entry_id.0), *log_scroll, view.len());
       } else {
         *log_scroll = 0;
       }
       crate::dbg!("scroll to line {}", *log_scroll);
       *log_scroll_state = log_scroll_state.position(*log_scroll);
     } else if key.code == KeyCode::PageUp {
-      *log_scroll = log_scroll.saturating_sub(log_area.height as usize);
+      *log_scroll = viewport.page_up(*log_scroll);
       *log_scroll_state = log_scroll_state.position(*log_scroll);
+      cursor.keep_in_view(&view, *log_scroll, viewport);
     } else if key.code == KeyCode::PageDown {
-      if *log_scroll < build_lines.len().saturating_sub(log_area.height as usize) {
-        *log_scroll = log_scroll.saturating_add(log_area.height as usize);
-        *log_scroll_state = log_scroll_state.position(*log_scroll);
-      }
+      *log_scroll = viewport.page_down(*log_scroll, view.len());
+      *log_scroll_state = log_scroll_state.position(*log_scroll);
+      cursor.keep_in_view(&view, *log_scroll, viewport);
     } else if key.code == KeyCode::Up {
       if let Some(previous) = markers.previous_selection() {
-        Self::select_marker(&previous, markers, log_scroll, log_scroll_state, log_area);
+        Self::select_marker(
+          &previous,
+          markers,
+          log_scroll,
+          log_scroll_state,
+          viewport,
+          &view,
+        );
       }
     } else if key.code == KeyCode::Down {
       if let Some(next) = markers.next_selection() {
-        Self::select_marker(&next, markers, log_scroll, log_scroll_state, log_area);
+        Self::select_marker(
+          &next,
+          markers,
+          log_scroll,
+          log_scroll_state,
+          viewport,
+          &view,
+        );
+      }
+    }
+    if split.is_enabled() && markers.selected_entry() != selected {
+      if let Some(entry_id) = markers.selected_entry() {
+        // the scroll was computed for the merged view, move the pane of the marker instead
+        *log_scroll = scroll_before;
+        split.reveal(
+          entry_id.0,
+          display,
+          log_scroll,
+          log_scroll_state,
+          viewport.height(),
+        );
       }
     }
   }
@@ -470,21 +2211,230 @@ impl Renderer {
     markers: &mut Markers,
     scroll: &mut usize,
     state: &mut ScrollbarState,
-    log_area: &Rect,
+    viewport: Viewport,
+    view: &DisplayMap,
   ) {
     if markers.is_empty() {
-      *scroll = selection.entry_id;
+      *scroll = view.row(selection.entry_id.0);
       *state = state.position(*scroll);
     } else {
       markers.select(selection.marker_id, selection.region.clone());
-      let entry_id = markers.selected_entry().unwrap_or_default();
-      if entry_id >= *scroll + (log_area.height as usize) {
-        *scroll = entry_id;
-        *state = state.position(*scroll);
-      } else if entry_id < *scroll {
-        *scroll = scroll.saturating_sub(log_area.height as usize);
-        *state = state.position(*scroll);
-      }
+      let row = view.row(markers.selected_entry().unwrap_or_default().0);
+      *scroll = viewport.reveal(row, *scroll, view.len());
+      *state = state.position(*scroll);
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    cell::RefCell,
+    fs::{read_dir, remove_dir_all},
+    sync::mpsc::channel,
+    time::Duration,
+  };
+
+  use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    style::Style,
+    widgets::ScrollbarState,
+  };
+
+  use crate::{
+    score_rules, BuildOutput, BuildTagKind, Direction, DisplayMap, EntryId, Markers, QuitStep,
+    Rule, RuleEditorAction, RunContext, SearchQuery, SessionInfo, StatusBar, StatusHistoryAction,
+    StatusMessage, StatusPriority, Viewport, DEFAULT_RULES,
+  };
+
+  use super::{KeyContext, Renderer, UiState};
+
+  /// Press `code`, returning whether the app stopped and whether the quit request was sent
+  fn press(code: KeyCode, ui: &mut UiState) -> (bool, bool) {
+    press_with(code, ui, false, &mut Markers::default())
+  }
+
+  /// Press `code` with the `markers`, while a build is `building` or not
+  fn press_with(
+    code: KeyCode,
+    ui: &mut UiState,
+    building: bool,
+    markers: &mut Markers,
+  ) -> (bool, bool) {
+    let mut build = BuildOutput::default();
+    let snapshot = build.snapshot();
+    let (tx_quit, rx_quit) = channel();
+    let mut stop = false;
+    let context = KeyContext {
+      user_quit: tx_quit,
+      viewport: Viewport::new(20),
+      snapshot: &snapshot,
+      display: &DisplayMap::default(),
+      search_query: channel().0,
+      status_records: &[],
+      tester_action: channel().0,
+      elapsed: Duration::ZERO,
+      compact: false,
+      building,
+    };
+    Renderer::handle_key_press(
+      KeyEvent::from(code),
+      ui,
+      context,
+      &mut 0,
+      &mut ScrollbarState::default(),
+      markers,
+      &mut stop,
+    );
+    (stop, rx_quit.try_recv().is_ok())
+  }
+
+  #[test]
+  fn quit_closes_help() {
+    let mut ui = UiState::default();
+    assert_eq!(press(KeyCode::Char('h'), &mut ui), (false, false));
+    assert!(ui.help.is_shown());
+    assert_eq!(press(KeyCode::Down, &mut ui), (false, false));
+    assert_eq!(ui.help.scroll(), 1);
+    assert_eq!(press(KeyCode::Char('q'), &mut ui), (false, false));
+    assert!(!ui.help.is_shown());
+    // once the help is closed, q quits
+    assert_eq!(press(KeyCode::Char('q'), &mut ui), (true, true));
+  }
+
+  #[test]
+  fn quit_while_building() {
+    let mut ui = UiState::default();
+    let press = |code, ui: &mut UiState| press_with(code, ui, true, &mut Markers::default());
+    let q = KeyCode::Char('q');
+    assert_eq!(press(q, &mut ui), (false, false));
+    assert_eq!(ui.quit.take_step(), Some(QuitStep::Prompt));
+    assert_eq!(press(KeyCode::Esc, &mut ui), (false, false));
+    assert_eq!(ui.quit.take_step(), Some(QuitStep::Stay));
+    assert_eq!(press(q, &mut ui), (false, false));
+    // the other keys keep the confirmation pending
+    assert_eq!(press(KeyCode::Down, &mut ui), (false, false));
+    assert_eq!(press(q, &mut ui), (true, true));
+    assert_eq!(ui.quit.take_step(), Some(QuitStep::Cancel));
+  }
+
+  #[test]
+  fn note_after_search() {
+    let snapshot = snapshot_of("warning: unused variable\nnote: `#[warn(unused)]` on by default");
+    let mut markers = snapshot.markers().clone();
+    let mut ui = UiState {
+      last_search: Some(SearchQuery::new("unused", Direction::Forward)),
+      ..UiState::default()
+    };
+    // repeats the search
+    press_with(KeyCode::Char('n'), &mut ui, false, &mut markers);
+    assert_eq!(markers.selected_entry(), None);
+    // dismissed, n shows the first note again
+    press_with(KeyCode::Esc, &mut ui, false, &mut markers);
+    assert_eq!(ui.last_search, None);
+    press_with(KeyCode::Char('n'), &mut ui, false, &mut markers);
+    assert_eq!(markers.selected_entry(), Some(EntryId(1)));
+  }
+
+  #[test]
+  fn save_status_history() {
+    let root = std::env::temp_dir().join(format!("nbuild-runs-status-{}", std::process::id()));
+    let run = RunContext::create_in(&root).unwrap();
+    let session = SessionInfo::default().with_run(Some(&run));
+    let mut bar = StatusBar::default();
+    bar.push_message(StatusMessage::new([("Build running", Style::default())]));
+    let message = Renderer::apply_status_history_action(
+      StatusHistoryAction::Save,
+      &RefCell::new(bar),
+      &session,
+    )
+    .unwrap();
+    let saved = read_dir(run.dir())
+      .unwrap()
+      .flatten()
+      .map(|entry| entry.path())
+      .find(|path| path.extension().is_some_and(|ext| ext == "txt"))
+      .unwrap();
+    assert!(message.text().starts_with("Saved the status history"));
+    // the user was told where to find it, it outlives the run
+    drop(run);
+    assert!(saved.exists());
+    remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn save_builtin_rule() {
+    let (tx, _rx) = channel();
+    let mut generation = 0;
+    let message = Renderer::apply_rule_editor_action(
+      RuleEditorAction::Save(DEFAULT_RULES[0].clone()),
+      &snapshot_of(""),
+      &tx,
+      &mut Markers::default(),
+      &mut generation,
+    );
+    assert_eq!(message.priority(), StatusPriority::High);
+    assert!(message.text().contains("built-in"), "{}", message.text());
+  }
+
+  /// A rule matching the diagnostics of a french gcc
+  fn gcc_fr_rule() -> Rule {
+    Rule::new(
+      ["c: gcc", "gcc"],
+      "make",
+      [
+        (
+          BuildTagKind::Error,
+          regex::Regex::new(r"erreur\s?:").unwrap(),
+          Default::default(),
+        ),
+        (
+          BuildTagKind::Warning,
+          regex::Regex::new(r"attention\s?:").unwrap(),
+          Default::default(),
+        ),
+        (
+          BuildTagKind::Note,
+          regex::Regex::new(r"note\s?:").unwrap(),
+          Default::default(),
+        ),
+      ],
+    )
+  }
+
+  fn snapshot_of(log: &str) -> crate::BuildSnapshot {
+    let mut build = BuildOutput::from(log.lines()).with_noise_removed(false);
+    build.set_rule(DEFAULT_RULES[0].clone());
+    build.prepare();
+    build.snapshot()
+  }
+
+  #[test]
+  fn suggest_rule() {
+    let rules = [DEFAULT_RULES[0].clone(), gcc_fr_rule()];
+    let snapshot = snapshot_of(include_str!("../../../tests/fixtures/c/gcc-fr.log"));
+    assert!(snapshot.markers().is_empty());
+    let lines = snapshot
+      .entries()
+      .map(|entry| entry.message().as_str())
+      .collect::<Vec<_>>();
+    let candidates = score_rules(&rules, &lines);
+    assert_eq!(candidates[0].name, "c: gcc");
+    assert_eq!(candidates[1].score, 0);
+    assert_eq!(
+      Renderer::suggest_rule(&snapshot, &candidates),
+      Some(format!(
+        "no diagnostics matched rule 'rust: cargo'; press R to switch rules (best matches: {})",
+        candidates[0]
+      ))
+    );
+    // the cargo rule matches cargo's output
+    let log = include_str!("../../../tests/fixtures/rust/rust-macro-backtrace.log").repeat(2);
+    let snapshot = snapshot_of(&log);
+    assert!(snapshot.num_entries() > super::RULE_SUGGESTION_MIN_LINES);
+    assert_eq!(Renderer::suggest_rule(&snapshot, &candidates), None);
+    // too short to tell
+    let snapshot = snapshot_of("   Compiling demo v0.1.0\n    Finished dev");
+    assert_eq!(Renderer::suggest_rule(&snapshot, &candidates), None);
+  }
+}
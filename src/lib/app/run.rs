@@ -0,0 +1,332 @@
+use std::{
+  cmp::Reverse,
+  fs::{create_dir_all, read_dir, remove_dir_all, File},
+  io,
+  path::{Path, PathBuf},
+  time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use dirs::cache_dir;
+
+use crate::human_size;
+
+/// A run directory listed by `--runs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunDir {
+  pub path: PathBuf,
+  pub modified: SystemTime,
+  /// The size of the files inside, in bytes
+  pub size: u64,
+}
+
+impl RunDir {
+  /// Format the entry for `--runs`: date, size and path
+  pub fn line(&self) -> String {
+    format!(
+      "{}  {:>9}  {}",
+      DateTime::<Local>::from(self.modified).format("%Y-%m-%d %H:%M:%S"),
+      human_size(self.size),
+      self.path.display()
+    )
+  }
+}
+
+/// The directory owning the files written during one run: the debug log, ...
+///
+/// Created by [`crate::App::run`] in [`RunContext::runs_dir`], it is named after the start
+/// time and the pid so that concurrent runs never share files. It gets removed when dropped,
+/// unless kept with `--keep-artifacts`, after a failure or once the user saved a file there
+#[derive(Debug)]
+pub struct RunContext {
+  dir: PathBuf,
+  keep: bool,
+  debug_log: bool,
+}
+
+impl RunContext {
+  /// The age after which the run directories get pruned
+  pub const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+  /// The name of the debug log inside the run directory
+  pub const DEBUG_LOG: &'static str = "debug.log";
+
+  /// The format of the start time naming the run directories, followed by the pid
+  pub const NAME_FORMAT: &'static str = "%Y%m%d-%H%M%S";
+
+  /// The marker of a run directory kept by [`RunContext::keep_dir`]
+  pub const KEEP_MARKER: &'static str = ".keep";
+
+  /// Retrieve the directory holding the run directories, in the user's cache directory
+  pub fn runs_dir() -> PathBuf {
+    cache_dir()
+      .unwrap_or_else(std::env::temp_dir)
+      .join(env!("CARGO_PKG_NAME"))
+      .join("runs")
+  }
+
+  /// Create the directory of the current run in `root`
+  pub fn create_in<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+    let dir = root.as_ref().join(format!(
+      "{}-{}",
+      Local::now().format(Self::NAME_FORMAT),
+      std::process::id()
+    ));
+    create_dir_all(&dir)?;
+    Ok(Self {
+      dir,
+      keep: false,
+      debug_log: false,
+    })
+  }
+
+  /// Keep the directory once dropped
+  pub fn with_keep(mut self, v: bool) -> Self {
+    self.keep = v;
+    self
+  }
+
+  /// Keep the directory once dropped, e.g. after a failure
+  pub fn keep(&mut self) {
+    self.keep = true;
+  }
+
+  /// Keep the run directory `dir` once its context is dropped, e.g. after the user saved
+  /// a file there: unlike [`RunContext::keep`], callable from any thread
+  pub fn keep_dir<P: AsRef<Path>>(dir: P) -> io::Result<()> {
+    File::create(dir.as_ref().join(Self::KEEP_MARKER)).map(|_| ())
+  }
+
+  /// Whether the directory outlives the run
+  pub fn is_kept(&self) -> bool {
+    self.keep || self.path(Self::KEEP_MARKER).exists()
+  }
+
+  /// Retrieve the directory of the run
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// Retrieve the path of a file owned by the run
+  pub fn path<N: AsRef<Path>>(&self, name: N) -> PathBuf {
+    self.dir.join(name)
+  }
+
+  /// Write the debug log in the run directory, see [`RunContext::DEBUG_LOG`]
+  pub fn capture_debug_log(&mut self) -> io::Result<()> {
+    crate::Debug::redirect(self.path(Self::DEBUG_LOG))?;
+    self.debug_log = true;
+    Ok(())
+  }
+
+  /// Retrieve the path of the debug log, if captured by [`RunContext::capture_debug_log`]
+  pub fn debug_log_path(&self) -> Option<PathBuf> {
+    self.debug_log.then(|| self.path(Self::DEBUG_LOG))
+  }
+
+  /// List the run directories of `root`, the most recent first
+  pub fn list<P: AsRef<Path>>(root: P) -> io::Result<Vec<RunDir>> {
+    let mut ret = vec![];
+    for entry in read_dir(root)?.flatten() {
+      let Ok(metadata) = entry.metadata() else {
+        continue;
+      };
+      if !metadata.is_dir() {
+        continue;
+      }
+      ret.push(RunDir {
+        size: Self::size_of(&entry.path()),
+        path: entry.path(),
+        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+      });
+    }
+    ret.sort_by_key(|run| Reverse(run.modified));
+    Ok(ret)
+  }
+
+  /// Remove the run directories of `root` whose files were not modified for `max_age` at `now`.
+  ///
+  /// Only the directories named by [`RunContext::create_in`] get removed, and a run still
+  /// writing its debug log is kept whatever the age of its directory.
+  /// Returns the removed directories
+  pub fn prune<P: AsRef<Path>>(
+    root: P,
+    max_age: Duration,
+    now: SystemTime,
+  ) -> io::Result<Vec<PathBuf>> {
+    let mut ret = vec![];
+    for run in Self::list(root)? {
+      if !run
+        .path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(Self::is_run_name)
+      {
+        continue;
+      }
+      let modified = run.modified.max(Self::last_modified(&run.path));
+      let age = now.duration_since(modified).unwrap_or_default();
+      if age > max_age && remove_dir_all(&run.path).is_ok() {
+        ret.push(run.path);
+      }
+    }
+    Ok(ret)
+  }
+
+  /// Check if `name` is the one of a run directory: `<start time>-<pid>`, see
+  /// [`RunContext::NAME_FORMAT`]
+  fn is_run_name(name: &str) -> bool {
+    let Some((started_at, pid)) = name.rsplit_once('-') else {
+      return false;
+    };
+    !pid.is_empty()
+      && pid.chars().all(|c| c.is_ascii_digit())
+      && NaiveDateTime::parse_from_str(started_at, Self::NAME_FORMAT).is_ok()
+  }
+
+  /// The most recent modification of the files inside `path`
+  fn last_modified(path: &Path) -> SystemTime {
+    let Ok(entries) = read_dir(path) else {
+      return SystemTime::UNIX_EPOCH;
+    };
+    entries
+      .flatten()
+      .filter_map(|entry| {
+        let metadata = entry.metadata().ok()?;
+        match metadata.is_dir() {
+          true => Some(Self::last_modified(&entry.path()).max(metadata.modified().ok()?)),
+          false => metadata.modified().ok(),
+        }
+      })
+      .max()
+      .unwrap_or(SystemTime::UNIX_EPOCH)
+  }
+
+  fn size_of(path: &Path) -> u64 {
+    let Ok(entries) = read_dir(path) else {
+      return 0;
+    };
+    entries
+      .flatten()
+      .map(|entry| match entry.metadata() {
+        Ok(metadata) if metadata.is_dir() => Self::size_of(&entry.path()),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+      })
+      .sum()
+  }
+}
+
+impl Drop for RunContext {
+  fn drop(&mut self) {
+    if self.is_kept() || std::thread::panicking() {
+      return;
+    }
+    if self.debug_log {
+      crate::Debug::close();
+    }
+    if let Err(e) = remove_dir_all(&self.dir) {
+      eprintln!(
+        "\x1b[90mnbuild:\x1b[0m failed to remove {}, {}",
+        self.dir.display(),
+        e
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{create_dir_all, remove_dir_all, write, File},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+  };
+
+  use super::RunContext;
+
+  fn root(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("nbuild-runs-{}-{}", test, std::process::id()));
+    let _ = remove_dir_all(&root);
+    root
+  }
+
+  #[test]
+  fn cleanup_on_drop() {
+    let root = root("drop");
+    let run = RunContext::create_in(&root).unwrap();
+    let path = run.path("spill.bin");
+    assert_eq!(path.parent(), Some(run.dir()));
+    write(&path, "spilled").unwrap();
+    let dir = run.dir().to_path_buf();
+    drop(run);
+    assert!(!dir.exists());
+
+    // kept after a failure
+    let mut run = RunContext::create_in(&root).unwrap();
+    run.keep();
+    let dir = run.dir().to_path_buf();
+    drop(run);
+    assert!(dir.exists());
+    let runs = RunContext::list(&root).unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].path, dir);
+
+    // kept once the user saved a file there
+    let run = RunContext::create_in(&root).unwrap();
+    RunContext::keep_dir(run.dir()).unwrap();
+    assert!(run.is_kept());
+    let dir = run.dir().to_path_buf();
+    drop(run);
+    assert!(dir.exists());
+    let _ = remove_dir_all(&root);
+  }
+
+  #[test]
+  fn prune() {
+    let root = root("prune");
+    let now = SystemTime::now();
+    let day = Duration::from_secs(24 * 60 * 60);
+    for (name, age) in [
+      ("20260101-120000-41", day * 10),
+      ("20260110-120000-42", day),
+      ("20260111-120000-43", Duration::ZERO),
+      // not a run directory
+      ("notes", day * 11),
+    ] {
+      let dir = root.join(name);
+      create_dir_all(&dir).unwrap();
+      let log = File::create(dir.join("debug.log")).unwrap();
+      log.set_len(name.len() as u64).unwrap();
+      log.set_modified(now - age).unwrap();
+      File::open(&dir).unwrap().set_modified(now - age).unwrap();
+    }
+    let runs = RunContext::list(&root).unwrap();
+    assert_eq!(
+      runs
+        .iter()
+        .map(|run| (run.path.file_name().unwrap().to_str().unwrap(), run.size))
+        .collect::<Vec<_>>(),
+      vec![
+        ("20260111-120000-43", 18),
+        ("20260110-120000-42", 18),
+        ("20260101-120000-41", 18),
+        ("notes", 5)
+      ]
+    );
+    // an old run still writing its debug log
+    let live = root.join("20260101-120000-44");
+    create_dir_all(&live).unwrap();
+    File::open(&live)
+      .unwrap()
+      .set_modified(now - day * 10)
+      .unwrap();
+    write(live.join("debug.log"), "live").unwrap();
+    assert_eq!(
+      RunContext::prune(&root, RunContext::MAX_AGE, now).unwrap(),
+      vec![root.join("20260101-120000-41")]
+    );
+    assert_eq!(RunContext::list(&root).unwrap().len(), 4);
+    let _ = remove_dir_all(&root);
+  }
+}
@@ -1,53 +1,146 @@
+#[cfg(not(unix))]
+use std::io::IsTerminal;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
 use std::{
-  io::{stdin, BufRead, BufReader},
+  fs::File,
+  io::{stdin, BufReader, ErrorKind, Read},
   process::ExitStatus,
-  sync::mpsc::Sender,
+  sync::mpsc::{Sender, SyncSender},
   thread::spawn,
+  time::Duration,
 };
 
-use crate::{BuildEntry, BuildEvent, Debug, Origin};
+use crate::{
+  err, read_line_timed, BuildEntry, BuildEvent, BuildFailure, Debug, IntakeFilter, IntakeStats,
+  Origin,
+};
 
+/// Reads a build log instead of running the build: stdin, or the file given with [`Self::with_file`]
 pub struct Scanner {
   origin: Origin,
-  tx_entries: Sender<Vec<BuildEntry>>,
+  file: Option<File>,
+  intake_filter: Option<IntakeFilter>,
+  tx_entries: SyncSender<BuildEntry>,
   tx_events: Sender<BuildEvent>,
 }
 
 const THREADED_SCANNER: bool = false;
 
 impl Scanner {
+  /// The time without any piped input after which the user gets a hint
+  pub const IDLE_HINT_DELAY: Duration = Duration::from_secs(3);
+  /// The hint shown when nothing was piped, see [`Self::IDLE_HINT_DELAY`]
+  pub const IDLE_HINT: &'static str = "waiting for piped input… did you mean to run a build?";
+
   pub fn new(
     origin: Origin,
-    tx_entries: Sender<Vec<BuildEntry>>,
+    tx_entries: SyncSender<BuildEntry>,
     tx_events: Sender<BuildEvent>,
   ) -> Self {
     Self {
       origin,
+      file: None,
+      intake_filter: None,
       tx_entries,
       tx_events,
     }
   }
 
-  /// The stdin scanner thread
-  pub fn run(self) {
+  /// Read a build log file instead of stdin
+  pub fn with_file(mut self, file: File) -> Self {
+    self.file = Some(file);
+    self
+  }
+
+  /// Drop the noise lines of the log with `filter`, see [`IntakeFilter`]
+  pub fn with_intake_filter(mut self, filter: Option<IntakeFilter>) -> Self {
+    self.intake_filter = filter;
+    self
+  }
+
+  /// Check if `input` has a build log to read: a redirected file, or a pipe
+  /// with data available within `timeout`. A terminal or `/dev/null` has none
+  #[cfg(unix)]
+  pub fn has_input<F: AsRawFd>(input: &F, timeout: Duration) -> bool {
+    let fd = input.as_raw_fd();
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+      return false;
+    }
+    match stat.st_mode & libc::S_IFMT {
+      libc::S_IFREG => return true,
+      libc::S_IFIFO | libc::S_IFSOCK => {}
+      _ => return false,
+    }
+    let mut poll = libc::pollfd {
+      fd,
+      events: libc::POLLIN,
+      revents: 0,
+    };
+    let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    unsafe { libc::poll(&mut poll, 1, timeout) > 0 && poll.revents & libc::POLLIN != 0 }
+  }
+
+  /// Check if `input` has a build log to read, anything but a terminal
+  #[cfg(not(unix))]
+  pub fn has_input<F: IsTerminal>(input: &F, _timeout: Duration) -> bool {
+    !input.is_terminal()
+  }
+
+  /// The scanner thread
+  pub fn run(mut self) -> crate::Result<()> {
     crate::dbg!("scan thread started on {:?}", self.origin);
     let _ = self.tx_events.send(BuildEvent::BuildStarted);
     Debug::log("spawned cargo process");
-    let buf = BufReader::new(stdin());
     let entries = self.tx_entries.clone();
+    let events = self.tx_events.clone();
+    let input: Box<dyn Read + Send> = match self.file.take() {
+      Some(file) => Box::new(file),
+      None => Box::new(stdin()),
+    };
+    let mut filter = self.intake_filter.take();
     let f = move || {
-      for line in buf.lines() {
-        let line = line.expect("invalid input line").replace("\x00", "");
+      let mut buf = BufReader::new(input);
+      let mut stats = IntakeStats::new(self.origin);
+      loop {
+        let (at, line) = match read_line_timed(&mut buf) {
+          Ok(Some(line)) => line,
+          Ok(None) => break,
+          Err(e) => {
+            let failure = match e.kind() {
+              ErrorKind::InvalidData => BuildFailure::InvalidLine(self.origin),
+              _ => BuildFailure::Unreadable(self.origin),
+            };
+            Debug::log(format!("error: {}, {}", failure, e));
+            let _ = events.send(BuildEvent::BuildFailed(failure));
+            match failure {
+              BuildFailure::InvalidLine(_) => continue,
+              _ => break,
+            }
+          }
+        };
+        stats.record_line(&line, at);
+        let line = line.replace("\x00", "");
+        if filter.as_mut().is_some_and(|filter| !filter.keep(&line)) {
+          stats.dropped += 1;
+          continue;
+        }
         // crate::dbg!("[stdin] {}", line);
-        let _ = entries.send(vec![BuildEntry::new(line, self.origin)]);
+        let _ = entries.send(BuildEntry::with_timestamp(line, self.origin, at));
       }
+      Debug::log(format!("intake {}", stats));
+      let _ = events.send(BuildEvent::IntakeStats(stats));
     };
     if THREADED_SCANNER {
       let thread = spawn(f);
       Debug::log("Waiting for scanner thread");
-      thread
-        .join()
-        .expect("failed to join process scanner thread");
+      thread.join().map_err(|_| {
+        err!(
+          crate::ErrorKind::Thread,
+          "the process scanner thread panicked"
+        )
+      })?;
     } else {
       f();
     }
@@ -55,5 +148,99 @@ impl Scanner {
     let _ = self.tx_events.send(BuildEvent::BuildFinished(exit_status));
     Debug::log(format!("Exit status: {}", exit_status));
     Debug::log("scan thread stopped");
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{remove_file, write, File},
+    path::Path,
+    sync::mpsc::{channel, sync_channel},
+    time::Duration,
+  };
+
+  use crate::{BuildEntry, BuildEvent, IntakeFilter, Origin, DEFAULT_RULES};
+
+  use super::Scanner;
+
+  #[test]
+  fn drop_noise() {
+    let path = std::env::temp_dir().join(format!("nbuild-noise-{}.log", std::process::id()));
+    std::fs::copy(
+      Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rust/rust-spinner.log"),
+      &path,
+    )
+    .unwrap();
+    let (tx_entries, rx_entries) = sync_channel::<BuildEntry>(4096);
+    let (tx_events, rx_events) = channel::<BuildEvent>();
+    Scanner::new(Origin::Stdin, tx_entries, tx_events)
+      .with_file(File::open(&path).unwrap())
+      .with_intake_filter(Some(IntakeFilter::new(&DEFAULT_RULES[0])))
+      .run()
+      .unwrap();
+    let _ = remove_file(&path);
+    let kept = rx_entries.try_iter().count();
+    let stats = rx_events
+      .try_iter()
+      .find_map(|event| match event {
+        BuildEvent::IntakeStats(stats) => Some(stats),
+        _ => None,
+      })
+      .expect("no intake stats");
+    assert_eq!(stats.lines, 2212);
+    assert_eq!(stats.lines, kept + stats.dropped);
+    assert_eq!(kept, 14);
+  }
+
+  #[test]
+  fn scan_file() {
+    let path = std::env::temp_dir().join(format!("nbuild-scan-{}.log", std::process::id()));
+    write(&path, "warning: unused variable\r\n  --> src/main.rs:2:7\n").unwrap();
+    let (tx_entries, rx_entries) = sync_channel::<BuildEntry>(16);
+    let (tx_events, rx_events) = channel::<BuildEvent>();
+    Scanner::new(Origin::Stdin, tx_entries, tx_events)
+      .with_file(File::open(&path).unwrap())
+      .run()
+      .unwrap();
+    let _ = remove_file(&path);
+    let lines = rx_entries
+      .try_iter()
+      .map(|entry| entry.message().clone())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      lines,
+      vec!["warning: unused variable", "  --> src/main.rs:2:7"]
+    );
+    let events = rx_events.try_iter().collect::<Vec<_>>();
+    assert!(matches!(events.first(), Some(BuildEvent::BuildStarted)));
+    assert!(matches!(events.last(), Some(BuildEvent::BuildFinished(_))));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn has_input() {
+    use std::io::{pipe, Write};
+
+    let timeout = Duration::from_millis(50);
+    let (reader, mut writer) = pipe().unwrap();
+    assert!(!Scanner::has_input(&reader, timeout));
+    writer.write_all(b"error: oops\n").unwrap();
+    assert!(Scanner::has_input(&reader, timeout));
+    // the redirections that never produce a log
+    assert!(!Scanner::has_input(
+      &File::open("/dev/null").unwrap(),
+      timeout
+    ));
+    let (reader, writer) = pipe().unwrap();
+    drop(writer);
+    assert!(!Scanner::has_input(&reader, timeout));
+    // a redirected file is read even if empty
+    let path = std::env::temp_dir().join(format!("nbuild-input-{}.log", std::process::id()));
+    write(&path, "").unwrap();
+    let file = File::open(&path).unwrap();
+    let _ = remove_file(&path);
+    assert!(Scanner::has_input(&file, timeout));
   }
 }
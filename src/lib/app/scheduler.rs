@@ -0,0 +1,184 @@
+use std::time::{Duration, Instant};
+
+use super::AppOptions;
+
+/// Decide when the [`super::Preparer`] prepares the pending entries, and how many at once.
+///
+/// A trickle of lines gets prepared by groups of `min_pending` entries or after `max_delay`,
+/// a burst gets spread over several calls of at most `max_batch` entries. When the lines
+/// keep arriving faster than that, each call takes what arrives during `max_delay`
+/// so that the backlog does not grow
+#[derive(Debug, Clone)]
+pub struct PrepareScheduler {
+  min_pending: usize,
+  max_delay: Duration,
+  max_batch: usize,
+  /// The smoothed incoming rate, in lines per second
+  rate: f64,
+  /// The start of the current rate window and the lines received since
+  window: Option<(Instant, usize)>,
+  last_prepare: Option<Instant>,
+}
+
+impl Default for PrepareScheduler {
+  fn default() -> Self {
+    Self::new(Self::MIN_PENDING, Self::MAX_DELAY, Self::MAX_BATCH)
+  }
+}
+
+impl PrepareScheduler {
+  /// The number of pending entries prepared without waiting for [`Self::MAX_DELAY`]
+  pub const MIN_PENDING: usize = 32;
+
+  /// The longest time the pending entries wait for more
+  pub const MAX_DELAY: Duration = Duration::from_millis(30);
+
+  /// The number of entries prepared per call, unless the lines arrive faster
+  pub const MAX_BATCH: usize = 2000;
+
+  /// The weight of the last window in the smoothed rate
+  const RATE_SMOOTHING: f64 = 0.3;
+
+  pub fn new(min_pending: usize, max_delay: Duration, max_batch: usize) -> Self {
+    Self {
+      min_pending,
+      max_delay,
+      max_batch: max_batch.max(1),
+      rate: 0.0,
+      window: None,
+      last_prepare: None,
+    }
+  }
+
+  /// Construct the scheduler with the knobs of the options
+  pub fn from_options(options: &AppOptions) -> Self {
+    Self::new(
+      options.prepare_min_pending,
+      options.prepare_max_delay,
+      options.prepare_max_batch,
+    )
+  }
+
+  /// Retrieve the smoothed incoming rate, in lines per second
+  #[cfg(test)]
+  pub fn rate(&self) -> f64 {
+    self.rate
+  }
+
+  /// Record `count` entries received at `now`.
+  ///
+  /// The rate gets sampled once per window of `max_delay`, so that a single burst
+  /// does not count as a sustained rate
+  pub fn arrived(&mut self, count: usize, now: Instant) {
+    let (start, received) = self.window.get_or_insert((now, 0));
+    *received += count;
+    let elapsed = now.saturating_duration_since(*start);
+    if elapsed >= self.max_delay && !elapsed.is_zero() {
+      let sample = *received as f64 / elapsed.as_secs_f64();
+      self.rate = self.rate * (1.0 - Self::RATE_SMOOTHING) + sample * Self::RATE_SMOOTHING;
+      self.window = Some((now, 0));
+    }
+  }
+
+  /// Retrieve the most entries prepared per call: `max_batch`,
+  /// or what arrives during `max_delay` at the current rate
+  pub fn batch_size(&self) -> usize {
+    let sustained = (self.rate * self.max_delay.as_secs_f64()).ceil() as usize;
+    self.max_batch.max(sustained)
+  }
+
+  /// Plan the preparation of the `pending` entries at `now`.
+  ///
+  /// Returns the number of entries to prepare, none to wait for more
+  pub fn plan(&mut self, pending: usize, now: Instant) -> Option<usize> {
+    if pending == 0 {
+      return None;
+    }
+    let recent = self
+      .last_prepare
+      .is_some_and(|last| now.saturating_duration_since(last) < self.max_delay);
+    if pending < self.min_pending && recent {
+      return None;
+    }
+    self.last_prepare = Some(now);
+    Some(pending.min(self.batch_size()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use super::PrepareScheduler;
+
+  /// Simulate the preparer loop: every `tick`, `arrivals` gives the lines received
+  /// and the scheduler plans the preparation of the backlog.
+  ///
+  /// Returns the size of each call and the backlog left
+  fn simulate<F: Fn(usize) -> usize>(
+    scheduler: &mut PrepareScheduler,
+    ticks: usize,
+    tick: Duration,
+    arrivals: F,
+  ) -> (Vec<usize>, usize) {
+    let start = Instant::now();
+    let mut pending = 0;
+    let mut calls = vec![];
+    for i in 0..ticks {
+      let now = start + tick * i as u32;
+      let count = arrivals(i);
+      pending += count;
+      scheduler.arrived(count, now);
+      if let Some(n) = scheduler.plan(pending, now) {
+        calls.push(n);
+        pending -= n;
+      }
+    }
+    (calls, pending)
+  }
+
+  #[test]
+  fn burst() {
+    let mut scheduler = PrepareScheduler::default();
+    let (calls, pending) = simulate(&mut scheduler, 20, Duration::from_millis(5), |i| match i {
+      0 => 10_000,
+      _ => 0,
+    });
+    assert_eq!(calls, vec![2000; 5]);
+    assert_eq!(pending, 0);
+  }
+
+  #[test]
+  fn trickle() {
+    // one line every 10ms during a second
+    let mut scheduler = PrepareScheduler::default();
+    let (calls, pending) = simulate(&mut scheduler, 200, Duration::from_millis(5), |i| {
+      (i % 2 == 0) as usize
+    });
+    // the lines get prepared by groups of 3, every 30ms
+    assert_eq!(calls.len(), 34);
+    assert_eq!(calls[0], 1);
+    assert!(calls[1..].iter().all(|n| *n == 3));
+    assert_eq!(pending, 0);
+
+    // a quick trickle is prepared by groups of the minimum
+    let mut scheduler = PrepareScheduler::new(4, Duration::from_millis(30), 100);
+    let (calls, _pending) = simulate(&mut scheduler, 100, Duration::from_millis(5), |_i| 2);
+    assert_eq!(calls.len(), 50);
+    assert!(calls[1..].iter().all(|n| *n == 4));
+  }
+
+  #[test]
+  fn sustained() {
+    // 5000 lines every 5ms, faster than the batches
+    let mut scheduler = PrepareScheduler::default();
+    let (calls, pending) = simulate(&mut scheduler, 200, Duration::from_millis(5), |_i| 5000);
+    assert!(scheduler.rate() > 900_000.0);
+    assert!(scheduler.batch_size() > PrepareScheduler::MAX_BATCH);
+    assert_eq!(calls.len(), 200);
+    // the calls grow with the rate, the backlog stays bounded
+    assert_eq!(calls[0], 2000);
+    assert!(calls.iter().all(|n| *n <= scheduler.batch_size()));
+    assert!(pending < 5000, "backlog of {} entries", pending);
+  }
+}
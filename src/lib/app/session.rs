@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use super::RunContext;
+
+/// Where the current session reads and writes its files, shown by the info popup (`F1`).
+///
+/// Built by [`crate::App::run`] once the rules are loaded, then handed to the [`crate::Renderer`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionInfo {
+  /// The config files the rules were loaded from, in precedence order. The built-in
+  /// rules come first, the aliases they define win over the files
+  pub config_files: Vec<PathBuf>,
+  /// The debug log, `None` when kept in memory
+  pub debug_log: Option<PathBuf>,
+  /// The run directory, `None` when it could not be created
+  pub run_dir: Option<PathBuf>,
+}
+
+impl SessionInfo {
+  /// Record a config file the rules were loaded from
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn with_config_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.config_files.push(path.into());
+    self
+  }
+
+  /// Define the debug log path
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn with_debug_log<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.debug_log = Some(path.into());
+    self
+  }
+
+  /// Define the run directory
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn with_run_dir<P: Into<PathBuf>>(mut self, path: P) -> Self {
+    self.run_dir = Some(path.into());
+    self
+  }
+
+  /// Fill the run directory and the debug log from the [`RunContext`]
+  pub fn with_run(mut self, run: Option<&RunContext>) -> Self {
+    self.run_dir = run.map(|run| run.dir().to_path_buf());
+    self.debug_log = run.and_then(|run| run.debug_log_path());
+    self
+  }
+}
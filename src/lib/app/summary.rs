@@ -0,0 +1,365 @@
+use std::{fmt::Display, process::ExitStatus, str::FromStr, time::Duration};
+
+use crate::{
+  err, format_count, human_duration, Artifact, BuildOutput, BuildSnapshot, CrateFailure, ErrorKind,
+  ExitCause, TargetCounts,
+};
+
+/// How the process exit code gets computed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExitCodeMode {
+  /// Mirror the build's exit status
+  #[default]
+  Build,
+  /// Also fail if any error was detected
+  Errors,
+  /// Also fail if any error or warning was detected
+  Warnings,
+}
+
+impl FromStr for ExitCodeMode {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "build" => Ok(Self::Build),
+      "errors" => Ok(Self::Errors),
+      "warnings" => Ok(Self::Warnings),
+      _ => Err(err!(
+        ErrorKind::Parsing,
+        "invalid exit code mode '{}', expected one of: build, errors, warnings",
+        s
+      )),
+    }
+  }
+}
+
+impl Display for ExitCodeMode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Self::Build => "build",
+        Self::Errors => "errors",
+        Self::Warnings => "warnings",
+      }
+    )
+  }
+}
+
+impl ExitCodeMode {
+  /// Compute the process exit code for the supplied [`BuildSummary`]
+  pub fn exit_code(&self, summary: &BuildSummary) -> u8 {
+    let build_code = match summary.exit_status {
+      Some(status) if !status.success() => status.code().unwrap_or(1).clamp(1, 255) as u8,
+      _ => 0,
+    };
+    if build_code != 0 {
+      return build_code;
+    }
+    match self {
+      Self::Build => 0,
+      Self::Errors => (summary.num_errors > 0) as u8,
+      Self::Warnings => (summary.num_errors > 0 || summary.num_warnings > 0) as u8,
+    }
+  }
+}
+
+/// The outcome of a build, reported when the application exits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildSummary {
+  /// The build's exit status, if it finished
+  pub exit_status: Option<ExitStatus>,
+  /// The number of detected errors, the ones repeated by another target counted once
+  pub num_errors: usize,
+  /// The number of detected warnings, the ones repeated by another target counted once
+  pub num_warnings: usize,
+  /// The number of detected notes
+  pub num_notes: usize,
+  /// The crates that failed to build
+  pub failed_crates: Vec<CrateFailure>,
+  /// The files produced by the build
+  pub artifacts: Vec<Artifact>,
+  /// The diagnostics of each compiled target
+  pub targets: Vec<TargetCounts>,
+  /// The time the build took, or was followed for
+  pub duration: Duration,
+}
+
+impl BuildSummary {
+  /// The format of [`BuildSummary::script_line`], stable across versions
+  pub const SCRIPT_LINE_FORMAT: &'static str =
+    "nbuild: exit=<code> errors=<N> warnings=<N> notes=<N> duration=<secs>";
+
+  /// Construct a summary from the counts of a prepared [`BuildOutput`]
+  pub fn new(build: &BuildOutput, exit_status: Option<ExitStatus>) -> Self {
+    Self {
+      exit_status,
+      num_errors: build.num_errors(),
+      num_warnings: build.num_warnings(),
+      num_notes: build.notes().len(),
+      failed_crates: build.failed_crates(),
+      artifacts: build.artifacts().clone(),
+      targets: build.target_counts(),
+      duration: Duration::ZERO,
+    }
+  }
+
+  /// Construct a summary from the counts of a [`BuildSnapshot`]
+  pub fn from_snapshot(snapshot: &BuildSnapshot, exit_status: Option<ExitStatus>) -> Self {
+    Self {
+      exit_status,
+      num_errors: snapshot.num_errors(),
+      num_warnings: snapshot.num_warnings(),
+      num_notes: snapshot.num_notes(),
+      failed_crates: snapshot.failed_crates().clone(),
+      artifacts: snapshot.artifacts().clone(),
+      targets: snapshot.target_counts().to_vec(),
+      duration: Duration::ZERO,
+    }
+  }
+
+  /// Define the time the build took
+  pub fn with_duration(mut self, duration: Duration) -> Self {
+    self.duration = duration;
+    self
+  }
+
+  /// Format the names of the crates that failed to build, e.g. `failed crates: foo, bar`
+  pub fn failed_crates_line(failed_crates: &[CrateFailure]) -> Option<String> {
+    if failed_crates.is_empty() {
+      return None;
+    }
+    let mut names = failed_crates
+      .iter()
+      .map(|failure| failure.name.as_str())
+      .collect::<Vec<_>>();
+    names.dedup();
+    Some(format!("failed crates: {}", names.join(", ")))
+  }
+
+  /// Format the produced files, one per line, e.g. `artifact: demo (bin) target/debug/demo`
+  pub fn artifact_lines(&self) -> Vec<String> {
+    self
+      .artifacts
+      .iter()
+      .map(|artifact| format!("artifact: {}", artifact))
+      .collect()
+  }
+
+  /// Format the diagnostics of each target, one per line, e.g.
+  /// `target: demo (lib test): 0 error(s), 2 warning(s) (1 duplicate(s))`.
+  /// Nothing for a single target, the summary line already counts them
+  pub fn target_lines(&self) -> Vec<String> {
+    if self.targets.len() < 2 {
+      return vec![];
+    }
+    self
+      .targets
+      .iter()
+      .map(|counts| format!("target: {}", counts))
+      .collect()
+  }
+
+  /// Format the likely cause of a build killed by a signal,
+  /// e.g. `hint: possibly out of memory, see ...` for `SIGKILL`
+  pub fn hint_line(&self) -> Option<String> {
+    let cause = ExitCause::from_status(self.exit_status?)?;
+    cause.hint().map(|hint| format!("hint: {}", hint))
+  }
+
+  /// Format the parse-friendly line printed for scripts, see [`BuildSummary::SCRIPT_LINE_FORMAT`].
+  /// `exit_code` is the one of the process
+  pub fn script_line(&self, exit_code: u8) -> String {
+    format!(
+      "nbuild: exit={} errors={} warnings={} notes={} duration={:.2}",
+      exit_code,
+      self.num_errors,
+      self.num_warnings,
+      self.num_notes,
+      self.duration.as_secs_f64()
+    )
+  }
+
+  /// Format the summary line printed on exit
+  pub fn line(&self, mode: ExitCodeMode) -> String {
+    format!(
+      "{} error(s), {} warning(s), {} note(s) | build {}{}{} | exit code ({}): {}",
+      format_count(self.num_errors),
+      format_count(self.num_warnings),
+      format_count(self.num_notes),
+      match self.exit_status {
+        Some(status) => match ExitCause::from_status(status) {
+          Some(cause) => cause.to_string(),
+          None => format!("{}", status),
+        },
+        None => "not finished".to_string(),
+      },
+      match self.duration.is_zero() {
+        true => String::new(),
+        false => format!(", took {}", human_duration(self.duration)),
+      },
+      match Self::failed_crates_line(&self.failed_crates) {
+        Some(line) => format!(" | {}", line),
+        None => String::new(),
+      },
+      mode,
+      mode.exit_code(self)
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{process::ExitStatus, time::Duration};
+
+  use crate::{BuildOutput, CrateFailure};
+
+  use super::{BuildSummary, ExitCodeMode};
+
+  const CLEAN_LOG: &str = r#"   Compiling demo v0.1.0 (/tmp/demo)
+    Finished `dev` profile [unoptimized + debuginfo] target(s) in 0.32s"#;
+
+  const WARNING_LOG: &str = r#"warning: unused variable: `x`
+ --> src/main.rs:2:7
+  |
+2 |   let x = 1;
+  |       ^ help: if this is intentional, prefix it with an underscore: `_x`
+    Finished `dev` profile [unoptimized + debuginfo] target(s) in 0.32s"#;
+
+  const ERROR_LOG: &str = r#"error[E0425]: cannot find value `y` in this scope
+ --> src/main.rs:3:3
+  |
+3 |   y
+  |   ^ not found in this scope
+warning: unused variable: `x`
+ --> src/main.rs:2:7"#;
+
+  fn summary(log: &str) -> BuildSummary {
+    let mut build = BuildOutput::from(log.split('\n'));
+    build.prepare();
+    BuildSummary::new(&build, Some(ExitStatus::default()))
+  }
+
+  #[test]
+  fn build_mode() {
+    assert_eq!(ExitCodeMode::Build.exit_code(&summary(CLEAN_LOG)), 0);
+    assert_eq!(ExitCodeMode::Build.exit_code(&summary(WARNING_LOG)), 0);
+    assert_eq!(ExitCodeMode::Build.exit_code(&summary(ERROR_LOG)), 0);
+  }
+
+  #[test]
+  fn errors_mode() {
+    assert_eq!(ExitCodeMode::Errors.exit_code(&summary(CLEAN_LOG)), 0);
+    assert_eq!(ExitCodeMode::Errors.exit_code(&summary(WARNING_LOG)), 0);
+    assert_eq!(ExitCodeMode::Errors.exit_code(&summary(ERROR_LOG)), 1);
+  }
+
+  #[test]
+  fn warnings_mode() {
+    assert_eq!(ExitCodeMode::Warnings.exit_code(&summary(CLEAN_LOG)), 0);
+    assert_eq!(ExitCodeMode::Warnings.exit_code(&summary(WARNING_LOG)), 1);
+    assert_eq!(ExitCodeMode::Warnings.exit_code(&summary(ERROR_LOG)), 1);
+  }
+
+  #[test]
+  fn failed_crates() {
+    let log = include_str!("../../../tests/fixtures/rust/rust-macro-backtrace.log");
+    let failed = summary(log);
+    assert_eq!(failed.num_errors, 1);
+    assert_eq!(
+      failed.failed_crates,
+      vec![CrateFailure {
+        name: "demo".to_string(),
+        target: Some("bin \"demo\"".to_string()),
+        errors: 1,
+        warnings: 1,
+      }]
+    );
+    assert!(failed
+      .line(ExitCodeMode::Build)
+      .contains("| failed crates: demo |"));
+    assert!(!summary(ERROR_LOG)
+      .line(ExitCodeMode::Build)
+      .contains("failed crates"));
+  }
+
+  #[test]
+  fn line() {
+    assert_eq!(
+      summary(ERROR_LOG).line(ExitCodeMode::Errors),
+      "1 error(s), 1 warning(s), 0 note(s) | build exit status: 0 | exit code (errors): 1"
+    );
+    assert_eq!(
+      summary(ERROR_LOG)
+        .with_duration(Duration::from_secs(123))
+        .line(ExitCodeMode::Errors),
+      "1 error(s), 1 warning(s), 0 note(s) | build exit status: 0, took 2m 03s | exit code (errors): 1"
+    );
+  }
+
+  #[test]
+  fn target_lines() {
+    let targets = summary(include_str!(
+      "../../../tests/fixtures/rust/rust-targets.log"
+    ));
+    // the warning of the lib repeated by its tests counts once
+    assert_eq!(targets.num_warnings, 2);
+    assert_eq!(
+      targets.target_lines(),
+      vec![
+        "target: demo (lib): 0 error(s), 1 warning(s)",
+        "target: demo (lib test): 0 error(s), 2 warning(s) (1 duplicate(s))",
+      ]
+    );
+    assert!(summary(WARNING_LOG).target_lines().is_empty());
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn artifact_lines() {
+    let summary = summary(include_str!(
+      "../../../tests/fixtures/rust/rust-artifacts.log"
+    ));
+    assert_eq!(summary.artifacts.len(), 4);
+    assert_eq!(
+      summary.artifact_lines().last().map(String::as_str),
+      Some("artifact: demo (bin) /home/user/demo/target/debug/demo")
+    );
+    assert!(BuildSummary::default().artifact_lines().is_empty());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn failed_build() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let summary = BuildSummary {
+      exit_status: Some(ExitStatus::from_raw(101 << 8)),
+      ..summary(CLEAN_LOG)
+    };
+    assert_eq!(ExitCodeMode::Build.exit_code(&summary), 101);
+    assert_eq!(ExitCodeMode::Warnings.exit_code(&summary), 101);
+    assert_eq!(summary.hint_line(), None);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn killed_build() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let summary = BuildSummary {
+      exit_status: Some(ExitStatus::from_raw(libc::SIGSEGV | 0x80)),
+      ..summary(CLEAN_LOG)
+    };
+    assert!(summary
+      .line(ExitCodeMode::Build)
+      .contains("| build killed by SIGSEGV (core dumped) |"));
+    assert_eq!(
+      summary.hint_line().as_deref(),
+      Some("hint: a crash of the compiler, a build script or a proc-macro")
+    );
+    assert_eq!(ExitCodeMode::Build.exit_code(&summary), 1);
+  }
+}
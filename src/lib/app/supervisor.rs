@@ -0,0 +1,171 @@
+use std::{
+  collections::VecDeque,
+  thread::{self, spawn, JoinHandle},
+};
+
+use crate::{err, Error, ErrorKind};
+
+/// The threads of the [`crate::App`], joined in the order they were added.
+///
+/// Each thread returns a [`crate::Result`], the first failure is the one of the whole run
+#[derive(Debug, Default)]
+pub struct Supervisor {
+  threads: VecDeque<(String, JoinHandle<crate::Result<()>>)>,
+}
+
+impl Supervisor {
+  /// Spawn a thread named `name` in the logs
+  pub fn spawn<N: AsRef<str>, F: FnOnce() -> crate::Result<()> + Send + 'static>(
+    &mut self,
+    name: N,
+    f: F,
+  ) {
+    self.add(name, spawn(f));
+  }
+
+  /// Supervise an already spawned thread
+  pub fn add<N: AsRef<str>>(&mut self, name: N, thread: JoinHandle<crate::Result<()>>) {
+    self.threads.push_back((name.as_ref().to_string(), thread));
+  }
+
+  /// Retrieve the number of supervised threads
+  #[cfg(test)]
+  pub fn len(&self) -> usize {
+    self.threads.len()
+  }
+
+  /// Check if no thread is supervised
+  #[cfg(test)]
+  pub fn is_empty(&self) -> bool {
+    self.threads.is_empty()
+  }
+
+  /// Wait for the threads, see [`Supervisor::collect`]
+  pub fn join(&mut self) -> crate::Result<()> {
+    let outcomes = self.threads.drain(..).map(|(name, thread)| {
+      crate::Debug::log(format!("Waiting for the {} thread", name));
+      (name, thread.join())
+    });
+    Self::collect(outcomes)
+  }
+
+  /// Log the outcome of each thread and return the first failure, a panic counting as one
+  pub fn collect<N: AsRef<str>, I: IntoIterator<Item = (N, thread::Result<crate::Result<()>>)>>(
+    outcomes: I,
+  ) -> crate::Result<()> {
+    let mut ret = Ok(());
+    for (name, outcome) in outcomes {
+      let result = match outcome {
+        Ok(result) => result,
+        Err(panic) => Err(err!(
+          ErrorKind::Thread,
+          "the {} thread panicked, {}",
+          name.as_ref(),
+          Self::panic_message(&panic)
+        )),
+      };
+      if let Err(e) = result {
+        crate::Debug::log(format!(
+          "the {} thread failed:\n{}",
+          name.as_ref(),
+          Self::trace(&e)
+        ));
+        if ret.is_ok() {
+          ret = Err(e);
+        }
+      }
+    }
+    ret
+  }
+
+  /// Format an error and its causes, one per line with their location
+  pub fn trace(e: &Error) -> String {
+    let mut lines = vec![];
+    let mut cause = Some(e);
+    while let Some(e) = cause {
+      lines.push(format!(
+        "{}{}{}",
+        e.kind(),
+        match e.message() {
+          Some(msg) => format!(": {}", msg),
+          None => String::new(),
+        },
+        match e.location() {
+          Some(location) => format!(" (at {})", location),
+          None => String::new(),
+        }
+      ));
+      cause = e.cause().map(|cause| cause.as_ref());
+    }
+    lines.join("\n")
+  }
+
+  fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+      (Some(msg), _) => msg.to_string(),
+      (_, Some(msg)) => msg.clone(),
+      _ => "without message".to_string(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{err, ErrorKind};
+
+  use super::Supervisor;
+
+  #[test]
+  fn collect() {
+    assert!(Supervisor::collect::<&str, _>([]).is_ok());
+    assert!(Supervisor::collect([("render", Ok(Ok(()))), ("prepare", Ok(Ok(())))]).is_ok());
+
+    // the first failure, in the order of the threads
+    let e = Supervisor::collect([
+      ("render", Ok(Ok(()))),
+      ("prepare", Ok(Err(err!(ErrorKind::IO, "disconnected")))),
+      ("build", Ok(Err(err!(ErrorKind::Rule, "no such rule")))),
+    ])
+    .unwrap_err();
+    assert_eq!(e.to_string(), "i/o: disconnected");
+
+    // a panic counts as a failure
+    let e = Supervisor::collect([
+      (
+        "render",
+        Err(Box::new("boom") as Box<dyn std::any::Any + Send>),
+      ),
+      ("build", Ok(Err(err!(ErrorKind::IO, "failed to spawn")))),
+    ])
+    .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Thread);
+    assert_eq!(e.to_string(), "thread: the render thread panicked, boom");
+  }
+
+  #[test]
+  fn join() {
+    let mut supervisor = Supervisor::default();
+    supervisor.spawn("ok", || Ok(()));
+    supervisor.spawn("failed", || Err(err!(ErrorKind::Parsing, "bad line")));
+    supervisor.spawn("panicked", || panic!("{}", "formatted"));
+    assert_eq!(supervisor.len(), 3);
+    let e = supervisor.join().unwrap_err();
+    assert_eq!(e.to_string(), "parsing failed: bad line");
+    assert!(supervisor.is_empty());
+  }
+
+  #[test]
+  fn trace() {
+    let e = err!(
+      with_cause err!(ErrorKind::FileNotFound),
+      ErrorKind::Rule,
+      "failed to load the rules"
+    );
+    let trace = Supervisor::trace(&e);
+    let lines = trace.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("rule: failed to load the rules (at "));
+    assert!(lines[0].contains("supervisor.rs:"));
+    assert!(lines[1].starts_with("file not found (at "));
+  }
+}
@@ -0,0 +1,171 @@
+use std::{
+  io::{self, stdout, IsTerminal},
+  process::ExitStatus,
+  time::Duration,
+};
+
+use ratatui::{
+  crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+  },
+  DefaultTerminal,
+};
+
+use crate::restore_title;
+
+/// Format the terminal title of the build of `name`, running for `elapsed` until its `exit_status`,
+/// e.g. `nbuild: building demo… (12s)` then `nbuild: ✓ 0 errors / 3 warnings`
+pub fn window_title<N: AsRef<str>>(
+  name: N,
+  elapsed: Duration,
+  exit_status: Option<ExitStatus>,
+  errors: usize,
+  warnings: usize,
+) -> String {
+  let count = |n: usize, what: &str| match n {
+    1 => format!("1 {}", what),
+    n => format!("{} {}s", n, what),
+  };
+  match exit_status {
+    None => format!(
+      "nbuild: building {}… ({}s)",
+      name.as_ref(),
+      elapsed.as_secs()
+    ),
+    Some(status) if status.success() => format!(
+      "nbuild: ✓ {} / {}",
+      count(errors, "error"),
+      count(warnings, "warning")
+    ),
+    Some(_) => match warnings {
+      0 => format!("nbuild: ✗ {}", count(errors, "error")),
+      _ => format!(
+        "nbuild: ✗ {} / {}",
+        count(errors, "error"),
+        count(warnings, "warning")
+      ),
+    },
+  }
+}
+
+/// Set up the terminal of the UI, see [`TerminalGuard::try_init`].
+/// It gets chosen at runtime, the tests inject a failing one
+pub type TerminalInit = fn() -> io::Result<DefaultTerminal>;
+
+/// Own the terminal state of the UI: raw mode, the alternate screen and the mouse capture.
+///
+/// The terminal gets restored when the guard is dropped, including while unwinding,
+/// so that a panic never leaves it spewing mouse escape codes.
+pub struct TerminalGuard {
+  restore: Option<Box<dyn FnOnce()>>,
+}
+
+impl TerminalGuard {
+  /// Enter raw mode and the alternate screen, then capture the mouse.
+  ///
+  /// Fails without touching the terminal if stdout is not one, e.g. piped or inside some CI shells
+  pub fn init_terminal() -> io::Result<DefaultTerminal> {
+    if !stdout().is_terminal() {
+      return Err(io::Error::other("stdout is not a terminal"));
+    }
+    ratatui::try_init().inspect_err(|_| ratatui::restore())
+  }
+
+  /// Set up the terminal with `init`, e.g. [`TerminalGuard::init_terminal`],
+  /// the returned guard restores it
+  pub fn try_init(init: TerminalInit) -> io::Result<(Self, DefaultTerminal)> {
+    let mut terminal = init()?;
+    let _ = terminal.clear();
+    let _ = execute!(stdout(), EnableMouseCapture);
+    Ok((Self::with_restore(Self::restore_terminal), terminal))
+  }
+
+  /// Construct a guard running `restore` once dropped
+  pub fn with_restore<F: FnOnce() + 'static>(restore: F) -> Self {
+    Self {
+      restore: Some(Box::new(restore)),
+    }
+  }
+
+  /// Leave raw mode and the alternate screen, release the mouse and restore the title
+  pub fn restore_terminal() {
+    ratatui::restore();
+    let _ = execute!(stdout(), DisableMouseCapture);
+    let _ = restore_title(&mut stdout());
+  }
+}
+
+impl Drop for TerminalGuard {
+  fn drop(&mut self) {
+    if let Some(restore) = self.restore.take() {
+      restore();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    panic::{catch_unwind, resume_unwind},
+    sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc,
+    },
+    thread::spawn,
+  };
+
+  use super::{window_title, TerminalGuard};
+
+  #[test]
+  fn restore_on_panic() {
+    let restored = Arc::new(AtomicUsize::new(0));
+    let counter = restored.clone();
+    let result = catch_unwind(move || {
+      let _guard = TerminalGuard::with_restore(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+      });
+      // a builder thread panicking, its join error gets propagated to the main thread
+      let builder = spawn(|| panic!("invalid input line"));
+      if let Err(e) = builder.join() {
+        resume_unwind(e);
+      }
+    });
+    assert!(result.is_err());
+    assert_eq!(restored.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn title() {
+    use std::{process::ExitStatus, time::Duration};
+
+    assert_eq!(
+      window_title("demo", Duration::from_millis(12_400), None, 0, 0),
+      "nbuild: building demo… (12s)"
+    );
+    assert_eq!(
+      window_title("demo", Duration::ZERO, Some(ExitStatus::default()), 0, 3),
+      "nbuild: ✓ 0 errors / 3 warnings"
+    );
+    assert_eq!(
+      window_title("demo", Duration::ZERO, Some(ExitStatus::default()), 0, 1),
+      "nbuild: ✓ 0 errors / 1 warning"
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn title_failed() {
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus, time::Duration};
+
+    let failed = Some(ExitStatus::from_raw(101 << 8));
+    assert_eq!(
+      window_title("demo", Duration::ZERO, failed, 2, 0),
+      "nbuild: ✗ 2 errors"
+    );
+    assert_eq!(
+      window_title("demo", Duration::ZERO, failed, 1, 4),
+      "nbuild: ✗ 1 error / 4 warnings"
+    );
+  }
+}
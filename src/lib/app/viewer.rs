@@ -0,0 +1,91 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use ratatui::{
+  crossterm::event::{self, KeyCode, KeyEventKind, KeyModifiers},
+  DefaultTerminal,
+};
+
+use crate::{BuildDiff, BuildOutput, DiffState, DiffStatus, DiffView, DIFF_SECTIONS};
+
+/// Compare two build logs, see `--diff`
+pub struct DiffViewer {
+  names: [String; 2],
+  old: BuildOutput<'static>,
+  new: BuildOutput<'static>,
+  diff: BuildDiff,
+}
+
+impl DiffViewer {
+  /// Load and compare the logs
+  pub fn new(old_path: &PathBuf, new_path: &PathBuf) -> crate::Result<Self> {
+    let old = BuildDiff::load(old_path)?;
+    let new = BuildDiff::load(new_path)?;
+    let diff = BuildDiff::new(&old, &new);
+    Ok(Self {
+      names: [old_path, new_path].map(|path| path.display().to_string()),
+      old,
+      new,
+      diff,
+    })
+  }
+
+  /// Retrieve the comparison
+  pub fn diff(&self) -> &BuildDiff {
+    &self.diff
+  }
+
+  /// Print the block lists, without the terminal UI
+  pub fn print(&self) {
+    for (status, title) in DIFF_SECTIONS {
+      let blocks = self.diff.with_status(status).collect::<Vec<_>>();
+      println!("{} ({})", title, blocks.len());
+      for block in blocks {
+        let (sign, range, build) = match status {
+          DiffStatus::Fixed => ('-', block.old.as_ref(), &self.old),
+          DiffStatus::Regression => ('+', block.new.as_ref(), &self.new),
+          DiffStatus::Unchanged => (' ', block.new.as_ref(), &self.new),
+        };
+        let location = range.and_then(|range| {
+          build.entries()[range.clone()]
+            .iter()
+            .find_map(|e| e.location_str())
+        });
+        match location {
+          Some(location) => println!("{} {} ({})", sign, block.fingerprint.first_line, location),
+          None => println!("{} {}", sign, block.fingerprint.first_line),
+        }
+      }
+    }
+  }
+
+  /// Show the comparison until the user quits with `q`
+  pub fn run(&self, mut terminal: DefaultTerminal) -> io::Result<()> {
+    let mut state = DiffState::default();
+    let (old_lines, new_lines) = (self.old.display(), self.new.display());
+    loop {
+      terminal.draw(|frame| {
+        let view = DiffView::new(
+          &self.diff,
+          old_lines.clone(),
+          new_lines.clone(),
+          self.names.clone(),
+        );
+        frame.render_stateful_widget(view, frame.area(), &mut state);
+      })?;
+      if !event::poll(Duration::from_millis(100))? {
+        continue;
+      }
+      if let event::Event::Key(key) = event::read()? {
+        if key.kind != KeyEventKind::Press {
+          continue;
+        }
+        if key.code == KeyCode::Char('q')
+          || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        {
+          return Ok(());
+        }
+        DiffState::handle_key(key, &mut state, &self.diff);
+      }
+    }
+  }
+}
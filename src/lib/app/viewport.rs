@@ -0,0 +1,169 @@
+/// The rows of the log displayed by the [`super::Renderer`], for the paging and scrolling math.
+///
+/// The log area is only known once drawn, the keys handled before that use
+/// [`Viewport::DEFAULT_HEIGHT`]. A viewport is always at least one row high
+/// so that paging keeps moving in a tiny terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+  height: usize,
+  scrolloff: usize,
+}
+
+impl Default for Viewport {
+  fn default() -> Self {
+    Self::new(Self::DEFAULT_HEIGHT)
+  }
+}
+
+impl Viewport {
+  /// The height used until the log area gets drawn
+  pub const DEFAULT_HEIGHT: usize = 20;
+  /// The rows of context kept around a revealed row, see `--scrolloff`
+  pub const DEFAULT_SCROLLOFF: usize = 3;
+
+  /// Construct the viewport of `height` log rows, borders and ruler excluded
+  pub fn new(height: usize) -> Self {
+    Self {
+      height,
+      scrolloff: Self::DEFAULT_SCROLLOFF,
+    }
+  }
+
+  /// Define the rows of context kept around a revealed row
+  pub fn with_scrolloff(mut self, scrolloff: usize) -> Self {
+    self.scrolloff = scrolloff;
+    self
+  }
+
+  /// Retrieve the number of rows, at least one
+  pub fn height(&self) -> usize {
+    self.height.max(1)
+  }
+
+  /// Retrieve the scroll showing the last of `len` lines
+  pub fn bottom(&self, len: usize) -> usize {
+    len.saturating_sub(self.height())
+  }
+
+  /// Compute the scroll one page above `scroll`
+  pub fn page_up(&self, scroll: usize) -> usize {
+    scroll.saturating_sub(self.height())
+  }
+
+  /// Compute the scroll one page below `scroll`, without going past the last of `len` lines
+  pub fn page_down(&self, scroll: usize, len: usize) -> usize {
+    self.scroll_down(scroll, self.height(), len)
+  }
+
+  /// Compute the scroll `rows` below `scroll`, without going past the last of `len` lines.
+  /// A view already past the last line stays where it is
+  pub fn scroll_down(&self, scroll: usize, rows: usize, len: usize) -> usize {
+    let bottom = self.bottom(len);
+    match scroll < bottom {
+      true => scroll.saturating_add(rows).min(bottom),
+      false => scroll,
+    }
+  }
+
+  /// Retrieve the rows of context kept above and below a revealed row: the scrolloff,
+  /// reduced for the row to fit between them in a small viewport
+  pub fn margin(&self) -> usize {
+    self.scrolloff.min((self.height() - 1) / 2)
+  }
+
+  /// Compute the scroll revealing `row` of `len` rows, with [`Viewport::margin`] rows around it.
+  /// A row below the view comes first after its margin, a row above it moves the view up a page,
+  /// or up to its margin if it is further. A row in the margins moves the view just enough,
+  /// without going past the last row
+  pub fn reveal(&self, row: usize, scroll: usize, len: usize) -> usize {
+    let (height, margin) = (self.height(), self.margin());
+    let top = row.saturating_sub(margin);
+    let scroll = if row < scroll {
+      self.page_up(scroll).min(top)
+    } else if row >= scroll + height || row < scroll + margin {
+      top
+    } else if row + margin >= scroll + height {
+      row + margin + 1 - height
+    } else {
+      return scroll;
+    };
+    scroll.min(self.bottom(len))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Viewport;
+
+  #[test]
+  fn paging() {
+    // not drawn yet or no room left, the keys still move one row at a time
+    for height in [0, 1] {
+      let viewport = Viewport::new(height);
+      assert_eq!(viewport.height(), 1);
+      assert_eq!(viewport.bottom(30), 29);
+      assert_eq!(viewport.page_up(10), 9);
+      assert_eq!(viewport.page_up(0), 0);
+      assert_eq!(viewport.page_down(10, 30), 11);
+      assert_eq!(viewport.page_down(29, 30), 29);
+      assert_eq!(viewport.page_down(0, 0), 0);
+    }
+    let viewport = Viewport::new(50);
+    assert_eq!(viewport.bottom(30), 0);
+    assert_eq!(viewport.bottom(120), 70);
+    assert_eq!(viewport.page_up(60), 10);
+    assert_eq!(viewport.page_up(30), 0);
+    assert_eq!(viewport.page_down(0, 30), 0);
+    assert_eq!(viewport.page_down(0, 120), 50);
+    // the last page is not scrolled past
+    assert_eq!(viewport.page_down(50, 120), 70);
+    assert_eq!(viewport.page_down(90, 120), 90);
+    assert_eq!(Viewport::default().height(), Viewport::DEFAULT_HEIGHT);
+  }
+
+  #[test]
+  fn reveal() {
+    // no room for a margin
+    for height in [0, 1, 2] {
+      let viewport = Viewport::new(height);
+      assert_eq!(viewport.margin(), 0);
+      assert_eq!(viewport.reveal(10, 10, 200), 10);
+      assert_eq!(viewport.reveal(4, 10, 200), 4);
+    }
+    assert_eq!(Viewport::new(1).reveal(11, 10, 200), 11);
+    let viewport = Viewport::new(50).with_scrolloff(0);
+    assert_eq!(viewport.reveal(20, 20, 200), 20);
+    assert_eq!(viewport.reveal(69, 20, 200), 20);
+    assert_eq!(viewport.reveal(70, 20, 200), 70);
+    // a page up, or up to a row further away
+    assert_eq!(viewport.reveal(90, 100, 200), 50);
+    assert_eq!(viewport.reveal(10, 100, 200), 10);
+    assert_eq!(viewport.reveal(0, 30, 200), 0);
+  }
+
+  #[test]
+  fn reveal_margin() {
+    let viewport = Viewport::new(50);
+    assert_eq!(viewport.margin(), Viewport::DEFAULT_SCROLLOFF);
+    // in the view, away from its edges
+    assert_eq!(viewport.reveal(40, 20, 200), 20);
+    // in the margins, moved just enough
+    assert_eq!(viewport.reveal(21, 20, 200), 18);
+    assert_eq!(viewport.reveal(67, 20, 200), 21);
+    // out of the view, with its margin
+    assert_eq!(viewport.reveal(70, 20, 200), 67);
+    assert_eq!(viewport.reveal(90, 100, 200), 50);
+    assert_eq!(viewport.reveal(51, 100, 200), 48);
+    // near the start and the end of the content
+    assert_eq!(viewport.reveal(1, 30, 200), 0);
+    assert_eq!(viewport.reveal(199, 20, 200), 150);
+    assert_eq!(viewport.reveal(180, 0, 200), 150);
+    // a viewport smaller than twice the margin and the row
+    let viewport = Viewport::new(6);
+    assert_eq!(viewport.margin(), 2);
+    assert_eq!(viewport.reveal(10, 0, 200), 8);
+    assert_eq!(viewport.reveal(10, 9, 200), 8);
+    assert_eq!(viewport.reveal(10, 6, 200), 7);
+    assert_eq!(Viewport::new(50).with_scrolloff(40).margin(), 24);
+  }
+}
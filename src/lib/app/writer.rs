@@ -0,0 +1,277 @@
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+
+use crate::BuildEntry;
+
+use super::AppOptions;
+
+/// Append the build output to a file (`--output-file`), rotated once it grows past a size.
+///
+/// The rotated files get suffixed: `build.log` becomes `build.log.1`, `build.log.1` becomes
+/// `build.log.2` and so on, up to `build.log.<keep>`, the oldest one being removed. The
+/// rotations happen between two entries, a line never spans two files
+#[derive(Debug)]
+pub struct RotatingWriter {
+  path: PathBuf,
+  max_size: Option<u64>,
+  keep: usize,
+  file: Option<File>,
+  size: u64,
+}
+
+impl RotatingWriter {
+  /// The rotated files kept, unless defined with `--output-file-keep`
+  pub const DEFAULT_KEEP: usize = 3;
+
+  /// Construct this object, the file gets opened on the first write
+  pub fn new<P: AsRef<Path>>(path: P) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+      max_size: None,
+      keep: Self::DEFAULT_KEEP,
+      file: None,
+      size: 0,
+    }
+  }
+
+  /// The writer of the `--output-file` options, if any
+  pub fn from_options(options: &AppOptions) -> Option<Self> {
+    let path = options.output_file.as_ref()?;
+    Some(
+      Self::new(path)
+        .with_max_size(options.output_file_max_size)
+        .with_keep(options.output_file_keep),
+    )
+  }
+
+  /// Rotate the file once it would grow past `max_size` bytes, never by default
+  pub fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+    self.max_size = max_size;
+    self
+  }
+
+  /// Keep this many rotated files, none only truncates the file
+  pub fn with_keep(mut self, keep: usize) -> Self {
+    self.keep = keep;
+    self
+  }
+
+  /// Retrieve the path of the n-th rotated file, the current one for 0
+  pub fn rotated_path(&self, n: usize) -> PathBuf {
+    match n {
+      0 => self.path.clone(),
+      n => {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+      }
+    }
+  }
+
+  /// Write the line starting a run: its date and command
+  pub fn write_header(&mut self, command: &str, at: DateTime<Local>) -> io::Result<()> {
+    self.write_line(&format!("# {} {}", at.format("%Y-%m-%d %H:%M:%S"), command))
+  }
+
+  /// Write the messages of the entries, one per line
+  pub fn write_entries(&mut self, entries: &[BuildEntry]) -> io::Result<()> {
+    for entry in entries {
+      self.write_line(entry.message())?;
+    }
+    self.flush()
+  }
+
+  /// Write a line, rotating the file first if it would grow past the max size.
+  /// A line longer than the max size gets a file of its own
+  pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+    let len = line.len() as u64 + 1;
+    if self.file.is_none() {
+      self.open()?;
+    }
+    if self
+      .max_size
+      .is_some_and(|max_size| self.size > 0 && self.size + len > max_size)
+    {
+      self.rotate()?;
+    }
+    let file = self.file.as_mut().expect("output file not opened");
+    writeln!(file, "{}", line)?;
+    self.size += len;
+    Ok(())
+  }
+
+  pub fn flush(&mut self) -> io::Result<()> {
+    match self.file.as_mut() {
+      Some(file) => file.flush(),
+      None => Ok(()),
+    }
+  }
+
+  /// Open the file in append mode, the previous runs count in its size
+  fn open(&mut self) -> io::Result<()> {
+    let file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)?;
+    self.size = file.metadata()?.len();
+    self.file = Some(file);
+    Ok(())
+  }
+
+  /// Shift the rotated files, then start a new file
+  fn rotate(&mut self) -> io::Result<()> {
+    self.flush()?;
+    self.file = None;
+    if self.keep == 0 {
+      File::create(&self.path)?;
+    } else {
+      let oldest = self.rotated_path(self.keep);
+      if oldest.exists() {
+        fs::remove_file(&oldest)?;
+      }
+      for n in (0..self.keep).rev() {
+        let from = self.rotated_path(n);
+        if from.exists() {
+          fs::rename(&from, self.rotated_path(n + 1))?;
+        }
+      }
+    }
+    self.open()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use chrono::{Local, TimeZone};
+
+  use crate::{BuildEntry, Origin};
+
+  use super::RotatingWriter;
+
+  fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nbuild-writer-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn entries<I: IntoIterator<Item = usize>>(ids: I) -> Vec<BuildEntry> {
+    ids
+      .into_iter()
+      .map(|i| BuildEntry::new(format!("line {:02}", i), Origin::Stderr))
+      .collect()
+  }
+
+  /// The names and contents of the files of `dir`, sorted by name
+  fn files(dir: &PathBuf) -> Vec<(String, String)> {
+    let mut files = fs::read_dir(dir)
+      .unwrap()
+      .flatten()
+      .map(|entry| {
+        (
+          entry.file_name().to_string_lossy().to_string(),
+          fs::read_to_string(entry.path()).unwrap(),
+        )
+      })
+      .collect::<Vec<_>>();
+    files.sort();
+    files
+  }
+
+  #[test]
+  fn unbounded() {
+    let dir = dir("unbounded");
+    let mut writer = RotatingWriter::new(dir.join("build.log"));
+    writer.write_entries(&entries(0..3)).unwrap();
+    // appended, e.g. by the next run
+    let mut writer = RotatingWriter::new(dir.join("build.log"));
+    writer.write_entries(&entries(3..4)).unwrap();
+    assert_eq!(
+      files(&dir),
+      vec![(
+        "build.log".to_string(),
+        "line 00\nline 01\nline 02\nline 03\n".to_string()
+      )]
+    );
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn rotate() {
+    let dir = dir("rotate");
+    // 8 bytes per line, 2 lines per file
+    let mut writer = RotatingWriter::new(dir.join("build.log"))
+      .with_max_size(Some(20))
+      .with_keep(2);
+    writer.write_entries(&entries(0..7)).unwrap();
+    assert_eq!(
+      files(&dir),
+      vec![
+        ("build.log".to_string(), "line 06\n".to_string()),
+        ("build.log.1".to_string(), "line 04\nline 05\n".to_string()),
+        ("build.log.2".to_string(), "line 02\nline 03\n".to_string()),
+      ]
+    );
+    assert_eq!(writer.rotated_path(2), dir.join("build.log.2"));
+    // the size of the existing file counts
+    let mut writer = RotatingWriter::new(dir.join("build.log"))
+      .with_max_size(Some(20))
+      .with_keep(2);
+    writer.write_entries(&entries(7..9)).unwrap();
+    assert_eq!(
+      files(&dir),
+      vec![
+        ("build.log".to_string(), "line 08\n".to_string()),
+        ("build.log.1".to_string(), "line 06\nline 07\n".to_string()),
+        ("build.log.2".to_string(), "line 04\nline 05\n".to_string()),
+      ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn rotate_without_keep() {
+    let dir = dir("nokeep");
+    let mut writer = RotatingWriter::new(dir.join("build.log"))
+      .with_max_size(Some(20))
+      .with_keep(0);
+    writer.write_entries(&entries(0..5)).unwrap();
+    // a line longer than the cap gets a file of its own
+    writer.write_line(&"x".repeat(30)).unwrap();
+    writer.flush().unwrap();
+    assert_eq!(
+      files(&dir),
+      vec![("build.log".to_string(), format!("{}\n", "x".repeat(30)))]
+    );
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn header() {
+    let dir = dir("header");
+    let mut writer = RotatingWriter::new(dir.join("build.log"))
+      .with_max_size(Some(55))
+      .with_keep(1);
+    let at = Local.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap();
+    writer.write_header("cargo build --release", at).unwrap();
+    writer.write_entries(&entries(0..2)).unwrap();
+    assert_eq!(
+      files(&dir),
+      vec![
+        ("build.log".to_string(), "line 01\n".to_string()),
+        (
+          "build.log.1".to_string(),
+          "# 2024-03-01 12:30:00 cargo build --release\nline 00\n".to_string()
+        ),
+      ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,146 @@
+use std::{
+  fmt::Display,
+  path::{Path, PathBuf},
+};
+
+/// A file produced by the build, parsed from cargo's `compiler-artifact`
+/// messages (`--message-format=json`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+  /// The name of the target
+  pub name: String,
+  /// The kinds of the target, e.g. `bin`, `lib` or `proc-macro`
+  pub kind: String,
+  /// The executable, or the first produced file
+  pub path: PathBuf,
+  /// Reused from a previous build
+  pub fresh: bool,
+}
+
+impl Artifact {
+  /// The key of the messages worth parsing
+  #[cfg(feature = "json")]
+  const REASON: &'static str = r#""reason":"compiler-artifact""#;
+
+  pub fn new<N: AsRef<str>, K: AsRef<str>, P: AsRef<Path>>(name: N, kind: K, path: P) -> Self {
+    Self {
+      name: name.as_ref().to_string(),
+      kind: kind.as_ref().to_string(),
+      path: path.as_ref().to_path_buf(),
+      fresh: false,
+    }
+  }
+
+  pub fn with_fresh(mut self, v: bool) -> Self {
+    self.fresh = v;
+    self
+  }
+
+  /// Parse a `compiler-artifact` message, `None` for any other line
+  #[cfg(feature = "json")]
+  pub fn parse<M: AsRef<str>>(message: M) -> Option<Self> {
+    let message = message.as_ref().trim();
+    if !message.starts_with('{') || !message.contains(Self::REASON) {
+      return None;
+    }
+    let value = serde_json::from_str::<serde_json::Value>(message).ok()?;
+    let target = value.get("target")?;
+    let kind = target
+      .get("kind")?
+      .as_array()?
+      .iter()
+      .filter_map(|kind| kind.as_str())
+      .collect::<Vec<_>>()
+      .join(", ");
+    let path = value
+      .get("executable")
+      .and_then(|executable| executable.as_str())
+      .or_else(|| value.get("filenames")?.as_array()?.first()?.as_str())?;
+    Some(
+      Self::new(target.get("name")?.as_str()?, kind, path).with_fresh(
+        value
+          .get("fresh")
+          .and_then(|fresh| fresh.as_bool())
+          .unwrap_or_default(),
+      ),
+    )
+  }
+
+  /// Parse a `compiler-artifact` message, never available without the `json` feature
+  #[cfg(not(feature = "json"))]
+  pub fn parse<M: AsRef<str>>(_message: M) -> Option<Self> {
+    None
+  }
+
+  /// The command revealing the artifact in the file manager
+  pub fn reveal_command(&self) -> Vec<String> {
+    let dir = self
+      .path
+      .parent()
+      .unwrap_or(&self.path)
+      .display()
+      .to_string();
+    if cfg!(target_os = "macos") {
+      vec![
+        "open".to_string(),
+        "-R".to_string(),
+        self.path.display().to_string(),
+      ]
+    } else if cfg!(windows) {
+      vec![
+        "explorer".to_string(),
+        format!("/select,{}", self.path.display()),
+      ]
+    } else {
+      vec!["xdg-open".to_string(), dir]
+    }
+  }
+}
+
+impl Display for Artifact {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} ({}) {}", self.name, self.kind, self.path.display())
+  }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+  use super::Artifact;
+
+  const LIB: &str = r#"{"reason":"compiler-artifact","package_id":"path+file:///tmp/demo#0.1.0","manifest_path":"/tmp/demo/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"demo","src_path":"/tmp/demo/src/lib.rs","edition":"2021","doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/tmp/demo/target/debug/libdemo.rlib","/tmp/demo/target/debug/deps/libdemo-1a2b3c.rmeta"],"executable":null,"fresh":true}"#;
+
+  const BIN: &str = r#"{"reason":"compiler-artifact","package_id":"path+file:///tmp/demo#0.1.0","manifest_path":"/tmp/demo/Cargo.toml","target":{"kind":["bin"],"crate_types":["bin"],"name":"demo-cli","src_path":"/tmp/demo/src/main.rs","edition":"2021","doctest":false,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/tmp/demo/target/debug/demo-cli"],"executable":"/tmp/demo/target/debug/demo-cli","fresh":false}"#;
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      Artifact::parse(LIB),
+      Some(Artifact::new("demo", "lib", "/tmp/demo/target/debug/libdemo.rlib").with_fresh(true))
+    );
+    assert_eq!(
+      Artifact::parse(BIN),
+      Some(Artifact::new(
+        "demo-cli",
+        "bin",
+        "/tmp/demo/target/debug/demo-cli"
+      ))
+    );
+    for line in [
+      r#"{"reason":"build-finished","success":true}"#,
+      r#"{"reason":"compiler-message","message":{"rendered":"\"reason\":\"compiler-artifact\""}}"#,
+      r#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"broken"}"#,
+      "   Compiling demo v0.1.0 (/tmp/demo)",
+    ] {
+      assert_eq!(Artifact::parse(line), None, "{}", line);
+    }
+  }
+
+  #[cfg(all(unix, not(target_os = "macos")))]
+  #[test]
+  fn reveal_command() {
+    assert_eq!(
+      Artifact::parse(BIN).unwrap().reveal_command(),
+      vec!["xdg-open", "/tmp/demo/target/debug"]
+    );
+  }
+}
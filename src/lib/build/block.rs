@@ -1,12 +1,18 @@
 use std::{fmt::Display, ops::Range};
 
-use crate::MarkerRef;
+use crate::{MarkerId, MarkerRef};
 
-use super::BuildEntry;
+use super::{
+  allow_attribute, block_lint_name, BlockFingerprint, BuildEntry, BuildTagKind, FingerprintOptions,
+  Location, Suggestion,
+};
+
+/// A block listed by the quick-jump dialog, as `(marker_id, kind, first_line, location)`
+pub type JumpTarget = (MarkerId, BuildTagKind, String, Option<Location>);
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct MarkedBlock<'a> {
-  marker_id: usize,
+  marker_id: MarkerId,
   marker: MarkerRef,
   entry_range: Range<usize>,
   entries: Vec<&'a BuildEntry>,
@@ -36,7 +42,7 @@ impl<'a> Display for MarkedBlock<'a> {
 }
 impl<'a> MarkedBlock<'a> {
   pub fn new(
-    marker_id: usize,
+    marker_id: MarkerId,
     marker: MarkerRef,
     entry_range: Range<usize>,
     entries: Vec<&'a BuildEntry>,
@@ -75,10 +81,10 @@ impl<'a> MarkedBlock<'a> {
     &mut self.entry_range
   }
 
-  pub fn marker_id(&self) -> usize {
+  pub fn marker_id(&self) -> MarkerId {
     self.marker_id
   }
-  pub fn marker_id_mut(&mut self) -> &mut usize {
+  pub fn marker_id_mut(&mut self) -> &mut MarkerId {
     &mut self.marker_id
   }
 
@@ -88,4 +94,55 @@ impl<'a> MarkedBlock<'a> {
   pub fn entries_mut(&mut self) -> &mut Vec<&'a BuildEntry> {
     &mut self.entries
   }
+
+  /// Retrieve the first location found in this block
+  pub fn primary_location(&self) -> Option<&Location> {
+    self
+      .entries
+      .iter()
+      .find_map(|entry| entry.locations().next())
+  }
+
+  /// Retrieve the [`Suggestion`]s of the diagnostic rendered in this block
+  pub fn suggestions(&self) -> Vec<Suggestion> {
+    self
+      .entries
+      .iter()
+      .flat_map(|entry| entry.suggestions().iter().cloned())
+      .collect::<Vec<_>>()
+  }
+
+  /// Retrieve the code of the block's marker, e.g. `E0308`
+  pub fn error_code(&self) -> Option<&str> {
+    self.marker.code()
+  }
+
+  /// Format the attribute allowing the lint of this block, see [`allow_attribute`]
+  pub fn allow_attribute(&self) -> String {
+    allow_attribute(
+      block_lint_name(&self.lines()).as_deref(),
+      self.primary_location(),
+    )
+  }
+
+  /// Compute the fingerprint of this block with the components of `options`,
+  /// stable across builds unless the line or column is included
+  pub fn fingerprint(&self, options: FingerprintOptions) -> BlockFingerprint {
+    BlockFingerprint::with_options(self, options)
+  }
+
+  /// Retrieve the first line of the block, the marked one
+  pub fn first_line(&self) -> Option<&str> {
+    self.entries.first().map(|entry| entry.message().as_str())
+  }
+
+  /// Describe this block for the quick-jump dialog
+  pub fn jump_target(&self) -> JumpTarget {
+    (
+      self.marker_id,
+      self.marker.kind(),
+      self.first_line().unwrap_or_default().to_string(),
+      self.primary_location().cloned(),
+    )
+  }
 }
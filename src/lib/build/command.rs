@@ -1,23 +1,119 @@
 use std::{
+  fs::File,
   io,
   ops::{Deref, DerefMut},
-  process::{Child, Command, Stdio},
+  path::Path,
+  process::{Child, ChildStdin, Command, Stdio},
 };
 
+use super::FORCED_LOCALE;
+
 /// Represent the `cargo build` process.
-pub struct BuildCommand(Child);
+pub struct BuildCommand(Child, Option<File>);
 
 impl BuildCommand {
-  /// Spawn the process, setting piped stdout/stderr streams
-  pub fn spawn(args: Vec<String>) -> io::Result<Self> {
-    let child = Command::new("cargo")
-      .arg("build")
-      .args(args)
+  /// Check if [`Self::spawn_pty`] runs the process inside a pseudo-terminal on this platform
+  pub const PTY_SUPPORTED: bool = cfg!(unix);
+
+  /// The size of the pseudo-terminal when the current one is unknown
+  pub const PTY_SIZE: (u16, u16) = (120, 40);
+
+  /// Prepare the `cargo build` command.
+  ///
+  /// The project of `manifest_path` gets built instead of the current one (`--manifest-path`).
+  ///
+  /// Unless `keep_locale` is true, the [`FORCED_LOCALE`] overrides the user's locale
+  pub fn command(args: Vec<String>, manifest_path: Option<&Path>, keep_locale: bool) -> Command {
+    let mut command = Command::new("cargo");
+    command.arg("build");
+    if let Some(path) = manifest_path {
+      command.arg("--manifest-path").arg(path);
+    }
+    command.args(args);
+    if !keep_locale {
+      command.envs(FORCED_LOCALE.iter().copied());
+    }
+    command
+  }
+
+  /// Spawn a custom process, setting piped stdout/stderr streams
+  pub fn spawn_command(mut command: Command) -> io::Result<Self> {
+    let child = command
       .stderr(Stdio::piped())
       .stdout(Stdio::piped())
       .spawn()?;
 
-    Ok(BuildCommand(child))
+    Ok(BuildCommand(child, None))
+  }
+
+  /// Spawn a custom process with a pseudo-terminal as stdout/stderr, so that it emits its colors
+  /// and progress bars. Both streams are read from [`Self::take_pty`].
+  ///
+  /// Falls back to [`Self::spawn_command`] where not [`Self::PTY_SUPPORTED`]
+  #[cfg(unix)]
+  pub fn spawn_pty(mut command: Command) -> io::Result<Self> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    let (columns, rows) = ratatui::crossterm::terminal::size().unwrap_or(Self::PTY_SIZE);
+    let size = libc::winsize {
+      ws_row: rows,
+      ws_col: columns,
+      ws_xpixel: 0,
+      ws_ypixel: 0,
+    };
+    let (mut master, mut slave) = (0, 0);
+    // SAFETY: the out pointers are valid, the name buffer is not requested
+    let ret = unsafe {
+      libc::openpty(
+        &mut master,
+        &mut slave,
+        std::ptr::null_mut(),
+        std::ptr::null(),
+        &size,
+      )
+    };
+    if ret != 0 {
+      return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openpty succeeded, both descriptors are owned from now on
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    for fd in [&master, &slave] {
+      // the child only gets the slave side, through its stdout/stderr
+      // SAFETY: the descriptor is valid for the duration of the call
+      unsafe { libc::ioctl(std::os::fd::AsRawFd::as_raw_fd(fd), libc::FIOCLEX) };
+    }
+    command
+      .stdout(Stdio::from(slave.try_clone()?))
+      .stderr(Stdio::from(slave));
+    let child = command.spawn()?;
+    // the slave copies held by the command must be closed to read EOF once the child exits
+    drop(command);
+    Ok(BuildCommand(child, Some(File::from(master))))
+  }
+
+  /// Spawn a custom process with a pseudo-terminal as stdout/stderr, see the unix version
+  #[cfg(not(unix))]
+  pub fn spawn_pty(command: Command) -> io::Result<Self> {
+    Self::spawn_command(command)
+  }
+
+  /// Take the master side of the pseudo-terminal, `None` unless spawned by [`Self::spawn_pty`]
+  pub fn take_pty(&mut self) -> Option<File> {
+    self.1.take()
+  }
+
+  /// Take the stdin of the child, `None` unless piped by the command, see `--interactive`
+  pub fn take_stdin(&mut self) -> Option<ChildStdin> {
+    self.0.stdin.take()
+  }
+
+  /// Check if a read error on the [`Self::take_pty`] side means the child closed it:
+  /// linux reports `EIO` instead of EOF once the slave side is closed
+  pub fn is_pty_closed(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    return e.raw_os_error() == Some(libc::EIO);
+    #[cfg(not(unix))]
+    return false;
   }
 }
 
@@ -34,3 +130,88 @@ impl DerefMut for BuildCommand {
     &mut self.0
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{ffi::OsStr, path::Path};
+
+  use super::BuildCommand;
+
+  fn env_value(keep_locale: bool, var: &str) -> Option<Option<String>> {
+    BuildCommand::command(vec![], None, keep_locale)
+      .get_envs()
+      .find(|(key, _)| *key == OsStr::new(var))
+      .map(|(_, value)| value.map(|value| value.to_string_lossy().to_string()))
+  }
+
+  #[test]
+  fn forced_locale() {
+    assert_eq!(env_value(false, "LC_ALL"), Some(Some("C".to_string())));
+    assert_eq!(env_value(true, "LC_ALL"), None);
+  }
+
+  #[test]
+  fn manifest_path() {
+    let args = |manifest_path: Option<&Path>| {
+      BuildCommand::command(vec!["--release".to_string()], manifest_path, false)
+        .get_args()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(args(None), vec!["build", "--release"]);
+    assert_eq!(
+      args(Some(Path::new("../demo/Cargo.toml"))),
+      vec![
+        "build",
+        "--manifest-path",
+        "../demo/Cargo.toml",
+        "--release"
+      ]
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn pty_is_terminal() {
+    use std::{io::Read, process::Command};
+
+    let mut command = Command::new("sh");
+    command
+      .arg("-c")
+      .arg("[ -t 1 ] && [ -t 2 ] && echo terminal || echo pipe");
+    let mut child = BuildCommand::spawn_pty(command).expect("failed to spawn sh");
+    let mut pty = child.take_pty().expect("missing pty");
+    assert!(child.stdout.is_none() && child.stderr.is_none());
+    let _ = child.wait();
+    let mut out = vec![];
+    let mut buf = [0; 256];
+    loop {
+      match pty.read(&mut buf) {
+        Ok(0) => break,
+        Ok(n) => out.extend_from_slice(&buf[..n]),
+        Err(e) if BuildCommand::is_pty_closed(&e) => break,
+        Err(e) => panic!("failed to read pty, {}", e),
+      }
+    }
+    assert_eq!(String::from_utf8_lossy(&out), "terminal\r\n");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn child_env() {
+    use std::{io::Read, process::Command};
+
+    let mut command = Command::new("env");
+    command.envs(super::FORCED_LOCALE.iter().copied());
+    let mut child = BuildCommand::spawn_command(command).expect("failed to spawn env");
+    let mut out = String::new();
+    child
+      .stdout
+      .take()
+      .unwrap()
+      .read_to_string(&mut out)
+      .unwrap();
+    let _ = child.wait();
+    assert!(out.lines().any(|line| line == "LC_ALL=C"));
+  }
+}
@@ -0,0 +1,235 @@
+use std::{fs::read_to_string, ops::Range, path::Path};
+
+use crate::{err, ErrorKind};
+
+use super::{BlockFingerprint, BuildOutput, MarkedBlock};
+
+/// Where a block of a [`BuildDiff`] was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStatus {
+  /// Only in the old log
+  Fixed,
+  /// Only in the new log
+  Regression,
+  /// In both logs
+  Unchanged,
+}
+
+/// A block of one or both logs of a [`BuildDiff`], with its entry ranges
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffBlock {
+  pub fingerprint: BlockFingerprint,
+  /// The entries of the block in the old log
+  pub old: Option<Range<usize>>,
+  /// The entries of the block in the new log
+  pub new: Option<Range<usize>>,
+}
+
+impl DiffBlock {
+  pub fn status(&self) -> DiffStatus {
+    match (self.old.is_some(), self.new.is_some()) {
+      (true, false) => DiffStatus::Fixed,
+      (false, true) => DiffStatus::Regression,
+      _ => DiffStatus::Unchanged,
+    }
+  }
+}
+
+/// Compare the blocks of two build logs, matched by their [`BlockFingerprint`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildDiff {
+  blocks: Vec<DiffBlock>,
+}
+
+impl BuildDiff {
+  /// Compare the blocks of two prepared build outputs
+  pub fn new(old: &BuildOutput, new: &BuildOutput) -> Self {
+    Self::from_blocks(&old.blocks(), &new.blocks())
+  }
+
+  /// Match each new block with the most similar old block having the same first line.
+  ///
+  /// The blocks present in the new log come first, in order, then the fixed ones
+  pub fn from_blocks(old: &[MarkedBlock<'_>], new: &[MarkedBlock<'_>]) -> Self {
+    let old_fingerprints = old
+      .iter()
+      .map(BlockFingerprint::from_block)
+      .collect::<Vec<_>>();
+    let mut matched = vec![false; old.len()];
+    let mut blocks = vec![];
+    for block in new {
+      let fingerprint = BlockFingerprint::from_block(block);
+      let mut best: Option<(usize, u8)> = None;
+      for (id, candidate) in old_fingerprints.iter().enumerate() {
+        let score = fingerprint.score(candidate);
+        // the first line must match, the location path only breaks ties
+        if matched[id] || score < 2 {
+          continue;
+        }
+        if best
+          .map(|(_, best_score)| score > best_score)
+          .unwrap_or(true)
+        {
+          best = Some((id, score));
+        }
+      }
+      if let Some((id, _)) = best {
+        matched[id] = true;
+      }
+      blocks.push(DiffBlock {
+        fingerprint,
+        old: best.map(|(id, _)| old[id].range()),
+        new: Some(block.range()),
+      });
+    }
+    for ((block, fingerprint), _) in old
+      .iter()
+      .zip(old_fingerprints)
+      .zip(matched)
+      .filter(|(_, matched)| !matched)
+    {
+      blocks.push(DiffBlock {
+        fingerprint,
+        old: Some(block.range()),
+        new: None,
+      });
+    }
+    Self { blocks }
+  }
+
+  /// Read a build log and prepare it with the active rule
+  pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<BuildOutput<'static>> {
+    let content = read_to_string(path.as_ref()).map_err(|e| {
+      err!(
+        ErrorKind::IO,
+        "failed to read build log '{}', {}",
+        path.as_ref().display(),
+        e
+      )
+    })?;
+    let mut build = BuildOutput::from(content.lines());
+    build.prepare();
+    Ok(build)
+  }
+
+  /// Retrieve the blocks of a given status, in order
+  pub fn with_status(&self, status: DiffStatus) -> impl Iterator<Item = &DiffBlock> {
+    self
+      .blocks
+      .iter()
+      .filter(move |block| block.status() == status)
+  }
+
+  /// Retrieve the blocks only found in the old log
+  #[cfg(test)]
+  pub fn fixed(&self) -> impl Iterator<Item = &DiffBlock> {
+    self.with_status(DiffStatus::Fixed)
+  }
+
+  /// Retrieve the blocks only found in the new log
+  pub fn regressions(&self) -> impl Iterator<Item = &DiffBlock> {
+    self.with_status(DiffStatus::Regression)
+  }
+
+  /// Retrieve the blocks found in both logs
+  #[cfg(test)]
+  pub fn unchanged(&self) -> impl Iterator<Item = &DiffBlock> {
+    self.with_status(DiffStatus::Unchanged)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use crate::{BuildOutput, BuildTagKind};
+
+  use super::{BuildDiff, DiffStatus};
+
+  const OLD_BUILD: &str = r#"warning: unused variable: `x`
+ --> src/main.rs:2:7
+error[E0425]: cannot find value `y` in this scope
+ --> src/main.rs:3:3
+error[E0308]: mismatched types
+ --> src/lib.rs:10:5"#;
+
+  // `y` was fixed, the lines moved and a new error appeared
+  const NEW_BUILD: &str = r#"error[E0308]: mismatched types
+ --> src/lib.rs:12:5
+error[E0599]: no method named `foo` found
+ --> src/lib.rs:20:9
+warning: unused variable: `x`
+ --> src/main.rs:4:7"#;
+
+  fn diff(old: &str, new: &str) -> BuildDiff {
+    let mut old = BuildOutput::from(old.split('\n'));
+    old.prepare();
+    let mut new = BuildOutput::from(new.split('\n'));
+    new.prepare();
+    BuildDiff::new(&old, &new)
+  }
+
+  fn first_lines<'d, I: Iterator<Item = &'d super::DiffBlock>>(blocks: I) -> Vec<String> {
+    blocks
+      .map(|block| block.fingerprint.first_line.clone())
+      .collect()
+  }
+
+  #[test]
+  fn compare() {
+    let diff = diff(OLD_BUILD, NEW_BUILD);
+    assert_eq!(
+      first_lines(diff.unchanged()),
+      vec![
+        "error[E0308]: mismatched types",
+        "warning: unused variable: `x`"
+      ]
+    );
+    assert_eq!(
+      first_lines(diff.regressions()),
+      vec!["error[E0599]: no method named `foo` found"]
+    );
+    assert_eq!(
+      first_lines(diff.fixed()),
+      vec!["error[E0425]: cannot find value `y` in this scope"]
+    );
+    let unchanged = diff.unchanged().next().unwrap();
+    assert_eq!(unchanged.old, Some(4..6));
+    assert_eq!(unchanged.new, Some(0..2));
+    assert_eq!(diff.fixed().next().unwrap().new, None);
+  }
+
+  #[test]
+  fn duplicates() {
+    // the same warning in two files, one of them got fixed
+    let old =
+      "warning: unused import\n --> src/a.rs:1:5\nwarning: unused import\n --> src/b.rs:1:5";
+    let new = "warning: unused import\n --> src/b.rs:1:5";
+    let diff = diff(old, new);
+    let unchanged = diff.unchanged().collect::<Vec<_>>();
+    assert_eq!(unchanged.len(), 1);
+    assert_eq!(unchanged[0].old, Some(2..4));
+    let fixed = diff.fixed().collect::<Vec<_>>();
+    assert_eq!(fixed.len(), 1);
+    assert_eq!(fixed[0].fingerprint.path, Some(PathBuf::from("src/a.rs")));
+    assert_eq!(fixed[0].fingerprint.kind, BuildTagKind::Warning);
+    assert_eq!(fixed[0].status(), DiffStatus::Fixed);
+  }
+
+  #[test]
+  fn same_log() {
+    let diff = diff(OLD_BUILD, OLD_BUILD);
+    assert_eq!(diff.unchanged().count(), 3);
+    assert_eq!(diff.fixed().count() + diff.regressions().count(), 0);
+  }
+
+  #[test]
+  fn load() {
+    let path = std::env::temp_dir().join(format!("nbuild-diff-{}.log", std::process::id()));
+    fs::write(&path, NEW_BUILD).unwrap();
+    let build = BuildDiff::load(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    assert_eq!(build.blocks().len(), 3);
+    assert!(BuildDiff::load(&path).is_err());
+  }
+}
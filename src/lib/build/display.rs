@@ -0,0 +1,486 @@
+use std::ops::Range;
+
+use crate::{DisplayedEntry, SplitState};
+
+use super::{BuildTagKind, Fold, FoldMap, Origin};
+
+/// The layers deciding which entries get displayed, combined by a [`DisplayMap`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayLayers {
+  /// The entries from this one are hidden, e.g. created after the cursor of the timeline
+  pub limit: Option<usize>,
+  /// The folded entries share the row of their fold
+  pub folds: FoldMap,
+  /// Only the entries tagged with this kind, e.g. the errors with `f`
+  pub kind: Option<BuildTagKind>,
+  /// Only the entries of a split pane, see [`SplitState::pane`]
+  pub pane: Option<Origin>,
+}
+
+/// A displayed row: an entry, or the entries of a fold sharing its row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayRow {
+  pub entries: Range<usize>,
+  /// The origin of the first entry, giving the pane of the row
+  pub origin: Origin,
+}
+
+/// Map the entries to the displayed rows, through all the [`DisplayLayers`].
+///
+/// The rows get mapped incrementally as the entries arrive. A change of the kind or the
+/// pane maps them again, a change of the folds or the limit only from the first entry
+/// it affects. A fold is displayed when one of its entries passes the filters,
+/// the pane being the one of its first entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayMap {
+  layers: DisplayLayers,
+  /// Sorted and non-overlapping
+  rows: Vec<DisplayRow>,
+  /// The number of entries mapped to the rows
+  mapped: usize,
+  /// The start of the fold cut by the last mapped entry, mapped again with the next ones
+  open_fold: Option<usize>,
+}
+
+impl DisplayMap {
+  #[cfg(test)]
+  pub fn new(layers: DisplayLayers) -> Self {
+    Self {
+      layers,
+      ..Default::default()
+    }
+  }
+
+  /// Retrieve the layers the rows get mapped with
+  #[cfg(test)]
+  pub fn layers(&self) -> &DisplayLayers {
+    &self.layers
+  }
+
+  /// Hide the entries from `limit`
+  pub fn set_limit(&mut self, limit: Option<usize>) {
+    if limit == self.layers.limit {
+      return;
+    }
+    if let Some(limit) = limit {
+      self.rewind(limit);
+    }
+    self.layers.limit = limit;
+  }
+
+  /// Define the folds, the rows get mapped again from the first changed fold
+  pub fn set_folds(&mut self, folds: FoldMap) {
+    if folds == self.layers.folds {
+      return;
+    }
+    let (old, new) = (self.layers.folds.folds(), folds.folds());
+    let changed = old
+      .iter()
+      .zip(new)
+      .find(|(old, new)| old != new)
+      .map(|(old, new)| old.range.start.min(new.range.start))
+      .or_else(|| {
+        old
+          .get(new.len())
+          .or(new.get(old.len()))
+          .map(|fold| fold.range.start)
+      });
+    if let Some(entry_id) = changed {
+      self.rewind(entry_id);
+    }
+    self.layers.folds = folds;
+  }
+
+  /// Only display the entries tagged with `kind`
+  pub fn set_kind(&mut self, kind: Option<BuildTagKind>) {
+    if kind != self.layers.kind {
+      self.layers.kind = kind;
+      self.invalidate();
+    }
+  }
+
+  /// Only display the entries of a split pane
+  #[cfg(test)]
+  pub fn set_pane(&mut self, pane: Option<Origin>) {
+    let pane = pane.map(SplitState::pane);
+    if pane != self.layers.pane {
+      self.layers.pane = pane;
+      self.invalidate();
+    }
+  }
+
+  /// Map all the entries again on the next update, e.g. when they got prepared again
+  pub fn invalidate(&mut self) {
+    self.rewind(0);
+  }
+
+  /// Forget the rows displaying the entries from `entry_id`
+  fn rewind(&mut self, entry_id: usize) {
+    // a fold gets mapped again as a whole
+    let entry_id = self
+      .layers
+      .folds
+      .fold_at(entry_id)
+      .map_or(entry_id, |fold| fold.range.start);
+    let kept = self.rows.partition_point(|row| row.entries.end <= entry_id);
+    self.rows.truncate(kept);
+    self.mapped = self.mapped.min(entry_id);
+    self.open_fold = self.open_fold.filter(|start| *start < entry_id);
+  }
+
+  /// Check if the row of `lines` passes the kind and the pane filters
+  fn shows(&self, lines: &[DisplayedEntry<'_>]) -> bool {
+    let pane = match (self.layers.pane, lines.first()) {
+      (Some(pane), Some(first)) => SplitState::pane(first.origin()) == pane,
+      (_, first) => first.is_some(),
+    };
+    pane
+      && match self.layers.kind {
+        Some(kind) => lines.iter().any(|line| line.kinds().contains(kind)),
+        None => true,
+      }
+  }
+
+  /// Map the entries of `lines` not mapped yet, or all of them after a change of the layers
+  pub fn update(&mut self, lines: &[DisplayedEntry<'_>]) {
+    let end = self
+      .layers
+      .limit
+      .map_or(lines.len(), |limit| limit.min(lines.len()));
+    // e.g. a new run
+    if end < self.mapped {
+      self.rewind(end);
+    }
+    if let Some(start) = self.open_fold.take() {
+      self.rewind(start);
+    }
+    let folds = self.layers.folds.folds();
+    let mut next_fold = folds.partition_point(|fold| fold.range.end <= self.mapped);
+    let mut entry_id = self.mapped;
+    while entry_id < end {
+      let fold = folds
+        .get(next_fold)
+        .filter(|fold| fold.range.start <= entry_id);
+      let range = match fold {
+        Some(fold) => {
+          next_fold += 1;
+          if fold.range.end > end {
+            self.open_fold = Some(entry_id);
+          }
+          entry_id..fold.range.end.min(end)
+        }
+        None => entry_id..entry_id + 1,
+      };
+      if self.shows(&lines[range.clone()]) {
+        self.rows.push(DisplayRow {
+          origin: lines[range.start].origin(),
+          entries: range.clone(),
+        });
+      }
+      entry_id = range.end;
+    }
+    self.mapped = self.mapped.max(end);
+  }
+
+  /// Retrieve the rows of a pane, mapped from the rows of this one
+  pub fn pane(&self, pane: Origin) -> DisplayMap {
+    let pane = SplitState::pane(pane);
+    Self {
+      layers: DisplayLayers {
+        pane: Some(pane),
+        ..self.layers.clone()
+      },
+      rows: self
+        .rows
+        .iter()
+        .filter(|row| SplitState::pane(row.origin) == pane)
+        .cloned()
+        .collect(),
+      mapped: self.mapped,
+      open_fold: self.open_fold,
+    }
+  }
+
+  /// Retrieve the displayed rows, in order
+  pub fn rows(&self) -> &[DisplayRow] {
+    &self.rows
+  }
+
+  /// Retrieve the number of displayed rows
+  pub fn len(&self) -> usize {
+    self.rows.len()
+  }
+
+  /// Check if no row is displayed
+  #[cfg(test)]
+  pub fn is_empty(&self) -> bool {
+    self.rows.is_empty()
+  }
+
+  /// Retrieve the row displaying `entry_id`, or the next displayed one when it is hidden.
+  /// [`Self::len`] when no row displays it or a later entry
+  pub fn row(&self, entry_id: usize) -> usize {
+    self.rows.partition_point(|row| row.entries.end <= entry_id)
+  }
+
+  /// Retrieve the first entry displayed on `row`
+  pub fn entry_at(&self, row: usize) -> Option<usize> {
+    self.rows.get(row).map(|row| row.entries.start)
+  }
+
+  /// Retrieve the fold displayed on `row`
+  pub fn fold_at(&self, row: usize) -> Option<&Fold> {
+    let row = self.rows.get(row)?;
+    self
+      .layers
+      .folds
+      .fold_at(row.entries.start)
+      .filter(|fold| fold.range.start == row.entries.start)
+  }
+
+  /// Build the displayed rows from the `lines` given to [`Self::update`]:
+  /// the hidden lines are dropped, the folded ones replaced with the row of their fold
+  pub fn apply<'a>(&self, lines: Vec<DisplayedEntry<'a>>) -> Vec<DisplayedEntry<'a>> {
+    let mut ret = Vec::with_capacity(self.rows.len());
+    let mut lines = lines.into_iter().enumerate();
+    for (i, row) in self.rows.iter().enumerate() {
+      let Some((_, line)) = lines.find(|(entry_id, _)| *entry_id == row.entries.start) else {
+        break;
+      };
+      let folded = lines
+        .by_ref()
+        .take(row.entries.len() - 1)
+        .map(|(_, line)| line);
+      ret.push(match self.fold_at(i) {
+        Some(fold) => FoldMap::fold_row(fold, line, folded),
+        None => line,
+      });
+    }
+    ret
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::text::Line;
+
+  use crate::{BuildTagKind, BuildTagKinds, DisplayedEntry, Fold, FoldMap, LogEntry, Origin};
+
+  use super::{DisplayLayers, DisplayMap};
+
+  /// The lines of a build: `E` an error, `N` a note, `-` a plain line, lower case on stdout
+  fn lines(spec: &str) -> Vec<DisplayedEntry<'static>> {
+    spec
+      .chars()
+      .enumerate()
+      .map(|(i, ch)| {
+        let kinds = match ch.to_ascii_uppercase() {
+          'E' => BuildTagKinds::from_iter([BuildTagKind::Error]),
+          'N' => BuildTagKinds::from_iter([BuildTagKind::Note]),
+          _ => BuildTagKinds::default(),
+        };
+        let origin = match ch.is_ascii_lowercase() {
+          true => Origin::Stdout,
+          false => Origin::Stderr,
+        };
+        LogEntry::new(Line::raw(i.to_string()), kinds)
+          .with_origin(origin)
+          .into()
+      })
+      .collect()
+  }
+
+  /// The first entry of each row
+  fn starts(display: &DisplayMap) -> Vec<usize> {
+    display.rows().iter().map(|row| row.entries.start).collect()
+  }
+
+  /// The text of the displayed rows
+  fn text(display: &DisplayMap, lines: Vec<DisplayedEntry<'_>>) -> Vec<String> {
+    display
+      .apply(lines)
+      .iter()
+      .map(|line| line.line().to_string())
+      .collect()
+  }
+
+  /// The map of `lines` built at once
+  fn mapped(layers: DisplayLayers, lines: &[DisplayedEntry<'_>]) -> DisplayMap {
+    let mut display = DisplayMap::new(layers);
+    display.update(lines);
+    display
+  }
+
+  #[test]
+  fn unfiltered() {
+    let lines = lines("E--N-");
+    let display = mapped(DisplayLayers::default(), &lines);
+    assert_eq!(starts(&display), vec![0, 1, 2, 3, 4]);
+    for entry_id in 0..5 {
+      assert_eq!(display.row(entry_id), entry_id);
+      assert_eq!(display.entry_at(entry_id), Some(entry_id));
+    }
+    assert_eq!(display.row(5), 5);
+    assert_eq!(display.entry_at(5), None);
+  }
+
+  #[test]
+  fn layers() {
+    let lines = lines("E-n-EeN--E");
+    let layers = DisplayLayers {
+      limit: Some(9),
+      folds: FoldMap::new([Fold::new(1..4, "backtrace"), Fold::repeats(7..9)]),
+      kind: None,
+      pane: None,
+    };
+    let display = mapped(layers.clone(), &lines);
+    assert_eq!(starts(&display), vec![0, 1, 4, 5, 6, 7]);
+    assert_eq!(
+      text(&display, lines.clone()),
+      vec![
+        "0",
+        "     … backtrace (3 lines), press x to expand",
+        "4",
+        "5",
+        "6",
+        "7 (×2)"
+      ]
+    );
+    // the folded entries share the row of their fold, the hidden ones the next row
+    assert_eq!(display.row(2), 1);
+    assert_eq!(display.row(8), 5);
+    assert_eq!(display.row(9), 6);
+    assert_eq!(
+      display.fold_at(1).map(|fold| fold.label.as_str()),
+      Some("backtrace")
+    );
+    assert_eq!(display.fold_at(2), None);
+    // the errors only: none in the fold
+    let errors = mapped(
+      DisplayLayers {
+        kind: Some(BuildTagKind::Error),
+        ..layers.clone()
+      },
+      &lines,
+    );
+    assert_eq!(starts(&errors), vec![0, 4, 5]);
+    assert_eq!(errors.row(2), 1);
+    assert_eq!(errors.row(6), 3);
+    // with the stdout pane, the fold shows for its first entry
+    let stdout = mapped(
+      DisplayLayers {
+        kind: Some(BuildTagKind::Error),
+        pane: Some(Origin::Stdout),
+        ..layers.clone()
+      },
+      &lines,
+    );
+    assert_eq!(starts(&stdout), vec![5]);
+    assert_eq!(text(&stdout, lines.clone()), vec!["5"]);
+    let stderr = mapped(
+      DisplayLayers {
+        pane: Some(Origin::Stderr),
+        ..layers.clone()
+      },
+      &lines,
+    );
+    assert_eq!(starts(&stderr), vec![0, 1, 4, 6, 7]);
+    // the projection of the merged rows gives the same panes
+    assert_eq!(display.pane(Origin::Stderr), stderr);
+    assert_eq!(display.pane(Origin::Stdin).rows(), &display.rows()[3..4]);
+  }
+
+  #[test]
+  fn empty() {
+    let display = mapped(DisplayLayers::default(), &[]);
+    assert!(display.is_empty());
+    assert_eq!(display.row(3), 0);
+    assert_eq!(display.entry_at(0), None);
+    assert!(text(&display, vec![]).is_empty());
+    // nothing passes the filters
+    let lines = lines("-n--");
+    let display = mapped(
+      DisplayLayers {
+        kind: Some(BuildTagKind::Error),
+        ..Default::default()
+      },
+      &lines,
+    );
+    assert!(display.is_empty());
+    assert_eq!(display.row(2), 0);
+    assert!(text(&display, lines).is_empty());
+    // all hidden by the limit
+    let lines = self::lines("EE");
+    let display = mapped(
+      DisplayLayers {
+        limit: Some(0),
+        ..Default::default()
+      },
+      &lines,
+    );
+    assert!(display.is_empty());
+    assert!(display.pane(Origin::Stderr).is_empty());
+  }
+
+  #[test]
+  fn incremental() {
+    let all = lines("E-N--E-n-E");
+    let layers = DisplayLayers {
+      folds: FoldMap::new([Fold::new(2..6, "backtrace")]),
+      kind: Some(BuildTagKind::Error),
+      ..Default::default()
+    };
+    // the fold is cut by the entries received so far
+    let mut display = DisplayMap::new(layers.clone());
+    for end in [1, 3, 4, 7, 7, 10] {
+      display.update(&all[..end]);
+      assert_eq!(display, mapped(layers.clone(), &all[..end]), "{}", end);
+    }
+    assert_eq!(starts(&display), vec![0, 2, 9]);
+    // a new run starts over
+    display.update(&all[..2]);
+    assert_eq!(starts(&display), vec![0]);
+  }
+
+  #[test]
+  fn invalidation() {
+    let all = lines("E-eN-E--nE");
+    let mut display = DisplayMap::default();
+    display.update(&all);
+    let changes: Vec<fn(&mut DisplayMap)> = vec![
+      |display| display.set_kind(Some(BuildTagKind::Error)),
+      |display| display.set_folds(FoldMap::new([Fold::new(6..9, "backtrace")])),
+      |display| {
+        display.set_folds(FoldMap::new([
+          Fold::new(1..3, "first"),
+          Fold::new(6..9, "backtrace"),
+        ]))
+      },
+      |display| display.set_limit(Some(7)),
+      |display| display.set_pane(Some(Origin::Stdin)),
+      |display| display.set_limit(None),
+      |display| display.set_folds(FoldMap::default()),
+      |display| display.set_kind(Some(BuildTagKind::Note)),
+      |display| display.set_pane(None),
+    ];
+    for (i, change) in changes.iter().enumerate() {
+      change(&mut display);
+      display.update(&all);
+      assert_eq!(
+        display,
+        mapped(display.layers().clone(), &all),
+        "change #{}",
+        i
+      );
+    }
+    assert_eq!(starts(&display), vec![3, 8]);
+    // the same layers keep the rows
+    let rows = display.rows().to_vec();
+    display.set_kind(Some(BuildTagKind::Note));
+    assert_eq!(display.rows(), rows.as_slice());
+    display.invalidate();
+    assert!(display.is_empty());
+    display.update(&all);
+    assert_eq!(display.rows(), rows.as_slice());
+  }
+}
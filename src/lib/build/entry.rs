@@ -1,8 +1,10 @@
 use std::time::Instant;
 
-use crate::MarkerRef;
+use crate::{display_path, CapturedMarker, MarkerRef};
 
-use super::{rules, BuildTag, BuildTagKind, Origin, Rule, DEFAULT_RULES};
+use super::{
+  rules, BuildTag, BuildTagKind, Location, Origin, Rule, Severity, Suggestion, DEFAULT_RULES,
+};
 
 /// Represent an output line written by the cargo build process [`BuildCommand`]
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
@@ -11,16 +13,24 @@ pub struct BuildEntry {
   message: String,
   origin: Origin,
   tags: Vec<BuildTag>,
+  suggestions: Vec<Suggestion>,
 }
 
 impl BuildEntry {
   /// Construct a new entry
   pub fn new<M: AsRef<str>>(msg: M, orig: Origin) -> Self {
+    Self::with_timestamp(msg, orig, Instant::now())
+  }
+
+  /// Construct a new entry captured at a specific [`Instant`],
+  /// readers pass the time the line was read rather than the time it gets queued
+  pub fn with_timestamp<M: AsRef<str>>(msg: M, orig: Origin, at: Instant) -> Self {
     Self {
-      created_at: Instant::now(),
+      created_at: at,
       message: msg.as_ref().to_string(),
       origin: orig,
       tags: vec![],
+      suggestions: vec![],
     }
   }
 
@@ -34,6 +44,11 @@ impl BuildEntry {
     self
   }
 
+  pub fn with_suggestions<I: IntoIterator<Item = Suggestion>>(mut self, suggestions: I) -> Self {
+    self.suggestions.extend(suggestions);
+    self
+  }
+
   /// Retrieve the [`Instant`] this entry was created
   pub fn created_at(&self) -> &Instant {
     &self.created_at
@@ -44,6 +59,16 @@ impl BuildEntry {
     &self.message
   }
 
+  /// Replace the line's content
+  pub fn set_message<M: AsRef<str>>(&mut self, msg: M) {
+    self.message = msg.as_ref().to_string();
+  }
+
+  /// Retrieve the [`Suggestion`]s of the JSON diagnostic this entry was rendered from
+  pub fn suggestions(&self) -> &Vec<Suggestion> {
+    &self.suggestions
+  }
+
   /// Retrieve the [`Origin`] this entry was created from
   pub fn origin(&self) -> Origin {
     self.origin
@@ -91,6 +116,41 @@ impl BuildEntry {
     None
   }
 
+  /// Retrieve the most severe [`MarkerRef`] of this entry, the first one on ties.
+  ///
+  /// This is the marker displayed, counted and navigated to, the other matches
+  /// stay available through [`Self::tags`]
+  pub fn primary_marker(&self) -> Option<&MarkerRef> {
+    self
+      .tags
+      .iter()
+      .filter_map(|tag| tag.get_marker())
+      .rev()
+      .max_by_key(|marker| marker.kind().severity())
+  }
+
+  /// Retrieve the severity of the [`Self::primary_marker`], `None` if this entry is not a marker
+  pub fn severity(&self) -> Option<Severity> {
+    self
+      .primary_marker()
+      .and_then(|marker| marker.kind().severity())
+  }
+
+  /// Retrieve the tags of a kind, in the order they were added
+  pub fn tags_of_kind(&self, kind: BuildTagKind) -> impl Iterator<Item = &BuildTag> {
+    self.tags.iter().filter(move |tag| tag.get_kind() == kind)
+  }
+
+  /// Retrieve the text captured by the first marker of a kind
+  pub fn captured_marker(&self, kind: BuildTagKind) -> Option<&CapturedMarker> {
+    self.tags_of_kind(kind).find_map(|tag| tag.get_capture())
+  }
+
+  /// Retrieve the [`Location`]s tagged on this entry
+  pub fn locations(&self) -> impl Iterator<Item = &Location> {
+    self.tags.iter().filter_map(|tag| tag.get_location())
+  }
+
   /// Retrieve all tags
   pub fn tags(&self) -> &Vec<BuildTag> {
     &self.tags
@@ -124,7 +184,7 @@ impl BuildEntry {
         match t.get_location() {
           Some(loc) => format!(
             "{}{}{}",
-            loc.path().display(),
+            display_path(loc.path()),
             match loc.line() {
               Some(line) => format!(":{}", line),
               None => String::new(),
@@ -146,3 +206,63 @@ impl<S: AsRef<str>> From<S> for BuildEntry {
     BuildEntry::new(value.as_ref(), Origin::default())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use crate::{BuildEntry, BuildTag, BuildTagKind, CapturedMarker, Origin, Severity};
+
+  #[test]
+  fn severity() {
+    let plain = BuildEntry::new("   Compiling demo", Origin::default());
+    assert_eq!(plain.severity(), None);
+    let entry =
+      BuildEntry::new("note: warning: error: all of them", Origin::default()).with_tags([
+        BuildTag::note(0..5, "note:").unwrap(),
+        BuildTag::warning(6..14, "warning:").unwrap(),
+        BuildTag::error(15..21, "error:").unwrap(),
+      ]);
+    assert_eq!(entry.severity(), Some(Severity::Error));
+    assert!(Severity::Note < Severity::Warning && Severity::Warning < Severity::Error);
+    assert_eq!(BuildTagKind::Location.severity(), None);
+  }
+
+  #[test]
+  fn captured_marker() {
+    let entry =
+      BuildEntry::new("warning: error[E0308]: mismatched", Origin::default()).with_tags([
+        BuildTag::warning(0..8, "warning:").unwrap(),
+        BuildTag::error(9..22, "error[E0308]:").unwrap(),
+      ]);
+    assert_eq!(
+      entry.captured_marker(BuildTagKind::Error),
+      Some(&CapturedMarker::new(9, "error[E0308]:"))
+    );
+    assert_eq!(
+      entry.captured_marker(BuildTagKind::Warning),
+      Some(&CapturedMarker::new(0, "warning:"))
+    );
+    assert_eq!(entry.captured_marker(BuildTagKind::Note), None);
+    assert_eq!(entry.tags_of_kind(BuildTagKind::Error).count(), 1);
+  }
+
+  #[test]
+  fn locations() {
+    let entry = BuildEntry::new("  --> src/a.rs:1:2", Origin::default()).with_tags([
+      BuildTag::location("src/a.rs", Some(1), Some(2)),
+      BuildTag::location("src/b.rs", None, None),
+    ]);
+    assert_eq!(
+      entry
+        .locations()
+        .map(|location| (location.path().clone(), location.line()))
+        .collect::<Vec<_>>(),
+      vec![
+        (PathBuf::from("src/a.rs"), Some(1)),
+        (PathBuf::from("src/b.rs"), None)
+      ]
+    );
+    assert_eq!(BuildEntry::from("nothing").locations().count(), 0);
+  }
+}
@@ -1,4 +1,12 @@
-use std::process::ExitStatus;
+use std::{
+  fmt::Display,
+  process::ExitStatus,
+  time::{Duration, Instant},
+};
+
+use crate::{format_count, human_duration};
+
+use super::Origin;
 
 /// Represent a cargo build event
 #[derive(Debug, Clone, Copy)]
@@ -7,6 +15,105 @@ pub enum BuildEvent {
   BuildStarted,
   /// Cargo process finished
   BuildFinished(ExitStatus),
-  /// Compilation error detected
-  BuildError(usize),
+  /// The first compilation error of the build got prepared, sent once whatever the number
+  /// of errors. The next ones are listed by [`crate::BuildOutput::errors`]
+  FirstErrorDetected(usize),
+  /// The build output could not be followed, see [`BuildFailure`]
+  BuildFailed(BuildFailure),
+  /// A stream of the build output reached its end, see [`IntakeStats`]
+  IntakeStats(IntakeStats),
+  /// Cargo's progress bar got drawn with the crates compiled and to compile,
+  /// only shown with a terminal, see [`crate::build_progress`]
+  BuildProgress(usize, usize),
+}
+
+/// What the reader of a stream measured, to tell a slow build from a slow pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntakeStats {
+  pub origin: Origin,
+  /// The bytes of the lines read, one per line ending
+  pub bytes: u64,
+  pub lines: usize,
+  /// The time spent blocked in the reads, waiting for the build
+  pub blocked: Duration,
+  /// The time spent handling the lines read, until they got sent
+  pub processing: Duration,
+  /// The longest time between two lines
+  pub max_gap: Duration,
+  /// The lines dropped by the [`crate::IntakeFilter`]
+  pub dropped: usize,
+  last_line_at: Option<Instant>,
+}
+
+impl IntakeStats {
+  pub fn new(origin: Origin) -> Self {
+    Self {
+      origin,
+      bytes: 0,
+      lines: 0,
+      blocked: Duration::ZERO,
+      processing: Duration::ZERO,
+      max_gap: Duration::ZERO,
+      dropped: 0,
+      last_line_at: None,
+    }
+  }
+
+  /// Count a line read at `at`
+  pub fn record_line(&mut self, line: &str, at: Instant) {
+    self.bytes += line.len() as u64 + 1;
+    self.lines += 1;
+    if let Some(last) = self.last_line_at {
+      self.max_gap = self.max_gap.max(at.duration_since(last));
+    }
+    self.last_line_at = Some(at);
+  }
+}
+
+impl Display for IntakeStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}: {} line(s), {} byte(s), blocked {}, processing {}, max gap {}",
+      format!("{:?}", self.origin).to_lowercase(),
+      format_count(self.lines),
+      format_count(self.bytes as usize),
+      human_duration(self.blocked),
+      human_duration(self.processing),
+      human_duration(self.max_gap)
+    )?;
+    if self.dropped > 0 {
+      write!(f, ", dropped {}", format_count(self.dropped))?;
+    }
+    Ok(())
+  }
+}
+
+/// Why the build output could not be followed, the details are logged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildFailure {
+  /// A line of the stream was not valid UTF-8, it was skipped
+  InvalidLine(Origin),
+  /// The stream could not be read anymore
+  Unreadable(Origin),
+  /// The cargo process could not be waited for
+  Lost,
+}
+
+impl Display for BuildFailure {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::InvalidLine(origin) => write!(
+        f,
+        "skipped an invalid line from {}",
+        format!("{:?}", origin).to_lowercase()
+      ),
+      Self::Unreadable(origin) => write!(
+        f,
+        "failed to read {}",
+        format!("{:?}", origin).to_lowercase()
+      ),
+      Self::Lost => write!(f, "failed to wait for cargo"),
+    }
+  }
 }
@@ -0,0 +1,139 @@
+use std::{fmt::Display, process::ExitStatus};
+
+/// The signal that terminated a build, decoded from its [`ExitStatus`] on unix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitCause {
+  /// The number of the signal
+  pub signal: i32,
+  /// Whether the process dumped its core
+  pub core_dumped: bool,
+}
+
+impl ExitCause {
+  /// Decode the signal terminating the process of `status`,
+  /// `None` for a process that exited by itself or on other platforms
+  #[cfg(unix)]
+  pub fn from_status(status: ExitStatus) -> Option<Self> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal().map(|signal| Self {
+      signal,
+      core_dumped: status.core_dumped(),
+    })
+  }
+
+  /// Decode the signal terminating the process of `status`,
+  /// `None` for a process that exited by itself or on other platforms
+  #[cfg(not(unix))]
+  pub fn from_status(_status: ExitStatus) -> Option<Self> {
+    None
+  }
+
+  /// Retrieve the name of the signal, e.g. `SIGKILL`
+  #[cfg(unix)]
+  pub fn signal_name(&self) -> Option<&'static str> {
+    Some(match self.signal {
+      libc::SIGHUP => "SIGHUP",
+      libc::SIGINT => "SIGINT",
+      libc::SIGQUIT => "SIGQUIT",
+      libc::SIGILL => "SIGILL",
+      libc::SIGTRAP => "SIGTRAP",
+      libc::SIGABRT => "SIGABRT",
+      libc::SIGBUS => "SIGBUS",
+      libc::SIGFPE => "SIGFPE",
+      libc::SIGKILL => "SIGKILL",
+      libc::SIGSEGV => "SIGSEGV",
+      libc::SIGPIPE => "SIGPIPE",
+      libc::SIGALRM => "SIGALRM",
+      libc::SIGTERM => "SIGTERM",
+      libc::SIGXCPU => "SIGXCPU",
+      libc::SIGXFSZ => "SIGXFSZ",
+      _ => return None,
+    })
+  }
+
+  /// Retrieve the name of the signal, e.g. `SIGKILL`
+  #[cfg(not(unix))]
+  pub fn signal_name(&self) -> Option<&'static str> {
+    None
+  }
+
+  /// Guess why the build got the signal, e.g. `possibly out of memory` for `SIGKILL`
+  #[cfg(unix)]
+  pub fn hint(&self) -> Option<&'static str> {
+    Some(match self.signal {
+      libc::SIGKILL => "possibly out of memory, see `dmesg` for the OOM killer",
+      libc::SIGSEGV | libc::SIGBUS => "a crash of the compiler, a build script or a proc-macro",
+      libc::SIGABRT => "an abort, e.g. a failed assertion or a panic with panic=abort",
+      libc::SIGILL | libc::SIGFPE | libc::SIGTRAP => {
+        "a crash, possibly a miscompiled build script or proc-macro"
+      }
+      libc::SIGINT | libc::SIGTERM | libc::SIGHUP | libc::SIGQUIT => "interrupted",
+      libc::SIGXCPU | libc::SIGXFSZ => "a resource limit was reached, see `ulimit`",
+      _ => return None,
+    })
+  }
+
+  /// Guess why the build got the signal, e.g. `possibly out of memory` for `SIGKILL`
+  #[cfg(not(unix))]
+  pub fn hint(&self) -> Option<&'static str> {
+    None
+  }
+
+  /// Format the cause followed by its hint,
+  /// e.g. `killed by SIGKILL — possibly out of memory, see ...`
+  pub fn explain(&self) -> String {
+    match self.hint() {
+      Some(hint) => format!("{} — {}", self, hint),
+      None => self.to_string(),
+    }
+  }
+}
+
+impl Display for ExitCause {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.signal_name() {
+      Some(name) => write!(f, "killed by {}", name)?,
+      None => write!(f, "killed by signal {}", self.signal)?,
+    }
+    if self.core_dumped {
+      write!(f, " (core dumped)")?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ExitCause;
+
+  #[cfg(unix)]
+  #[test]
+  fn from_status() {
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    assert_eq!(ExitCause::from_status(ExitStatus::default()), None);
+    assert_eq!(ExitCause::from_status(ExitStatus::from_raw(101 << 8)), None);
+
+    let killed = ExitCause::from_status(ExitStatus::from_raw(libc::SIGKILL)).unwrap();
+    assert_eq!(killed.signal_name(), Some("SIGKILL"));
+    assert!(!killed.core_dumped);
+    assert_eq!(killed.to_string(), "killed by SIGKILL");
+    assert_eq!(
+      killed.explain(),
+      "killed by SIGKILL — possibly out of memory, see `dmesg` for the OOM killer"
+    );
+
+    // the core dump flag
+    let crashed = ExitCause::from_status(ExitStatus::from_raw(libc::SIGSEGV | 0x80)).unwrap();
+    assert!(crashed.core_dumped);
+    assert_eq!(crashed.to_string(), "killed by SIGSEGV (core dumped)");
+    assert!(crashed.hint().unwrap().contains("crash"));
+
+    // e.g. a real-time signal
+    let unknown = ExitCause::from_status(ExitStatus::from_raw(63)).unwrap();
+    assert_eq!(unknown.signal_name(), None);
+    assert_eq!(unknown.hint(), None);
+    assert_eq!(unknown.explain(), "killed by signal 63");
+  }
+}
@@ -0,0 +1,244 @@
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::Location;
+
+lazy_static! {
+  /// The help of the lints denied on the command line,
+  /// e.g. ``to override `-D warnings` add `#[allow(clippy::needless_return)]` ``
+  static ref ALLOW_HELP: Regex = Regex::new(r"add `#!?\[allow\(([\w:-]+)\)\]`")
+    .expect("invalid regular expression");
+  /// The note of a lint enabled by default, e.g. ``#[warn(dead_code)]` on by default``
+  static ref LINT_LEVEL: Regex = Regex::new(r"`#!?\[(?:warn|deny|forbid)\(([\w:-]+)\)\]`")
+    .expect("invalid regular expression");
+  /// The note of a lint enabled on the command line,
+  /// e.g. ``-W clippy::needless-pass-by-value` implied by `-W clippy::pedantic` ``
+  static ref LINT_FLAG: Regex = Regex::new(r"`-[WDF] ([\w:-]+)` implied by")
+    .expect("invalid regular expression");
+}
+
+/// The crate roots, where the lints get allowed for the whole crate
+const CRATE_ROOTS: [&str; 2] = ["lib.rs", "main.rs"];
+
+/// The lints checked once for the whole crate, only allowed by an inner attribute
+const CRATE_LEVEL_LINTS: [&str; 6] = [
+  "unused_crate_dependencies",
+  "clippy::cargo_common_metadata",
+  "clippy::multiple_crate_versions",
+  "clippy::negative_feature_names",
+  "clippy::redundant_feature_names",
+  "clippy::wildcard_dependencies",
+];
+
+/// Extract the name of the lint of a warning from one of its note lines, with the
+/// underscores of the attributes: `-W unused-imports` gives `unused_imports`.
+///
+/// The case and the tool (`clippy::`) are kept. `None` if the line names no lint
+pub fn lint_name<L: AsRef<str>>(line: L) -> Option<String> {
+  let line = line.as_ref();
+  [&*ALLOW_HELP, &*LINT_LEVEL, &*LINT_FLAG]
+    .into_iter()
+    .find_map(|regex| regex.captures(line))
+    .map(|captures| captures[1].replace('-', "_"))
+}
+
+/// Extract the name of the lint of a block, from its first line naming one
+pub fn block_lint_name<L: AsRef<str>>(lines: &[L]) -> Option<String> {
+  lines.iter().find_map(lint_name)
+}
+
+/// Check if a file is a crate root: `lib.rs`, `main.rs` or a binary of `src/bin`
+pub fn is_crate_root<P: AsRef<Path>>(path: P) -> bool {
+  let path = path.as_ref();
+  let binary = path.parent().is_some_and(|dir| dir.ends_with("src/bin"))
+    && path.extension().is_some_and(|extension| extension == "rs");
+  binary
+    || path
+      .file_name()
+      .is_some_and(|name| CRATE_ROOTS.iter().any(|root| name == *root))
+}
+
+/// Check if the 1-based `line` of a file comes before its first item: only blank
+/// lines, comments, inner doc comments and inner attributes lead to it
+pub fn before_first_item<L: AsRef<str>>(lines: &[L], line: usize) -> bool {
+  // the brackets left open by a multi-line inner attribute
+  let mut depth = 0usize;
+  (1..=lines.len()).contains(&line)
+    && lines.iter().take(line).all(|content| {
+      let content = content.as_ref().trim();
+      let attribute = depth > 0 || content.starts_with("#![");
+      if attribute {
+        depth = (depth + content.matches('[').count()).saturating_sub(content.matches(']').count());
+      }
+      attribute || content.is_empty() || (content.starts_with("//") && !content.starts_with("///"))
+    })
+}
+
+/// Check if a warning was reported at the top of a crate root, before its first item
+fn is_crate_header(location: &Location) -> bool {
+  let Some(line) = location.line() else {
+    return false;
+  };
+  is_crate_root(location.path())
+    && std::fs::read_to_string(location.canonical_path())
+      .is_ok_and(|content| before_first_item(&content.lines().collect::<Vec<_>>(), line))
+}
+
+/// Format the text allowing `lint` where the warning was reported:
+/// - `#![allow(lint)]` for the crate-level lints, or a warning of a crate root before its
+///   first item: the inner attribute goes at the top of the file
+/// - `#[allow(lint)]` otherwise, the outer attribute goes above the item
+/// - `-A warnings` without lint name, for the `RUSTFLAGS`
+pub fn allow_attribute(lint: Option<&str>, location: Option<&Location>) -> String {
+  match lint {
+    None => "-A warnings".to_string(),
+    Some(lint) if CRATE_LEVEL_LINTS.contains(&lint) || location.is_some_and(is_crate_header) => {
+      format!("#![allow({})]", lint)
+    }
+    Some(lint) => format!("#[allow({})]", lint),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{create_dir_all, remove_dir_all, write};
+
+  use crate::Location;
+
+  use super::{allow_attribute, before_first_item, block_lint_name, is_crate_root, lint_name};
+
+  #[test]
+  fn lint_names() {
+    for (line, expected) in [
+      ("  = note: `#[warn(dead_code)]` on by default", "dead_code"),
+      (
+        "  = note: `#[warn(unused_variables)]` (part of `#[warn(unused)]`) on by default",
+        "unused_variables",
+      ),
+      (
+        "  = note: `#[warn(non_snake_case)]` on by default",
+        "non_snake_case",
+      ),
+      (
+        "  = note: `#[warn(clippy::needless_return)]` on by default",
+        "clippy::needless_return",
+      ),
+      (
+        "  = note: `#[deny(clippy::approx_constant)]` on by default",
+        "clippy::approx_constant",
+      ),
+      (
+        "  = note: `-W clippy::needless-pass-by-value` implied by `-W clippy::pedantic`",
+        "clippy::needless_pass_by_value",
+      ),
+      (
+        "  = note: `-D unused-variables` implied by `-D warnings`",
+        "unused_variables",
+      ),
+      (
+        "  = help: to override `-D warnings` add `#[allow(clippy::redundant_clone)]`",
+        "clippy::redundant_clone",
+      ),
+      (
+        "  = help: to override `-W clippy::pedantic` add `#[allow(clippy::must_use_candidate)]`",
+        "clippy::must_use_candidate",
+      ),
+    ] {
+      assert_eq!(lint_name(line).as_deref(), Some(expected), "{}", line);
+    }
+    for line in [
+      "warning: unused variable: `x`",
+      "  = help: for further information visit https://rust-lang.github.io/rust-clippy/master/index.html#needless_return",
+      // the source of the crate, not a note
+      "1 | #![warn(missing_docs)]",
+    ] {
+      assert_eq!(lint_name(line), None, "{}", line);
+    }
+  }
+
+  #[test]
+  fn block_lint() {
+    let block = [
+      "warning: unneeded `return` statement",
+      "  --> src/lib.rs:3:5",
+      "  = help: for further information visit https://rust-lang.github.io/rust-clippy/master/index.html#needless_return",
+      "  = note: `#[warn(clippy::needless_return)]` on by default",
+    ];
+    assert_eq!(
+      block_lint_name(&block).as_deref(),
+      Some("clippy::needless_return")
+    );
+    assert_eq!(block_lint_name(&block[..3]), None);
+  }
+
+  #[test]
+  fn header() {
+    let lines = [
+      "//! A demo",
+      "#![cfg_attr(",
+      "  feature = \"nightly\",",
+      "  feature(test)",
+      ")]",
+      "",
+      "/// The entry point",
+      "fn main() {}",
+    ];
+    assert!((1..=6).all(|line| before_first_item(&lines, line)));
+    assert!(!before_first_item(&lines, 7));
+    assert!(!before_first_item(&lines, 8));
+    assert!(!before_first_item(&lines, 0));
+    assert!(!before_first_item(&lines, 9));
+  }
+
+  #[test]
+  fn attributes() {
+    let dir = std::env::temp_dir().join(format!("nbuild-export-{}", std::process::id()));
+    create_dir_all(dir.join("src/bin")).unwrap();
+    let source = "//! A demo\n#![warn(missing_docs)]\n\nuse std::env;\n\nfn main() {}\n";
+    for file in ["src/main.rs", "src/bin/domain.rs", "src/parser.rs"] {
+      write(dir.join(file), source).unwrap();
+    }
+    let at = |file: &str, line| Location::new(dir.join(file), Some(line), Some(1));
+    assert!(is_crate_root("crates/demo/src/lib.rs"));
+    assert!(is_crate_root("src/bin/domain.rs"));
+    assert!(!is_crate_root("src/binary/domain.rs"));
+    assert!(!is_crate_root("src/parser/mod.rs"));
+    assert_eq!(
+      allow_attribute(Some("dead_code"), Some(&at("src/parser.rs", 6))),
+      "#[allow(dead_code)]"
+    );
+    // an item of a crate root
+    assert_eq!(
+      allow_attribute(Some("unused_imports"), Some(&at("src/main.rs", 4))),
+      "#[allow(unused_imports)]"
+    );
+    assert_eq!(
+      allow_attribute(Some("missing_docs"), Some(&at("src/main.rs", 1))),
+      "#![allow(missing_docs)]"
+    );
+    assert_eq!(
+      allow_attribute(Some("unused_attributes"), Some(&at("src/bin/domain.rs", 2))),
+      "#![allow(unused_attributes)]"
+    );
+    // the header of a module is not the crate's
+    assert_eq!(
+      allow_attribute(Some("unused_attributes"), Some(&at("src/parser.rs", 2))),
+      "#[allow(unused_attributes)]"
+    );
+    assert_eq!(
+      allow_attribute(Some("clippy::multiple_crate_versions"), None),
+      "#![allow(clippy::multiple_crate_versions)]"
+    );
+    assert_eq!(
+      allow_attribute(Some("unused_imports"), None),
+      "#[allow(unused_imports)]"
+    );
+    assert_eq!(
+      allow_attribute(None, Some(&at("src/main.rs", 1))),
+      "-A warnings"
+    );
+    let _ = remove_dir_all(&dir);
+  }
+}
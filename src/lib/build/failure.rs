@@ -0,0 +1,131 @@
+use std::fmt::Display;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+  /// cargo's trailer of a crate that failed to build
+  static ref COULD_NOT_COMPILE: Regex = Regex::new(
+    r"^error: could not compile `([^`]+)`(?: \(([^)]+)\))?(?: due to (?:(\d+) )?previous errors?)?(?:; (\d+) warnings? emitted)?"
+  )
+  .expect("invalid regular expression");
+}
+
+/// A crate that failed to build, parsed from cargo's
+/// `error: could not compile` trailer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateFailure {
+  /// The name of the crate
+  pub name: String,
+  /// The failed target, e.g. `lib`, `bin "demo"` or `lib test`
+  pub target: Option<String>,
+  /// The number of errors, `1` when cargo only says `due to previous error`
+  pub errors: usize,
+  /// The number of warnings emitted
+  pub warnings: usize,
+}
+
+impl CrateFailure {
+  /// Parse a `could not compile` trailer, `None` for any other line
+  pub fn parse<M: AsRef<str>>(message: M) -> Option<Self> {
+    let text = message.as_ref().trim_start();
+    // the captures get allocated even when nothing matches, most lines don't
+    if !COULD_NOT_COMPILE.is_match(text) {
+      return None;
+    }
+    let captures = COULD_NOT_COMPILE.captures(text)?;
+    let count = |i: usize| {
+      captures
+        .get(i)
+        .and_then(|count| count.as_str().parse::<usize>().ok())
+    };
+    let has_errors = message.as_ref().contains("previous error");
+    Some(Self {
+      name: captures[1].to_string(),
+      target: captures.get(2).map(|target| target.as_str().to_string()),
+      errors: count(3).unwrap_or(has_errors as usize),
+      warnings: count(4).unwrap_or_default(),
+    })
+  }
+}
+
+impl Display for CrateFailure {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.target.as_ref() {
+      Some(target) => write!(f, "{} ({})", self.name, target),
+      None => write!(f, "{}", self.name),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::CrateFailure;
+
+  fn failure(name: &str, target: Option<&str>, errors: usize, warnings: usize) -> CrateFailure {
+    CrateFailure {
+      name: name.to_string(),
+      target: target.map(|target| target.to_string()),
+      errors,
+      warnings,
+    }
+  }
+
+  #[test]
+  fn parse() {
+    for (line, expected) in [
+      (
+        "error: could not compile `foo` (lib) due to 2 previous errors",
+        failure("foo", Some("lib"), 2, 0),
+      ),
+      (
+        "error: could not compile `demo` (bin \"demo\") due to 1 previous error; 1 warning emitted",
+        failure("demo", Some("bin \"demo\""), 1, 1),
+      ),
+      (
+        "error: could not compile `foo-bar` (lib test) due to 3 previous errors; 12 warnings emitted",
+        failure("foo-bar", Some("lib test"), 3, 12),
+      ),
+      (
+        "error: could not compile `foo` (build script) due to 1 previous error",
+        failure("foo", Some("build script"), 1, 0),
+      ),
+      // older cargo versions
+      (
+        "error: could not compile `foo` due to previous error",
+        failure("foo", None, 1, 0),
+      ),
+      (
+        "error: could not compile `foo` due to 4 previous errors; 2 warnings emitted",
+        failure("foo", None, 4, 2),
+      ),
+      (
+        "error: could not compile `foo`",
+        failure("foo", None, 0, 0),
+      ),
+      (
+        "  error: could not compile `foo` (lib)",
+        failure("foo", Some("lib"), 0, 0),
+      ),
+    ] {
+      assert_eq!(CrateFailure::parse(line), Some(expected), "{}", line);
+    }
+    for line in [
+      "error[E0308]: mismatched types",
+      "error: aborting due to 2 previous errors",
+      "warning: `foo` (lib) generated 1 warning",
+      "note: could not compile `foo`",
+    ] {
+      assert_eq!(CrateFailure::parse(line), None, "{}", line);
+    }
+  }
+
+  #[test]
+  fn display() {
+    assert_eq!(
+      failure("demo", Some("bin \"demo\""), 1, 0).to_string(),
+      "demo (bin \"demo\")"
+    );
+    assert_eq!(failure("foo", None, 1, 0).to_string(), "foo");
+  }
+}
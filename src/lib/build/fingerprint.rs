@@ -0,0 +1,482 @@
+use std::{
+  hash::{Hash, Hasher},
+  path::PathBuf,
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+
+use super::{resolve_path, BuildTagKind, MarkedBlock};
+
+lazy_static! {
+  /// Line and column numbers, which move around between two builds
+  static ref LINE_NUMBERS: Regex = Regex::new(r":\d+(:\d+)?\b|\bline \d+").expect("invalid regular expression");
+  /// The start of a line which does not continue the message: a label like `note:`
+  /// or `error[E0308]:`, or a location
+  static ref NEW_LABEL: Regex = Regex::new(r"^([\w\[\]-]+:(\s|$)|\S+:\d+)").expect("invalid regular expression");
+}
+
+/// The components of a [`BlockFingerprint`] computed by [`MarkedBlock::fingerprint`],
+/// the kind of the marker is always included.
+///
+/// By default, the line and column of the location are left out: they move around between two builds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintOptions {
+  code: bool,
+  message: bool,
+  path: bool,
+  line: bool,
+  column: bool,
+}
+
+impl Default for FingerprintOptions {
+  fn default() -> Self {
+    Self {
+      code: true,
+      message: true,
+      path: true,
+      line: false,
+      column: false,
+    }
+  }
+}
+
+impl FingerprintOptions {
+  /// Include the error code, e.g. `E0308`
+  pub fn with_code(mut self, code: bool) -> Self {
+    self.code = code;
+    self
+  }
+
+  /// Include the message, see [`BlockFingerprint::message`]
+  pub fn with_message(mut self, message: bool) -> Self {
+    self.message = message;
+    self
+  }
+
+  /// Include the path of the location, see [`BlockFingerprint::path_key`]
+  pub fn with_path(mut self, path: bool) -> Self {
+    self.path = path;
+    self
+  }
+
+  /// Include the line of the location
+  pub fn with_line(mut self, line: bool) -> Self {
+    self.line = line;
+    self
+  }
+
+  /// Include the column of the location
+  pub fn with_column(mut self, column: bool) -> Self {
+    self.column = column;
+    self
+  }
+}
+
+/// Identify a [`MarkedBlock`] independently of its entry ids,
+/// to find it back in the output of another build.
+///
+/// Two fingerprints are equal, and hash the same, if their components are,
+/// whatever the form of their path
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockFingerprint {
+  /// The kind of the block's marker
+  pub kind: BuildTagKind,
+  /// The code of the block's marker, e.g. `E0308`
+  pub code: Option<String>,
+  /// The normalized first line of the block (see [`BlockFingerprint::normalize`])
+  pub first_line: String,
+  /// The path of the block's location
+  pub path: Option<PathBuf>,
+  /// The key of the path, the same for its relative and absolute forms (see [`resolve_path`])
+  pub path_key: Option<PathBuf>,
+  /// The line of the block's location
+  pub line: Option<usize>,
+  /// The column of the block's location
+  pub column: Option<usize>,
+}
+
+impl PartialEq for BlockFingerprint {
+  fn eq(&self, other: &Self) -> bool {
+    self.kind == other.kind
+      && self.code == other.code
+      && self.first_line == other.first_line
+      && self.path_key == other.path_key
+      && self.line == other.line
+      && self.column == other.column
+  }
+}
+
+impl Eq for BlockFingerprint {}
+
+impl Hash for BlockFingerprint {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.kind.hash(state);
+    self.code.hash(state);
+    self.first_line.hash(state);
+    self.path_key.hash(state);
+    self.line.hash(state);
+    self.column.hash(state);
+  }
+}
+
+impl BlockFingerprint {
+  /// Construct a fingerprint from its parts, normalizing the first line
+  pub fn new<L: AsRef<str>>(kind: BuildTagKind, first_line: L, path: Option<PathBuf>) -> Self {
+    Self {
+      kind,
+      code: None,
+      first_line: Self::normalize(first_line),
+      path_key: path.as_ref().map(resolve_path),
+      path,
+      line: None,
+      column: None,
+    }
+  }
+
+  /// Define the error code
+  pub fn with_code<C: AsRef<str>>(mut self, code: C) -> Self {
+    self.code = Some(code.as_ref().to_string());
+    self
+  }
+
+  /// Define the line and column of the location
+  pub fn with_position(mut self, line: Option<usize>, column: Option<usize>) -> Self {
+    self.line = line;
+    self.column = column;
+    self
+  }
+
+  /// Compute the fingerprint of a block with the components of `options`,
+  /// see [`MarkedBlock::fingerprint`]
+  pub fn with_options(block: &MarkedBlock<'_>, options: FingerprintOptions) -> Self {
+    let location = block.primary_location();
+    let message = match options.message {
+      true => Self::message(block.lines()),
+      false => String::new(),
+    };
+    let path = location
+      .filter(|_| options.path)
+      .map(|location| location.path().clone());
+    Self {
+      code: block
+        .error_code()
+        .filter(|_| options.code)
+        .map(str::to_string),
+      line: location
+        .filter(|_| options.line)
+        .and_then(|location| location.line()),
+      column: location
+        .filter(|_| options.column)
+        .and_then(|location| location.column()),
+      ..Self::new(block.marker().kind(), message, path)
+    }
+  }
+
+  /// Join the first line of a block with the lines continuing its message, e.g. wrapped
+  /// by the tool: up to the first blank or indented line, label or location
+  pub fn message<'l, I: IntoIterator<Item = &'l str>>(lines: I) -> String {
+    let mut lines = lines.into_iter();
+    let mut message = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+      if line.trim().is_empty() || line.starts_with(char::is_whitespace) || NEW_LABEL.is_match(line)
+      {
+        break;
+      }
+      message.push(' ');
+      message.push_str(line);
+    }
+    message
+  }
+
+  /// Compute the fingerprint of a block
+  pub fn from_block(block: &MarkedBlock<'_>) -> Self {
+    Self::new(
+      block.marker().kind(),
+      block.first_line().unwrap_or_default(),
+      block
+        .primary_location()
+        .map(|location| location.path().clone()),
+    )
+  }
+
+  /// Trim the line, collapse its whitespaces and hide its line/column numbers
+  pub fn normalize<L: AsRef<str>>(line: L) -> String {
+    let collapsed = line
+      .as_ref()
+      .split_whitespace()
+      .collect::<Vec<_>>()
+      .join(" ");
+    LINE_NUMBERS.replace_all(&collapsed, ":#").to_string()
+  }
+
+  /// Score the similarity with another fingerprint:
+  /// 0 if the kinds differ or nothing else matches, 2 for the same first line
+  /// plus 1 for the same location path, whatever its form
+  pub fn score(&self, other: &BlockFingerprint) -> u8 {
+    if self.kind != other.kind {
+      return 0;
+    }
+    let mut score = 0;
+    if self.first_line == other.first_line {
+      score += 2;
+    }
+    if self.path_key.is_some() && self.path_key == other.path_key {
+      score += 1;
+    }
+    score
+  }
+
+  /// Find the index of the best matching fingerprint, the first one wins on ties
+  pub fn best_match<'f, I: IntoIterator<Item = &'f BlockFingerprint>>(
+    &self,
+    candidates: I,
+  ) -> Option<usize> {
+    let mut best: Option<(usize, u8)> = None;
+    for (id, candidate) in candidates.into_iter().enumerate() {
+      let score = self.score(candidate);
+      let better = match best {
+        Some((_, best_score)) => score > best_score,
+        None => score > 0,
+      };
+      if better {
+        best = Some((id, score));
+      }
+    }
+    best.map(|(id, _)| id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+  };
+
+  use crate::{BuildOutput, BuildTagKind};
+
+  use super::{BlockFingerprint, FingerprintOptions};
+
+  const FIRST_BUILD: &str = r#"warning: unused variable: `x`
+ --> src/main.rs:2:7
+error[E0425]: cannot find value `y` in this scope
+ --> src/main.rs:3:3
+error[E0308]: mismatched types
+ --> src/lib.rs:10:5"#;
+
+  // a line was added at the top of both files
+  const SECOND_BUILD: &str = r#"error[E0308]: mismatched types
+ --> src/lib.rs:11:5
+warning: unused variable: `x`
+ --> src/main.rs:3:7
+error[E0425]: cannot find value `y` in this scope
+ --> src/main.rs:4:3"#;
+
+  fn fingerprints(log: &str) -> Vec<BlockFingerprint> {
+    let mut build = BuildOutput::from(log.split('\n'));
+    build.prepare();
+    build
+      .blocks()
+      .iter()
+      .map(BlockFingerprint::from_block)
+      .collect::<Vec<_>>()
+  }
+
+  fn fingerprints_with(log: &str, options: FingerprintOptions) -> Vec<BlockFingerprint> {
+    let mut build = BuildOutput::from(log.split('\n'));
+    build.prepare();
+    build
+      .blocks()
+      .iter()
+      .map(|block| block.fingerprint(options))
+      .collect::<Vec<_>>()
+  }
+
+  fn hash(fingerprint: &BlockFingerprint) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    fingerprint.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  #[test]
+  fn normalize() {
+    assert_eq!(
+      BlockFingerprint::normalize("  error:   expected `;`,   found src/main.rs:12:5 "),
+      "error: expected `;`, found src/main.rs:#"
+    );
+    assert_eq!(
+      BlockFingerprint::normalize("panicked at line 42"),
+      BlockFingerprint::normalize("panicked at line 43")
+    );
+  }
+
+  #[test]
+  fn from_block() {
+    let first = fingerprints(FIRST_BUILD);
+    assert_eq!(
+      first[1],
+      BlockFingerprint::new(
+        BuildTagKind::Error,
+        "error[E0425]: cannot find value `y` in this scope",
+        Some(PathBuf::from("src/main.rs"))
+      )
+    );
+  }
+
+  #[test]
+  fn with_options() {
+    let options = FingerprintOptions::default();
+    let first = fingerprints_with(FIRST_BUILD, options);
+    assert_eq!(
+      first[1],
+      BlockFingerprint::new(
+        BuildTagKind::Error,
+        "error[E0425]: cannot find value `y` in this scope",
+        Some(PathBuf::from("src/main.rs"))
+      )
+      .with_code("E0425")
+    );
+    assert_eq!(first[0].code, None);
+    let bare = fingerprints_with(
+      FIRST_BUILD,
+      options
+        .with_code(false)
+        .with_message(false)
+        .with_path(false),
+    );
+    assert_eq!(
+      bare[1],
+      BlockFingerprint::new(BuildTagKind::Error, "", None)
+    );
+    // the errors differ by their kind only
+    assert_eq!(bare[1], bare[2]);
+    let positioned = fingerprints_with(FIRST_BUILD, options.with_line(true).with_column(true));
+    assert_eq!(
+      positioned[1],
+      first[1].clone().with_position(Some(3), Some(3))
+    );
+  }
+
+  #[test]
+  fn stable_under_line_drift() {
+    let options = FingerprintOptions::default();
+    let first = fingerprints_with(FIRST_BUILD, options);
+    let second = fingerprints_with(SECOND_BUILD, options);
+    assert_eq!(
+      first.iter().collect::<HashSet<_>>(),
+      second.iter().collect::<HashSet<_>>()
+    );
+    assert_eq!(hash(&first[0]), hash(&second[1]));
+    // unless the lines are included
+    let options = options.with_line(true);
+    let first = fingerprints_with(FIRST_BUILD, options);
+    let second = fingerprints_with(SECOND_BUILD, options);
+    assert!(first
+      .iter()
+      .all(|fingerprint| !second.contains(fingerprint)));
+  }
+
+  #[test]
+  fn stable_under_path_forms() {
+    let absolute = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/lib/./mod.rs");
+    let relative = fingerprints_with(
+      "error: one\n --> src/lib/mod.rs:3:3",
+      FingerprintOptions::default(),
+    );
+    let absolute = fingerprints_with(
+      &format!("error: one\n --> {}:3:3", absolute.display()),
+      FingerprintOptions::default(),
+    );
+    assert_ne!(relative[0].path, absolute[0].path);
+    assert_eq!(relative[0], absolute[0]);
+    assert_eq!(hash(&relative[0]), hash(&absolute[0]));
+  }
+
+  #[test]
+  fn stable_under_wrapping() {
+    let wrapped = fingerprints_with(
+      "error[E0425]: cannot find value `y`\n  in   this scope\n --> src/main.rs:3:3",
+      FingerprintOptions::default(),
+    );
+    let unwrapped = fingerprints_with(FIRST_BUILD, FingerprintOptions::default());
+    assert_eq!(wrapped[0].first_line, "error[E0425]: cannot find value `y`");
+    let wrapped = fingerprints_with(
+      "error[E0425]: cannot find value `y`\nin   this scope\n --> src/main.rs:3:3",
+      FingerprintOptions::default(),
+    );
+    assert_eq!(wrapped[0], unwrapped[1]);
+  }
+
+  #[test]
+  fn message() {
+    assert_eq!(
+      BlockFingerprint::message([
+        "error: expected `;`,",
+        "found `}`",
+        "note: the next one",
+        "ignored"
+      ]),
+      "error: expected `;`, found `}`"
+    );
+    for end in [
+      "",
+      "  | indented",
+      " --> src/main.rs:1:1",
+      "src/main.c:1:1: note",
+    ] {
+      assert_eq!(
+        BlockFingerprint::message(["warning: one", end, "two"]),
+        "warning: one"
+      );
+    }
+    assert_eq!(BlockFingerprint::message([]), "");
+  }
+
+  #[test]
+  fn match_moved_lines() {
+    let first = fingerprints(FIRST_BUILD);
+    let second = fingerprints(SECOND_BUILD);
+    assert_eq!(first[0].best_match(&second), Some(1));
+    assert_eq!(first[1].best_match(&second), Some(2));
+    assert_eq!(first[2].best_match(&second), Some(0));
+  }
+
+  #[test]
+  fn match_partial() {
+    let second = fingerprints(SECOND_BUILD);
+    // the message changed but the error is still in the same file
+    let changed = BlockFingerprint::new(
+      BuildTagKind::Error,
+      "error[E0599]: no method named `foo`",
+      Some(PathBuf::from("src/lib.rs")),
+    );
+    assert_eq!(changed.best_match(&second), Some(0));
+    // nothing in common
+    let fixed = BlockFingerprint::new(
+      BuildTagKind::Note,
+      "note: gone",
+      Some(PathBuf::from("src/other.rs")),
+    );
+    assert_eq!(fixed.best_match(&second), None);
+  }
+
+  #[test]
+  fn match_path_forms() {
+    let relative = BlockFingerprint::new(
+      BuildTagKind::Error,
+      "error: one",
+      Some(PathBuf::from("src/lib/mod.rs")),
+    );
+    // the same file, reported with an absolute path
+    let absolute = BlockFingerprint::new(
+      BuildTagKind::Error,
+      "error: another",
+      Some(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/lib/./mod.rs")),
+    );
+    assert_eq!(relative.path_key, absolute.path_key);
+    assert_eq!(relative.score(&absolute), 1);
+  }
+}
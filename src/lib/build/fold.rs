@@ -0,0 +1,375 @@
+use std::ops::Range;
+
+use ratatui::{
+  style::{Style, Stylize},
+  text::{Line, Span},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{regex_serde, DisplayedEntry, LogEntry};
+
+use super::{BuildEntry, DuplicateBlock};
+
+/// The lines of a block delimiting a sub-section folded by default,
+/// e.g. the `in this macro invocation` notes of rustc's macro backtraces.
+///
+/// The section spans from the line after the first match of a block to its last match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoldableMarker {
+  /// The description shown in place of the folded lines
+  pub label: String,
+  /// The regex matching the delimiting lines
+  #[serde(with = "regex_serde")]
+  pub regex: Regex,
+}
+
+impl PartialEq for FoldableMarker {
+  fn eq(&self, other: &Self) -> bool {
+    self.label == other.label && self.regex.as_str() == other.regex.as_str()
+  }
+}
+
+impl FoldableMarker {
+  /// The smallest number of lines worth folding
+  pub const MIN_FOLDED_LINES: usize = 3;
+
+  pub fn new<L: AsRef<str>>(label: L, regex: Regex) -> Self {
+    Self {
+      label: label.as_ref().to_string(),
+      regex,
+    }
+  }
+
+  /// Find the foldable section of a block, as a range of `entries` and its label
+  pub fn section<'a>(
+    markers: &'a [FoldableMarker],
+    entries: &[BuildEntry],
+  ) -> Option<(Range<usize>, &'a str)> {
+    let matching = |entry: &BuildEntry| {
+      markers
+        .iter()
+        .find(|marker| marker.regex.is_match(entry.message()))
+    };
+    let (first, marker) = entries
+      .iter()
+      .enumerate()
+      .find_map(|(i, entry)| matching(entry).map(|marker| (i, marker)))?;
+    let last = entries
+      .iter()
+      .rposition(|entry| matching(entry).is_some())?;
+    let range = first + 1..last + 1;
+    match range.len() >= Self::MIN_FOLDED_LINES {
+      true => Some((range, marker.label.as_str())),
+      false => None,
+    }
+  }
+}
+
+/// How a [`Fold`] gets displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FoldKind {
+  /// A placeholder row describing the folded lines
+  #[default]
+  Section,
+  /// The first line, suffixed with the number of repetitions
+  Repeats,
+  /// The first line, suffixed with the target repeating it
+  Duplicate,
+}
+
+/// A folded range of entries, displayed as a single placeholder row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fold {
+  pub range: Range<usize>,
+  pub label: String,
+  pub kind: FoldKind,
+}
+
+impl Fold {
+  pub fn new<L: AsRef<str>>(range: Range<usize>, label: L) -> Self {
+    Self {
+      range,
+      label: label.as_ref().to_string(),
+      kind: FoldKind::Section,
+    }
+  }
+
+  /// Construct the fold of a run of identical lines
+  pub fn repeats(range: Range<usize>) -> Self {
+    Self {
+      range,
+      label: "repeated".to_string(),
+      kind: FoldKind::Repeats,
+    }
+  }
+
+  /// Construct the fold of a block repeated by another target, labeled with the target
+  pub fn duplicate(duplicate: &DuplicateBlock) -> Self {
+    Self {
+      range: duplicate.range.clone(),
+      label: duplicate.target.target.clone(),
+      kind: FoldKind::Duplicate,
+    }
+  }
+}
+
+/// Map the entries to the displayed rows when some of them are folded.
+///
+/// The folds may be nested or overlap, they get merged: a fold inside
+/// another one is hidden along with it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FoldMap {
+  /// Sorted, non-overlapping and non-empty
+  folds: Vec<Fold>,
+}
+
+impl FoldMap {
+  pub fn new<I: IntoIterator<Item = Fold>>(folds: I) -> Self {
+    let mut sorted = folds
+      .into_iter()
+      .filter(|fold| !fold.range.is_empty())
+      .collect::<Vec<_>>();
+    sorted.sort_by_key(|fold| (fold.range.start, std::cmp::Reverse(fold.range.end)));
+    let mut merged: Vec<Fold> = vec![];
+    for fold in sorted {
+      match merged.last_mut() {
+        Some(last) if fold.range.start < last.range.end => {
+          // the repeated lines are not all alike anymore
+          if last.kind == FoldKind::Repeats && fold.range.end > last.range.end {
+            last.kind = fold.kind;
+            last.label = fold.label;
+          }
+          last.range.end = last.range.end.max(fold.range.end);
+        }
+        _ => merged.push(fold),
+      }
+    }
+    Self { folds: merged }
+  }
+
+  /// Retrieve the merged folds, in order
+  pub fn folds(&self) -> &Vec<Fold> {
+    &self.folds
+  }
+
+  /// Retrieve the fold hiding `entry_id`
+  pub fn fold_at(&self, entry_id: usize) -> Option<&Fold> {
+    self
+      .folds
+      .iter()
+      .find(|fold| fold.range.contains(&entry_id))
+  }
+
+  /// Retrieve the row displaying `entry_id`, the folded entries share the row of their placeholder
+  pub fn row(&self, entry_id: usize) -> usize {
+    let mut hidden = 0;
+    for fold in &self.folds {
+      if entry_id < fold.range.start {
+        break;
+      }
+      if fold.range.contains(&entry_id) {
+        return fold.range.start - hidden;
+      }
+      hidden += fold.range.len() - 1;
+    }
+    entry_id - hidden
+  }
+
+  /// Retrieve the first entry displayed on `row`
+  pub fn entry_at(&self, row: usize) -> usize {
+    let mut hidden = 0;
+    for fold in &self.folds {
+      if row + hidden <= fold.range.start {
+        break;
+      }
+      hidden += fold.range.len() - 1;
+    }
+    row + hidden
+  }
+
+  /// Replace the folded lines with their placeholder.
+  ///
+  /// The folds may extend past the lines, e.g. when going back in time
+  pub fn apply<'a>(&self, lines: Vec<DisplayedEntry<'a>>) -> Vec<DisplayedEntry<'a>> {
+    if self.folds.is_empty() {
+      return lines;
+    }
+    let mut ret = Vec::with_capacity(lines.len());
+    let mut folds = self.folds.iter().peekable();
+    let mut lines = lines.into_iter().enumerate();
+    while let Some((entry_id, line)) = lines.next() {
+      let Some(fold) = folds.next_if(|fold| fold.range.start == entry_id) else {
+        ret.push(line);
+        continue;
+      };
+      let folded = lines
+        .by_ref()
+        .take(fold.range.len() - 1)
+        .map(|(_, line)| line);
+      ret.push(Self::fold_row(fold, line, folded));
+    }
+    ret
+  }
+
+  /// Build the row of a fold from its first line and the folded ones
+  pub(crate) fn fold_row<'a, I: Iterator<Item = DisplayedEntry<'a>>>(
+    fold: &Fold,
+    line: DisplayedEntry<'a>,
+    folded: I,
+  ) -> DisplayedEntry<'a> {
+    let mut num_lines = 1;
+    let mut style = line.line().style;
+    for folded in folded {
+      num_lines += 1;
+      // e.g. the highlight of a selected repetition
+      style = style.patch(folded.line().style);
+    }
+    match fold.kind {
+      FoldKind::Section => Self::placeholder(&fold.label, num_lines)
+        .with_origin(line.origin())
+        .into(),
+      FoldKind::Repeats if num_lines == 1 => line,
+      FoldKind::Repeats => {
+        let mut line = line;
+        let entry = line.to_mut().line_mut();
+        entry.style = style;
+        entry.push_span(Span::styled(
+          format!(" (×{})", num_lines),
+          Style::default().dim(),
+        ));
+        line
+      }
+      FoldKind::Duplicate => {
+        let mut line = line;
+        let entry = line.to_mut().line_mut();
+        entry.style = style;
+        entry.push_span(Span::styled(
+          format!(" (also in {} target)", fold.label),
+          Style::default().dim(),
+        ));
+        line
+      }
+    }
+  }
+
+  fn placeholder<'a>(label: &str, num_lines: usize) -> LogEntry<'a> {
+    LogEntry::new(
+      Line::from(vec![
+        Span::raw("     "),
+        Span::styled(
+          format!("… {} ({} lines), press x to expand", label, num_lines),
+          Style::default().dark_gray().italic(),
+        ),
+      ]),
+      Default::default(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::text::Line;
+
+  use crate::{BuildEntry, LogEntry, Origin, DEFAULT_RULES};
+
+  use super::{Fold, FoldMap, FoldableMarker};
+
+  #[test]
+  fn macro_backtrace_section() {
+    let entries = include_str!("../../../tests/fixtures/rust/rust-macro-backtrace.log")
+      .lines()
+      .map(|line| BuildEntry::new(line, Origin::Stderr))
+      .collect::<Vec<_>>();
+    let (range, label) = FoldableMarker::section(&DEFAULT_RULES[0].foldable, &entries).unwrap();
+    assert_eq!(label, "macro backtrace");
+    assert_eq!(
+      entries[range.start - 1].message(),
+      "   |     in this macro invocation"
+    );
+    assert!(entries[range.end - 1]
+      .message()
+      .contains("this error originates in the macro"));
+    assert_eq!(range.len(), 17);
+    // a single note is not worth folding
+    let entries = &entries[range.end - 1..];
+    assert!(FoldableMarker::section(&DEFAULT_RULES[0].foldable, entries).is_none());
+  }
+
+  #[test]
+  fn nested_folds() {
+    let folds = FoldMap::new([
+      Fold::new(12..14, "inner"),
+      Fold::new(2..5, "first"),
+      Fold::new(10..20, "outer"),
+      Fold::new(18..22, "overlapping"),
+      Fold::new(30..30, "empty"),
+    ]);
+    assert_eq!(
+      folds.folds(),
+      &vec![Fold::new(2..5, "first"), Fold::new(10..22, "outer")]
+    );
+    assert_eq!(folds.row(1), 1);
+    assert_eq!(folds.row(3), 2);
+    assert_eq!(folds.row(5), 3);
+    assert_eq!(folds.row(13), 8);
+    assert_eq!(folds.row(22), 9);
+    assert_eq!(folds.entry_at(2), 2);
+    assert_eq!(folds.entry_at(3), 5);
+    assert_eq!(folds.entry_at(8), 10);
+    assert_eq!(folds.entry_at(9), 22);
+    for entry_id in [0, 2, 5, 9, 10, 22, 25] {
+      assert_eq!(folds.entry_at(folds.row(entry_id)), entry_id);
+    }
+    assert_eq!(
+      folds.fold_at(15).map(|fold| fold.label.as_str()),
+      Some("outer")
+    );
+  }
+
+  #[test]
+  fn repeats() {
+    let lines = ["a", "b", "b", "b", "c"]
+      .into_iter()
+      .map(|text| LogEntry::new(Line::raw(text), Default::default()).into())
+      .collect::<Vec<_>>();
+    let folds = FoldMap::new([Fold::repeats(1..4)]);
+    let text = folds
+      .apply(lines)
+      .iter()
+      .map(|entry| entry.line().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(text, vec!["a", "b (×3)", "c"]);
+    assert_eq!(folds.row(3), 1);
+    assert_eq!(folds.entry_at(2), 4);
+    // a section overlapping the run takes it over
+    let folds = FoldMap::new([Fold::repeats(1..4), Fold::new(3..6, "macro backtrace")]);
+    assert_eq!(folds.folds(), &vec![Fold::new(1..6, "macro backtrace")]);
+    let folds = FoldMap::new([Fold::new(0..6, "macro backtrace"), Fold::repeats(1..4)]);
+    assert_eq!(folds.folds(), &vec![Fold::new(0..6, "macro backtrace")]);
+  }
+
+  #[test]
+  fn apply() {
+    let lines = (0..8)
+      .map(|i| LogEntry::new(Line::raw(i.to_string()), Default::default()).into())
+      .collect::<Vec<_>>();
+    // the second fold extends past the lines
+    let folds = FoldMap::new([Fold::new(1..4, "macro backtrace"), Fold::new(6..10, "tail")]);
+    let text = folds
+      .apply(lines)
+      .iter()
+      .map(|entry| entry.line().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      text,
+      vec![
+        "0",
+        "     … macro backtrace (3 lines), press x to expand",
+        "4",
+        "5",
+        "     … tail (2 lines), press x to expand",
+      ]
+    );
+  }
+}
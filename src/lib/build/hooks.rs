@@ -0,0 +1,371 @@
+use std::{
+  process::{Command, Stdio},
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+  thread::spawn,
+  time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{err, Debug, ErrorKind};
+
+use super::{BuildEntry, BuildTagKind};
+
+/// An external command spawned when [`super::BuildOutput::prepare`] detects a new marker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarkerHook {
+  /// The command template, see [`HookContext::render`] for the placeholders
+  pub command: String,
+  /// The marker kinds triggering the hook
+  #[serde(default = "MarkerHook::default_kinds")]
+  pub kinds: Vec<BuildTagKind>,
+  /// The maximum number of commands running at the same time
+  #[serde(default = "MarkerHook::default_max_concurrent")]
+  pub max_concurrent: usize,
+  /// The minimum delay between two spawns, in milliseconds
+  #[serde(default = "MarkerHook::default_min_interval_ms")]
+  pub min_interval_ms: u64,
+}
+
+impl MarkerHook {
+  /// Construct a hook triggered by errors, with the default limits
+  pub fn new<C: AsRef<str>>(command: C) -> Self {
+    Self {
+      command: command.as_ref().to_string(),
+      kinds: Self::default_kinds(),
+      max_concurrent: Self::default_max_concurrent(),
+      min_interval_ms: Self::default_min_interval_ms(),
+    }
+  }
+
+  /// Define the marker kinds triggering the hook
+  pub fn with_kinds<I: IntoIterator<Item = BuildTagKind>>(mut self, kinds: I) -> Self {
+    self.kinds = kinds.into_iter().collect();
+    self
+  }
+
+  /// Define the maximum number of commands running at the same time
+  pub fn with_max_concurrent(mut self, v: usize) -> Self {
+    self.max_concurrent = v;
+    self
+  }
+
+  /// Define the minimum delay between two spawns
+  pub fn with_min_interval(mut self, v: Duration) -> Self {
+    self.min_interval_ms = v.as_millis() as u64;
+    self
+  }
+
+  fn default_kinds() -> Vec<BuildTagKind> {
+    vec![BuildTagKind::Error]
+  }
+
+  fn default_max_concurrent() -> usize {
+    2
+  }
+
+  fn default_min_interval_ms() -> u64 {
+    500
+  }
+}
+
+/// The values substituted in a [`MarkerHook::command`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookContext {
+  pub kind: BuildTagKind,
+  pub file: Option<String>,
+  pub line: Option<usize>,
+  pub column: Option<usize>,
+  pub message: String,
+}
+
+impl HookContext {
+  /// Construct the context of a marked entry, the location is only known
+  /// if the block's location line was prepared along with the marker
+  pub fn new(kind: BuildTagKind, entry: &BuildEntry) -> Self {
+    let location = entry.locations().next();
+    Self {
+      kind,
+      file: location.map(|location| location.path().display().to_string()),
+      line: location.and_then(|location| location.line()),
+      column: location.and_then(|location| location.column()),
+      message: entry.message().clone(),
+    }
+  }
+
+  /// Split the template into arguments, then replace the `{kind}`, `{file}`, `{line}`,
+  /// `{column}` and `{message}` placeholders of each one (unknown values are left empty).
+  ///
+  /// No shell is involved: a placeholder never spans more than its own argument.
+  pub fn render<T: AsRef<str>>(&self, template: T) -> Vec<String> {
+    let or_empty = |v: Option<usize>| v.map(|v| v.to_string()).unwrap_or_default();
+    split_args(template)
+      .into_iter()
+      .map(|arg| {
+        arg
+          .replace("{kind}", &self.kind.to_string())
+          .replace("{file}", self.file.as_deref().unwrap_or_default())
+          .replace("{line}", &or_empty(self.line))
+          .replace("{column}", &or_empty(self.column))
+          .replace("{message}", &self.message)
+      })
+      .collect()
+  }
+}
+
+/// Split a command line on whitespaces, single and double quotes group words together
+pub fn split_args<S: AsRef<str>>(command: S) -> Vec<String> {
+  let mut ret = vec![];
+  let mut current: Option<String> = None;
+  let mut quote: Option<char> = None;
+  for ch in command.as_ref().chars() {
+    match (quote, ch) {
+      (Some(q), ch) if ch == q => quote = None,
+      (Some(_), ch) => current.get_or_insert_with(String::new).push(ch),
+      (None, '\'' | '"') => {
+        quote = Some(ch);
+        current.get_or_insert_with(String::new);
+      }
+      (None, ch) if ch.is_whitespace() => ret.extend(current.take()),
+      (None, ch) => current.get_or_insert_with(String::new).push(ch),
+    }
+  }
+  ret.extend(current);
+  ret
+}
+
+/// Run the commands of the [`MarkerHook`]s, replaceable for tests
+pub trait CommandRunner: Send + Sync {
+  /// Run the command and wait for it to exit
+  fn run(&self, args: &[String]) -> crate::Result<()>;
+}
+
+/// The [`CommandRunner`] spawning real processes, without inheriting the terminal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+  fn run(&self, args: &[String]) -> crate::Result<()> {
+    let (program, args) = args
+      .split_first()
+      .ok_or_else(|| err!(ErrorKind::Rule, "empty hook command"))?;
+    let status = Command::new(program)
+      .args(args)
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status()
+      .map_err(|e| err!(ErrorKind::IO, "failed to spawn '{}', {}", program, e))?;
+    match status.success() {
+      true => Ok(()),
+      false => Err(err!(ErrorKind::IO, "'{}' exited with {}", program, status)),
+    }
+  }
+}
+
+/// Trigger the [`MarkerHook`]s, each command runs in its own thread.
+///
+/// Triggers arriving too fast or while too many commands are running get dropped.
+pub struct MarkerHooks {
+  runner: Arc<dyn CommandRunner>,
+  running: Arc<AtomicUsize>,
+  last_spawn: Option<Instant>,
+  /// The entries before this one already triggered their hook
+  cursor: usize,
+}
+
+impl Default for MarkerHooks {
+  fn default() -> Self {
+    Self::new(Arc::new(ProcessRunner))
+  }
+}
+
+impl MarkerHooks {
+  /// Construct the hooks with a custom [`CommandRunner`]
+  pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+    Self {
+      runner,
+      running: Arc::new(AtomicUsize::new(0)),
+      last_spawn: None,
+      cursor: 0,
+    }
+  }
+
+  /// Retrieve the number of commands still running
+  pub fn running(&self) -> usize {
+    self.running.load(Ordering::SeqCst)
+  }
+
+  /// Trigger the hook for the marker of entry `entry_id`,
+  /// entries that already triggered it (before a rule change for example) are skipped.
+  ///
+  /// Returns true if a command was spawned
+  pub fn trigger(&mut self, hook: &MarkerHook, entry_id: usize, context: &HookContext) -> bool {
+    if entry_id < self.cursor || !hook.kinds.contains(&context.kind) {
+      return false;
+    }
+    self.cursor = entry_id + 1;
+    let now = Instant::now();
+    if let Some(last) = self.last_spawn {
+      if now.duration_since(last) < Duration::from_millis(hook.min_interval_ms) {
+        crate::dbg!("Hook rate-limited, skipping entry #{}", entry_id);
+        return false;
+      }
+    }
+    if self.running() >= hook.max_concurrent {
+      crate::dbg!("Too many running hooks, skipping entry #{}", entry_id);
+      return false;
+    }
+    let args = context.render(&hook.command);
+    if args.is_empty() {
+      return false;
+    }
+    self.last_spawn = Some(now);
+    self.running.fetch_add(1, Ordering::SeqCst);
+    let runner = self.runner.clone();
+    let running = self.running.clone();
+    spawn(move || {
+      if let Err(e) = runner.run(&args) {
+        Debug::log(format!("hook {:?} failed, {}", args, e));
+      }
+      running.fetch_sub(1, Ordering::SeqCst);
+    });
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::{
+      mpsc::{channel, Receiver, Sender},
+      Arc, Mutex,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+  };
+
+  use crate::{active_rule, BuildOutput, BuildTagKind};
+
+  use super::{split_args, CommandRunner, HookContext, MarkerHook, MarkerHooks};
+
+  /// Record the commands, optionally waiting for a release before exiting
+  struct RecordingRunner {
+    calls: Sender<Vec<String>>,
+    release: Option<Mutex<Receiver<()>>>,
+  }
+
+  impl CommandRunner for RecordingRunner {
+    fn run(&self, args: &[String]) -> crate::Result<()> {
+      let _ = self.calls.send(args.to_vec());
+      if let Some(release) = self.release.as_ref() {
+        let _ = release.lock().unwrap().recv();
+      }
+      Ok(())
+    }
+  }
+
+  fn context() -> HookContext {
+    HookContext {
+      kind: BuildTagKind::Error,
+      file: Some("src/main.rs".to_string()),
+      line: Some(3),
+      column: None,
+      message: "error: it's broken; rm -rf /".to_string(),
+    }
+  }
+
+  #[test]
+  fn split() {
+    assert_eq!(
+      split_args(r#"notify-send  "nbuild {kind}" '{message}' x"" "#),
+      vec!["notify-send", "nbuild {kind}", "{message}", "x"]
+    );
+    assert_eq!(split_args("a ''"), vec!["a", ""]);
+  }
+
+  #[test]
+  fn render() {
+    assert_eq!(
+      context().render("echo {kind} '{file}:{line}:{column}' {message}"),
+      vec![
+        "echo",
+        "Error",
+        "src/main.rs:3:",
+        "error: it's broken; rm -rf /"
+      ]
+    );
+  }
+
+  #[test]
+  fn limits() {
+    let (tx_calls, rx_calls) = channel();
+    let (tx_release, rx_release) = channel();
+    let mut hooks = MarkerHooks::new(Arc::new(RecordingRunner {
+      calls: tx_calls,
+      release: Some(Mutex::new(rx_release)),
+    }));
+    let hook = MarkerHook::new("echo {line}")
+      .with_max_concurrent(1)
+      .with_min_interval(Duration::ZERO);
+    let warning = HookContext {
+      kind: BuildTagKind::Warning,
+      ..context()
+    };
+    assert!(!hooks.trigger(&hook, 0, &warning));
+    assert!(hooks.trigger(&hook, 1, &context()));
+    assert_eq!(
+      rx_calls.recv_timeout(Duration::from_secs(5)).unwrap(),
+      vec!["echo", "3"]
+    );
+    // the first command is still running
+    assert!(!hooks.trigger(&hook, 2, &context()));
+    tx_release.send(()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while hooks.running() > 0 && Instant::now() < deadline {
+      sleep(Duration::from_millis(5));
+    }
+    assert!(hooks.trigger(&hook, 3, &context()));
+    // already triggered
+    tx_release.send(()).unwrap();
+    assert!(!hooks.trigger(&hook, 3, &context()));
+
+    let mut hooks = MarkerHooks::new(Arc::new(RecordingRunner {
+      calls: channel().0,
+      release: None,
+    }));
+    let hook = MarkerHook::new("echo").with_min_interval(Duration::from_secs(60));
+    assert!(hooks.trigger(&hook, 0, &context()));
+    assert!(!hooks.trigger(&hook, 1, &context()));
+  }
+
+  #[test]
+  fn prepare_triggers_hooks() {
+    let (tx_calls, rx_calls) = channel();
+    let mut rule = active_rule();
+    rule.on_marker =
+      Some(MarkerHook::new("notify {kind} {file} {line}").with_min_interval(Duration::ZERO));
+    let mut build = BuildOutput::from([
+      "warning: unused variable",
+      "error[E0425]: cannot find value `y`",
+      " --> src/main.rs:3:3",
+    ])
+    .with_hook_runner(Arc::new(RecordingRunner {
+      calls: tx_calls,
+      release: None,
+    }));
+    build.set_rule(rule.clone());
+    build.prepare();
+    assert_eq!(
+      rx_calls.recv_timeout(Duration::from_secs(5)).unwrap(),
+      vec!["notify", "Error", "src/main.rs", "3"]
+    );
+    // preparing again after a rule change does not trigger the hook twice
+    build.set_rule(rule);
+    build.prepare();
+    assert!(rx_calls.recv_timeout(Duration::from_millis(200)).is_err());
+  }
+}
@@ -0,0 +1,165 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{marker::regex_serde, AnsiLine, AppOptions, Rule};
+
+/// A line carrying nothing, dropped before it enters the pipeline, e.g. blank
+/// or a lone progress spinner glyph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NoiseLine {
+  #[serde(with = "regex_serde")]
+  pub regex: Regex,
+}
+
+impl PartialEq for NoiseLine {
+  fn eq(&self, other: &Self) -> bool {
+    self.regex.as_str() == other.regex.as_str()
+  }
+}
+
+impl NoiseLine {
+  /// Whitespace-only lines
+  pub const BLANK: &'static str = r"^\s*$";
+  /// Lines holding only a spinner glyph: braille, quarter circles or `|/-\`
+  pub const SPINNER: &'static str = r"^\s*[\x{2800}-\x{28FF}◐◓◑◒◴◷◶◵|/\\-]\s*$";
+
+  pub fn new(regex: Regex) -> Self {
+    Self { regex }
+  }
+
+  /// The noise lines of the rules that do not define theirs
+  pub fn defaults() -> Vec<Self> {
+    [Self::BLANK, Self::SPINNER]
+      .into_iter()
+      .map(|regex| Self::new(Regex::new(regex).expect("invalid regular expression")))
+      .collect()
+  }
+
+  /// Check if `noise` are the [`Self::defaults`], not worth saving
+  pub fn are_defaults(noise: &Vec<Self>) -> bool {
+    *noise == Self::defaults()
+  }
+}
+
+/// Drop the [`NoiseLine`]s of a stream before they get sent, counted by
+/// [`crate::IntakeStats::dropped`].
+///
+/// The markers are not detected yet: a line matching the regex of a marker opens
+/// a block, kept until its first blank line, itself kept as the end of the block
+#[derive(Debug, Clone)]
+pub struct IntakeFilter {
+  noise: Vec<Regex>,
+  markers: Vec<Regex>,
+  in_block: bool,
+}
+
+impl IntakeFilter {
+  /// Construct the filter of the noise lines of `rule`, blocks delimited by its enabled markers
+  pub fn new(rule: &Rule) -> Self {
+    Self {
+      noise: rule.noise.iter().map(|line| line.regex.clone()).collect(),
+      markers: rule
+        .markers
+        .iter()
+        .filter(|marker| !marker.disabled)
+        .map(|marker| marker.regex.clone())
+        .collect(),
+      in_block: false,
+    }
+  }
+
+  /// Construct the filter of the active rule, `None` with `--keep-blank-lines`
+  pub fn from_options(options: &AppOptions) -> Option<Self> {
+    match options.keep_blank_lines {
+      true => None,
+      false => Some(Self::new(&crate::active_rule())),
+    }
+  }
+
+  /// Check if the raw `line` must be sent, in the order of the stream
+  pub fn keep(&mut self, line: &str) -> bool {
+    if self.noise.is_empty() {
+      return true;
+    }
+    AnsiLine::with_stripped(line, |text| {
+      if self.markers.iter().any(|regex| regex.is_match(text)) {
+        self.in_block = true;
+        return true;
+      }
+      if self.in_block {
+        self.in_block = !text.trim().is_empty();
+        return true;
+      }
+      !self.noise.iter().any(|regex| regex.is_match(text))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Rule, DEFAULT_RULES};
+
+  use super::{IntakeFilter, NoiseLine};
+
+  /// The lines of `log` kept by the filter of the cargo rule
+  fn kept(log: &str) -> Vec<&str> {
+    let mut filter = IntakeFilter::new(&DEFAULT_RULES[0]);
+    log.lines().filter(|line| filter.keep(line)).collect()
+  }
+
+  #[test]
+  fn noise() {
+    let log = include_str!("../../../tests/fixtures/rust/rust-spinner.log");
+    let lines = log.lines().count();
+    let kept = kept(log);
+    assert!(lines - kept.len() > 1000, "{} of {}", kept.len(), lines);
+    assert!(kept.iter().all(|line| line.trim() != "⠋"));
+    assert!(kept.contains(&"   Compiling demo v0.1.0 (/tmp/demo)"));
+    assert!(
+      kept.contains(&"    Finished `dev` profile [unoptimized + debuginfo] target(s) in 2.31s")
+    );
+  }
+
+  #[test]
+  fn keep_blocks() {
+    let log = include_str!("../../../tests/fixtures/rust/rust-spinner.log");
+    let kept = kept(log);
+    // the `|` gutters of the warning look like spinners, kept up to its blank end
+    let start = kept
+      .iter()
+      .position(|line| line.starts_with("warning: unused variable"))
+      .unwrap();
+    assert_eq!(
+      kept[start..start + 8],
+      [
+        "warning: unused variable: `spinner`",
+        " --> src/main.rs:2:7",
+        "  |",
+        "2 |   let spinner = '|';",
+        "  |       ^^^^^^^ help: if this is intentional, prefix it with an underscore: `_spinner`",
+        "  |",
+        "  = note: `#[warn(unused_variables)]` on by default",
+        "",
+      ]
+    );
+    assert_ne!(kept[start + 8], "");
+  }
+
+  #[test]
+  fn rule_noise() {
+    let rule = Rule::new(["custom"], "make", []);
+    assert_eq!(rule.noise, NoiseLine::defaults());
+    let mut filter = IntakeFilter::new(&rule);
+    assert!(!filter.keep("  "));
+    assert!(!filter.keep("\x1b[2K⠙"));
+    assert!(filter.keep("⠙ building"));
+    assert!(filter.keep("-- done"));
+    // a rule without noise keeps everything
+    let mut rule = rule;
+    rule.noise.clear();
+    let mut filter = IntakeFilter::new(&rule);
+    assert!(filter.keep("  "));
+    assert!(filter.keep("⠙"));
+  }
+}
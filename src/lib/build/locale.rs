@@ -0,0 +1,204 @@
+use regex::Regex;
+
+use crate::DeclaredMarker;
+
+use super::{active_rule, update_rule, BuildTagKind, Rule};
+
+/// The environment variables set on the build command to get English diagnostics
+pub const FORCED_LOCALE: &[(&str, &str)] = &[("LC_ALL", "C")];
+
+/// The translations of the `error`, `warning` and `note` diagnostic levels
+pub const LOCALIZED_LEVELS: &[(&str, [&str; 3])] = &[
+  ("fr", ["erreur", "avertissement", "note"]),
+  ("de", ["Fehler", "Warnung", "Hinweis"]),
+  ("es", ["error", "advertencia", "nota"]),
+  ("it", ["errore", "avviso", "nota"]),
+  ("pt", ["erro", "aviso", "nota"]),
+  ("ja", ["エラー", "警告", "注記"]),
+  ("zh", ["错误", "警告", "注意"]),
+];
+
+/// Extract the language of a locale: `fr` for `fr_FR.UTF-8`,
+/// nothing for the `C` and `POSIX` locales
+pub fn locale_language<L: AsRef<str>>(locale: L) -> Option<String> {
+  let language = locale
+    .as_ref()
+    .split(['_', '.', '@', '-'])
+    .next()
+    .unwrap_or_default()
+    .to_lowercase();
+  match language.as_str() {
+    "" | "c" | "posix" => None,
+    _ => Some(language),
+  }
+}
+
+/// Detect the language of the messages from the environment
+pub fn detect_language() -> Option<String> {
+  ["LC_ALL", "LC_MESSAGES", "LANG"]
+    .iter()
+    .filter_map(|var| std::env::var(var).ok())
+    .find(|value| !value.is_empty())
+    .and_then(locale_language)
+}
+
+/// Build the markers matching the translated diagnostic levels of a language,
+/// with the style of the rule's marker of the same kind
+pub fn localized_markers<L: AsRef<str>>(language: L, rule: &Rule) -> Vec<DeclaredMarker> {
+  let Some((_, levels)) = LOCALIZED_LEVELS
+    .iter()
+    .find(|(lang, _)| *lang == language.as_ref())
+  else {
+    return vec![];
+  };
+  [
+    BuildTagKind::Error,
+    BuildTagKind::Warning,
+    BuildTagKind::Note,
+  ]
+  .into_iter()
+  .zip(levels.iter())
+  .filter_map(|(kind, level)| {
+    let regex = Regex::new(&format!(r"{}(\[\w+\])?\s?:", regex::escape(level))).ok()?;
+    // the english markers already cover the levels that were not translated
+    if rule
+      .markers
+      .iter()
+      .any(|marker| marker.tag == kind && marker.regex.is_match(&format!("{}:", level)))
+    {
+      return None;
+    }
+    let style = rule
+      .markers
+      .iter()
+      .find(|marker| marker.tag == kind)
+      .map(|marker| marker.style)
+      .unwrap_or_default();
+    Some(DeclaredMarker::new(kind, regex, style))
+  })
+  .collect()
+}
+
+/// Fold a localized marker into the first marker of its kind: one alternation keeps a
+/// single row and a single scan per kind. The markers narrowing their text with a
+/// `marker` group, or missing from the rule, get appended instead
+pub fn fold_marker(rule: &mut Rule, marker: DeclaredMarker) {
+  let existing = rule.markers.iter_mut().find(|existing| {
+    existing.tag == marker.tag
+      && !existing
+        .regex
+        .capture_names()
+        .flatten()
+        .any(|name| name == "marker")
+  });
+  let Some(existing) = existing else {
+    rule.markers.push(marker);
+    return;
+  };
+  let alternation = format!(
+    "(?:{})|(?:{})",
+    existing.regex.as_str(),
+    marker.regex.as_str()
+  );
+  match Regex::new(&alternation) {
+    Ok(regex) => existing.regex = regex,
+    Err(_) => rule.markers.push(marker),
+  }
+}
+
+/// Add the localized markers to the active rule, return the language they were added for.
+///
+/// The markers of a rule match English diagnostics, the precedence is:
+/// 1. by default the build command runs with [`FORCED_LOCALE`], its diagnostics
+///    stay in English whatever the user's locale;
+/// 2. the rule's `locale` field always adds the markers of its language;
+/// 3. with `--keep-locale` and no `locale` field, the markers of the language
+///    detected from `LC_ALL`, `LC_MESSAGES` then `LANG` get added.
+///
+/// The translated levels are folded into the markers of the same kind, see [`fold_marker`]
+pub fn localize_active_rule(keep_locale: bool) -> Option<String> {
+  let mut rule = active_rule();
+  let language = match (rule.locale.as_ref(), keep_locale) {
+    (Some(locale), _) => locale_language(locale),
+    (None, true) => detect_language(),
+    (None, false) => None,
+  }?;
+  let markers = localized_markers(&language, &rule);
+  crate::dbg!(
+    "Adding {} markers for language '{}'",
+    markers.len(),
+    language
+  );
+  for marker in markers {
+    fold_marker(&mut rule, marker);
+  }
+  update_rule(rule);
+  Some(language)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BuildTagKind, DEFAULT_RULES};
+
+  use super::{fold_marker, locale_language, localized_markers};
+
+  #[test]
+  fn language() {
+    assert_eq!(locale_language("fr_FR.UTF-8"), Some("fr".to_string()));
+    assert_eq!(locale_language("de_DE@euro"), Some("de".to_string()));
+    assert_eq!(locale_language("C.UTF-8"), None);
+    assert_eq!(locale_language("POSIX"), None);
+    assert_eq!(locale_language(""), None);
+  }
+
+  #[test]
+  fn markers() {
+    let rule = DEFAULT_RULES[0].clone();
+    let markers = localized_markers("fr", &rule);
+    // "note" is already matched by the english marker
+    assert_eq!(
+      markers.iter().map(|marker| marker.tag).collect::<Vec<_>>(),
+      vec![BuildTagKind::Error, BuildTagKind::Warning]
+    );
+    assert!(markers[0]
+      .regex
+      .is_match("erreur[E0425]: valeur introuvable"));
+    assert!(markers[1]
+      .regex
+      .is_match("avertissement : variable inutilisée"));
+    assert_eq!(markers[0].style, rule.markers[0].style);
+    assert!(localized_markers("ja", &rule)[0]
+      .regex
+      .is_match("エラー: 型が一致しません"));
+    assert!(localized_markers("xx", &rule).is_empty());
+  }
+  #[test]
+  fn folded() {
+    let mut rule = DEFAULT_RULES[0].clone();
+    let len = rule.markers.len();
+    for marker in localized_markers("fr", &rule.clone()) {
+      fold_marker(&mut rule, marker);
+    }
+    assert_eq!(rule.markers.len(), len);
+    let error = rule
+      .markers
+      .iter()
+      .find(|marker| marker.tag == BuildTagKind::Error)
+      .unwrap();
+    assert!(error.regex.is_match("error[E0425]: cannot find value"));
+    assert!(error.regex.is_match("erreur[E0425]: valeur introuvable"));
+
+    // no marker to fold into
+    rule
+      .markers
+      .retain(|marker| marker.tag != BuildTagKind::Warning);
+    for marker in localized_markers("fr", &rule.clone()) {
+      fold_marker(&mut rule, marker);
+    }
+    assert_eq!(rule.markers.len(), len);
+    assert!(rule
+      .markers
+      .last()
+      .is_some_and(|marker| marker.tag == BuildTagKind::Warning));
+  }
+}
@@ -1,10 +1,22 @@
 use std::{
   fmt::Display,
-  path::{Path, PathBuf},
+  path::{Component, Path, PathBuf},
   str::FromStr,
+  sync::RwLock,
 };
 
-use crate::{Error, ErrorKind};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{regex_serde, AnsiLine, Error, ErrorKind, PathDisplay};
+
+lazy_static! {
+  /// The roots detected on first use, and the crate dirs reported by the build
+  static ref PATH_RESOLVER: RwLock<PathResolver> = RwLock::new(PathResolver::detect());
+  /// Cargo's progress line for a local crate, e.g. `Compiling foo v0.1.0 (/work/crates/foo)`
+  static ref CRATE_DIR: Regex = Regex::new(r"^\s*(?:Compiling|Checking|Documenting) \S+ v\S+ \((/.*|[A-Za-z]:\\.*)\)$").expect("invalid regular expression");
+}
 
 /// Represent a source-code location. Captured from Cargo's output
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -46,6 +58,150 @@ impl Location {
   pub fn column_mut(&mut self) -> &mut Option<usize> {
     &mut self.column
   }
+
+  /// Retrieve the key of the file, the same for all its path forms, see [`resolve_path`]
+  pub fn canonical_path(&self) -> PathBuf {
+    resolve_path(&self.path)
+  }
+}
+
+/// Map the path forms of a file to a single absolute path: relative to the workspace root
+/// (`crates/foo/src/lib.rs`), relative to its crate dir (`src/lib.rs`), or absolute.
+///
+/// A relative path is resolved against the crate dirs, then the workspace root, keeping
+/// the first existing file. Otherwise it gets joined to the workspace root and normalized
+/// lexically, without touching the filesystem
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathResolver {
+  workspace_root: Option<PathBuf>,
+  crate_dirs: Vec<PathBuf>,
+}
+
+impl PathResolver {
+  /// Construct the resolver of a workspace, the relative paths stay relative without one
+  pub fn new(workspace_root: Option<PathBuf>) -> Self {
+    Self {
+      workspace_root,
+      crate_dirs: vec![],
+    }
+  }
+
+  /// Detect the workspace root from the current directory, else use the current directory
+  pub fn detect() -> Self {
+    Self::detect_with(None)
+  }
+
+  /// Detect the workspace root from the directory containing `manifest_path` if any, else
+  /// use that directory, see [`PathDisplay::workspace_root`]
+  pub fn detect_with(manifest_path: Option<&Path>) -> Self {
+    let root = PathDisplay::workspace_root(manifest_path)
+      .or_else(|| PathDisplay::project_dir(manifest_path));
+    Self::new(root)
+  }
+
+  /// Resolve the relative paths against these crate dirs first, see [`Self::add_crate_dir`]
+  #[cfg(test)]
+  pub fn with_crate_dirs<I: IntoIterator<Item = PathBuf>>(mut self, dirs: I) -> Self {
+    for dir in dirs {
+      self.add_crate_dir(dir);
+    }
+    self
+  }
+
+  /// Add the dir of a crate built by the workspace, once
+  pub fn add_crate_dir<P: AsRef<Path>>(&mut self, dir: P) {
+    let dir = Self::normalize(dir);
+    if !self.crate_dirs.contains(&dir) {
+      self.crate_dirs.push(dir);
+    }
+  }
+
+  /// Extract the dir of a local crate from cargo's progress line
+  pub fn crate_dir<L: AsRef<str>>(line: L) -> Option<PathBuf> {
+    let line = line.as_ref();
+    let line = match line.contains('\x1b') {
+      true => AnsiLine::strip(line),
+      false => line.to_string(),
+    };
+    CRATE_DIR
+      .captures(&line)
+      .map(|captures| PathBuf::from(&captures[1]))
+  }
+
+  /// Map `path` to its key, see [`PathResolver`]
+  pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_absolute() {
+      return Self::normalize(path);
+    }
+    let existing = self
+      .crate_dirs
+      .iter()
+      .chain(self.workspace_root.as_ref())
+      .map(|dir| Self::normalize(dir.join(path)))
+      .find(|candidate| candidate.is_file());
+    match (existing, self.workspace_root.as_ref()) {
+      (Some(existing), _) => existing,
+      (None, Some(root)) => Self::normalize(root.join(path)),
+      (None, None) => Self::normalize(path),
+    }
+  }
+
+  /// Remove the `.` and resolve the `..` components of `path`, without touching the filesystem
+  pub fn normalize<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut ret = PathBuf::new();
+    for component in path.as_ref().components() {
+      match component {
+        Component::CurDir => {}
+        Component::ParentDir => match ret.components().next_back() {
+          Some(Component::Normal(_)) => {
+            ret.pop();
+          }
+          // nothing above the root
+          Some(Component::RootDir | Component::Prefix(_)) => {}
+          _ => ret.push(".."),
+        },
+        component => ret.push(component),
+      }
+    }
+    ret
+  }
+}
+
+/// Map a path to its key with the detected workspace root and the crate dirs
+/// of the build, see [`PathResolver::resolve`]
+pub fn resolve_path<P: AsRef<Path>>(path: P) -> PathBuf {
+  match PATH_RESOLVER.read() {
+    Ok(resolver) => resolver.resolve(path),
+    Err(_) => PathResolver::normalize(path),
+  }
+}
+
+/// Resolve and shorten the paths against the project of `--manifest-path` instead of the
+/// current directory, see [`resolve_path`] and [`crate::display_path`]
+pub fn set_manifest_path<P: AsRef<Path>>(manifest_path: P) {
+  let manifest_path = manifest_path.as_ref();
+  crate::dbg!("Using the workspace of {}", manifest_path.display());
+  PathDisplay::set_global(PathDisplay::detect_with(Some(manifest_path)));
+  let resolver = PathResolver::detect_with(Some(manifest_path));
+  match PATH_RESOLVER.write() {
+    Ok(mut global) => *global = resolver,
+    Err(e) => *e.into_inner() = resolver,
+  }
+}
+
+/// Remember the crate dir reported by cargo's progress `line`, if any, see [`resolve_path`]
+pub fn register_crate_dir<L: AsRef<str>>(line: L) {
+  let line = line.as_ref();
+  if !line.ends_with(')') {
+    return;
+  }
+  let Some(dir) = PathResolver::crate_dir(line) else {
+    return;
+  };
+  if let Ok(mut resolver) = PATH_RESOLVER.write() {
+    resolver.add_crate_dir(dir);
+  }
 }
 
 impl FromStr for Location {
@@ -85,6 +241,61 @@ impl FromStr for Location {
   }
 }
 
+/// A location written on the line of a diagnostic, e.g. gcc's `main.c:3:10: error: ...`.
+///
+/// The regex captures the `path`, and optionally the `line` and the `column`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InlineLocation {
+  #[serde(with = "regex_serde")]
+  pub regex: Regex,
+}
+
+impl PartialEq for InlineLocation {
+  fn eq(&self, other: &Self) -> bool {
+    self.regex.as_str() == other.regex.as_str()
+  }
+}
+
+impl InlineLocation {
+  pub fn new(regex: Regex) -> Self {
+    Self { regex }
+  }
+
+  /// Check the regex captures a `path`
+  pub fn validate(&self) -> Result<(), String> {
+    match self
+      .regex
+      .capture_names()
+      .flatten()
+      .any(|name| name == "path")
+    {
+      true => Ok(()),
+      false => Err(format!(
+        "location '{}' does not capture a 'path'",
+        self.regex.as_str()
+      )),
+    }
+  }
+
+  /// Find the first location written on `message`
+  pub fn find(&self, message: &str) -> Option<Location> {
+    if !self.regex.is_match(message) {
+      return None;
+    }
+    let captures = self.regex.captures(message)?;
+    let number = |name| {
+      captures
+        .name(name)
+        .and_then(|num| num.as_str().parse::<usize>().ok())
+    };
+    Some(Location::new(
+      captures.name("path")?.as_str(),
+      number("line"),
+      number("column"),
+    ))
+  }
+}
+
 impl Display for Location {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(
@@ -116,3 +327,131 @@ macro_rules! here {
     )
   };
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use regex::Regex;
+
+  use crate::PathDisplay;
+
+  use super::{InlineLocation, Location, PathResolver};
+
+  #[test]
+  fn inline() {
+    let gcc = InlineLocation::new(
+      Regex::new(r"^(?P<path>[^\s:]+):(?P<line>\d+):(?:(?P<column>\d+):)? error:").unwrap(),
+    );
+    assert!(gcc.validate().is_ok());
+    assert_eq!(
+      gcc.find("src/main.c:3:10: error: expected ';'"),
+      Some(Location::new("src/main.c", Some(3), Some(10)))
+    );
+    assert_eq!(
+      gcc.find("src/main.c:3: error: expected ';'"),
+      Some(Location::new("src/main.c", Some(3), None))
+    );
+    assert_eq!(gcc.find("error: expected ';'"), None);
+    assert!(InlineLocation::new(Regex::new(r"(\S+):(\d+)").unwrap())
+      .validate()
+      .is_err());
+  }
+
+  #[test]
+  fn normalize() {
+    assert_eq!(
+      PathResolver::normalize("/work/./crates/bar/../foo/src/lib.rs"),
+      PathBuf::from("/work/crates/foo/src/lib.rs")
+    );
+    assert_eq!(PathResolver::normalize("/../a.rs"), PathBuf::from("/a.rs"));
+    assert_eq!(
+      PathResolver::normalize("../a/./b.rs"),
+      PathBuf::from("../a/b.rs")
+    );
+  }
+
+  #[test]
+  fn crate_dir() {
+    assert_eq!(
+      PathResolver::crate_dir("   Compiling foo v0.1.0 (/work/crates/foo)"),
+      Some(PathBuf::from("/work/crates/foo"))
+    );
+    assert_eq!(
+      PathResolver::crate_dir("\x1b[1m\x1b[32m    Checking\x1b[0m foo v0.1.0 (/work/crates/foo)"),
+      Some(PathBuf::from("/work/crates/foo"))
+    );
+    // the registry crates have no dir
+    assert_eq!(PathResolver::crate_dir("   Compiling serde v1.0.210"), None);
+    assert_eq!(
+      PathResolver::crate_dir("error: could not compile `foo` (lib)"),
+      None
+    );
+  }
+
+  #[test]
+  fn resolve() {
+    let root = std::env::temp_dir().join(format!("nbuild-workspace-{}", std::process::id()));
+    let foo = root.join("crates").join("foo");
+    fs::create_dir_all(foo.join("src")).unwrap();
+    fs::write(
+      root.join("Cargo.toml"),
+      "[workspace]\nmembers = [\"crates/*\"]\n",
+    )
+    .unwrap();
+    fs::write(foo.join("src").join("lib.rs"), "").unwrap();
+
+    let resolver = PathResolver::new(Some(root.clone())).with_crate_dirs([foo.clone()]);
+    let key = foo.join("src").join("lib.rs");
+    // from the crate dir, from the workspace root, absolute
+    assert_eq!(resolver.resolve("src/lib.rs"), key);
+    assert_eq!(resolver.resolve("crates/foo/src/lib.rs"), key);
+    assert_eq!(resolver.resolve(root.join("crates/./foo/src/lib.rs")), key);
+    // a missing file gets joined to the workspace root
+    assert_eq!(
+      resolver.resolve("crates/foo/../foo/src/gone.rs"),
+      foo.join("src").join("gone.rs")
+    );
+    assert_eq!(
+      resolver.resolve("src/gone.rs"),
+      root.join("src").join("gone.rs")
+    );
+    // without the crate dir, only the workspace root form exists
+    let resolver = PathResolver::new(Some(root.clone()));
+    assert_ne!(resolver.resolve("src/lib.rs"), key);
+    assert_eq!(
+      PathResolver::new(None).resolve("./src/lib.rs"),
+      PathBuf::from("src/lib.rs")
+    );
+    let _ = fs::remove_dir_all(&root);
+  }
+
+  #[test]
+  fn manifest_path() {
+    let root = std::env::temp_dir().join(format!("nbuild-project-{}", std::process::id()));
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+    fs::write(root.join("src").join("lib.rs"), "").unwrap();
+    let manifest = root.join("Cargo.toml");
+    let file = root.join("src").join("lib.rs");
+    assert_ne!(std::env::current_dir().ok(), Some(root.clone()));
+
+    // the locations are resolved from the project, not from the current directory
+    let resolver = PathResolver::detect_with(Some(&manifest));
+    assert_eq!(resolver.resolve("src/lib.rs"), file);
+    assert_eq!(
+      resolver.resolve("./src/../src/gone.rs"),
+      root.join("src").join("gone.rs")
+    );
+    assert_ne!(PathResolver::detect().resolve("src/lib.rs"), file);
+    assert_eq!(
+      PathDisplay::workspace_root(Some(&root.join("src/../Cargo.toml"))),
+      Some(root.clone())
+    );
+    assert_eq!(
+      PathDisplay::detect_with(Some(&manifest)).shorten(&file),
+      "src/lib.rs"
+    );
+    let _ = fs::remove_dir_all(&root);
+  }
+}
@@ -1,19 +1,49 @@
-pub mod block;
-pub mod command;
-pub mod entry;
-pub mod event;
-pub mod location;
-pub mod origin;
-pub mod output;
-pub mod rule;
-pub mod tag;
+mod artifact;
+mod block;
+mod command;
+mod diff;
+mod display;
+mod entry;
+mod event;
+mod exit;
+mod export;
+mod failure;
+mod fingerprint;
+mod fold;
+mod hooks;
+mod intake;
+mod locale;
+mod location;
+mod origin;
+mod output;
+mod rule;
+mod script;
+mod snapshot;
+mod suggestion;
+mod tag;
+mod target;
 
+pub use artifact::*;
 pub use block::*;
 pub use command::*;
+pub use diff::*;
+pub use display::*;
 pub use entry::*;
 pub use event::*;
+pub use exit::*;
+pub use export::*;
+pub use failure::*;
+pub use fingerprint::*;
+pub use fold::*;
+pub use hooks::*;
+pub use intake::*;
+pub use locale::*;
 pub use location::*;
 pub use origin::*;
 pub use output::*;
 pub use rule::*;
+pub use script::*;
+pub use snapshot::*;
+pub use suggestion::*;
 pub use tag::*;
+pub use target::*;
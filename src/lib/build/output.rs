@@ -1,4 +1,6 @@
 use std::{
+  borrow::Cow,
+  collections::{BTreeSet, HashMap},
   ops::Range,
   sync::{
     mpsc::{channel, Receiver, Sender},
@@ -13,17 +15,24 @@ use ratatui::{
   style::{Style, Stylize},
   text::{Line, Span},
 };
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
-  err, BuildTagKind, Debug, DeclaredMarker, ErrorKind, LogEntry, MarkerSelection, Markers,
-  TryLockFor, DEFAULT_RULES,
+  err, expand_tabs, AnsiLine, BuildTagKind, BuildTagKinds, Debug, DeclaredMarker, Direction,
+  DisplayedEntry, EntryId, ErrorKind, IpcEvent, IpcServer, LogEntry, MarkerBudget, MarkerRef,
+  MarkerSelection, Markers, RawLayout, SlowMarker, TryLockFor, DEFAULT_RULES,
 };
 
-use super::{active_rule, BuildEntry, BuildEvent, BuildTag, Location, MarkedBlock, Rule};
+use super::{
+  active_rule, register_crate_dir, restyle, Artifact, BuildEntry, BuildEvent, BuildScript,
+  BuildScriptLine, BuildSnapshot, BuildTag, CommandRunner, CompileTarget, CrateFailure, Diagnostic,
+  DuplicateBlock, FoldableMarker, HookContext, JumpTarget, Location, MarkedBlock, MarkerHooks,
+  Rule, SnapshotSegment, TargetCounts,
+};
 
 /// The BuildOutput struct prepares the [`BuildCommand`] raw output lines.
 /// It creates the necessary [`ratatui`] elements: [`Line`] and [`Span`]
-/// to be rendered later by the [`crate::widgets::log::LogView`] widget.
+/// to be rendered later by the [`crate::LogView`] widget.
 ///
 /// It first aggregates the raw entries in [`BuildOutput::entries`],
 /// and when [`BuildOutput::prepare`] gets called it batches up those unprocessed
@@ -38,9 +47,8 @@ use super::{active_rule, BuildEntry, BuildEvent, BuildTag, Location, MarkedBlock
 /// use cargo_nbuild::{BuildOutput, BuildEntry, Origin};
 ///
 /// let mut build = BuildOutput::default();
-/// let (tx, rx) = std::sync::mpsc::channel();
 /// build.push(BuildEntry::new("my log", Origin::Stdout));
-/// build.prepare(tx);
+/// build.prepare();
 /// let _lines = build.display();
 /// ```
 pub struct BuildOutput<'a> {
@@ -50,11 +58,51 @@ pub struct BuildOutput<'a> {
   warnings: Vec<usize>,
   notes: Vec<usize>,
   errors: Vec<usize>,
+  /// The `could not compile` trailers, see [`CrateFailure`]
+  failures: Vec<usize>,
+  /// The warnings of the build scripts, not counted in [`BuildOutput::warnings`]
+  script_warnings: Vec<usize>,
+  /// The files produced by the build, collected on intake, see [`Artifact`]
+  artifacts: Vec<Artifact>,
   build_events: Option<Sender<BuildEvent>>,
   remove_noise: bool,
+  inline_markers: bool,
+  ansi_colors: bool,
   cursor: usize,
-  prepared: Vec<Line<'a>>,
+  /// The displayable entries, their kinds follow the tags of [`BuildOutput::entries`]
+  prepared: Vec<LogEntry<'a>>,
   markers: Markers,
+  queue_latency: Duration,
+  /// The segments of the last [`BuildOutput::snapshot`]
+  segments: Vec<Arc<SnapshotSegment>>,
+  /// The number of entries the segments are still up to date for
+  segments_valid: usize,
+  hooks: MarkerHooks,
+  /// The prepared entries tagged since, see [`BuildOutput::tag_entry`]
+  dirty: BTreeSet<usize>,
+  ipc: IpcServer,
+  /// The entries before this one already streamed their marker to the [`IpcServer`]
+  ipc_cursor: usize,
+  /// The markers detected since the last [`BuildOutput::snapshot`], in entry order
+  detected: Vec<(EntryId, BuildTagKind)>,
+  /// The runs of identical consecutive messages, see [`BuildOutput::repeats`]
+  repeats: Vec<Range<usize>>,
+  /// The most entries prepared by one [`BuildOutput::prepare`] call, see [`BuildOutput::set_prepare_limit`]
+  prepare_limit: Option<usize>,
+  /// The time each marker may spend per batch, see [`MarkerBudget`]
+  marker_budget: Option<Duration>,
+  /// The markers disabled for going over the budget, for the rest of the run
+  slow_markers: Vec<SlowMarker>,
+  /// The columns between two tab stops of the displayed lines, see [`expand_tabs`]
+  tab_width: usize,
+  /// The bytes of a message displayed before it gets cut, see [`RawLayout::truncated`]
+  max_line_length: usize,
+  /// The trailers ending the diagnostics of a target, in entry order, see [`CompileTarget`]
+  targets: Vec<(usize, CompileTarget)>,
+  /// The blocks repeated by another target of their package, see [`BuildOutput::duplicates`]
+  duplicates: Vec<DuplicateBlock>,
+  /// The number of targets the duplicates were detected for
+  checked_targets: usize,
 }
 
 impl<'a> Default for BuildOutput<'a> {
@@ -66,10 +114,32 @@ impl<'a> Default for BuildOutput<'a> {
       notes: Default::default(),
       build_events: Default::default(),
       errors: Default::default(),
+      failures: Default::default(),
+      script_warnings: Default::default(),
+      artifacts: Default::default(),
       remove_noise: Default::default(),
+      inline_markers: Default::default(),
+      ansi_colors: Default::default(),
       cursor: Default::default(),
       prepared: Default::default(),
       markers: Default::default(),
+      queue_latency: Default::default(),
+      segments: Default::default(),
+      segments_valid: Default::default(),
+      hooks: Default::default(),
+      dirty: Default::default(),
+      ipc: Default::default(),
+      ipc_cursor: Default::default(),
+      detected: Default::default(),
+      repeats: Default::default(),
+      prepare_limit: Default::default(),
+      marker_budget: Some(MarkerBudget::DEFAULT),
+      slow_markers: Default::default(),
+      tab_width: Self::DEFAULT_TAB_WIDTH,
+      max_line_length: Self::DEFAULT_MAX_LINE_LENGTH,
+      targets: Default::default(),
+      duplicates: Default::default(),
+      checked_targets: Default::default(),
     }
   }
 }
@@ -84,43 +154,213 @@ impl<'a> BuildOutput<'a> {
   /// Number of workers to spawn for display preparation [`Self::prepare`] call
   pub const WORKERS: u8 = 5;
 
+  /// Width of the compact gutter, enough for a rustc error code like `E0308`
+  pub const COMPACT_GUTTER_WIDTH: usize = 5;
+
+  /// The blank margins shared by the entries without marker, see [`Self::padding`]
+  const PADDING: &'static str = "                                ";
+
+  /// Number of segments after which [`BuildOutput::snapshot`] merges them back into one
+  pub const MAX_SNAPSHOT_SEGMENTS: usize = 64;
+
+  /// The columns between two tab stops of the displayed lines
+  pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+  /// The bytes of a message displayed before it gets cut, e.g. a serialized JSON blob
+  /// printed by a build script
+  pub const DEFAULT_MAX_LINE_LENGTH: usize = 4096;
+
   /// If true remove non-marker output lines
   pub fn with_noise_removed(mut self, r: bool) -> Self {
     self.remove_noise = r;
     self
   }
 
+  /// If true keep the captured marker text inside the message and style it in place,
+  /// the margin then becomes a compact gutter of badges (see [`MarkerRef::badge`])
+  pub fn with_inline_markers(mut self, v: bool) -> Self {
+    self.inline_markers = v;
+    self
+  }
+
+  /// Switch between the compact gutter and the captured text in the margin,
+  /// the prepared lines get rendered again by the next [`Self::prepare`]
+  pub fn set_inline_markers(&mut self, v: bool) {
+    if self.inline_markers != v {
+      self.inline_markers = v;
+      self.rewind(0);
+    }
+  }
+
+  /// Check if the markers are styled inline, with the compact gutter
+  pub fn inline_markers(&self) -> bool {
+    self.inline_markers
+  }
+
+  /// If true translate the ANSI colors of the entries into styles and keep them
+  /// instead of restyling the markers, otherwise the escape sequences are stripped
+  pub fn with_ansi_colors(mut self, v: bool) -> Self {
+    self.ansi_colors = v;
+    self
+  }
+
+  /// Define the time each marker may spend per batch before getting disabled,
+  /// `None` to never disable them
+  pub fn with_marker_budget(mut self, v: Option<Duration>) -> Self {
+    self.marker_budget = v;
+    self
+  }
+
+  /// Define the columns between two tab stops of the displayed lines,
+  /// the entries keep their raw message
+  pub fn with_tab_width(mut self, v: usize) -> Self {
+    self.tab_width = v;
+    self
+  }
+
+  /// Define the bytes of a message displayed before it gets cut, the entries keep
+  /// their raw message for the search, the copies and the exports
+  pub fn with_max_line_length(mut self, v: usize) -> Self {
+    self.max_line_length = v;
+    self
+  }
+
+  /// Replace the [`CommandRunner`] of the rule's [`Rule::on_marker`] hook
+  pub fn with_hook_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+    self.hooks = MarkerHooks::new(runner);
+    self
+  }
+
   /// If true remove non-marker output lines
   pub fn with_build_events(mut self, events: Sender<BuildEvent>) -> Self {
     self.build_events = Some(events);
     self
   }
 
+  /// Stream the detected markers to the clients of an [`IpcServer`]
+  pub fn with_ipc(mut self, ipc: IpcServer) -> Self {
+    self.ipc = ipc;
+    self
+  }
+
   /// Add a new build entry to the unprocessed queue
   pub fn push(&mut self, e: BuildEntry) {
-    self.entries.push(e);
+    self.intake(e);
   }
 
   /// Add multiple build entries to the unprocessed queue
   pub fn extend<Item: Into<BuildEntry>, Iter: IntoIterator<Item = Item>>(&mut self, entries: Iter) {
-    self
-      .entries
-      .extend(entries.into_iter().map(|e| e.into()).collect::<Vec<_>>());
+    for e in entries {
+      self.intake(e.into());
+    }
   }
 
   /// Pull all build entries from the supplied [`Receiver`].
   ///
   /// Does not block the current thread
-  pub fn pull(&mut self, from: &Receiver<Vec<BuildEntry>>) {
-    while let Ok(entries) = from.try_recv() {
-      self.entries.extend(entries);
+  pub fn pull(&mut self, from: &Receiver<BuildEntry>) {
+    self.pull_up_to(from, usize::MAX);
+  }
+
+  /// Pull at most `max` build entries from the supplied [`Receiver`], the others
+  /// stay queued (see [`crate::Builder::QUEUE_CAPACITY`]).
+  ///
+  /// Does not block the current thread
+  pub fn pull_up_to(&mut self, from: &Receiver<BuildEntry>, max: usize) {
+    let now = Instant::now();
+    self.queue_latency = Duration::ZERO;
+    for entry in from.try_iter().take(max) {
+      self.queue_latency = self
+        .queue_latency
+        .max(now.saturating_duration_since(*entry.created_at()));
+      self.intake(entry);
+    }
+  }
+
+  /// Queue an entry, collecting the [`Artifact`] it reports and the crate dir
+  /// of its progress line (see [`register_crate_dir`]).
+  ///
+  /// A JSON diagnostic gets queued as its rendered lines, the first one
+  /// carrying the [`Suggestion`]s of the diagnostic
+  fn intake(&mut self, e: BuildEntry) {
+    if let Some(artifact) = Artifact::parse(e.message()) {
+      self.artifacts.push(artifact);
+    }
+    if let Some(diagnostic) = Diagnostic::parse(e.message()) {
+      let mut suggestions = Some(diagnostic.suggestions);
+      for line in diagnostic.rendered.trim_end().lines() {
+        self.entries.push(
+          BuildEntry::with_timestamp(line, e.origin(), *e.created_at())
+            .with_suggestions(suggestions.take().unwrap_or_default()),
+        );
+      }
+      return;
     }
+    register_crate_dir(e.message());
+    self.entries.push(e);
+  }
+
+  /// Retrieve the longest time spent in the queue by the entries of the last [`Self::pull`],
+  /// from their capture by the reader threads
+  pub fn queue_latency(&self) -> Duration {
+    self.queue_latency
   }
 
-  /// Tag a [`BuildEntry`] with the supplied [`BuildTag`]
+  /// Tag a [`BuildEntry`] with the supplied [`BuildTag`].
+  ///
+  /// If the entry is already prepared, its line gets rendered again by the next [`Self::prepare`]
   pub fn tag_entry(&mut self, i: usize, tag: BuildTag) {
     if let Some(e) = self.entries.get_mut(i) {
       e.tags_mut().push(tag);
+      self.segments_valid = self.segments_valid.min(i);
+      if i < self.cursor {
+        self.dirty.insert(i);
+      }
+    }
+  }
+
+  /// Check the invariants between the entries, the prepared lines and the cursor
+  pub fn is_consistent(&self) -> bool {
+    self.cursor <= self.entries.len() && self.prepared.len() == self.cursor
+  }
+
+  /// Restore the invariants broken by an external mutation: the prepared lines
+  /// get truncated and the cursor clamped, so that the next [`Self::prepare`]
+  /// renders the missing lines again.
+  ///
+  /// Returns true if anything had to be fixed
+  pub fn recover(&mut self) -> bool {
+    if self.is_consistent() {
+      return false;
+    }
+    Debug::log(format!(
+      "build output out of sync: {} entries, {} prepared lines, cursor at {}",
+      self.entries.len(),
+      self.prepared.len(),
+      self.cursor
+    ));
+    self.rewind(self.cursor.min(self.entries.len()).min(self.prepared.len()));
+    true
+  }
+
+  /// Forget the preparation of the entries from `entry_id`
+  fn rewind(&mut self, entry_id: usize) {
+    self.cursor = self.cursor.min(entry_id);
+    self.prepared.truncate(self.cursor);
+    let cursor = self.cursor;
+    self.errors.retain(|id| *id < cursor);
+    self.warnings.retain(|id| *id < cursor);
+    self.notes.retain(|id| *id < cursor);
+    self.failures.retain(|id| *id < cursor);
+    self.script_warnings.retain(|id| *id < cursor);
+    self.segments_valid = self.segments_valid.min(cursor);
+    self.dirty.retain(|id| *id < cursor);
+    self.trim_repeats();
+    let num_targets = self.targets.len();
+    self.targets.retain(|(id, _)| *id < cursor);
+    if self.targets.len() < num_targets {
+      self.duplicates.clear();
+      self.checked_targets = 0;
     }
   }
 
@@ -139,6 +379,22 @@ impl<'a> BuildOutput<'a> {
     self.entries.len()
   }
 
+  /// Retrieve the number of entries the next [`Self::prepare`] has to process,
+  /// including the ones tagged again since their preparation
+  pub fn num_unprepared(&self) -> usize {
+    let from = self
+      .dirty
+      .first()
+      .map_or(self.cursor, |entry_id| (*entry_id).min(self.cursor));
+    self.entries.len().saturating_sub(from)
+  }
+
+  /// Limit the entries processed by each [`Self::prepare`] call, the others
+  /// are left to the next calls. None prepares all of them
+  pub fn set_prepare_limit(&mut self, limit: Option<usize>) {
+    self.prepare_limit = limit;
+  }
+
   /// Retrieve the unprepared entries tuple `(id, entry)`
   pub fn unprepared_entries(&self) -> Option<Vec<(usize, &BuildEntry)>> {
     if self.cursor >= self.entries.len() {
@@ -150,6 +406,7 @@ impl<'a> BuildOutput<'a> {
         .iter()
         .enumerate()
         .skip(self.cursor)
+        .take(self.prepare_limit.unwrap_or(usize::MAX))
         .map(|(id, item)| (id, item))
         .collect::<Vec<_>>(),
     )
@@ -176,6 +433,47 @@ impl<'a> BuildOutput<'a> {
     }
   }
 
+  /// Retrieve the active [`Rule`]
+  pub fn rule(&self) -> &Rule {
+    &self.rule
+  }
+
+  /// Replace the [`Rule`] and reset the prepared state,
+  /// all entries get prepared again on the next [`Self::prepare`] call
+  pub fn set_rule(&mut self, rule: Rule) {
+    self.rule = rule;
+    self.disable_slow_markers();
+    for entry in &mut self.entries {
+      entry.tags_mut().clear();
+    }
+    self.errors.clear();
+    self.warnings.clear();
+    self.notes.clear();
+    self.script_warnings.clear();
+    self.prepared.clear();
+    self.cursor = 0;
+    self.segments.clear();
+    self.segments_valid = 0;
+    self.dirty.clear();
+    self.markers.tags_mut().clear();
+    self.markers.secondary_mut().clear();
+    self.markers.set_selection(None);
+  }
+
+  /// Retrieve the markers disabled for going over the budget, see [`Self::with_marker_budget`]
+  pub fn slow_markers(&self) -> &[SlowMarker] {
+    &self.slow_markers
+  }
+
+  /// Disable the slow markers in the current rule
+  fn disable_slow_markers(&mut self) {
+    for marker in &mut self.rule.markers {
+      if self.slow_markers.iter().any(|slow| slow.marker == *marker) {
+        marker.disabled = true;
+      }
+    }
+  }
+
   /// Retrieve the list of markers
   pub fn markers(&self) -> &Markers {
     &self.markers
@@ -186,50 +484,37 @@ impl<'a> BuildOutput<'a> {
     &mut self.markers
   }
 
+  /// Parse cargo's `--> src/main.rs:3:10`, on its own line or after a single-line diagnostic
   pub fn extract_location<M: AsRef<str>>(message: M) -> crate::Result<Option<Location>> {
     let trimmed_message = message.as_ref().trim();
-    if trimmed_message.starts_with("-->") {
-      return Ok(Some(trimmed_message[3..].trim().parse::<Location>()?));
-    }
-    Ok(None)
+    let arrow = match trimmed_message.find("-->") {
+      Some(0) => 0,
+      Some(i) if trimmed_message[..i].ends_with(char::is_whitespace) => i,
+      _ => return Ok(None),
+    };
+    Ok(Some(
+      trimmed_message[arrow + 3..].trim().parse::<Location>()?,
+    ))
   }
 
-  pub fn block_range_at(&self, entry_id: usize) -> Option<Range<usize>> {
-    if self.markers.is_empty() {
-      return None;
-    }
-    let mut found = None;
-    for chunks in self.markers.chunks(2) {
-      let (marker_before, _) = chunks[0];
-      let marker_after = chunks.get(1).map(|(id, _tag)| *id);
-      if entry_id >= marker_before && (marker_after.is_none() || entry_id < marker_after.unwrap()) {
-        found = Some((marker_before, marker_after));
-        break;
-      }
-    }
-    found
-      .or_else(|| Some((self.markers.last().unwrap().0, None)))
-      .map(|(before, after)| Range {
-        start: before,
-        end: after.unwrap_or_else(|| self.entries.len()),
+  /// Retrieve the range of entries of the block containing `entry_id`.
+  ///
+  /// Blocks are delimited by primary markers, secondary markers
+  /// (see [`DeclaredMarker::is_secondary`]) stay inside the enclosing block.
+  pub fn block_range_at(&self, entry_id: EntryId) -> Option<Range<usize>> {
+    self
+      .markers
+      .block_bounds(entry_id)
+      .map(|(_marker_id, start, end)| Range {
+        start: start.0,
+        end: end.map(|end| end.0).unwrap_or(self.entries.len()),
       })
   }
 
-  pub fn block_at(&'a self, entry_id: usize) -> Option<MarkedBlock<'a>> {
-    if let Some(range) = self.block_range_at(entry_id) {
-      let marker_id = *self
-        .markers
-        .iter()
-        .enumerate()
-        .find_map(|(marker_id, (entry_id, _tag))| {
-          if *entry_id == range.start {
-            return Some(marker_id);
-          }
-          None
-        })
-        .as_ref()
-        .expect("block found with no coresponding marker");
-      let marker = self.entries[range.start].first_marker().unwrap();
+  pub fn block_at(&self, entry_id: EntryId) -> Option<MarkedBlock<'_>> {
+    if let Some((marker_id, _start, _end)) = self.markers.block_bounds(entry_id) {
+      let range = self.block_range_at(entry_id)?;
+      let marker = self.entries[range.start].primary_marker().unwrap();
       let entries = self.entries[range.start..range.end]
         .iter()
         .collect::<Vec<_>>();
@@ -238,7 +523,26 @@ impl<'a> BuildOutput<'a> {
     None
   }
 
-  pub fn block_size(&'a self, entry_id: usize) -> Option<usize> {
+  /// Retrieve all the blocks, in order
+  pub fn blocks(&self) -> Vec<MarkedBlock<'_>> {
+    self
+      .markers
+      .primary_ids()
+      .filter_map(|marker_id| self.block_at(self.markers[marker_id].0))
+      .collect::<Vec<_>>()
+  }
+
+  /// List the blocks of `kind` for the quick-jump dialog, in order
+  pub fn jump_targets(&self, kind: BuildTagKind) -> Vec<JumpTarget> {
+    self
+      .blocks()
+      .iter()
+      .filter(|block| block.marker().kind() == kind)
+      .map(|block| block.jump_target())
+      .collect::<Vec<_>>()
+  }
+
+  pub fn block_size(&self, entry_id: EntryId) -> Option<usize> {
     if let Some(block) = self.block_at(entry_id) {
       return Some(block.entries().len());
     }
@@ -260,9 +564,90 @@ impl<'a> BuildOutput<'a> {
     None
   }
 
+  /// The raw byte a message of `text` gets cut at for display, past `min` and on a char
+  /// boundary. `None` if it fits in `max_len` bytes
+  fn display_end(text: &str, min: usize, max_len: usize) -> Option<usize> {
+    if text.len() <= max_len.max(min) {
+      return None;
+    }
+    let mut end = max_len.max(min);
+    while !text.is_char_boundary(end) {
+      end -= 1;
+    }
+    Some(end)
+  }
+
+  /// The suffix of a message cut for display, with the size of the hidden bytes
+  fn truncation_suffix(hidden: usize) -> Span<'static> {
+    Span::styled(
+      format!(
+        " … [+{}, press x to expand]",
+        crate::human_size(hidden as u64)
+      ),
+      Style::default().dark_gray().italic(),
+    )
+  }
+
+  /// The badge of a marker, padded to the gutter width so that the messages stay aligned
+  /// The display copy of the `spans` of a raw message starting at terminal `column`:
+  /// the tabs get expanded and the trailing whitespace stripped
+  fn display_spans<'s>(spans: Vec<Span<'s>>, column: usize, tab_width: usize) -> Vec<Span<'s>> {
+    let mut column = column;
+    let mut spans = spans
+      .into_iter()
+      .map(|span| {
+        if !span.content.contains('\t') {
+          column += span.width();
+          return span;
+        }
+        let content = expand_tabs(&span.content, column, tab_width);
+        column += content.width();
+        Span::styled(content, span.style)
+      })
+      .collect::<Vec<_>>();
+    while let Some(last) = spans.last_mut() {
+      let trimmed = last.content.trim_end();
+      if trimmed.is_empty() {
+        spans.pop();
+        continue;
+      }
+      let len = trimmed.len();
+      match &mut last.content {
+        Cow::Owned(content) => content.truncate(len),
+        Cow::Borrowed(content) => *content = &content[..len],
+      }
+      break;
+    }
+    spans
+  }
+
+  /// The blank margin of the entries without marker, borrowed from [`Self::PADDING`]
+  /// up to its width
+  fn padding(width: usize) -> Cow<'static, str> {
+    match Self::PADDING.get(..width) {
+      Some(padding) => Cow::Borrowed(padding),
+      None => Cow::Owned(" ".repeat(width)),
+    }
+  }
+
+  fn gutter_badge(marker: &MarkerRef, width: Option<usize>) -> String {
+    format!(
+      "{:<width$}",
+      marker.badge(),
+      width = width.unwrap_or_default()
+    )
+  }
+
   /// Prepare the entries that have not been processed yet
   /// by batch processing in multiple threads.
   pub fn prepare(&mut self) -> bool {
+    self.recover();
+    // the entries tagged since their preparation get prepared again
+    if let Some(entry_id) = self.dirty.first().copied() {
+      crate::dbg!("Preparing again from dirty entry #{}", entry_id);
+      self.rewind(entry_id);
+    }
+    self.tag_build_scripts();
     let mut threads = vec![];
     let start_time = Instant::now();
     let mut num_prepared = 0;
@@ -273,7 +658,7 @@ impl<'a> BuildOutput<'a> {
       pub batch_id: usize,
       pub entry_id: usize,
       pub entry: BuildEntry,
-      pub display: Line<'a>,
+      pub display: LogEntry<'a>,
     }
 
     let locations: Arc<Mutex<Vec<(usize, Location)>>> = Arc::new(Mutex::new(Vec::new()));
@@ -281,63 +666,178 @@ impl<'a> BuildOutput<'a> {
     if let Some(batches) = self.batch_unprepared_entries() {
       for (batch_id, mut batch) in batches {
         num_prepared += batch.len();
-        let (tx, rx) = channel::<(usize, Vec<PreparedEntry<'_>>)>();
+        let (tx, rx) = channel::<(usize, Vec<PreparedEntry<'_>>, MarkerBudget)>();
         recv.push(rx);
         let style_log = Style::default().dim();
         let th_locations = locations.clone();
         let rule = self.rule.clone();
+        let inline_markers = self.inline_markers;
+        let ansi_colors = self.ansi_colors;
+        let tab_width = self.tab_width;
+        let max_line_length = self.max_line_length;
+        let mut budget = MarkerBudget::new(self.marker_budget);
         threads.push(spawn(move || {
           Debug::log(format!(
             "preparing batch #{} -> {} entries",
             batch_id,
             batch.len()
           ));
-          let mut ret: Vec<PreparedEntry<'_>> = vec![];
+          let mut ret: Vec<PreparedEntry<'_>> = Vec::with_capacity(batch.len());
+          // raw escape bytes never reach the markers nor the display
+          let colored = batch
+            .iter_mut()
+            .map(|(_, entry)| {
+              if !entry.message().contains('\x1b') {
+                return None;
+              }
+              let line = AnsiLine::parse(entry.message());
+              entry.set_message(line.text());
+              match ansi_colors && line.is_styled() {
+                true => Some(line),
+                false => None,
+              }
+            })
+            .collect::<Vec<_>>();
           for (_, entry) in &mut batch {
-            if let Err(e) = Markers::prepare(entry, &rule) {
+            // the captured output of a failed build script stays in the failure's block
+            let script_output = entry
+              .tag(BuildTagKind::BuildScript)
+              .and_then(|tag| tag.get_build_script())
+              .is_some_and(|script| script.line == BuildScriptLine::Output);
+            if script_output {
+              continue;
+            }
+            if let Err(e) = Markers::prepare(entry, &rule, &mut budget) {
               crate::dbg!("Failed to prepare markers: {}", e);
             }
+            if let Some(failure) = CrateFailure::parse(entry.message()) {
+              entry.set_tag(BuildTag::trailer(failure));
+            }
           }
-          let margin_width = batch
-            .iter()
-            .map(|(_id, entry)| {
-              if let Some(marker) = entry.first_marker() {
-                return marker.captured().unwrap().text.len();
-              }
-              return 0;
-            })
-            .max();
-          for (_batch_entry_id, (global_entry_id, entry)) in batch.into_iter().enumerate() {
-            let mut line = Line::default(); //format!("{} | {}", entry_id, entry.message().to_string());
+          // the compact gutter is shared by all the batches
+          let margin_width = match inline_markers {
+            true => Some(Self::COMPACT_GUTTER_WIDTH),
+            false => batch
+              .iter()
+              .map(|(_id, entry)| {
+                entry
+                  .primary_marker()
+                  .and_then(|marker| marker.captured())
+                  .map_or(0, |captured| captured.text.len())
+              })
+              .max(),
+          };
+          for (_batch_entry_id, ((global_entry_id, entry), colored)) in
+            batch.into_iter().zip(colored).enumerate()
+          {
             let mut margin = Span::default();
-            let mut message = entry.message().clone();
+            // past the cap only a display copy is kept, the entry keeps its raw message
+            let text = entry.message().as_str();
+            let margin_end = entry
+              .primary_marker()
+              .and_then(|marker| marker.captured())
+              .map_or(0, |captured| captured.range.end);
+            let truncated = Self::display_end(text, margin_end, max_line_length);
+            let end = truncated.unwrap_or(text.len());
+            // only copied once the part shown by the margin is known
+            let mut message;
+            // the terminal column of the raw message the displayed one starts at
+            let mut column = 0;
+            let mut raw = RawLayout {
+              tab_width,
+              ..Default::default()
+            };
+            // single-line diagnostics carry their location on the marker line
+            if let Some(loc) = rule.find_location(entry.message()) {
+              if let Ok(mut g) = th_locations.try_lock_for(Duration::from_millis(150)) {
+                g.push((global_entry_id, loc));
+              }
+            }
 
-            if let Some(marker) = entry.first_marker() {
+            if let Some(marker) = entry.primary_marker() {
               // crate::dbg!("entry #{} is a marker: {}", global_entry_id, marker.kind());
               let captured = marker.captured().unwrap();
-              margin = margin.content(captured.text.clone());
-              margin = margin.style(marker.declared().style);
-              message = message.as_str()[captured.range.end..].to_string();
-            } else {
-              if let Ok(Some(loc)) = Self::extract_location(message.as_str()) {
-                if let Ok(mut g) = th_locations.try_lock_for(Duration::from_millis(150)) {
-                  g.push((global_entry_id, loc));
+              if let Some(colored) = colored.as_ref() {
+                // already colored by cargo, keep its styles instead of ours
+                match inline_markers {
+                  true => {
+                    margin = margin
+                      .content(Self::gutter_badge(marker, margin_width))
+                      .style(marker.declared().style);
+                    message = colored.spans(0..end);
+                  }
+                  false => {
+                    margin = margin
+                      .content(captured.text.clone())
+                      .style(colored.style_at(captured.range.start));
+                    message = colored.spans(captured.range.end..end);
+                    column = text[..captured.range.end].width();
+                    raw.margin = Some(captured.range.clone());
+                    raw.message_start = captured.range.end;
+                  }
                 }
+              } else if inline_markers {
+                margin = margin.content(Self::gutter_badge(marker, margin_width));
+                message = vec![
+                  Span::raw(text[..captured.range.start].to_string()),
+                  Span::styled(
+                    text[captured.range.clone()].to_string(),
+                    marker.declared().style,
+                  ),
+                  Span::raw(text[captured.range.end..end].to_string()),
+                ];
+              } else {
+                margin = margin.content(captured.text.clone());
+                message = vec![Span::raw(text[captured.range.end..end].to_string())];
+                column = text[..captured.range.end].width();
+                raw.margin = Some(captured.range.clone());
+                raw.message_start = captured.range.end;
+              }
+              if colored.is_none() {
+                margin = margin.style(marker.declared().style);
               }
-              margin = margin.content(" ".repeat(margin_width.unwrap_or_else(|| 4)));
+              // toned down, the diagnostics it sums up are the interesting part,
+              // see [`crate::LogView::SUMMARY_HINT`]
+              if marker.kind() == BuildTagKind::Summary {
+                let style = marker.declared().style;
+                margin = margin.style(style);
+                message = message
+                  .into_iter()
+                  .map(|span| span.patch_style(style))
+                  .collect();
+              }
+            } else {
+              margin = margin.content(Self::padding(margin_width.unwrap_or(4)));
               margin = margin.style(style_log);
+              message = match colored.as_ref() {
+                Some(colored) => colored.spans(0..end),
+                None => vec![Span::raw(text[..end].to_string())],
+              };
             }
-            line.push_span(margin);
-            line.push_span(" ");
-            line.push_span(message);
+            let gutter = margin.width() + 1;
+            raw.column = column;
+            raw.display_start = margin.content.len() + 1;
+            raw.truncated = truncated;
+            let mut spans = Vec::with_capacity(message.len() + 3);
+            spans.push(margin);
+            spans.push(Span::raw(" "));
+            spans.extend(Self::display_spans(message, column, tab_width));
+            if truncated.is_some() {
+              spans.push(Self::truncation_suffix(text.len() - end));
+            }
+            let line = Line::from(spans);
+            let display = LogEntry::new(line, BuildTagKinds::of(entry.tags()))
+              .with_origin(entry.origin())
+              .with_gutter(gutter)
+              .with_raw_layout(raw);
             ret.push(PreparedEntry {
               batch_id,
               entry_id: global_entry_id,
               entry,
-              display: line,
+              display,
             });
           }
-          let _ = tx.send((batch_id, ret));
+          let _ = tx.send((batch_id, ret, budget));
         }));
       }
       for th in threads {
@@ -346,46 +846,91 @@ impl<'a> BuildOutput<'a> {
       crate::dbg!("prepare_mt: all workers done, receiving data ...");
       self
         .prepared
-        .resize(self.prepared.len() + num_prepared, Line::default());
+        .resize(self.prepared.len() + num_prepared, LogEntry::default());
+      self.segments_valid = self.segments_valid.min(self.cursor);
+      let first_prepared = self.cursor;
+      // the prepared entries tagged again after their preparation
+      let mut retagged = first_prepared;
       self.cursor += num_prepared;
       let mut selection = None;
+      let mut new_markers = vec![];
       for r in recv {
-        if let Ok((batch_id, batch)) = r.try_recv() {
+        if let Ok((batch_id, batch, budget)) = r.try_recv() {
+          for (marker, spent) in budget.exceeded() {
+            if self.slow_markers.iter().any(|slow| slow.marker == *marker) {
+              continue;
+            }
+            let slow = SlowMarker {
+              rule: self.rule.aliases.first().cloned().unwrap_or_default(),
+              marker: marker.clone(),
+              spent: *spent,
+            };
+            Debug::log(format!("prepare_mt: {}", slow));
+            self.slow_markers.push(slow);
+          }
           crate::dbg!(
             "prepare_mt: worker #{} produced {} lines",
             batch_id,
             batch.len()
           );
           for entry in batch {
-            if let Some(_) = entry.entry.tag(BuildTagKind::Error) {
-              self.send_event(BuildEvent::BuildError(entry.entry_id));
-              if self.markers.selection().is_none() {
-                selection = Some(entry.entry_id);
+            // an entry only counts as its most severe marker, the trailers and summaries not at all
+            let kind = match entry.entry.has_tag(BuildTagKind::Trailer) {
+              true => {
+                self.failures.push(entry.entry_id);
+                None
+              }
+              false => entry
+                .entry
+                .primary_marker()
+                .map(|marker| marker.kind())
+                .filter(|kind| *kind != BuildTagKind::Summary),
+            };
+            match kind {
+              Some(BuildTagKind::Error) => {
+                if self.errors.is_empty() {
+                  self.send_event(BuildEvent::FirstErrorDetected(entry.entry_id));
+                }
+                if self.markers.selection().is_none() {
+                  selection = Some(entry.entry_id);
+                }
+                self.errors.push(entry.entry_id);
               }
-              self.errors.push(entry.entry_id);
+              Some(BuildTagKind::Warning) if entry.entry.has_tag(BuildTagKind::BuildScript) => {
+                self.script_warnings.push(entry.entry_id)
+              }
+              Some(BuildTagKind::Warning) => self.warnings.push(entry.entry_id),
+              Some(BuildTagKind::Note) => self.notes.push(entry.entry_id),
+              _ => {}
             }
-            if let Some(_) = entry.entry.tag(BuildTagKind::Warning) {
-              self.warnings.push(entry.entry_id);
+            if let Some(kind) = kind {
+              new_markers.push((entry.entry_id, kind));
             }
-            if let Some(_) = entry.entry.tag(BuildTagKind::Note) {
-              self.notes.push(entry.entry_id);
+            if let Some(target) = CompileTarget::parse(entry.entry.message()) {
+              self.targets.push((entry.entry_id, target));
             }
             self.entries[entry.entry_id] = entry.entry;
             self.prepared[entry.entry_id] = entry.display;
           }
         }
       }
-      *self.markers.tags_mut() = Markers::from_entries(self.entries.as_slice())
-        .tags()
-        .clone();
+      self.disable_slow_markers();
+      let markers = Markers::from_entries(self.entries.as_slice());
+      *self.markers.tags_mut() = markers.tags().clone();
+      *self.markers.secondary_mut() = markers.secondary().clone();
       if let Some(sel) = selection {
-        self.select_block_from_entry(sel);
+        self.select_block_from_entry(EntryId(sel));
       }
-      if let Ok(g) = locations.lock() {
+      if let Ok(mut g) = locations.lock() {
+        // the first location of a block wins over the ones of its secondary markers
+        g.sort_by(|(a, _), (b, _)| b.cmp(a));
         for (entry_id, location) in g.iter() {
-          let block = self.block_at(*entry_id);
+          let block = self.block_at(EntryId(*entry_id));
           if let Some(block) = block {
-            for i in block.range() {
+            let range = block.range();
+            self.segments_valid = self.segments_valid.min(range.start);
+            retagged = retagged.min(range.start);
+            for i in range {
               self.entries[i].set_tag(BuildTag::location(
                 location.path().clone(),
                 location.line(),
@@ -395,6 +940,29 @@ impl<'a> BuildOutput<'a> {
           }
         }
       }
+      if let Some(entry_id) = self.fold_sections(first_prepared) {
+        retagged = retagged.min(entry_id);
+      }
+      self.detect_repeats(first_prepared);
+      self.detect_duplicates();
+      for entry_id in retagged..self.cursor {
+        let kinds = BuildTagKinds::of(self.entries[entry_id].tags());
+        self.prepared[entry_id].set_kinds(kinds);
+      }
+      // after the locations, so that the hooks and the IPC clients know about them
+      new_markers.sort_by_key(|(entry_id, _kind)| *entry_id);
+      for (entry_id, kind) in new_markers {
+        let context = HookContext::new(kind, &self.entries[entry_id]);
+        if let Some(hook) = self.rule.on_marker.as_ref() {
+          self.hooks.trigger(hook, entry_id, &context);
+        }
+        // entries prepared again after a rewind were already streamed
+        if entry_id >= self.ipc_cursor {
+          self.ipc_cursor = entry_id + 1;
+          self.ipc.send(IpcEvent::marker(entry_id, context));
+          self.detected.push((EntryId(entry_id), kind));
+        }
+      }
       crate::dbg!(
         "prepare_mt: done preparing {} entries in {}s (selected marker: {:?})",
         num_prepared,
@@ -406,46 +974,273 @@ impl<'a> BuildOutput<'a> {
     false
   }
 
-  pub fn select_block_from_entry(&mut self, entry_id: usize) {
-    let marker_id = match self.block_at(entry_id) {
-      Some(block) => block.marker_id(),
-      None => 0,
-    };
-    self.markers.select(marker_id, None);
+  /// Tag the foldable sub-sections (see [`FoldableMarker`]) of the blocks
+  /// prepared from `entry_id`, a section grows with its block.
+  ///
+  /// Returns the first entry whose tags changed
+  fn fold_sections(&mut self, entry_id: usize) -> Option<usize> {
+    if self.rule.foldable.is_empty() {
+      return None;
+    }
+    let mut changed: Option<usize> = None;
+    let mut entry_id = self
+      .block_range_at(EntryId(entry_id))
+      .map(|range| range.start)
+      .unwrap_or(entry_id);
+    while entry_id < self.cursor {
+      let Some(range) = self.block_range_at(EntryId(entry_id)) else {
+        entry_id += 1;
+        continue;
+      };
+      let range = range.start..range.end.min(self.cursor);
+      let section = FoldableMarker::section(&self.rule.foldable, &self.entries[range.clone()])
+        .map(|(section, label)| (section, label.to_string()));
+      for id in range.clone() {
+        let folded = section
+          .as_ref()
+          .filter(|(section, _label)| section.contains(&(id - range.start)));
+        let entry = &mut self.entries[id];
+        match folded {
+          Some(_) if entry.has_tag(BuildTagKind::Foldable) => continue,
+          Some((_section, label)) => entry.set_tag(BuildTag::foldable(label)),
+          None if entry.has_tag(BuildTagKind::Foldable) => entry
+            .tags_mut()
+            .retain(|tag| tag.get_kind() != BuildTagKind::Foldable),
+          None => continue,
+        }
+        self.segments_valid = self.segments_valid.min(id);
+        changed = Some(changed.map_or(id, |changed| changed.min(id)));
+      }
+      entry_id = range.end.max(entry_id + 1);
+    }
+    changed
   }
 
-  pub fn select_entry(&mut self, entry_id: usize, region: Option<Range<usize>>) {
-    let marker_id = match self.block_at(entry_id) {
-      Some(block) => block.marker_id(),
-      None => 0,
+  /// Tag the build script output (see [`BuildScript`]) among the entries the next
+  /// [`Self::prepare`] processes, the report of a failed build script continues
+  /// from the entries prepared before
+  fn tag_build_scripts(&mut self) {
+    let end = match self.prepare_limit {
+      Some(limit) => self.entries.len().min(self.cursor.saturating_add(limit)),
+      None => self.entries.len(),
     };
-    self
-      .markers
-      .set_selection(Some(MarkerSelection::new(marker_id, entry_id, region)));
-    crate::dbg!(
-      "Selecting marker #{} -> {:?}",
-      marker_id,
-      self.markers.selection()
-    );
+    let mut previous = self
+      .cursor
+      .checked_sub(1)
+      .and_then(|entry_id| self.entries[entry_id].tag(BuildTagKind::BuildScript))
+      .and_then(|tag| tag.get_build_script())
+      .cloned();
+    for entry in &mut self.entries[self.cursor.min(end)..end] {
+      // the escape sequences are only stripped by the workers
+      previous = AnsiLine::with_stripped(entry.message(), |text| {
+        BuildScript::parse(text).or_else(|| previous.and_then(|script| script.next(text)))
+      });
+      match previous.as_ref() {
+        Some(script) => entry.set_tag(BuildTag::build_script(script.clone())),
+        None => entry
+          .tags_mut()
+          .retain(|tag| tag.get_kind() != BuildTagKind::BuildScript),
+      }
+    }
   }
 
-  /// Retrieve the displayable lines
-  pub fn display(&self) -> Vec<LogEntry<'_>> {
-    let mut ret = self
-      .prepared
-      .iter()
-      .enumerate()
-      .map(|(id, line)| LogEntry::new(line.clone(), self.entries[id].tags().clone()))
+  /// Find the runs of identical consecutive messages among the entries prepared
+  /// from `entry_id`, a run grows with the entries that repeat its message.
+  ///
+  /// The blank lines are never grouped
+  fn detect_repeats(&mut self, entry_id: usize) {
+    self.trim_repeats();
+    for id in entry_id.max(1)..self.cursor {
+      let message = self.entries[id].message();
+      if message.trim().is_empty() || message != self.entries[id - 1].message() {
+        continue;
+      }
+      match self.repeats.last_mut() {
+        Some(run) if run.end == id => run.end += 1,
+        _ => self.repeats.push(id - 1..id + 1),
+      }
+    }
+  }
+
+  /// Drop the repeats past the preparation cursor, after a rewind
+  fn trim_repeats(&mut self) {
+    let cursor = self.cursor;
+    self.repeats.retain_mut(|run| {
+      run.end = run.end.min(cursor);
+      run.len() >= 2
+    });
+  }
+
+  /// Retrieve the runs of identical consecutive messages, as ranges of entries
+  pub fn repeats(&self) -> &Vec<Range<usize>> {
+    &self.repeats
+  }
+
+  /// Find the error and warning blocks repeated by another target of their package,
+  /// once new targets got their trailer.
+  ///
+  /// The blocks of a target are the ones before its trailer, they end at the latest there.
+  /// A block is repeated when its lines are the same, the trailing blank ones aside
+  fn detect_duplicates(&mut self) {
+    if self.targets.len() == self.checked_targets {
+      return;
+    }
+    self.targets.sort_by_key(|(entry_id, _)| *entry_id);
+    let mut seen: HashMap<(&str, String), &CompileTarget> = HashMap::new();
+    let mut duplicates = vec![];
+    let mut start = 0;
+    for (trailer, target) in &self.targets {
+      let range = start..*trailer;
+      let mut marked = [
+        (BuildTagKind::Error, &self.errors),
+        (BuildTagKind::Warning, &self.warnings),
+      ]
+      .into_iter()
+      .flat_map(|(kind, ids)| {
+        let from = ids.partition_point(|id| *id < range.start);
+        let to = ids.partition_point(|id| *id < range.end);
+        ids[from..to].iter().map(move |id| (*id, kind))
+      })
       .collect::<Vec<_>>();
-    if let Some(sel_entry_id) = self.markers.selected_entry() {
-      ret[sel_entry_id].line_mut().style = ret[sel_entry_id]
-        .line()
-        .style
-        .patch(Style::default().on_light_blue());
+      marked.sort_by_key(|(entry_id, _)| *entry_id);
+      for (entry_id, kind) in marked {
+        let Some(block) = self.block_at(EntryId(entry_id)) else {
+          continue;
+        };
+        let block = block.range().start..block.range().end.min(*trailer);
+        let mut lines = self.entries[block.clone()]
+          .iter()
+          .map(|entry| entry.message().as_str())
+          .collect::<Vec<_>>();
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+          lines.pop();
+        }
+        let key = (target.package.as_str(), lines.join("\n"));
+        match seen.get(&key) {
+          Some(first) if *first != target => duplicates.push(DuplicateBlock {
+            range: block,
+            kind,
+            target: target.clone(),
+            first: (*first).clone(),
+          }),
+          Some(_) => {}
+          None => {
+            seen.insert(key, target);
+          }
+        }
+      }
+      start = trailer + 1;
+    }
+    self.duplicates = duplicates;
+    self.checked_targets = self.targets.len();
+  }
+
+  /// Retrieve the error and warning blocks already reported for another target of their
+  /// package, e.g. the warnings of a lib repeated when compiling its tests
+  pub fn duplicates(&self) -> &Vec<DuplicateBlock> {
+    &self.duplicates
+  }
+
+  /// Count the diagnostics of `kind` once, without their duplicates
+  fn num_unique(&self, kind: BuildTagKind) -> usize {
+    let ids = match kind {
+      BuildTagKind::Error => &self.errors,
+      BuildTagKind::Warning => &self.warnings,
+      BuildTagKind::Note => &self.notes,
+      _ => return 0,
+    };
+    let duplicates = self
+      .duplicates
+      .iter()
+      .filter(|duplicate| duplicate.kind == kind)
+      .count();
+    ids.len().saturating_sub(duplicates)
+  }
+
+  /// Retrieve the number of errors, the ones repeated by another target counted once
+  pub fn num_errors(&self) -> usize {
+    self.num_unique(BuildTagKind::Error)
+  }
+
+  /// Retrieve the number of warnings, the ones repeated by another target counted once
+  pub fn num_warnings(&self) -> usize {
+    self.num_unique(BuildTagKind::Warning)
+  }
+
+  /// Count the diagnostics of each target, in the order of their trailers
+  pub fn target_counts(&self) -> Vec<TargetCounts> {
+    let mut start = 0;
+    let count = |ids: &Vec<usize>, range: &Range<usize>| {
+      ids.partition_point(|id| *id < range.end) - ids.partition_point(|id| *id < range.start)
+    };
+    let mut ret: Vec<TargetCounts> = vec![];
+    for (trailer, target) in &self.targets {
+      let range = start..*trailer;
+      start = trailer + 1;
+      let pos = match ret.iter().position(|counts| counts.target == *target) {
+        Some(pos) => pos,
+        None => {
+          ret.push(TargetCounts::new(target.clone()));
+          ret.len() - 1
+        }
+      };
+      let counts = &mut ret[pos];
+      counts.errors += count(&self.errors, &range);
+      counts.warnings += count(&self.warnings, &range);
+      counts.duplicates += self
+        .duplicates
+        .iter()
+        .filter(|duplicate| range.contains(&duplicate.range.start))
+        .count();
     }
     ret
   }
 
+  /// Select the block containing `entry_id`, nothing gets selected
+  /// for the entries located before the first marker
+  pub fn select_block_from_entry(&mut self, entry_id: EntryId) {
+    match self.block_at(entry_id) {
+      Some(block) => self.markers.select(block.marker_id(), None),
+      None => self.markers.unselect(),
+    }
+  }
+
+  /// Select an entry and the block containing it, nothing gets selected
+  /// for the entries located before the first marker
+  pub fn select_entry(&mut self, entry_id: EntryId, region: Option<Range<usize>>) {
+    let selection = self
+      .block_at(entry_id)
+      .map(|block| MarkerSelection::new(block.marker_id(), entry_id, region));
+    crate::dbg!("Selecting entry #{} -> {:?}", entry_id, selection);
+    self.markers.set_selection(selection);
+  }
+
+  /// Retrieve the displayable lines.
+  ///
+  /// Only the lines that are still in sync with their entries are returned,
+  /// see [`Self::recover`]. They are borrowed, except the dimmed and the selected ones
+  pub fn display(&self) -> Vec<DisplayedEntry<'_>> {
+    self.display_range(0..self.cursor)
+  }
+
+  /// Retrieve the displayable lines of the entries in `range`, see [`Self::display`]
+  pub fn display_range(&self, range: Range<usize>) -> Vec<DisplayedEntry<'_>> {
+    debug_assert!(
+      self.is_consistent(),
+      "build output out of sync, call recover() first"
+    );
+    let range = range.start.min(self.cursor)..range.end.min(self.cursor);
+    let entries = self.prepared[range.clone()]
+      .iter()
+      .map(DisplayedEntry::from);
+    restyle(entries.collect(), range.start, &self.markers, |entry_id| {
+      self
+        .entries
+        .get(entry_id.0)
+        .map(|entry| entry.message().as_str())
+    })
+  }
+
   /// Retrieve the stored entries
   pub fn entries(&self) -> &Vec<BuildEntry> {
     &self.entries
@@ -466,6 +1261,26 @@ impl<'a> BuildOutput<'a> {
     &self.notes
   }
 
+  /// Retrieve the warnings of the build scripts, see [`BuildScript`]
+  pub fn script_warnings(&self) -> &Vec<usize> {
+    &self.script_warnings
+  }
+
+  /// Retrieve the crates that failed to build, from cargo's `could not compile` trailers
+  pub fn failed_crates(&self) -> Vec<CrateFailure> {
+    self
+      .failures
+      .iter()
+      .filter_map(|entry_id| self.entries[*entry_id].tag(BuildTagKind::Trailer))
+      .filter_map(|tag| tag.get_failure().cloned())
+      .collect::<Vec<_>>()
+  }
+
+  /// Retrieve the files produced by the build, from cargo's `compiler-artifact` messages
+  pub fn artifacts(&self) -> &Vec<Artifact> {
+    &self.artifacts
+  }
+
   /// Retrieve the preparation cursor.
   /// This value corresponds to the number of [`BuildEntry`] we
   /// already processed.
@@ -474,25 +1289,79 @@ impl<'a> BuildOutput<'a> {
     self.cursor
   }
 
+  /// Find the first entry of a block containing `query`
   pub fn search<Q: AsRef<str>>(&self, query: Q) -> Option<(MarkedBlock, MarkerSelection)> {
-    self
-      .entries
-      .iter()
-      .enumerate()
-      .find_map(|(entry_id, entry)| {
-        if let Some(pos) = entry.message().find(query.as_ref()) {
-          let block = self.block_at(entry_id).unwrap();
-          let marker_id = block.marker_id();
-          return Some((
-            block,
-            MarkerSelection::new(marker_id, entry_id, Some(pos..pos + query.as_ref().len())),
-          ));
-        }
-        None
+    self.search_from(0, query, Direction::Forward)
+  }
+
+  /// Find the entry of a block containing `query`, from the entry `start` included in
+  /// `direction`, wrapping around. Backward, the last match of the entry gets selected
+  pub fn search_from<Q: AsRef<str>>(
+    &self,
+    start: usize,
+    query: Q,
+    direction: Direction,
+  ) -> Option<(MarkedBlock<'_>, MarkerSelection)> {
+    let query = query.as_ref();
+    direction
+      .order(self.entries.len(), start)
+      .find_map(|entry_id| {
+        let message = self.entries[entry_id].message();
+        let pos = match direction {
+          Direction::Forward => message.find(query),
+          Direction::Backward => message.rfind(query),
+        }?;
+        let entry_id = EntryId(entry_id);
+        let block = self.block_at(entry_id)?;
+        let marker_id = block.marker_id();
+        Some((
+          block,
+          MarkerSelection::new(marker_id, entry_id, Some(pos..pos + query.len())),
+        ))
       })
   }
 }
 
+impl BuildOutput<'static> {
+  /// Produce a [`BuildSnapshot`] of the prepared entries.
+  ///
+  /// Only the entries prepared or re-tagged since the previous call get copied,
+  /// the other segments are shared with the previous snapshot.
+  pub fn snapshot(&mut self) -> BuildSnapshot {
+    self.recover();
+    if self.segments.len() >= Self::MAX_SNAPSHOT_SEGMENTS {
+      self.segments_valid = 0;
+    }
+    let valid = self.segments_valid;
+    self.segments.retain(|seg| seg.range().end <= valid);
+    let start = self.segments.last().map(|seg| seg.range().end).unwrap_or(0);
+    if start < self.cursor {
+      self.segments.push(Arc::new(SnapshotSegment::new(
+        start,
+        self.entries[start..self.cursor].to_vec(),
+        self.prepared[start..self.cursor].to_vec(),
+      )));
+    }
+    self.segments_valid = self.cursor;
+    BuildSnapshot::new(
+      self.segments.clone(),
+      self.entries.len(),
+      self.markers.clone(),
+      self.rule.clone(),
+    )
+    .with_counts(self.num_errors(), self.num_warnings(), self.notes.len())
+    .with_failed_crates(self.failed_crates())
+    .with_num_script_warnings(self.script_warnings.len())
+    .with_artifacts(self.artifacts.clone())
+    .with_queue_latency(self.queue_latency)
+    .with_detected(std::mem::take(&mut self.detected))
+    .with_repeats(self.repeats.clone())
+    .with_duplicates(self.duplicates.clone())
+    .with_target_counts(self.target_counts())
+    .with_slow_markers(self.slow_markers.clone())
+  }
+}
+
 impl<'a, T: Into<BuildEntry>, I: IntoIterator<Item = T>> From<I> for BuildOutput<'a> {
   fn from(value: I) -> Self {
     let mut ret = BuildOutput::default();
@@ -508,11 +1377,26 @@ impl<'a, T: Into<BuildEntry>, I: IntoIterator<Item = T>> From<I> for BuildOutput
 
 #[cfg(test)]
 mod tests {
-  use std::{ops::Range, sync::mpsc::channel};
+  use std::{
+    ops::Range,
+    path::PathBuf,
+    sync::{mpsc::channel, Arc},
+    thread::sleep,
+    time::{Duration, Instant},
+  };
+
+  use ratatui::{
+    backend::TestBackend,
+    style::{Color, Modifier, Style, Stylize},
+    widgets::ScrollbarState,
+    Terminal,
+  };
+  use regex::Regex;
 
   use crate::{
-    BuildEntry, BuildTag, BuildTagKind, CapturedMarker, MarkedBlock, MarkerRef, MarkerSelection,
-    Origin,
+    AppOptions, BuildEntry, BuildEvent, BuildSnapshot, BuildTag, BuildTagKind, CapturedMarker,
+    CompileTarget, DeclaredMarker, Direction, DuplicateBlock, EntryId, Fold, FoldMap, Location,
+    LogView, MarkedBlock, MarkerId, MarkerRef, MarkerSelection, Markers, Origin, REGION_STYLE,
   };
 
   use super::BuildOutput;
@@ -545,35 +1429,914 @@ mod tests {
   }
 
   #[test]
-  fn block_range_at() {
-    let sample_output = r#"warning: field `batch_id` is never read
-    blasdf
-    asdf asdf asdf
-    adsf s
-    error: test error
-    blasdf asdfl alsdf
-    asdfasdf"#;
-    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+  fn prepare_margin_markers() {
+    let sample_output = "warning: unused variable\n  --> src/main.rs:2:7";
+    let mut build = BuildOutput::from(sample_output.split('\n'));
     build.prepare();
-    assert_eq!(build.block_range_at(1), Some(Range { start: 0, end: 4 }));
-    assert_eq!(build.block_range_at(5), Some(Range { start: 4, end: 7 }));
+    let lines = build.display();
+    assert_eq!(
+      lines[0]
+        .line()
+        .spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect::<Vec<_>>(),
+      vec!["warning:", " ", " unused variable"]
+    );
+    assert_eq!(
+      lines[0].line().spans[0].style,
+      build.entries()[0].first_marker().unwrap().declared().style
+    );
+    assert_eq!(build.entries()[0].message(), "warning: unused variable");
   }
 
   #[test]
-  fn block_at() {
-    let sample_output = r#"warning: field `batch_id` is never read
-    blasdf
-    asdf asdf asdf
-    adsf s
-    error: test error
-    blasdf asdfl alsdf
-    asdfasdf"#;
-    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+  fn prepare_inline_markers() {
+    let sample_output = "warning: unused variable\n  --> src/main.rs:2:7";
+    let mut build = BuildOutput::from(sample_output.split('\n')).with_inline_markers(true);
     build.prepare();
+    let lines = build.display();
+    let style = build.entries()[0].first_marker().unwrap().declared().style;
     assert_eq!(
-      build.block_at(1),
+      lines[0]
+        .line()
+        .spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect::<Vec<_>>(),
+      vec!["W    ", " ", "", "warning:", " unused variable"]
+    );
+    assert_eq!(lines[0].line().spans[0].style, style);
+    assert_eq!(lines[0].line().spans[3].style, style);
+    assert_eq!(
+      lines[1]
+        .line()
+        .spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect::<Vec<_>>(),
+      vec!["     ", " ", "  --> src/main.rs:2:7"]
+    );
+    // the original message round-trips
+    assert_eq!(
+      lines[0].line().spans[2..]
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect::<String>(),
+      *build.entries()[0].message()
+    );
+    assert_eq!(build.entries()[0].message(), "warning: unused variable");
+  }
+
+  /// Draw the log of a prepared build, without the borders
+  fn draw(build: &BuildOutput, width: u16) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(width, 5)).unwrap();
+    terminal
+      .draw(|frame| {
+        let view = LogView::default().with_content(build.display());
+        frame.render_stateful_widget(view, frame.area(), &mut ScrollbarState::default())
+      })
+      .unwrap();
+    let buf = terminal.backend().buffer();
+    (1..4)
+      .map(|y| {
+        (1..width - 1)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>()
+          .trim_end()
+          .to_string()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn compact_gutter() {
+    let sample_output =
+      "error[E0308]: mismatched types\n  --> src/main.rs:2:7\nwarning: unused variable";
+    let options = AppOptions::default();
+    let mut build = BuildOutput::from(sample_output.split('\n'));
+    build.set_inline_markers(options.compact_gutter(80));
+    build.prepare();
+    assert_eq!(
+      draw(&build, 80),
+      vec![
+        "E0308 error[E0308]: mismatched types",
+        "        --> src/main.rs:2:7",
+        "W     warning: unused variable",
+      ]
+    );
+    // switching the mode renders the prepared lines again
+    build.set_inline_markers(options.compact_gutter(200));
+    assert_eq!(build.cursor(), 0);
+    build.prepare();
+    assert_eq!(
+      draw(&build, 200),
+      vec![
+        "error[E0308]:  mismatched types",
+        "   --> src/main.rs:2:7",
+        "warning:  unused variable",
+      ]
+    );
+    assert_eq!(build.errors(), &vec![0]);
+    assert_eq!(build.warnings(), &vec![2]);
+  }
+
+  const COLORED_OUTPUT: &str = "\x1b[0m\x1b[1m\x1b[33mwarning\x1b[0m\x1b[0m\x1b[1m: unused variable\x1b[0m\n\x1b[0m  \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m--> \x1b[0m\x1b[0msrc/main.rs:2:7\x1b[0m";
+
+  fn display_text(build: &BuildOutput) -> Vec<String> {
+    build
+      .display()
+      .iter()
+      .map(|line| {
+        line
+          .line()
+          .spans
+          .iter()
+          .map(|span| span.content.as_ref())
+          .collect::<String>()
+      })
+      .collect::<Vec<_>>()
+  }
+
+  /// The display column of `pattern` in `line`
+  fn column_of(line: &str, pattern: &str) -> usize {
+    use unicode_width::UnicodeWidthStr;
+
+    line[..line.find(pattern).unwrap()].width()
+  }
+
+  #[test]
+  fn prepare_expands_tabs() {
+    let log = "warning: unused variable: `x`\n --> src/main.rs:4:9\n  |\n4 |\tlet x = 1;   \n  |\t    ^ help: if this is intentional, prefix it with an underscore: `_x`\n  |";
+    let mut columns = vec![];
+    for tab_width in [4, 8] {
+      let mut build = BuildOutput::from(log.lines()).with_tab_width(tab_width);
+      build.prepare();
+      let lines = display_text(&build);
+      assert!(lines.iter().all(|line| !line.contains('\t')), "{:?}", lines);
+      assert!(lines[3].ends_with("let x = 1;"), "{:?}", lines[3]);
+      // the caret stays under the variable
+      let column = column_of(&lines[3], "x = 1");
+      assert_eq!(column, column_of(&lines[4], "^ help"), "{:?}", lines);
+      columns.push(column);
+
+      // the raw message keeps its tabs for the copies and the searches
+      assert_eq!(build.entries()[3].message(), "4 |\tlet x = 1;   ");
+      let block = build.block_at(EntryId(3)).unwrap();
+      assert!(block.content().contains("4 |\tlet x = 1;"));
+      let (_block, selection) = build.search("x = 1").unwrap();
+      assert_eq!(selection.region, Some(8..13));
+    }
+    assert_eq!(columns[1] - columns[0], 4);
+  }
+
+  #[test]
+  fn prepare_caps_long_lines() {
+    // a serialized JSON blob of about 4 MiB printed by a build script
+    let item = r#"{"key":"value","n":42},"#;
+    let mut blob = format!("[{}", item.repeat(3 * 1024 * 1024 / item.len()));
+    blob.push_str(r#""needle":true,"#);
+    blob.push_str(&item.repeat(1024 * 1024 / item.len()));
+    let mut build = BuildOutput::from([
+      "warning: unused variable: `x`",
+      blob.as_str(),
+      " --> src/main.rs:2:9",
+    ]);
+    let start = Instant::now();
+    while build.prepare() {}
+    let elapsed = start.elapsed();
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+    let lines = display_text(&build);
+    let suffix = " … [+4.0 MiB, press x to expand]";
+    assert!(lines[1].ends_with(suffix), "{}", &lines[1][4000..]);
+    let displayed = lines[1].len() - suffix.len();
+    assert!(
+      displayed <= BuildOutput::DEFAULT_MAX_LINE_LENGTH + 5,
+      "{}",
+      displayed
+    );
+    let prepared = &build.display()[1];
+    assert!(prepared.is_truncated());
+    assert_eq!(
+      prepared.raw_layout().truncated,
+      Some(BuildOutput::DEFAULT_MAX_LINE_LENGTH)
+    );
+    assert!(!build.display()[0].is_truncated());
+
+    // the raw message stays whole for the searches and the copies
+    assert_eq!(build.entries()[1].message(), &blob);
+    let (_block, selection) = build.search("needle").unwrap();
+    let at = blob.find("needle").unwrap();
+    assert_eq!(selection.region, Some(at..at + 6));
+    // past the cut, nothing of the displayed line gets highlighted
+    build.select_entry(selection.entry_id, selection.region);
+    let (text, region) = highlighted(&build, 1);
+    assert!(region.is_none(), "{:?}", region);
+    assert!(text.ends_with(suffix));
+
+    // a longer cap shows more of the line
+    let mut build = BuildOutput::from([blob.as_str()]).with_max_line_length(8192);
+    while build.prepare() {}
+    assert_eq!(build.display()[0].raw_layout().truncated, Some(8192));
+  }
+
+  #[test]
+  fn duplicate_targets() {
+    let mut build =
+      BuildOutput::from(include_str!("../../../tests/fixtures/rust/rust-targets.log").lines());
+    while build.prepare() {}
+    // the trailers of the targets are summaries
+    assert_eq!(build.warnings(), &vec![1, 10, 18]);
+    assert_eq!(build.num_warnings(), 2);
+    let lib = CompileTarget::new("demo", "lib");
+    let test = CompileTarget::new("demo", "lib test");
+    assert_eq!(
+      build.duplicates(),
+      &vec![DuplicateBlock {
+        range: 10..18,
+        kind: BuildTagKind::Warning,
+        target: test.clone(),
+        first: lib.clone(),
+      }]
+    );
+    assert_eq!(
+      build
+        .target_counts()
+        .iter()
+        .map(|counts| (counts.target.clone(), counts.warnings, counts.duplicates))
+        .collect::<Vec<_>>(),
+      vec![(lib, 1, 0), (test, 2, 1)]
+    );
+    let snapshot = build.snapshot();
+    assert_eq!(snapshot.num_warnings(), 2);
+    assert_eq!(snapshot.target_counts().len(), 2);
+
+    // the repeated block shows as its first line
+    let folds = FoldMap::new(snapshot.duplicates());
+    let lines = folds
+      .apply(snapshot.display(snapshot.markers()))
+      .iter()
+      .map(|line| line.line().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 29 - 7);
+    assert!(
+      lines[10].ends_with("unused variable: `x` (also in lib test target)"),
+      "{:#?}",
+      lines
+    );
+    assert!(lines[11].ends_with("function `helper` is never used"));
+
+    // prepared again, e.g. with another rule
+    build.rewind(0);
+    while build.prepare() {}
+    assert_eq!(build.duplicates().len(), 1);
+    assert_eq!(build.num_warnings(), 2);
+  }
+
+  /// The displayed text of the selected entry, and its bytes styled as the selected region
+  fn highlighted(build: &BuildOutput, entry_id: usize) -> (String, Option<Range<usize>>) {
+    let lines = build.display_range(entry_id..entry_id + 1);
+    let line = lines[0].line();
+    let (mut at, mut region) = (0, None::<Range<usize>>);
+    for span in &line.spans {
+      let range = at..at + span.content.len();
+      at = range.end;
+      if span.style.bg == REGION_STYLE.bg {
+        region = Some(region.map_or(range.clone(), |region| region.start..range.end));
+      }
+    }
+    (line.to_string(), region)
+  }
+
+  #[test]
+  fn search_region() {
+    let mut build = BuildOutput::from([
+      "   Compiling demo v0.1.0",
+      "warning: unused variable: `x`",
+      " --> src/main.rs:2:9",
+      "  warning: indented\tand tabbed",
+    ]);
+    while build.prepare() {}
+
+    // inside the captured marker, displayed in the margin
+    let (_block, selection) = build.search("warning").unwrap();
+    assert_eq!(selection.region, Some(0..7));
+    build.select_entry(selection.entry_id, selection.region);
+    let (text, region) = highlighted(&build, 1);
+    assert_eq!(&text[region.clone().unwrap()], "warning");
+    assert_eq!(region, Some(0..7));
+    // the styled margin takes the background of the selection, the region stands out
+    for span in &build.display_range(1..2)[0].line().spans {
+      match span.style.bg == REGION_STYLE.bg {
+        true => assert!(span.style.add_modifier.contains(Modifier::BOLD)),
+        false => assert_eq!(span.style.bg, Some(Color::LightBlue), "{:?}", span),
+      }
+    }
+
+    // right after the stripped marker
+    let (_block, selection) = build.search("unused").unwrap();
+    assert_eq!(selection.region, Some(9..15));
+    build.select_entry(selection.entry_id, selection.region);
+    let (text, region) = highlighted(&build, 1);
+    assert!(text.starts_with("warning:  unused"), "{:?}", text);
+    assert_eq!(region, Some(10..16));
+    assert_eq!(&text[10..16], "unused");
+
+    // across the margin and the message
+    build.select_entry(EntryId(1), Some(5..15));
+    let (text, region) = highlighted(&build, 1);
+    assert_eq!(&text[region.unwrap()], "ng:  unused");
+
+    // the stripped indentation is clamped, the tabs are expanded
+    build.select_entry(EntryId(3), Some(0..2));
+    assert_eq!(highlighted(&build, 3).1, None);
+    let (_block, selection) = build.search("tabbed").unwrap();
+    build.select_entry(selection.entry_id, selection.region);
+    let (text, region) = highlighted(&build, 3);
+    assert!(!text.contains('\t'));
+    assert_eq!(&text[region.unwrap()], "tabbed");
+  }
+
+  #[test]
+  fn prepare_strips_escapes() {
+    let mut build = BuildOutput::from(COLORED_OUTPUT.split('\n'));
+    build.prepare();
+    let lines = display_text(&build);
+    assert!(
+      lines.iter().all(|line| !line.contains('\x1b')),
+      "{:?}",
+      lines
+    );
+    assert_eq!(lines[0], "warning:  unused variable");
+    assert_eq!(lines[1].trim(), "--> src/main.rs:2:7");
+    assert_eq!(build.warnings(), &vec![0]);
+    // the location is still detected
+    assert!(build.entries()[0].locations().next().is_some());
+  }
+
+  #[test]
+  fn prepare_ansi_colors() {
+    let mut build = BuildOutput::from(COLORED_OUTPUT.split('\n')).with_ansi_colors(true);
+    build.prepare();
+    let lines = display_text(&build);
+    assert!(
+      lines.iter().all(|line| !line.contains('\x1b')),
+      "{:?}",
+      lines
+    );
+    let display = build.display();
+    let spans = &display[0].line().spans;
+    // cargo's style is kept instead of the marker's
+    assert_eq!(spans[0].content, "warning:");
+    assert_eq!(spans[0].style, Style::default().bold().yellow());
+    assert_eq!(spans[2].style, Style::default().bold());
+    assert_eq!(build.warnings(), &vec![0]);
+  }
+
+  #[test]
+  fn pull_queue_latency() {
+    let (tx, rx) = channel();
+    let captured_at = Instant::now();
+    tx.send(BuildEntry::with_timestamp(
+      "warning: unused variable",
+      Origin::Stderr,
+      captured_at,
+    ))
+    .unwrap();
+    // the renderer is late
+    sleep(Duration::from_millis(100));
+    let mut build = BuildOutput::default();
+    build.pull(&rx);
+    assert_eq!(*build.entries()[0].created_at(), captured_at);
+    assert!(build.queue_latency() >= Duration::from_millis(100));
+    build.pull(&rx);
+    assert_eq!(build.queue_latency(), Duration::ZERO);
+  }
+
+  #[test]
+  fn pull_up_to() {
+    let (tx, rx) = channel();
+    for i in 0..5 {
+      tx.send(BuildEntry::new(format!("line {}", i), Origin::Stdout))
+        .unwrap();
+    }
+    let mut build = BuildOutput::default();
+    build.pull_up_to(&rx, 2);
+    assert_eq!(build.num_entries(), 2);
+    // the next ones wait in the queue, in order
+    build.pull_up_to(&rx, 0);
+    assert_eq!(build.num_entries(), 2);
+    build.pull(&rx);
+    assert_eq!(build.num_entries(), 5);
+    assert_eq!(build.entries()[2].message(), "line 2");
+  }
+
+  #[test]
+  fn set_rule() {
+    let sample_output = "warning: unused variable\nlint: custom check failed";
+    let mut build = BuildOutput::from(sample_output.split('\n'));
+    build.prepare();
+    assert_eq!(build.warnings(), &vec![0]);
+    assert!(build.errors().is_empty());
+
+    let mut rule = build.rule().clone();
+    rule.markers.push(DeclaredMarker::new(
+      BuildTagKind::Error,
+      Regex::new(r"^lint:").unwrap(),
+      Style::default(),
+    ));
+    build.set_rule(rule);
+    assert_eq!(build.cursor(), 0);
+    assert!(build.warnings().is_empty());
+    build.prepare();
+    assert_eq!(build.warnings(), &vec![0]);
+    assert_eq!(build.errors(), &vec![1]);
+    assert_eq!(build.markers().len(), 2);
+  }
+
+  #[test]
+  fn slow_marker() {
+    let slow = DeclaredMarker::new(
+      BuildTagKind::Error,
+      Regex::new(r"(\w+\s?)+\d").unwrap(),
+      Style::default(),
+    );
+    let mut rule = BuildOutput::default().rule().clone();
+    rule.markers = vec![
+      DeclaredMarker::new(
+        BuildTagKind::Warning,
+        Regex::new(r"^warning:").unwrap(),
+        Style::default(),
+      ),
+      slow.clone(),
+    ];
+    // far over the budget for the slow marker, far under it for the cheap one,
+    // see marker::tests::prepare_budget
+    let mut build = BuildOutput::default().with_marker_budget(Some(Duration::from_millis(50)));
+    build.set_rule(rule.clone());
+    let line = "ab ".repeat(100_000);
+    for _ in 0..100 {
+      build.push(BuildEntry::new(line.clone(), Origin::Stdout));
+    }
+    build.prepare();
+    assert_eq!(build.slow_markers().len(), 1);
+    assert_eq!(build.slow_markers()[0].marker, slow);
+    assert_eq!(build.snapshot().slow_markers(), build.slow_markers());
+    // disabled for the rest of the run, even once the rule is set again
+    build.set_rule(rule);
+    build.push(BuildEntry::new("warning: unused 1", Origin::Stdout));
+    build.prepare();
+    assert!(build.errors().is_empty());
+    assert_eq!(build.warnings(), &vec![100]);
+    assert_eq!(build.slow_markers().len(), 1);
+  }
+
+  #[test]
+  fn most_severe_marker() {
+    // matches both the note and the warning regexes
+    let sample_output = "note: see warning: unused variable\nerror: boom\nwarning: unused import";
+    let mut build = BuildOutput::from(sample_output.split('\n'));
+    build.prepare();
+    assert_eq!(build.warnings(), &vec![0, 2]);
+    assert_eq!(build.errors(), &vec![1]);
+    assert!(build.notes().is_empty());
+    assert_eq!(build.snapshot().num_notes(), 0);
+    // the other match is still tagged
+    let entry = &build.entries()[0];
+    assert_eq!(
+      entry.primary_marker().unwrap().kind(),
+      BuildTagKind::Warning
+    );
+    assert!(entry.tag(BuildTagKind::Note).is_some());
+    // navigation visits each entry once
+    let mut markers = build.markers().clone();
+    let mut visited = vec![markers.select_first().unwrap().entry_id];
+    while let Some(selection) = markers.select_next() {
+      if visited.contains(&selection.entry_id) {
+        break;
+      }
+      visited.push(selection.entry_id);
+    }
+    assert_eq!(visited, vec![EntryId(0), EntryId(1), EntryId(2)]);
+    assert_eq!(markers.count(BuildTagKind::Note), 0);
+  }
+
+  #[test]
+  fn summary_lines() {
+    let sample_output = [
+      "error[E0308]: mismatched types",
+      "  --> src/main.rs:4:18",
+      "warning: unused variable: `x`",
+      "error: aborting due to 1 previous error; 1 warning emitted",
+      "error: could not compile `demo` (bin \"demo\") due to 1 previous error",
+    ];
+    let mut build = BuildOutput::from(sample_output);
+    build.prepare();
+    assert_eq!(build.errors(), &vec![0]);
+    assert_eq!(build.warnings(), &vec![2]);
+    // the summaries replace their error markers, `error:` stays in the margin
+    for entry in &build.entries()[3..] {
+      let marker = entry.primary_marker().unwrap();
+      assert_eq!(marker.kind(), BuildTagKind::Summary);
+      assert_eq!(marker.captured().unwrap().text, "error:");
+      assert!(entry.tag(BuildTagKind::Error).is_none());
+      assert_eq!(entry.severity(), None);
+    }
+    assert_eq!(build.failed_crates().len(), 1);
+    let mut markers = build.markers().clone();
+    assert_eq!(markers.count(BuildTagKind::Summary), 0);
+    assert_eq!(markers.select_last().unwrap().entry_id, EntryId(2));
+    let snapshot = build.snapshot();
+    assert_eq!((snapshot.num_errors(), snapshot.num_warnings()), (1, 1));
+  }
+
+  #[test]
+  fn entries_before() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let entry = |msg, ms| BuildEntry::with_timestamp(msg, Origin::Stderr, at(ms));
+    let mut build = BuildOutput::default();
+    build.extend([entry("warning: a", 0), entry("  --> src/a.rs:1:1", 100)]);
+    build.prepare();
+    build.snapshot();
+    // a second segment
+    build.extend([entry("error: b", 200), entry("error: c", 300)]);
+    build.prepare();
+    let snapshot = build.snapshot();
+    assert_eq!(snapshot.segments().len(), 2);
+    assert_eq!(snapshot.started_at(), Some(start));
+    assert_eq!(snapshot.entries_before(start - Duration::from_millis(1)), 0);
+    assert_eq!(snapshot.entries_before(at(0)), 1);
+    assert_eq!(snapshot.entries_before(at(150)), 2);
+    assert_eq!(snapshot.entries_before(at(200)), 3);
+    assert_eq!(snapshot.entries_before(at(1000)), 4);
+    assert_eq!(BuildSnapshot::default().entries_before(at(1000)), 0);
+  }
+
+  #[test]
+  fn macro_backtrace_folds() {
+    let mut build = BuildOutput::default();
+    build.extend(include_str!("../../../tests/fixtures/rust/rust-macro-backtrace.log").lines());
+    // the section grows along with its block, a few entries per prepare
+    while build.prepare() {
+      build.snapshot();
+    }
+    let snapshot = build.snapshot();
+    assert_eq!(snapshot.folds(), vec![Fold::new(8..25, "macro backtrace")]);
+    let block = snapshot.block_at(EntryId(8)).unwrap();
+    assert_eq!(block.range(), 1..26);
+    assert!(snapshot
+      .entry(EntryId(24))
+      .unwrap()
+      .tag(BuildTagKind::Note)
+      .is_some());
+
+    let folds = FoldMap::new(snapshot.folds());
+    let lines = folds.apply(snapshot.display(&Markers::default()));
+    assert_eq!(lines.len(), snapshot.len() - 16);
+    assert_eq!(
+      lines[8].line().to_string().trim(),
+      "… macro backtrace (17 lines), press x to expand"
+    );
+    // the warning follows the placeholder
+    assert_eq!(folds.row(26), 10);
+    assert!(lines[10].line().to_string().contains("unused variable"));
+  }
+
+  #[test]
+  fn repeats() {
+    let deprecated = "warning: use of deprecated function `build::helper`";
+    let mut build = BuildOutput::default();
+    build.extend(["   Compiling demo v0.1.0"]);
+    // more than a batch of repetitions
+    build.extend([deprecated; 12]);
+    build.extend(["", "", "note: done"]);
+    while build.prepare() {}
+    assert_eq!(build.repeats().as_slice(), &[Range { start: 1, end: 13 }]);
+    // the run grows across prepares
+    build.extend(["note: done", "note: done"]);
+    build.prepare();
+    assert_eq!(build.repeats(), &vec![1..13, 15..18]);
+
+    let snapshot = build.snapshot();
+    let mut markers = snapshot.markers().clone();
+    // the selection is kept on the run
+    markers.select(MarkerId(5), None);
+    let folds = FoldMap::new(snapshot.repeats());
+    let lines = folds.apply(snapshot.display(&markers));
+    assert_eq!(lines.len(), 5);
+    assert!(lines[1].line().to_string().ends_with("(×12)"));
+    assert_eq!(lines[1].line().style, Style::default().on_light_blue());
+    // the blank lines are not grouped
+    assert_eq!(lines[3].line().to_string().trim(), "");
+    assert!(lines[4].line().to_string().ends_with("done (×3)"));
+    // every entry is kept
+    assert_eq!(snapshot.len(), 18);
+    assert_eq!(snapshot.num_warnings(), 12);
+    assert_eq!(folds.row(6), 1);
+  }
+
+  #[test]
+  fn jump_targets() {
+    let mut build = BuildOutput::default();
+    build.extend(include_str!("../../../tests/fixtures/rust/rust-macro-backtrace.log").lines());
+    while build.prepare() {}
+    // the `could not compile` trailer is not a block of its own
+    let errors = build.jump_targets(BuildTagKind::Error);
+    assert_eq!(
+      errors,
+      vec![(
+        MarkerId(0),
+        BuildTagKind::Error,
+        "error[E0308]: mismatched types".to_string(),
+        Some(Location::new("src/main.rs", Some(10), Some(5)))
+      ),]
+    );
+    let warnings = build.jump_targets(BuildTagKind::Warning);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+      warnings[0].3,
+      Some(Location::new("src/main.rs", Some(4), Some(9)))
+    );
+    assert_eq!(build.snapshot().jump_targets(BuildTagKind::Error), errors);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn artifacts() {
+    let (tx, rx) = channel::<BuildEntry>();
+    let mut build = BuildOutput::default();
+    let mut lines = include_str!("../../../tests/fixtures/rust/rust-artifacts.log").lines();
+    build.extend(lines.by_ref().take(4));
+    for line in lines {
+      tx.send(BuildEntry::new(line, Origin::Stdout)).unwrap();
+    }
+    build.pull(&rx);
+    let artifacts = build
+      .artifacts()
+      .iter()
+      .map(|artifact| {
+        (
+          artifact.name.as_str(),
+          artifact.kind.as_str(),
+          artifact.fresh,
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      artifacts,
+      vec![
+        ("proc_macro2", "lib", true),
+        ("demo_derive", "proc-macro", false),
+        ("demo", "lib", false),
+        ("demo", "bin", false),
+      ]
+    );
+    assert_eq!(
+      build.artifacts()[3].path.display().to_string(),
+      "/home/user/demo/target/debug/demo"
+    );
+    while build.prepare() {}
+    assert_eq!(build.snapshot().artifacts(), build.artifacts());
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn diagnostics() {
+    use crate::{Applicability, Suggestion};
+
+    let mut build = BuildOutput::from([
+      "   Compiling demo v0.1.0 (/tmp/demo)",
+      r#"{"reason":"compiler-message","message":{"rendered":"warning: unused variable: `x`\n --> src/main.rs:2:7\n  |\n2 |   let x = 1;\n  |       ^ help: prefix it with an underscore: `_x`\n\n","children":[{"children":[],"message":"prefix it","spans":[{"byte_start":18,"byte_end":19,"file_name":"src/main.rs","suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","text":[{"text":"  let x = 1;","highlight_start":7,"highlight_end":8}]}]}],"spans":[]}}"#,
+      r#"{"reason":"build-finished","success":true}"#,
+    ]);
+    while build.prepare() {}
+    // the rendered lines replace the JSON message
+    assert_eq!(build.entries().len(), 7);
+    assert_eq!(
+      build.entries()[1].message(),
+      "warning: unused variable: `x`"
+    );
+    assert_eq!(
+      build.entries()[5].message(),
+      "  |       ^ help: prefix it with an underscore: `_x`"
+    );
+    let block = build.block_at(EntryId(1)).unwrap();
+    assert_eq!(
+      block.suggestions(),
+      vec![Suggestion::new("src/main.rs", 18, "x", "_x")
+        .with_applicability(Applicability::MachineApplicable)]
+    );
+    assert_eq!(build.entries()[2].suggestions(), &vec![]);
+  }
+
+  #[test]
+  fn snapshot() {
+    let mut build = BuildOutput::from(["warning: unused variable", "note: first"]);
+    build.prepare();
+    let first = build.snapshot();
+    assert_eq!(first.len(), 2);
+    assert_eq!(first.num_warnings(), 1);
+    assert_eq!(first.detected()[0], (EntryId(0), BuildTagKind::Warning));
+
+    // the location belongs to the block of the first prepare
+    build.extend(["  --> src/main.rs:2:7"]);
+    build.prepare();
+    let second = build.snapshot();
+    assert_eq!(second.len(), 3);
+    // the markers are only reported by the snapshot following their detection
+    assert!(second.detected().is_empty());
+    assert!(second
+      .entry(EntryId(1))
+      .unwrap()
+      .locations()
+      .next()
+      .is_some());
+    // the first snapshot is left untouched
+    assert_eq!(first.len(), 2);
+    assert!(first
+      .entry(EntryId(1))
+      .unwrap()
+      .locations()
+      .next()
+      .is_none());
+
+    build.extend(["warning: unused import"]);
+    build.prepare();
+    let third = build.snapshot();
+    assert_eq!(third.segments().len(), 2);
+    assert!(Arc::ptr_eq(&second.segments()[0], &third.segments()[0]));
+    assert_eq!(third.num_warnings(), 2);
+    assert_eq!(third.detected(), &[(EntryId(3), BuildTagKind::Warning)]);
+    assert_eq!(third.display(build.markers()).len(), build.display().len());
+    // the kinds of the cached lines follow the location found by the second prepare
+    assert!(build.display()[1].kinds().contains(BuildTagKind::Location));
+    assert!(third.display(build.markers())[1]
+      .kinds()
+      .contains(BuildTagKind::Location));
+  }
+
+  #[test]
+  fn inline_locations() {
+    let location = |build: &BuildOutput, i: usize| build.entries()[i].locations().next().cloned();
+    // cargo-style, single-line
+    let mut build = BuildOutput::from(["error: expected `;` --> src/main.rs:3:10", "  context"]);
+    build.prepare();
+    for i in 0..2 {
+      assert_eq!(
+        location(&build, i),
+        Some(Location::new("src/main.rs", Some(3), Some(10)))
+      );
+    }
+    // gcc-style
+    let mut build = BuildOutput::from([
+      "src/foo.c:12:5: error: expected ';' before '}' token",
+      "   12 |     return 0",
+      "include/bar.h:4: warning: unused macro",
+    ]);
+    build.prepare();
+    assert_eq!(
+      location(&build, 1),
+      Some(Location::new("src/foo.c", Some(12), Some(5)))
+    );
+    assert_eq!(
+      location(&build, 2),
+      Some(Location::new("include/bar.h", Some(4), None))
+    );
+    // mixed: the location of the marker line wins over the following `-->` ones
+    let mut build = BuildOutput::from([
+      "warning: unused variable: `x`",
+      "  --> src/main.rs:2:9",
+      "build/lib.c:7:1: error: undeclared identifier",
+      "error: mismatched types --> src/lib.rs:5:3",
+      "  --> src/other.rs:1:1",
+      "  not a location --> here",
+    ]);
+    build.prepare();
+    assert_eq!(
+      location(&build, 0),
+      Some(Location::new("src/main.rs", Some(2), Some(9)))
+    );
+    assert_eq!(
+      location(&build, 2),
+      Some(Location::new("build/lib.c", Some(7), Some(1)))
+    );
+    assert_eq!(
+      location(&build, 4),
+      Some(Location::new("src/lib.rs", Some(5), Some(3)))
+    );
+  }
+
+  #[test]
+  fn block_range_at() {
+    let sample_output = r#"warning: field `batch_id` is never read
+    blasdf
+    asdf asdf asdf
+    adsf s
+    error: test error
+    blasdf asdfl alsdf
+    asdfasdf"#;
+    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+    build.prepare();
+    assert_eq!(
+      build.block_range_at(EntryId(1)),
+      Some(Range { start: 0, end: 4 })
+    );
+    assert_eq!(
+      build.block_range_at(EntryId(5)),
+      Some(Range { start: 4, end: 7 })
+    );
+  }
+
+  #[test]
+  fn block_range_at_secondary() {
+    let sample_output = r#"error: test error
+    blasdf
+    note: attached to the error
+    asdf asdf
+    warning: test warning
+    asdfasdf"#;
+    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+    build.prepare();
+    assert_eq!(build.markers().len(), 3);
+    assert_eq!(
+      build.block_range_at(EntryId(2)),
+      Some(Range { start: 0, end: 4 })
+    );
+    assert_eq!(
+      build.block_range_at(EntryId(3)),
+      Some(Range { start: 0, end: 4 })
+    );
+    assert_eq!(
+      build.block_range_at(EntryId(5)),
+      Some(Range { start: 4, end: 6 })
+    );
+    assert_eq!(
+      build
+        .blocks()
+        .iter()
+        .map(|block| block.range())
+        .collect::<Vec<_>>(),
+      vec![0..4, 4..6]
+    );
+  }
+
+  #[test]
+  fn build_script() {
+    let mut build = BuildOutput::default();
+    build.extend(include_str!("../../../tests/fixtures/rust/rust-build-script.log").lines());
+    // the failure report spans several prepares
+    build.set_prepare_limit(Some(7));
+    while build.prepare() {}
+    // the warnings of the build script are counted apart
+    assert_eq!(build.script_warnings(), &vec![5, 6]);
+    // the trailer of the lib target is a summary
+    assert_eq!(build.warnings(), &vec![7, 39]);
+    assert_eq!(build.errors(), &vec![15]);
+    assert_eq!(build.notes(), &vec![13]);
+    let script = build.entries[5].tag(BuildTagKind::BuildScript).unwrap();
+    assert_eq!(script.get_label(), Some("openssl-sys"));
+    // the captured stdout and stderr stay in the failure's block
+    let block = build.block_at(EntryId(15)).unwrap();
+    assert_eq!(block.range(), 15..39);
+    for entry_id in 15..39 {
+      assert!(
+        build.entries[entry_id].has_tag(BuildTagKind::BuildScript),
+        "{}",
+        entry_id
+      );
+    }
+    assert_eq!(
+      build.entries[38].primary_marker(),
+      None,
+      "{}",
+      build.entries[38].message()
+    );
+    assert!(!build.entries[39].has_tag(BuildTagKind::BuildScript));
+    assert!(build.prepared[20]
+      .kinds()
+      .contains(BuildTagKind::BuildScript));
+    let snapshot = build.snapshot();
+    assert_eq!(snapshot.num_warnings(), 2);
+    assert_eq!(snapshot.num_script_warnings(), 2);
+  }
+
+  #[test]
+  fn block_at() {
+    let sample_output = r#"warning: field `batch_id` is never read
+    blasdf
+    asdf asdf asdf
+    adsf s
+    error: test error
+    blasdf asdfl alsdf
+    asdfasdf"#;
+    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+    build.prepare();
+    assert_eq!(
+      build.block_at(EntryId(1)),
       Some(MarkedBlock::new(
-        0,
+        MarkerId(0),
         MarkerRef::known(
           BuildTagKind::Warning,
           Some(CapturedMarker::new(0, "warning:"))
@@ -583,9 +2346,9 @@ mod tests {
       ))
     );
     assert_eq!(
-      build.block_at(5),
+      build.block_at(EntryId(5)),
       Some(MarkedBlock::new(
-        1,
+        MarkerId(1),
         MarkerRef::known(BuildTagKind::Error, Some(CapturedMarker::new(4, "error:"))),
         4..7,
         build.entries[4..7].iter().collect::<Vec<_>>(),
@@ -593,9 +2356,50 @@ mod tests {
     );
   }
 
+  #[test]
+  fn block_accessors() {
+    let sample_output = "error[E0308]: mismatched types\n  --> src/main.rs:4:5\n   |";
+    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+    build.prepare();
+    let block = build.block_at(EntryId(0)).unwrap();
+    assert_eq!(block.first_line(), Some("error[E0308]: mismatched types"));
+    assert_eq!(block.error_code(), Some("E0308"));
+    let location = block.primary_location().unwrap();
+    assert_eq!(location.path(), &PathBuf::from("src/main.rs"));
+    assert_eq!((location.line(), location.column()), (Some(4), Some(5)));
+    // no lint in an error
+    assert_eq!(block.allow_attribute(), "-A warnings");
+    let sample_output = [
+      "warning: function `helper` is never used",
+      "  --> src/parser.rs:12:4",
+      "  = note: `#[warn(dead_code)]` on by default",
+    ];
+    let mut build = BuildOutput::from(sample_output).with_noise_removed(false);
+    build.prepare();
+    let block = build.block_at(EntryId(0)).unwrap();
+    assert_eq!(block.allow_attribute(), "#[allow(dead_code)]");
+  }
+
+  #[test]
+  fn select_before_first_marker() {
+    let sample_output = "   Compiling demo v0.1.0\nerror: test error\nblasdf";
+    let mut build = BuildOutput::from(sample_output.split('\n')).with_noise_removed(false);
+    build.prepare();
+    assert_eq!(build.block_at(EntryId(0)), None);
+    build.select_block_from_entry(EntryId(0));
+    assert_eq!(build.markers().selection(), None);
+    build.select_entry(EntryId(2), None);
+    assert_eq!(
+      build.markers().selection(),
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(2), None))
+    );
+    build.select_entry(EntryId(0), None);
+    assert_eq!(build.markers().selection(), None);
+  }
+
   #[test]
   fn search() {
-    let content = include_str!("../../../samples/rust/rust-panic.log")
+    let content = include_str!("../../../tests/fixtures/rust/rust-panic.log")
       .split("\n")
       .collect::<Vec<_>>();
     let mut build = BuildOutput::default();
@@ -605,19 +2409,132 @@ mod tests {
       build.search("panic"),
       Some((
         MarkedBlock::new(
-          0,
+          MarkerId(0),
           MarkerRef::known(BuildTagKind::Error, Some(CapturedMarker::new(0, "error:"))),
-          1..7,
+          // the `could not compile` trailer stays in the block
+          1..9,
           build
             .entries
             .iter()
             .enumerate()
-            .filter(|(id, _entry)| *id >= 1 && *id < 7)
+            .filter(|(id, _entry)| *id >= 1 && *id < 9)
             .map(|(_id, entry)| entry)
             .collect::<Vec<_>>()
         ),
-        MarkerSelection::new(0, 6, Some(16..21))
+        MarkerSelection::new(MarkerId(0), EntryId(6), Some(16..21))
       ))
     );
   }
+
+  #[test]
+  fn search_from() {
+    let mut build = BuildOutput::from([
+      "warning: unused variable: `x`",
+      " --> src/a.rs:1:5",
+      "  = help: x is unused, x again",
+      "",
+      "error: cannot find `x`",
+      " --> src/b.rs:2:1",
+    ]);
+    build.prepare();
+    let found = |start, direction| {
+      build
+        .search_from(start, "x", direction)
+        .map(|(_block, selection)| (selection.entry_id.0, selection.region.unwrap().start))
+    };
+    assert_eq!(found(0, Direction::Forward), Some((0, 27)));
+    // from inside a block, its next line
+    assert_eq!(found(1, Direction::Forward), Some((2, 10)));
+    assert_eq!(found(1, Direction::Backward), Some((0, 27)));
+    // the last match of a line going backward
+    assert_eq!(found(3, Direction::Backward), Some((2, 23)));
+    // wrapped around
+    assert_eq!(found(5, Direction::Forward), Some((0, 27)));
+    assert_eq!(found(6, Direction::Forward), Some((0, 27)));
+    assert_eq!(found(usize::MAX, Direction::Backward), Some((4, 20)));
+    assert_eq!(build.search_from(3, "missing", Direction::Forward), None);
+    // the snapshot searches the same way
+    let snapshot = build.snapshot();
+    let (_block, selection) = snapshot.search_from(3, "x", Direction::Backward).unwrap();
+    assert_eq!(selection.entry_id, EntryId(2));
+    assert_eq!(selection.region, Some(23..24));
+  }
+
+  #[test]
+  fn push_after_prepare() {
+    let mut build = BuildOutput::from(["error: first", "some output"]);
+    build.prepare();
+    build.push(BuildEntry::new("error: second", Origin::Stdout));
+    // the new entry is not displayed until prepared
+    assert_eq!(build.display().len(), 2);
+    build.prepare();
+    assert_eq!(display_text(&build).len(), 3);
+    assert_eq!(build.errors(), &vec![0, 2]);
+  }
+
+  #[test]
+  fn tag_prepared_entry() {
+    let mut build = BuildOutput::from(["error: first", "flagged by hand"]);
+    build.prepare();
+    let before = display_text(&build);
+    build.tag_entry(1, BuildTag::warning(0..7, "flagged").unwrap());
+    assert_eq!(build.display().len(), 2);
+    assert!(build.prepare());
+    let after = display_text(&build);
+    assert_eq!(after[0], before[0]);
+    assert_ne!(after[1], before[1]);
+    assert!(after[1].starts_with("flagged"));
+    assert_eq!(build.warnings(), &vec![1]);
+    assert_eq!(build.errors(), &vec![0]);
+  }
+
+  #[test]
+  fn recover() {
+    let mut build = BuildOutput::from(["error: first", "second", "third"]);
+    build.prepare();
+    assert!(!build.recover());
+    // a prepared line went missing
+    build.prepared.pop();
+    assert!(!build.is_consistent());
+    assert_eq!(build.snapshot().len(), 2);
+    assert!(build.is_consistent());
+    assert_eq!(build.cursor(), 2);
+    assert_eq!(build.display().len(), 2);
+    build.prepare();
+    assert_eq!(build.display().len(), 3);
+    // the cursor went past the entries
+    build.cursor = 5;
+    assert!(build.recover());
+    assert_eq!(build.cursor(), 3);
+    assert_eq!(build.display().len(), 3);
+  }
+
+  #[test]
+  fn selection_out_of_range() {
+    let mut build = BuildOutput::from(["error: first"]);
+    build.prepare();
+    build
+      .markers_mut()
+      .set_selection(Some(MarkerSelection::new(MarkerId(0), EntryId(10), None)));
+    assert_eq!(build.display().len(), 1);
+  }
+
+  #[test]
+  fn first_error_event() {
+    let (tx, rx) = channel();
+    let lines = (0..10_000).map(|i| format!("error: boom #{}", i));
+    let mut build = BuildOutput::from(lines).with_build_events(tx);
+    build.prepare();
+    assert_eq!(build.errors().len(), 10_000);
+    let events = rx.try_iter().collect::<Vec<_>>();
+    assert!(
+      matches!(events[..], [BuildEvent::FirstErrorDetected(0)]),
+      "{:?}",
+      events
+    );
+    // the next preparations find no first error anymore
+    build.push(BuildEntry::new("error: again", Origin::Stderr));
+    build.prepare();
+    assert_eq!(rx.try_iter().count(), 0);
+  }
 }
@@ -1,6 +1,7 @@
 use std::{
   collections::HashMap,
-  io::{stderr, stdout, Read, Write},
+  fmt::Display,
+  io::{stderr, stdout, Cursor, Read, Write},
   path::{Path, PathBuf},
   process::exit,
   sync::{Arc, Mutex, MutexGuard},
@@ -15,15 +16,83 @@ use serde::{
   Deserialize, Serialize,
 };
 
-use crate::{err, search, DeclaredMarker, ErrorKind};
+use crate::{err, DeclaredMarker, DeclaredMarkerSpec, ErrorKind, Provenance};
 
-use super::BuildTagKind;
+use super::{
+  BuildOutput, BuildTagKind, FoldableMarker, InlineLocation, Location, MarkerHook, NoiseLine,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "RuleSpec")]
 pub struct Rule {
   pub aliases: Vec<String>,
   pub command: String,
   pub markers: Vec<DeclaredMarker>,
+  /// The command spawned when a new marker gets detected
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub on_marker: Option<MarkerHook>,
+  /// The locale of the build output, see [`crate::localize_active_rule`]
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub locale: Option<String>,
+  /// The sub-sections of the blocks folded by default
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub foldable: Vec<FoldableMarker>,
+  /// The locations written on the lines of the diagnostics, besides cargo's `-->` lines
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub locations: Vec<InlineLocation>,
+  /// The lines dropped on intake, see [`crate::IntakeFilter`]
+  #[serde(
+    default = "NoiseLine::defaults",
+    skip_serializing_if = "NoiseLine::are_defaults"
+  )]
+  pub noise: Vec<NoiseLine>,
+}
+
+/// The deserialized form of a [`Rule`], its markers get validated on conversion
+#[derive(Deserialize, Clone, Debug)]
+pub struct RuleSpec {
+  pub aliases: Vec<String>,
+  pub command: String,
+  pub markers: Vec<DeclaredMarkerSpec>,
+  #[serde(default)]
+  pub on_marker: Option<MarkerHook>,
+  #[serde(default)]
+  pub locale: Option<String>,
+  #[serde(default)]
+  pub foldable: Vec<FoldableMarker>,
+  #[serde(default)]
+  pub locations: Vec<InlineLocation>,
+  #[serde(default = "NoiseLine::defaults")]
+  pub noise: Vec<NoiseLine>,
+}
+
+impl TryFrom<RuleSpec> for Rule {
+  type Error = String;
+
+  fn try_from(value: RuleSpec) -> Result<Self, Self::Error> {
+    let name = value.aliases.first().cloned().unwrap_or_default();
+    let markers = value
+      .markers
+      .into_iter()
+      .map(DeclaredMarker::try_from)
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|e| format!("rule '{}': {}", name, e))?;
+    value
+      .locations
+      .iter()
+      .try_for_each(InlineLocation::validate)
+      .map_err(|e| format!("rule '{}': {}", name, e))?;
+    Ok(Self {
+      aliases: value.aliases,
+      command: value.command,
+      markers,
+      on_marker: value.on_marker,
+      locale: value.locale,
+      foldable: value.foldable,
+      locations: value.locations,
+      noise: value.noise,
+    })
+  }
 }
 
 impl Rule {
@@ -48,8 +117,42 @@ impl Rule {
           .into_iter()
           .map(|(tag, regex, style)| DeclaredMarker::new(tag, regex, style)),
       ),
+      on_marker: None,
+      locale: None,
+      foldable: vec![],
+      locations: vec![],
+      noise: NoiseLine::defaults(),
     }
   }
+
+  /// Define the command spawned when a new marker gets detected
+  pub fn with_on_marker(mut self, hook: MarkerHook) -> Self {
+    self.on_marker = Some(hook);
+    self
+  }
+
+  /// Define the sub-sections of the blocks folded by default
+  pub fn with_foldable<I: IntoIterator<Item = FoldableMarker>>(mut self, foldable: I) -> Self {
+    self.foldable = foldable.into_iter().collect();
+    self
+  }
+
+  /// Define the locations written on the lines of the diagnostics
+  pub fn with_locations<I: IntoIterator<Item = InlineLocation>>(mut self, locations: I) -> Self {
+    self.locations = locations.into_iter().collect();
+    self
+  }
+
+  /// Find the location of a diagnostic line, cargo's `-->` form first
+  pub fn find_location(&self, message: &str) -> Option<Location> {
+    if let Ok(Some(location)) = BuildOutput::extract_location(message) {
+      return Some(location);
+    }
+    self
+      .locations
+      .iter()
+      .find_map(|location| location.find(message))
+  }
 }
 
 pub const CONFIG_BASE_NAME: &'static str = "nbuild";
@@ -86,6 +189,11 @@ impl RuleFormat {
       serialize: Box::new(serialize),
     }
   }
+
+  /// Retrieve the name of this format, its first extension
+  pub fn name(&self) -> &str {
+    &self.exts[0]
+  }
 }
 
 lazy_static! {
@@ -94,8 +202,13 @@ lazy_static! {
     RuleFormat::new(
       ["json".to_string()],
       |r| {
-        serde_json::from_reader(r)
-          .map_err(|e| err!(ErrorKind::IO, "failed to read json from stream, {}", e))
+        serde_json::from_reader(r).map_err(|e| {
+          let error = err!(ErrorKind::Parsing, "failed to read json from stream, {}", e);
+          match e.line() {
+            0 => error,
+            line => error.with_snippet(crate::Snippet::at(line, e.column())),
+          }
+        })
       },
       |rules, w| {
         serde_json::to_writer_pretty(w, rules)
@@ -106,12 +219,17 @@ lazy_static! {
     RuleFormat::new(
       ["yaml".to_string(), "yml".to_string()],
       |r| {
-        serde_yml::from_reader(r)
-          .map_err(|e| err!(ErrorKind::IO, "failed to read yaml from stream, {}", e))
+        serde_yml::from_reader(r).map_err(|e| {
+          let error = err!(ErrorKind::Parsing, "failed to read yaml from stream, {}", e);
+          match e.location() {
+            Some(at) => error.with_snippet(crate::Snippet::at(at.line(), at.column())),
+            None => error,
+          }
+        })
       },
       |rules, w| {
         serde_yml::to_writer(w, rules)
-          .map_err(|e| err!(ErrorKind::IO, "failed to write json to stream, {}", e))
+          .map_err(|e| err!(ErrorKind::IO, "failed to write yaml to stream, {}", e))
       }
     ),
     #[cfg(feature = "toml")]
@@ -120,9 +238,19 @@ lazy_static! {
       |mut r| {
         let mut buf = String::new();
         r.read_to_string(&mut buf)
-          .map_err(|e| err!(ErrorKind::IO, "failed to read yaml from stream, {}", e))?;
-        toml::from_str(&buf)
-          .map_err(|e| err!(ErrorKind::IO, "failed to read yaml from stream, {}", e))
+          .map_err(|e| err!(ErrorKind::IO, "failed to read toml from stream, {}", e))?;
+        toml::from_str(&buf).map_err(|e| {
+          // the display of the error already quotes the line, keep the message only
+          let error = err!(
+            ErrorKind::Parsing,
+            "failed to read toml from stream, {}",
+            e.message()
+          );
+          match e.span() {
+            Some(span) => error.with_snippet(crate::Snippet::at_offset(&buf, span.start)),
+            None => error,
+          }
+        })
       },
       |rules, mut w| {
         let content = toml::to_string(rules)
@@ -153,10 +281,31 @@ lazy_static! {
         Regex::new(r"warning(\[\w+\])?:").expect("invalid regular expression"),
         Style::default().yellow().bold()
       ),
+      (
+        BuildTagKind::Summary,
+        Regex::new(r"^(?P<marker>error:|warning:) (aborting due to|could not compile|`[^`]+` \([^)]+\) generated \d)")
+          .expect("invalid regular expression"),
+        Style::default().dark_gray()
+      ),
     ]
-  )];
+  )
+  .with_foldable([FoldableMarker::new(
+    "macro backtrace",
+    Regex::new(r"in this (macro invocation|expansion of)|originates in the macro")
+      .expect("invalid regular expression")
+  )])
+  // gcc-style diagnostics, e.g. from the C code compiled by the build scripts
+  .with_locations([InlineLocation::new(
+    Regex::new(r"^\s*(?P<path>(?:[A-Za-z]:)?[^\s:]+):(?P<line>\d+):(?:(?P<column>\d+):)? (?:fatal )?(?:error|warning|note):")
+      .expect("invalid regular expression")
+  )])];
   static ref _rules: Arc<Mutex<Vec<Rule>>> = Arc::new(Mutex::new(DEFAULT_RULES.clone()));
   static ref _active_rule: Arc<Mutex<String>> = Arc::new(Mutex::new("rust".to_string()));
+  /// The config file each rule was loaded from, by lowercase alias
+  static ref _rule_sources: Arc<Mutex<HashMap<String, PathBuf>>> = Default::default();
+  /// The config file each rule was registered from, by lowercase alias: a rule declared
+  /// again by another file keeps the definition registered first
+  static ref _registered_rules: Arc<Mutex<HashMap<String, PathBuf>>> = Default::default();
 }
 
 pub fn rules() -> Vec<Rule> {
@@ -195,20 +344,143 @@ pub fn rule<S: AsRef<str>>(s: S) -> Option<Rule> {
     .cloned()
 }
 
-pub fn active_rule<'a>() -> Rule {
+/// A rule scored against the build output, see [`score_rules`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleCandidate {
+  /// The first alias of the rule
+  pub name: String,
+  /// The number of lines matched by the rule's markers
+  pub score: usize,
+}
+
+impl Display for RuleCandidate {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} ({} lines)", self.name, self.score)
+  }
+}
+
+/// Score the rules against output lines: the number of lines matched by any of their markers.
+///
+/// Sorted from the best match, ties keep the declaration order
+pub fn score_rules<L: AsRef<str>>(rules: &[Rule], lines: &[L]) -> Vec<RuleCandidate> {
+  let mut candidates = rules
+    .iter()
+    .map(|rule| RuleCandidate {
+      name: rule.aliases.first().cloned().unwrap_or_default(),
+      score: lines
+        .iter()
+        .filter(|line| {
+          rule
+            .markers
+            .iter()
+            .filter(|marker| !marker.disabled)
+            .any(|marker| marker.regex.is_match(line.as_ref()))
+        })
+        .count(),
+    })
+    .collect::<Vec<_>>();
+  candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.score));
+  candidates
+}
+
+pub fn active_rule() -> Rule {
+  with_active_rule(Rule::clone)
+}
+
+/// Run `f` on the active rule without cloning it, e.g. to look up a single marker
+/// per entry. The rules stay locked meanwhile
+pub fn with_active_rule<'a, R, F: FnOnce(&Rule) -> R>(f: F) -> R {
   let rules: MutexGuard<'a, Vec<Rule>> = _rules.lock().expect("failed to lock rules");
   let a: MutexGuard<'a, String> = _active_rule.lock().expect("failed to lock active rule");
-  rules
+  let rule = rules
     .iter()
     .find(|rule| {
       rule
         .aliases
         .iter()
-        .find(|alias| alias.to_lowercase().eq(a.as_str()))
-        .is_some()
+        .any(|alias| alias.chars().flat_map(char::to_lowercase).eq(a.chars()))
+    })
+    .expect("invalid active rule");
+  f(rule)
+}
+
+/// Replace the in-memory rule sharing the first alias of `rule`.
+///
+/// Returns false if there is no such rule
+pub fn update_rule(rule: Rule) -> bool {
+  let Some(name) = rule.aliases.first().map(|alias| alias.to_lowercase()) else {
+    return false;
+  };
+  let mut rules = _rules.lock().expect("failed to lock rules");
+  let existing = rules.iter_mut().find(|existing| {
+    existing
+      .aliases
+      .iter()
+      .any(|alias| alias.to_lowercase() == name)
+  });
+  match existing {
+    Some(existing) => {
+      crate::dbg!("Updating rule {:?}", rule.aliases);
+      *existing = rule;
+      true
+    }
+    None => false,
+  }
+}
+
+/// Retrieve the config file the rule `name` was loaded from, see [`load_rules`].
+///
+/// `None` for the rules never read from a file
+pub fn rule_source<S: AsRef<str>>(name: S) -> Option<PathBuf> {
+  let sources = _rule_sources.lock().expect("failed to lock rule sources");
+  sources.get(&name.as_ref().to_lowercase()).cloned()
+}
+
+/// Retrieve where the definition in use of the rule `name` comes from: the config file
+/// it was registered from, else the built-in rules.
+///
+/// A rule declared again by a config file keeps its definition, [`rule_source`] then
+/// differs
+pub fn rule_provenance<S: AsRef<str>>(name: S) -> Provenance {
+  let registered = _registered_rules
+    .lock()
+    .expect("failed to lock registered rules");
+  match registered.get(&name.as_ref().to_lowercase()) {
+    Some(path) => Provenance::File(path.clone()),
+    None => Provenance::Default,
+  }
+}
+
+/// Retrieve the config files the rules were loaded from, see [`load_rules`]
+pub fn rule_files() -> Vec<PathBuf> {
+  let sources = _rule_sources.lock().expect("failed to lock rule sources");
+  let mut files = sources.values().cloned().collect::<Vec<_>>();
+  files.sort();
+  files.dedup();
+  files
+}
+
+/// Append a marker to the active in-memory rule and return the updated rule
+pub fn add_active_rule_marker(marker: DeclaredMarker) -> Rule {
+  let mut rules = _rules.lock().expect("failed to lock rules");
+  let a = _active_rule.lock().expect("failed to lock active rule");
+  let rule = rules
+    .iter_mut()
+    .find(|rule| {
+      rule
+        .aliases
+        .iter()
+        .any(|alias| alias.to_lowercase().eq(a.as_str()))
     })
-    .expect("invalid active rule")
-    .clone()
+    .expect("invalid active rule");
+  crate::dbg!(
+    "Adding {:?} marker {:?} to rule {:?}",
+    marker.tag,
+    marker.regex.as_str(),
+    rule.aliases
+  );
+  rule.markers.push(marker);
+  rule.clone()
 }
 
 pub fn default_system_location() -> Option<PathBuf> {
@@ -304,13 +576,22 @@ pub fn load_rules(custom_path: Option<PathBuf>) -> crate::Result<Vec<Rule>> {
   match path {
     Some((loc, fmt)) => {
       crate::dbg!("Loading rules from {}", loc.display());
-      if let Ok(f) = std::fs::File::open(&loc) {
-        let rules = (fmt.deserialize)(Box::new(f))?;
+      if loc.is_file() {
+        let rules = read_rules(&loc, fmt)?;
+        {
+          let mut sources = _rule_sources.lock().expect("failed to lock rule sources");
+          for alias in rules.iter().flat_map(|r| r.aliases.iter()) {
+            sources.insert(alias.to_lowercase(), loc.clone());
+          }
+        }
         let mut g = _rules.lock().expect("failed to lock rules");
         let existing_rule_names = g
           .iter()
           .flat_map(|r| r.aliases.iter().map(|alias| alias.to_lowercase()))
           .collect::<Vec<_>>();
+        let mut registered = _registered_rules
+          .lock()
+          .expect("failed to lock registered rules");
         for r in &rules {
           if r
             .aliases
@@ -318,6 +599,11 @@ pub fn load_rules(custom_path: Option<PathBuf>) -> crate::Result<Vec<Rule>> {
             .find(|alias| existing_rule_names.contains(&alias.to_lowercase()))
             .is_none()
           {
+            registered.extend(
+              r.aliases
+                .iter()
+                .map(|alias| (alias.to_lowercase(), loc.clone())),
+            );
             g.push(r.clone());
           }
         }
@@ -336,6 +622,120 @@ pub fn load_rules(custom_path: Option<PathBuf>) -> crate::Result<Vec<Rule>> {
   }
 }
 
+/// Read and deserialize the rules of a config file
+fn read_rules(loc: &Path, fmt: &RuleFormat) -> crate::Result<Vec<Rule>> {
+  let content = std::fs::read_to_string(loc).map_err(|e| {
+    err!(
+      ErrorKind::IO,
+      "failed to open file for reading {}, {}",
+      loc.display(),
+      e
+    )
+  })?;
+  (fmt.deserialize)(Box::new(Cursor::new(content.clone()))).map_err(|e| {
+    let error = err!(
+      ErrorKind::Parsing,
+      "failed to load rules from {} as {}, {}",
+      loc.display(),
+      fmt.name(),
+      e
+    );
+    // the position of the format, in the file just read
+    match e.snippet() {
+      Some(snippet) => error.with_snippet(snippet.clone().in_file(loc, &content)),
+      None => error,
+    }
+  })
+}
+
+/// Serialize the rules to a temporary file next to `loc`, then rename it over `loc`.
+///
+/// On any error the temporary file is removed and `loc` is left untouched
+fn write_rules(rules: &Vec<Rule>, loc: &Path, fmt: &RuleFormat) -> crate::Result<()> {
+  let tmp = loc.with_file_name(format!(
+    ".{}.tmp-{}",
+    loc
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default(),
+    std::process::id()
+  ));
+  let io_err = |e: std::io::Error| err!(ErrorKind::IO, "{}", e);
+  let result = std::fs::File::create(&tmp)
+    .map_err(io_err)
+    .and_then(|f| {
+      (fmt.serialize)(rules, Box::new(f.try_clone().map_err(io_err)?))?;
+      f.sync_all().map_err(io_err)
+    })
+    .and_then(|_| std::fs::rename(&tmp, loc).map_err(io_err));
+  if let Err(e) = result {
+    let _ = std::fs::remove_file(&tmp);
+    return Err(err!(
+      ErrorKind::IO,
+      "failed to save rules to {} as {}, {}",
+      loc.display(),
+      fmt.name(),
+      e
+    ));
+  }
+  Ok(())
+}
+
+/// Load the rules of a config file without registering them, and report
+/// the problems found: no alias, no marker or an alias used by several rules.
+///
+/// Returns the path of the checked file and its rules
+pub fn check_rules(custom_path: Option<PathBuf>) -> crate::Result<(PathBuf, Vec<Rule>)> {
+  let (loc, fmt) = match custom_path {
+    Some(path) => {
+      let fmt = find_format(&path).ok_or_else(|| {
+        err!(
+          ErrorKind::Parsing,
+          "unknown config format for {}",
+          path.display()
+        )
+      })?;
+      (path, fmt)
+    }
+    None => locate_rules().ok_or_else(|| err!(ErrorKind::FileNotFound))?,
+  };
+  let rules = read_rules(&loc, fmt)?;
+  let mut problems = vec![];
+  let mut aliases: HashMap<String, usize> = HashMap::new();
+  for (id, rule) in rules.iter().enumerate() {
+    let name = rule
+      .aliases
+      .first()
+      .cloned()
+      .unwrap_or_else(|| format!("#{}", id));
+    if rule.aliases.is_empty() {
+      problems.push(format!("rule {} has no alias", name));
+    }
+    if rule.markers.is_empty() {
+      problems.push(format!("rule '{}' has no marker", name));
+    }
+    for alias in &rule.aliases {
+      if let Some(other) = aliases.insert(alias.to_lowercase(), id) {
+        if other != id {
+          problems.push(format!(
+            "alias '{}' of rule '{}' is already used by rule #{}",
+            alias, name, other
+          ));
+        }
+      }
+    }
+  }
+  if !problems.is_empty() {
+    return Err(err!(
+      ErrorKind::Rule,
+      "invalid config {}:\n  - {}",
+      loc.display(),
+      problems.join("\n  - ")
+    ));
+  }
+  Ok((loc, rules))
+}
+
 pub fn save_rules(rules: &Vec<Rule>, custom_path: Option<PathBuf>) -> crate::Result<PathBuf> {
   let path = custom_path
     .and_then(|p| find_format(&p).and_then(|fmt| Some((p, fmt))))
@@ -358,20 +758,8 @@ pub fn save_rules(rules: &Vec<Rule>, custom_path: Option<PathBuf>) -> crate::Res
         let _ = std::fs::create_dir_all(parent);
       }
     }
-    match std::fs::File::create(&loc) {
-      Ok(f) => {
-        (fmt.serialize)(rules, Box::new(f))?;
-        return Ok(loc);
-      }
-      Err(e) => {
-        return Err(err!(
-          ErrorKind::IO,
-          "failed to open file for writing {}, {}",
-          loc.display(),
-          e
-        ))
-      }
-    }
+    write_rules(rules, &loc, fmt)?;
+    return Ok(loc);
   }
   Err(err!(
     ErrorKind::IO,
@@ -382,6 +770,15 @@ pub fn save_rules(rules: &Vec<Rule>, custom_path: Option<PathBuf>) -> crate::Res
 pub fn init_rules(custom_path: Option<PathBuf>) -> crate::Result<Vec<Rule>> {
   match load_rules(custom_path.clone()) {
     Ok(rules) => return Ok(rules),
+    // never replace an existing config with the defaults, it failed to load
+    Err(eload)
+      if custom_path
+        .as_ref()
+        .map(|path| path.exists())
+        .unwrap_or_else(|| locate_rules().is_some()) =>
+    {
+      Err(eload)
+    }
     Err(eload) => match save_rules(&DEFAULT_RULES, custom_path.clone()) {
       Ok(_) => return load_rules(custom_path),
       Err(esave) => Err(err!(
@@ -396,11 +793,266 @@ pub fn init_rules(custom_path: Option<PathBuf>) -> crate::Result<Vec<Rule>> {
 
 #[cfg(test)]
 mod tests {
-  use std::path::PathBuf;
+  use std::{fs, io::Write, path::PathBuf};
 
   use dirs::config_dir;
+  use ratatui::style::Stylize;
+
+  use crate::{err, save_rules, ErrorKind, Rule, CONFIG_BASE_NAME, DEFAULT_RULES, RULE_FORMATS};
+
+  use super::{write_rules, RuleFormat};
+
+  fn assert_same_rules(a: &[Rule], b: &[Rule]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b.iter()) {
+      assert_eq!(a.aliases, b.aliases);
+      assert_eq!(a.command, b.command);
+      assert_eq!(a.markers, b.markers);
+      // the style is not part of the markers' identity
+      for (a, b) in a.markers.iter().zip(b.markers.iter()) {
+        assert_eq!(a.style, b.style);
+        assert_eq!(a.is_secondary(), b.is_secondary());
+      }
+      assert_eq!(a.foldable, b.foldable);
+    }
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn json_round_trip() {
+    let content = serde_json::to_string_pretty(&*DEFAULT_RULES).expect("failed to serialize");
+    assert!(content.contains(r#""modifiers": ["#));
+    let rules: Vec<Rule> = serde_json::from_str(&content).expect("failed to deserialize");
+    assert_same_rules(&rules, &DEFAULT_RULES);
+  }
+
+  #[cfg(feature = "yaml")]
+  #[test]
+  fn yaml_round_trip() {
+    let content = serde_yml::to_string(&*DEFAULT_RULES).expect("failed to serialize");
+    let rules: Vec<Rule> = serde_yml::from_str(&content).expect("failed to deserialize");
+    assert_same_rules(&rules, &DEFAULT_RULES);
+  }
+
+  #[cfg(feature = "toml")]
+  #[test]
+  fn toml_round_trip() {
+    let content = toml::to_string(&DEFAULT_RULES[0]).expect("failed to serialize");
+    let rule: Rule = toml::from_str(&content).expect("failed to deserialize");
+    assert_same_rules(&[rule], &DEFAULT_RULES[0..1]);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn legacy_style() {
+    let content = r#"[{
+      "aliases": ["legacy"],
+      "command": "make",
+      "markers": [{
+        "tag": "Error",
+        "regex": "error:",
+        "style": {"fg": "Red", "bg": null, "underline_color": null, "add_modifier": "BOLD", "sub_modifier": ""}
+      }]
+    }]"#;
+    let rules: Vec<Rule> = serde_json::from_str(content).expect("failed to deserialize");
+    assert_eq!(
+      rules[0].markers[0].style,
+      ratatui::style::Style::default().red().bold()
+    );
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn invalid_color() {
+    let content = r#"[{
+      "aliases": ["custom"],
+      "command": "make",
+      "markers": [{"tag": "Warning", "regex": "warning:", "style": {"fg": "yelow"}}]
+    }]"#;
+    let e = serde_json::from_str::<Vec<Rule>>(content)
+      .expect_err("invalid color accepted")
+      .to_string();
+    assert!(e.contains("rule 'custom'"), "{}", e);
+    assert!(e.contains("marker Warning"), "{}", e);
+    assert!(e.contains("unknown color 'yelow'"), "{}", e);
+  }
+
+  /// A fresh directory holding only `name`
+  fn config_file(test: &str, name: &str, content: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nbuild-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, content).unwrap();
+    path
+  }
 
-  use crate::{rule::RULE_FORMATS, CONFIG_BASE_NAME};
+  #[cfg(feature = "json")]
+  #[test]
+  fn edited_rule_round_trip() {
+    let mut rule = DEFAULT_RULES[0].clone();
+    rule.aliases = vec!["edited".to_string()];
+    let path = config_file("edited", "nbuild.json", "");
+    save_rules(&vec![rule.clone()], Some(path.clone())).unwrap();
+    let rules = super::load_rules(Some(path.clone())).unwrap();
+    assert!(rules.iter().any(|rule| rule.aliases == ["edited"]));
+    assert_eq!(super::rule_source("Edited"), Some(path.clone()));
+    assert_eq!(super::rule_source("rust: cargo"), None);
+
+    // the warning and the error swap, the error gets disabled
+    rule.markers.swap(0, 2);
+    rule.markers[2].disabled = true;
+    assert!(super::update_rule(rule.clone()));
+    let rules = super::rules();
+    save_rules(&rules, super::rule_source("edited")).unwrap();
+    let content = fs::read_to_string(&path).unwrap();
+    assert_eq!(content.matches(r#""disabled": true"#).count(), 1);
+    let (_path, saved) = super::check_rules(Some(path.clone())).unwrap();
+    let saved = saved
+      .into_iter()
+      .find(|rule| rule.aliases == ["edited"])
+      .unwrap();
+    assert_eq!(
+      saved
+        .markers
+        .iter()
+        .map(|marker| (marker.tag, marker.disabled))
+        .collect::<Vec<_>>(),
+      vec![
+        (crate::BuildTagKind::Warning, false),
+        (crate::BuildTagKind::Note, false),
+        (crate::BuildTagKind::Error, true),
+        (crate::BuildTagKind::Summary, false),
+      ]
+    );
+
+    // the next launch loads the edits, simulated with an alias new to this process
+    let next = config_file(
+      "edited-next",
+      "nbuild.json",
+      &content.replace("edited", "reloaded"),
+    );
+    super::load_rules(Some(next.clone())).unwrap();
+    assert_eq!(super::rule("reloaded").unwrap().markers, saved.markers);
+    // where the rule editor saves it again
+    assert_eq!(
+      super::rule_provenance("reloaded"),
+      crate::Provenance::File(next)
+    );
+  }
+
+  #[test]
+  fn save_failure_keeps_config() {
+    let path = config_file("save-failure", "nbuild.json", "original");
+    let failing = RuleFormat::new(
+      ["json"],
+      |_r| Ok(vec![]),
+      |_rules, mut w| {
+        let _ = w.write_all(b"partial");
+        Err(err!(ErrorKind::Codec, "unsupported style"))
+      },
+    );
+    let e = write_rules(&DEFAULT_RULES, &path, &failing)
+      .expect_err("failing serializer accepted")
+      .to_string();
+    assert!(e.contains(&path.display().to_string()), "{}", e);
+    assert!(e.contains("as json"), "{}", e);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+    // the temporary file is removed
+    assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+  }
+
+  #[cfg(feature = "toml")]
+  #[test]
+  fn save_toml_failure_keeps_config() {
+    // toml has no top-level arrays, the rule list never serializes
+    let path = config_file("save-toml", "nbuild.toml", "original");
+    assert!(save_rules(&DEFAULT_RULES, Some(path.clone())).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+    // the broken config is reported instead of being replaced by the defaults
+    assert!(super::init_rules(Some(path.clone())).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn check_rules() {
+    let path = config_file("check", "nbuild.json", "");
+    save_rules(&DEFAULT_RULES, Some(path.clone())).unwrap();
+    let (checked, rules) = super::check_rules(Some(path.clone())).unwrap();
+    assert_eq!(checked, path);
+    assert_same_rules(&rules, &DEFAULT_RULES);
+
+    let mut duplicated = DEFAULT_RULES.clone();
+    duplicated.push(DEFAULT_RULES[0].clone());
+    save_rules(&duplicated, Some(path.clone())).unwrap();
+    let e = super::check_rules(Some(path.clone()))
+      .expect_err("duplicated aliases accepted")
+      .to_string();
+    assert!(e.contains("alias 'rust: cargo'"), "{}", e);
+
+    fs::write(&path, "[{").unwrap();
+    let e = super::check_rules(Some(path.clone()))
+      .expect_err("invalid json accepted")
+      .to_string();
+    assert!(e.contains("as json"), "{}", e);
+  }
+
+  /// Load the malformed `content` as `name`, expect an error at `line`
+  #[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+  fn assert_parse_error(name: &str, content: &str, line: usize, offending: &str) {
+    let path = config_file(&format!("parse-error-{}", name), name, content);
+    let e = super::check_rules(Some(path.clone())).expect_err("malformed config accepted");
+    let snippet = e.snippet().expect("no position").clone();
+    assert_eq!(snippet.location.path(), &path);
+    assert_eq!(snippet.location.line(), Some(line));
+    assert_eq!(snippet.text, offending);
+    let block = snippet.to_string();
+    assert!(
+      block.contains(&format!("{}:{}:", path.display(), line)),
+      "{}",
+      block
+    );
+    assert!(
+      block.contains(&format!("{} | {}", line, offending)),
+      "{}",
+      block
+    );
+    assert!(block.ends_with('^'), "{}", block);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn json_parse_error() {
+    assert_parse_error(
+      "nbuild.json",
+      "[{\n  \"aliases\": [\"custom\"],\n  \"command\": make\n}]",
+      3,
+      "  \"command\": make",
+    );
+  }
+
+  #[cfg(feature = "yaml")]
+  #[test]
+  fn yaml_parse_error() {
+    assert_parse_error(
+      "nbuild.yml",
+      "- aliases: [custom]\n  command: make\n   markers: []\n",
+      3,
+      "   markers: []",
+    );
+  }
+
+  #[cfg(feature = "toml")]
+  #[test]
+  fn toml_parse_error() {
+    assert_parse_error(
+      "nbuild.toml",
+      "aliases = [\"custom\"]\ncommand = make\n",
+      2,
+      "command = make",
+    );
+  }
 
   #[test]
   fn search_locations() {
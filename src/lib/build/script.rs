@@ -0,0 +1,125 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+  /// The warnings of a build script, `cargo::warning=` prefixed by cargo with the package
+  static ref SCRIPT_WARNING: Regex =
+    Regex::new(r"^warning: ([\w-]+)@[^\s:]+: ").expect("invalid regular expression");
+  /// cargo's report of a build script that failed
+  static ref SCRIPT_FAILURE: Regex =
+    Regex::new(r"^error: failed to run custom build command for `([^`\s]+)")
+      .expect("invalid regular expression");
+}
+
+/// The part of the build script output a line belongs to, see [`BuildScript`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildScriptLine {
+  /// A warning emitted by the build script: `warning: foo@0.1.0: ...`
+  Warning,
+  /// cargo's `failed to run custom build command` report
+  Failure,
+  /// The body of the failure report: the exit status then the captured
+  /// `--- stdout` and `--- stderr` sections
+  Output,
+}
+
+/// A line of build script output, attached to the package of the build script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildScript {
+  /// The name of the package
+  pub package: String,
+  pub line: BuildScriptLine,
+}
+
+impl BuildScript {
+  /// Parse a build script warning or a failure report, `None` for any other line
+  pub fn parse<M: AsRef<str>>(message: M) -> Option<Self> {
+    let message = message.as_ref();
+    for (regex, line) in [
+      (&*SCRIPT_WARNING, BuildScriptLine::Warning),
+      (&*SCRIPT_FAILURE, BuildScriptLine::Failure),
+    ] {
+      // checked first, the captures get allocated even when nothing matches
+      if !regex.is_match(message) {
+        continue;
+      }
+      if let Some(captures) = regex.captures(message) {
+        return Some(Self {
+          package: captures[1].to_string(),
+          line,
+        });
+      }
+    }
+    None
+  }
+
+  /// Check if `message` follows this line in the failure report: cargo indents
+  /// the captured output, separated by blank lines.
+  ///
+  /// Returns the [`BuildScriptLine::Output`] line if it does
+  pub fn next<M: AsRef<str>>(&self, message: M) -> Option<Self> {
+    if self.line == BuildScriptLine::Warning {
+      return None;
+    }
+    let message = message.as_ref();
+    let continues = message.trim().is_empty()
+      || message.starts_with(char::is_whitespace)
+      || message == "Caused by:";
+    continues.then(|| Self {
+      package: self.package.clone(),
+      line: BuildScriptLine::Output,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{BuildScript, BuildScriptLine};
+
+  fn script(package: &str, line: BuildScriptLine) -> BuildScript {
+    BuildScript {
+      package: package.to_string(),
+      line,
+    }
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      BuildScript::parse("warning: openssl-sys@0.9.102: Could not find directory of OpenSSL"),
+      Some(script("openssl-sys", BuildScriptLine::Warning))
+    );
+    assert_eq!(
+      BuildScript::parse("error: failed to run custom build command for `foo v0.1.0 (/tmp/foo)`"),
+      Some(script("foo", BuildScriptLine::Failure))
+    );
+    for line in [
+      "warning: unused import: `std::fmt`",
+      "warning: `demo` (lib) generated 1 warning",
+      "error: could not compile `foo` (build script) due to 1 previous error",
+      "  warning: foo@0.1.0: indented",
+    ] {
+      assert_eq!(BuildScript::parse(line), None, "{}", line);
+    }
+  }
+
+  #[test]
+  fn next() {
+    let failure = script("foo", BuildScriptLine::Failure);
+    let output = script("foo", BuildScriptLine::Output);
+    for line in [
+      "",
+      "Caused by:",
+      "  --- stdout",
+      "  note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace",
+    ] {
+      assert_eq!(failure.next(line), Some(output.clone()), "{}", line);
+      assert_eq!(output.next(line), Some(output.clone()), "{}", line);
+    }
+    assert_eq!(
+      output.next("warning: build failed, waiting for other jobs to finish..."),
+      None
+    );
+    assert_eq!(script("foo", BuildScriptLine::Warning).next("  more"), None);
+  }
+}
@@ -0,0 +1,490 @@
+use std::{
+  ops::Range,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use ratatui::style::{Color, Style, Stylize};
+
+use crate::{
+  apply_selection, Direction, DisplayedEntry, EntryId, LogEntry, MarkerSelection, Markers,
+  SelectionTheme, SlowMarker,
+};
+
+use super::{
+  active_rule, Artifact, BuildEntry, BuildTagKind, CrateFailure, DuplicateBlock, Fold, JumpTarget,
+  MarkedBlock, Rule, TargetCounts,
+};
+
+/// A contiguous run of prepared entries and their displayable lines.
+///
+/// Segments are never modified once built, consecutive snapshots share
+/// the ones that did not change.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotSegment {
+  start: usize,
+  entries: Vec<BuildEntry>,
+  lines: Vec<LogEntry<'static>>,
+}
+
+impl SnapshotSegment {
+  /// Construct a segment starting at entry `start`
+  pub fn new(start: usize, entries: Vec<BuildEntry>, lines: Vec<LogEntry<'static>>) -> Self {
+    Self {
+      start,
+      entries,
+      lines,
+    }
+  }
+
+  /// Retrieve the range of entry ids covered by this segment
+  pub fn range(&self) -> Range<usize> {
+    self.start..self.start + self.entries.len()
+  }
+}
+
+/// Dim the disabled blocks of `markers` and highlight its selected entry,
+/// `entries` start at entry `start`. Only the restyled entries get copied.
+///
+/// The region of the selection is in the coordinates of the raw `message`,
+/// translated to the displayed line by [`crate::LogEntry::display_region`]
+pub(crate) fn restyle<'e, 'm, F: Fn(EntryId) -> Option<&'m str>>(
+  mut entries: Vec<DisplayedEntry<'e>>,
+  start: usize,
+  markers: &Markers,
+  message: F,
+) -> Vec<DisplayedEntry<'e>> {
+  let end = start + entries.len();
+  for range in markers.disabled_ranges(end) {
+    let range = range.start.clamp(start, end) - start..range.end.clamp(start, end) - start;
+    for entry in &mut entries[range] {
+      let line = entry.to_mut().line_mut();
+      line.style = line.style.patch(Style::default().dim());
+    }
+  }
+  if let Some(entry) = markers
+    .selected_entry()
+    .and_then(|entry_id| entry_id.0.checked_sub(start))
+    .and_then(|i| entries.get_mut(i))
+  {
+    let theme = SelectionTheme::default();
+    let entry = entry.to_mut();
+    let line = entry.line_mut();
+    *line = apply_selection(std::mem::take(line), &theme);
+    let selection = markers.selection();
+    let region = selection.and_then(|selection| {
+      let region = selection.region.clone()?;
+      Some(entry.display_region(message(selection.entry_id)?, region))
+    });
+    if let Some(region) = region {
+      entry.highlight(region, theme.region);
+    }
+  }
+  entries
+}
+
+/// The style of the region of the selection, e.g. a search match
+pub const REGION_STYLE: Style = Style::new().fg(Color::Black).bg(Color::Yellow);
+
+/// A mutation-free view of a [`super::BuildOutput`], produced by
+/// [`super::BuildOutput::snapshot`] after each prepare.
+///
+/// It is cheap to clone and can be sent to the rendering thread while
+/// the build output keeps preparing new entries.
+#[derive(Debug, Clone)]
+pub struct BuildSnapshot {
+  segments: Vec<Arc<SnapshotSegment>>,
+  num_entries: usize,
+  markers: Markers,
+  rule: Rule,
+  num_errors: usize,
+  num_warnings: usize,
+  num_notes: usize,
+  failed_crates: Vec<CrateFailure>,
+  num_script_warnings: usize,
+  artifacts: Vec<Artifact>,
+  queue_latency: Duration,
+  generation: usize,
+  flushed: bool,
+  detected: Vec<(EntryId, BuildTagKind)>,
+  repeats: Vec<Range<usize>>,
+  duplicates: Vec<DuplicateBlock>,
+  target_counts: Vec<TargetCounts>,
+  slow_markers: Vec<SlowMarker>,
+}
+
+impl Default for BuildSnapshot {
+  fn default() -> Self {
+    Self {
+      segments: Default::default(),
+      num_entries: Default::default(),
+      markers: Default::default(),
+      rule: active_rule(),
+      num_errors: Default::default(),
+      num_warnings: Default::default(),
+      num_notes: Default::default(),
+      failed_crates: Default::default(),
+      num_script_warnings: Default::default(),
+      artifacts: Default::default(),
+      queue_latency: Default::default(),
+      generation: Default::default(),
+      flushed: Default::default(),
+      detected: Default::default(),
+      repeats: Default::default(),
+      duplicates: Default::default(),
+      target_counts: Default::default(),
+      slow_markers: Default::default(),
+    }
+  }
+}
+
+impl BuildSnapshot {
+  /// Construct a snapshot from its prepared segments
+  pub fn new(
+    segments: Vec<Arc<SnapshotSegment>>,
+    num_entries: usize,
+    markers: Markers,
+    rule: Rule,
+  ) -> Self {
+    Self {
+      segments,
+      num_entries,
+      markers,
+      rule,
+      ..Default::default()
+    }
+  }
+
+  /// Define the number of detected `(errors, warnings, notes)`
+  pub fn with_counts(mut self, errors: usize, warnings: usize, notes: usize) -> Self {
+    self.num_errors = errors;
+    self.num_warnings = warnings;
+    self.num_notes = notes;
+    self
+  }
+
+  /// Define the crates that failed to build
+  pub fn with_failed_crates(mut self, failed_crates: Vec<CrateFailure>) -> Self {
+    self.failed_crates = failed_crates;
+    self
+  }
+
+  /// Define the number of warnings emitted by the build scripts
+  pub fn with_num_script_warnings(mut self, n: usize) -> Self {
+    self.num_script_warnings = n;
+    self
+  }
+
+  /// Define the files produced by the build
+  pub fn with_artifacts(mut self, artifacts: Vec<Artifact>) -> Self {
+    self.artifacts = artifacts;
+    self
+  }
+
+  /// Define the queue latency of the entries pulled since the previous snapshot
+  pub fn with_queue_latency(mut self, latency: Duration) -> Self {
+    self.queue_latency = latency;
+    self
+  }
+
+  /// Define the generation, bumped each time the build output starts over
+  pub fn with_generation(mut self, generation: usize) -> Self {
+    self.generation = generation;
+    self
+  }
+
+  /// If true every entry received before the snapshot was requested is prepared
+  pub fn with_flushed(mut self, flushed: bool) -> Self {
+    self.flushed = flushed;
+    self
+  }
+
+  /// Define the markers detected since the previous snapshot, in entry order
+  pub fn with_detected(mut self, detected: Vec<(EntryId, BuildTagKind)>) -> Self {
+    self.detected = detected;
+    self
+  }
+
+  /// Define the runs of identical consecutive messages
+  pub fn with_repeats(mut self, repeats: Vec<Range<usize>>) -> Self {
+    self.repeats = repeats;
+    self
+  }
+
+  /// Define the blocks repeated by another target, see [`super::BuildOutput::duplicates`]
+  pub fn with_duplicates(mut self, duplicates: Vec<DuplicateBlock>) -> Self {
+    self.duplicates = duplicates;
+    self
+  }
+
+  /// Define the diagnostics of each target, see [`super::BuildOutput::target_counts`]
+  pub fn with_target_counts(mut self, target_counts: Vec<TargetCounts>) -> Self {
+    self.target_counts = target_counts;
+    self
+  }
+
+  /// Define the markers disabled for going over their budget, see [`crate::MarkerBudget`]
+  pub fn with_slow_markers(mut self, slow_markers: Vec<SlowMarker>) -> Self {
+    self.slow_markers = slow_markers;
+    self
+  }
+
+  /// Retrieve the shared segments
+  pub fn segments(&self) -> &Vec<Arc<SnapshotSegment>> {
+    &self.segments
+  }
+
+  /// Retrieve the number of prepared entries
+  pub fn len(&self) -> usize {
+    self.segments.last().map(|seg| seg.range().end).unwrap_or(0)
+  }
+
+  /// Check if no entry was prepared yet
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Retrieve the number of entries received, prepared or not
+  pub fn num_entries(&self) -> usize {
+    self.num_entries
+  }
+
+  /// Retrieve the markers of the prepared entries
+  pub fn markers(&self) -> &Markers {
+    &self.markers
+  }
+
+  /// Retrieve the [`Rule`] the entries were prepared with
+  pub fn rule(&self) -> &Rule {
+    &self.rule
+  }
+
+  /// Retrieve the number of detected errors
+  pub fn num_errors(&self) -> usize {
+    self.num_errors
+  }
+
+  /// Retrieve the number of detected warnings
+  pub fn num_warnings(&self) -> usize {
+    self.num_warnings
+  }
+
+  /// Retrieve the number of detected notes
+  pub fn num_notes(&self) -> usize {
+    self.num_notes
+  }
+
+  /// Retrieve the number of warnings emitted by the build scripts,
+  /// not counted in [`Self::num_warnings`]
+  pub fn num_script_warnings(&self) -> usize {
+    self.num_script_warnings
+  }
+
+  /// Retrieve the crates that failed to build, from cargo's `could not compile` trailers
+  pub fn failed_crates(&self) -> &Vec<CrateFailure> {
+    &self.failed_crates
+  }
+
+  /// Retrieve the files produced by the build, from cargo's `compiler-artifact` messages
+  pub fn artifacts(&self) -> &Vec<Artifact> {
+    &self.artifacts
+  }
+
+  /// Retrieve the longest queue latency of the entries pulled since the previous snapshot
+  pub fn queue_latency(&self) -> Duration {
+    self.queue_latency
+  }
+
+  /// Retrieve the generation, see [`Self::with_generation`]
+  pub fn generation(&self) -> usize {
+    self.generation
+  }
+
+  /// Check if the snapshot was flushed, see [`Self::with_flushed`]
+  pub fn is_flushed(&self) -> bool {
+    self.flushed
+  }
+
+  /// Retrieve the markers detected since the previous snapshot, see [`crate::FollowPolicy`]
+  pub fn detected(&self) -> &[(EntryId, BuildTagKind)] {
+    &self.detected
+  }
+
+  fn segment_at(&self, entry_id: usize) -> Option<&SnapshotSegment> {
+    let pos = self
+      .segments
+      .partition_point(|seg| seg.start <= entry_id)
+      .checked_sub(1)?;
+    let segment = &self.segments[pos];
+    match segment.range().contains(&entry_id) {
+      true => Some(segment),
+      false => None,
+    }
+  }
+
+  /// Retrieve a prepared entry
+  pub fn entry(&self, entry_id: EntryId) -> Option<&BuildEntry> {
+    self
+      .segment_at(entry_id.0)
+      .map(|seg| &seg.entries[entry_id.0 - seg.start])
+  }
+
+  /// Retrieve the displayable line of a prepared entry, as prepared
+  pub fn line(&self, entry_id: EntryId) -> Option<&LogEntry<'static>> {
+    self
+      .segment_at(entry_id.0)
+      .map(|seg| &seg.lines[entry_id.0 - seg.start])
+  }
+
+  /// Iterate over the prepared entries, in order
+  pub fn entries(&self) -> impl Iterator<Item = &BuildEntry> {
+    self.segments.iter().flat_map(|seg| seg.entries.iter())
+  }
+
+  /// Retrieve the creation time of the first prepared entry
+  pub fn started_at(&self) -> Option<Instant> {
+    self.entries().next().map(|entry| *entry.created_at())
+  }
+
+  /// Count the prepared entries created up to `at`, the log of the build as it was then.
+  ///
+  /// The entries are in time order, so this is a binary search over the segments
+  /// then over the entries of one segment
+  pub fn entries_before(&self, at: Instant) -> usize {
+    let pos = self
+      .segments
+      .partition_point(|seg| match seg.entries.first() {
+        Some(entry) => *entry.created_at() <= at,
+        None => true,
+      });
+    let Some(segment) = pos.checked_sub(1).map(|pos| &self.segments[pos]) else {
+      return 0;
+    };
+    segment.start
+      + segment
+        .entries
+        .partition_point(|entry| *entry.created_at() <= at)
+  }
+
+  /// Retrieve the foldable sub-sections, runs of entries tagged [`BuildTagKind::Foldable`]
+  pub fn folds(&self) -> Vec<Fold> {
+    let mut ret: Vec<Fold> = vec![];
+    for (entry_id, entry) in self.entries().enumerate() {
+      let Some(label) = entry
+        .tag(BuildTagKind::Foldable)
+        .and_then(|tag| tag.get_label())
+      else {
+        continue;
+      };
+      match ret.last_mut() {
+        Some(fold) if fold.range.end == entry_id && fold.label == label => fold.range.end += 1,
+        _ => ret.push(Fold::new(entry_id..entry_id + 1, label)),
+      }
+    }
+    ret
+  }
+
+  /// Retrieve the markers disabled for going over their budget, since the start of the run
+  pub fn slow_markers(&self) -> &[SlowMarker] {
+    &self.slow_markers
+  }
+
+  /// Retrieve the runs of identical consecutive messages, see [`Fold::repeats`]
+  pub fn repeats(&self) -> Vec<Fold> {
+    self.repeats.iter().cloned().map(Fold::repeats).collect()
+  }
+
+  /// Retrieve the folds of the blocks repeated by another target, see [`Fold::duplicate`]
+  pub fn duplicates(&self) -> Vec<Fold> {
+    self.duplicates.iter().map(Fold::duplicate).collect()
+  }
+
+  /// Retrieve the diagnostics of each target
+  pub fn target_counts(&self) -> &[TargetCounts] {
+    &self.target_counts
+  }
+
+  /// Retrieve the displayable lines, dimming the disabled blocks
+  /// and highlighting the selection of `markers`.
+  ///
+  /// The lines are borrowed from the segments, except the restyled ones
+  pub fn display(&self, markers: &Markers) -> Vec<DisplayedEntry<'_>> {
+    self.display_range(0..self.len(), markers)
+  }
+
+  /// Retrieve the displayable lines of the entries in `range`, see [`Self::display`]
+  pub fn display_range(&self, range: Range<usize>, markers: &Markers) -> Vec<DisplayedEntry<'_>> {
+    let range = range.start.min(self.len())..range.end.min(self.len());
+    let entries = self
+      .segments
+      .iter()
+      .flat_map(|seg| seg.lines.iter())
+      .skip(range.start)
+      .take(range.len())
+      .map(DisplayedEntry::from);
+    restyle(entries.collect(), range.start, markers, |entry_id| {
+      self.entry(entry_id).map(|entry| entry.message().as_str())
+    })
+  }
+
+  /// Retrieve the block containing `entry_id`
+  pub fn block_at(&self, entry_id: EntryId) -> Option<MarkedBlock<'_>> {
+    let (marker_id, start, end) = self.markers.block_bounds(entry_id)?;
+    let range = start.0..end.map(|end| end.0).unwrap_or(self.len()).min(self.len());
+    let marker = self.entry(start)?.primary_marker()?;
+    let entries = range
+      .clone()
+      .filter_map(|id| self.entry(EntryId(id)))
+      .collect::<Vec<_>>();
+    Some(MarkedBlock::new(marker_id, marker.clone(), range, entries))
+  }
+
+  /// Retrieve all the blocks, in order
+  pub fn blocks(&self) -> Vec<MarkedBlock<'_>> {
+    self
+      .markers
+      .primary_ids()
+      .filter_map(|marker_id| self.block_at(self.markers[marker_id].0))
+      .collect::<Vec<_>>()
+  }
+
+  /// List the blocks of `kind` for the quick-jump dialog, in order
+  pub fn jump_targets(&self, kind: BuildTagKind) -> Vec<JumpTarget> {
+    self
+      .blocks()
+      .iter()
+      .filter(|block| block.marker().kind() == kind)
+      .map(|block| block.jump_target())
+      .collect::<Vec<_>>()
+  }
+
+  /// Find the first prepared entry containing `query`
+  pub fn search<Q: AsRef<str>>(&self, query: Q) -> Option<(MarkedBlock<'_>, MarkerSelection)> {
+    self.search_from(0, query, Direction::Forward)
+  }
+
+  /// Find the prepared entry containing `query`, from the entry `start` included, see
+  /// [`crate::BuildOutput::search_from`]
+  pub fn search_from<Q: AsRef<str>>(
+    &self,
+    start: usize,
+    query: Q,
+    direction: Direction,
+  ) -> Option<(MarkedBlock<'_>, MarkerSelection)> {
+    let query = query.as_ref();
+    direction.order(self.len(), start).find_map(|entry_id| {
+      let entry_id = EntryId(entry_id);
+      let message = self.entry(entry_id)?.message();
+      let pos = match direction {
+        Direction::Forward => message.find(query),
+        Direction::Backward => message.rfind(query),
+      }?;
+      let block = self.block_at(entry_id)?;
+      let marker_id = block.marker_id();
+      Some((
+        block,
+        MarkerSelection::new(marker_id, entry_id, Some(pos..pos + query.len())),
+      ))
+    })
+  }
+}
@@ -0,0 +1,434 @@
+use std::{
+  collections::BTreeMap,
+  fmt::Display,
+  fs::{read_to_string, remove_file, rename, write},
+  path::{Path, PathBuf},
+};
+
+use crate::{err, ErrorKind};
+
+use super::resolve_path;
+
+/// How confident the compiler is in a [`Suggestion`], see rustc's `Applicability`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Applicability {
+  /// The suggestion is definitely what the user intended, it can be applied automatically
+  MachineApplicable,
+  /// The suggestion may be what the user intended, but it is uncertain
+  MaybeIncorrect,
+  /// The suggestion contains placeholders like `(...)` or `{ /* fields */ }`
+  HasPlaceholders,
+  /// The applicability is unknown
+  #[default]
+  Unspecified,
+}
+
+impl Applicability {
+  /// Parse the `suggestion_applicability` of a span
+  pub fn parse<S: AsRef<str>>(value: S) -> Self {
+    match value.as_ref() {
+      "MachineApplicable" => Self::MachineApplicable,
+      "MaybeIncorrect" => Self::MaybeIncorrect,
+      "HasPlaceholders" => Self::HasPlaceholders,
+      _ => Self::Unspecified,
+    }
+  }
+}
+
+/// A replacement suggested by the compiler for a span of a file,
+/// parsed from the `compiler-message` JSON diagnostics (`--message-format=json`)
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Suggestion {
+  /// The file, as reported by the compiler
+  pub path: PathBuf,
+  /// The byte offset of the span in the file
+  pub byte_start: usize,
+  /// The byte offset of the end of the span, exclusive
+  pub byte_end: usize,
+  /// The text of the span when the diagnostic was emitted
+  pub original: String,
+  /// The text replacing the span
+  pub replacement: String,
+  /// Whether the suggestion can be applied automatically
+  pub applicability: Applicability,
+}
+
+impl Suggestion {
+  pub fn new<P: AsRef<Path>, O: AsRef<str>, R: AsRef<str>>(
+    path: P,
+    byte_start: usize,
+    original: O,
+    replacement: R,
+  ) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+      byte_start,
+      byte_end: byte_start + original.as_ref().len(),
+      original: original.as_ref().to_string(),
+      replacement: replacement.as_ref().to_string(),
+      applicability: Applicability::default(),
+    }
+  }
+
+  pub fn with_applicability(mut self, v: Applicability) -> Self {
+    self.applicability = v;
+    self
+  }
+
+  /// Check if the suggestion can be applied automatically
+  pub fn is_machine_applicable(&self) -> bool {
+    self.applicability == Applicability::MachineApplicable
+  }
+}
+
+impl Display for Suggestion {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}@{}..{}: {:?} -> {:?}",
+      self.path.display(),
+      self.byte_start,
+      self.byte_end,
+      self.original,
+      self.replacement
+    )
+  }
+}
+
+/// A diagnostic of a `compiler-message`: the text rustc would have printed
+/// and the replacements it suggests
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diagnostic {
+  /// The human readable diagnostic, possibly multiline
+  pub rendered: String,
+  /// The suggestions of the diagnostic and of its children
+  pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+  /// The key of the messages worth parsing
+  #[cfg(feature = "json")]
+  const REASON: &'static str = r#""reason":"compiler-message""#;
+
+  /// Parse a `compiler-message` message, `None` for any other line
+  #[cfg(feature = "json")]
+  pub fn parse<M: AsRef<str>>(message: M) -> Option<Self> {
+    let message = message.as_ref().trim();
+    if !message.starts_with('{') || !message.contains(Self::REASON) {
+      return None;
+    }
+    let value = serde_json::from_str::<serde_json::Value>(message).ok()?;
+    let diagnostic = value.get("message")?;
+    let mut suggestions = vec![];
+    Self::collect_suggestions(diagnostic, &mut suggestions);
+    Some(Self {
+      rendered: diagnostic.get("rendered")?.as_str()?.to_string(),
+      suggestions,
+    })
+  }
+
+  /// Parse a `compiler-message` message, never available without the `json` feature
+  #[cfg(not(feature = "json"))]
+  pub fn parse<M: AsRef<str>>(_message: M) -> Option<Self> {
+    None
+  }
+
+  /// Collect the suggested replacements of the spans of `diagnostic` and its children
+  #[cfg(feature = "json")]
+  fn collect_suggestions(diagnostic: &serde_json::Value, suggestions: &mut Vec<Suggestion>) {
+    let spans = diagnostic
+      .get("spans")
+      .and_then(|spans| spans.as_array())
+      .into_iter()
+      .flatten();
+    for span in spans {
+      let Some(replacement) = span
+        .get("suggested_replacement")
+        .and_then(|replacement| replacement.as_str())
+      else {
+        continue;
+      };
+      let fields = (
+        span.get("file_name").and_then(|path| path.as_str()),
+        span.get("byte_start").and_then(|start| start.as_u64()),
+        span.get("byte_end").and_then(|end| end.as_u64()),
+        Self::span_text(span),
+      );
+      let (Some(path), Some(byte_start), Some(byte_end), Some(original)) = fields else {
+        crate::dbg!("Skipped malformed suggestion span {}", span);
+        continue;
+      };
+      suggestions.push(Suggestion {
+        path: PathBuf::from(path),
+        byte_start: byte_start as usize,
+        byte_end: byte_end as usize,
+        original,
+        replacement: replacement.to_string(),
+        applicability: span
+          .get("suggestion_applicability")
+          .and_then(|applicability| applicability.as_str())
+          .map(Applicability::parse)
+          .unwrap_or_default(),
+      });
+    }
+    let children = diagnostic
+      .get("children")
+      .and_then(|children| children.as_array())
+      .into_iter()
+      .flatten();
+    for child in children {
+      Self::collect_suggestions(child, suggestions);
+    }
+  }
+
+  /// Retrieve the highlighted text of a span, its `highlight_start` and
+  /// `highlight_end` being 1-based char columns of each source line
+  #[cfg(feature = "json")]
+  fn span_text(span: &serde_json::Value) -> Option<String> {
+    let lines = span.get("text")?.as_array()?;
+    let mut ret = vec![];
+    for line in lines {
+      let text = line.get("text")?.as_str()?;
+      let start = line.get("highlight_start")?.as_u64()? as usize;
+      let end = line.get("highlight_end")?.as_u64()? as usize;
+      ret.push(
+        text
+          .chars()
+          .skip(start.saturating_sub(1))
+          .take(end.saturating_sub(start))
+          .collect::<String>(),
+      );
+    }
+    Some(ret.join("\n"))
+  }
+}
+
+/// Apply the machine-applicable `suggestions` to their files, returning the number of replacements.
+///
+/// Each file must still contain the original text of the spans, and the spans must not overlap:
+/// all the files are checked before any gets written, so that a refused fix leaves them untouched.
+/// The files are written to a temporary sibling first, then renamed over the original
+pub fn apply_suggestions(suggestions: &[Suggestion]) -> crate::Result<usize> {
+  let applicable = suggestions
+    .iter()
+    .filter(|suggestion| suggestion.is_machine_applicable())
+    .collect::<Vec<_>>();
+  if applicable.is_empty() {
+    return Err(match suggestions.is_empty() {
+      true => err!(ErrorKind::Fix, "no suggestion for this diagnostic"),
+      false => err!(
+        ErrorKind::Fix,
+        "the suggestion is not machine-applicable, it needs a review"
+      ),
+    });
+  }
+  let mut files = BTreeMap::<PathBuf, Vec<&Suggestion>>::new();
+  for suggestion in applicable {
+    files
+      .entry(resolve_path(&suggestion.path))
+      .or_default()
+      .push(suggestion);
+  }
+  let mut patched = vec![];
+  for (path, mut suggestions) in files {
+    suggestions.sort_by_key(|suggestion| (suggestion.byte_start, suggestion.byte_end));
+    suggestions.dedup();
+    let content = read_to_string(&path)
+      .map_err(|e| err!(ErrorKind::IO, "failed to read {}, {}", path.display(), e))?;
+    patched.push((
+      path.clone(),
+      patch(&path, content, &suggestions)?,
+      suggestions.len(),
+    ));
+  }
+  let mut ret = 0;
+  for (path, content, count) in patched {
+    write_atomically(&path, content)?;
+    ret += count;
+  }
+  Ok(ret)
+}
+
+/// Replace the spans of the sorted `suggestions` in the `content` of `path`
+fn patch(path: &Path, mut content: String, suggestions: &[&Suggestion]) -> crate::Result<String> {
+  for pair in suggestions.windows(2) {
+    if pair[1].byte_start < pair[0].byte_end {
+      return Err(err!(
+        ErrorKind::Fix,
+        "overlapping suggestions in {} at bytes {} and {}",
+        path.display(),
+        pair[0].byte_start,
+        pair[1].byte_start
+      ));
+    }
+  }
+  for suggestion in suggestions {
+    let current = content.get(suggestion.byte_start..suggestion.byte_end);
+    if current != Some(suggestion.original.as_str()) {
+      return Err(err!(
+        ErrorKind::Fix,
+        "{} changed since the build, expected {:?} at byte {}",
+        path.display(),
+        suggestion.original,
+        suggestion.byte_start
+      ));
+    }
+  }
+  // from the end, so that the offsets of the previous spans stay valid
+  for suggestion in suggestions.iter().rev() {
+    content.replace_range(
+      suggestion.byte_start..suggestion.byte_end,
+      &suggestion.replacement,
+    );
+  }
+  Ok(content)
+}
+
+/// Write `content` to a temporary file next to `path` and rename it over `path`
+fn write_atomically(path: &Path, content: String) -> crate::Result<()> {
+  let mut name = path.file_name().unwrap_or_default().to_os_string();
+  name.push(format!(".{}.nbuild", std::process::id()));
+  let tmp = path.with_file_name(name);
+  let written = write(&tmp, content).and_then(|_| {
+    if let Ok(meta) = path.metadata() {
+      std::fs::set_permissions(&tmp, meta.permissions())?;
+    }
+    rename(&tmp, path)
+  });
+  written.map_err(|e| {
+    let _ = remove_file(&tmp);
+    err!(ErrorKind::IO, "failed to write {}, {}", path.display(), e)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    path::PathBuf,
+  };
+
+  use crate::ErrorKind;
+
+  use super::{apply_suggestions, Applicability, Suggestion};
+
+  const MAIN: &str = "fn main() {\n  let mut x = 1;\n  let é = 2;\n  println!(\"{}\", x);\n}\n";
+
+  fn fixture(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nbuild-fix-{}-{}", test, std::process::id()));
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let path = dir.join("main.rs");
+    write(&path, MAIN).unwrap();
+    path
+  }
+
+  fn suggestion(path: &PathBuf, original: &str, replacement: &str) -> Suggestion {
+    Suggestion::new(path, MAIN.find(original).unwrap(), original, replacement)
+      .with_applicability(Applicability::MachineApplicable)
+  }
+
+  #[test]
+  fn apply() {
+    let path = fixture("apply");
+    assert_eq!(
+      apply_suggestions(&[suggestion(&path, "mut ", "")]).unwrap(),
+      1
+    );
+    assert_eq!(
+      read_to_string(&path).unwrap(),
+      MAIN.replace("let mut x", "let x")
+    );
+    // the file changed: the span does not match anymore
+    let e = apply_suggestions(&[suggestion(&path, "mut ", "")]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Fix);
+    assert!(e.to_string().contains("changed since the build"));
+    let _ = remove_dir_all(path.parent().unwrap());
+  }
+
+  #[test]
+  fn apply_multipart() {
+    let path = fixture("multipart");
+    // e.g. renaming a binding and its uses, listed out of order
+    let suggestions = [
+      suggestion(&path, "x);", "_y);"),
+      suggestion(&path, "é", "_e"),
+      suggestion(&path, "x = 1", "_y = 1"),
+      Suggestion::new(&path, MAIN.len(), "", "// fixed\n")
+        .with_applicability(Applicability::MachineApplicable),
+      // not applied
+      suggestion(&path, "main", "start").with_applicability(Applicability::MaybeIncorrect),
+    ];
+    assert_eq!(apply_suggestions(&suggestions).unwrap(), 4);
+    assert_eq!(
+      read_to_string(&path).unwrap(),
+      "fn main() {\n  let mut _y = 1;\n  let _e = 2;\n  println!(\"{}\", _y);\n}\n// fixed\n"
+    );
+    let _ = remove_dir_all(path.parent().unwrap());
+  }
+
+  #[test]
+  fn refuse() {
+    let path = fixture("refuse");
+    let e = apply_suggestions(&[]).unwrap_err();
+    assert!(e.to_string().contains("no suggestion"));
+    let e = apply_suggestions(&[
+      suggestion(&path, "mut ", "").with_applicability(Applicability::HasPlaceholders)
+    ])
+    .unwrap_err();
+    assert!(e.to_string().contains("not machine-applicable"));
+    // overlapping spans
+    let e = apply_suggestions(&[
+      suggestion(&path, "let mut", "let"),
+      suggestion(&path, "mut x", "y"),
+    ])
+    .unwrap_err();
+    assert!(e.to_string().contains("overlapping"));
+    // a span past the end or cutting a char
+    let e = apply_suggestions(&[
+      suggestion(&path, "mut ", ""),
+      Suggestion::new(&path, MAIN.find('é').unwrap() + 1, "", "?")
+        .with_applicability(Applicability::MachineApplicable),
+    ])
+    .unwrap_err();
+    assert!(e.to_string().contains("changed since the build"));
+    // nothing gets written when one of the suggestions is refused
+    assert_eq!(read_to_string(&path).unwrap(), MAIN);
+    let missing = path.with_file_name("missing.rs");
+    let e = apply_suggestions(&[suggestion(&missing, "mut ", "")]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::IO);
+    let _ = remove_dir_all(path.parent().unwrap());
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn parse() {
+    use super::Diagnostic;
+
+    const MESSAGE: &str = r#"{"reason":"compiler-message","package_id":"path+file:///tmp/demo#0.1.0","manifest_path":"/tmp/demo/Cargo.toml","target":{"kind":["bin"],"crate_types":["bin"],"name":"demo","src_path":"/tmp/demo/src/main.rs","edition":"2021","doctest":false,"test":true},"message":{"rendered":"warning: variable does not need to be mutable\n --> src/main.rs:2:7\n  |\n2 |   let mut x = 1;\n  |       ----^\n  |       |\n  |       help: remove this `mut`\n\n","$message_type":"diagnostic","children":[{"children":[],"code":null,"level":"note","message":"`#[warn(unused_mut)]` on by default","rendered":null,"spans":[]},{"children":[],"code":null,"level":"help","message":"remove this `mut`","rendered":null,"spans":[{"byte_end":22,"byte_start":18,"column_end":11,"column_start":7,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":"","suggestion_applicability":"MachineApplicable","text":[{"highlight_end":11,"highlight_start":7,"text":"  let mut x = 1;"}]}]}],"code":{"code":"unused_mut","explanation":null},"level":"warning","message":"variable does not need to be mutable","spans":[{"byte_end":23,"byte_start":18,"column_end":12,"column_start":7,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":null,"suggestion_applicability":null,"text":[{"highlight_end":12,"highlight_start":7,"text":"  let mut x = 1;"}]}]}}"#;
+
+    let diagnostic = Diagnostic::parse(MESSAGE).unwrap();
+    assert!(diagnostic
+      .rendered
+      .starts_with("warning: variable does not need to be mutable\n --> src/main.rs:2:7"));
+    assert_eq!(
+      diagnostic.suggestions,
+      vec![Suggestion::new("src/main.rs", 18, "mut ", "")
+        .with_applicability(Applicability::MachineApplicable)]
+    );
+    // a span over multiple lines, with placeholders
+    let multiline = r#"{"reason":"compiler-message","message":{"rendered":"error: missing fields\n","children":[],"spans":[{"byte_start":4,"byte_end":13,"file_name":"src/lib.rs","suggested_replacement":"S { .. }","suggestion_applicability":"HasPlaceholders","text":[{"text":"let S {","highlight_start":5,"highlight_end":8},{"text":"  a }","highlight_start":1,"highlight_end":6}]}]}}"#;
+    assert_eq!(
+      Diagnostic::parse(multiline).unwrap().suggestions,
+      vec![Suggestion::new("src/lib.rs", 4, "S {\n  a }", "S { .. }")
+        .with_applicability(Applicability::HasPlaceholders)]
+    );
+    for line in [
+      r#"{"reason":"compiler-artifact","target":{"kind":["lib"],"name":"demo"}}"#,
+      r#"{"reason":"compiler-message","message":{"spans":[]}}"#,
+      "warning: unused variable",
+    ] {
+      assert_eq!(Diagnostic::parse(line), None, "{}", line);
+    }
+  }
+}
@@ -2,12 +2,12 @@ use std::{fmt::Display, ops::Range, path::Path};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{err, CapturedMarker, DeclaredMarker, ErrorKind, MarkerRef};
+use crate::{err, BuildScript, CapturedMarker, CrateFailure, DeclaredMarker, ErrorKind, MarkerRef};
 
-use super::{active_rule, active_rule_name, Location};
+use super::{active_rule_name, with_active_rule, Location};
 
 /// Represent the kind of a BuildTag, put on each [`BuildEntry`]
-#[derive(Debug, Clone, PartialEq, PartialOrd, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Copy, Serialize, Deserialize)]
 pub enum BuildTagKind {
   /// A cargo warning
   Warning,
@@ -19,6 +19,116 @@ pub enum BuildTagKind {
   Hidden,
   /// A marker's location
   Location,
+  /// Part of a sub-section folded by default, see [`crate::FoldableMarker`]
+  Foldable,
+  /// cargo's `could not compile` trailer, see [`crate::CrateFailure`]
+  Trailer,
+  /// The output of a build script, see [`crate::BuildScript`]
+  BuildScript,
+  /// A line summing up the failure, e.g. `error: aborting due to 2 previous errors`.
+  /// Wins over the other markers of its entry, neither counted nor navigated
+  Summary,
+}
+
+impl BuildTagKind {
+  /// The one-character badge displayed in the margin, when markers are styled inline
+  pub fn badge(&self) -> char {
+    match self {
+      Self::Warning => 'W',
+      Self::Error => 'E',
+      Self::Note => 'N',
+      Self::Hidden => 'H',
+      Self::Location => 'L',
+      Self::Foldable => 'F',
+      Self::Trailer => 'T',
+      Self::BuildScript => 'S',
+      Self::Summary => 'Σ',
+    }
+  }
+
+  /// The severity of a marker kind, an entry matching several markers
+  /// only counts as the most severe one, see [`crate::BuildEntry::primary_marker`].
+  ///
+  /// `None` for the kinds that are not markers
+  pub fn severity(&self) -> Option<Severity> {
+    match self {
+      Self::Error => Some(Severity::Error),
+      Self::Warning => Some(Severity::Warning),
+      Self::Note => Some(Severity::Note),
+      Self::Hidden
+      | Self::Location
+      | Self::Foldable
+      | Self::Trailer
+      | Self::BuildScript
+      | Self::Summary => None,
+    }
+  }
+}
+
+/// A set of [`BuildTagKind`]s, all the log view needs to know about the tags of an entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BuildTagKinds(u16);
+
+impl BuildTagKinds {
+  /// Collect the kinds of some tags
+  pub fn of(tags: &[BuildTag]) -> Self {
+    tags.iter().map(|tag| tag.get_kind()).collect()
+  }
+
+  /// Add a kind to the set
+  pub fn insert(&mut self, kind: BuildTagKind) {
+    self.0 |= 1 << kind as u16;
+  }
+
+  /// Check if a kind is in the set
+  pub fn contains(&self, kind: BuildTagKind) -> bool {
+    self.0 & (1 << kind as u16) != 0
+  }
+
+  /// Check if the set is empty
+  pub fn is_empty(&self) -> bool {
+    self.0 == 0
+  }
+
+  /// Check if the set has a marker kind: an error, a warning or a note
+  pub fn has_severity(&self) -> bool {
+    [
+      BuildTagKind::Error,
+      BuildTagKind::Warning,
+      BuildTagKind::Note,
+    ]
+    .into_iter()
+    .any(|kind| self.contains(kind))
+  }
+}
+
+impl FromIterator<BuildTagKind> for BuildTagKinds {
+  fn from_iter<T: IntoIterator<Item = BuildTagKind>>(iter: T) -> Self {
+    let mut kinds = Self::default();
+    for kind in iter {
+      kinds.insert(kind);
+    }
+    kinds
+  }
+}
+
+/// The severity of the marker kinds, ordered from the least to the most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+  Note,
+  Warning,
+  Error,
+}
+
+impl Severity {
+  /// Retrieve the marker kind of this severity
+  pub fn kind(&self) -> BuildTagKind {
+    match self {
+      Self::Note => BuildTagKind::Note,
+      Self::Warning => BuildTagKind::Warning,
+      Self::Error => BuildTagKind::Error,
+    }
+  }
 }
 
 impl Display for BuildTagKind {
@@ -33,6 +143,9 @@ pub struct BuildTag {
   kind: BuildTagKind,
   marker: Option<MarkerRef>,
   location: Option<Location>,
+  label: Option<String>,
+  failure: Option<CrateFailure>,
+  script: Option<BuildScript>,
 }
 
 impl BuildTag {
@@ -42,11 +155,13 @@ impl BuildTag {
     range: Range<usize>,
     capture: C,
   ) -> crate::Result<Self> {
-    let declared = active_rule()
-      .markers
-      .iter()
-      .find(|marker| marker.tag == tag)
-      .cloned();
+    let declared = with_active_rule(|rule| {
+      rule
+        .markers
+        .iter()
+        .find(|marker| marker.tag == tag && !marker.disabled)
+        .cloned()
+    });
     let declared = declared.ok_or_else(|| {
       err!(
         ErrorKind::Rule,
@@ -65,6 +180,9 @@ impl BuildTag {
         declared,
       )),
       location: None,
+      label: None,
+      failure: None,
+      script: None,
     })
   }
 
@@ -86,6 +204,9 @@ impl BuildTag {
       kind: BuildTagKind::Hidden,
       marker: None,
       location: None,
+      label: None,
+      failure: None,
+      script: None,
     }
   }
 
@@ -95,6 +216,45 @@ impl BuildTag {
       kind: BuildTagKind::Location,
       marker: None,
       location: Some(Location::new(path.as_ref().to_path_buf(), line, column)),
+      label: None,
+      failure: None,
+      script: None,
+    }
+  }
+
+  /// Construct a foldable tag, `label` describes the folded sub-section
+  pub fn foldable<L: AsRef<str>>(label: L) -> Self {
+    Self {
+      kind: BuildTagKind::Foldable,
+      marker: None,
+      location: None,
+      label: Some(label.as_ref().to_string()),
+      failure: None,
+      script: None,
+    }
+  }
+
+  /// Construct a trailer tag, the entry stays in the enclosing block instead of starting its own
+  pub fn trailer(failure: CrateFailure) -> Self {
+    Self {
+      kind: BuildTagKind::Trailer,
+      marker: None,
+      location: None,
+      label: None,
+      failure: Some(failure),
+      script: None,
+    }
+  }
+
+  /// Construct a build script tag, labelled with the package of the build script
+  pub fn build_script(script: BuildScript) -> Self {
+    Self {
+      kind: BuildTagKind::BuildScript,
+      marker: None,
+      location: None,
+      label: Some(script.package.clone()),
+      failure: None,
+      script: Some(script),
     }
   }
 
@@ -113,6 +273,18 @@ impl BuildTag {
   pub fn get_location(&self) -> Option<&Location> {
     self.location.as_ref()
   }
+
+  pub fn get_label(&self) -> Option<&str> {
+    self.label.as_deref()
+  }
+
+  pub fn get_failure(&self) -> Option<&CrateFailure> {
+    self.failure.as_ref()
+  }
+
+  pub fn get_build_script(&self) -> Option<&BuildScript> {
+    self.script.as_ref()
+  }
 }
 
 impl PartialEq for BuildTag {
@@ -0,0 +1,156 @@
+use std::{fmt::Display, ops::Range};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{BuildTagKind, CrateFailure};
+
+lazy_static! {
+  /// cargo's trailer of a target that built with warnings,
+  /// e.g. ``warning: `demo` (lib test) generated 2 warnings (1 duplicate)``
+  static ref GENERATED: Regex = Regex::new(r"^warning: `([^`]+)` \(([^)]+)\) generated \d+ warnings?")
+    .expect("invalid regular expression");
+}
+
+/// A target of a package compiled by cargo, e.g. `demo` (`lib test`).
+///
+/// cargo compiles a crate once per target, the diagnostics of its lib
+/// get repeated by its tests
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CompileTarget {
+  /// The name of the package
+  pub package: String,
+  /// The target, e.g. `lib`, `bin "demo"` or `lib test`
+  pub target: String,
+}
+
+impl CompileTarget {
+  pub fn new<P: AsRef<str>, T: AsRef<str>>(package: P, target: T) -> Self {
+    Self {
+      package: package.as_ref().to_string(),
+      target: target.as_ref().to_string(),
+    }
+  }
+
+  /// Parse the trailer ending the diagnostics of a target: `generated N warnings`,
+  /// or `could not compile` with its target. `None` for any other line
+  pub fn parse<M: AsRef<str>>(message: M) -> Option<Self> {
+    let message = message.as_ref().trim_start();
+    if GENERATED.is_match(message) {
+      let captures = GENERATED.captures(message)?;
+      return Some(Self::new(&captures[1], &captures[2]));
+    }
+    let failure = CrateFailure::parse(message)?;
+    Some(Self::new(failure.name, failure.target?))
+  }
+}
+
+impl Display for CompileTarget {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} ({})", self.package, self.target)
+  }
+}
+
+/// A diagnostic block repeated by another target of its package, see
+/// [`super::BuildOutput::duplicates`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateBlock {
+  /// The entries of the repeated block, up to the trailer of its target
+  pub range: Range<usize>,
+  /// The kind of the block's marker
+  pub kind: BuildTagKind,
+  /// The target repeating the block
+  pub target: CompileTarget,
+  /// The target the block was first reported for
+  pub first: CompileTarget,
+}
+
+/// The diagnostics of one target, the duplicates included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetCounts {
+  pub target: CompileTarget,
+  pub errors: usize,
+  pub warnings: usize,
+  /// The blocks already reported for another target, see [`DuplicateBlock`]
+  pub duplicates: usize,
+}
+
+impl TargetCounts {
+  pub fn new(target: CompileTarget) -> Self {
+    Self {
+      target,
+      errors: 0,
+      warnings: 0,
+      duplicates: 0,
+    }
+  }
+}
+
+impl Display for TargetCounts {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}: {} error(s), {} warning(s)",
+      self.target, self.errors, self.warnings
+    )?;
+    if self.duplicates > 0 {
+      write!(f, " ({} duplicate(s))", self.duplicates)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CompileTarget, TargetCounts};
+
+  #[test]
+  fn parse() {
+    for (line, expected) in [
+      ("warning: `demo` (lib) generated 1 warning", ("demo", "lib")),
+      (
+        "warning: `demo` (lib test) generated 2 warnings (1 duplicate)",
+        ("demo", "lib test"),
+      ),
+      (
+        "warning: `foo-bar` (bin \"foo\") generated 3 warnings (run `cargo fix --bin \"foo\"` to apply 1 suggestion)",
+        ("foo-bar", "bin \"foo\""),
+      ),
+      (
+        "error: could not compile `demo` (lib test) due to 1 previous error; 1 warning emitted",
+        ("demo", "lib test"),
+      ),
+    ] {
+      assert_eq!(
+        CompileTarget::parse(line),
+        Some(CompileTarget::new(expected.0, expected.1)),
+        "{}",
+        line
+      );
+    }
+    for line in [
+      "warning: unused variable: `x`",
+      "warning: 1 warning emitted",
+      // older cargo versions, without the target
+      "error: could not compile `demo` due to previous error",
+      "   Compiling demo v0.1.0",
+    ] {
+      assert_eq!(CompileTarget::parse(line), None, "{}", line);
+    }
+  }
+
+  #[test]
+  fn display() {
+    let mut counts = TargetCounts::new(CompileTarget::new("demo", "lib test"));
+    counts.warnings = 2;
+    assert_eq!(
+      counts.to_string(),
+      "demo (lib test): 0 error(s), 2 warning(s)"
+    );
+    counts.duplicates = 1;
+    assert_eq!(
+      counts.to_string(),
+      "demo (lib test): 0 error(s), 2 warning(s) (1 duplicate(s))"
+    );
+  }
+}
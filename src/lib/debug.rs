@@ -1,71 +1,306 @@
 use std::{
   collections::VecDeque,
   fs::File,
-  io::Write,
-  sync::{Arc, Mutex, MutexGuard},
-  time::{Duration},
+  io::{self, BufWriter, Write},
+  path::Path,
+  sync::mpsc::{channel, RecvTimeoutError, Sender},
+  thread::spawn,
+  time::Duration,
 };
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 use lazy_static::lazy_static;
 
-use crate::TryLockFor;
-
-/// The debug file path
+/// The debug file path, when the messages are not redirected to a run directory
 pub const DEBUG_FILE_PATH: &'static str = ".cargo-nbuild.log";
 
 lazy_static! {
+  /// The queue of the debug file, held until redirected, see [`Debug::redirect`]
+  static ref debug_log: DebugSink = DebugSink::held();
+}
 
-  /// The handle to the debug file
-  static ref debug_log_handle: Arc<Mutex<File>> = Arc::new(Mutex::new(
-    File::create(DEBUG_FILE_PATH).expect("failed to create debug log")
-  ));
+/// Format a line of the debug log: its date, then `msg`.
+/// Shared by the exported status history, see [`crate::StatusBar::export`]
+pub fn timestamped_line(at: DateTime<Local>, msg: &str) -> String {
+  format!("{}  {}", at.format("%Y-%m-%d %H:%M:%S"), msg)
+}
 
-  /// A buffer to queue messages when the [`debug_log_handle`] couldn't be locked
-  static ref debug_log_buf: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
+/// A message sent to the writer thread of a [`DebugSink`]
+enum DebugMessage {
+  Line(DateTime<Local>, String),
+  /// Write the buffered lines, then acknowledge
+  Flush(Sender<()>),
+  /// Write the next lines to another writer
+  Redirect(Box<dyn Write + Send>),
 }
 
-/// The Debug struct allows custom logging to a file since we can't log to UI
-pub struct Debug {}
+/// Where the writer thread of a [`DebugSink`] writes
+enum DebugOutput {
+  /// The lines held until a redirect, past [`DebugSink::HELD_CAPACITY`] bytes
+  /// they go to [`DEBUG_FILE_PATH`]
+  Held(Vec<u8>),
+  Writer(BufWriter<Box<dyn Write + Send>>),
+}
 
-impl Debug {
-  fn format<S: AsRef<str>>(msg: S) -> String {
-    format!(
-      "{}  {}\n",
-      Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-      msg.as_ref(),
-    )
+impl Write for DebugOutput {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match self {
+      Self::Held(held) if held.len() + buf.len() > DebugSink::HELD_CAPACITY => {
+        let mut writer = BufWriter::new(match File::create(DEBUG_FILE_PATH) {
+          Ok(file) => Box::new(file) as Box<dyn Write + Send>,
+          Err(_) => Box::new(io::sink()),
+        });
+        writer.write_all(held)?;
+        *self = Self::Writer(writer);
+        self.write(buf)
+      }
+      Self::Held(held) => {
+        held.extend_from_slice(buf);
+        Ok(buf.len())
+      }
+      Self::Writer(writer) => writer.write(buf),
+    }
   }
 
-  fn write<S: AsRef<str>>(f: &mut MutexGuard<'_, File>, s: S) {
-    let entry = Self::format(s);
-    let _ = f.write(entry.as_bytes());
-    let _ = f.flush();
+  fn flush(&mut self) -> io::Result<()> {
+    match self {
+      Self::Held(_) => Ok(()),
+      Self::Writer(writer) => writer.flush(),
+    }
   }
+}
+
+/// A run of repeated lines, see [`Repeats`]
+struct Repeat {
+  /// The number of lines of the repeated cycle
+  period: usize,
+  /// The number of lines suppressed so far
+  lines: usize,
+  since: DateTime<Local>,
+  last: DateTime<Local>,
+}
+
+/// Collapse the consecutive identical lines, or cycles of up to [`Repeats::MAX_PERIOD`]
+/// alternating lines, into a single `… repeated N times (over Ts)` line
+#[derive(Default)]
+struct Repeats {
+  /// The last written lines, most recent last
+  written: VecDeque<String>,
+  repeat: Option<Repeat>,
+}
+
+impl Repeats {
+  /// The longest cycle of alternating lines detected
+  const MAX_PERIOD: usize = 4;
 
-  fn queue<S: AsRef<str>>(s: S) {
-    if let Ok(mut buf) = debug_log_buf.try_lock_for(Duration::from_millis(50)) {
-      buf.push_back(s.as_ref().to_string())
+  /// Retrieve the lines to write for `msg`, none while it repeats the previous ones
+  fn push(&mut self, at: DateTime<Local>, msg: String) -> Vec<(DateTime<Local>, String)> {
+    if let Some(repeat) = self.repeat.as_mut() {
+      let cycle = self.written.len() - repeat.period;
+      if self.written[cycle + repeat.lines % repeat.period] == msg {
+        repeat.lines += 1;
+        repeat.last = at;
+        return vec![];
+      }
+    }
+    let mut ret = self.finish();
+    if let Some(period) =
+      (1..=self.written.len()).find(|p| self.written[self.written.len() - p] == msg)
+    {
+      self.repeat = Some(Repeat {
+        period,
+        lines: 1,
+        since: at,
+        last: at,
+      });
+      return ret;
     }
+    self.remember(msg.clone());
+    ret.push((at, msg));
+    ret
   }
 
-  /// Log a message to the [`debug_log_handle`]. If the lock can't be acquired
-  /// the messages are queued in [`debug_log_buf`]
-  pub fn log<S: AsRef<str>>(msg: S) {
-    if let Ok(mut f) = debug_log_handle.try_lock_for(Duration::from_millis(50)) {
-      if let Ok(mut buf) = debug_log_buf.try_lock_for(Duration::from_millis(50)) {
-        while let Some(e) = buf.pop_front() {
-          Self::write(&mut f, e.as_str().trim());
+  /// Retrieve the lines summarizing the current run, if any
+  fn finish(&mut self) -> Vec<(DateTime<Local>, String)> {
+    let Some(repeat) = self.repeat.take() else {
+      return vec![];
+    };
+    let cycle = self.written.len() - repeat.period;
+    let times = repeat.lines / repeat.period;
+    let mut ret = vec![];
+    if times > 0 {
+      let over = (repeat.last - repeat.since).num_milliseconds() as f64 / 1000.0;
+      ret.push((
+        repeat.last,
+        match repeat.period {
+          1 => format!("… repeated {} times (over {:.1}s)", times, over),
+          n => format!(
+            "… last {} lines repeated {} times (over {:.1}s)",
+            n, times, over
+          ),
+        },
+      ));
+    }
+    // an incomplete cycle is written as is
+    let partial = self
+      .written
+      .range(cycle..cycle + repeat.lines % repeat.period)
+      .cloned()
+      .collect::<Vec<_>>();
+    for line in partial {
+      ret.push((repeat.last, line.clone()));
+      self.remember(line);
+    }
+    ret
+  }
+
+  fn remember(&mut self, line: String) {
+    if self.written.len() == Self::MAX_PERIOD {
+      self.written.pop_front();
+    }
+    self.written.push_back(line);
+  }
+}
+
+/// A queue of log lines written by a dedicated thread, so that logging never blocks.
+///
+/// The lines are flushed every [`DebugSink::FLUSH_INTERVAL`] or [`DebugSink::FLUSH_BATCH`] lines,
+/// and on [`DebugSink::flush`]. The repeated lines are collapsed, see [`Repeats`]
+#[derive(Debug, Clone)]
+pub struct DebugSink {
+  queue: Sender<DebugMessage>,
+}
+
+impl DebugSink {
+  /// The longest time a line waits in the buffer
+  pub const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+  /// The number of lines buffered before a flush
+  pub const FLUSH_BATCH: usize = 64;
+
+  /// The longest time [`DebugSink::flush`] waits for the writer thread
+  pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+  /// The size of the lines held by [`DebugSink::held`] until they get written to [`DEBUG_FILE_PATH`]
+  pub const HELD_CAPACITY: usize = 1 << 20;
+
+  /// Spawn the writer thread, it stops once every sink is dropped
+  #[cfg(test)]
+  pub fn spawn<W: Write + Send + 'static>(writer: W) -> Self {
+    Self::start(DebugOutput::Writer(BufWriter::new(Box::new(writer))))
+  }
+
+  /// Spawn the writer thread holding the lines until [`DebugSink::redirect`]
+  pub fn held() -> Self {
+    Self::start(DebugOutput::Held(vec![]))
+  }
+
+  fn start(mut writer: DebugOutput) -> Self {
+    let (queue, messages) = channel::<DebugMessage>();
+    spawn(move || {
+      let mut pending = 0;
+      let mut repeats = Repeats::default();
+      let write_lines = |writer: &mut DebugOutput, lines: Vec<(DateTime<Local>, String)>| {
+        for (at, msg) in lines {
+          let _ = writeln!(writer, "{}", timestamped_line(at, &msg));
         }
+      };
+      loop {
+        match messages.recv_timeout(Self::FLUSH_INTERVAL) {
+          Ok(DebugMessage::Line(at, msg)) => {
+            let lines = repeats.push(at, msg);
+            if lines.is_empty() {
+              continue;
+            }
+            pending += lines.len();
+            write_lines(&mut writer, lines);
+            if pending < Self::FLUSH_BATCH {
+              continue;
+            }
+          }
+          Ok(DebugMessage::Flush(ack)) => {
+            write_lines(&mut writer, repeats.finish());
+            let _ = writer.flush();
+            pending = 0;
+            let _ = ack.send(());
+            continue;
+          }
+          Ok(DebugMessage::Redirect(next)) => {
+            write_lines(&mut writer, repeats.finish());
+            let mut next = BufWriter::new(next);
+            match &writer {
+              DebugOutput::Held(held) => {
+                let _ = next.write_all(held);
+              }
+              DebugOutput::Writer(_) => {
+                let _ = writer.flush();
+              }
+            }
+            writer = DebugOutput::Writer(next);
+          }
+          Err(RecvTimeoutError::Timeout) if pending == 0 => continue,
+          Err(RecvTimeoutError::Timeout) => {}
+          Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let _ = writer.flush();
+        pending = 0;
       }
-      Self::write(&mut f, msg);
-    } else {
-      Self::queue(msg);
-    }
+      write_lines(&mut writer, repeats.finish());
+      let _ = writer.flush();
+    });
+    Self { queue }
+  }
+
+  /// Queue a line, never blocks
+  pub fn log<S: AsRef<str>>(&self, msg: S) {
+    let _ = self
+      .queue
+      .send(DebugMessage::Line(Local::now(), msg.as_ref().to_string()));
+  }
+
+  /// Write the next lines to `writer`, along with the held ones, see [`DebugSink::held`]
+  pub fn redirect<W: Write + Send + 'static>(&self, writer: W) {
+    let _ = self.queue.send(DebugMessage::Redirect(Box::new(writer)));
+  }
+
+  /// Wait for the queued lines to be written, up to [`DebugSink::FLUSH_TIMEOUT`].
+  ///
+  /// Returns whether they all got written in time
+  pub fn flush(&self) -> bool {
+    let (ack, flushed) = channel();
+    self.queue.send(DebugMessage::Flush(ack)).is_ok()
+      && flushed.recv_timeout(Self::FLUSH_TIMEOUT).is_ok()
+  }
+}
+
+/// The Debug struct allows custom logging to a file since we can't log to UI
+pub struct Debug {}
+
+impl Debug {
+  /// Queue a message for the debug file, see [`DebugSink`]
+  pub fn log<S: AsRef<str>>(msg: S) {
+    debug_log.log(msg);
+  }
+
+  /// Write the queued messages, called before exiting and when panicking
+  pub fn flush() {
+    debug_log.flush();
+  }
+
+  /// Write the messages to the file `path`, including the ones logged until now
+  pub fn redirect<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    debug_log.redirect(File::create(path)?);
+    Ok(())
+  }
+
+  /// Close the debug file, the next messages are dropped
+  pub fn close() {
+    debug_log.redirect(io::sink());
+    debug_log.flush();
   }
 }
 
-/// A custom debug macro writing it's output to the [`debug_log_handle`]
+/// A custom debug macro writing it's output to the [`debug_log`]
 #[macro_export]
 macro_rules! dbg {
   ($msg:expr) => {
@@ -76,3 +311,160 @@ macro_rules! dbg {
     $crate::Debug::log(format!("{}", format_args!($( $args, )+)))
   };
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    thread::spawn,
+    time::{Duration, Instant},
+  };
+
+  use super::DebugSink;
+
+  /// A writer shared with the test
+  #[derive(Clone, Default)]
+  struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn redirect() {
+    let sink = DebugSink::held();
+    sink.log("held");
+    sink.flush();
+    let buf = SharedBuf::default();
+    sink.redirect(buf.clone());
+    sink.log("redirected");
+    sink.flush();
+    let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    let lines = content.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{:?}", content);
+    assert!(lines[0].ends_with("  held"));
+    assert!(lines[1].ends_with("  redirected"));
+  }
+
+  /// Log the messages, then retrieve the written ones without their timestamp
+  fn written(messages: &[&str]) -> Vec<String> {
+    let buf = SharedBuf::default();
+    let sink = DebugSink::spawn(buf.clone());
+    for msg in messages {
+      sink.log(msg);
+    }
+    sink.flush();
+    let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    content
+      .lines()
+      .map(|line| line.split_once("  ").unwrap().1.to_string())
+      .collect()
+  }
+
+  #[test]
+  fn repeated() {
+    let mut messages = vec!["start"];
+    messages.extend(["idle"; 1000]);
+    messages.push("done");
+    let lines = written(&messages);
+    assert_eq!(lines.len(), 4, "{:?}", lines);
+    assert_eq!(lines[..2], ["start", "idle"]);
+    assert!(
+      lines[2].starts_with("… repeated 999 times (over "),
+      "{:?}",
+      lines[2]
+    );
+    assert_eq!(lines[3], "done");
+    // the run ends with the flush
+    let lines = written(&["a", "a"]);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("… repeated 1 times"));
+  }
+
+  #[test]
+  fn repeated_cycle() {
+    let mut messages = vec![];
+    for _ in 0..100 {
+      messages.extend(["tick", "tock"]);
+    }
+    messages.extend(["tick", "other"]);
+    let lines = written(&messages);
+    assert_eq!(lines.len(), 5, "{:?}", lines);
+    assert_eq!(lines[..2], ["tick", "tock"]);
+    assert!(lines[2].starts_with("… last 2 lines repeated 99 times"));
+    // the incomplete cycle is kept
+    assert_eq!(lines[3..], ["tick", "other"]);
+    // alternating over more lines than the cap
+    let messages = ["a", "b", "c", "d", "e", "a", "b", "c", "d", "e"];
+    assert_eq!(written(&messages), messages);
+    assert_eq!(
+      written(&["a", "b", "c", "a", "d"]),
+      ["a", "b", "c", "a", "d"]
+    );
+  }
+
+  #[test]
+  fn periodic_flush() {
+    let buf = SharedBuf::default();
+    let sink = DebugSink::spawn(buf.clone());
+    sink.log("first");
+    std::thread::sleep(DebugSink::FLUSH_INTERVAL * 3);
+    let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    assert!(content.ends_with("  first\n"), "{:?}", content);
+  }
+
+  #[test]
+  fn stress() {
+    const THREADS: usize = 8;
+    const MESSAGES: usize = 10_000;
+    let buf = SharedBuf::default();
+    let sink = DebugSink::spawn(buf.clone());
+    let threads = (0..THREADS)
+      .map(|th| {
+        let sink = sink.clone();
+        spawn(move || {
+          let mut slow_calls = 0;
+          let start = Instant::now();
+          for i in 0..MESSAGES {
+            let call = Instant::now();
+            sink.log(format!("thread {} message {}", th, i));
+            if call.elapsed() > Duration::from_millis(1) {
+              slow_calls += 1;
+            }
+          }
+          (start.elapsed(), slow_calls)
+        })
+      })
+      .collect::<Vec<_>>();
+    for th in threads {
+      let (total, slow_calls) = th.join().unwrap();
+      // coarse bounds, the scheduler may preempt any call
+      assert!(
+        total / MESSAGES as u32 <= Duration::from_micros(50),
+        "{:?} per call",
+        total / MESSAGES as u32
+      );
+      assert!(
+        slow_calls <= MESSAGES / 100,
+        "{} calls over 1ms",
+        slow_calls
+      );
+    }
+    // a loaded machine may write the lines slower than the flush timeout
+    assert!((0..10).any(|_| sink.flush()));
+    let content = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(content.lines().count(), THREADS * MESSAGES);
+    for th in 0..THREADS {
+      let last = format!("  thread {} message {}\n", th, MESSAGES - 1);
+      assert!(content.contains(&last), "missing {:?}", last);
+    }
+  }
+}
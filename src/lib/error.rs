@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::Path};
 
 use crate::Location;
 
@@ -19,6 +19,10 @@ pub enum ErrorKind {
   FileNotFound,
   /// Rule
   Rule,
+  /// A thread panicked
+  Thread,
+  /// A quick-fix could not be applied
+  Fix,
 }
 
 impl Display for ErrorKind {
@@ -34,11 +38,76 @@ impl Display for ErrorKind {
         Self::Codec => "codec",
         Self::FileNotFound => "file not found",
         Self::Rule => "rule",
+        Self::Thread => "thread",
+        Self::Fix => "quick-fix",
       }
     )
   }
 }
 
+/// The offending line of a file that failed to parse, with a caret under the column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+  /// The position of the error, 1-based
+  pub location: Location,
+  /// The offending line, empty until [`Snippet::in_file`]
+  pub text: String,
+}
+
+impl Snippet {
+  /// Construct a snippet at a 1-based `line` and `column`, of a file still unknown
+  pub fn at(line: usize, column: usize) -> Self {
+    Self {
+      location: Location::new("", Some(line.max(1)), Some(column.max(1))),
+      text: String::new(),
+    }
+  }
+
+  /// Construct a snippet at the byte `offset` of `content`
+  pub fn at_offset(content: &str, offset: usize) -> Self {
+    let mut offset = offset.min(content.len());
+    while !content.is_char_boundary(offset) {
+      offset -= 1;
+    }
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|at| at + 1).unwrap_or(0);
+    Self::at(line, before[line_start..].chars().count() + 1)
+  }
+
+  /// Attach the file `path` and pick the offending line from its `content`
+  pub fn in_file<P: AsRef<Path>>(mut self, path: P, content: &str) -> Self {
+    *self.location.path_mut() = path.as_ref().to_path_buf();
+    let line = self.location.line().unwrap_or(1);
+    self.text = content
+      .lines()
+      .nth(line - 1)
+      .unwrap_or_default()
+      .trim_end()
+      .to_string();
+    self
+  }
+}
+
+impl Display for Snippet {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let line = self.location.line().unwrap_or(1).to_string();
+    let column = self.location.column().unwrap_or(1);
+    let gutter = " ".repeat(line.len());
+    writeln!(f, "{}--> {}", gutter, self.location)?;
+    writeln!(f, "{} |", gutter)?;
+    // the tabs keep their width under the caret
+    let indent = self
+      .text
+      .chars()
+      .take(column - 1)
+      .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+      .collect::<String>();
+    writeln!(f, "{} | {}", line, self.text)?;
+    write!(f, "{} | {}^", gutter, indent)
+  }
+}
+
 /// Represent an error
 #[derive(Debug, Clone)]
 pub struct Error {
@@ -46,6 +115,8 @@ pub struct Error {
   message: Option<String>,
   cause: Option<Box<Error>>,
   location: Option<Location>,
+  /// Boxed, the errors stay small
+  snippet: Option<Box<Snippet>>,
 }
 
 impl Error {
@@ -61,6 +132,7 @@ impl Error {
       message: msg,
       cause,
       location,
+      snippet: None,
     }
   }
 
@@ -79,6 +151,12 @@ impl Error {
     self
   }
 
+  /// Attach the position in the parsed file, see [`Snippet`]
+  pub fn with_snippet(mut self, snippet: Snippet) -> Self {
+    self.snippet = Some(Box::new(snippet));
+    self
+  }
+
   /// Retrieve the error kind
   pub fn kind(&self) -> ErrorKind {
     self.kind
@@ -98,6 +176,14 @@ impl Error {
   pub fn location(&self) -> Option<&Location> {
     self.location.as_ref()
   }
+
+  /// Retrieve the position in the parsed file, of this error or of its causes
+  pub fn snippet(&self) -> Option<&Snippet> {
+    self
+      .snippet
+      .as_deref()
+      .or_else(|| self.cause().and_then(|cause| cause.snippet()))
+  }
 }
 
 impl std::error::Error for Error {}
@@ -146,3 +232,23 @@ macro_rules! err {
     )
   };
 }
+
+#[cfg(test)]
+mod tests {
+  use super::Snippet;
+
+  #[test]
+  fn snippet() {
+    let content = "first\nsecond line\n\tthird";
+    let snippet = Snippet::at_offset(content, 13);
+    assert_eq!(snippet.location.line(), Some(2));
+    assert_eq!(snippet.location.column(), Some(8));
+    assert_eq!(
+      snippet.in_file("nbuild.json", content).to_string(),
+      " --> nbuild.json:2:8\n  |\n2 | second line\n  |        ^"
+    );
+    let snippet = Snippet::at(3, 2).in_file("nbuild.json", content);
+    assert_eq!(snippet.text, "\tthird");
+    assert!(snippet.to_string().ends_with("3 | \tthird\n  | \t^"));
+  }
+}
@@ -1,6 +1,9 @@
 use std::{
-  ops::{Deref, DerefMut, Range},
+  fmt::Display,
+  hash::{Hash, Hasher},
+  ops::{Deref, DerefMut, Index, Range},
   sync::Arc,
+  time::{Duration, Instant},
 };
 
 use lazy_static::lazy_static;
@@ -8,7 +11,10 @@ use ratatui::style::{Style, Stylize};
 use regex::Regex;
 use serde::{de::Visitor, ser::SerializeStruct as _, Deserialize, Serialize};
 
-use crate::{rules, BuildEntry, BuildTag, BuildTagKind, MarkedBlock, Rule, DEFAULT_RULES};
+use crate::{
+  human_duration, rules, serialize_style, BuildEntry, BuildTag, BuildTagKind, MarkedBlock, Rule,
+  StyleRepr, DEFAULT_RULES,
+};
 
 pub fn known_marker(k: BuildTagKind) -> Option<DeclaredMarker> {
   for r in rules().iter() {
@@ -51,6 +57,7 @@ use serde::{self};
 
 /// Represent a marker definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "DeclaredMarkerSpec")]
 pub struct DeclaredMarker {
   /// The tag kind
   pub tag: BuildTagKind,
@@ -58,10 +65,47 @@ pub struct DeclaredMarker {
   #[serde(with = "regex_serde")]
   pub regex: Regex,
   /// The final style applied to the marker
+  #[serde(serialize_with = "serialize_style")]
   pub style: Style,
+  /// Secondary markers (notes by default) are tagged and navigable but
+  /// never terminate the enclosing block
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub secondary: Option<bool>,
+  /// Disabled markers are kept in the rule but never match
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub disabled: bool,
+}
+
+/// The deserialized form of a [`DeclaredMarker`], its style gets validated on conversion
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeclaredMarkerSpec {
+  pub tag: BuildTagKind,
+  #[serde(with = "regex_serde")]
+  pub regex: Regex,
+  pub style: StyleRepr,
+  #[serde(default)]
+  pub secondary: Option<bool>,
+  #[serde(default)]
+  pub disabled: bool,
 }
 
-mod regex_serde {
+impl TryFrom<DeclaredMarkerSpec> for DeclaredMarker {
+  type Error = String;
+
+  fn try_from(value: DeclaredMarkerSpec) -> Result<Self, Self::Error> {
+    let style =
+      Style::try_from(value.style).map_err(|e| format!("marker {:?}: {}", value.tag, e))?;
+    Ok(Self {
+      tag: value.tag,
+      regex: value.regex,
+      style,
+      secondary: value.secondary,
+      disabled: value.disabled,
+    })
+  }
+}
+
+pub(crate) mod regex_serde {
   use regex::Regex;
   use serde::{self, Deserialize, Deserializer, Serializer};
 
@@ -84,20 +128,87 @@ mod regex_serde {
 
 impl DeclaredMarker {
   pub fn new(tag: BuildTagKind, regex: Regex, style: Style) -> Self {
-    Self { tag, regex, style }
+    Self {
+      tag,
+      regex,
+      style,
+      secondary: None,
+      disabled: false,
+    }
+  }
+
+  pub fn with_secondary(mut self, v: bool) -> Self {
+    self.secondary = Some(v);
+    self
+  }
+
+  /// Keep the marker in the rule without matching, see [`DeclaredMarker::disabled`]
+  pub fn with_disabled(mut self, v: bool) -> Self {
+    self.disabled = v;
+    self
+  }
+
+  /// Check if this marker is secondary, [`BuildTagKind::Note`] defaults to `true`
+  pub fn is_secondary(&self) -> bool {
+    self.secondary.unwrap_or(self.tag == BuildTagKind::Note)
+  }
+
+  /// Retrieve the identity of this marker, see [`MarkerKey`]
+  pub fn key(&self) -> MarkerKey<'_> {
+    MarkerKey {
+      tag: self.tag,
+      regex: self.regex.as_str(),
+    }
   }
 }
+
+/// The identity of a [`DeclaredMarker`]: its tag and the source of its regex.
+///
+/// The presentation (style, ...) is left out, so that a config restyling
+/// a marker does not change which markers are equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MarkerKey<'a> {
+  pub tag: BuildTagKind,
+  pub regex: &'a str,
+}
+
 impl PartialEq for DeclaredMarker {
   fn eq(&self, other: &Self) -> bool {
-    self.tag == other.tag
-      && self.regex.as_str() == other.regex.as_str()
-      && self.style == other.style
+    self.key() == other.key()
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Eq for DeclaredMarker {}
+
+impl Hash for DeclaredMarker {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.key().hash(state);
+  }
+}
+
+/// A marker found in a [`BuildEntry`]: what was captured and the [`DeclaredMarker`] that matched.
+///
+/// Two references are equal if they point at the same range with the same [`MarkerKey`]
+#[derive(Debug, Clone)]
 pub struct MarkerRef(Option<CapturedMarker>, DeclaredMarker);
 
+impl PartialEq for MarkerRef {
+  fn eq(&self, other: &Self) -> bool {
+    self.1.key() == other.1.key()
+      && self.0.as_ref().map(|captured| &captured.range)
+        == other.0.as_ref().map(|captured| &captured.range)
+  }
+}
+
+impl Eq for MarkerRef {}
+
+impl Hash for MarkerRef {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.1.key().hash(state);
+    self.0.as_ref().map(|captured| &captured.range).hash(state);
+  }
+}
+
 impl MarkerRef {
   pub fn new(capture: Option<CapturedMarker>, declared: DeclaredMarker) -> Self {
     Self(capture, declared)
@@ -118,17 +229,59 @@ impl MarkerRef {
   pub fn declared(&self) -> &DeclaredMarker {
     &self.1
   }
+
+  /// The code captured between brackets, e.g. `E0308` for `error[E0308]:`
+  pub fn code(&self) -> Option<&str> {
+    let captured = self.captured()?;
+    let (_, code) = captured.text.split_once('[')?;
+    let (code, _) = code.split_once(']')?;
+    Some(code)
+  }
+
+  /// The badge displayed in the compact gutter: the code captured between brackets
+  /// (e.g. `E0308` for `error[E0308]:`), prefixed by the [`BuildTagKind::badge`]
+  /// unless it already starts with it, or the kind badge alone
+  pub fn badge(&self) -> String {
+    let badge = self.kind().badge();
+    match self.code() {
+      Some(code) if code.starts_with(badge) => code.to_string(),
+      Some(code) => format!("{}{}", badge, code),
+      None => badge.to_string(),
+    }
+  }
+}
+
+/// The index of a marker in the [`Markers::tags`] list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MarkerId(pub usize);
+
+impl Display for MarkerId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+/// The index of a [`BuildEntry`] in the build output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct EntryId(pub usize);
+
+impl Display for EntryId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct MarkerSelection {
-  pub marker_id: usize,
-  pub entry_id: usize,
+  pub marker_id: MarkerId,
+  pub entry_id: EntryId,
+  /// The selected bytes of the raw message of the entry, e.g. a search match,
+  /// see [`crate::LogEntry::display_region`] for their place in the displayed line
   pub region: Option<Range<usize>>,
 }
 
 impl MarkerSelection {
-  pub fn new(marker_id: usize, entry_id: usize, text_selected: Option<Range<usize>>) -> Self {
+  pub fn new(marker_id: MarkerId, entry_id: EntryId, text_selected: Option<Range<usize>>) -> Self {
     Self {
       marker_id,
       entry_id,
@@ -137,13 +290,112 @@ impl MarkerSelection {
   }
 }
 
+/// The time spent matching each marker during a batch of [`Markers::prepare`].
+///
+/// A marker over the budget gets skipped for the rest of the batch, so that
+/// a catastrophic user regex cannot stall the preparation
+#[derive(Debug, Clone, Default)]
+pub struct MarkerBudget {
+  limit: Option<Duration>,
+  spent: Vec<(DeclaredMarker, Duration)>,
+  exceeded: Vec<(DeclaredMarker, Duration)>,
+}
+
+impl MarkerBudget {
+  /// The time a marker may spend per batch by default
+  pub const DEFAULT: Duration = Duration::from_millis(250);
+
+  /// Construct a budget of `limit` per marker, `None` to never skip any
+  pub fn new(limit: Option<Duration>) -> Self {
+    Self {
+      limit,
+      ..Default::default()
+    }
+  }
+
+  /// Retrieve the time spent by `marker` so far
+  pub fn spent(&self, marker: &DeclaredMarker) -> Duration {
+    self
+      .spent
+      .iter()
+      .find(|(spent, _elapsed)| spent == marker)
+      .map(|(_marker, elapsed)| *elapsed)
+      .unwrap_or_default()
+  }
+
+  /// Check if `marker` went over the budget
+  pub fn is_exceeded(&self, marker: &DeclaredMarker) -> bool {
+    self
+      .exceeded
+      .iter()
+      .any(|(exceeded, _spent)| exceeded == marker)
+  }
+
+  /// Record `elapsed` spent matching `marker`.
+  ///
+  /// Returns true if it just went over the budget
+  pub fn spend(&mut self, marker: &DeclaredMarker, elapsed: Duration) -> bool {
+    let spent = match self
+      .spent
+      .iter_mut()
+      .find(|(spent, _elapsed)| spent == marker)
+    {
+      Some((_marker, spent)) => {
+        *spent += elapsed;
+        *spent
+      }
+      None => {
+        self.spent.push((marker.clone(), elapsed));
+        elapsed
+      }
+    };
+    let over = self.limit.is_some_and(|limit| spent > limit) && !self.is_exceeded(marker);
+    if over {
+      self.exceeded.push((marker.clone(), spent));
+    }
+    over
+  }
+
+  /// Retrieve the markers that went over the budget and the time they spent
+  pub fn exceeded(&self) -> &[(DeclaredMarker, Duration)] {
+    &self.exceeded
+  }
+}
+
+/// A marker disabled for the rest of the run, after going over its [`MarkerBudget`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowMarker {
+  /// The name of the rule declaring the marker
+  pub rule: String,
+  pub marker: DeclaredMarker,
+  /// The time it spent in the batch
+  pub spent: Duration,
+}
+
+impl Display for SlowMarker {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "disabled the {} marker `{}` of rule '{}', it took {} in one batch",
+      self.marker.tag,
+      self.marker.regex.as_str(),
+      self.rule,
+      human_duration(self.spent)
+    )
+  }
+}
+
 /// Represent a list of markers extracted from [`BuildEntry`] tags
 #[derive(Debug, Clone, PartialEq)]
 pub struct Markers {
   /// The list of tags as a list of `(entry_id, marker_kind)` tuples
-  tags: Vec<(usize, BuildTagKind)>,
+  tags: Vec<(EntryId, BuildTagKind)>,
   /// The currently selected marker, which corresponds to an item in the [`Markers::tags`] list
   selection: Option<MarkerSelection>,
+  /// The marker kinds that do not start their own block (see [`DeclaredMarker::is_secondary`])
+  secondary: Vec<BuildTagKind>,
+  /// The marker kinds disabled at runtime: still tagged but skipped by navigation and counts
+  disabled: Vec<BuildTagKind>,
 }
 
 impl Markers {
@@ -152,19 +404,120 @@ impl Markers {
     Self {
       tags: Vec::new(),
       selection: None,
+      secondary: Vec::new(),
+      disabled: Vec::new(),
     }
   }
 
   /// Retrieve the list of tags
-  pub fn tags(&self) -> &Vec<(usize, BuildTagKind)> {
+  pub fn tags(&self) -> &Vec<(EntryId, BuildTagKind)> {
     &self.tags
   }
 
   /// Retrieve the list of tags as a mutable reference
-  pub fn tags_mut(&mut self) -> &mut Vec<(usize, BuildTagKind)> {
+  pub fn tags_mut(&mut self) -> &mut Vec<(EntryId, BuildTagKind)> {
     &mut self.tags
   }
 
+  /// Retrieve the secondary marker kinds
+  pub fn secondary(&self) -> &Vec<BuildTagKind> {
+    &self.secondary
+  }
+
+  /// Retrieve the secondary marker kinds as a mutable reference
+  pub fn secondary_mut(&mut self) -> &mut Vec<BuildTagKind> {
+    &mut self.secondary
+  }
+
+  /// Check if a marker kind is secondary
+  pub fn is_secondary(&self, kind: BuildTagKind) -> bool {
+    self.secondary.contains(&kind)
+  }
+
+  /// Retrieve the disabled marker kinds
+  pub fn disabled(&self) -> &Vec<BuildTagKind> {
+    &self.disabled
+  }
+
+  /// Retrieve the disabled marker kinds as a mutable reference
+  pub fn disabled_mut(&mut self) -> &mut Vec<BuildTagKind> {
+    &mut self.disabled
+  }
+
+  /// Check if a marker kind is enabled
+  pub fn is_enabled(&self, kind: BuildTagKind) -> bool {
+    !self.disabled.contains(&kind)
+  }
+
+  /// Enable or disable a marker kind
+  pub fn toggle(&mut self, kind: BuildTagKind) {
+    match self.disabled.iter().position(|disabled| *disabled == kind) {
+      Some(pos) => {
+        self.disabled.remove(pos);
+      }
+      None => self.disabled.push(kind),
+    }
+    crate::dbg!("Disabled marker kinds: {:?}", self.disabled);
+  }
+
+  /// Iterate over the `(marker_id, (entry_id, marker_kind))` of the enabled markers
+  pub fn enabled(&self) -> impl Iterator<Item = (MarkerId, &(EntryId, BuildTagKind))> + '_ {
+    self
+      .tags
+      .iter()
+      .enumerate()
+      .filter(|(_marker_id, (_entry_id, tag))| self.is_enabled(*tag))
+      .map(|(marker_id, tag)| (MarkerId(marker_id), tag))
+  }
+
+  /// Count the enabled markers of the supplied kind
+  pub fn count(&self, kind: BuildTagKind) -> usize {
+    self
+      .enabled()
+      .filter(|(_marker_id, (_entry_id, tag))| *tag == kind)
+      .count()
+  }
+
+  /// Iterate over the ids of the enabled markers that start a block
+  pub fn navigable_ids(&self) -> impl DoubleEndedIterator<Item = MarkerId> + '_ {
+    self
+      .primary_ids()
+      .filter(|marker_id| self.is_enabled(self[*marker_id].1))
+  }
+
+  /// Retrieve the entry ranges covered by disabled markers: whole blocks for primary
+  /// markers and single entries for secondary ones
+  pub fn disabled_ranges(&self, num_entries: usize) -> Vec<Range<usize>> {
+    let mut ret = vec![];
+    let primary = self.primary_ids().collect::<Vec<_>>();
+    for (i, marker_id) in primary.iter().enumerate() {
+      let (start, kind) = self[*marker_id];
+      if !self.is_enabled(kind) {
+        let end = primary
+          .get(i + 1)
+          .map(|next| self[*next].0 .0)
+          .unwrap_or(num_entries);
+        ret.push(start.0..end);
+      }
+    }
+    for (entry_id, kind) in &self.tags {
+      if self.is_secondary(*kind) && !self.is_enabled(*kind) {
+        ret.push(entry_id.0..entry_id.0 + 1);
+      }
+    }
+    ret
+  }
+
+  /// Iterate over the ids of the markers that start a block
+  pub fn primary_ids(&self) -> impl DoubleEndedIterator<Item = MarkerId> + '_ {
+    self
+      .tags
+      .iter()
+      .enumerate()
+      .filter(|(_marker_id, (_entry_id, tag))| !self.is_secondary(*tag))
+      .map(|(marker_id, _)| MarkerId(marker_id))
+  }
+
   /// Retrieve the currently selected marker.
   /// The value coresponds to an entry in the [`Markers::tags`] vector
   pub fn selection(&self) -> Option<&MarkerSelection> {
@@ -182,7 +535,7 @@ impl Markers {
   }
 
   /// Retrieve the currently selected entry.
-  pub fn selected_entry(&self) -> Option<usize> {
+  pub fn selected_entry(&self) -> Option<EntryId> {
     if let Some(selected) = &self.selection {
       return Some(selected.entry_id);
     }
@@ -193,26 +546,41 @@ impl Markers {
   pub fn selected_kind(&self) -> Option<BuildTagKind> {
     if let Some(selected) = &self.selection {
       return self
-        .tags
-        .iter()
-        .enumerate()
-        .find_map(|(marker_id, (_entry_id, tag))| {
-          if marker_id == selected.marker_id {
-            return Some(tag);
-          }
-          None
-        })
-        .cloned();
+        .entry_for_marker(selected.marker_id)
+        .map(|(_entry_id, tag)| *tag);
     }
     None
   }
 
   /// Prepare markers of each [`BuildEntry`].
   ///
-  /// Markers are messages that cargo emits like `^(warning|error|note):`
-  pub fn prepare(entry: &mut BuildEntry, rule: &Rule) -> crate::Result<()> {
-    for known_marker in &rule.markers {
-      if let Some(m) = known_marker.regex.find(&entry.message()) {
+  /// Markers are messages that cargo emits like `^(warning|error|note):`,
+  /// the time spent by each regex is accounted in the `budget`
+  pub fn prepare(
+    entry: &mut BuildEntry,
+    rule: &Rule,
+    budget: &mut MarkerBudget,
+  ) -> crate::Result<()> {
+    for known_marker in rule.markers.iter().filter(|marker| !marker.disabled) {
+      if budget.is_exceeded(known_marker) {
+        continue;
+      }
+      let start = Instant::now();
+      let found = match known_marker
+        .regex
+        .capture_names()
+        .flatten()
+        .any(|name| name == "marker")
+      {
+        // the `marker` group narrows the captured text, e.g. to the `error:` of a longer match
+        true => known_marker
+          .regex
+          .captures(entry.message())
+          .and_then(|captures| captures.name("marker")),
+        false => known_marker.regex.find(entry.message()),
+      };
+      budget.spend(known_marker, start.elapsed());
+      if let Some(m) = found {
         entry.set_tag(BuildTag::marker(
           known_marker.tag,
           m.range(),
@@ -220,48 +588,67 @@ impl Markers {
         )?);
       }
     }
+    // "error: aborting due to ..." is no error of its own
+    if entry.has_tag(BuildTagKind::Summary) {
+      entry
+        .tags_mut()
+        .retain(|tag| tag.get_marker().is_none() || tag.get_kind() == BuildTagKind::Summary);
+    }
     Ok(())
   }
 
   /// Retrieve a marker's `(entry_id, marker_kind)` value by it's id
-  pub fn entry_for_marker(&self, id: usize) -> Option<&(usize, BuildTagKind)> {
-    self.tags.get(id)
+  pub fn entry_for_marker(&self, id: MarkerId) -> Option<&(EntryId, BuildTagKind)> {
+    self.tags.get(id.0)
   }
 
   /// Retrieve a marker's `(entry_id, marker_kind)` value by it's id as a mutable reference
-  pub fn entry_for_marker_mut(&mut self, id: usize) -> Option<&mut (usize, BuildTagKind)> {
-    self.tags.get_mut(id)
+  pub fn entry_for_marker_mut(&mut self, id: MarkerId) -> Option<&mut (EntryId, BuildTagKind)> {
+    self.tags.get_mut(id.0)
   }
 
   /// Select a specific marker
-  pub fn select(&mut self, mut id: usize, text: Option<Range<usize>>) {
+  pub fn select(&mut self, mut id: MarkerId, text: Option<Range<usize>>) {
     if self.tags.is_empty() {
       self.selection = None;
       crate::dbg!("Selecting marker #{} -> None (no tags registered yet)", id);
     } else {
-      id = id.min(self.tags.len().saturating_sub(1));
-      self.selection = Some(MarkerSelection::new(id, self.tags[id].0, text));
+      id = id.min(MarkerId(self.tags.len() - 1));
+      self.selection = Some(MarkerSelection::new(id, self[id].0, text));
       crate::dbg!("Selecting marker #{} -> {:?}", id, self.selection);
     }
   }
 
-  pub fn block_range_at(&self, entry_id: usize) -> Option<(usize, Range<usize>)> {
-    for chunk in self.tags.iter().enumerate().collect::<Vec<_>>().chunks(2) {
-      let (cur_marker_id, (cur_entry_id, cur_tag)) = chunk[0];
-      if let Some((next_marker_id, (next_entry_id, next_tag))) = chunk.get(1) {
-        if entry_id >= *cur_entry_id && entry_id < *next_entry_id {
-          return Some((cur_marker_id, *cur_entry_id..*next_entry_id));
-        }
+  /// Retrieve the `(marker_id, start, end)` bounds of the block containing `entry_id`.
+  ///
+  /// Only primary markers open a block, secondary ones stay inside the enclosing block.
+  /// The `end` is `None` for the last block. Entries located before the first marker
+  /// are not part of any block.
+  pub fn block_bounds(&self, entry_id: EntryId) -> Option<(MarkerId, EntryId, Option<EntryId>)> {
+    let mut current = None;
+    let mut next = None;
+    for marker_id in self.primary_ids() {
+      let marker_entry_id = self[marker_id].0;
+      if marker_entry_id <= entry_id {
+        current = Some((marker_id, marker_entry_id));
       } else {
-        return Some((cur_marker_id, *cur_entry_id..*cur_entry_id + 1));
+        next = Some(marker_entry_id);
+        break;
       }
     }
-    None
+    current.map(|(marker_id, start)| (marker_id, start, next))
   }
 
-  pub fn block_at(&self, entry_id: usize) -> Option<MarkedBlock> {
+  pub fn block_range_at(&self, entry_id: EntryId) -> Option<(MarkerId, Range<usize>)> {
+    self.block_bounds(entry_id).map(|(marker_id, start, end)| {
+      let end = end.map(|end| end.0).unwrap_or(entry_id.0 + 1);
+      (marker_id, start.0..end)
+    })
+  }
+
+  pub fn block_at(&self, entry_id: EntryId) -> Option<MarkedBlock> {
     if let Some((marker_id, range)) = self.block_range_at(entry_id) {
-      let (_entry_id, tag) = &self.tags[marker_id];
+      let (_entry_id, tag) = &self[marker_id];
       return Some(MarkedBlock::new(
         marker_id,
         MarkerRef::known(*tag, None),
@@ -283,16 +670,16 @@ impl Markers {
   /// If the currently selected marker is the first one, it selects the first marker again.
   /// Otherwise it just decrements the currently selected marker.
   pub fn previous_selection(&self) -> Option<MarkerSelection> {
-    let prev_marker = match self.tags.is_empty() {
-      true => return None,
-      false => match self.selection.as_ref() {
-        Some(cur) => cur.marker_id.saturating_sub(1),
-        None => 0,
-      },
+    let prev_marker = match self.selection.as_ref() {
+      Some(cur) => self
+        .navigable_ids()
+        .rev()
+        .find(|marker_id| *marker_id < cur.marker_id)
+        .or_else(|| self.navigable_ids().next())?,
+      None => self.navigable_ids().next()?,
     };
     let entry_id = self
-      .tags
-      .get(prev_marker)
+      .entry_for_marker(prev_marker)
       .map(|tag| tag.0)
       .unwrap_or_default();
     Some(MarkerSelection {
@@ -308,26 +695,19 @@ impl Markers {
   /// If the currently selected marker is the last one, it selects the last marker again.
   /// Otherwise it just increments the currently selected marker.
   pub fn next_selection(&self) -> Option<MarkerSelection> {
-    let prev_marker = match self.tags.is_empty() {
-      true => return None,
-      false => match self.selection.as_ref() {
-        Some(cur) => {
-          if cur.marker_id < self.tags.len().saturating_sub(1) {
-            cur.marker_id.saturating_add(1)
-          } else {
-            self.tags.len().saturating_sub(1)
-          }
-        }
-        None => 0,
-      },
+    let next_marker = match self.selection.as_ref() {
+      Some(cur) => self
+        .navigable_ids()
+        .find(|marker_id| *marker_id > cur.marker_id)
+        .or_else(|| self.navigable_ids().last())?,
+      None => self.navigable_ids().next()?,
     };
     let entry_id = self
-      .tags
-      .get(prev_marker)
+      .entry_for_marker(next_marker)
       .map(|tag| tag.0)
       .unwrap_or_default();
     Some(MarkerSelection {
-      marker_id: prev_marker,
+      marker_id: next_marker,
       entry_id,
       ..Default::default()
     })
@@ -355,13 +735,18 @@ impl Markers {
 
   /// Select the first marker
   pub fn select_first(&mut self) -> Option<&MarkerSelection> {
-    self.select(0, None);
+    let first = self.navigable_ids().next().unwrap_or_default();
+    self.select(first, None);
     self.selection.as_ref()
   }
 
   /// Select the last marker
   pub fn select_last(&mut self) -> Option<&MarkerSelection> {
-    self.select(self.tags.len(), None);
+    let last = self
+      .navigable_ids()
+      .last()
+      .unwrap_or(MarkerId(self.tags.len()));
+    self.select(last, None);
     self.selection.as_ref()
   }
 
@@ -371,21 +756,47 @@ impl Markers {
         .iter()
         .enumerate()
         // .inspect(|(id, entry)| crate::dbg!(format!("entry #{}: {:?}", id, entry.tags())))
-        .filter_map(|(id, entry)| entry.first_marker().map(|marker| (id, marker.kind())))
+        // the trailers stay in the enclosing block, like secondary markers
+        .filter(|(_id, entry)| !entry.has_tag(BuildTagKind::Trailer))
+        .filter_map(|(id, entry)| {
+          entry
+            .primary_marker()
+            .map(|marker| (EntryId(id), marker.kind()))
+        })
+        .filter(|(_id, kind)| *kind != BuildTagKind::Summary)
         .collect::<Vec<_>>(),
       selection: None,
+      secondary: entries
+        .iter()
+        .filter_map(|entry| entry.primary_marker())
+        .filter(|marker| marker.declared().is_secondary())
+        .fold(vec![], |mut kinds, marker| {
+          if !kinds.contains(&marker.kind()) {
+            kinds.push(marker.kind());
+          }
+          kinds
+        }),
+      disabled: vec![],
     }
   }
 }
 
-impl AsRef<Vec<(usize, BuildTagKind)>> for Markers {
-  fn as_ref(&self) -> &Vec<(usize, BuildTagKind)> {
+impl AsRef<Vec<(EntryId, BuildTagKind)>> for Markers {
+  fn as_ref(&self) -> &Vec<(EntryId, BuildTagKind)> {
     &self.tags
   }
 }
 
+impl Index<MarkerId> for Markers {
+  type Output = (EntryId, BuildTagKind);
+
+  fn index(&self, id: MarkerId) -> &Self::Output {
+    &self.tags[id.0]
+  }
+}
+
 impl Deref for Markers {
-  type Target = Vec<(usize, BuildTagKind)>;
+  type Target = Vec<(EntryId, BuildTagKind)>;
 
   fn deref(&self) -> &Self::Target {
     &self.tags
@@ -403,17 +814,27 @@ impl Default for Markers {
     Self {
       tags: Default::default(),
       selection: Default::default(),
+      secondary: Default::default(),
+      disabled: Default::default(),
     }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use std::ops::Range;
+  use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+    time::Duration,
+  };
+
+  use ratatui::style::{Style, Stylize};
+  use regex::Regex;
 
   use crate::{
     active_rule, must_know_marker, rules, BuildEntry, BuildTag, BuildTagKind, CapturedMarker,
-    MarkerRef, MarkerSelection, Origin, DEFAULT_RULES,
+    DeclaredMarker, EntryId, MarkerBudget, MarkerId, MarkerRef, MarkerSelection, Origin,
+    DEFAULT_RULES,
   };
 
   use super::Markers;
@@ -421,7 +842,7 @@ mod tests {
   #[test]
   fn prepare_error() {
     let mut entry = BuildEntry::new("error: test", Origin::default());
-    Markers::prepare(&mut entry, &active_rule());
+    Markers::prepare(&mut entry, &active_rule(), &mut MarkerBudget::default()).unwrap();
     assert_eq!(
       entry.first_marker(),
       Some(&MarkerRef::new(
@@ -431,10 +852,108 @@ mod tests {
     )
   }
 
+  #[test]
+  fn prepare_disabled() {
+    let mut rule = active_rule();
+    for marker in &mut rule.markers {
+      marker.disabled = marker.tag == BuildTagKind::Error;
+    }
+    let mut entry = BuildEntry::new("error: test, note: more", Origin::default());
+    Markers::prepare(&mut entry, &rule, &mut MarkerBudget::default()).unwrap();
+    assert!(entry.tag(BuildTagKind::Error).is_none());
+    assert!(entry.tag(BuildTagKind::Note).is_some());
+  }
+
+  #[test]
+  fn prepare_budget() {
+    let cheap = DeclaredMarker::new(
+      BuildTagKind::Error,
+      Regex::new(r"^error:").unwrap(),
+      Style::default(),
+    );
+    // no literal to look for, the whole line gets scanned
+    let slow = DeclaredMarker::new(
+      BuildTagKind::Warning,
+      Regex::new(r"(\w+\s?)+\d").unwrap(),
+      Style::default(),
+    );
+    let mut rule = active_rule();
+    rule.markers = vec![cheap.clone(), slow.clone()];
+    // the slow marker scans about 30MB, well over the budget even in release; the cheap one
+    // stays some microseconds per line, far from it even when the thread gets preempted
+    // by the tests running in parallel
+    let line = "ab ".repeat(100_000);
+    let mut budget = MarkerBudget::new(Some(Duration::from_millis(50)));
+    for _ in 0..100 {
+      let mut entry = BuildEntry::new(line.clone(), Origin::default());
+      Markers::prepare(&mut entry, &rule, &mut budget).unwrap();
+    }
+    assert_eq!(budget.exceeded().len(), 1);
+    assert_eq!(budget.exceeded()[0].0, slow);
+    assert!(!budget.is_exceeded(&cheap));
+    // the cheap marker still tags
+    let mut entry = BuildEntry::new("error: boom", Origin::default());
+    Markers::prepare(&mut entry, &rule, &mut budget).unwrap();
+    assert!(entry.tag(BuildTagKind::Error).is_some());
+    // skipped for the rest of the batch
+    let spent = budget.spent(&slow);
+    let mut entry = BuildEntry::new(format!("{}1", line), Origin::default());
+    Markers::prepare(&mut entry, &rule, &mut budget).unwrap();
+    assert_eq!(budget.spent(&slow), spent);
+    assert!(entry.tag(BuildTagKind::Warning).is_none());
+
+    // without a limit nothing gets skipped
+    let mut budget = MarkerBudget::default();
+    Markers::prepare(&mut entry, &rule, &mut budget).unwrap();
+    assert!(budget.exceeded().is_empty());
+    assert!(entry.tag(BuildTagKind::Warning).is_some());
+  }
+
+  #[test]
+  fn identity() {
+    let error = must_know_marker(BuildTagKind::Error);
+    // restyled by a user config
+    let restyled = DeclaredMarker::new(error.tag, error.regex.clone(), Style::default().blue());
+    assert_eq!(restyled, error);
+    assert_eq!(restyled.key(), error.key());
+    let other = DeclaredMarker::new(
+      BuildTagKind::Error,
+      Regex::new("fatal:").unwrap(),
+      error.style,
+    );
+    assert_ne!(other, error);
+    let warning = DeclaredMarker::new(BuildTagKind::Warning, error.regex.clone(), error.style);
+    assert_ne!(warning, error);
+
+    // the references compare the captured range, not its text
+    let captured = MarkerRef::new(Some(CapturedMarker::new(0, "error:")), error.clone());
+    assert_eq!(
+      captured,
+      MarkerRef::new(Some(CapturedMarker::new(0, "ERROR:")), restyled.clone())
+    );
+    assert_ne!(
+      captured,
+      MarkerRef::new(Some(CapturedMarker::new(2, "error:")), error.clone())
+    );
+    assert_ne!(captured, MarkerRef::new(None, error.clone()));
+    let hash = |marker: &MarkerRef| {
+      let mut hasher = DefaultHasher::new();
+      marker.hash(&mut hasher);
+      hasher.finish()
+    };
+    assert_eq!(
+      hash(&captured),
+      hash(&MarkerRef::new(
+        Some(CapturedMarker::new(0, "error:")),
+        restyled
+      ))
+    );
+  }
+
   #[test]
   fn prepare_warning() {
     let mut entry = BuildEntry::new("warning: test", Origin::default());
-    Markers::prepare(&mut entry, &active_rule());
+    Markers::prepare(&mut entry, &active_rule(), &mut MarkerBudget::default()).unwrap();
     assert_eq!(
       entry.first_marker(),
       Some(&MarkerRef::new(
@@ -447,7 +966,7 @@ mod tests {
   #[test]
   fn prepare_note() {
     let mut entry = BuildEntry::new("note: test", Origin::default());
-    Markers::prepare(&mut entry, &active_rule());
+    Markers::prepare(&mut entry, &active_rule(), &mut MarkerBudget::default()).unwrap();
     assert_eq!(
       entry.first_marker(),
       Some(&MarkerRef::new(
@@ -471,8 +990,10 @@ mod tests {
     assert_eq!(
       markers,
       Markers {
-        tags: vec![(0, BuildTagKind::Error)],
-        selection: None
+        tags: vec![(EntryId(0), BuildTagKind::Error)],
+        selection: None,
+        secondary: vec![],
+        disabled: vec![],
       }
     )
   }
@@ -493,9 +1014,12 @@ mod tests {
       .unwrap()]),
     ];
     let mut markers = Markers::from_entries(&entries);
-    markers.select(1, None);
-    assert_eq!(markers.selection, Some(MarkerSelection::new(1, 2, None)));
-    assert_eq!(markers.selected_entry(), Some(2));
+    markers.select(MarkerId(1), None);
+    assert_eq!(
+      markers.selection,
+      Some(MarkerSelection::new(MarkerId(1), EntryId(2), None))
+    );
+    assert_eq!(markers.selected_entry(), Some(EntryId(2)));
     assert_eq!(markers.selected_kind(), Some(BuildTagKind::Warning));
   }
 
@@ -515,8 +1039,8 @@ mod tests {
       .unwrap()]),
     ];
     let markers = Markers::from_entries(&entries);
-    let entry = markers.entry_for_marker(1);
-    assert_eq!(entry, Some(&(2, BuildTagKind::Warning)))
+    let entry = markers.entry_for_marker(MarkerId(1));
+    assert_eq!(entry, Some(&(EntryId(2), BuildTagKind::Warning)))
   }
 
   #[test]
@@ -538,17 +1062,17 @@ mod tests {
     // first time goes from None -> Some(0)
     assert_eq!(
       markers.select_next(),
-      Some(&MarkerSelection::new(0, 0, None))
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(0), None))
     );
     // second time goes from Some(0) -> Some(1)
     assert_eq!(
       markers.select_next(),
-      Some(&MarkerSelection::new(1, 2, None))
+      Some(&MarkerSelection::new(MarkerId(1), EntryId(2), None))
     );
     // third time goes from Some(1) -> Some(1) as it is out-of-bounds
     assert_eq!(
       markers.select_next(),
-      Some(&MarkerSelection::new(1, 2, None))
+      Some(&MarkerSelection::new(MarkerId(1), EntryId(2), None))
     );
   }
 
@@ -571,12 +1095,104 @@ mod tests {
     // first time goes from None -> Some(0)
     assert_eq!(
       markers.select_previous(),
-      Some(&MarkerSelection::new(0, 0, None))
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(0), None))
     );
     // second time goes from Some(0) -> Some(0)
     assert_eq!(
       markers.select_previous(),
-      Some(&MarkerSelection::new(0, 0, None))
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(0), None))
+    );
+  }
+
+  #[test]
+  fn secondary_markers() {
+    let entries = vec![
+      BuildEntry::new("error: test error", Origin::default()).with_tags([BuildTag::error(
+        Range { start: 0, end: 6 },
+        "error:",
+      )
+      .unwrap()]),
+      BuildEntry::new("note: attached note", Origin::default()).with_tags([BuildTag::note(
+        Range { start: 0, end: 5 },
+        "note:",
+      )
+      .unwrap()]),
+      BuildEntry::new("warning: test warning", Origin::default()).with_tags([BuildTag::warning(
+        Range { start: 0, end: 7 },
+        "warning:",
+      )
+      .unwrap()]),
+    ];
+    let mut markers = Markers::from_entries(&entries);
+    assert_eq!(markers.secondary(), &vec![BuildTagKind::Note]);
+    assert_eq!(
+      markers.block_range_at(EntryId(1)),
+      Some((MarkerId(0), 0..2))
+    );
+    assert_eq!(
+      markers.block_range_at(EntryId(2)),
+      Some((MarkerId(2), 2..3))
+    );
+    // the note is skipped when navigating between blocks
+    assert_eq!(
+      markers.select_next(),
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(0), None))
+    );
+    assert_eq!(
+      markers.select_next(),
+      Some(&MarkerSelection::new(MarkerId(2), EntryId(2), None))
+    );
+    assert_eq!(
+      markers.select_previous(),
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(0), None))
+    );
+  }
+
+  #[test]
+  fn disabled_markers() {
+    let entries = vec![
+      BuildEntry::new("error: test error", Origin::default()).with_tags([BuildTag::error(
+        Range { start: 0, end: 6 },
+        "error:",
+      )
+      .unwrap()]),
+      BuildEntry::new("warning: test warning", Origin::default()).with_tags([BuildTag::warning(
+        Range { start: 0, end: 7 },
+        "warning:",
+      )
+      .unwrap()]),
+      BuildEntry::new("and a non-marker", Origin::default()),
+      BuildEntry::new("error: other error", Origin::default()).with_tags([BuildTag::error(
+        Range { start: 0, end: 6 },
+        "error:",
+      )
+      .unwrap()]),
+    ];
+    let mut markers = Markers::from_entries(&entries);
+    markers.toggle(BuildTagKind::Warning);
+    assert_eq!(markers.count(BuildTagKind::Warning), 0);
+    assert_eq!(markers.count(BuildTagKind::Error), 2);
+    assert_eq!(markers.disabled_ranges(entries.len()), vec![1..3]);
+    // the warning is skipped but still delimits the blocks
+    assert_eq!(
+      markers.select_next(),
+      Some(&MarkerSelection::new(MarkerId(0), EntryId(0), None))
+    );
+    assert_eq!(
+      markers.select_next(),
+      Some(&MarkerSelection::new(MarkerId(2), EntryId(3), None))
+    );
+    assert_eq!(
+      markers.block_range_at(EntryId(2)),
+      Some((MarkerId(1), 1..3))
+    );
+    // re-enabling restores everything
+    markers.toggle(BuildTagKind::Warning);
+    assert_eq!(markers.count(BuildTagKind::Warning), 1);
+    assert!(markers.disabled_ranges(entries.len()).is_empty());
+    assert_eq!(
+      markers.select_previous(),
+      Some(&MarkerSelection::new(MarkerId(1), EntryId(1), None))
     );
   }
 }
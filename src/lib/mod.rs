@@ -1,15 +1,45 @@
-pub mod app;
-pub mod build;
-pub mod debug;
-pub mod error;
-pub mod marker;
-pub mod utils;
-pub mod widgets;
-
-pub use app::*;
-pub use build::*;
-pub use debug::*;
-pub use error::*;
-pub use marker::*;
-pub use utils::*;
-pub use widgets::*;
+//! Wrap build reports in a terminal UI to find the errors, warnings and notes.
+//!
+//! The applications embedding nbuild use the [`prelude`], whose items are also
+//! re-exported here. The modules are private to the crate: the few hidden items of the
+//! root are only public for the binary and the tests, they may change without notice
+
+// the options document the internals they tune, see `cargo doc --document-private-items`
+#![allow(rustdoc::private_intra_doc_links)]
+
+mod ansi;
+mod app;
+mod build;
+mod debug;
+mod error;
+mod marker;
+pub mod prelude;
+mod style;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+mod utils;
+mod widgets;
+
+pub(crate) use app::*;
+pub(crate) use build::*;
+pub(crate) use debug::*;
+pub(crate) use error::*;
+pub(crate) use marker::*;
+pub(crate) use style::*;
+pub(crate) use utils::*;
+pub(crate) use widgets::*;
+
+pub use app::{App, AppOptions, BuildSummary, ExitCodeMode};
+pub use build::{
+  active_rule, init_rules, load_rules, rules, set_active_rule, BuildEntry, BuildEvent, BuildOutput,
+  BuildTagKind, Origin, Rule, DEFAULT_RULES,
+};
+pub use error::{Error, ErrorKind};
+pub use marker::{DeclaredMarker, Markers};
+
+#[doc(hidden)]
+pub use ansi::AnsiLine;
+#[doc(hidden)]
+pub use build::FoldMap;
+#[doc(hidden)]
+pub use debug::Debug;
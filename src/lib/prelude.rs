@@ -0,0 +1,33 @@
+//! The surface of the crate intended for the applications embedding nbuild.
+//!
+//! The rest of the items reachable from the crate root are internal: they are kept
+//! for the binary and the tests, and may change with any release.
+//!
+//! Run the whole application, as the `cargo-nbuild` binary does:
+//!
+//! ```no_run
+//! use cargo_nbuild::prelude::*;
+//!
+//! let options = AppOptions::parse_from(["--no-tui", "--release"]).unwrap();
+//! let exit_code = App::new(options).run().unwrap();
+//! ```
+//!
+//! Or prepare a build log without the terminal UI:
+//!
+//! ```
+//! use cargo_nbuild::prelude::*;
+//!
+//! let mut build = BuildOutput::from(["error: oops", " --> src/main.rs:1:1"]);
+//! build.prepare();
+//! assert_eq!(BuildSummary::new(&build, None).num_errors, 1);
+//! ```
+
+pub use crate::{
+  active_rule, init_rules, load_rules, rules, set_active_rule, App, AppOptions, BuildEntry,
+  BuildEvent, BuildOutput, BuildSummary, BuildTagKind, DeclaredMarker, Error, ErrorKind,
+  ExitCodeMode, Markers, Origin, Rule, DEFAULT_RULES,
+};
+
+/// The headless runs of the whole pipeline, see [`crate::testing`]
+#[cfg(any(test, feature = "test-util"))]
+pub use crate::testing::{Harness, ScriptedBuild};
@@ -0,0 +1,386 @@
+use ratatui::{
+  style::{Color, Modifier, Style},
+  text::Line,
+};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::REGION_STYLE;
+
+/// The known modifier names, the first name is the one used when serializing
+const MODIFIERS: &[(&[&str], Modifier)] = &[
+  (&["bold"], Modifier::BOLD),
+  (&["dim"], Modifier::DIM),
+  (&["italic"], Modifier::ITALIC),
+  (&["underline", "underlined"], Modifier::UNDERLINED),
+  (&["slow_blink"], Modifier::SLOW_BLINK),
+  (&["rapid_blink"], Modifier::RAPID_BLINK),
+  (&["reversed"], Modifier::REVERSED),
+  (&["hidden"], Modifier::HIDDEN),
+  (&["crossed_out", "strikethrough"], Modifier::CROSSED_OUT),
+];
+
+/// A human-friendly [`Style`] representation used in rule files.
+///
+/// Colors are either names (`red`, `light-blue`, ...) or `#RRGGBB` strings,
+/// modifiers a list of names like `["bold", "underline"]`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StyleSpec {
+  /// The foreground color
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub fg: Option<String>,
+  /// The background color
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub bg: Option<String>,
+  /// The modifiers
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub modifiers: Vec<String>,
+}
+
+impl StyleSpec {
+  fn parse_color(color: &str) -> Result<Color, String> {
+    color
+      .parse::<Color>()
+      .map_err(|_| format!("unknown color '{}'", color))
+  }
+
+  fn format_color(color: Color) -> String {
+    let mut ret = String::new();
+    for (i, ch) in color.to_string().chars().enumerate() {
+      if ch.is_ascii_uppercase() && i > 0 {
+        ret.push('-');
+      }
+      ret.push(ch.to_ascii_lowercase());
+    }
+    ret
+  }
+
+  fn parse_modifier(modifier: &str) -> Result<Modifier, String> {
+    MODIFIERS
+      .iter()
+      .find(|(names, _)| {
+        names
+          .iter()
+          .any(|name| name.eq_ignore_ascii_case(modifier.trim()))
+      })
+      .map(|(_, modifier)| *modifier)
+      .ok_or_else(|| format!("unknown modifier '{}'", modifier))
+  }
+}
+
+impl TryFrom<&StyleSpec> for Style {
+  type Error = String;
+
+  fn try_from(value: &StyleSpec) -> Result<Self, Self::Error> {
+    let mut style = Style::default();
+    if let Some(fg) = value.fg.as_ref() {
+      style = style.fg(StyleSpec::parse_color(fg)?);
+    }
+    if let Some(bg) = value.bg.as_ref() {
+      style = style.bg(StyleSpec::parse_color(bg)?);
+    }
+    for modifier in &value.modifiers {
+      style = style.add_modifier(StyleSpec::parse_modifier(modifier)?);
+    }
+    Ok(style)
+  }
+}
+
+impl From<&Style> for StyleSpec {
+  fn from(value: &Style) -> Self {
+    Self {
+      fg: value.fg.map(StyleSpec::format_color),
+      bg: value.bg.map(StyleSpec::format_color),
+      modifiers: MODIFIERS
+        .iter()
+        .filter(|(_, modifier)| value.add_modifier.contains(*modifier))
+        .map(|(names, _)| names[0].to_string())
+        .collect::<Vec<_>>(),
+    }
+  }
+}
+
+/// A style read from a rule file: either a [`StyleSpec`] or
+/// the legacy ratatui [`Style`] structure (detected by its `add_modifier` field)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StyleRepr {
+  Legacy(Style),
+  Spec(StyleSpec),
+}
+
+impl TryFrom<StyleRepr> for Style {
+  type Error = String;
+
+  fn try_from(value: StyleRepr) -> Result<Self, Self::Error> {
+    match value {
+      StyleRepr::Legacy(style) => Ok(style),
+      StyleRepr::Spec(spec) => Style::try_from(&spec),
+    }
+  }
+}
+
+/// Serialize a [`Style`] as a [`StyleSpec`]
+pub fn serialize_style<S: Serializer>(style: &Style, s: S) -> Result<S::Ok, S::Error> {
+  StyleSpec::from(style).serialize(s)
+}
+
+/// How the selected line gets rendered, see [`apply_selection`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectionTheme {
+  /// The background of the whole line, replacing the one of its spans
+  pub bg: Color,
+  /// Flip the foregrounds hard to read on `bg` to black or white
+  pub contrast: bool,
+  /// The style of the region of the selection, e.g. a search match, layered on top
+  pub region: Style,
+}
+
+impl Default for SelectionTheme {
+  fn default() -> Self {
+    Self {
+      bg: Color::LightBlue,
+      contrast: true,
+      region: REGION_STYLE.add_modifier(Modifier::BOLD),
+    }
+  }
+}
+
+impl SelectionTheme {
+  /// The minimum contrast ratio of a foreground on the background, below it gets flipped
+  pub const MIN_CONTRAST: f64 = 3.0;
+
+  /// The theme of the line cursor, dimmer than the selection, see [`crate::CursorState`]
+  pub fn cursor() -> Self {
+    Self {
+      bg: Color::DarkGray,
+      ..Default::default()
+    }
+  }
+}
+
+/// Approximate the color of the default xterm palette, `None` for the terminal's defaults
+pub fn color_rgb(color: Color) -> Option<(u8, u8, u8)> {
+  const ANSI: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+  ];
+  let index = match color {
+    Color::Reset => return None,
+    Color::Rgb(r, g, b) => return Some((r, g, b)),
+    Color::Indexed(i @ 16..=231) => {
+      let level = |v: u8| match v {
+        0 => 0,
+        v => 55 + v * 40,
+      };
+      let i = i - 16;
+      return Some((level(i / 36), level(i / 6 % 6), level(i % 6)));
+    }
+    Color::Indexed(i @ 232..=255) => {
+      let v = 8 + (i - 232) * 10;
+      return Some((v, v, v));
+    }
+    Color::Indexed(i) => i,
+    Color::Black => 0,
+    Color::Red => 1,
+    Color::Green => 2,
+    Color::Yellow => 3,
+    Color::Blue => 4,
+    Color::Magenta => 5,
+    Color::Cyan => 6,
+    Color::Gray => 7,
+    Color::DarkGray => 8,
+    Color::LightRed => 9,
+    Color::LightGreen => 10,
+    Color::LightYellow => 11,
+    Color::LightBlue => 12,
+    Color::LightMagenta => 13,
+    Color::LightCyan => 14,
+    Color::White => 15,
+  };
+  Some(ANSI[index as usize])
+}
+
+/// Compute the relative luminance of a color, from 0 (black) to 1 (white)
+pub fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+  let linear = |v: u8| {
+    let v = v as f64 / 255.0;
+    match v <= 0.03928 {
+      true => v / 12.92,
+      false => ((v + 0.055) / 1.055).powf(2.4),
+    }
+  };
+  0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// Compute the contrast ratio of two colors, from 1 (the same) to 21 (black on white)
+pub fn contrast_ratio(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+  let (a, b) = (luminance(a), luminance(b));
+  (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Render `line` as the selected one: the background of `theme` replaces the one of
+/// every span, rather than getting merged like [`Style::patch`] does, and the
+/// foregrounds hard to read on it get flipped to black or white.
+///
+/// The reversed spans lose their modifier, their background would be their foreground
+pub fn apply_selection<'a>(mut line: Line<'a>, theme: &SelectionTheme) -> Line<'a> {
+  let selected = |mut style: Style| {
+    style = style.bg(theme.bg);
+    if style.add_modifier.contains(Modifier::REVERSED) {
+      style = style.remove_modifier(Modifier::REVERSED);
+    }
+    let fg = style.fg.and_then(color_rgb);
+    match (theme.contrast, fg, color_rgb(theme.bg)) {
+      (true, Some(fg), Some(bg)) if contrast_ratio(fg, bg) < SelectionTheme::MIN_CONTRAST => {
+        let (black, white) = ((0, 0, 0), (255, 255, 255));
+        match contrast_ratio(black, bg) >= contrast_ratio(white, bg) {
+          true => style.fg(Color::Black),
+          false => style.fg(Color::White),
+        }
+      }
+      _ => style,
+    }
+  };
+  line.style = selected(line.style);
+  for span in &mut line.spans {
+    span.style = selected(span.style);
+  }
+  line
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+  };
+
+  use super::{apply_selection, color_rgb, contrast_ratio, SelectionTheme, StyleSpec};
+
+  #[test]
+  fn spec_round_trip() {
+    let style = Style::default().light_blue().on_black().bold().underlined();
+    let spec = StyleSpec::from(&style);
+    assert_eq!(
+      spec,
+      StyleSpec {
+        fg: Some("light-blue".to_string()),
+        bg: Some("black".to_string()),
+        modifiers: vec!["bold".to_string(), "underline".to_string()],
+      }
+    );
+    assert_eq!(Style::try_from(&spec), Ok(style));
+  }
+
+  #[test]
+  fn spec_rgb() {
+    let spec = StyleSpec {
+      fg: Some("#FF8000".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(
+      Style::try_from(&spec),
+      Ok(Style::default().fg(ratatui::style::Color::Rgb(255, 128, 0)))
+    );
+  }
+
+  #[test]
+  fn spec_invalid() {
+    let spec = StyleSpec {
+      fg: Some("redd".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(
+      Style::try_from(&spec),
+      Err("unknown color 'redd'".to_string())
+    );
+    let spec = StyleSpec {
+      modifiers: vec!["blod".to_string()],
+      ..Default::default()
+    };
+    assert_eq!(
+      Style::try_from(&spec),
+      Err("unknown modifier 'blod'".to_string())
+    );
+  }
+
+  #[test]
+  fn palette() {
+    assert_eq!(color_rgb(Color::Reset), None);
+    assert_eq!(color_rgb(Color::White), Some((255, 255, 255)));
+    assert_eq!(color_rgb(Color::Indexed(9)), color_rgb(Color::LightRed));
+    assert_eq!(color_rgb(Color::Indexed(16)), Some((0, 0, 0)));
+    assert_eq!(color_rgb(Color::Indexed(231)), Some((255, 255, 255)));
+    assert_eq!(color_rgb(Color::Indexed(232)), Some((8, 8, 8)));
+    let ratio = contrast_ratio((0, 0, 0), (255, 255, 255));
+    assert!((ratio - 21.0).abs() < 1e-9, "{}", ratio);
+    assert_eq!(contrast_ratio((92, 92, 255), (92, 92, 255)), 1.0);
+  }
+
+  #[test]
+  fn selection_overrides_backgrounds() {
+    let theme = SelectionTheme::default();
+    let line = Line::from(vec![
+      Span::from("plain"),
+      // a marker style with its own background
+      Span::from("error").white().on_red().bold(),
+      Span::from("reversed").reversed(),
+    ]);
+    let selected = apply_selection(line, &theme);
+    assert_eq!(selected.style.bg, Some(Color::LightBlue));
+    for span in &selected.spans {
+      assert_eq!(span.style.bg, Some(Color::LightBlue), "{:?}", span);
+      assert!(!span.style.add_modifier.contains(Modifier::REVERSED));
+    }
+    assert_eq!(selected.spans[0].style.fg, None);
+    // readable on light blue, kept
+    assert_eq!(selected.spans[1].style.fg, Some(Color::White));
+    assert!(selected.spans[1]
+      .style
+      .add_modifier
+      .contains(Modifier::BOLD));
+    assert_eq!(selected.to_string(), "plainerrorreversed");
+  }
+
+  #[test]
+  fn selection_contrast() {
+    let theme = SelectionTheme::default();
+    let line = Line::from(vec![
+      Span::from("blue").blue(),
+      Span::from("black").black().on_white(),
+      Span::from("cyan").light_cyan(),
+    ])
+    .style(Style::default().light_blue());
+    let selected = apply_selection(line.clone(), &theme);
+    // the same color as the background
+    assert_eq!(selected.style.fg, Some(Color::White));
+    assert_eq!(selected.spans[0].style.fg, Some(Color::White));
+    assert_eq!(selected.spans[1].style.fg, Some(Color::Black));
+    assert_eq!(selected.spans[2].style.fg, Some(Color::LightCyan));
+    // left untouched without the contrast adjustment
+    let selected = apply_selection(
+      line,
+      &SelectionTheme {
+        contrast: false,
+        ..theme
+      },
+    );
+    assert_eq!(selected.spans[0].style.fg, Some(Color::Blue));
+    assert_eq!(selected.spans[1].style.bg, Some(Color::LightBlue));
+  }
+}
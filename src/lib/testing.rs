@@ -0,0 +1,523 @@
+//! Headless runs of the whole pipeline, for the integration tests.
+//!
+//! A [`ScriptedBuild`] replaces the [`crate::Builder`]: it sends its lines over the same
+//! channels, without spawning cargo. The [`Harness`] connects it to a [`crate::Preparer`]
+//! and runs the render loop on a [`TestBackend`], the final buffer and state are returned
+//! for the assertions:
+//!
+//! ```ignore
+//! let run = Harness::new(
+//!   ScriptedBuild::default()
+//!     .with_lines(Origin::Stderr, ["error: boom", "  --> src/main.rs:1:1"]),
+//! )
+//! .with_keys("e")
+//! .run_until_finished();
+//! assert!(run.contains("1 error(s)"));
+//! assert_eq!(run.state.markers.selected_entry(), Some(EntryId(0)));
+//! ```
+//!
+//! The keys are pressed once the build finished and its entries got prepared, so that
+//! the runs do not depend on the timing of the threads. Available to the tests of the
+//! crate, and to the other crates with the `test-util` feature.
+//!
+//! The allocations get counted by a [`CountingAllocator`], installed by the test binaries
+//! measuring them only: a global allocator applies to every test of its binary
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  collections::VecDeque,
+  fs,
+  path::PathBuf,
+  process::ExitStatus,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc::{channel, sync_channel, Sender, SyncSender},
+  },
+  thread::{sleep, spawn},
+  time::Duration,
+};
+
+use ratatui::{
+  backend::TestBackend,
+  buffer::Buffer,
+  crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+  Terminal,
+};
+
+use crate::{
+  AppOptions, BuildEntry, BuildEvent, BuildSummary, History, IpcServer, Origin, PrepareCommand,
+  Preparer, RenderInput, RenderState, Renderer, ScriptedInput, SessionInfo,
+};
+
+/// A fake build sending scripted lines over the channels of the [`crate::Builder`]
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedBuild {
+  /// The lines and the delay before each of them
+  steps: Vec<(Duration, Origin, String)>,
+  exit_status: ExitStatus,
+}
+
+impl ScriptedBuild {
+  /// Add a line sent after `delay`
+  pub fn with_line<L: AsRef<str>>(mut self, delay: Duration, origin: Origin, line: L) -> Self {
+    self.steps.push((delay, origin, line.as_ref().to_string()));
+    self
+  }
+
+  /// Add lines sent at once
+  pub fn with_lines<L: AsRef<str>, I: IntoIterator<Item = L>>(
+    mut self,
+    origin: Origin,
+    lines: I,
+  ) -> Self {
+    for line in lines {
+      self = self.with_line(Duration::ZERO, origin, line);
+    }
+    self
+  }
+
+  /// Define how the build exits, successfully by default
+  pub fn with_exit_status(mut self, status: ExitStatus) -> Self {
+    self.exit_status = status;
+    self
+  }
+
+  /// Send the lines, then the exit status. The sends block while the queue is full,
+  /// like the [`crate::Builder`] waiting on the preparation
+  pub fn run(self, tx_entries: SyncSender<BuildEntry>, tx_events: Sender<BuildEvent>) {
+    let _ = tx_events.send(BuildEvent::BuildStarted);
+    for (delay, origin, line) in self.steps {
+      if !delay.is_zero() {
+        sleep(delay);
+      }
+      if tx_entries.send(BuildEntry::new(line, origin)).is_err() {
+        break;
+      }
+    }
+    let _ = tx_events.send(BuildEvent::BuildFinished(self.exit_status));
+  }
+}
+
+/// The outcome of a [`Harness`] run
+pub struct HarnessRun {
+  /// The last drawn frame
+  pub buffer: Buffer,
+  pub state: RenderState,
+  /// The summary sent when the render loop stopped
+  pub summary: Option<BuildSummary>,
+}
+
+impl HarnessRun {
+  /// Retrieve the rows of the last frame
+  pub fn lines(&self) -> Vec<String> {
+    let area = self.buffer.area;
+    (area.top()..area.bottom())
+      .map(|y| {
+        (area.left()..area.right())
+          .map(|x| self.buffer[(x, y)].symbol())
+          .collect()
+      })
+      .collect()
+  }
+
+  /// Check if a row of the last frame contains `text`
+  pub fn contains(&self, text: &str) -> bool {
+    self.lines().iter().any(|line| line.contains(text))
+  }
+}
+
+/// Run a [`ScriptedBuild`] through the preparation and the render loop, headlessly
+pub struct Harness {
+  build: ScriptedBuild,
+  options: AppOptions,
+  size: (u16, u16),
+  events: VecDeque<Event>,
+  timeout: Duration,
+  session: SessionInfo,
+}
+
+impl Harness {
+  /// The size of the terminal, unless defined with [`Harness::with_size`]
+  pub const SIZE: (u16, u16) = (120, 30);
+
+  /// The longest time a run takes, whatever its stop condition
+  pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+  pub fn new(build: ScriptedBuild) -> Self {
+    Self {
+      build,
+      options: AppOptions::default(),
+      size: Self::SIZE,
+      events: VecDeque::new(),
+      timeout: Self::TIMEOUT,
+      session: SessionInfo::default(),
+    }
+  }
+
+  /// Define the options of the preparation and the render loop
+  pub fn with_options(mut self, options: AppOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  /// Define the files of the session, shown by the info popup
+  pub fn with_session(mut self, session: SessionInfo) -> Self {
+    self.session = session;
+    self
+  }
+
+  /// Define the longest time a run takes, [`Harness::TIMEOUT`] by default
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Define the size of the terminal
+  pub fn with_size(mut self, width: u16, height: u16) -> Self {
+    self.size = (width, height);
+    self
+  }
+
+  /// Queue an event, delivered once the build finished and its entries got prepared
+  pub fn with_event(mut self, event: Event) -> Self {
+    self.events.push_back(event);
+    self
+  }
+
+  /// Queue the presses of the characters of `keys`, see [`Harness::with_event`]
+  pub fn with_keys<K: AsRef<str>>(mut self, keys: K) -> Self {
+    for ch in keys.as_ref().chars() {
+      self = self.with_event(Event::Key(KeyEvent::new(
+        KeyCode::Char(ch),
+        KeyModifiers::NONE,
+      )));
+    }
+    self
+  }
+
+  /// Run `frames` frames of the render loop
+  pub fn run_frames(self, frames: usize) -> HarnessRun {
+    self.run(Some(frames), false)
+  }
+
+  /// Run the render loop until the build finished, and the queued events got handled and drawn
+  pub fn run_until_finished(self) -> HarnessRun {
+    self.run(None, true)
+  }
+
+  fn run(self, frames: Option<usize>, until_finished: bool) -> HarnessRun {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let (tx_entries, rx_entries) = sync_channel::<BuildEntry>(self.options.queue_capacity);
+    let (tx_events, rx_events) = channel::<BuildEvent>();
+    let (tx_commands, rx_commands) = channel::<PrepareCommand>();
+    let (tx_snapshots, rx_snapshots) = channel();
+    let (tx_user_quit, _rx_user_quit) = channel();
+    let (tx_builds, _rx_builds) = channel();
+    let (tx_summary, rx_summary) = channel();
+    let build = self.build;
+    let build_events = tx_events.clone();
+    let builder = spawn(move || build.run(tx_entries, build_events));
+    let preparer = Preparer::new(
+      self.options.clone(),
+      rx_entries,
+      tx_events,
+      rx_commands,
+      tx_snapshots,
+    );
+    let preparer = spawn(move || preparer.run());
+    // the finished builds get recorded apart from the user's history
+    let history_path: PathBuf = std::env::temp_dir().join(format!(
+      "nbuild-harness-{}-{}.jsonl",
+      std::process::id(),
+      RUNS.fetch_add(1, Ordering::SeqCst)
+    ));
+    let mut terminal =
+      Terminal::new(TestBackend::new(self.size.0, self.size.1)).expect("failed to create terminal");
+    let input = RenderInput::Scripted(ScriptedInput {
+      events: self.events,
+      frames,
+      until_finished,
+      timeout: self.timeout,
+      ..Default::default()
+    });
+    let state = Renderer::render_loop(
+      self.options,
+      &mut terminal,
+      input,
+      tx_user_quit,
+      rx_snapshots,
+      tx_commands,
+      tx_builds,
+      rx_events,
+      tx_summary,
+      IpcServer::default(),
+      History::new(&history_path),
+      self.session,
+    )
+    .expect("failed to render");
+    let _ = builder.join();
+    // hangs up once the renderer dropped its commands
+    let _ = preparer.join();
+    let _ = fs::remove_file(&history_path);
+    HarnessRun {
+      buffer: terminal.backend().buffer().clone(),
+      state,
+      summary: rx_summary.try_recv().ok(),
+    }
+  }
+}
+
+/// The allocations made by the process, see [`allocations_by`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Allocations {
+  pub count: usize,
+  pub bytes: usize,
+}
+
+static ALLOCATED_COUNT: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Count the allocations of every thread, for [`allocations_by`].
+///
+/// Installed as the global allocator of a test binary:
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator;
+/// ```
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATED_COUNT.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+/// Retrieve the allocations made by the process so far
+fn allocated() -> Allocations {
+  Allocations {
+    count: ALLOCATED_COUNT.load(Ordering::Relaxed),
+    bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+  }
+}
+
+/// Count the allocations made while `f` runs, zero unless the binary installed the
+/// [`CountingAllocator`].
+///
+/// The threads spawned by `f` get counted, as any other thread of the process: the
+/// binaries measuring them run a single test
+pub fn allocations_by<R, F: FnOnce() -> R>(f: F) -> (Allocations, R) {
+  let start = allocated();
+  let ret = f();
+  let end = allocated();
+  (
+    Allocations {
+      count: end.count - start.count,
+      bytes: end.bytes - start.bytes,
+    },
+    ret,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use ratatui::crossterm::event::{Event, KeyCode, KeyEvent};
+
+  use crate::{AppOptions, BuildTagKind, EntryId, Origin, SessionInfo};
+
+  use super::{Harness, ScriptedBuild};
+
+  fn build() -> ScriptedBuild {
+    ScriptedBuild::default()
+      .with_lines(
+        Origin::Stderr,
+        [
+          "   Compiling demo v0.1.0 (/work/demo)",
+          "warning: unused variable: `x`",
+          "  --> src/main.rs:2:7",
+        ],
+      )
+      .with_line(
+        Duration::from_millis(20),
+        Origin::Stderr,
+        "error[E0308]: mismatched types",
+      )
+      .with_lines(
+        Origin::Stderr,
+        [
+          "  --> src/main.rs:4:18",
+          "error: could not compile `demo` (bin \"demo\") due to 1 previous error",
+        ],
+      )
+  }
+
+  #[test]
+  fn first_error_selected() {
+    let run = Harness::new(build()).run_until_finished();
+    let selected = run.state.markers.selected_entry();
+    assert_eq!(selected, Some(EntryId(3)));
+    assert_eq!(
+      run
+        .state
+        .markers
+        .entry_for_marker(run.state.markers.selection().unwrap().marker_id),
+      Some(&(EntryId(3), BuildTagKind::Error))
+    );
+    // 'w' jumps to the first warning
+    let run = Harness::new(build()).with_keys("w").run_until_finished();
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(1)));
+  }
+
+  #[test]
+  fn search_from_selection() {
+    let enter = Event::Key(KeyEvent::from(KeyCode::Enter));
+    // forward from the selected error
+    let run = Harness::new(build())
+      .with_keys("/src")
+      .with_event(enter.clone())
+      .run_until_finished();
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(4)));
+    // repeated, wrapped around then back
+    let search = |keys: &str| {
+      Harness::new(build())
+        .with_keys("/src")
+        .with_event(enter.clone())
+        .with_keys(keys)
+        .run_until_finished()
+        .state
+        .markers
+        .selected_entry()
+    };
+    assert_eq!(search("n"), Some(EntryId(2)));
+    assert_eq!(search("nN"), Some(EntryId(4)));
+    assert_eq!(search("N"), Some(EntryId(2)));
+  }
+
+  #[test]
+  fn status_counts_after_finish() {
+    let run = Harness::new(build()).run_until_finished();
+    assert!(run.state.exit_status.is_some_and(|status| status.success()));
+    let status = run.lines().last().cloned().unwrap_or_default();
+    assert!(status.contains("Build finished ✓"), "{:?}", status);
+    assert!(status.contains("1 error(s)"), "{:?}", status);
+    assert!(status.contains("1 warning(s)"), "{:?}", status);
+    let summary = run.summary.expect("no summary");
+    assert_eq!((summary.num_errors, summary.num_warnings), (1, 1));
+  }
+
+  #[test]
+  fn frames() {
+    let run = Harness::new(ScriptedBuild::default())
+      .with_size(60, 10)
+      .run_frames(3);
+    assert_eq!(run.state.frames, 3);
+    assert_eq!(run.lines().len(), 10);
+    assert!(run.contains("h: Show help"));
+  }
+
+  #[test]
+  fn error_storm() {
+    let lines = (0..10_000).map(|i| format!("error: boom #{}", i));
+    let run = Harness::new(ScriptedBuild::default().with_lines(Origin::Stderr, lines))
+      .with_timeout(Duration::from_secs(60))
+      .run_until_finished();
+    // one event per build, not per error: the finished one does not wait behind
+    // thousands of frames
+    assert!(run.state.exit_status.is_some());
+    assert_eq!(run.state.snapshot.len(), 10_000);
+    let status = run.lines().last().cloned().unwrap_or_default();
+    assert!(status.contains("Build finished ✓"), "{:?}", status);
+    assert!(status.contains("10,000 error(s)"), "{:?}", status);
+  }
+
+  #[test]
+  fn info_popup() {
+    let session = SessionInfo::default().with_config_file("/work/demo/nbuild.json");
+    let run = Harness::new(build())
+      .with_session(session)
+      .with_event(Event::Key(KeyEvent::from(KeyCode::F(1))))
+      .run_until_finished();
+    assert!(run.contains("cmd: cargo build | rule: rust: cargo"));
+    assert!(run.contains("Rule           rust: cargo"));
+    assert!(run.contains(" 2. /work/demo/nbuild.json"));
+    assert!(run.contains("Run directory  none"));
+  }
+
+  #[test]
+  fn summary_skipped() {
+    let build = build().with_line(
+      Duration::ZERO,
+      Origin::Stderr,
+      "error: aborting due to 1 previous error; 1 warning emitted",
+    );
+    let run = Harness::new(build.clone()).run_until_finished();
+    let status = run.lines().last().cloned().unwrap_or_default();
+    assert!(status.contains("1 error(s)"), "{:?}", status);
+    assert!(
+      run.contains("aborting due to 1 previous error; 1 warning emitted (press e for first error)")
+    );
+    // neither End nor 'e' land on the trailers
+    let run = Harness::new(build.clone())
+      .with_event(Event::Key(KeyEvent::from(KeyCode::End)))
+      .run_until_finished();
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(3)));
+    let run = Harness::new(build).with_keys("we").run_until_finished();
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(3)));
+  }
+
+  #[test]
+  fn zen_mode() {
+    let run = Harness::new(build()).run_until_finished();
+    assert!(run.lines()[1].contains("cmd: cargo build"));
+    assert!(run.contains("h: Show help"));
+    let bordered = run.lines()[3].clone();
+    assert!(bordered.starts_with('┌'), "{:?}", bordered);
+    // no chrome: the log starts on the first row, without its border
+    let run = Harness::new(build()).with_keys("z").run_until_finished();
+    let lines = run.lines();
+    assert!(!run.contains("cmd:"));
+    assert!(!run.contains("h: Show help"));
+    assert!(lines[0].contains("Compiling demo"), "{:#?}", lines);
+    assert!(!lines[..lines.len() - 1]
+      .iter()
+      .any(|line| line.contains('│') || line.contains('┌')));
+    // the selection survives the toggles
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(3)));
+    let run = Harness::new(build())
+      .with_options(AppOptions::builder().zen(true).finish().unwrap())
+      .with_event(Event::Key(KeyEvent::from(KeyCode::F(11))))
+      .run_until_finished();
+    assert_eq!(run.lines()[3], bordered);
+    assert!(run.contains("h: Show help"));
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(3)));
+  }
+
+  #[test]
+  fn filtered_rows() {
+    let build = ScriptedBuild::default()
+      .with_lines(Origin::Stderr, ["error: first"])
+      .with_lines(
+        Origin::Stderr,
+        (0..100).map(|i| format!("   Compiling crate{} v0.1.0", i)),
+      )
+      .with_lines(Origin::Stderr, ["error: second"]);
+    // the errors only: the second one is on the second row, no scroll needed
+    let run = Harness::new(build)
+      .with_keys("f")
+      .with_event(Event::Key(KeyEvent::from(KeyCode::Down)))
+      .run_until_finished();
+    assert_eq!(run.state.markers.selected_entry(), Some(EntryId(101)));
+    assert_eq!(run.state.scroll, 0);
+    assert!(run.contains("lines 1-2 of 2 (100%), 102 in total"));
+    assert!(run.contains("second"), "{:#?}", run.lines());
+    assert!(!run.contains("Compiling"));
+  }
+}
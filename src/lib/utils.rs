@@ -1,13 +1,37 @@
 use std::{
-  io::{BufRead as _, BufReader, Read},
-  sync::{Mutex, MutexGuard},
+  fmt::Display,
+  io::{self, BufRead, IsTerminal, Write},
+  ops::Range,
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::{
+    atomic::{AtomicBool, AtomicU8, Ordering},
+    Mutex, MutexGuard, RwLock, RwLockReadGuard,
+  },
   time::{Duration, Instant},
 };
 
-use crate::{err, Error, ErrorKind};
+use lazy_static::lazy_static;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{err, AnsiLine, Error, ErrorKind};
+
+lazy_static! {
+  /// The roots detected on first use, replaced with `--manifest-path`
+  static ref PATH_DISPLAY: RwLock<PathDisplay> = RwLock::new(PathDisplay::detect());
+}
+
+/// If true [`display_path`] leaves paths untouched
+static FULL_PATHS: AtomicBool = AtomicBool::new(false);
+
+/// If true [`set_title`] replaced the terminal title, see [`restore_title`]
+static TITLE_SET: AtomicBool = AtomicBool::new(false);
+
+/// The [`ThousandsSeparator`] of [`format_count`], as its discriminant
+static THOUSANDS_SEPARATOR: AtomicU8 = AtomicU8::new(ThousandsSeparator::Comma as u8);
 
 /// A trait to support trying to lock a mutex for a certain amount of time
-pub trait TryLockFor<T> {
+pub(crate) trait TryLockFor<T> {
   /// Try to lock the mutex for [`dur`] amount of time
   fn try_lock_for(&self, dur: Duration) -> Result<MutexGuard<'_, T>, Error>;
 }
@@ -26,69 +50,995 @@ impl<T> TryLockFor<T> for Mutex<T> {
   }
 }
 
-/// A batched line reader
-pub struct BatchLineReader<R: ?Sized> {
-  reader: Box<BufReader<R>>,
-  has_more_batches: bool,
-  max_time_per_batch: Option<Duration>,
-  max_lines_per_batch: Option<usize>,
+/// Read a line without its terminator, timestamped as soon as the read returns.
+///
+/// Returns `None` on EOF
+pub(crate) fn read_line_timed<R: BufRead + ?Sized>(
+  reader: &mut R,
+) -> std::io::Result<Option<(Instant, String)>> {
+  let mut buf = String::new();
+  let nbytes = reader.read_line(&mut buf)?;
+  let at = Instant::now();
+  if nbytes == 0 {
+    return Ok(None);
+  }
+  if buf.ends_with('\n') {
+    buf.pop();
+    if buf.ends_with('\r') {
+      buf.pop();
+    }
+  }
+  Ok(Some((at, buf)))
+}
+
+/// Keep what a terminal would display of a line overwritten by carriage returns:
+/// the text after the last `\r`, e.g. cargo's progress bar followed by a message
+pub fn overwritten_line(line: &str) -> &str {
+  match line.rsplit_once('\r') {
+    Some((_, last)) => last,
+    None => line,
+  }
 }
 
-impl<R: Read> BatchLineReader<R> {
-  pub fn new(r: R) -> Self {
+/// Parse the crates compiled and to compile of cargo's progress bar, e.g.
+/// `Building [==> ] 1/4: demo`, from the last one of a line overwritten by carriage returns
+pub fn build_progress(line: &str) -> Option<(usize, usize)> {
+  line.rsplit('\r').find_map(|part| {
+    let part = AnsiLine::strip(part);
+    let (_, counts) = part
+      .trim_start()
+      .strip_prefix("Building")?
+      .split_once("] ")?;
+    let counts = counts.split([':', ' ']).next()?;
+    let (done, total) = counts.split_once('/')?;
+    Some((done.parse().ok()?, total.parse().ok()?))
+  })
+}
+
+/// Shorten paths for display:
+/// - files inside the workspace are made relative to its root
+/// - files from the cargo registry become `crate-x.y.z/src/...`
+/// - files inside the home directory start with `~`
+///
+/// Both `/` and `\` separators are supported, the path's own separator is kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathDisplay {
+  workspace_root: Option<PathBuf>,
+  home: Option<PathBuf>,
+}
+
+impl PathDisplay {
+  /// The registry sources folder, crates are stored under `<index>/<crate-x.y.z>`
+  const REGISTRY_DIR: &'static str = "/.cargo/registry/src/";
+
+  /// Construct this object from known roots
+  pub fn new(workspace_root: Option<PathBuf>, home: Option<PathBuf>) -> Self {
     Self {
-      reader: Box::new(BufReader::new(r)),
-      has_more_batches: true,
-      max_time_per_batch: None,
-      max_lines_per_batch: None,
+      workspace_root,
+      home,
     }
   }
 
-  pub fn has_more_batches(&self) -> bool {
-    self.has_more_batches
+  /// Detect the roots from the current directory and the user's home
+  pub fn detect() -> Self {
+    Self::detect_with(None)
+  }
+
+  /// Detect the roots from the directory of `manifest_path` if any, see [`Self::workspace_root`]
+  pub fn detect_with(manifest_path: Option<&Path>) -> Self {
+    let workspace_root = Self::workspace_root(manifest_path);
+    crate::dbg!("Detected workspace root: {:?}", workspace_root);
+    Self::new(workspace_root, dirs::home_dir())
   }
 
-  pub fn next_line(&mut self) -> Option<String> {
-    let mut buf = String::new();
-    if let Ok(nbytes) = self.reader.read_line(&mut buf) {
-      if nbytes == 0 {
-        self.has_more_batches = false;
+  /// Find the workspace root of the project: from the directory containing `manifest_path`
+  /// (`--manifest-path`), else from the current directory, see [`Self::find_workspace_root`]
+  pub fn workspace_root(manifest_path: Option<&Path>) -> Option<PathBuf> {
+    let dir = Self::project_dir(manifest_path)?;
+    Self::find_workspace_root(&dir)
+  }
+
+  /// Retrieve the directory containing `manifest_path`, made absolute against the current
+  /// directory, else the current directory
+  pub fn project_dir(manifest_path: Option<&Path>) -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok();
+    match manifest_path {
+      Some(path) => {
+        let path = match (path.is_absolute(), cwd) {
+          (false, Some(cwd)) => cwd.join(path),
+          _ => path.to_path_buf(),
+        };
+        crate::PathResolver::normalize(path)
+          .parent()
+          .map(Path::to_path_buf)
       }
-      return Some(buf);
-    }
-    return None;
-  }
-
-  pub fn next_batch(&mut self) -> Option<Vec<String>> {
-    let batch_start = Instant::now();
-    let batch_end = self
-      .max_time_per_batch
-      .map(|max_time| batch_start + max_time);
-    let mut ret = vec![];
-    let mut limit_reached = false;
-    loop {
-      let time_limit_reached = match batch_end {
-        Some(batch_end) => Instant::now() >= batch_end,
-        None => false,
-      };
-      let line_limit_reached = match self.max_lines_per_batch {
-        Some(line_limit) => ret.len() >= line_limit,
-        None => false,
-      };
-      if time_limit_reached || line_limit_reached {
-        limit_reached = true;
-        break;
+      None => cwd,
+    }
+  }
+
+  /// Find the workspace root of `dir`: the top-most `Cargo.toml` declaring a `[workspace]`,
+  /// else the nearest `Cargo.toml`, else the nearest git repository
+  pub fn find_workspace_root(dir: &Path) -> Option<PathBuf> {
+    let manifests = dir
+      .ancestors()
+      .filter(|dir| dir.join("Cargo.toml").is_file())
+      .collect::<Vec<_>>();
+    manifests
+      .iter()
+      .rev()
+      .find(|dir| {
+        std::fs::read_to_string(dir.join("Cargo.toml"))
+          .map(|manifest| manifest.contains("[workspace]"))
+          .unwrap_or_default()
+      })
+      .or_else(|| manifests.first())
+      .map(|dir| dir.to_path_buf())
+      .or_else(|| {
+        dir
+          .ancestors()
+          .find(|dir| dir.join(".git").exists())
+          .map(|dir| dir.to_path_buf())
+      })
+  }
+
+  /// Retrieve the detected roots, cached after the first call
+  pub fn global() -> RwLockReadGuard<'static, Self> {
+    PATH_DISPLAY.read().unwrap_or_else(|e| e.into_inner())
+  }
+
+  /// Replace the roots of [`Self::global`], e.g. for `--manifest-path`
+  pub fn set_global(display: Self) {
+    match PATH_DISPLAY.write() {
+      Ok(mut global) => *global = display,
+      Err(e) => *e.into_inner() = display,
+    }
+  }
+
+  fn normalize<P: AsRef<Path>>(path: P) -> String {
+    path
+      .as_ref()
+      .to_string_lossy()
+      .replace('\\', "/")
+      .trim_end_matches('/')
+      .to_string()
+  }
+
+  fn strip_root<'p>(path: &'p str, root: Option<&PathBuf>) -> Option<&'p str> {
+    let root = Self::normalize(root?);
+    path
+      .strip_prefix(root.as_str())
+      .and_then(|rest| rest.strip_prefix('/'))
+  }
+
+  /// Shorten a path, unrelated paths are returned as-is
+  pub fn shorten<P: AsRef<Path>>(&self, path: P) -> String {
+    let original = path.as_ref().to_string_lossy().to_string();
+    let normalized = Self::normalize(&original);
+    let short = if let Some(rest) = Self::strip_root(&normalized, self.workspace_root.as_ref()) {
+      rest.to_string()
+    } else if let Some(pos) = normalized.find(Self::REGISTRY_DIR) {
+      // skip the `index.crates.io-xxxx` folder
+      let rest = &normalized[pos + Self::REGISTRY_DIR.len()..];
+      match rest.split_once('/') {
+        Some((_index, rest)) => rest.to_string(),
+        None => return original,
       }
-      match self.next_line() {
-        Some(line) => ret.push(line),
-        None => break,
+    } else if let Some(rest) = Self::strip_root(&normalized, self.home.as_ref()) {
+      format!("~/{}", rest)
+    } else {
+      return original;
+    };
+    match original.contains('\\') && !original.contains('/') {
+      true => short.replace('/', "\\"),
+      false => short,
+    }
+  }
+}
+
+/// Shorten a path with the [`PathDisplay::global`] roots, unless full paths are requested
+pub fn display_path<P: AsRef<Path>>(path: P) -> String {
+  match full_paths() {
+    true => path.as_ref().display().to_string(),
+    false => PathDisplay::global().shorten(path),
+  }
+}
+
+/// Check if [`display_path`] shows full paths
+pub fn full_paths() -> bool {
+  FULL_PATHS.load(Ordering::Relaxed)
+}
+
+/// Show full or shortened paths in [`display_path`]
+pub fn set_full_paths(v: bool) {
+  FULL_PATHS.store(v, Ordering::Relaxed);
+}
+
+/// The separator of the thousands in the counts, see [`format_count`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ThousandsSeparator {
+  /// `12,345`
+  #[default]
+  Comma,
+  /// `12 345`
+  Space,
+  /// `12345`
+  None,
+}
+
+impl FromStr for ThousandsSeparator {
+  type Err = crate::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "comma" => Ok(Self::Comma),
+      "space" => Ok(Self::Space),
+      "none" => Ok(Self::None),
+      _ => Err(err!(
+        ErrorKind::Parsing,
+        "invalid thousands separator '{}', expected one of: comma, space, none",
+        s
+      )),
+    }
+  }
+}
+
+impl Display for ThousandsSeparator {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Self::Comma => "comma",
+        Self::Space => "space",
+        Self::None => "none",
+      }
+    )
+  }
+}
+
+/// Retrieve the separator used by [`format_count`]
+pub fn thousands_separator() -> ThousandsSeparator {
+  match THOUSANDS_SEPARATOR.load(Ordering::Relaxed) {
+    v if v == ThousandsSeparator::Space as u8 => ThousandsSeparator::Space,
+    v if v == ThousandsSeparator::None as u8 => ThousandsSeparator::None,
+    _ => ThousandsSeparator::Comma,
+  }
+}
+
+/// Define the separator used by [`format_count`], see `--thousands-separator`
+pub fn set_thousands_separator(v: ThousandsSeparator) {
+  THOUSANDS_SEPARATOR.store(v as u8, Ordering::Relaxed);
+}
+
+/// Format a count with the [`thousands_separator`], e.g. `12,345`
+pub fn format_count(n: usize) -> String {
+  format_count_with(n, thousands_separator())
+}
+
+/// Format a count with the thousands separated by `separator`
+pub fn format_count_with(n: usize, separator: ThousandsSeparator) -> String {
+  let digits = n.to_string();
+  let separator = match separator {
+    ThousandsSeparator::Comma => ',',
+    ThousandsSeparator::Space => ' ',
+    ThousandsSeparator::None => return digits,
+  };
+  let mut ret = String::with_capacity(digits.len() + digits.len() / 3);
+  for (i, digit) in digits.chars().enumerate() {
+    if i > 0 && (digits.len() - i).is_multiple_of(3) {
+      ret.push(separator);
+    }
+    ret.push(digit);
+  }
+  ret
+}
+
+/// Format a count in at most 5 chars for the narrow layouts, e.g. `999`, `1k`, `12.3k` or `123k`.
+///
+/// The counts from a trillion keep growing in `G`, e.g. `2000G`
+pub fn compact_count(n: usize) -> String {
+  if n < 1000 {
+    return n.to_string();
+  }
+  // a decimal below 100 only
+  let round = |value: f64| match value < 99.95 {
+    true => (value * 10.0).round() / 10.0,
+    false => value.round(),
+  };
+  let format = |rounded: f64, unit: &str| match rounded.fract() == 0.0 {
+    true => format!("{:.0}{}", rounded, unit),
+    false => format!("{:.1}{}", rounded, unit),
+  };
+  let mut value = n as f64;
+  for unit in ["k", "M"] {
+    value /= 1000.0;
+    // rounded up to the next unit
+    let rounded = round(value);
+    if rounded < 1000.0 {
+      return format(rounded, unit);
+    }
+  }
+  format(round(value / 1000.0), "G")
+}
+
+/// Format a duration for humans: `450µs`, `980ms`, `45.2s`, `2m 03s` or `1h 05m`
+pub fn human_duration(d: Duration) -> String {
+  if d < Duration::from_millis(1) {
+    return format!("{}µs", d.as_micros());
+  }
+  if d < Duration::from_secs(1) {
+    return format!("{}ms", d.as_millis());
+  }
+  // 59.96s would show as 60.0s
+  if (d.as_secs_f64() * 10.0).round() < 600.0 {
+    return format!("{:.1}s", d.as_secs_f64());
+  }
+  let secs = d.as_secs_f64().round() as u64;
+  match secs < 3600 {
+    true => format!("{}m {:02}s", secs / 60, secs % 60),
+    false => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+  }
+}
+
+/// Format a size in bytes for humans: `512 B`, `1.5 KiB` or `4.0 MiB`
+pub fn human_size(bytes: u64) -> String {
+  const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+  let mut size = bytes as f64;
+  let mut unit = 0;
+  while size >= 1024.0 && unit + 1 < UNITS.len() {
+    size /= 1024.0;
+    unit += 1;
+  }
+  match unit {
+    0 => format!("{} {}", size, UNITS[unit]),
+    _ => format!("{:.1} {}", size, UNITS[unit]),
+  }
+}
+
+/// Pad `text` with spaces up to `width` terminal columns
+pub fn pad_to_width<S: AsRef<str>>(text: S, width: usize) -> String {
+  let text = text.as_ref();
+  format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+/// Cut `text` so that it fits in `width` terminal columns,
+/// the last column shows an ellipsis when something was cut
+pub fn truncate_to_width<S: AsRef<str>>(text: S, width: usize) -> String {
+  let text = text.as_ref();
+  if text.width() <= width {
+    return text.to_string();
+  }
+  let mut ret = String::new();
+  let mut ret_width = 0;
+  for ch in text.chars() {
+    let ch_width = ch.width().unwrap_or(0);
+    if ret_width + ch_width + 1 > width {
+      break;
+    }
+    ret.push(ch);
+    ret_width += ch_width;
+  }
+  if width > 0 {
+    ret.push('…');
+  }
+  ret
+}
+
+/// Expand the tabs of `text` with spaces up to the next multiple of `tab_width` columns.
+///
+/// The text starts at terminal column `column`, the tab stops are the ones of the whole line
+pub fn expand_tabs<S: AsRef<str>>(text: S, column: usize, tab_width: usize) -> String {
+  let text = text.as_ref();
+  if !text.contains('\t') {
+    return text.to_string();
+  }
+  let tab_width = tab_width.max(1);
+  let mut ret = String::with_capacity(text.len());
+  let mut column = column;
+  for ch in text.chars() {
+    match ch {
+      '\t' => {
+        let spaces = tab_width - column % tab_width;
+        ret.push_str(&" ".repeat(spaces));
+        column += spaces;
+      }
+      ch => {
+        ret.push(ch);
+        column += ch.width().unwrap_or(0);
+      }
+    }
+  }
+  ret
+}
+
+/// Map the byte `range` of a raw `text` to the text expanded by [`expand_tabs`],
+/// e.g. a search match to the displayed line
+pub fn expanded_range<S: AsRef<str>>(
+  text: S,
+  range: Range<usize>,
+  column: usize,
+  tab_width: usize,
+) -> Range<usize> {
+  let text = text.as_ref();
+  let offset = |end: usize| expand_tabs(&text[..end], column, tab_width).len();
+  offset(range.start.min(text.len()))..offset(range.end.min(text.len()))
+}
+
+/// Check if the terminal shows the titles of [`set_title`]: stdout must be a terminal,
+/// and not one advertising no capabilities (`TERM=dumb`)
+pub fn supports_title() -> bool {
+  io::stdout().is_terminal() && std::env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+/// Set the terminal title (OSC 2), the control characters of `title` being dropped.
+///
+/// The first call saves the previous title on the terminal's title stack (`CSI 22 t`)
+pub fn set_title<W: Write, T: AsRef<str>>(out: &mut W, title: T) -> io::Result<()> {
+  if !TITLE_SET.swap(true, Ordering::Relaxed) {
+    write!(out, "\x1b[22;2t")?;
+  }
+  let title = title
+    .as_ref()
+    .chars()
+    .filter(|c| !c.is_control())
+    .collect::<String>();
+  write!(out, "\x1b]2;{}\x07", title)?;
+  out.flush()
+}
+
+/// Restore the title saved by [`set_title`] (`CSI 23 t`), does nothing if no title was set.
+///
+/// The title gets cleared first, for the terminals without a title stack
+pub fn restore_title<W: Write>(out: &mut W) -> io::Result<()> {
+  if !TITLE_SET.swap(false, Ordering::Relaxed) {
+    return Ok(());
+  }
+  write!(out, "\x1b]2;\x07\x1b[23;2t")?;
+  out.flush()
+}
+
+/// The state of the terminal's progress indicator, e.g. on the taskbar or the tab,
+/// see [`TermProgress::escape`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermProgress {
+  /// No progress shown
+  Clear,
+  /// Busy, for how long is unknown
+  Indeterminate,
+  /// The percentage done
+  Value(u8),
+  /// Failed, the bar staying at the percentage done
+  Error(u8),
+}
+
+impl TermProgress {
+  /// The percentage of `done` out of `total`, [`TermProgress::Indeterminate`] when
+  /// the total is unknown
+  pub fn of(done: usize, total: usize) -> Self {
+    match total {
+      0 => Self::Indeterminate,
+      total => Self::Value((done.min(total) * 100 / total) as u8),
+    }
+  }
+
+  /// Format the progress escape of ConEmu, `OSC 9 ; 4 ; state ; percentage`
+  pub fn escape(&self) -> String {
+    let (state, value) = match *self {
+      Self::Clear => (0, 0),
+      Self::Value(value) => (1, value.min(100)),
+      Self::Error(value) => (2, value.min(100)),
+      Self::Indeterminate => (3, 0),
+    };
+    format!("\x1b]9;4;{};{}\x07", state, value)
+  }
+}
+
+/// Format a desktop notification, `OSC 777 ; notify ; title ; body`. The control characters
+/// and the `;` separating the fields are dropped
+pub fn notification_escape<T: AsRef<str>, B: AsRef<str>>(title: T, body: B) -> String {
+  let clean = |text: &str| {
+    text
+      .chars()
+      .filter(|c| !c.is_control() && *c != ';')
+      .collect::<String>()
+  };
+  format!(
+    "\x1b]777;notify;{};{}\x07",
+    clean(title.as_ref()),
+    clean(body.as_ref())
+  )
+}
+
+/// The escapes of [`ProgressReporter`] understood by the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TermCapabilities {
+  /// The progress indicator, see [`TermProgress`]
+  pub progress: bool,
+  /// The desktop notifications, see [`notification_escape`]
+  pub notifications: bool,
+}
+
+impl TermCapabilities {
+  /// Detect the capabilities of the terminal of stdout, see [`TermCapabilities::from_env`]
+  pub fn detect() -> Self {
+    Self::from_env(io::stdout().is_terminal(), |name| std::env::var(name).ok())
+  }
+
+  /// Detect the capabilities from the environment variables given by `var`.
+  ///
+  /// Nothing gets supported off a terminal, on a dumb one or inside tmux which would
+  /// swallow the escapes. Windows Terminal and ConEmu show the progress, foot the
+  /// notifications and Ghostty both
+  pub fn from_env<F: Fn(&str) -> Option<String>>(is_terminal: bool, var: F) -> Self {
+    let term = var("TERM").unwrap_or_default();
+    if !is_terminal || term == "dumb" || var("TMUX").is_some() {
+      return Self::default();
+    }
+    let ghostty = var("TERM_PROGRAM").is_some_and(|program| program == "ghostty");
+    Self {
+      progress: ghostty || var("WT_SESSION").is_some() || var("ConEmuPID").is_some(),
+      notifications: ghostty || term.starts_with("foot"),
+    }
+  }
+}
+
+/// Report the state of a build to the terminal, see [`TermProgress`].
+///
+/// The escapes never go through ratatui's buffer: [`Self::write`] prints them once
+/// a frame is drawn. The progress gets written again every [`Self::REFRESH`] for
+/// the terminals dropping a stale one, and cleared [`Self::CLEAR_DELAY`] after
+/// the build finished
+#[derive(Debug, Clone, Default)]
+pub struct ProgressReporter {
+  capabilities: TermCapabilities,
+  progress: Option<TermProgress>,
+  written_at: Option<Instant>,
+  clear_at: Option<Instant>,
+  notification: Option<String>,
+}
+
+impl ProgressReporter {
+  /// The time between two writes of the same progress
+  pub const REFRESH: Duration = Duration::from_secs(5);
+  /// The time the state of a finished build stays shown
+  pub const CLEAR_DELAY: Duration = Duration::from_secs(3);
+
+  /// Construct the reporter, writing only the escapes of `capabilities`
+  pub fn new(capabilities: TermCapabilities) -> Self {
+    Self {
+      capabilities,
+      ..Default::default()
+    }
+  }
+
+  /// Show the build as running, with the percentage done if the `total` is known
+  pub fn running(&mut self, done: usize, total: usize) {
+    self.set(TermProgress::of(done, total));
+    self.clear_at = None;
+  }
+
+  /// Show the outcome of the build finished at `now`, cleared after [`Self::CLEAR_DELAY`].
+  /// The terminals supporting the notifications get `summary`
+  pub fn finished<S: AsRef<str>>(&mut self, success: bool, summary: S, now: Instant) {
+    self.set(match success {
+      true => TermProgress::Value(100),
+      false => TermProgress::Error(100),
+    });
+    self.clear_at = Some(now + Self::CLEAR_DELAY);
+    if self.capabilities.notifications {
+      self.notification = Some(notification_escape("nbuild", summary));
+    }
+  }
+
+  /// Clear the progress, e.g. when quitting
+  pub fn clear(&mut self) {
+    if self.progress.is_some() {
+      self.set(TermProgress::Clear);
+    }
+    self.clear_at = None;
+  }
+
+  fn set(&mut self, progress: TermProgress) {
+    if self.progress != Some(progress) {
+      self.progress = Some(progress);
+      self.written_at = None;
+    }
+  }
+
+  /// Clear the progress once the delay after the build elapsed at `now`
+  pub fn tick(&mut self, now: Instant) {
+    if self.clear_at.is_some_and(|at| now >= at) {
+      self.clear();
+    }
+  }
+
+  /// Write the escapes pending at `now`: the progress when it changed or needs a refresh,
+  /// and the notification of the finished build
+  pub fn write<W: Write>(&mut self, out: &mut W, now: Instant) -> io::Result<()> {
+    let mut escapes = self.notification.take().unwrap_or_default();
+    if let Some(progress) = self.progress.filter(|_| self.capabilities.progress) {
+      let stale = self
+        .written_at
+        .is_none_or(|at| now.saturating_duration_since(at) >= Self::REFRESH);
+      if stale {
+        escapes.push_str(&progress.escape());
+        self.written_at = Some(now);
       }
     }
-    if ret.len() == 0 && !limit_reached {
-      // we didn't receive anything and no limit was reached
-      // which means we encountered EOF
-      return None;
+    if self.progress == Some(TermProgress::Clear) && self.written_at.is_some() {
+      self.progress = None;
+    }
+    if escapes.is_empty() {
+      return Ok(());
+    }
+    out.write_all(escapes.as_bytes())?;
+    out.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+  };
+
+  use super::{
+    build_progress, compact_count, expand_tabs, expanded_range, format_count_with, human_duration,
+    human_size, notification_escape, overwritten_line, pad_to_width, restore_title, set_title,
+    truncate_to_width, PathDisplay, ProgressReporter, TermCapabilities, TermProgress,
+    ThousandsSeparator,
+  };
+
+  fn unix() -> PathDisplay {
+    PathDisplay::new(
+      Some(PathBuf::from("/home/me/projects/demo")),
+      Some(PathBuf::from("/home/me")),
+    )
+  }
+
+  fn windows() -> PathDisplay {
+    PathDisplay::new(
+      Some(PathBuf::from("C:\\Users\\me\\projects\\demo")),
+      Some(PathBuf::from("C:\\Users\\me")),
+    )
+  }
+
+  #[test]
+  fn tabs() {
+    assert_eq!(expand_tabs("no tabs", 0, 4), "no tabs");
+    assert_eq!(expand_tabs("\tx", 0, 4), "    x");
+    assert_eq!(expand_tabs("ab\tx\ty", 0, 4), "ab  x   y");
+    assert_eq!(expand_tabs("ab\tx", 0, 8), "ab      x");
+    // the tab stops of the whole line
+    assert_eq!(expand_tabs("\tx", 3, 4), " x");
+    assert_eq!(expand_tabs("日\tx", 0, 4), "日  x");
+    assert_eq!(expand_tabs("\tx", 0, 0), " x");
+
+    let raw = "\tlet\tx = 1;";
+    let display = expand_tabs(raw, 0, 4);
+    let range = expanded_range(raw, 5..6, 0, 4);
+    assert_eq!(&raw[5..6], "x");
+    assert_eq!(&display[range], "x");
+    assert_eq!(expanded_range(raw, 0..raw.len(), 0, 4), 0..display.len());
+  }
+
+  #[test]
+  fn overwritten() {
+    assert_eq!(overwritten_line("plain"), "plain");
+    assert_eq!(
+      overwritten_line("    Building [==> ] 1/4: demo\r\x1b[K   Compiling demo"),
+      "\x1b[K   Compiling demo"
+    );
+    assert_eq!(overwritten_line("    Building [==> ] 1/4: demo\r"), "");
+  }
+
+  #[test]
+  fn progress_bar() {
+    assert_eq!(build_progress("   Compiling demo"), None);
+    assert_eq!(
+      build_progress("    Building [==> ] 1/4: demo\r"),
+      Some((1, 4))
+    );
+    assert_eq!(
+      build_progress("\x1b[1m\x1b[36m    Building\x1b[0m [=====>  ] 12/34: a, b\r"),
+      Some((12, 34))
+    );
+    // the last bar drawn
+    assert_eq!(
+      build_progress("Building [=> ] 1/2\r\x1b[K   Compiling demo\rBuilding [==>] 2/2\r"),
+      Some((2, 2))
+    );
+    assert_eq!(build_progress("    Building [    ] 0/?: demo\r"), None);
+  }
+
+  #[test]
+  fn workspace() {
+    assert_eq!(
+      unix().shorten("/home/me/projects/demo/src/main.rs"),
+      "src/main.rs"
+    );
+    assert_eq!(
+      windows().shorten("C:\\Users\\me\\projects\\demo\\src\\main.rs"),
+      "src\\main.rs"
+    );
+    // a sibling sharing the root's prefix is not inside the workspace
+    assert_eq!(
+      unix().shorten("/home/me/projects/demo-ui/src/main.rs"),
+      "~/projects/demo-ui/src/main.rs"
+    );
+    assert_eq!(unix().shorten("src/main.rs"), "src/main.rs");
+  }
+
+  #[test]
+  fn registry() {
+    assert_eq!(
+      unix().shorten(
+        "/home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f/serde-1.0.200/src/de/mod.rs"
+      ),
+      "serde-1.0.200/src/de/mod.rs"
+    );
+    assert_eq!(
+      windows().shorten(
+        "C:\\Users\\me\\.cargo\\registry\\src\\index.crates.io-6f17d22bba15001f\\serde-1.0.200\\src\\lib.rs"
+      ),
+      "serde-1.0.200\\src\\lib.rs"
+    );
+    // registries outside of the home directory
+    assert_eq!(
+      unix().shorten("/opt/cargo/.cargo/registry/src/index-1234/regex-1.10.0/src/lib.rs"),
+      "regex-1.10.0/src/lib.rs"
+    );
+  }
+
+  #[test]
+  fn home() {
+    assert_eq!(unix().shorten("/home/me/notes/todo.rs"), "~/notes/todo.rs");
+    assert_eq!(
+      windows().shorten("C:\\Users\\me\\notes\\todo.rs"),
+      "~\\notes\\todo.rs"
+    );
+  }
+
+  #[test]
+  fn unrelated() {
+    assert_eq!(
+      unix().shorten("/usr/lib/rustlib/src/rust/library/core/src/option.rs"),
+      "/usr/lib/rustlib/src/rust/library/core/src/option.rs"
+    );
+    assert_eq!(windows().shorten("D:\\other\\lib.rs"), "D:\\other\\lib.rs");
+    assert_eq!(
+      PathDisplay::default().shorten("/home/me/projects/demo/src/main.rs"),
+      "/home/me/projects/demo/src/main.rs"
+    );
+  }
+
+  #[test]
+  fn pad_wide_chars() {
+    assert_eq!(pad_to_width("↑", 3), "↑  ");
+    assert_eq!(pad_to_width("日本", 5), "日本 ");
+    assert_eq!(pad_to_width("toolong", 3), "toolong");
+  }
+
+  #[test]
+  fn truncate_wide_chars() {
+    assert_eq!(truncate_to_width("short", 10), "short");
+    assert_eq!(truncate_to_width("description", 6), "descr…");
+    // a wide char never gets split in half
+    assert_eq!(truncate_to_width("日本語", 4), "日…");
+    assert_eq!(truncate_to_width("abc", 0), "");
+  }
+
+  #[test]
+  fn title() {
+    let mut out = vec![];
+    restore_title(&mut out).unwrap();
+    assert!(out.is_empty());
+    set_title(&mut out, "nbuild: building demo… (1s)").unwrap();
+    set_title(&mut out, "nbuild: ✗ 2\nerrors\x07").unwrap();
+    restore_title(&mut out).unwrap();
+    restore_title(&mut out).unwrap();
+    assert_eq!(
+      String::from_utf8(out).unwrap(),
+      concat!(
+        // the previous title gets saved once
+        "\x1b[22;2t\x1b]2;nbuild: building demo… (1s)\x07",
+        "\x1b]2;nbuild: ✗ 2errors\x07",
+        "\x1b]2;\x07\x1b[23;2t",
+      )
+    );
+  }
+
+  #[test]
+  fn progress_escapes() {
+    assert_eq!(TermProgress::Clear.escape(), "\x1b]9;4;0;0\x07");
+    assert_eq!(TermProgress::Indeterminate.escape(), "\x1b]9;4;3;0\x07");
+    assert_eq!(TermProgress::Value(42).escape(), "\x1b]9;4;1;42\x07");
+    assert_eq!(TermProgress::Value(250).escape(), "\x1b]9;4;1;100\x07");
+    assert_eq!(TermProgress::Error(100).escape(), "\x1b]9;4;2;100\x07");
+    assert_eq!(TermProgress::of(3, 0), TermProgress::Indeterminate);
+    assert_eq!(TermProgress::of(1, 3), TermProgress::Value(33));
+    assert_eq!(TermProgress::of(5, 4), TermProgress::Value(100));
+    assert_eq!(
+      notification_escape("nbuild", "✗ 2 errors;\n1 warning\x07"),
+      "\x1b]777;notify;nbuild;✗ 2 errors1 warning\x07"
+    );
+  }
+
+  #[test]
+  fn term_capabilities() {
+    let none = TermCapabilities::default();
+    let progress = TermCapabilities {
+      progress: true,
+      notifications: false,
+    };
+    let notifications = TermCapabilities {
+      progress: false,
+      notifications: true,
+    };
+    let both = TermCapabilities {
+      progress: true,
+      notifications: true,
+    };
+    for (is_terminal, vars, expected) in [
+      (true, &[("TERM", "xterm-256color")][..], none),
+      (true, &[("WT_SESSION", "1")][..], progress),
+      (true, &[("ConEmuPID", "42")][..], progress),
+      (true, &[("TERM", "foot")][..], notifications),
+      (true, &[("TERM", "foot-extra")][..], notifications),
+      (true, &[("TERM_PROGRAM", "ghostty")][..], both),
+      (true, &[("TERM_PROGRAM", "vscode")][..], none),
+      // piped, dumb or behind tmux
+      (false, &[("WT_SESSION", "1")][..], none),
+      (true, &[("TERM", "dumb"), ("WT_SESSION", "1")][..], none),
+      (
+        true,
+        &[("TMUX", "/tmp/tmux"), ("TERM_PROGRAM", "ghostty")][..],
+        none,
+      ),
+    ] {
+      let vars = vars.iter().copied().collect::<HashMap<_, _>>();
+      assert_eq!(
+        TermCapabilities::from_env(is_terminal, |name| vars.get(name).map(|v| v.to_string())),
+        expected,
+        "{:?}",
+        vars
+      );
+    }
+  }
+
+  #[test]
+  fn progress_reporter() {
+    let write = |reporter: &mut ProgressReporter, now: Instant| {
+      let mut out = vec![];
+      reporter.write(&mut out, now).unwrap();
+      String::from_utf8(out).unwrap()
+    };
+    let now = Instant::now();
+    let mut reporter = ProgressReporter::new(TermCapabilities {
+      progress: true,
+      notifications: true,
+    });
+    assert_eq!(write(&mut reporter, now), "");
+    reporter.running(0, 0);
+    assert_eq!(write(&mut reporter, now), "\x1b]9;4;3;0\x07");
+    // unchanged, written again once stale
+    reporter.running(0, 0);
+    assert_eq!(write(&mut reporter, now + Duration::from_secs(1)), "");
+    let later = now + ProgressReporter::REFRESH;
+    assert_eq!(write(&mut reporter, later), "\x1b]9;4;3;0\x07");
+    reporter.running(1, 4);
+    assert_eq!(write(&mut reporter, later), "\x1b]9;4;1;25\x07");
+    reporter.finished(false, "✗ 1 error", later);
+    assert_eq!(
+      write(&mut reporter, later),
+      "\x1b]777;notify;nbuild;✗ 1 error\x07\x1b]9;4;2;100\x07"
+    );
+    reporter.tick(later + Duration::from_secs(1));
+    assert_eq!(write(&mut reporter, later + Duration::from_secs(1)), "");
+    let cleared = later + ProgressReporter::CLEAR_DELAY;
+    reporter.tick(cleared);
+    assert_eq!(write(&mut reporter, cleared), "\x1b]9;4;0;0\x07");
+    // cleared once, never refreshed
+    reporter.clear();
+    assert_eq!(
+      write(&mut reporter, cleared + ProgressReporter::REFRESH),
+      ""
+    );
+
+    // nothing supported, nothing written
+    let mut reporter = ProgressReporter::new(TermCapabilities::default());
+    reporter.running(0, 0);
+    reporter.finished(true, "✓", now);
+    assert_eq!(write(&mut reporter, now), "");
+  }
+
+  #[test]
+  fn count() {
+    for (n, comma, space) in [
+      (0, "0", "0"),
+      (999, "999", "999"),
+      (1000, "1,000", "1 000"),
+      (12345, "12,345", "12 345"),
+      (1234567, "1,234,567", "1 234 567"),
+    ] {
+      assert_eq!(format_count_with(n, ThousandsSeparator::Comma), comma);
+      assert_eq!(format_count_with(n, ThousandsSeparator::Space), space);
+      assert_eq!(
+        format_count_with(n, ThousandsSeparator::None),
+        n.to_string()
+      );
+    }
+    assert_eq!(
+      "Space".parse::<ThousandsSeparator>().unwrap(),
+      ThousandsSeparator::Space
+    );
+    assert!("dot".parse::<ThousandsSeparator>().is_err());
+  }
+
+  #[test]
+  fn compact() {
+    for (n, expected) in [
+      (999, "999"),
+      (1000, "1k"),
+      (1049, "1k"),
+      (1250, "1.3k"),
+      (12345, "12.3k"),
+      (99_949, "99.9k"),
+      (99_950, "100k"),
+      (123_456, "123k"),
+      (999_499, "999k"),
+      // rounded up to the next unit
+      (999_500, "1M"),
+      (12_345_678, "12.3M"),
+      (999_499_999_999, "999G"),
+      (2_000_000_000_000, "2000G"),
+    ] {
+      assert_eq!(compact_count(n), expected, "{}", n);
+    }
+    // the width the status bar budgets for
+    for n in (0..12)
+      .map(|exp| 10usize.pow(exp))
+      .flat_map(|n| [n, n * 99_949 / 1000])
+    {
+      assert!(compact_count(n).len() <= 5, "{}", n);
+    }
+  }
+
+  #[test]
+  fn duration() {
+    for (d, expected) in [
+      (Duration::ZERO, "0µs"),
+      (Duration::from_micros(450), "450µs"),
+      (Duration::from_micros(999), "999µs"),
+      (Duration::from_millis(1), "1ms"),
+      (Duration::from_millis(980), "980ms"),
+      (Duration::from_millis(1000), "1.0s"),
+      (Duration::from_millis(45_234), "45.2s"),
+      (Duration::from_millis(59_940), "59.9s"),
+      (Duration::from_millis(59_960), "1m 00s"),
+      (Duration::from_secs(60), "1m 00s"),
+      (Duration::from_secs(123), "2m 03s"),
+      (Duration::from_secs(3599), "59m 59s"),
+      (Duration::from_secs(3600), "1h 00m"),
+      (Duration::from_secs(3900), "1h 05m"),
+    ] {
+      assert_eq!(human_duration(d), expected, "{:?}", d);
+    }
+  }
+  #[test]
+  fn size() {
+    for (bytes, expected) in [
+      (0, "0 B"),
+      (1023, "1023 B"),
+      (1024, "1.0 KiB"),
+      (1536, "1.5 KiB"),
+      (4 * 1024 * 1024, "4.0 MiB"),
+      (3 * 1024 * 1024 * 1024, "3.0 GiB"),
+      (2048 * 1024 * 1024 * 1024, "2048.0 GiB"),
+    ] {
+      assert_eq!(human_size(bytes), expected, "{}", bytes);
     }
-    return Some(ret);
   }
 }
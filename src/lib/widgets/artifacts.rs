@@ -0,0 +1,184 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{display_path, Artifact};
+
+use super::help::popup_area;
+
+/// What to do with the selected [`Artifact`], applied by the renderer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactAction {
+  /// Copy the path of the n-th artifact to the clipboard
+  Copy(usize),
+  /// Reveal the n-th artifact in the file manager
+  Reveal(usize),
+}
+
+/// The state of the [`ArtifactList`]: the selected artifact and the pending action
+#[derive(Debug, Clone, Default)]
+pub struct ArtifactsState {
+  show: bool,
+  cursor: usize,
+  action: Option<ArtifactAction>,
+}
+
+impl ArtifactsState {
+  /// Check if the list is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the position of the selected artifact
+  #[cfg(test)]
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  /// Take the action chosen by the user
+  pub fn take_action(&mut self) -> Option<ArtifactAction> {
+    self.action.take()
+  }
+}
+
+/// The files produced by the build: `Enter` copies the path of the selected one, `o` reveals it
+pub struct ArtifactList<'a> {
+  artifacts: &'a [Artifact],
+}
+
+impl<'a> ArtifactList<'a> {
+  /// Construct this object from the produced files
+  pub fn new(artifacts: &'a [Artifact]) -> Self {
+    Self { artifacts }
+  }
+
+  /// Handle `a` to show/hide the list. Once displayed, `Up`/`Down` select an artifact,
+  /// `Enter` copies its path, `o` reveals it and `Esc`/`q` closes.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut ArtifactsState, num_artifacts: usize) -> bool {
+    if !state.show {
+      if key.code != KeyCode::Char('a') {
+        return false;
+      }
+      state.show = true;
+      state.cursor = state.cursor.min(num_artifacts.saturating_sub(1));
+      return true;
+    }
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('a') => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.cursor = state.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => {
+        state.cursor = (state.cursor + 1).min(num_artifacts.saturating_sub(1))
+      }
+      KeyCode::Enter if num_artifacts > 0 => {
+        state.action = Some(ArtifactAction::Copy(state.cursor))
+      }
+      KeyCode::Char('o') if num_artifacts > 0 => {
+        state.action = Some(ArtifactAction::Reveal(state.cursor))
+      }
+      _ => {}
+    }
+    true
+  }
+}
+
+impl<'a> StatefulWidget for ArtifactList<'a> {
+  type State = ArtifactsState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 70, 50);
+    Clear.render(area, buf);
+    let num_rows = area.height.saturating_sub(2) as usize;
+    let first = (state.cursor + 1).saturating_sub(num_rows);
+    let name_width = self
+      .artifacts
+      .iter()
+      .map(|artifact| artifact.name.len())
+      .max()
+      .unwrap_or_default();
+    let lines = match self.artifacts.is_empty() {
+      true => {
+        vec![
+          Line::from("no artifact reported, build with --message-format=json to list them")
+            .dark_gray(),
+        ]
+      }
+      false => self
+        .artifacts
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(num_rows)
+        .map(|(i, artifact)| {
+          let line = Line::default().spans(vec![
+            Span::from(format!("{:<width$} ", artifact.name, width = name_width)).bold(),
+            Span::from(format!("{:<10} ", artifact.kind)).cyan(),
+            Span::from(display_path(&artifact.path)),
+          ]);
+          match i == state.cursor {
+            true => line.style(Style::default().on_light_blue()),
+            false => line,
+          }
+        })
+        .collect::<Vec<_>>(),
+    };
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title(format!(
+            "Artifacts ({}, Enter: copy path, o: reveal, Esc: close)",
+            self.artifacts.len()
+          ))
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+  use super::{ArtifactAction, ArtifactList, ArtifactsState};
+
+  fn press(state: &mut ArtifactsState, code: KeyCode) -> bool {
+    ArtifactList::handle_key(KeyEvent::from(code), state, 3)
+  }
+
+  #[test]
+  fn handle_key() {
+    let mut state = ArtifactsState::default();
+    assert!(!press(&mut state, KeyCode::Enter));
+    assert!(press(&mut state, KeyCode::Char('a')));
+    assert!(state.is_shown());
+    for code in [KeyCode::Down, KeyCode::Down, KeyCode::Down, KeyCode::Up] {
+      assert!(press(&mut state, code));
+    }
+    assert_eq!(state.cursor(), 1);
+    assert!(press(&mut state, KeyCode::Enter));
+    assert_eq!(state.take_action(), Some(ArtifactAction::Copy(1)));
+    assert_eq!(state.take_action(), None);
+    assert!(press(&mut state, KeyCode::Char('o')));
+    assert_eq!(state.take_action(), Some(ArtifactAction::Reveal(1)));
+    // the list stays open until closed
+    assert!(state.is_shown());
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_shown());
+    // nothing to act on
+    let mut state = ArtifactsState::default();
+    ArtifactList::handle_key(KeyEvent::from(KeyCode::Char('a')), &mut state, 0);
+    ArtifactList::handle_key(KeyEvent::from(KeyCode::Enter), &mut state, 0);
+    assert_eq!(state.take_action(), None);
+  }
+}
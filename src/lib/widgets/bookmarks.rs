@@ -0,0 +1,394 @@
+use std::{
+  collections::BTreeMap,
+  time::{Duration, Instant},
+};
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{BlockFingerprint, EntryId, MarkedBlock};
+
+use super::help::popup_area;
+
+/// A named log line, see [`BookmarksState`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+  pub entry_id: EntryId,
+  /// The beginning of the line, for display
+  pub text: String,
+  /// The block of the line and its offset in it, to find it back after a re-run
+  anchor: Option<(BlockFingerprint, usize)>,
+}
+
+impl Bookmark {
+  /// The number of chars of the line kept for display
+  pub const MAX_TEXT_LEN: usize = 80;
+
+  pub fn new<T: AsRef<str>>(entry_id: EntryId, text: T) -> Self {
+    Self {
+      entry_id,
+      text: text.as_ref().chars().take(Self::MAX_TEXT_LEN).collect(),
+      anchor: None,
+    }
+  }
+}
+
+/// The first key of a two-key sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prefix {
+  /// `m`: set a bookmark
+  Set,
+  /// `'`: jump to a bookmark
+  Jump,
+}
+
+/// The bookmarks `a` to `z` and the state of the [`BookmarkList`]
+#[derive(Debug, Clone, Default)]
+pub struct BookmarksState {
+  show: bool,
+  cursor: usize,
+  marks: BTreeMap<char, Bookmark>,
+  /// The first key of a sequence and when it was pressed
+  pending: Option<(Prefix, Instant)>,
+  /// The bookmarks wait for a re-run to finish, see [`Self::detach`]
+  detached: bool,
+  /// The entry to jump to, taken by the renderer
+  jump: Option<EntryId>,
+}
+
+impl BookmarksState {
+  /// The delay after which the first key of a sequence is forgotten
+  pub const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+  /// Check if the list is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Check if the first key of a sequence is waiting for the letter
+  #[cfg(test)]
+  pub fn is_pending(&self) -> bool {
+    self.pending.is_some()
+  }
+
+  /// Retrieve the bookmarks, sorted by letter
+  pub fn marks(&self) -> &BTreeMap<char, Bookmark> {
+    &self.marks
+  }
+
+  /// Retrieve a bookmark
+  pub fn get(&self, letter: char) -> Option<&Bookmark> {
+    self.marks.get(&letter)
+  }
+
+  /// Set the bookmark `letter`, replacing the previous one
+  pub fn set(&mut self, letter: char, bookmark: Bookmark) {
+    self.marks.insert(letter, bookmark);
+  }
+
+  /// Take the entry of the bookmark chosen by the user
+  pub fn take_jump(&mut self) -> Option<EntryId> {
+    self.jump.take()
+  }
+
+  /// Check if the bookmarks wait for [`Self::reattach`]
+  pub fn is_detached(&self) -> bool {
+    self.detached
+  }
+
+  /// Forget the entry ids before a re-run: the bookmarks `anchor` places in a block
+  /// are kept to be found back by [`Self::reattach`], the others are dropped
+  pub fn detach<F: Fn(EntryId) -> Option<(BlockFingerprint, usize)>>(&mut self, anchor: F) {
+    self.marks.retain(|_letter, bookmark| {
+      bookmark.anchor = anchor(bookmark.entry_id);
+      bookmark.anchor.is_some()
+    });
+    self.detached = !self.marks.is_empty();
+    self.cursor = 0;
+  }
+
+  /// Find the detached bookmarks back in the blocks of the new build,
+  /// the ones without a matching block are dropped
+  pub fn reattach(&mut self, blocks: &[MarkedBlock<'_>]) {
+    let fingerprints = blocks
+      .iter()
+      .map(BlockFingerprint::from_block)
+      .collect::<Vec<_>>();
+    self.marks.retain(|_letter, bookmark| {
+      let Some((fingerprint, offset)) = bookmark.anchor.take() else {
+        return true;
+      };
+      let Some(block) = fingerprint.best_match(&fingerprints).map(|id| &blocks[id]) else {
+        return false;
+      };
+      let offset = offset.min(block.entries().len().saturating_sub(1));
+      let Some(entry) = block.entries().get(offset) else {
+        return false;
+      };
+      *bookmark = Bookmark::new(EntryId(block.range().start + offset), entry.message());
+      true
+    });
+    self.detached = false;
+  }
+
+  fn letter(key: &KeyEvent) -> Option<char> {
+    match key.code {
+      KeyCode::Char(ch @ 'a'..='z') => Some(ch),
+      _ => None,
+    }
+  }
+}
+
+/// The bookmarks popup. `m` followed by a letter sets a bookmark on the current line,
+/// `'` followed by the letter jumps back to it
+pub struct BookmarkList;
+
+impl BookmarkList {
+  /// Handle the `m`/`'` sequences and `B` to show/hide the list. Once displayed,
+  /// `Up`/`Down` select a bookmark, `Enter` jumps, `d` deletes and `Esc`/`q` closes.
+  ///
+  /// `current` retrieves the line to bookmark. Returns true if the key was handled,
+  /// a sequence interrupted by another key lets it be handled as usual
+  pub fn handle_key<F: FnOnce() -> Option<(EntryId, String)>>(
+    key: KeyEvent,
+    state: &mut BookmarksState,
+    now: Instant,
+    current: F,
+  ) -> bool {
+    if let Some((prefix, at)) = state.pending.take() {
+      if now.saturating_duration_since(at) <= BookmarksState::SEQUENCE_TIMEOUT {
+        let Some(letter) = BookmarksState::letter(&key) else {
+          return key.code == KeyCode::Esc;
+        };
+        match prefix {
+          Prefix::Set => {
+            if let Some((entry_id, text)) = current() {
+              state.set(letter, Bookmark::new(entry_id, text));
+            }
+          }
+          // the entry ids are stale until reattached
+          Prefix::Jump if !state.detached => {
+            state.jump = state.get(letter).map(|bookmark| bookmark.entry_id)
+          }
+          Prefix::Jump => {}
+        }
+        return true;
+      }
+    }
+    if !state.show {
+      match key.code {
+        KeyCode::Char('m') => state.pending = Some((Prefix::Set, now)),
+        KeyCode::Char('\'') => state.pending = Some((Prefix::Jump, now)),
+        KeyCode::Char('B') => {
+          state.show = true;
+          state.cursor = state.cursor.min(state.marks.len().saturating_sub(1));
+        }
+        _ => return false,
+      }
+      return true;
+    }
+    let selected = state.marks.keys().nth(state.cursor).copied();
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('B') => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.cursor = state.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => {
+        state.cursor = (state.cursor + 1).min(state.marks.len().saturating_sub(1))
+      }
+      KeyCode::Enter => {
+        if let Some(letter) = selected {
+          state.jump = state.get(letter).map(|bookmark| bookmark.entry_id);
+          state.show = false;
+        }
+      }
+      KeyCode::Char('d') | KeyCode::Delete => {
+        if let Some(letter) = selected {
+          state.marks.remove(&letter);
+          state.cursor = state.cursor.min(state.marks.len().saturating_sub(1));
+        }
+      }
+      _ => {}
+    }
+    true
+  }
+}
+
+impl StatefulWidget for BookmarkList {
+  type State = BookmarksState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 70, 50);
+    Clear.render(area, buf);
+    let num_rows = area.height.saturating_sub(2) as usize;
+    let first = (state.cursor + 1).saturating_sub(num_rows);
+    let number_width = state
+      .marks
+      .values()
+      .map(|bookmark| (bookmark.entry_id.0 + 1).to_string().len())
+      .max()
+      .unwrap_or_default();
+    let lines = match state.marks.is_empty() {
+      true => vec![Line::from("no bookmark, press m then a letter to set one").dark_gray()],
+      false => state
+        .marks
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(num_rows)
+        .map(|(i, (letter, bookmark))| {
+          let line = Line::default().spans(vec![
+            Span::from(format!("{} ", letter)).bold().yellow(),
+            Span::from(format!(
+              "{:>width$} ",
+              bookmark.entry_id.0 + 1,
+              width = number_width
+            ))
+            .dark_gray(),
+            Span::from(bookmark.text.clone()),
+          ]);
+          match i == state.cursor {
+            true => line.style(Style::default().on_light_blue()),
+            false => line,
+          }
+        })
+        .collect::<Vec<_>>(),
+    };
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title("Bookmarks (Enter: jump, d: delete, Esc: close)")
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+  use crate::{BlockFingerprint, BuildOutput, EntryId};
+
+  use super::{Bookmark, BookmarkList, BookmarksState};
+
+  fn press(state: &mut BookmarksState, code: KeyCode, now: Instant, line: usize) -> bool {
+    BookmarkList::handle_key(KeyEvent::from(code), state, now, || {
+      Some((EntryId(line), format!("line {}", line)))
+    })
+  }
+
+  #[test]
+  fn set_and_jump() {
+    let (mut state, now) = (BookmarksState::default(), Instant::now());
+    assert!(press(&mut state, KeyCode::Char('m'), now, 4));
+    assert!(state.is_pending());
+    assert!(press(&mut state, KeyCode::Char('a'), now, 4));
+    assert!(!state.is_pending());
+    press(&mut state, KeyCode::Char('m'), now, 9);
+    press(&mut state, KeyCode::Char('c'), now, 9);
+    assert_eq!(state.get('a'), Some(&Bookmark::new(EntryId(4), "line 4")));
+    assert!(press(&mut state, KeyCode::Char('\''), now, 0));
+    assert!(press(&mut state, KeyCode::Char('c'), now, 0));
+    assert_eq!(state.take_jump(), Some(EntryId(9)));
+    // unknown bookmark
+    press(&mut state, KeyCode::Char('\''), now, 0);
+    press(&mut state, KeyCode::Char('z'), now, 0);
+    assert_eq!(state.take_jump(), None);
+    assert_eq!(
+      Bookmark::new(EntryId(0), "é".repeat(100))
+        .text
+        .chars()
+        .count(),
+      Bookmark::MAX_TEXT_LEN
+    );
+  }
+
+  #[test]
+  fn sequencing() {
+    let (mut state, now) = (BookmarksState::default(), Instant::now());
+    // a non-letter interrupts the sequence and gets handled as usual
+    press(&mut state, KeyCode::Char('m'), now, 1);
+    assert!(!press(&mut state, KeyCode::Down, now, 1));
+    assert!(state.marks().is_empty());
+    assert!(!press(&mut state, KeyCode::Char('j'), now, 1));
+    // Esc cancels it
+    press(&mut state, KeyCode::Char('m'), now, 1);
+    assert!(press(&mut state, KeyCode::Esc, now, 1));
+    assert!(!state.is_pending());
+    // too late, the letter is a key of its own
+    press(&mut state, KeyCode::Char('m'), now, 1);
+    let later = now + BookmarksState::SEQUENCE_TIMEOUT + Duration::from_millis(1);
+    assert!(!press(&mut state, KeyCode::Char('e'), later, 1));
+    assert!(state.marks().is_empty());
+    // the first key of a new sequence restarts the timeout
+    press(&mut state, KeyCode::Char('m'), now, 1);
+    assert!(press(&mut state, KeyCode::Char('m'), later, 1));
+    assert!(press(&mut state, KeyCode::Char('a'), later, 1));
+    assert_eq!(state.marks().keys().collect::<String>(), "a");
+  }
+
+  #[test]
+  fn list() {
+    let (mut state, now) = (BookmarksState::default(), Instant::now());
+    for (letter, line) in [('b', 7), ('a', 3), ('c', 12)] {
+      press(&mut state, KeyCode::Char('m'), now, line);
+      press(&mut state, KeyCode::Char(letter), now, line);
+    }
+    assert!(press(&mut state, KeyCode::Char('B'), now, 0));
+    assert!(state.is_shown());
+    // sorted by letter: a, b, c
+    press(&mut state, KeyCode::Down, now, 0);
+    press(&mut state, KeyCode::Char('d'), now, 0);
+    assert_eq!(state.marks().keys().collect::<String>(), "ac");
+    press(&mut state, KeyCode::Enter, now, 0);
+    assert!(!state.is_shown());
+    assert_eq!(state.take_jump(), Some(EntryId(12)));
+  }
+
+  #[test]
+  fn reattach() {
+    let (mut state, now) = (BookmarksState::default(), Instant::now());
+    let mut build = BuildOutput::from([
+      "   Compiling demo v0.1.0",
+      "warning: unused variable: `x`",
+      " --> src/main.rs:2:9",
+      "error[E0308]: mismatched types",
+      " --> src/main.rs:4:5",
+    ]);
+    while build.prepare() {}
+    for (letter, line) in [('a', 0), ('b', 4)] {
+      press(&mut state, KeyCode::Char('m'), now, line);
+      press(&mut state, KeyCode::Char(letter), now, line);
+    }
+    state.detach(|entry_id| {
+      let block = build.block_at(entry_id)?;
+      Some((
+        BlockFingerprint::from_block(&block),
+        entry_id.0 - block.range().start,
+      ))
+    });
+    assert!(state.is_detached());
+    // the line outside of any block is dropped
+    assert_eq!(state.marks().keys().collect::<String>(), "b");
+    let mut rebuilt = BuildOutput::from(["error[E0308]: mismatched types", " --> src/main.rs:6:5"]);
+    while rebuilt.prepare() {}
+    state.reattach(&rebuilt.blocks());
+    assert!(!state.is_detached());
+    assert_eq!(
+      state.get('b'),
+      Some(&Bookmark::new(EntryId(1), " --> src/main.rs:6:5"))
+    );
+  }
+}
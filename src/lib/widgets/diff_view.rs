@@ -0,0 +1,328 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::{Constraint, Layout},
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Paragraph, ScrollbarState, StatefulWidget, Tabs, Widget},
+};
+
+use crate::{BuildDiff, DiffBlock, DiffStatus, DisplayedEntry, LogView};
+
+/// The sections of the diff tab, in display order
+pub const DIFF_SECTIONS: [(DiffStatus, &str); 3] = [
+  (DiffStatus::Regression, "Regressions"),
+  (DiffStatus::Fixed, "Fixed"),
+  (DiffStatus::Unchanged, "Unchanged"),
+];
+
+/// The tabs of the [`DiffView`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffTab {
+  /// The block lists
+  #[default]
+  Diff,
+  /// The full old log
+  Old,
+  /// The full new log
+  New,
+}
+
+/// The state of the [`DiffView`]: the tab, the selected block and the log scrolls
+#[derive(Debug, Clone, Default)]
+pub struct DiffState {
+  tab: DiffTab,
+  selected: usize,
+  list_scroll: usize,
+  scrolls: [usize; 2],
+}
+
+impl DiffState {
+  #[cfg(test)]
+  pub fn tab(&self) -> DiffTab {
+    self.tab
+  }
+
+  /// Retrieve the index of the selected block, see [`Self::items`]
+  #[cfg(test)]
+  pub fn selected(&self) -> usize {
+    self.selected
+  }
+
+  /// Retrieve the scroll of a log tab
+  pub fn scroll(&self, tab: DiffTab) -> usize {
+    match tab {
+      DiffTab::Old => self.scrolls[0],
+      DiffTab::New => self.scrolls[1],
+      DiffTab::Diff => self.list_scroll,
+    }
+  }
+
+  fn scroll_mut(&mut self) -> &mut usize {
+    match self.tab {
+      DiffTab::Old => &mut self.scrolls[0],
+      DiffTab::New => &mut self.scrolls[1],
+      DiffTab::Diff => &mut self.list_scroll,
+    }
+  }
+
+  /// Retrieve the blocks of the diff tab, grouped by [`DIFF_SECTIONS`]
+  pub fn items(diff: &BuildDiff) -> Vec<&DiffBlock> {
+    DIFF_SECTIONS
+      .iter()
+      .flat_map(|(status, _)| diff.with_status(*status))
+      .collect()
+  }
+
+  /// Open the log containing the selected block, scrolled to it:
+  /// the old one for fixed blocks, the new one otherwise
+  pub fn jump(&mut self, diff: &BuildDiff) {
+    let Some(block) = Self::items(diff).get(self.selected).copied() else {
+      return;
+    };
+    let (tab, range) = match (block.new.as_ref(), block.old.as_ref()) {
+      (Some(range), _) => (DiffTab::New, range),
+      (None, Some(range)) => (DiffTab::Old, range),
+      (None, None) => return,
+    };
+    self.tab = tab;
+    *self.scroll_mut() = range.start;
+  }
+
+  /// Handle `Tab`/`1`/`2`/`3` to switch tabs, `j`/`k` to move and `Enter` to jump
+  /// to the selected block, `Esc` goes back to the diff tab.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut DiffState, diff: &BuildDiff) -> bool {
+    match key.code {
+      KeyCode::Tab => {
+        state.tab = match state.tab {
+          DiffTab::Diff => DiffTab::Old,
+          DiffTab::Old => DiffTab::New,
+          DiffTab::New => DiffTab::Diff,
+        }
+      }
+      KeyCode::Char('1') => state.tab = DiffTab::Diff,
+      KeyCode::Char('2') => state.tab = DiffTab::Old,
+      KeyCode::Char('3') => state.tab = DiffTab::New,
+      KeyCode::Esc if state.tab != DiffTab::Diff => state.tab = DiffTab::Diff,
+      KeyCode::Enter if state.tab == DiffTab::Diff => state.jump(diff),
+      KeyCode::Char('j') | KeyCode::Down => match state.tab {
+        DiffTab::Diff => {
+          state.selected = (state.selected + 1).min(Self::items(diff).len().saturating_sub(1))
+        }
+        _ => *state.scroll_mut() += 1,
+      },
+      KeyCode::Char('k') | KeyCode::Up => match state.tab {
+        DiffTab::Diff => state.selected = state.selected.saturating_sub(1),
+        _ => *state.scroll_mut() = state.scroll_mut().saturating_sub(1),
+      },
+      _ => return false,
+    }
+    true
+  }
+}
+
+/// Display a [`BuildDiff`] in three tabs: the block lists, the old log and the new log
+pub struct DiffView<'a> {
+  diff: &'a BuildDiff,
+  logs: [Vec<DisplayedEntry<'a>>; 2],
+  names: [String; 2],
+}
+
+impl<'a> DiffView<'a> {
+  /// Construct the view from the diff, the displayable lines and the names of both logs
+  pub fn new<N: AsRef<str>>(
+    diff: &'a BuildDiff,
+    old: Vec<DisplayedEntry<'a>>,
+    new: Vec<DisplayedEntry<'a>>,
+    names: [N; 2],
+  ) -> Self {
+    Self {
+      diff,
+      logs: [old, new],
+      names: names.map(|name| name.as_ref().to_string()),
+    }
+  }
+
+  /// Build the lines of the diff tab, returning the row of the selected block
+  fn list(&self, selected: usize) -> (Vec<Line<'static>>, usize) {
+    let mut lines = vec![];
+    let mut selected_row = 0;
+    let mut item_id = 0;
+    for (status, title) in DIFF_SECTIONS {
+      let style = match status {
+        DiffStatus::Regression => Style::default().red(),
+        DiffStatus::Fixed => Style::default().green(),
+        DiffStatus::Unchanged => Style::default().gray(),
+      };
+      let blocks = self.diff.with_status(status).collect::<Vec<_>>();
+      lines.push(Line::from(Span::styled(
+        format!("{} ({})", title, blocks.len()),
+        style.bold(),
+      )));
+      for block in blocks {
+        let mut spans = vec![
+          Span::raw("  "),
+          Span::styled(block.fingerprint.first_line.clone(), style),
+        ];
+        if let Some(path) = block.fingerprint.path.as_ref() {
+          spans.push(Span::raw(format!("  {}", path.display())).dim());
+        }
+        let mut line = Line::from(spans);
+        if item_id == selected {
+          line = line.patch_style(Style::default().on_light_blue());
+          selected_row = lines.len();
+        }
+        lines.push(line);
+        item_id += 1;
+      }
+      lines.push(Line::default());
+    }
+    (lines, selected_row)
+  }
+}
+
+impl<'a> StatefulWidget for DiffView<'a> {
+  type State = DiffState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let [tabs_area, body_area] =
+      Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(area);
+    let selected_tab = match state.tab {
+      DiffTab::Diff => 0,
+      DiffTab::Old => 1,
+      DiffTab::New => 2,
+    };
+    Tabs::new(vec![
+      "1: diff".to_string(),
+      format!("2: {}", self.names[0]),
+      format!("3: {}", self.names[1]),
+    ])
+    .select(selected_tab)
+    .highlight_style(Style::default().bold().on_light_blue())
+    .render(tabs_area, buf);
+    match state.tab {
+      DiffTab::Diff => {
+        let (lines, selected_row) = self.list(state.selected);
+        // keep the selection visible
+        let height = body_area.height.saturating_sub(2) as usize;
+        if selected_row < state.list_scroll {
+          state.list_scroll = selected_row;
+        } else if height > 0 && selected_row >= state.list_scroll + height {
+          state.list_scroll = selected_row + 1 - height;
+        }
+        Paragraph::new(lines)
+          .block(Block::bordered())
+          .scroll((state.list_scroll as u16, 0))
+          .render(body_area, buf);
+      }
+      tab => {
+        let id = (tab == DiffTab::New) as usize;
+        let [old, new] = self.logs;
+        let lines = match tab {
+          DiffTab::New => new,
+          _ => old,
+        };
+        let scroll = state.scroll(tab);
+        let mut scroll_state = ScrollbarState::default().position(scroll);
+        LogView::default()
+          .with_content(lines)
+          .with_title(&self.names[id])
+          .with_focus(true)
+          .with_scroll(scroll)
+          .render(body_area, buf, &mut scroll_state);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    backend::TestBackend,
+    crossterm::event::{KeyCode, KeyEvent},
+    Terminal,
+  };
+
+  use crate::{BuildDiff, BuildOutput};
+
+  use super::{DiffState, DiffTab, DiffView};
+
+  fn diff() -> (BuildOutput<'static>, BuildOutput<'static>, BuildDiff) {
+    let mut old = BuildOutput::from(["error: a", "error: b"]);
+    old.prepare();
+    let mut new = BuildOutput::from(["warning: c", "error: b", "error: d"]);
+    new.prepare();
+    let diff = BuildDiff::new(&old, &new);
+    (old, new, diff)
+  }
+
+  fn draw(state: &mut DiffState) -> Vec<String> {
+    let (old, new, diff) = diff();
+    let mut terminal = Terminal::new(TestBackend::new(30, 12)).unwrap();
+    terminal
+      .draw(|frame| {
+        let view = DiffView::new(&diff, old.display(), new.display(), ["old.log", "new.log"]);
+        frame.render_stateful_widget(view, frame.area(), state)
+      })
+      .unwrap();
+    let buf = terminal.backend().buffer();
+    (0..buf.area.height)
+      .map(|y| {
+        (0..buf.area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>()
+          .trim_end()
+          .to_string()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn sections() {
+    let lines = draw(&mut DiffState::default());
+    assert_eq!(
+      lines[1..12],
+      [
+        "┌────────────────────────────┐",
+        "│Regressions (2)             │",
+        "│  warning: c                │",
+        "│  error: d                  │",
+        "│                            │",
+        "│Fixed (1)                   │",
+        "│  error: a                  │",
+        "│                            │",
+        "│Unchanged (1)               │",
+        "│  error: b                  │",
+        "└────────────────────────────┘",
+      ]
+    );
+  }
+
+  #[test]
+  fn jump() {
+    let (_old, _new, diff) = diff();
+    let mut state = DiffState::default();
+    let press =
+      |state: &mut DiffState, code| DiffState::handle_key(KeyEvent::from(code), state, &diff);
+    // the fixed block
+    assert!(press(&mut state, KeyCode::Char('j')));
+    assert!(press(&mut state, KeyCode::Char('j')));
+    assert_eq!(state.selected(), 2);
+    assert!(press(&mut state, KeyCode::Enter));
+    assert_eq!(state.tab(), DiffTab::Old);
+    assert_eq!(state.scroll(DiffTab::Old), 0);
+    assert!(press(&mut state, KeyCode::Esc));
+    // the unchanged block, in the new log
+    assert!(press(&mut state, KeyCode::Char('j')));
+    assert!(press(&mut state, KeyCode::Enter));
+    assert_eq!(state.tab(), DiffTab::New);
+    assert_eq!(state.scroll(DiffTab::New), 1);
+    assert!(!press(&mut state, KeyCode::Char('x')));
+  }
+}
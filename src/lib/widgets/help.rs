@@ -1,133 +1,512 @@
 use ratatui::{
   crossterm::event::{KeyCode, KeyEvent},
   layout::{Alignment, Constraint, Flex, Layout, Rect},
-  style::Stylize,
+  style::{Style, Stylize},
   text::Line,
   widgets::{
     Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget,
     Widget,
   },
 };
+use unicode_width::UnicodeWidthStr;
 
-/// The help menu displayed as a popup
-pub struct HelpMenu {
-  keys: Vec<[String; 2]>,
+use crate::{pad_to_width, truncate_to_width};
+
+use super::SearchState;
+
+/// The state of the [`HelpMenu`] popup, kept across frames.
+///
+/// The scroll and the filter are kept while the popup is displayed, and reset once closed
+#[derive(Default)]
+pub struct HelpState {
+  show: bool,
   scroll: usize,
+  scroll_state: ScrollbarState,
+  filter: Option<SearchState>,
+  editing: bool,
+  cursor_position: Option<(u16, u16)>,
+}
+
+impl HelpState {
+  /// Check if the popup is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the first displayed row
+  #[cfg(test)]
+  pub fn scroll(&self) -> usize {
+    self.scroll
+  }
+
+  /// Retrieve the text narrowing the rows, empty unless filtered with `/`
+  pub fn filter(&self) -> &str {
+    self
+      .filter
+      .as_ref()
+      .map(|filter| filter.query())
+      .unwrap_or_default()
+  }
+
+  /// Check if the filter is being typed
+  pub fn is_editing(&self) -> bool {
+    self.editing
+  }
+
+  /// Retrieve the terminal cursor position in the filter, computed during the last render
+  pub fn cursor_position(&self) -> Option<(u16, u16)> {
+    self.cursor_position.filter(|_| self.editing)
+  }
+
+  fn scroll_to(&mut self, scroll: usize) {
+    self.scroll = scroll;
+    self.scroll_state = self.scroll_state.position(scroll);
+  }
+
+  fn close(&mut self) {
+    self.show = false;
+    self.filter = None;
+    self.editing = false;
+    self.scroll_to(0);
+  }
+}
+
+/// A titled group of key bindings of the [`HelpMenu`]
+struct HelpSection {
+  title: String,
+  keys: Vec<[String; 2]>,
+}
+
+/// A row of the [`HelpMenu`]: a section title or a key binding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HelpRow<'a> {
+  Header(&'a str),
+  Key(&'a str, &'a str),
+}
+
+/// The help menu displayed as a popup: the key bindings grouped by sections,
+/// `/` narrowing them to the ones containing a text
+pub struct HelpMenu {
+  sections: Vec<HelpSection>,
 }
 
 impl HelpMenu {
+  /// Descriptions wider than this get truncated
+  pub const MAX_DESCRIPTION_WIDTH: usize = 60;
+
   /// Construct this object
   pub fn new() -> Self {
-    Self {
-      keys: vec![],
-      scroll: 0,
-    }
+    Self { sections: vec![] }
   }
 
-  pub fn with_scroll(mut self, v: usize) -> Self {
-    self.scroll = v;
-    self
+  /// The keybindings section to be displayed, without a title
+  #[cfg(test)]
+  pub fn with_keys<K: AsRef<str>, V: AsRef<str>>(self, keys: &[(K, V)]) -> Self {
+    self.with_section("", keys)
   }
-  /// The keybindings section to be displayed
-  pub fn with_keys<K: AsRef<str>, V: AsRef<str>>(mut self, keys: &[(K, V)]) -> Self {
-    self.keys.extend(
-      keys
+
+  /// A keybindings section displayed under its `title`
+  pub fn with_section<T: AsRef<str>, K: AsRef<str>, V: AsRef<str>>(
+    mut self,
+    title: T,
+    keys: &[(K, V)],
+  ) -> Self {
+    self.sections.push(HelpSection {
+      title: title.as_ref().to_string(),
+      keys: keys
         .iter()
         .map(|(k, v)| [k.as_ref().to_string(), v.as_ref().to_string()])
-        .collect::<Vec<_>>(),
-    );
+        .collect(),
+    });
     self
   }
 
-  pub fn handle_key(
-    key: KeyEvent,
-    show: &mut bool,
-    num_keys: usize,
-    scroll: &mut usize,
-    scroll_state: &mut ScrollbarState,
-  ) -> bool {
-    if key.code == KeyCode::Char('h') {
-      *show = !*show;
-      if *show {
-        *scroll = 0;
-        *scroll_state = scroll_state.position(*scroll);
+  /// The keybindings sections to be displayed, in order
+  pub fn with_sections<T: AsRef<str>, K: AsRef<str>, V: AsRef<str>>(
+    self,
+    sections: &[(T, &[(K, V)])],
+  ) -> Self {
+    sections
+      .iter()
+      .fold(self, |menu, (title, keys)| menu.with_section(title, keys))
+  }
+
+  /// Retrieve the displayed rows: the key bindings whose key or description contains
+  /// `filter` (ignoring the case), under the title of their section
+  pub fn rows(&self, filter: &str) -> Vec<HelpRow<'_>> {
+    let filter = filter.to_lowercase();
+    let mut rows = vec![];
+    for section in &self.sections {
+      let keys = section
+        .keys
+        .iter()
+        .filter(|[key, desc]| {
+          key.to_lowercase().contains(&filter) || desc.to_lowercase().contains(&filter)
+        })
+        .map(|[key, desc]| HelpRow::Key(key, desc))
+        .collect::<Vec<_>>();
+      if keys.is_empty() {
+        continue;
       }
-      return true;
+      if !section.title.is_empty() {
+        rows.push(HelpRow::Header(&section.title));
+      }
+      rows.extend(keys);
     }
-    if !*show {
+    rows
+  }
+
+  /// Toggle the popup with `h`, and consume every key while it is displayed.
+  ///
+  /// `/` types a filter, `Enter` keeps it and `Esc` clears it
+  pub fn handle_key(key: KeyEvent, state: &mut HelpState, menu: &HelpMenu) -> bool {
+    if !state.show {
+      if key.code == KeyCode::Char('h') {
+        state.show = true;
+        state.scroll_to(0);
+        return true;
+      }
       return false;
     }
-    if key.code == KeyCode::Esc || key.code == KeyCode::Char('q') {
-      *show = !*show;
-      if *show {
-        *scroll = 0;
-        *scroll_state = scroll_state.position(*scroll);
+    if state.editing {
+      match key.code {
+        KeyCode::Esc => {
+          state.filter = None;
+          state.editing = false;
+        }
+        KeyCode::Enter => state.editing = false,
+        _ => {
+          if let Some(filter) = state.filter.as_mut() {
+            filter.edit(key);
+          }
+        }
+      }
+      // the narrowed rows start from the top
+      state.scroll_to(0);
+      return true;
+    }
+    let num_rows = menu.rows(state.filter()).len();
+    match key.code {
+      KeyCode::Esc if state.filter.is_some() => {
+        state.filter = None;
+        state.scroll_to(0);
+      }
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('h') => state.close(),
+      KeyCode::Char('/') => {
+        state.editing = true;
+        if state.filter.is_none() {
+          state.filter = Some(SearchState::new("/"));
+        }
       }
-    } else if key.code == KeyCode::Down {
-      if *scroll < num_keys.saturating_sub(1) {
-        *scroll += 1;
-        *scroll_state = scroll_state.position(*scroll);
-        crate::dbg!("Scroll help: {}", *scroll);
+      KeyCode::Down if state.scroll < num_rows.saturating_sub(1) => {
+        state.scroll_to(state.scroll + 1);
+        crate::dbg!("Scroll help: {}", state.scroll);
       }
-    } else if key.code == KeyCode::Up {
-      *scroll = scroll.saturating_sub(1);
-      *scroll_state = scroll_state.position(*scroll);
-      crate::dbg!("Scroll help: {}", *scroll);
+      KeyCode::Up => {
+        state.scroll_to(state.scroll.saturating_sub(1));
+        crate::dbg!("Scroll help: {}", state.scroll);
+      }
+      _ => {}
     }
-    return true;
+    true
   }
 }
 
 impl StatefulWidget for HelpMenu {
-  type State = ScrollbarState;
+  type State = HelpState;
 
   fn render(
     self,
     area: ratatui::prelude::Rect,
     buf: &mut ratatui::prelude::Buffer,
-    state: &mut ScrollbarState,
+    state: &mut HelpState,
   ) where
     Self: Sized,
   {
     let area = popup_area(area, 40, 80);
     Clear::default().render(area, buf);
+    let block = Block::bordered()
+      .title("Help menu")
+      .title_alignment(Alignment::Center)
+      .on_black();
+    let inner = block.inner(area);
+    block.render(area, buf);
+    let [list_area, filter_area] = Layout::vertical([
+      Constraint::Fill(1),
+      Constraint::Length(state.filter.is_some() as u16),
+    ])
+    .areas(inner);
+    let inner_width = inner.width as usize;
+    // the columns stay aligned while filtering
     let mut help_col_widths = vec![0; 2];
-    let mut final_help = vec![];
-    for hl in &self.keys {
-      help_col_widths[0] = help_col_widths[0].max(hl[0].len());
-      help_col_widths[1] = help_col_widths[1].max(hl[1].len());
-    }
-    for hl in &self.keys {
-      let key = format!("{:width$}", hl[0], width = help_col_widths[0]);
-      let desc = format!("{:width$}", hl[1], width = help_col_widths[1]);
-      final_help.push(Line::default().spans([key, " ".into(), desc]));
+    for [key, desc] in self.sections.iter().flat_map(|section| &section.keys) {
+      help_col_widths[0] = help_col_widths[0].max(key.width());
+      help_col_widths[1] = help_col_widths[1].max(desc.width());
     }
+    help_col_widths[1] = help_col_widths[1]
+      .min(Self::MAX_DESCRIPTION_WIDTH)
+      .min(inner_width.saturating_sub(help_col_widths[0] + 1));
+    let rows = self.rows(state.filter());
+    let final_help = rows
+      .iter()
+      .map(|row| match row {
+        HelpRow::Header(title) => Line::from(title.to_string()).bold(),
+        HelpRow::Key(key, desc) => {
+          let key = pad_to_width(key, help_col_widths[0]);
+          let desc = pad_to_width(
+            truncate_to_width(desc, help_col_widths[1]),
+            help_col_widths[1],
+          );
+          Line::default().spans([key, " ".into(), desc])
+        }
+      })
+      .collect::<Vec<_>>();
 
-    *state = state.content_length(self.keys.len());
+    state.scroll_state = state.scroll_state.content_length(rows.len());
 
     Paragraph::new(final_help)
-      .block(
-        Block::bordered()
-          .title("Help menu")
-          .title_alignment(Alignment::Center),
-      )
       .centered()
-      .scroll((self.scroll as u16, 0))
-      .on_black()
-      .render(area, buf);
-    if self.keys.len() + 2 >= area.height as usize {
+      .scroll((state.scroll as u16, 0))
+      .render(list_area, buf);
+    if let Some(filter) = state.filter.as_mut() {
+      filter.fit(filter_area.width);
+      filter
+        .line(filter_area.width, Style::default().bold())
+        .render(filter_area, buf);
+      state.cursor_position = Some(filter.cursor_at(filter_area));
+    }
+    if rows.len() > list_area.height as usize {
       Scrollbar::new(ScrollbarOrientation::VerticalRight)
         .begin_symbol(Some("↑"))
         .end_symbol(Some("↓"))
-        .render(area, buf, state)
+        .render(area, buf, &mut state.scroll_state)
     }
   }
 }
 
-fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+pub(crate) fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
   let vertical = Layout::vertical([Constraint::Percentage(percent_y)]).flex(Flex::Center);
   let horizontal = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center);
   let [area] = vertical.areas(area);
   let [area] = horizontal.areas(area);
   area
 }
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    style::Modifier,
+    widgets::StatefulWidget,
+  };
+
+  use super::{popup_area, HelpMenu, HelpRow, HelpState};
+
+  const KEYS: &[(&str, &str)] = &[
+    ("↑", "go up"),
+    ("⏎", "validate"),
+    ("日本", "xyz"),
+    (
+      "ab",
+      "a very long description that does not fit in a small popup at all",
+    ),
+  ];
+
+  fn render(width: u16, height: u16) -> (Buffer, Rect) {
+    let area = Rect::new(0, 0, width, height);
+    let mut buf = Buffer::empty(area);
+    let mut state = HelpState::default();
+    HelpMenu::new()
+      .with_keys(KEYS)
+      .render(area, &mut buf, &mut state);
+    (buf, popup_area(area, 40, 80))
+  }
+
+  /// Find the column of the first cell of `text` in the row
+  fn find_column(buf: &Buffer, popup: Rect, text: &str) -> Option<u16> {
+    let first = text.chars().next().unwrap().to_string();
+    (popup.top()..popup.bottom()).find_map(|y| {
+      (popup.left()..popup.right()).find(|x| {
+        let rest = (*x..popup.right())
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>();
+        buf[(*x, y)].symbol() == first && rest.starts_with(text)
+      })
+    })
+  }
+
+  #[test]
+  fn wide_keys_alignment() {
+    for (width, height) in [(100, 20), (150, 30), (60, 12)] {
+      let (buf, popup) = render(width, height);
+      let columns = ["go up", "validate", "xyz"]
+        .iter()
+        .map(|desc| find_column(&buf, popup, desc))
+        .collect::<Vec<_>>();
+      assert!(
+        columns[0].is_some(),
+        "{}x{}: description not found",
+        width,
+        height
+      );
+      assert!(
+        columns.iter().all(|col| *col == columns[0]),
+        "{}x{}: misaligned descriptions {:?}",
+        width,
+        height,
+        columns
+      );
+    }
+  }
+
+  #[test]
+  fn truncate_long_descriptions() {
+    let (buf, popup) = render(60, 12);
+    assert!(find_column(&buf, popup, "a very").is_some());
+    assert!(find_column(&buf, popup, "at all").is_none());
+    assert!(find_column(&buf, popup, "…").is_some());
+  }
+
+  #[test]
+  fn scrollbar_visibility() {
+    // 4 keys fit in the 6 inner rows of a 8 rows popup
+    let (buf, popup) = render(100, 10);
+    assert_eq!(popup.height, 8);
+    assert_eq!(buf[(popup.right() - 1, popup.top())].symbol(), "┐");
+    // but not in the 2 inner rows of a 4 rows popup
+    let (buf, popup) = render(100, 5);
+    assert_eq!(popup.height, 4);
+    assert_eq!(buf[(popup.right() - 1, popup.top())].symbol(), "↑");
+  }
+
+  const SECTIONS: &[(&str, &[(&str, &str)])] = &[
+    ("Navigation", &[("j", "next row"), ("k", "previous row")]),
+    ("Search", &[("/", "enter search mode"), ("n", "next match")]),
+  ];
+
+  #[test]
+  fn handle_key() {
+    let mut state = HelpState::default();
+    let menu = HelpMenu::new().with_keys(KEYS);
+    let press =
+      |state: &mut HelpState, code| HelpMenu::handle_key(KeyEvent::from(code), state, &menu);
+    assert!(!press(&mut state, KeyCode::Char('q')));
+    assert!(!press(&mut state, KeyCode::Down));
+    assert!(press(&mut state, KeyCode::Char('h')));
+    assert!(state.is_shown());
+    // the keys are consumed while the popup is displayed
+    for _ in 0..KEYS.len() + 2 {
+      assert!(press(&mut state, KeyCode::Down));
+    }
+    assert_eq!(state.scroll(), KEYS.len() - 1);
+    assert!(press(&mut state, KeyCode::Up));
+    assert_eq!(state.scroll(), KEYS.len() - 2);
+    assert!(press(&mut state, KeyCode::Char('q')));
+    assert!(!state.is_shown());
+    // reopening starts from the top
+    assert!(press(&mut state, KeyCode::Char('h')));
+    assert_eq!(state.scroll(), 0);
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_shown());
+  }
+
+  #[test]
+  fn sections() {
+    let menu = HelpMenu::new().with_sections(SECTIONS);
+    assert_eq!(
+      menu.rows(""),
+      vec![
+        HelpRow::Header("Navigation"),
+        HelpRow::Key("j", "next row"),
+        HelpRow::Key("k", "previous row"),
+        HelpRow::Header("Search"),
+        HelpRow::Key("/", "enter search mode"),
+        HelpRow::Key("n", "next match"),
+      ]
+    );
+    let area = Rect::new(0, 0, 100, 20);
+    let mut buf = Buffer::empty(area);
+    menu.render(area, &mut buf, &mut HelpState::default());
+    let popup = popup_area(area, 40, 80);
+    let x = find_column(&buf, popup, "Search").unwrap();
+    let y = (popup.top()..popup.bottom())
+      .find(|y| buf[(x, *y)].symbol() == "S")
+      .unwrap();
+    assert!(buf[(x, y)].modifier.contains(Modifier::BOLD));
+    let x = find_column(&buf, popup, "next match").unwrap();
+    assert!(!buf[(x, y + 2)].modifier.contains(Modifier::BOLD));
+  }
+
+  #[test]
+  fn filter() {
+    let menu = HelpMenu::new().with_sections(SECTIONS);
+    // the keys and the descriptions, ignoring the case
+    assert_eq!(
+      menu.rows("ROW"),
+      vec![
+        HelpRow::Header("Navigation"),
+        HelpRow::Key("j", "next row"),
+        HelpRow::Key("k", "previous row"),
+      ]
+    );
+    assert_eq!(
+      menu.rows("n"),
+      vec![
+        HelpRow::Header("Navigation"),
+        HelpRow::Key("j", "next row"),
+        HelpRow::Header("Search"),
+        HelpRow::Key("/", "enter search mode"),
+        HelpRow::Key("n", "next match"),
+      ]
+    );
+    assert!(menu.rows("nothing").is_empty());
+
+    let mut state = HelpState::default();
+    let press =
+      |state: &mut HelpState, code| HelpMenu::handle_key(KeyEvent::from(code), state, &menu);
+    assert!(press(&mut state, KeyCode::Char('h')));
+    assert!(press(&mut state, KeyCode::Down));
+    assert!(press(&mut state, KeyCode::Down));
+    assert_eq!(state.scroll(), 2);
+    // typing the filter, 'h' and 'q' are part of it
+    assert!(press(&mut state, KeyCode::Char('/')));
+    assert!(state.is_editing());
+    for ch in "mqh".chars() {
+      assert!(press(&mut state, KeyCode::Char(ch)));
+    }
+    assert!(state.is_shown());
+    assert_eq!(state.filter(), "mqh");
+    assert_eq!(state.scroll(), 0);
+    assert!(press(&mut state, KeyCode::Backspace));
+    assert!(press(&mut state, KeyCode::Backspace));
+    assert!(press(&mut state, KeyCode::Enter));
+    assert!(!state.is_editing());
+    assert_eq!(menu.rows(state.filter()).len(), 3);
+
+    let area = Rect::new(0, 0, 100, 20);
+    let mut buf = Buffer::empty(area);
+    HelpMenu::new()
+      .with_sections(SECTIONS)
+      .render(area, &mut buf, &mut state);
+    let popup = popup_area(area, 40, 80);
+    assert!(find_column(&buf, popup, "next match").is_some());
+    assert!(find_column(&buf, popup, "next row").is_none());
+    assert!(find_column(&buf, popup, "/m").is_some());
+
+    // the scroll stops at the narrowed rows
+    for _ in 0..5 {
+      assert!(press(&mut state, KeyCode::Down));
+    }
+    assert_eq!(state.scroll(), 2);
+    // Esc clears the filter, then closes the popup
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(state.is_shown());
+    assert_eq!(state.filter(), "");
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_shown());
+    assert!(press(&mut state, KeyCode::Char('h')));
+    assert_eq!((state.scroll(), state.filter()), (0, ""));
+  }
+}
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{display_path, human_duration, History, HistoryRecord};
+
+use super::help::popup_area;
+
+/// The state of the [`HistoryList`]: the last runs, the oldest first
+#[derive(Debug, Clone, Default)]
+pub struct HistoryState {
+  show: bool,
+  records: Vec<HistoryRecord>,
+  current: Option<usize>,
+}
+
+impl HistoryState {
+  /// Construct the state from the runs of the previous sessions
+  pub fn new(records: Vec<HistoryRecord>) -> Self {
+    Self {
+      records,
+      ..Default::default()
+    }
+  }
+
+  /// Check if the list is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the listed runs, the oldest first
+  #[cfg(test)]
+  pub fn records(&self) -> &[HistoryRecord] {
+    &self.records
+  }
+
+  /// Retrieve the position of the run of this session
+  #[cfg(test)]
+  pub fn current(&self) -> Option<usize> {
+    self.current
+  }
+
+  /// Add the run that just finished, keeping the last [`History::SHOWN`] runs
+  pub fn push_current(&mut self, record: HistoryRecord) {
+    self.records.push(record);
+    self
+      .records
+      .drain(..self.records.len().saturating_sub(History::SHOWN));
+    self.current = Some(self.records.len() - 1);
+  }
+}
+
+/// The last runs with their counts, `H` shows/hides it
+pub struct HistoryList;
+
+impl HistoryList {
+  /// Handle `H` to show/hide the list, `Esc`/`q` also close it.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut HistoryState) -> bool {
+    if !state.show {
+      state.show = key.code == KeyCode::Char('H');
+      return state.show;
+    }
+    if matches!(
+      key.code,
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('H')
+    ) {
+      state.show = false;
+    }
+    true
+  }
+
+  /// The evolution of the warnings since the `previous` run
+  fn trend(record: &HistoryRecord, previous: Option<&HistoryRecord>) -> Span<'static> {
+    match previous.map(|previous| record.warnings.cmp(&previous.warnings)) {
+      Some(std::cmp::Ordering::Greater) => Span::from("↑").red(),
+      Some(std::cmp::Ordering::Less) => Span::from("↓").green(),
+      _ => Span::from(" "),
+    }
+  }
+
+  fn line<'a>(record: &'a HistoryRecord, previous: Option<&HistoryRecord>) -> Line<'a> {
+    Line::default().spans(vec![
+      Span::from(format!(
+        "{}  ",
+        DateTime::<Local>::from(record.time()).format("%Y-%m-%d %H:%M:%S")
+      ))
+      .dim(),
+      match record.success {
+        true => Span::from("✓ ").bold().green(),
+        false => Span::from("✗ ").bold().red(),
+      },
+      Span::from(format!(
+        "{:>8}  ",
+        human_duration(Duration::try_from_secs_f64(record.duration).unwrap_or_default())
+      )),
+      match record.errors {
+        0 => Span::from(format!("{:>3} error(s)  ", 0)).dim(),
+        n => Span::from(format!("{:>3} error(s)  ", n)).red(),
+      },
+      match record.warnings {
+        0 => Span::from(format!("{:>3} warning(s) ", 0)).dim(),
+        n => Span::from(format!("{:>3} warning(s) ", n)).yellow(),
+      },
+      Self::trend(record, previous),
+      Span::from(format!("  {}  ", record.command)).bold(),
+      Span::from(display_path(&record.workspace)).dim(),
+    ])
+  }
+}
+
+impl StatefulWidget for HistoryList {
+  type State = HistoryState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 80, 60);
+    Clear.render(area, buf);
+    let num_rows = area.height.saturating_sub(2) as usize;
+    // the most recent runs when they do not all fit
+    let first = state.records.len().saturating_sub(num_rows);
+    let lines = match state.records.is_empty() {
+      true => vec![Line::from("no run recorded yet").dark_gray()],
+      false => state
+        .records
+        .iter()
+        .enumerate()
+        .skip(first)
+        .map(|(i, record)| {
+          let line = Self::line(record, i.checked_sub(1).map(|i| &state.records[i]));
+          match Some(i) == state.current {
+            true => line.style(Style::default().on_light_blue()),
+            false => line,
+          }
+        })
+        .collect::<Vec<_>>(),
+    };
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title(format!(
+            "History (last {} runs, Esc: close)",
+            state.records.len()
+          ))
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{path::PathBuf, time::SystemTime};
+
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    style::Color,
+    widgets::StatefulWidget,
+  };
+
+  use crate::{History, HistoryRecord};
+
+  use super::{HistoryList, HistoryState};
+
+  fn record(errors: usize, warnings: usize) -> HistoryRecord {
+    HistoryRecord {
+      timestamp: SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs(),
+      command: "cargo build".to_string(),
+      duration: 2.5,
+      success: errors == 0,
+      exit: "exit status: 0".to_string(),
+      errors,
+      warnings,
+      workspace: PathBuf::from("/tmp/demo"),
+    }
+  }
+
+  fn rows(state: &mut HistoryState, height: u16) -> Vec<(String, Option<Color>)> {
+    let area = Rect::new(0, 0, 120, height);
+    let mut buf = Buffer::empty(area);
+    HistoryList.render(area, &mut buf, state);
+    (0..height)
+      .map(|y| {
+        let text = (0..area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>();
+        // the background of the first column inside the borders
+        let x = (0..area.width).find(|x| buf[(*x, y)].symbol() == "│");
+        let bg = x.map(|x| buf[(x + 1, y)].bg);
+        (text, bg)
+      })
+      .filter(|(text, _)| text.contains("error(s)"))
+      .collect()
+  }
+
+  #[test]
+  fn handle_key() {
+    let mut state = HistoryState::default();
+    assert!(!HistoryList::handle_key(
+      KeyEvent::from(KeyCode::Char('q')),
+      &mut state
+    ));
+    assert!(HistoryList::handle_key(
+      KeyEvent::from(KeyCode::Char('H')),
+      &mut state
+    ));
+    assert!(state.is_shown());
+    // the other keys are swallowed while displayed
+    assert!(HistoryList::handle_key(
+      KeyEvent::from(KeyCode::Char('r')),
+      &mut state
+    ));
+    assert!(state.is_shown());
+    assert!(HistoryList::handle_key(
+      KeyEvent::from(KeyCode::Esc),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+  }
+
+  #[test]
+  fn push_current() {
+    let mut state = HistoryState::new((0..History::SHOWN).map(|i| record(0, i)).collect());
+    assert_eq!(state.current(), None);
+    state.push_current(record(1, 99));
+    assert_eq!(state.records().len(), History::SHOWN);
+    assert_eq!(state.records()[0].warnings, 1);
+    assert_eq!(state.current(), Some(History::SHOWN - 1));
+  }
+
+  #[test]
+  fn render() {
+    let mut state = HistoryState::new(vec![record(0, 2), record(0, 5), record(0, 5)]);
+    state.push_current(record(3, 1));
+    let rows = rows(&mut state, 40);
+    assert_eq!(rows.len(), 4);
+    for (row, expected) in rows.iter().zip([
+      "✓     2.5s    0 error(s)    2 warning(s)    cargo build  /tmp/demo",
+      "✓     2.5s    0 error(s)    5 warning(s) ↑  cargo build  /tmp/demo",
+      "✓     2.5s    0 error(s)    5 warning(s)    cargo build  /tmp/demo",
+      "✗     2.5s    3 error(s)    1 warning(s) ↓  cargo build  /tmp/demo",
+    ]) {
+      assert!(row.0.contains(expected), "{:?}", row.0);
+    }
+    // the current run is highlighted
+    assert_eq!(
+      rows.iter().map(|(_, bg)| *bg).collect::<Vec<_>>(),
+      vec![
+        Some(Color::Black),
+        Some(Color::Black),
+        Some(Color::Black),
+        Some(Color::LightBlue)
+      ]
+    );
+
+    // the most recent runs in a small popup
+    let rows = self::rows(&mut state, 6);
+    assert!(rows.len() < 4);
+    assert!(rows.last().unwrap().0.contains("3 error(s)"));
+
+    let mut state = HistoryState::default();
+    let area = Rect::new(0, 0, 120, 20);
+    let mut buf = Buffer::empty(area);
+    HistoryList.render(area, &mut buf, &mut state);
+    let text = (0..area.height)
+      .flat_map(|y| (0..area.width).map(move |x| (x, y)))
+      .map(|(x, y)| buf[(x, y)].symbol().to_string())
+      .collect::<String>();
+    assert!(text.contains("no run recorded yet"));
+  }
+}
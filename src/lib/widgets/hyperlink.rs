@@ -0,0 +1,318 @@
+use std::{
+  env,
+  io::{self, Write},
+  path::Path,
+};
+
+use ratatui::{
+  buffer::Buffer,
+  crossterm::{
+    cursor::{MoveTo, RestorePosition, SavePosition},
+    queue,
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+  },
+  layout::Rect,
+  style::Modifier,
+  text::Line,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{BuildOutput, Location};
+
+/// The terminals known to support OSC 8 hyperlinks, matched against `TERM_PROGRAM`
+pub const HYPERLINK_TERMINALS: &[&str] = &["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"];
+
+/// Wrap `text` in the OSC 8 escape sequences opening `url`
+pub fn osc8<U: AsRef<str>, T: AsRef<str>>(url: U, text: T) -> String {
+  format!(
+    "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+    url.as_ref(),
+    text.as_ref()
+  )
+}
+
+/// Wrap `text` in the OSC 52 escape sequence copying it to the clipboard
+pub fn osc52<T: AsRef<str>>(text: T) -> String {
+  format!("\x1b]52;c;{}\x07", base64(text.as_ref().as_bytes()))
+}
+
+/// Encode `bytes` in standard, padded base64
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut ret = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      match i <= chunk.len() {
+        true => ret.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char),
+        false => ret.push('='),
+      }
+    }
+  }
+  ret
+}
+
+/// Compute the width of `text` in terminal cells, ignoring the OSC escape sequences
+#[cfg(test)]
+pub fn visible_width<T: AsRef<str>>(text: T) -> usize {
+  let mut visible = String::new();
+  let mut chars = text.as_ref().chars().peekable();
+  while let Some(ch) = chars.next() {
+    if ch == '\x1b' && chars.peek() == Some(&']') {
+      // skip up to the string terminator: BEL or ESC \
+      while let Some(ch) = chars.next() {
+        if ch == '\x07' || (ch == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+          break;
+        }
+      }
+      continue;
+    }
+    visible.push(ch);
+  }
+  visible.width()
+}
+
+/// A location displayed in the log, at column `x` and row `y` of the terminal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hyperlink {
+  pub x: u16,
+  pub y: u16,
+  /// The number of cells covered by the location
+  pub width: u16,
+  pub location: Location,
+}
+
+impl Hyperlink {
+  /// Find the location of a `-->` line displayed at row `y`, clipped to `area`
+  pub fn find(line: &Line<'_>, y: u16, area: Rect) -> Option<Self> {
+    let text = line
+      .spans
+      .iter()
+      .map(|span| span.content.as_ref())
+      .collect::<String>();
+    let location = BuildOutput::extract_location(&text).ok()??;
+    let start = text.find("-->")? + 3;
+    let start = start + text[start..].len() - text[start..].trim_start().len();
+    let x = area.x as usize + text[..start].width();
+    let width = text[start..].trim_end().width();
+    if x >= area.right() as usize || width == 0 {
+      return None;
+    }
+    Some(Self {
+      x: x as u16,
+      y,
+      width: width.min(area.right() as usize - x) as u16,
+      location,
+    })
+  }
+}
+
+/// Turn the locations of the log into clickable OSC 8 hyperlinks.
+///
+/// Ratatui does not know about OSC 8 and computes the cell widths from the symbols,
+/// so the escapes never go through the [`Buffer`]: once a frame is drawn,
+/// [`Self::write`] prints the cells of each link again, wrapped in the escapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hyperlinks {
+  enabled: bool,
+  format: String,
+}
+
+impl Default for Hyperlinks {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      format: Self::DEFAULT_FORMAT.to_string(),
+    }
+  }
+}
+
+impl Hyperlinks {
+  /// Open the file, see [`Self::with_format`] for the placeholders
+  pub const DEFAULT_FORMAT: &'static str = "file://{path}";
+
+  /// Construct the hyperlinks, printing nothing unless enabled
+  pub fn new(enabled: bool) -> Self {
+    Self {
+      enabled,
+      ..Default::default()
+    }
+  }
+
+  /// Enable the hyperlinks if `forced` or if the terminal advertises their support
+  pub fn detect(forced: bool) -> Self {
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let vte_version = env::var("VTE_VERSION")
+      .ok()
+      .and_then(|v| v.parse::<usize>().ok())
+      .unwrap_or_default();
+    Self::new(
+      forced
+        || HYPERLINK_TERMINALS.contains(&term_program.as_str())
+        || vte_version >= 5000
+        || env::var("KITTY_WINDOW_ID").is_ok()
+        || env::var("WT_SESSION").is_ok(),
+    )
+  }
+
+  /// Define the URL format, `{path}` is replaced by the absolute path,
+  /// `{line}` and `{column}` by the position (`1` when unknown).
+  /// e.g. `vscode://file/{path}:{line}:{column}`
+  pub fn with_format<F: AsRef<str>>(mut self, format: F) -> Self {
+    self.format = format.as_ref().to_string();
+    self
+  }
+
+  /// Build the URL of a location
+  pub fn url(&self, location: &Location) -> String {
+    let path = location.canonical_path();
+    self
+      .format
+      .replace("{path}", &Self::encode_path(&path))
+      .replace("{line}", &location.line().unwrap_or(1).to_string())
+      .replace("{column}", &location.column().unwrap_or(1).to_string())
+  }
+
+  fn encode_path(path: &Path) -> String {
+    path
+      .display()
+      .to_string()
+      .replace('%', "%25")
+      .replace(' ', "%20")
+  }
+
+  /// Print the cells of `buf` covered by the links again, wrapped in the escapes.
+  ///
+  /// Does nothing if the hyperlinks are disabled
+  pub fn write<W: Write>(&self, out: &mut W, buf: &Buffer, links: &[Hyperlink]) -> io::Result<()> {
+    if !self.enabled || links.is_empty() {
+      return Ok(());
+    }
+    queue!(out, SavePosition)?;
+    for link in links {
+      queue!(out, MoveTo(link.x, link.y))?;
+      let mut text = String::new();
+      let mut x = link.x;
+      let mut styled = false;
+      while x < link.x + link.width {
+        let Some(cell) = buf.cell((x, link.y)) else {
+          break;
+        };
+        if !styled {
+          // the cells of a location share the same style
+          queue!(
+            out,
+            SetAttribute(Attribute::Reset),
+            SetForegroundColor(cell.fg.into()),
+            SetBackgroundColor(cell.bg.into())
+          )?;
+          for (modifier, attribute) in [
+            (Modifier::BOLD, Attribute::Bold),
+            (Modifier::DIM, Attribute::Dim),
+            (Modifier::ITALIC, Attribute::Italic),
+            (Modifier::UNDERLINED, Attribute::Underlined),
+            (Modifier::REVERSED, Attribute::Reverse),
+          ] {
+            if cell.modifier.contains(modifier) {
+              queue!(out, SetAttribute(attribute))?;
+            }
+          }
+          styled = true;
+        }
+        text.push_str(cell.symbol());
+        x += cell.symbol().width().max(1) as u16;
+      }
+      queue!(
+        out,
+        Print(osc8(self.url(&link.location), text)),
+        SetAttribute(Attribute::Reset)
+      )?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{buffer::Buffer, layout::Rect, style::Style, text::Line};
+
+  use crate::Location;
+
+  use super::{base64, osc52, osc8, visible_width, Hyperlink, Hyperlinks};
+
+  #[test]
+  fn escapes() {
+    let link = osc8("file:///tmp/a.rs", "a.rs:1:2");
+    assert_eq!(link, "\x1b]8;;file:///tmp/a.rs\x1b\\a.rs:1:2\x1b]8;;\x1b\\");
+    assert_eq!(visible_width(&link), 8);
+    assert_eq!(visible_width("\x1b]8;;file:///x\x07été\x1b]8;;\x07"), 3);
+    assert_eq!(visible_width(osc8("file:///x", "ファイル")), 8);
+    assert_eq!(osc52("/tmp/a"), "\x1b]52;c;L3RtcC9h\x07");
+    assert_eq!(visible_width(osc52("/tmp/a")), 0);
+  }
+
+  #[test]
+  fn encode_base64() {
+    for (text, expected) in [
+      ("", ""),
+      ("f", "Zg=="),
+      ("fo", "Zm8="),
+      ("foo", "Zm9v"),
+      ("foob", "Zm9vYg=="),
+    ] {
+      assert_eq!(base64(text.as_bytes()), expected);
+    }
+  }
+
+  #[test]
+  fn find() {
+    let area = Rect::new(1, 1, 20, 5);
+    let line = Line::from(vec!["  ".into(), "--> src/lib/build.rs:450:7".into()]);
+    let link = Hyperlink::find(&line, 2, area).unwrap();
+    assert_eq!((link.x, link.y, link.width), (7, 2, 14));
+    assert_eq!(
+      link.location,
+      Location::new("src/lib/build.rs", Some(450), Some(7))
+    );
+    assert!(Hyperlink::find(&Line::raw("error: boom"), 2, area).is_none());
+    assert!(Hyperlink::find(&line, 2, Rect::new(0, 0, 6, 5)).is_none());
+  }
+
+  #[test]
+  fn url() {
+    let location = Location::new("/tmp/my file.rs", Some(3), None);
+    assert_eq!(
+      Hyperlinks::new(true).url(&location),
+      "file:///tmp/my%20file.rs"
+    );
+    assert_eq!(
+      Hyperlinks::new(true)
+        .with_format("vscode://file/{path}:{line}:{column}")
+        .url(&location),
+      "vscode://file//tmp/my%20file.rs:3:1"
+    );
+  }
+
+  #[test]
+  fn write() {
+    let mut buf = Buffer::empty(Rect::new(0, 0, 12, 1));
+    buf.set_string(0, 0, "--> a.rs:1:2", Style::default());
+    let links = vec![Hyperlink::find(&Line::raw("--> a.rs:1:2"), 0, buf.area).unwrap()];
+    let mut out = vec![];
+    Hyperlinks::new(false)
+      .write(&mut out, &buf, &links)
+      .unwrap();
+    assert!(out.is_empty());
+    Hyperlinks::new(true)
+      .with_format("file:///{path}")
+      .write(&mut out, &buf, &links)
+      .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("\x1b]8;;file:///"));
+    assert!(out.contains("a.rs\x1b\\a.rs:1:2\x1b]8;;\x1b\\"));
+  }
+}
@@ -0,0 +1,310 @@
+use std::path::Path;
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::Stylize,
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{Rule, SessionInfo};
+
+use super::help::popup_area;
+
+/// The state of the [`InfoPopup`]: displayed or not, and its scroll position
+#[derive(Debug, Clone, Default)]
+pub struct InfoState {
+  show: bool,
+  scroll: u16,
+}
+
+impl InfoState {
+  /// Check if the popup is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the first displayed row
+  #[cfg(test)]
+  pub fn scroll(&self) -> u16 {
+    self.scroll
+  }
+}
+
+/// What the session runs with: the active rule and its markers, the config files,
+/// the debug log and the run directory
+pub struct InfoPopup<'a> {
+  rule: &'a Rule,
+  session: &'a SessionInfo,
+}
+
+impl<'a> InfoPopup<'a> {
+  /// The width of the labels column
+  const LABEL_WIDTH: usize = 15;
+
+  /// Construct this object from the active rule and the session
+  pub fn new(rule: &'a Rule, session: &'a SessionInfo) -> Self {
+    Self { rule, session }
+  }
+
+  /// Handle `F1` to show/hide the popup. Once displayed, `Up`/`Down` scroll and
+  /// `Esc`/`q` closes.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut InfoState) -> bool {
+    if !state.show {
+      if key.code != KeyCode::F(1) {
+        return false;
+      }
+      state.show = true;
+      state.scroll = 0;
+      return true;
+    }
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::F(1) => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.scroll = state.scroll.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => state.scroll = state.scroll.saturating_add(1),
+      _ => {}
+    }
+    true
+  }
+
+  /// The first row of a section: its label, then `spans`
+  fn row<'b>(label: &str, spans: Vec<Span<'b>>) -> Line<'b> {
+    let mut row = vec![Span::from(format!("{:<width$}", label, width = Self::LABEL_WIDTH)).bold()];
+    row.extend(spans);
+    Line::default().spans(row)
+  }
+
+  /// The next rows of a section, aligned on the first one
+  fn continued(spans: Vec<Span<'_>>) -> Line<'_> {
+    Self::row("", spans)
+  }
+
+  /// A path, or `missing` dimmed
+  fn path(path: Option<&Path>, missing: &str) -> Span<'static> {
+    match path {
+      Some(path) => Span::from(path.display().to_string()),
+      None => Span::from(missing.to_string()).dark_gray(),
+    }
+  }
+
+  /// Format the content of the popup
+  pub fn lines(&self) -> Vec<Line<'a>> {
+    let mut lines = vec![];
+    let name = self.rule.aliases.first().cloned().unwrap_or_default();
+    let mut spans = vec![Span::from(name).bold()];
+    if self.rule.aliases.len() > 1 {
+      spans
+        .push(Span::from(format!(" (aliases: {})", self.rule.aliases[1..].join(", "))).dark_gray());
+    }
+    lines.push(Self::row("Rule", spans));
+    lines.push(Self::row(
+      "Command",
+      vec![Span::from(self.rule.command.clone())],
+    ));
+    let kind_width = self
+      .rule
+      .markers
+      .iter()
+      .map(|marker| marker.tag.to_string().len())
+      .max()
+      .unwrap_or_default();
+    for (i, marker) in self.rule.markers.iter().enumerate() {
+      let mut spans = vec![
+        Span::styled("■■", marker.style),
+        Span::from(" "),
+        Span::styled(
+          format!("{:<width$}", marker.tag.to_string(), width = kind_width),
+          marker.style,
+        ),
+        Span::from(format!(" {}", marker.regex.as_str())),
+      ];
+      if marker.disabled {
+        spans.push(Span::from(" (disabled)").dark_gray());
+      }
+      lines.push(match i {
+        0 => Self::row("Markers", spans),
+        _ => Self::continued(spans),
+      });
+    }
+    if self.rule.markers.is_empty() {
+      lines.push(Self::row("Markers", vec![Span::from("none").dark_gray()]));
+    }
+    lines.push(Line::default());
+    lines.push(Self::row(
+      "Config files",
+      vec![Span::from(" 1. built-in rules").dark_gray()],
+    ));
+    for (i, path) in self.session.config_files.iter().enumerate() {
+      lines.push(Self::continued(vec![
+        Span::from(format!("{:>2}. ", i + 2)).dark_gray(),
+        Span::from(path.display().to_string()),
+      ]));
+    }
+    lines.push(Self::row(
+      "Debug log",
+      vec![Self::path(
+        self.session.debug_log.as_deref(),
+        "kept in memory",
+      )],
+    ));
+    lines.push(Self::row(
+      "Run directory",
+      vec![Self::path(self.session.run_dir.as_deref(), "none")],
+    ));
+    lines
+  }
+}
+
+impl<'a> StatefulWidget for InfoPopup<'a> {
+  type State = InfoState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 80, 60);
+    Clear.render(area, buf);
+    let lines = self.lines();
+    // no blank page past the last row
+    let max_scroll = (lines.len() as u16).saturating_sub(area.height.saturating_sub(2));
+    state.scroll = state.scroll.min(max_scroll);
+    Paragraph::new(lines)
+      .scroll((state.scroll, 0))
+      .block(
+        Block::bordered()
+          .title("Session (Up/Down: scroll, Esc: close)")
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    style::Color,
+    widgets::StatefulWidget,
+  };
+
+  use crate::{SessionInfo, DEFAULT_RULES};
+
+  use super::{InfoPopup, InfoState};
+
+  fn session() -> SessionInfo {
+    SessionInfo::default()
+      .with_config_file("/home/me/.config/cargo-nbuild/nbuild.json")
+      .with_debug_log("/home/me/.cache/cargo-nbuild/runs/20240101-120000-42/debug.log")
+      .with_run_dir("/home/me/.cache/cargo-nbuild/runs/20240101-120000-42")
+  }
+
+  fn render(session: &SessionInfo, state: &mut InfoState) -> (Vec<String>, Buffer) {
+    let area = Rect::new(0, 0, 100, 25);
+    let mut buf = Buffer::empty(area);
+    InfoPopup::new(&DEFAULT_RULES[0], session).render(area, &mut buf, state);
+    let rows = (0..area.height)
+      .map(|y| {
+        (0..area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>()
+          .trim_end()
+          .to_string()
+      })
+      .collect();
+    (rows, buf)
+  }
+
+  fn find(rows: &[String], text: &str) -> Option<(u16, u16)> {
+    rows.iter().enumerate().find_map(|(y, row)| {
+      row
+        .find(text)
+        .map(|i| (row[..i].chars().count() as u16, y as u16))
+    })
+  }
+
+  #[test]
+  fn handle_key() {
+    let mut state = InfoState::default();
+    assert!(!InfoPopup::handle_key(
+      KeyEvent::from(KeyCode::Char('q')),
+      &mut state
+    ));
+    assert!(InfoPopup::handle_key(
+      KeyEvent::from(KeyCode::F(1)),
+      &mut state
+    ));
+    assert!(state.is_shown());
+    // the other keys are swallowed while displayed
+    assert!(InfoPopup::handle_key(
+      KeyEvent::from(KeyCode::Down),
+      &mut state
+    ));
+    assert_eq!(state.scroll(), 1);
+    assert!(InfoPopup::handle_key(
+      KeyEvent::from(KeyCode::Char('e')),
+      &mut state
+    ));
+    assert!(InfoPopup::handle_key(
+      KeyEvent::from(KeyCode::Esc),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+  }
+
+  #[test]
+  fn content() {
+    let session = session();
+    let (rows, buf) = render(&session, &mut InfoState::default());
+    let rule = find(&rows, "Rule           rust: cargo (aliases: cargo, rust)");
+    assert!(rule.is_some(), "{:#?}", rows);
+    assert!(find(&rows, "Command        cargo build").is_some());
+    // the markers in the rule order, with their swatch
+    let error = find(&rows, "Markers        ■■ Error   error(").expect("no error marker");
+    let note = find(&rows, "■■ Note    note(").expect("no note marker");
+    let warning = find(&rows, "■■ Warning warning(").expect("no warning marker");
+    assert_eq!((error.1 + 1, error.1 + 2), (note.1, warning.1));
+    assert_eq!(buf[(error.0 + 15, error.1)].fg, Color::Red);
+    assert_eq!(buf[note].fg, Color::Blue);
+    assert_eq!(buf[warning].fg, Color::Yellow);
+    // the built-in rules take precedence over the files
+    let builtin = find(&rows, "Config files    1. built-in rules").expect("no built-in rules");
+    let file =
+      find(&rows, " 2. /home/me/.config/cargo-nbuild/nbuild.json").expect("no config file");
+    assert_eq!(builtin.1 + 1, file.1);
+    assert!(find(
+      &rows,
+      "Debug log      /home/me/.cache/cargo-nbuild/runs/20240101-120000-42/debug.log"
+    )
+    .is_some());
+    assert!(find(
+      &rows,
+      "Run directory  /home/me/.cache/cargo-nbuild/runs/20240101-120000-42"
+    )
+    .is_some());
+  }
+
+  #[test]
+  fn missing_paths() {
+    let (rows, _) = render(&SessionInfo::default(), &mut InfoState::default());
+    assert!(find(&rows, "Config files    1. built-in rules").is_some());
+    assert!(find(&rows, " 2. ").is_none());
+    assert!(find(&rows, "Debug log      kept in memory").is_some());
+    assert!(find(&rows, "Run directory  none").is_some());
+    // scrolled past the end, the last row stays at the bottom
+    let mut state = InfoState::default();
+    for _ in 0..100 {
+      InfoPopup::handle_key(KeyEvent::from(KeyCode::Down), &mut state);
+    }
+    let (rows, _) = render(&SessionInfo::default(), &mut state);
+    assert!(state.scroll() < 100);
+    assert!(find(&rows, "Run directory  none").is_some());
+  }
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  style::{Style, Stylize},
+  widgets::{StatefulWidget, Widget},
+};
+
+use super::SearchState;
+
+/// The state of the [`InputPrompt`]: the line typed for the build's stdin
+#[derive(Default)]
+pub struct InputPromptState {
+  enabled: bool,
+  input: Option<SearchState>,
+  line: Option<String>,
+}
+
+impl InputPromptState {
+  /// The prompt displayed before the typed line
+  pub const PROMPT: &'static str = "stdin> ";
+
+  /// Construct the state, the prompt only opens if `enabled` (see `--interactive`)
+  pub fn new(enabled: bool) -> Self {
+    Self {
+      enabled,
+      ..Default::default()
+    }
+  }
+
+  /// Check if the prompt is displayed
+  pub fn is_shown(&self) -> bool {
+    self.input.is_some()
+  }
+
+  /// Display the prompt, unless disabled or already displayed
+  pub fn open(&mut self) {
+    if self.enabled && self.input.is_none() {
+      self.input = Some(SearchState::new(Self::PROMPT));
+    }
+  }
+
+  /// Retrieve the edited line
+  pub fn input(&self) -> Option<&SearchState> {
+    self.input.as_ref()
+  }
+
+  /// Take the line submitted with `Enter`, without its line feed
+  pub fn take_line(&mut self) -> Option<String> {
+    self.line.take()
+  }
+}
+
+/// The input line sending text to the stdin of the build: `I` opens it,
+/// `Enter` submits the line and `Esc` closes it
+pub struct InputPrompt;
+
+impl InputPrompt {
+  /// Time without output after which a line ending like a question opens the prompt,
+  /// see [`InputPrompt::is_waiting`]
+  pub const IDLE_DELAY: Duration = Duration::from_secs(2);
+
+  /// Handle the keys of the displayed prompt, and `I` opening it.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut InputPromptState) -> bool {
+    let Some(input) = state.input.as_mut() else {
+      if key.code == KeyCode::Char('I') && state.enabled {
+        state.open();
+        return true;
+      }
+      return false;
+    };
+    match key.code {
+      KeyCode::Esc => state.input = None,
+      KeyCode::Enter => {
+        state.line = Some(input.query().to_string());
+        state.input = None;
+      }
+      _ => {
+        input.edit(key);
+      }
+    }
+    true
+  }
+
+  /// Guess if the build waits for an answer: its `last_line` ends with `:` or `?`
+  /// and nothing was printed for [`InputPrompt::IDLE_DELAY`]
+  pub fn is_waiting(last_line: &str, idle: Duration) -> bool {
+    idle >= Self::IDLE_DELAY && last_line.trim_end().ends_with([':', '?'])
+  }
+}
+
+impl StatefulWidget for InputPrompt {
+  type State = InputPromptState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    if let Some(input) = state.input.as_mut() {
+      input.fit(area.width);
+      input
+        .line(area.width, Style::default().bold().yellow())
+        .render(area, buf);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+  use super::{InputPrompt, InputPromptState};
+
+  fn type_keys(state: &mut InputPromptState, keys: &str) {
+    for ch in keys.chars() {
+      assert!(InputPrompt::handle_key(
+        KeyEvent::from(KeyCode::Char(ch)),
+        state
+      ));
+    }
+  }
+
+  #[test]
+  fn handle_key() {
+    // only with --interactive
+    let mut state = InputPromptState::default();
+    assert!(!InputPrompt::handle_key(
+      KeyEvent::from(KeyCode::Char('I')),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+
+    let mut state = InputPromptState::new(true);
+    type_keys(&mut state, "I");
+    assert!(state.is_shown());
+    assert_eq!(state.input().unwrap().prompt(), InputPromptState::PROMPT);
+    type_keys(&mut state, "yes");
+    assert!(InputPrompt::handle_key(
+      KeyEvent::from(KeyCode::Enter),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+    assert_eq!(state.take_line().as_deref(), Some("yes"));
+    assert_eq!(state.take_line(), None);
+
+    // Esc drops the line
+    state.open();
+    type_keys(&mut state, "no");
+    assert!(InputPrompt::handle_key(
+      KeyEvent::from(KeyCode::Esc),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+    assert_eq!(state.take_line(), None);
+  }
+
+  #[test]
+  fn is_waiting() {
+    let idle = InputPrompt::IDLE_DELAY;
+    assert!(InputPrompt::is_waiting(
+      "Overwrite the registry token? ",
+      idle
+    ));
+    assert!(InputPrompt::is_waiting("password:", idle * 2));
+    assert!(!InputPrompt::is_waiting(
+      "password:",
+      Duration::from_millis(500)
+    ));
+    assert!(!InputPrompt::is_waiting("   Compiling demo v0.1.0", idle));
+  }
+}
@@ -0,0 +1,310 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{display_path, BuildTagKind, JumpTarget};
+
+use super::help::popup_area;
+
+/// The state of the [`JumpDialog`]: the listed kind and the typed number
+#[derive(Debug, Clone)]
+pub struct JumpState {
+  show: bool,
+  /// `g` was pressed, waiting for the kind
+  pending: bool,
+  kind: BuildTagKind,
+  input: String,
+  error: Option<String>,
+  /// The position of the target to jump to, taken by the renderer
+  jump: Option<usize>,
+}
+
+impl Default for JumpState {
+  fn default() -> Self {
+    Self {
+      show: false,
+      pending: false,
+      kind: BuildTagKind::Error,
+      input: String::new(),
+      error: None,
+      jump: None,
+    }
+  }
+}
+
+impl JumpState {
+  /// The longest number that can be typed
+  pub const MAX_INPUT_LEN: usize = 6;
+
+  /// Check if the dialog is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the kind of the listed blocks
+  pub fn kind(&self) -> BuildTagKind {
+    self.kind
+  }
+
+  /// Retrieve the number typed so far
+  #[cfg(test)]
+  pub fn input(&self) -> &str {
+    &self.input
+  }
+
+  /// Retrieve the error displayed under the input
+  #[cfg(test)]
+  pub fn error(&self) -> Option<&str> {
+    self.error.as_deref()
+  }
+
+  /// Take the position (0-based) of the target chosen by the user
+  pub fn take_jump(&mut self) -> Option<usize> {
+    self.jump.take()
+  }
+
+  fn open(&mut self, kind: BuildTagKind) {
+    self.show = true;
+    self.kind = kind;
+    self.input.clear();
+    self.error = None;
+  }
+
+  /// Validate the typed number against the `num_targets` listed blocks
+  fn submit(&mut self, num_targets: usize) {
+    let kind = self.kind.to_string().to_lowercase();
+    if num_targets == 0 {
+      self.error = Some(format!("no {} to jump to", kind));
+      return;
+    }
+    match self.input.parse::<usize>() {
+      Ok(n) if (1..=num_targets).contains(&n) => {
+        self.jump = Some(n - 1);
+        self.show = false;
+      }
+      _ => {
+        self.error = Some(format!("type a number between 1 and {}", num_targets));
+      }
+    }
+  }
+}
+
+/// The numbered list of the blocks of a kind, typing a number then `Enter` jumps to one
+pub struct JumpDialog {
+  targets: Vec<JumpTarget>,
+  style: Style,
+}
+
+impl JumpDialog {
+  /// Construct this object from the listed blocks
+  pub fn new(targets: Vec<JumpTarget>) -> Self {
+    Self {
+      targets,
+      style: Style::default(),
+    }
+  }
+
+  /// The style of the numbers, usually the one of the listed marker kind
+  pub fn with_style(mut self, style: Style) -> Self {
+    self.style = style;
+    self
+  }
+
+  /// Handle `g` followed by `e`/`w`/`n` to list the errors, warnings or notes, and `:` to list
+  /// the errors. Once displayed, digits type the number, `Enter` jumps and `Esc`/`q` closes.
+  ///
+  /// `num_targets` counts the blocks of a kind. Returns true if the key was handled
+  pub fn handle_key<F: Fn(BuildTagKind) -> usize>(
+    key: KeyEvent,
+    state: &mut JumpState,
+    num_targets: F,
+  ) -> bool {
+    if state.pending {
+      state.pending = false;
+      match key.code {
+        KeyCode::Char('e') => state.open(BuildTagKind::Error),
+        KeyCode::Char('w') => state.open(BuildTagKind::Warning),
+        KeyCode::Char('n') => state.open(BuildTagKind::Note),
+        _ => return false,
+      }
+      return true;
+    }
+    if !state.show {
+      match key.code {
+        KeyCode::Char('g') => state.pending = true,
+        KeyCode::Char(':') => state.open(BuildTagKind::Error),
+        _ => return false,
+      }
+      return true;
+    }
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') => state.show = false,
+      KeyCode::Char(ch @ '0'..='9') => {
+        if state.input.len() < JumpState::MAX_INPUT_LEN {
+          state.input.push(ch);
+        }
+        state.error = None;
+      }
+      KeyCode::Backspace => {
+        state.input.pop();
+        state.error = None;
+      }
+      KeyCode::Enter => state.submit(num_targets(state.kind)),
+      _ => {}
+    }
+    true
+  }
+}
+
+impl StatefulWidget for JumpDialog {
+  type State = JumpState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 60, 50);
+    Clear.render(area, buf);
+    // the borders and the input rows
+    let num_rows = area.height.saturating_sub(4) as usize;
+    let number_width = self.targets.len().to_string().len();
+    // keep the typed number in sight
+    let typed = state.input.parse::<usize>().unwrap_or_default();
+    let first = typed.min(self.targets.len()).saturating_sub(num_rows);
+    let mut lines = self
+      .targets
+      .iter()
+      .enumerate()
+      .skip(first)
+      .take(num_rows)
+      .map(|(i, (_marker_id, _kind, first_line, location))| {
+        let mut spans = vec![
+          Span::styled(
+            format!("{:>width$} ", i + 1, width = number_width),
+            self.style,
+          ),
+          Span::from(first_line.clone()),
+        ];
+        if let Some(location) = location {
+          spans.push(Span::from(format!(" {}", display_path(location.path()))).dark_gray());
+        }
+        let mut line = Line::default().spans(spans);
+        if i + 1 == typed {
+          line = line.style(Style::default().on_light_blue());
+        }
+        line
+      })
+      .collect::<Vec<_>>();
+    lines.resize(num_rows, Line::default());
+    lines.push(Line::from(format!("> {}", state.input)));
+    lines.push(match state.error.as_ref() {
+      Some(error) => Line::from(error.clone()).red(),
+      None => Line::default(),
+    });
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title(format!(
+            "Jump to {} (1..{}, Enter: jump, Esc: close)",
+            state.kind.to_string().to_lowercase(),
+            self.targets.len()
+          ))
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+  use crate::BuildTagKind;
+
+  use super::{JumpDialog, JumpState};
+
+  fn press(state: &mut JumpState, code: KeyCode) -> bool {
+    JumpDialog::handle_key(KeyEvent::from(code), state, |kind| match kind {
+      BuildTagKind::Error => 12,
+      _ => 0,
+    })
+  }
+
+  #[test]
+  fn open() {
+    let mut state = JumpState::default();
+    assert!(!press(&mut state, KeyCode::Char('j')));
+    // `g` waits for the kind
+    assert!(press(&mut state, KeyCode::Char('g')));
+    assert!(!state.is_shown());
+    assert!(press(&mut state, KeyCode::Char('w')));
+    assert!(state.is_shown());
+    assert_eq!(state.kind(), BuildTagKind::Warning);
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_shown());
+    // any other key cancels it and is handled as usual
+    assert!(press(&mut state, KeyCode::Char('g')));
+    assert!(!press(&mut state, KeyCode::Char('j')));
+    assert!(!state.is_shown());
+    assert!(!press(&mut state, KeyCode::Char('e')));
+    assert!(press(&mut state, KeyCode::Char(':')));
+    assert!(state.is_shown());
+    assert_eq!(state.kind(), BuildTagKind::Error);
+  }
+
+  #[test]
+  fn numeric_input() {
+    let mut state = JumpState::default();
+    press(&mut state, KeyCode::Char(':'));
+    for code in [
+      KeyCode::Char('1'),
+      KeyCode::Char('x'),
+      KeyCode::Char('3'),
+      KeyCode::Backspace,
+      KeyCode::Char('2'),
+    ] {
+      assert!(press(&mut state, code));
+    }
+    assert_eq!(state.input(), "12");
+    assert!(press(&mut state, KeyCode::Enter));
+    assert!(!state.is_shown());
+    assert_eq!(state.take_jump(), Some(11));
+    assert_eq!(state.take_jump(), None);
+  }
+
+  #[test]
+  fn out_of_range() {
+    let mut state = JumpState::default();
+    press(&mut state, KeyCode::Char(':'));
+    for code in [KeyCode::Char('1'), KeyCode::Char('3'), KeyCode::Enter] {
+      press(&mut state, code);
+    }
+    // the dialog stays open with an inline error
+    assert!(state.is_shown());
+    assert_eq!(state.take_jump(), None);
+    assert_eq!(state.error(), Some("type a number between 1 and 12"));
+    press(&mut state, KeyCode::Backspace);
+    assert_eq!(state.error(), None);
+    press(&mut state, KeyCode::Backspace);
+    press(&mut state, KeyCode::Enter);
+    assert!(state.error().is_some());
+    press(&mut state, KeyCode::Char('0'));
+    press(&mut state, KeyCode::Enter);
+    assert!(state.error().is_some());
+    // nothing to jump to
+    press(&mut state, KeyCode::Esc);
+    press(&mut state, KeyCode::Char('g'));
+    press(&mut state, KeyCode::Char('n'));
+    press(&mut state, KeyCode::Char('1'));
+    press(&mut state, KeyCode::Enter);
+    assert_eq!(state.error(), Some("no note to jump to"));
+    assert!(state.is_shown());
+  }
+}
@@ -1,17 +1,64 @@
+use std::{borrow::Cow, collections::BTreeMap, ops::Range};
+
 use ratatui::{
-  style::Stylize,
-  text::Line,
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::{Constraint, Layout, Margin, Rect},
+  style::{Style, Stylize},
+  text::{Line, Span},
   widgets::{
     Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget,
   },
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::{BuildTag, BuildTagKind};
+use crate::{
+  apply_selection, expanded_range, BuildTagKind, BuildTagKinds, DisplayMap, Hyperlink, Origin,
+  SelectionTheme,
+};
 
-#[derive(Clone, Default)]
+/// A displayable line: the prepared line of a [`crate::BuildEntry`] and the kinds of its tags
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct LogEntry<'a> {
   line: Line<'a>,
-  tags: Vec<BuildTag>,
+  kinds: BuildTagKinds,
+  origin: Origin,
+  gutter: usize,
+  /// Boxed, the displayed entries get collected for each frame
+  raw: Box<RawLayout>,
+}
+
+/// Where the parts of a displayed line come from in the raw message, see [`LogEntry::display_region`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawLayout {
+  /// The raw bytes shown verbatim in the margin, e.g. the captured `warning:` of a marker
+  pub margin: Option<Range<usize>>,
+  /// The byte the displayed message starts at in the raw message, the bytes before it
+  /// are either in the margin or stripped
+  pub message_start: usize,
+  /// The terminal column of `message_start`, for the tab stops
+  pub column: usize,
+  /// The columns between two tab stops
+  pub tab_width: usize,
+  /// The bytes of the displayed line before the message: the margin and its separator
+  pub display_start: usize,
+  /// The raw byte the displayed message stops at when longer than the display cap,
+  /// the rest is only shown by [`crate::LongLinePopup`]
+  pub truncated: Option<usize>,
+}
+
+/// A displayed [`LogEntry`]: borrowed from the prepared ones, owned once restyled or folded
+pub type DisplayedEntry<'a> = Cow<'a, LogEntry<'a>>;
+
+impl<'a> From<LogEntry<'a>> for DisplayedEntry<'a> {
+  fn from(value: LogEntry<'a>) -> Self {
+    Cow::Owned(value)
+  }
+}
+
+impl<'b, 'a: 'b> From<&'b LogEntry<'a>> for DisplayedEntry<'b> {
+  fn from(value: &'b LogEntry<'a>) -> Self {
+    Cow::Borrowed(value)
+  }
 }
 
 impl<'a> LogEntry<'a> {
@@ -22,43 +69,411 @@ impl<'a> LogEntry<'a> {
   pub fn line_mut(&mut self) -> &mut Line<'a> {
     &mut self.line
   }
+
+  /// Retrieve the stream the entry was read from
+  pub fn origin(&self) -> Origin {
+    self.origin
+  }
+
+  /// Retrieve the kinds of the entry's tags
+  pub fn kinds(&self) -> BuildTagKinds {
+    self.kinds
+  }
+
+  /// Replace the kinds of the entry's tags, after it was tagged again
+  pub fn set_kinds(&mut self, kinds: BuildTagKinds) {
+    self.kinds = kinds;
+  }
+
+  /// Retrieve the width of the margin before the message
+  pub fn gutter(&self) -> usize {
+    self.gutter
+  }
+
+  /// Retrieve where the parts of the line come from in the raw message
+  pub fn raw_layout(&self) -> &RawLayout {
+    &self.raw
+  }
+
+  /// Check if the message was cut at the display cap, see [`RawLayout::truncated`]
+  pub fn is_truncated(&self) -> bool {
+    self.raw.truncated.is_some()
+  }
+
+  /// Translate a byte `region` of the raw `message` to the bytes of the displayed line.
+  ///
+  /// The bytes shown in the margin keep their place in it, the message ones get shifted
+  /// past the margin with their tabs expanded, and the stripped ones are clamped
+  /// to the next displayed byte: a region inside them becomes empty. The bytes past
+  /// the display cap are clamped to the cut, before the truncation suffix
+  pub fn display_region(&self, message: &str, region: Range<usize>) -> Range<usize> {
+    let len = self
+      .line
+      .spans
+      .iter()
+      .map(|span| span.content.len())
+      .sum::<usize>();
+    let raw = &self.raw;
+    let offset = |at: usize| {
+      let at = at.min(raw.truncated.unwrap_or(message.len()).min(message.len()));
+      if let Some(margin) = raw.margin.as_ref().filter(|margin| at < margin.end) {
+        return at.saturating_sub(margin.start);
+      }
+      if at <= raw.message_start {
+        return raw.display_start;
+      }
+      let shown = &message[raw.message_start.min(message.len())..];
+      raw.display_start
+        + expanded_range(shown, 0..at - raw.message_start, raw.column, raw.tab_width).end
+    };
+    let (start, end) = (offset(region.start).min(len), offset(region.end).min(len));
+    start..end.max(start)
+  }
+
+  /// Patch the style of the bytes `range` of the line, splitting its spans
+  pub fn highlight(&mut self, range: Range<usize>, style: Style) {
+    if range.is_empty() {
+      return;
+    }
+    let mut at = 0;
+    let mut spans = vec![];
+    for span in self.line.spans.drain(..) {
+      let span_range = at..at + span.content.len();
+      at = span_range.end;
+      let (start, end) = (
+        range.start.clamp(span_range.start, span_range.end) - span_range.start,
+        range.end.clamp(span_range.start, span_range.end) - span_range.start,
+      );
+      let content = span.content.as_ref();
+      if start == end || !content.is_char_boundary(start) || !content.is_char_boundary(end) {
+        spans.push(span);
+        continue;
+      }
+      for (part, patched) in [
+        (&content[..start], false),
+        (&content[start..end], true),
+        (&content[end..], false),
+      ] {
+        if !part.is_empty() {
+          let part_style = match patched {
+            true => span.style.patch(style),
+            false => span.style,
+          };
+          spans.push(Span::styled(part.to_string(), part_style));
+        }
+      }
+    }
+    self.line.spans = spans;
+  }
+
+  /// Retrieve the message displayed after the margin
+  fn message(&self) -> String {
+    let mut width = 0;
+    self
+      .line
+      .to_string()
+      .chars()
+      .skip_while(|ch| {
+        width += ch.width().unwrap_or_default();
+        width <= self.gutter
+      })
+      .collect()
+  }
 }
 
 impl<'a> LogEntry<'a> {
-  pub fn new(line: Line<'a>, tags: Vec<BuildTag>) -> Self {
-    Self { line, tags }
+  pub fn new(line: Line<'a>, kinds: BuildTagKinds) -> Self {
+    Self {
+      line,
+      kinds,
+      origin: Origin::default(),
+      gutter: 0,
+      raw: Box::default(),
+    }
+  }
+
+  /// Define the width of the margin before the message, see [`Self::gutter`]
+  pub fn with_gutter(mut self, gutter: usize) -> Self {
+    self.gutter = gutter;
+    self
+  }
+
+  /// Define the stream the entry was read from
+  pub fn with_origin(mut self, origin: Origin) -> Self {
+    self.origin = origin;
+    self
+  }
+
+  /// Define where the parts of the line come from in the raw message, see [`Self::display_region`]
+  pub fn with_raw_layout(mut self, raw: RawLayout) -> Self {
+    *self.raw = raw;
+    self
   }
 }
 
-/// Support display of build entries
+/// The styles of the adornments helping to read the columns, see [`LogView::with_ruler`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuideStyles {
+  /// The column ruler above the log
+  pub ruler: Style,
+  /// The indentation guides of the code snippets
+  pub guides: Style,
+}
+
+impl Default for GuideStyles {
+  fn default() -> Self {
+    Self {
+      ruler: Style::default().dark_gray(),
+      guides: Style::default().dark_gray(),
+    }
+  }
+}
+
+/// Support display of build entries, the rows mapped by a [`DisplayMap`]
 #[derive(Default)]
 pub struct LogView<'a> {
   scroll: usize,
-  entries: Vec<LogEntry<'a>>,
+  entries: Vec<DisplayedEntry<'a>>,
+  /// The kind filter the rows were mapped with, shown in the title
   filter: Option<BuildTagKind>,
+  title: Option<String>,
+  focused: bool,
+  ruler: bool,
+  guide_styles: GuideStyles,
+  /// The number of entries before the filters and the folds
+  total: Option<usize>,
+  /// Without the border, its titles and the scroll bar, see the zen mode
+  borderless: bool,
+  /// The row of the line cursor, see [`crate::CursorState`]
+  cursor: Option<usize>,
 }
 
 impl<'a> LogView<'a> {
+  /// Appended to the [`BuildTagKind::Summary`] lines, only on screen
+  pub const SUMMARY_HINT: &'static str = " (press e for first error)";
+
   /// Define the scroll bar value
   pub fn with_scroll(mut self, v: usize) -> Self {
     self.scroll = v;
     self
   }
 
-  pub fn with_filter(mut self, f: BuildTagKind) -> Self {
-    self.filter = Some(f);
+  /// Define the title displayed on the border
+  pub fn with_title<T: AsRef<str>>(mut self, title: T) -> Self {
+    self.title = Some(title.as_ref().to_string());
+    self
+  }
+
+  /// Define the number of entries before the filters and the folds, shown by the position
+  /// indicator when it differs from the visible ones, see [`LogView::position`]
+  pub fn with_total(mut self, total: usize) -> Self {
+    self.total = Some(total);
+    self
+  }
+
+  /// If true highlight the border
+  pub fn with_focus(mut self, v: bool) -> Self {
+    self.focused = v;
+    self
+  }
+
+  /// If true draw the rows only, without the border, the titles and the scroll bar
+  pub fn with_borderless(mut self, v: bool) -> Self {
+    self.borderless = v;
+    self
+  }
+
+  /// If true show the column ruler above the log and the indentation guides of the code snippets
+  pub fn with_ruler(mut self, v: bool) -> Self {
+    self.ruler = v;
+    self
+  }
+
+  /// Define the row highlighted with [`SelectionTheme::cursor`]
+  pub fn with_cursor(mut self, row: Option<usize>) -> Self {
+    self.cursor = row;
     self
   }
 
-  /// Update the displayed lines
-  pub fn with_content(mut self, content: Vec<LogEntry<'a>>) -> Self {
-    self.entries = content;
+  /// Update the displayed rows, borrowed or owned, see [`DisplayMap::apply`]
+  pub fn with_content<E: Into<DisplayedEntry<'a>>, I: IntoIterator<Item = E>>(
+    mut self,
+    content: I,
+  ) -> Self {
+    self.entries = content.into_iter().map(Into::into).collect();
     self
   }
 
   pub fn set_filter(&mut self, f: Option<BuildTagKind>) {
     self.filter = f;
   }
+
+  /// Retrieve the displayed rows
+  fn visible_entries(&self) -> impl Iterator<Item = &LogEntry<'a>> {
+    self.entries.iter().map(|entry| entry.as_ref())
+  }
+
+  /// Build the title of the border: the given one and the active filters
+  fn filters_title(&self) -> Option<String> {
+    let filter = self.filter.map(|kind| match kind {
+      BuildTagKind::Error => "errors only".to_string(),
+      BuildTagKind::BuildScript => "build scripts only".to_string(),
+      kind => format!("{:?}s only", kind).to_lowercase(),
+    });
+    let parts = self.title.iter().cloned().chain(filter).collect::<Vec<_>>();
+    (!parts.is_empty()).then(|| parts.join(" | "))
+  }
+
+  /// Build the position indicator of the `rows` displayed ones,
+  /// e.g. `lines 1200-1260 of 40322 (3%)`
+  fn position(&self, rows: usize) -> String {
+    let num_lines = self.visible_entries().count();
+    let first = self.scroll.min(num_lines);
+    let last = (first + rows).min(num_lines);
+    let mut ret = match num_lines {
+      0 => "no lines".to_string(),
+      _ => format!(
+        "lines {}-{} of {} ({}%)",
+        first + 1,
+        last,
+        num_lines,
+        last * 100 / num_lines
+      ),
+    };
+    if let Some(total) = self.total.filter(|total| *total != num_lines) {
+      ret.push_str(&format!(", {} in total", total));
+    }
+    ret
+  }
+
+  /// Retrieve the number of log rows displayed in `area`, see [`crate::Viewport`]
+  pub fn num_rows(&self, area: Rect) -> usize {
+    self.rows_area(area).height as usize
+  }
+
+  /// Retrieve the area of the log rows when rendering in `area`, below the ruler
+  fn rows_area(&self, area: Rect) -> Rect {
+    let inner = match self.borderless {
+      true => area,
+      false => area.inner(Margin::new(1, 1)),
+    };
+    match self.ruler {
+      true => Rect {
+        y: inner.y + inner.height.min(1),
+        height: inner.height.saturating_sub(1),
+        ..inner
+      },
+      false => inner,
+    }
+  }
+
+  /// Build the column ruler of a `width` cells wide row: `----+----1----+----2`,
+  /// the message columns start after the `gutter`
+  fn ruler(gutter: usize, width: usize) -> String {
+    let mut ret = " ".repeat(gutter.min(width));
+    for column in 1..=width.saturating_sub(gutter) {
+      ret.push(match column % 10 {
+        0 => char::from_digit((column / 10 % 10) as u32, 10).unwrap_or('-'),
+        5 => '+',
+        _ => '-',
+      });
+    }
+    ret
+  }
+
+  /// Locate the `|` of a code snippet line, e.g. ` 12 |     let x = 5;`.
+  ///
+  /// Returns its column and if the line shows code, i.e. has a line number
+  fn snippet_pipe(message: &str) -> Option<(usize, bool)> {
+    let indent = message.len() - message.trim_start_matches(' ').len();
+    let rest = &message[indent..];
+    let digits = rest.len()
+      - rest
+        .trim_start_matches(|ch: char| ch.is_ascii_digit())
+        .len();
+    let spaces = rest[digits..].len() - rest[digits..].trim_start_matches(' ').len();
+    let pipe = indent + digits + spaces;
+    match message[pipe..].strip_prefix('|') {
+      Some(after) if after.is_empty() || after.starts_with(' ') => Some((pipe, digits > 0)),
+      _ => None,
+    }
+  }
+
+  /// Retrieve the column of the first character after the `|` of a code snippet line,
+  /// with if the line shows code. `None` for the other lines and the empty snippet lines
+  fn snippet_indent(message: &str) -> Option<(usize, bool)> {
+    let (pipe, code) = Self::snippet_pipe(message)?;
+    let content = message[pipe + 1..].trim_start_matches(' ');
+    match content.is_empty() {
+      true => None,
+      false => Some((message.len() - content.len(), code)),
+    }
+  }
+
+  /// Compute the indentation guides of the `window` rows of `entries`, as message columns.
+  ///
+  /// The guides of a block are drawn at the indentation levels of its code lines,
+  /// on the snippet lines indented further
+  fn indent_guides(entries: &[&LogEntry<'_>], window: Range<usize>) -> Vec<Vec<usize>> {
+    const MAX_BLOCK_LINES: usize = 1000;
+    let mut levels: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut block_start = None;
+    window
+      .map(|row| {
+        let entry = entries[row];
+        if entry.kinds.has_severity() {
+          block_start = Some(row);
+        } else if block_start.is_none() {
+          block_start = (row.saturating_sub(MAX_BLOCK_LINES)..row)
+            .rev()
+            .find(|i| entries[*i].kinds.has_severity());
+        }
+        let Some(start) = block_start else {
+          return vec![];
+        };
+        let Some((indent, _)) = Self::snippet_indent(&entry.message()) else {
+          return vec![];
+        };
+        let levels = levels.entry(start).or_insert_with(|| {
+          let mut levels = entries
+            .iter()
+            .skip(start + 1)
+            .take(MAX_BLOCK_LINES)
+            .take_while(|entry| !entry.kinds.has_severity())
+            .filter_map(|entry| {
+              let message = entry.message();
+              let (pipe, _) = Self::snippet_pipe(&message)?;
+              // the first code column gets no guide
+              match Self::snippet_indent(&message)? {
+                (indent, true) if indent > pipe + 2 => Some(indent),
+                _ => None,
+              }
+            })
+            .collect::<Vec<_>>();
+          levels.sort();
+          levels.dedup();
+          levels
+        });
+        levels
+          .iter()
+          .copied()
+          .filter(|level| *level < indent)
+          .collect()
+      })
+      .collect()
+  }
+
+  /// Retrieve the locations displayed when rendering in `area`, see [`Hyperlinks`]
+  pub fn hyperlinks(&self, area: Rect) -> Vec<Hyperlink> {
+    let inner = self.rows_area(area);
+    self
+      .visible_entries()
+      .skip(self.scroll)
+      .take(inner.height as usize)
+      .zip(inner.y..)
+      .filter_map(|(entry, y)| Hyperlink::find(&entry.line, y, inner))
+      .collect()
+  }
 }
 
 impl<'a> StatefulWidget for LogView<'a> {
@@ -70,39 +485,72 @@ impl<'a> StatefulWidget for LogView<'a> {
     buf: &mut ratatui::prelude::Buffer,
     state: &mut Self::State,
   ) {
-    let lines = if let Some(tag_filter) = self.filter {
-      self
-        .entries
-        .iter()
-        .filter_map(|entry| {
-          if self.filter.is_none()
-            || entry
-              .tags
-              .iter()
-              .find(|tag| tag.get_kind() == tag_filter)
-              .is_some()
-          {
-            return Some(entry.line.clone());
-          }
-          None
-        })
-        .collect::<Vec<_>>()
-    } else {
-      self
-        .entries
-        .iter()
-        .map(|entry| entry.line.clone())
-        .collect::<Vec<_>>()
-        .clone()
-    };
-    let num_lines = lines.len();
+    let num_lines = self.visible_entries().count();
     *state = state.content_length(num_lines);
-    let log = Paragraph::new(lines)
-      .gray()
-      .block(Block::bordered().gray())
-      .scroll((self.scroll as u16, 0));
+    let rows_area = self.rows_area(area);
+    // only the rows in view get copied
+    let mut lines = self
+      .visible_entries()
+      .enumerate()
+      .skip(self.scroll)
+      .take(area.height as usize)
+      .map(|(row, entry)| {
+        let mut line = entry.line.clone();
+        if entry.kinds.contains(BuildTagKind::Summary) {
+          line.push_span(Span::from(Self::SUMMARY_HINT).dark_gray().italic());
+        }
+        if self.cursor == Some(row) {
+          line = apply_selection(line, &SelectionTheme::cursor());
+        }
+        line
+      })
+      .collect::<Vec<_>>();
+    let mut guides = vec![];
+    if self.ruler {
+      lines.truncate(rows_area.height as usize);
+      let entries = self.visible_entries().collect::<Vec<_>>();
+      let window = self.scroll.min(entries.len())..(self.scroll + lines.len()).min(entries.len());
+      let gutter = entries.get(window.start).map_or(0, |entry| entry.gutter);
+      guides = Self::indent_guides(&entries, window.clone())
+        .into_iter()
+        .zip(&entries[window])
+        .map(|(columns, entry)| (entry.gutter, columns))
+        .collect();
+      let ruler = Self::ruler(gutter, rows_area.width as usize);
+      lines.insert(0, Line::styled(ruler, self.guide_styles.ruler));
+    }
+    let mut block = match (self.borderless, self.focused) {
+      (true, _) => Block::default(),
+      (false, true) => Block::bordered().white(),
+      (false, false) => Block::bordered().gray(),
+    };
+    let title = self.filters_title();
+    let position = format!(" {} ", self.position(rows_area.height as usize));
+    // only shown whole, past the title and the corners
+    let title_width = title.as_ref().map_or(0, |title| title.width() + 1);
+    if !self.borderless && title_width + position.width() + 2 <= area.width as usize {
+      block = block.title_top(Line::from(position).right_aligned());
+    }
+    if let Some(title) = title.filter(|_| !self.borderless) {
+      block = block.title(title);
+    }
+    let log = Paragraph::new(lines).gray().block(block);
     log.render(area, buf);
-    if num_lines + 2 >= area.height as usize {
+    for ((gutter, columns), y) in guides.into_iter().zip(rows_area.y..rows_area.bottom()) {
+      for column in columns {
+        let Ok(x) = u16::try_from(gutter + column) else {
+          continue;
+        };
+        let x = rows_area.x.saturating_add(x);
+        if x >= rows_area.right() {
+          break;
+        }
+        if let Some(cell) = buf.cell_mut((x, y)).filter(|cell| cell.symbol() == " ") {
+          cell.set_symbol("│").set_style(self.guide_styles.guides);
+        }
+      }
+    }
+    if !self.borderless && num_lines + 2 >= area.height as usize {
       Scrollbar::new(ScrollbarOrientation::VerticalRight)
         .begin_symbol(Some("↑"))
         .end_symbol(Some("↓"))
@@ -110,3 +558,495 @@ impl<'a> StatefulWidget for LogView<'a> {
     }
   }
 }
+
+/// The state of the side-by-side stdout/stderr layout.
+///
+/// The focused pane scrolls with the regular log scroll, the state
+/// keeps the scroll of the other one and swaps them on focus changes.
+#[derive(Debug, Clone, Default)]
+pub struct SplitState {
+  enabled: bool,
+  focus: Origin,
+  other_scroll: usize,
+  other_scroll_state: ScrollbarState,
+}
+
+impl SplitState {
+  /// Construct the state, split or not
+  pub fn new(enabled: bool) -> Self {
+    Self {
+      enabled,
+      ..Default::default()
+    }
+  }
+
+  /// Check if the panes are split
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Retrieve the focused pane
+  pub fn focus(&self) -> Origin {
+    self.focus
+  }
+
+  /// Retrieve the scroll of the pane that is not focused
+  pub fn other_scroll(&self) -> usize {
+    self.other_scroll
+  }
+
+  /// Retrieve the pane showing the entries of a stream: stdin goes with stdout
+  pub fn pane(origin: Origin) -> Origin {
+    match origin {
+      Origin::Stderr => Origin::Stderr,
+      _ => Origin::Stdout,
+    }
+  }
+
+  /// Give the focus to a pane, swapping the scrolls if it changes
+  pub fn focus_on(&mut self, pane: Origin, scroll: &mut usize, scroll_state: &mut ScrollbarState) {
+    let pane = Self::pane(pane);
+    if pane == self.focus {
+      return;
+    }
+    self.focus = pane;
+    std::mem::swap(&mut self.other_scroll, scroll);
+    std::mem::swap(&mut self.other_scroll_state, scroll_state);
+  }
+
+  /// Retrieve the rows of the focused pane from the merged ones, or the merged ones
+  /// when not split
+  pub fn view<'d>(&self, display: &'d DisplayMap) -> Cow<'d, DisplayMap> {
+    match self.enabled {
+      true => Cow::Owned(display.pane(self.focus)),
+      false => Cow::Borrowed(display),
+    }
+  }
+
+  /// Scroll the pane containing `entry_id` to reveal it, giving it the focus.
+  /// The rows of the panes get mapped from the merged ones of `display`
+  pub fn reveal(
+    &mut self,
+    entry_id: usize,
+    display: &DisplayMap,
+    scroll: &mut usize,
+    scroll_state: &mut ScrollbarState,
+    height: usize,
+  ) {
+    let Some(row) = display.rows().get(display.row(entry_id)) else {
+      return;
+    };
+    let pane = Self::pane(row.origin);
+    self.focus_on(pane, scroll, scroll_state);
+    let row = display.pane(pane).row(entry_id);
+    if row < *scroll || row >= *scroll + height {
+      *scroll = row;
+      *scroll_state = scroll_state.position(*scroll);
+    }
+  }
+
+  /// Handle `|` to split or merge the panes and `Tab` to switch the focus when split
+  pub fn handle_key(
+    key: KeyEvent,
+    state: &mut SplitState,
+    scroll: &mut usize,
+    scroll_state: &mut ScrollbarState,
+  ) -> bool {
+    if key.code == KeyCode::Char('|') {
+      // back to the merged view with the stdout scroll
+      state.focus_on(Origin::Stdout, scroll, scroll_state);
+      state.enabled = !state.enabled;
+      return true;
+    }
+    if state.enabled && key.code == KeyCode::Tab {
+      let other = match state.focus {
+        Origin::Stderr => Origin::Stdout,
+        _ => Origin::Stderr,
+      };
+      state.focus_on(other, scroll, scroll_state);
+      return true;
+    }
+    false
+  }
+}
+
+/// Display stdout and stderr entries in two [`LogView`]s side by side
+pub struct SplitLogView<'a> {
+  /// The rows of the stdout and the stderr panes
+  panes: [Vec<DisplayedEntry<'a>>; 2],
+  filter: Option<BuildTagKind>,
+  focus: Origin,
+  scrolls: [usize; 2],
+  ruler: bool,
+  /// The row of the line cursor in the focused pane
+  cursor: Option<usize>,
+}
+
+impl<'a> SplitLogView<'a> {
+  /// Construct the view from the rows of the stdout and the stderr panes, see
+  /// [`DisplayMap::pane`]. `scroll` applies to the focused pane
+  pub fn new(panes: [Vec<DisplayedEntry<'a>>; 2], state: &SplitState, scroll: usize) -> Self {
+    let scrolls = match state.focus() {
+      Origin::Stderr => [state.other_scroll(), scroll],
+      _ => [scroll, state.other_scroll()],
+    };
+    Self {
+      panes,
+      filter: None,
+      focus: state.focus(),
+      scrolls,
+      ruler: false,
+      cursor: None,
+    }
+  }
+
+  pub fn set_filter(&mut self, f: Option<BuildTagKind>) {
+    self.filter = f;
+  }
+
+  /// Show the column ruler and the indentation guides in both panes, see [`LogView::with_ruler`]
+  pub fn with_ruler(mut self, v: bool) -> Self {
+    self.ruler = v;
+    self
+  }
+
+  /// Highlight the row of the focused pane, see [`LogView::with_cursor`]
+  pub fn with_cursor(mut self, row: Option<usize>) -> Self {
+    self.cursor = row;
+    self
+  }
+
+  /// Build the view of each pane, with its area
+  fn panes(&self, area: Rect) -> Vec<(LogView<'_>, Rect, usize)> {
+    let areas: [_; 2] =
+      Layout::horizontal([Constraint::Percentage(50), Constraint::Fill(1)]).areas(area);
+    [Origin::Stdout, Origin::Stderr]
+      .into_iter()
+      .zip(&self.panes)
+      .zip(areas)
+      .zip(self.scrolls)
+      .map(|(((pane, rows), area), scroll)| {
+        let mut view = LogView::default()
+          .with_content(rows.iter().map(|entry| entry.as_ref()))
+          .with_title(format!("{:?}", pane).to_lowercase())
+          .with_focus(pane == self.focus)
+          .with_ruler(self.ruler)
+          .with_cursor(self.cursor.filter(|_| pane == self.focus))
+          .with_scroll(scroll);
+        view.set_filter(self.filter);
+        (view, area, scroll)
+      })
+      .collect()
+  }
+
+  /// Retrieve the locations displayed in both panes when rendering in `area`
+  pub fn hyperlinks(&self, area: Rect) -> Vec<Hyperlink> {
+    self
+      .panes(area)
+      .iter()
+      .flat_map(|(view, area, _)| view.hyperlinks(*area))
+      .collect()
+  }
+}
+
+impl<'a> Widget for SplitLogView<'a> {
+  fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+    for (view, area, scroll) in self.panes(area) {
+      let mut state = ScrollbarState::default().position(scroll);
+      view.render(area, buf, &mut state);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    backend::TestBackend,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    text::Line,
+    widgets::ScrollbarState,
+    Terminal,
+  };
+
+  use crate::{BuildTagKind, BuildTagKinds, DisplayLayers, DisplayMap, Origin};
+
+  use super::{DisplayedEntry, LogEntry, LogView, SplitLogView, SplitState};
+
+  fn entries() -> Vec<DisplayedEntry<'static>> {
+    [
+      ("Compiling a", Origin::Stdout),
+      ("error: boom", Origin::Stderr),
+      ("Compiling b", Origin::Stdout),
+      ("note: here", Origin::Stderr),
+      ("Compiling c", Origin::Stdout),
+    ]
+    .into_iter()
+    .map(|(text, origin)| {
+      LogEntry::new(Line::raw(text), Default::default())
+        .with_origin(origin)
+        .into()
+    })
+    .collect()
+  }
+
+  /// The rows of `entries`, merged or of a pane
+  fn display(layers: DisplayLayers) -> DisplayMap {
+    let mut display = DisplayMap::new(layers);
+    display.update(&entries());
+    display
+  }
+
+  fn draw(state: &SplitState, scroll: usize, width: u16, height: u16) -> Vec<String> {
+    let panes = [Origin::Stdout, Origin::Stderr].map(|pane| {
+      display(DisplayLayers::default())
+        .pane(pane)
+        .apply(entries())
+    });
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal
+      .draw(|frame| frame.render_widget(SplitLogView::new(panes, state, scroll), frame.area()))
+      .unwrap();
+    let buf = terminal.backend().buffer();
+    (0..height)
+      .map(|y| (0..width).map(|x| buf[(x, y)].symbol()).collect::<String>())
+      .collect()
+  }
+
+  #[test]
+  fn split_layout() {
+    assert_eq!(
+      draw(&SplitState::new(true), 0, 30, 5),
+      vec![
+        "┌stdout───────↑┌stderr───────┐",
+        "│Compiling a  █│error: boom  │",
+        "│Compiling b  █│note: here   │",
+        "│Compiling c  ║│             │",
+        "└─────────────↓└─────────────┘",
+      ]
+    );
+  }
+
+  #[test]
+  fn split_independent_scroll() {
+    let mut state = SplitState::new(true);
+    let (mut scroll, mut scroll_state) = (1, ScrollbarState::default());
+    // the stdout scroll is kept aside while the stderr pane is focused
+    assert!(SplitState::handle_key(
+      KeyEvent::from(KeyCode::Tab),
+      &mut state,
+      &mut scroll,
+      &mut scroll_state
+    ));
+    assert_eq!(state.focus(), Origin::Stderr);
+    assert_eq!(scroll, 0);
+    assert_eq!(
+      draw(&state, scroll, 30, 4),
+      vec![
+        "┌stdout───────↑┌stderr───────↑",
+        "│Compiling b  █│error: boom  █",
+        "│Compiling c  █│note: here   █",
+        "└─────────────↓└─────────────↓",
+      ]
+    );
+  }
+
+  #[test]
+  fn split_reveal() {
+    let mut state = SplitState::new(true);
+    let (mut scroll, mut scroll_state) = (0, ScrollbarState::default());
+    let display = display(DisplayLayers::default());
+    // entry #4 is the third stdout row
+    state.reveal(4, &display, &mut scroll, &mut scroll_state, 2);
+    assert_eq!(state.focus(), Origin::Stdout);
+    assert_eq!(scroll, 2);
+    state.reveal(3, &display, &mut scroll, &mut scroll_state, 2);
+    assert_eq!(state.focus(), Origin::Stderr);
+    assert_eq!(scroll, 0);
+    assert_eq!(state.other_scroll(), 2);
+    // the rows of the focused pane
+    assert_eq!(state.view(&display).len(), 2);
+    assert_eq!(SplitState::new(false).view(&display).len(), 5);
+  }
+
+  #[test]
+  fn hyperlinks() {
+    let lines = ["error: boom", "  --> src/main.rs:3:7", "note: here"]
+      .into_iter()
+      .map(|text| LogEntry::new(Line::raw(text), Default::default()))
+      .collect::<Vec<_>>();
+    let area = ratatui::layout::Rect::new(0, 0, 30, 5);
+    let links = LogView::default()
+      .with_content(lines.clone())
+      .hyperlinks(area);
+    assert_eq!(
+      links
+        .iter()
+        .map(|link| (link.x, link.y, link.width))
+        .collect::<Vec<_>>(),
+      vec![(7, 2, 15)]
+    );
+    // scrolled out of view
+    assert!(LogView::default()
+      .with_content(lines)
+      .with_scroll(2)
+      .hyperlinks(area)
+      .is_empty());
+  }
+
+  fn snippet() -> Vec<LogEntry<'static>> {
+    [
+      "E error[E0308]: mismatched types",
+      "     --> src/main.rs:3:22",
+      "      |",
+      "    2 |     fn main() {",
+      "    3 |         let x: u32 = \"a\";",
+      "      |                      ^^^ expected `u32`",
+      "    4 |     }",
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, text)| {
+      let kinds = match i {
+        0 => BuildTagKinds::from_iter([BuildTagKind::Error]),
+        _ => Default::default(),
+      };
+      LogEntry::new(Line::raw(text), kinds).with_gutter(2)
+    })
+    .collect()
+  }
+
+  fn draw_ruler(scroll: usize) -> Vec<String> {
+    let (width, height) = (40, 6);
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal
+      .draw(|frame| {
+        let view = LogView::default()
+          .with_content(snippet())
+          .with_ruler(true)
+          .with_scroll(scroll);
+        frame.render_stateful_widget(view, frame.area(), &mut ScrollbarState::default())
+      })
+      .unwrap();
+    let buf = terminal.backend().buffer();
+    (0..height)
+      .map(|y| (0..width).map(|x| buf[(x, y)].symbol()).collect::<String>())
+      .collect()
+  }
+
+  fn draw_title(view: LogView<'_>, width: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, 5)).unwrap();
+    terminal
+      .draw(|frame| {
+        frame.render_stateful_widget(view, frame.area(), &mut ScrollbarState::default())
+      })
+      .unwrap();
+    let buf = terminal.backend().buffer();
+    (0..width).map(|x| buf[(x, 0)].symbol()).collect()
+  }
+
+  #[test]
+  fn position() {
+    let view = || LogView::default().with_content(entries());
+    assert_eq!(
+      draw_title(view().with_total(8), 50),
+      format!("┌{} lines 1-3 of 5 (60%), 8 in total ↑", "─".repeat(14))
+    );
+    assert_eq!(
+      draw_title(view().with_total(5).with_scroll(2), 40),
+      format!("┌{} lines 3-5 of 5 (100%) ↑", "─".repeat(15))
+    );
+    // the filters on the left, the visible lines apart from the total
+    let snippet = snippet()
+      .into_iter()
+      .map(DisplayedEntry::from)
+      .collect::<Vec<_>>();
+    let mut display = DisplayMap::new(DisplayLayers {
+      kind: Some(BuildTagKind::Error),
+      ..Default::default()
+    });
+    display.update(&snippet);
+    let mut errors = LogView::default()
+      .with_content(display.apply(snippet))
+      .with_total(9);
+    errors.set_filter(Some(BuildTagKind::Error));
+    assert_eq!(
+      draw_title(errors, 50),
+      "┌errors only── lines 1-1 of 1 (100%), 9 in total ┐"
+    );
+    let mut scripts = LogView::default().with_title("stdout");
+    scripts.set_filter(Some(BuildTagKind::BuildScript));
+    assert_eq!(
+      draw_title(scripts, 40),
+      "┌stdout | build scripts only─ no lines ┐"
+    );
+    // too narrow for both
+    let mut errors = LogView::default();
+    errors.set_filter(Some(BuildTagKind::Error));
+    assert_eq!(
+      draw_title(errors, 20),
+      format!("┌errors only{}┐", "─".repeat(7))
+    );
+  }
+
+  #[test]
+  fn borderless() {
+    let area = Rect::new(0, 0, 14, 3);
+    let view = || {
+      LogView::default()
+        .with_content(entries())
+        .with_title("stdout")
+        .with_borderless(true)
+    };
+    assert_eq!(view().num_rows(area), 3);
+    let mut terminal = Terminal::new(TestBackend::new(area.width, area.height)).unwrap();
+    terminal
+      .draw(|frame| {
+        frame.render_stateful_widget(view().with_scroll(1), area, &mut ScrollbarState::default())
+      })
+      .unwrap();
+    let buf = terminal.backend().buffer();
+    // no border, title or scroll bar, even with more lines than rows
+    assert_eq!(
+      (0..area.height)
+        .map(|y| (0..area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>())
+        .collect::<Vec<_>>(),
+      vec!["error: boom   ", "Compiling b   ", "note: here    "]
+    );
+  }
+
+  #[test]
+  fn ruler() {
+    assert_eq!(
+      draw_ruler(0),
+      vec![
+        "┌──────────────── lines 1-3 of 7 (42%) ↑",
+        "│  ----+----1----+----2----+----3----+-█",
+        "│E error[E0308]: mismatched types      █",
+        "│     --> src/main.rs:3:22             ║",
+        "│      |                               ║",
+        "└──────────────────────────────────────↓",
+      ]
+    );
+    // the guides follow the indentation levels of the code lines
+    assert_eq!(
+      draw_ruler(3),
+      vec![
+        "┌──────────────── lines 4-6 of 7 (85%) ↑",
+        "│  ----+----1----+----2----+----3----+-█",
+        "│    2 |     fn main() {               █",
+        "│    3 |     │   let x: u32 = \"a\";     ║",
+        "│      |     │   │            ^^^ expec║",
+        "└──────────────────────────────────────↓",
+      ]
+    );
+    // the ruler row shifts the locations down
+    let links = LogView::default()
+      .with_content(snippet())
+      .with_ruler(true)
+      .hyperlinks(ratatui::layout::Rect::new(0, 0, 40, 6));
+    assert_eq!(links.iter().map(|link| link.y).collect::<Vec<_>>(), vec![3]);
+  }
+}
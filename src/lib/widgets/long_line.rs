@@ -0,0 +1,311 @@
+use std::ops::Range;
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::Stylize,
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{human_size, EntryId, REGION_STYLE};
+
+use super::help::popup_area;
+
+/// The state of the [`LongLinePopup`]: the expanded entry, its scroll position
+/// and the region to reveal, e.g. a search match past the display cap
+#[derive(Debug, Clone, Default)]
+pub struct LongLineState {
+  show: bool,
+  entry_id: EntryId,
+  /// The first displayed row
+  scroll: usize,
+  region: Option<Range<usize>>,
+  /// The region still has to be scrolled to, once the rows are known
+  reveal: bool,
+  /// The number of rows of the message, for the width they were counted at
+  rows: Option<(usize, usize)>,
+  /// The rows displayed by the last render, scrolled by `PageUp`/`PageDown`
+  page: usize,
+}
+
+impl LongLineState {
+  /// Display the whole message of `entry_id`, scrolled to the raw bytes `region` if any
+  pub fn open(&mut self, entry_id: EntryId, region: Option<Range<usize>>) {
+    *self = Self {
+      show: true,
+      entry_id,
+      reveal: region.is_some(),
+      region,
+      ..Default::default()
+    };
+  }
+
+  /// Check if the popup is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the expanded entry
+  pub fn entry_id(&self) -> EntryId {
+    self.entry_id
+  }
+
+  /// Retrieve the first displayed row
+  #[cfg(test)]
+  pub fn scroll(&self) -> usize {
+    self.scroll
+  }
+}
+
+/// The whole message of an entry cut at the display cap (see [`crate::RawLayout::truncated`]),
+/// wrapped at the width of the popup. Only the displayed rows get styled
+pub struct LongLinePopup<'a> {
+  message: &'a str,
+}
+
+impl<'a> LongLinePopup<'a> {
+  /// Construct this object from the raw message of the expanded entry
+  pub fn new(message: &'a str) -> Self {
+    Self { message }
+  }
+
+  /// Handle the keys of the displayed popup: `Up`/`Down`, `PageUp`/`PageDown` and
+  /// `Home`/`End` scroll, `Esc`/`q`/`x` closes. It gets opened by the renderer.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut LongLineState) -> bool {
+    if !state.show {
+      return false;
+    }
+    let page = state.page.max(1);
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('x') => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.scroll = state.scroll.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => state.scroll = state.scroll.saturating_add(1),
+      KeyCode::PageUp => state.scroll = state.scroll.saturating_sub(page),
+      KeyCode::PageDown => state.scroll = state.scroll.saturating_add(page),
+      KeyCode::Home | KeyCode::Char('g') => state.scroll = 0,
+      KeyCode::End | KeyCode::Char('G') => state.scroll = usize::MAX,
+      _ => {}
+    }
+    state.reveal = false;
+    true
+  }
+
+  /// Iterate over the byte ranges of the rows of `text` wrapped at `width` columns,
+  /// a wide char never gets split
+  pub fn rows(text: &str, width: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+    let width = width.max(1);
+    let mut chars = text.char_indices().peekable();
+    let mut start = 0;
+    std::iter::from_fn(move || {
+      if start >= text.len() {
+        return None;
+      }
+      let mut used = 0;
+      while let Some(&(at, ch)) = chars.peek() {
+        let ch_width = ch.width().unwrap_or(1);
+        if used > 0 && used + ch_width > width {
+          let row = start..at;
+          start = at;
+          return Some(row);
+        }
+        used += ch_width;
+        chars.next();
+      }
+      let row = start..text.len();
+      start = text.len();
+      Some(row)
+    })
+  }
+
+  /// Retrieve the row of `text` wrapped at `width` columns containing the raw byte `at`
+  pub fn row_of(text: &str, width: usize, at: usize) -> usize {
+    Self::rows(text, width)
+      .take_while(|row| row.end <= at)
+      .count()
+  }
+
+  /// Format one row, the part of `region` in it highlighted
+  fn row(&self, row: Range<usize>, region: Option<&Range<usize>>) -> Line<'a> {
+    // the control chars would move the cursor of the terminal
+    let span = |range: Range<usize>| {
+      let text = self.message[range]
+        .chars()
+        .map(|ch| match ch.is_control() {
+          true => ' ',
+          false => ch,
+        })
+        .collect::<String>();
+      Span::from(text)
+    };
+    let Some(region) = region.filter(|region| region.start < row.end && region.end > row.start)
+    else {
+      return Line::from(span(row));
+    };
+    let (start, end) = (region.start.max(row.start), region.end.min(row.end));
+    Line::default().spans([
+      span(row.start..start),
+      span(start..end).style(REGION_STYLE),
+      span(end..row.end),
+    ])
+  }
+}
+
+impl<'a> StatefulWidget for LongLinePopup<'a> {
+  type State = LongLineState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 90, 80);
+    Clear.render(area, buf);
+    let width = area.width.saturating_sub(2) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    // counting the rows goes through the whole message, only once per width
+    let num_rows = match state.rows {
+      Some((counted, num_rows)) if counted == width => num_rows,
+      _ => {
+        let num_rows = Self::rows(self.message, width).count();
+        state.rows = Some((width, num_rows));
+        num_rows
+      }
+    };
+    if state.reveal {
+      if let Some(region) = state.region.as_ref() {
+        // a row of context above the region
+        state.scroll = Self::row_of(self.message, width, region.start).saturating_sub(1);
+      }
+      state.reveal = false;
+    }
+    state.scroll = state.scroll.min(num_rows.saturating_sub(height));
+    state.page = height;
+    let lines = Self::rows(self.message, width)
+      .skip(state.scroll)
+      .take(height)
+      .map(|row| self.row(row, state.region.as_ref()))
+      .collect::<Vec<_>>();
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title(format!(
+            "Entry #{} ({}, rows {}-{}/{}, Up/Down/PgUp/PgDn: scroll, Esc: close)",
+            state.entry_id.0,
+            human_size(self.message.len() as u64),
+            state.scroll + 1,
+            (state.scroll + height).min(num_rows),
+            num_rows
+          ))
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    widgets::StatefulWidget,
+  };
+
+  use crate::{EntryId, REGION_STYLE};
+
+  use super::{super::help::popup_area, LongLinePopup, LongLineState};
+
+  /// A serialized JSON blob of about 4 MiB, `needle` at 3 MiB
+  fn blob(needle: &str) -> String {
+    let item = r#"{"key":"value","n":42},"#;
+    let mut blob = item.repeat(3 * 1024 * 1024 / item.len());
+    blob.push_str(needle);
+    blob.push_str(&item.repeat(1024 * 1024 / item.len()));
+    blob
+  }
+
+  const AREA: Rect = Rect {
+    x: 0,
+    y: 0,
+    width: 100,
+    height: 30,
+  };
+
+  fn render(message: &str, state: &mut LongLineState) -> (Vec<String>, Buffer) {
+    let area = AREA;
+    let mut buf = Buffer::empty(area);
+    LongLinePopup::new(message).render(area, &mut buf, state);
+    let rows = (0..area.height)
+      .map(|y| {
+        (0..area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>()
+      })
+      .collect();
+    (rows, buf)
+  }
+
+  #[test]
+  fn rows() {
+    let rows = LongLinePopup::rows("abcdefgh", 3).collect::<Vec<_>>();
+    assert_eq!(rows, [0..3, 3..6, 6..8]);
+    // the wide chars go to the next row rather than getting split
+    let rows = LongLinePopup::rows("ab日本", 3).collect::<Vec<_>>();
+    assert_eq!(rows, [0..2, 2..5, 5..8]);
+    assert_eq!(LongLinePopup::rows("", 3).count(), 0);
+    assert_eq!(LongLinePopup::row_of("abcdefgh", 3, 0), 0);
+    assert_eq!(LongLinePopup::row_of("abcdefgh", 3, 3), 1);
+    assert_eq!(LongLinePopup::row_of("abcdefgh", 3, 7), 2);
+  }
+
+  #[test]
+  fn reveal_region() {
+    let needle = "\"needle\":true";
+    let message = blob(needle);
+    let at = message.find(needle).unwrap();
+    let mut state = LongLineState::default();
+    state.open(EntryId(7), Some(at..at + needle.len()));
+    assert!(state.is_shown());
+    let (rows, buf) = render(&message, &mut state);
+    let popup = popup_area(AREA, 90, 80);
+    let (width, height) = (popup.width as usize - 2, popup.height as usize - 2);
+    let row = LongLinePopup::row_of(&message, width, at);
+    assert_eq!(state.scroll(), row - 1);
+    let (y, x) = rows
+      .iter()
+      .enumerate()
+      .find_map(|(y, row)| row.find("\"needle").map(|x| (y, row[..x].chars().count())))
+      .expect("the match is not displayed");
+    assert_eq!(buf[(x as u16, y as u16)].bg, REGION_STYLE.bg.unwrap());
+    assert!(rows[y - 1].contains("\"key\""));
+    assert!(rows.iter().any(|row| row.contains("Entry #7 (4.0 MiB")));
+
+    // scrolled past the end, the last row stays at the bottom
+    for code in [KeyCode::End, KeyCode::PageDown] {
+      assert!(LongLinePopup::handle_key(KeyEvent::from(code), &mut state));
+    }
+    render(&message, &mut state);
+    let num_rows = LongLinePopup::rows(&message, width).count();
+    assert_eq!(state.scroll(), num_rows - height);
+    assert!(LongLinePopup::handle_key(
+      KeyEvent::from(KeyCode::PageUp),
+      &mut state
+    ));
+    assert_eq!(state.scroll(), num_rows - 2 * height);
+    assert!(LongLinePopup::handle_key(
+      KeyEvent::from(KeyCode::Char('x')),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+    assert!(!LongLinePopup::handle_key(
+      KeyEvent::from(KeyCode::Down),
+      &mut state
+    ));
+  }
+}
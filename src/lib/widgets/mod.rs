@@ -1,9 +1,45 @@
-pub mod help;
-pub mod log;
-pub mod search;
-pub mod status;
+mod artifacts;
+mod bookmarks;
+mod diff_view;
+mod help;
+mod history_list;
+mod hyperlink;
+mod info;
+mod input;
+mod jump;
+mod log;
+mod long_line;
+mod pins;
+mod preview;
+mod rule_editor;
+mod rules;
+mod search;
+mod status;
+mod status_history;
+mod tester;
+mod timeline;
+mod timings;
+mod toggles;
 
+pub use artifacts::*;
+pub use bookmarks::*;
+pub use diff_view::*;
 pub use help::*;
+pub use history_list::*;
+pub use hyperlink::*;
+pub use info::*;
+pub use input::*;
+pub use jump::*;
 pub use log::*;
+pub use long_line::*;
+pub use pins::*;
+pub use preview::*;
+pub use rule_editor::*;
+pub use rules::*;
 pub use search::*;
 pub use status::*;
+pub use status_history::*;
+pub use tester::*;
+pub use timeline::*;
+pub use timings::*;
+pub use toggles::*;
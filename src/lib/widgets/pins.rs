@@ -0,0 +1,517 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Borders, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{BlockFingerprint, EntryId, MarkedBlock, MarkerId};
+
+use super::help::popup_area;
+
+/// A block kept visible above the log, see [`PinsState`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pin {
+  /// The marker line of the block
+  pub entry_id: EntryId,
+  /// The block, numbered as in the log
+  pub marker_id: MarkerId,
+  /// The beginning of the marker line, for the pins list
+  pub text: String,
+  /// The block, to find it back after a re-run
+  anchor: Option<BlockFingerprint>,
+}
+
+impl Pin {
+  /// The number of chars of the line kept for the pins list
+  pub const MAX_TEXT_LEN: usize = 80;
+
+  pub fn new<T: AsRef<str>>(entry_id: EntryId, marker_id: MarkerId, text: T) -> Self {
+    Self {
+      entry_id,
+      marker_id,
+      text: text.as_ref().chars().take(Self::MAX_TEXT_LEN).collect(),
+      anchor: None,
+    }
+  }
+}
+
+/// The pinned blocks, in the order they were pinned, and the state of the [`PinList`]
+#[derive(Debug, Clone, Default)]
+pub struct PinsState {
+  pins: Vec<Pin>,
+  /// The first pin displayed by the [`PinnedArea`]
+  scroll: usize,
+  show: bool,
+  cursor: usize,
+  /// The pins wait for a re-run to finish, see [`Self::detach`]
+  detached: bool,
+  /// The outcome of the last `!`, taken by the renderer
+  outcome: Option<Result<bool, usize>>,
+}
+
+impl PinsState {
+  /// The maximum number of pins
+  pub const MAX_PINS: usize = 20;
+
+  /// The number of pins displayed at once by the [`PinnedArea`], the others get scrolled to
+  pub const MAX_SHOWN: usize = 5;
+
+  /// Check if the pins list is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the pins, in the order they were pinned
+  pub fn pins(&self) -> &Vec<Pin> {
+    &self.pins
+  }
+
+  /// Retrieve the pins displayed by the [`PinnedArea`]
+  pub fn visible(&self) -> &[Pin] {
+    let start = self.scroll.min(self.pins.len());
+    &self.pins[start..(start + Self::MAX_SHOWN).min(self.pins.len())]
+  }
+
+  /// Retrieve the index of the first visible pin
+  pub fn scroll(&self) -> usize {
+    self.scroll
+  }
+
+  /// Pin a block, or unpin it if it already is.
+  ///
+  /// Returns whether the block is pinned, an error if [`Self::MAX_PINS`] are already pinned
+  pub fn toggle(&mut self, pin: Pin) -> Result<bool, usize> {
+    if let Some(i) = self
+      .pins
+      .iter()
+      .position(|cur| cur.entry_id == pin.entry_id)
+    {
+      self.remove(i);
+      return Ok(false);
+    }
+    if self.pins.len() >= Self::MAX_PINS {
+      return Err(Self::MAX_PINS);
+    }
+    self.pins.push(pin);
+    // the new pin comes into view
+    self.scroll = self.pins.len().saturating_sub(Self::MAX_SHOWN);
+    Ok(true)
+  }
+
+  /// Unpin the `i`th pin
+  pub fn remove(&mut self, i: usize) {
+    if i < self.pins.len() {
+      self.pins.remove(i);
+    }
+    self.cursor = self.cursor.min(self.pins.len().saturating_sub(1));
+    self.scroll = self
+      .scroll
+      .min(self.pins.len().saturating_sub(Self::MAX_SHOWN));
+  }
+
+  /// Take the outcome of the last `!`, see [`Self::toggle`]
+  pub fn take_outcome(&mut self) -> Option<Result<bool, usize>> {
+    self.outcome.take()
+  }
+
+  /// Check if the pins wait for [`Self::reattach`]
+  pub fn is_detached(&self) -> bool {
+    self.detached
+  }
+
+  /// Forget the entry ids before a re-run: the pins keep the fingerprint of their block
+  /// from `anchor`, to be found back by [`Self::reattach`]
+  pub fn detach<F: Fn(EntryId) -> Option<BlockFingerprint>>(&mut self, anchor: F) {
+    self.pins.retain_mut(|pin| {
+      pin.anchor = anchor(pin.entry_id);
+      pin.anchor.is_some()
+    });
+    self.detached = !self.pins.is_empty();
+    (self.cursor, self.scroll) = (0, 0);
+  }
+
+  /// Find the detached pins back in the blocks of the new build,
+  /// the ones without a matching block are dropped
+  pub fn reattach(&mut self, blocks: &[MarkedBlock<'_>]) {
+    let fingerprints = blocks
+      .iter()
+      .map(BlockFingerprint::from_block)
+      .collect::<Vec<_>>();
+    self.pins.retain_mut(|pin| {
+      let Some(fingerprint) = pin.anchor.take() else {
+        return true;
+      };
+      let Some(block) = fingerprint.best_match(&fingerprints).map(|id| &blocks[id]) else {
+        return false;
+      };
+      *pin = Pin::new(
+        EntryId(block.range().start),
+        block.marker_id(),
+        block.first_line().unwrap_or_default(),
+      );
+      true
+    });
+    // two pins may have matched the same block
+    let mut seen = vec![];
+    self.pins.retain(|pin| match seen.contains(&pin.entry_id) {
+      true => false,
+      false => {
+        seen.push(pin.entry_id);
+        true
+      }
+    });
+    self.detached = false;
+  }
+}
+
+/// The list of the pins. `p` shows/hides it, `Up`/`Down` select a pin
+/// and scroll the pinned area to it, `d` unpins it and `Esc`/`q` closes
+pub struct PinList;
+
+impl PinList {
+  /// Handle `!` to pin/unpin the block of `current`, and the keys of the list.
+  ///
+  /// Returns true if the key was handled, see [`PinsState::take_outcome`] for the outcome of `!`
+  pub fn handle_key<F: FnOnce() -> Option<Pin>>(
+    key: KeyEvent,
+    state: &mut PinsState,
+    current: F,
+  ) -> bool {
+    if !state.show {
+      match key.code {
+        KeyCode::Char('!') => {
+          // the entry ids are stale until reattached
+          if let Some(pin) = current().filter(|_| !state.detached) {
+            state.outcome = Some(state.toggle(pin));
+          }
+        }
+        KeyCode::Char('p') => {
+          state.show = true;
+          state.cursor = state.cursor.min(state.pins.len().saturating_sub(1));
+        }
+        _ => return false,
+      }
+      return true;
+    }
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('p') => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.cursor = state.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => {
+        state.cursor = (state.cursor + 1).min(state.pins.len().saturating_sub(1))
+      }
+      KeyCode::Char('d') | KeyCode::Delete | KeyCode::Char('!') => state.remove(state.cursor),
+      _ => {}
+    }
+    // the selected pin stays in the pinned area
+    if state.cursor < state.scroll {
+      state.scroll = state.cursor;
+    } else if state.cursor >= state.scroll + PinsState::MAX_SHOWN {
+      state.scroll = state.cursor + 1 - PinsState::MAX_SHOWN;
+    }
+    true
+  }
+}
+
+impl StatefulWidget for PinList {
+  type State = PinsState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 70, 50);
+    Clear.render(area, buf);
+    let num_rows = area.height.saturating_sub(2) as usize;
+    let first = (state.cursor + 1).saturating_sub(num_rows);
+    let lines = match state.pins.is_empty() {
+      true => vec![Line::from("no pin, press ! on a selected block to pin it").dark_gray()],
+      false => state
+        .pins
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(num_rows)
+        .map(|(i, pin)| {
+          let line = Line::default().spans(vec![
+            Span::from(format!("#{} ", pin.marker_id)).bold().yellow(),
+            Span::from(pin.text.clone()),
+          ]);
+          match i == state.cursor {
+            true => line.style(Style::default().on_light_blue()),
+            false => line,
+          }
+        })
+        .collect::<Vec<_>>(),
+    };
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title("Pins (d: unpin, Esc: close)")
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+/// The pinned blocks, displayed above the log: each pin shows the first lines
+/// of its block, the first one prefixed with the block number
+#[derive(Default)]
+pub struct PinnedArea<'a> {
+  pins: Vec<(MarkerId, Vec<Line<'a>>)>,
+  scroll: usize,
+  total: usize,
+}
+
+impl<'a> PinnedArea<'a> {
+  /// Construct the area showing the lines of the visible pins,
+  /// the `scroll`th of `total` pins being the first one
+  pub fn new<I: IntoIterator<Item = (MarkerId, Vec<Line<'a>>)>>(
+    pins: I,
+    scroll: usize,
+    total: usize,
+  ) -> Self {
+    Self {
+      pins: pins.into_iter().collect(),
+      scroll,
+      total,
+    }
+  }
+
+  /// Retrieve the rows taken by the area, its lines and the separator, none without pins
+  pub fn height(&self) -> u16 {
+    match self.pins.is_empty() {
+      true => 0,
+      false => {
+        self
+          .pins
+          .iter()
+          .map(|(_marker_id, lines)| lines.len().max(1))
+          .sum::<usize>() as u16
+          + 1
+      }
+    }
+  }
+}
+
+impl<'a> Widget for PinnedArea<'a> {
+  fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+    Clear.render(area, buf);
+    let width = self
+      .pins
+      .iter()
+      .map(|(marker_id, _lines)| format!("#{} ", marker_id).len())
+      .max()
+      .unwrap_or_default();
+    let mut lines = vec![];
+    for (marker_id, pin_lines) in self.pins {
+      let mut pin_lines = pin_lines.into_iter();
+      let first = pin_lines.next().unwrap_or_default();
+      lines.push(
+        Line::default()
+          .spans(
+            [
+              Span::from(format!("{:<width$}", format!("#{} ", marker_id)))
+                .bold()
+                .yellow(),
+            ]
+            .into_iter()
+            .chain(first.spans),
+          )
+          .style(first.style),
+      );
+      for line in pin_lines {
+        lines.push(
+          Line::default()
+            .spans(
+              [Span::from(" ".repeat(width))]
+                .into_iter()
+                .chain(line.spans),
+            )
+            .style(line.style),
+        );
+      }
+    }
+    let shown = format!(
+      "pinned {}-{}/{}, p: list",
+      self.scroll + 1,
+      (self.scroll + PinsState::MAX_SHOWN).min(self.total),
+      self.total
+    );
+    Paragraph::new(lines)
+      .block(
+        Block::new()
+          .borders(Borders::BOTTOM)
+          .title_bottom(Line::from(shown).right_aligned())
+          .dark_gray(),
+      )
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    backend::TestBackend,
+    crossterm::event::{KeyCode, KeyEvent},
+    text::Line,
+    Terminal,
+  };
+
+  use crate::{BlockFingerprint, BuildOutput, EntryId, MarkerId};
+
+  use super::{Pin, PinList, PinnedArea, PinsState};
+
+  fn pin(line: usize) -> Pin {
+    Pin::new(EntryId(line), MarkerId(line), format!("line {}", line))
+  }
+
+  fn press(state: &mut PinsState, code: KeyCode, line: usize) -> Option<Result<bool, usize>> {
+    assert!(PinList::handle_key(KeyEvent::from(code), state, || {
+      Some(pin(line))
+    }));
+    state.take_outcome()
+  }
+
+  #[test]
+  fn add_remove() {
+    let mut state = PinsState::default();
+    assert_eq!(press(&mut state, KeyCode::Char('!'), 4), Some(Ok(true)));
+    assert_eq!(press(&mut state, KeyCode::Char('!'), 9), Some(Ok(true)));
+    assert_eq!(state.pins(), &vec![pin(4), pin(9)]);
+    // pressed again on the same block
+    assert_eq!(press(&mut state, KeyCode::Char('!'), 4), Some(Ok(false)));
+    assert_eq!(state.pins(), &vec![pin(9)]);
+    // nothing selected
+    assert!(PinList::handle_key(
+      KeyEvent::from(KeyCode::Char('!')),
+      &mut state,
+      || None
+    ));
+    assert_eq!(state.take_outcome(), None);
+    assert!(!PinList::handle_key(
+      KeyEvent::from(KeyCode::Char('j')),
+      &mut state,
+      || None
+    ));
+
+    // from the list
+    press(&mut state, KeyCode::Char('!'), 12);
+    press(&mut state, KeyCode::Char('p'), 0);
+    assert!(state.is_shown());
+    press(&mut state, KeyCode::Down, 0);
+    press(&mut state, KeyCode::Char('d'), 0);
+    assert_eq!(state.pins(), &vec![pin(9)]);
+    press(&mut state, KeyCode::Esc, 0);
+    assert!(!state.is_shown());
+  }
+
+  #[test]
+  fn limit() {
+    let mut state = PinsState::default();
+    for line in 0..PinsState::MAX_PINS {
+      assert_eq!(state.toggle(pin(line)), Ok(true));
+    }
+    assert_eq!(
+      state.toggle(pin(PinsState::MAX_PINS)),
+      Err(PinsState::MAX_PINS)
+    );
+    assert_eq!(state.pins().len(), PinsState::MAX_PINS);
+    // the last pins are in view
+    assert_eq!(state.visible().len(), PinsState::MAX_SHOWN);
+    assert_eq!(
+      state.visible()[0],
+      pin(PinsState::MAX_PINS - PinsState::MAX_SHOWN)
+    );
+
+    // selecting a pin scrolls the area to it
+    press(&mut state, KeyCode::Char('p'), 0);
+    for _ in 0..PinsState::MAX_PINS {
+      press(&mut state, KeyCode::Up, 0);
+    }
+    assert_eq!(state.scroll(), 0);
+    for _ in 0..7 {
+      press(&mut state, KeyCode::Down, 0);
+    }
+    assert_eq!(state.scroll(), 3);
+    assert_eq!(state.visible().last(), Some(&pin(7)));
+    // unpinning the last ones keeps the area full
+    while state.pins().len() > 6 {
+      state.remove(0);
+    }
+    assert_eq!(state.scroll(), 1);
+    assert_eq!(state.visible().len(), PinsState::MAX_SHOWN);
+  }
+
+  #[test]
+  fn reattach() {
+    let mut build = BuildOutput::from([
+      "   Compiling demo v0.1.0",
+      "warning: unused variable: `x`",
+      " --> src/main.rs:2:9",
+      "error[E0308]: mismatched types",
+      " --> src/main.rs:4:5",
+    ]);
+    while build.prepare() {}
+    let mut state = PinsState::default();
+    for entry_id in [1, 3] {
+      let block = build.block_at(EntryId(entry_id)).unwrap();
+      let pin = Pin::new(
+        EntryId(entry_id),
+        block.marker_id(),
+        block.first_line().unwrap(),
+      );
+      state.toggle(pin).unwrap();
+    }
+    state.detach(|entry_id| Some(BlockFingerprint::from_block(&build.block_at(entry_id)?)));
+    assert!(state.is_detached());
+    // the entry ids are stale
+    assert_eq!(press(&mut state, KeyCode::Char('!'), 3), None);
+
+    let mut rebuilt = BuildOutput::from([
+      "   Compiling demo v0.1.0",
+      "error[E0308]: mismatched types",
+      " --> src/main.rs:6:5",
+    ]);
+    while rebuilt.prepare() {}
+    state.reattach(&rebuilt.blocks());
+    assert!(!state.is_detached());
+    // the fixed warning is unpinned
+    assert_eq!(state.pins().len(), 1);
+    assert_eq!(state.pins()[0].entry_id, EntryId(1));
+    assert_eq!(state.pins()[0].text, "error[E0308]: mismatched types");
+  }
+
+  #[test]
+  fn render() {
+    let area = PinnedArea::new(
+      [
+        (
+          MarkerId(3),
+          vec![Line::from("error: boom"), Line::from(" --> src/a.rs:1:2")],
+        ),
+        (MarkerId(12), vec![Line::from("warning: unused")]),
+      ],
+      0,
+      2,
+    );
+    assert_eq!(area.height(), 4);
+    assert_eq!(PinnedArea::default().height(), 0);
+    let mut terminal = Terminal::new(TestBackend::new(40, 4)).unwrap();
+    terminal
+      .draw(|frame| frame.render_widget(area, frame.area()))
+      .unwrap();
+    let buffer = terminal.backend().buffer();
+    let rows = (0..4)
+      .map(|y| (0..40).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+      .collect::<Vec<_>>();
+    assert_eq!(rows[0].trim_end(), "#3  error: boom");
+    assert_eq!(rows[1].trim_end(), "     --> src/a.rs:1:2");
+    assert_eq!(rows[2].trim_end(), "#12 warning: unused");
+    assert!(rows[3].ends_with("pinned 1-2/2, p: list"), "{:?}", rows[3]);
+  }
+}
@@ -0,0 +1,321 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{display_path, Location};
+
+/// The state of the [`FilePreview`] widget.
+///
+/// Files are read lazily the first time they are displayed and cached
+/// for the subsequent renders, until the preview gets opened on them again.
+#[derive(Default)]
+pub struct PreviewState {
+  location: Option<Location>,
+  scroll: usize,
+  focused: bool,
+  cache: HashMap<PathBuf, Result<Vec<String>, String>>,
+}
+
+impl PreviewState {
+  /// Open the preview on the supplied [`Location`] and give it the focus.
+  ///
+  /// The file is read again at the next render, it may have changed since the last one
+  pub fn open(&mut self, location: Location) {
+    self.cache.remove(&location.canonical_path());
+    self.scroll = location
+      .line()
+      .unwrap_or(1)
+      .saturating_sub(1)
+      .saturating_sub(FilePreview::NUM_LINES / 2);
+    self.location = Some(location);
+    self.focused = true;
+  }
+
+  /// Close the preview
+  pub fn close(&mut self) {
+    self.location = None;
+    self.focused = false;
+  }
+
+  /// Check if the preview is opened
+  pub fn is_open(&self) -> bool {
+    self.location.is_some()
+  }
+
+  /// Check if the preview has the focus
+  pub fn is_focused(&self) -> bool {
+    self.focused
+  }
+
+  /// Retrieve the previewed [`Location`]
+  pub fn location(&self) -> Option<&Location> {
+    self.location.as_ref()
+  }
+
+  /// Retrieve the first displayed line (0-based)
+  pub fn scroll(&self) -> usize {
+    self.scroll
+  }
+
+  /// Read the lines of the previewed file, going through the cache
+  pub fn lines(&mut self) -> Option<&Result<Vec<String>, String>> {
+    let path = self.location.as_ref()?.canonical_path();
+    Some(self.cache.entry(path.clone()).or_insert_with(|| {
+      crate::dbg!("Reading preview of {}", path.display());
+      std::fs::read_to_string(&path)
+        .map(|content| content.lines().map(|line| line.to_string()).collect())
+        .map_err(|e| format!("failed to read {}, {}", path.display(), e))
+    }))
+  }
+}
+
+/// Display an excerpt of a source file centered on a [`Location`]
+pub struct FilePreview;
+
+impl FilePreview {
+  /// The number of source lines displayed
+  pub const NUM_LINES: usize = 15;
+
+  /// The height of the widget, borders included
+  pub const HEIGHT: u16 = Self::NUM_LINES as u16 + 2;
+
+  /// Handle the preview keys when it is opened and focused
+  pub fn handle_key(key: KeyEvent, state: &mut PreviewState) -> bool {
+    if !state.is_open() {
+      return false;
+    }
+    if key.code == KeyCode::Tab {
+      state.focused = !state.focused;
+      return true;
+    }
+    if !state.focused {
+      return false;
+    }
+    if key.code == KeyCode::Esc {
+      state.close();
+    } else if key.code == KeyCode::Char('j') {
+      let num_lines = match state.lines() {
+        Some(Ok(lines)) => lines.len(),
+        _ => 0,
+      };
+      if state.scroll < num_lines.saturating_sub(Self::NUM_LINES) {
+        state.scroll += 1;
+      }
+    } else if key.code == KeyCode::Char('k') {
+      state.scroll = state.scroll.saturating_sub(1);
+    } else {
+      return false;
+    }
+    true
+  }
+}
+
+impl StatefulWidget for FilePreview {
+  type State = PreviewState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let location = match state.location() {
+      Some(location) => location.clone(),
+      None => return,
+    };
+    let scroll = state.scroll();
+    let border_style = match state.is_focused() {
+      true => Style::default().white(),
+      false => Style::default().gray(),
+    };
+    let lines = match state.lines() {
+      Some(Ok(lines)) => {
+        let width = format!("{}", lines.len()).len();
+        lines
+          .iter()
+          .enumerate()
+          .skip(scroll)
+          .take(Self::NUM_LINES)
+          .map(|(id, content)| {
+            let gutter = Span::from(format!("{:>width$} | ", id + 1, width = width)).dim();
+            if Some(id + 1) != location.line() {
+              return Line::default().spans([gutter, Span::from(content.clone())]);
+            }
+            let mut spans = vec![gutter];
+            match location.column() {
+              Some(column) if column > 0 && column <= content.chars().count() => {
+                let before = content.chars().take(column - 1).collect::<String>();
+                let at = content.chars().skip(column - 1).take(1).collect::<String>();
+                let after = content.chars().skip(column).collect::<String>();
+                spans.extend([
+                  Span::from(before),
+                  Span::from(at).reversed(),
+                  Span::from(after),
+                ]);
+              }
+              _ => spans.push(Span::from(content.clone())),
+            }
+            Line::default()
+              .spans(spans)
+              .style(Style::default().on_light_blue())
+          })
+          .collect::<Vec<_>>()
+      }
+      Some(Err(e)) => {
+        vec![Line::default().spans([Span::from(" ✗ ").bold().red(), Span::from(e.clone())])]
+      }
+      None => vec![],
+    };
+    Clear.render(area, buf);
+    Paragraph::new(lines)
+      .gray()
+      .block(
+        Block::bordered()
+          .title(format!(
+            " {}{} ",
+            display_path(location.path()),
+            match (location.line(), location.column()) {
+              (Some(line), Some(column)) => format!(":{}:{}", line, column),
+              (Some(line), None) => format!(":{}", line),
+              _ => String::new(),
+            }
+          ))
+          .border_style(border_style),
+      )
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{remove_file, write};
+
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    widgets::StatefulWidget,
+  };
+
+  use crate::Location;
+
+  use super::{FilePreview, PreviewState};
+
+  fn source(test: &str, num_lines: usize) -> Location {
+    let path =
+      std::env::temp_dir().join(format!("nbuild-preview-{}-{}.rs", test, std::process::id()));
+    let content = (1..=num_lines)
+      .map(|id| format!("let line{} = {};\n", id, id))
+      .collect::<String>();
+    write(&path, content).unwrap();
+    Location::new(path, Some(1), Some(5))
+  }
+
+  fn press(state: &mut PreviewState, code: KeyCode) -> bool {
+    FilePreview::handle_key(KeyEvent::from(code), state)
+  }
+
+  fn render(state: &mut PreviewState) -> Vec<String> {
+    let area = Rect::new(0, 0, 60, FilePreview::HEIGHT);
+    let mut buf = Buffer::empty(area);
+    FilePreview.render(area, &mut buf, state);
+    (0..area.height)
+      .map(|y| {
+        (0..area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn missing_file() {
+    let mut state = PreviewState::default();
+    state.open(Location::new("/nonexistent/nbuild/main.rs", Some(3), None));
+    assert!(matches!(
+      state.lines(),
+      Some(Err(e)) if e.starts_with("failed to read /nonexistent/nbuild/main.rs")
+    ));
+    assert!(render(&mut state)[1].contains("✗ failed to read"));
+    // nothing to scroll
+    assert!(press(&mut state, KeyCode::Char('j')));
+    assert_eq!(state.scroll(), 0);
+  }
+
+  #[test]
+  fn scroll_clamped() {
+    let location = source("scroll", 40);
+    let path = location.path().to_path_buf();
+    let mut state = PreviewState::default();
+    state.open(location);
+    assert_eq!(state.scroll(), 0);
+    assert!(press(&mut state, KeyCode::Char('k')));
+    assert_eq!(state.scroll(), 0);
+    for _ in 0..50 {
+      press(&mut state, KeyCode::Char('j'));
+    }
+    assert_eq!(state.scroll(), 40 - FilePreview::NUM_LINES);
+    let rows = render(&mut state);
+    assert!(rows[1].contains("26 | let line26 = 26;"));
+    assert!(rows[FilePreview::NUM_LINES].contains("40 | let line40 = 40;"));
+
+    // centered on the line, from the top of the file
+    state.open(Location::new(&path, Some(20), None));
+    assert_eq!(state.scroll(), 19 - FilePreview::NUM_LINES / 2);
+    state.open(Location::new(&path, Some(3), None));
+    assert_eq!(state.scroll(), 0);
+    let _ = remove_file(&path);
+  }
+
+  #[test]
+  fn cache_invalidated_on_open() {
+    let location = source("cache", 3);
+    let path = location.path().to_path_buf();
+    let mut state = PreviewState::default();
+    state.open(location.clone());
+    assert!(matches!(state.lines(), Some(Ok(lines)) if lines.len() == 3));
+
+    // the renders keep the lines read first
+    write(&path, "fn main() {}\n").unwrap();
+    assert!(matches!(state.lines(), Some(Ok(lines)) if lines.len() == 3));
+    state.open(location);
+    assert!(matches!(state.lines(), Some(Ok(lines)) if lines == &["fn main() {}"]));
+    let _ = remove_file(&path);
+  }
+
+  #[test]
+  fn keys() {
+    let location = source("keys", 40);
+    let path = location.path().to_path_buf();
+    let mut state = PreviewState::default();
+    assert!(!press(&mut state, KeyCode::Char('j')));
+    assert!(!press(&mut state, KeyCode::Tab));
+
+    state.open(location);
+    assert!(state.is_focused());
+    // the log gets the keys while the preview stays opened
+    assert!(press(&mut state, KeyCode::Tab));
+    assert!(!state.is_focused());
+    assert!(!press(&mut state, KeyCode::Char('j')));
+    assert!(!press(&mut state, KeyCode::Esc));
+    assert_eq!(state.scroll(), 0);
+    assert!(state.is_open());
+
+    assert!(press(&mut state, KeyCode::Tab));
+    assert!(press(&mut state, KeyCode::Char('j')));
+    assert!(press(&mut state, KeyCode::Char('j')));
+    assert!(press(&mut state, KeyCode::Char('k')));
+    assert_eq!(state.scroll(), 1);
+    assert!(!press(&mut state, KeyCode::Char('x')));
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_open());
+    assert!(!state.is_focused());
+    assert!(!press(&mut state, KeyCode::Esc));
+    let _ = remove_file(&path);
+  }
+}
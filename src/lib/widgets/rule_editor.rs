@@ -0,0 +1,252 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::Rule;
+
+use super::help::popup_area;
+
+/// The requests emitted by the [`RuleEditor`], handled by the renderer
+#[derive(Debug, Clone)]
+pub enum RuleEditorAction {
+  /// Replace the rule in memory and prepare the output again
+  Apply(Rule),
+  /// Save the rule to the config file it came from
+  Save(Rule),
+}
+
+/// The state of the [`RuleEditor`]: the edited rule and the selected marker
+#[derive(Debug, Clone, Default)]
+pub struct RuleEditorState {
+  show: bool,
+  cursor: usize,
+  rule: Option<Rule>,
+  action: Option<RuleEditorAction>,
+}
+
+impl RuleEditorState {
+  /// Check if the editor is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the position of the selected marker
+  #[cfg(test)]
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  /// Retrieve the edited rule
+  #[cfg(test)]
+  pub fn rule(&self) -> Option<&Rule> {
+    self.rule.as_ref()
+  }
+
+  /// Display the editor with the markers of `rule`
+  pub fn open(&mut self, rule: Rule) {
+    self.show = true;
+    self.cursor = 0;
+    self.rule = Some(rule);
+  }
+
+  /// Take the request emitted by the last key
+  pub fn take_action(&mut self) -> Option<RuleEditorAction> {
+    self.action.take()
+  }
+}
+
+/// The markers of a rule, in match order: `Up`/`Down` select a marker,
+/// `Shift-Up`/`Shift-Down` move it, `d` disables or enables it and `s` saves the rule
+pub struct RuleEditor;
+
+impl RuleEditor {
+  /// Handle the keys of the displayed editor, `Esc`/`q` closes it.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut RuleEditorState) -> bool {
+    if !state.show {
+      return false;
+    }
+    let Some(rule) = state.rule.as_mut() else {
+      state.show = false;
+      return false;
+    };
+    let last = rule.markers.len().saturating_sub(1);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') => state.show = false,
+      KeyCode::Up if shift && state.cursor > 0 => {
+        rule.markers.swap(state.cursor, state.cursor - 1);
+        state.cursor -= 1;
+        state.action = Some(RuleEditorAction::Apply(rule.clone()));
+      }
+      KeyCode::Down if shift && state.cursor < last => {
+        rule.markers.swap(state.cursor, state.cursor + 1);
+        state.cursor += 1;
+        state.action = Some(RuleEditorAction::Apply(rule.clone()));
+      }
+      KeyCode::Up | KeyCode::Char('k') => state.cursor = state.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => state.cursor = (state.cursor + 1).min(last),
+      KeyCode::Char('d') => {
+        if let Some(marker) = rule.markers.get_mut(state.cursor) {
+          marker.disabled = !marker.disabled;
+          state.action = Some(RuleEditorAction::Apply(rule.clone()));
+        }
+      }
+      KeyCode::Char('s') => state.action = Some(RuleEditorAction::Save(rule.clone())),
+      _ => {}
+    }
+    true
+  }
+}
+
+impl StatefulWidget for RuleEditor {
+  type State = RuleEditorState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let Some(rule) = state.rule.as_ref() else {
+      return;
+    };
+    let area = popup_area(area, 70, 40);
+    Clear.render(area, buf);
+    let kind_width = rule
+      .markers
+      .iter()
+      .map(|marker| marker.tag.to_string().len())
+      .max()
+      .unwrap_or_default();
+    let lines = rule
+      .markers
+      .iter()
+      .enumerate()
+      .map(|(i, marker)| {
+        let mut spans = vec![
+          Span::from(format!("{:>2}. ", i + 1)).dark_gray(),
+          Span::styled("■■", marker.style),
+          Span::from(format!(" {:<width$} ", marker.tag, width = kind_width)).bold(),
+          Span::from(marker.regex.as_str().to_string()),
+        ];
+        if marker.disabled {
+          spans.push(Span::from(" (disabled)").dark_gray());
+        }
+        let line = Line::default().spans(spans);
+        let line = match marker.disabled {
+          true => line.crossed_out(),
+          false => line,
+        };
+        match i == state.cursor {
+          true => line.style(Style::default().on_light_blue()),
+          false => line,
+        }
+      })
+      .collect::<Vec<_>>();
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title(format!(
+            "Rule '{}' (Shift-Up/Down: move, d: disable, s: save, Esc: close)",
+            rule.aliases.first().cloned().unwrap_or_default()
+          ))
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+  use crate::{BuildTagKind, DEFAULT_RULES};
+
+  use super::{RuleEditor, RuleEditorAction, RuleEditorState};
+
+  fn kinds(state: &RuleEditorState) -> Vec<BuildTagKind> {
+    state
+      .rule()
+      .unwrap()
+      .markers
+      .iter()
+      .map(|marker| marker.tag)
+      .collect()
+  }
+
+  #[test]
+  fn handle_key() {
+    let mut state = RuleEditorState::default();
+    assert!(!RuleEditor::handle_key(
+      KeyEvent::from(KeyCode::Down),
+      &mut state
+    ));
+    // the error, note and warning markers
+    let mut rule = DEFAULT_RULES[0].clone();
+    rule.markers.truncate(3);
+    state.open(rule);
+    assert_eq!(
+      kinds(&state),
+      vec![
+        BuildTagKind::Error,
+        BuildTagKind::Note,
+        BuildTagKind::Warning
+      ]
+    );
+    // moving the selection changes nothing
+    assert!(RuleEditor::handle_key(
+      KeyEvent::from(KeyCode::Down),
+      &mut state
+    ));
+    assert_eq!(state.cursor(), 1);
+    assert!(state.take_action().is_none());
+    // the note moves after the warning
+    let shift_down = KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT);
+    assert!(RuleEditor::handle_key(shift_down, &mut state));
+    assert_eq!(state.cursor(), 2);
+    assert_eq!(
+      kinds(&state),
+      vec![
+        BuildTagKind::Error,
+        BuildTagKind::Warning,
+        BuildTagKind::Note
+      ]
+    );
+    assert!(matches!(
+      state.take_action(),
+      Some(RuleEditorAction::Apply(rule)) if rule.markers[2].tag == BuildTagKind::Note
+    ));
+    // the last marker stays last
+    assert!(RuleEditor::handle_key(shift_down, &mut state));
+    assert!(state.take_action().is_none());
+    assert!(RuleEditor::handle_key(
+      KeyEvent::from(KeyCode::Char('d')),
+      &mut state
+    ));
+    assert!(state.rule().unwrap().markers[2].disabled);
+    assert!(matches!(
+      state.take_action(),
+      Some(RuleEditorAction::Apply(rule)) if rule.markers[2].disabled
+    ));
+    assert!(RuleEditor::handle_key(
+      KeyEvent::from(KeyCode::Char('s')),
+      &mut state
+    ));
+    assert!(matches!(
+      state.take_action(),
+      Some(RuleEditorAction::Save(rule)) if rule.markers[2].disabled
+    ));
+    assert!(RuleEditor::handle_key(
+      KeyEvent::from(KeyCode::Esc),
+      &mut state
+    ));
+    assert!(!state.is_shown());
+  }
+}
@@ -0,0 +1,200 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget, Wrap},
+};
+
+use crate::RuleCandidate;
+
+use super::help::popup_area;
+
+/// The state of the [`RulePicker`]: the scored rules and the one picked
+#[derive(Debug, Clone, Default)]
+pub struct RulePickerState {
+  show: bool,
+  cursor: usize,
+  candidates: Vec<RuleCandidate>,
+  active: String,
+  reason: Option<String>,
+  pick: Option<String>,
+  edit: Option<String>,
+}
+
+impl RulePickerState {
+  /// Check if the picker is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the position of the selected rule
+  #[cfg(test)]
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  /// Display the picker with the scored rules, the `reason` explains why it opened by itself
+  pub fn open(&mut self, candidates: Vec<RuleCandidate>, active: String, reason: Option<String>) {
+    self.show = true;
+    self.cursor = 0;
+    self.candidates = candidates;
+    self.active = active;
+    self.reason = reason;
+  }
+
+  /// Take the name of the rule picked by the user
+  pub fn take_pick(&mut self) -> Option<String> {
+    self.pick.take()
+  }
+
+  /// Take the name of the rule to open in the [`crate::RuleEditor`]
+  pub fn take_edit(&mut self) -> Option<String> {
+    self.edit.take()
+  }
+}
+
+/// The rules scored against the build output: `Enter` switches to the selected one
+pub struct RulePicker;
+
+impl RulePicker {
+  /// Handle `R` to show the picker, `candidates` scores the rules and names the active one.
+  /// Once displayed, `Up`/`Down` select a rule, `Enter` picks it, `E` edits it and `Esc`/`q` closes.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key<F: FnOnce() -> (Vec<RuleCandidate>, String)>(
+    key: KeyEvent,
+    state: &mut RulePickerState,
+    candidates: F,
+  ) -> bool {
+    if !state.show {
+      if key.code != KeyCode::Char('R') {
+        return false;
+      }
+      let (candidates, active) = candidates();
+      state.open(candidates, active, None);
+      return true;
+    }
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('R') => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.cursor = state.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => {
+        state.cursor = (state.cursor + 1).min(state.candidates.len().saturating_sub(1))
+      }
+      KeyCode::Enter => {
+        if let Some(candidate) = state.candidates.get(state.cursor) {
+          state.pick = Some(candidate.name.clone());
+          state.show = false;
+        }
+      }
+      KeyCode::Char('E') => {
+        if let Some(candidate) = state.candidates.get(state.cursor) {
+          state.edit = Some(candidate.name.clone());
+          state.show = false;
+        }
+      }
+      _ => {}
+    }
+    true
+  }
+}
+
+impl StatefulWidget for RulePicker {
+  type State = RulePickerState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 60, 40);
+    Clear.render(area, buf);
+    let mut lines = vec![];
+    if let Some(reason) = state.reason.as_ref() {
+      lines.push(Line::from(reason.clone()).yellow());
+      lines.push(Line::default());
+    }
+    let name_width = state
+      .candidates
+      .iter()
+      .map(|candidate| candidate.name.len())
+      .max()
+      .unwrap_or_default();
+    lines.extend(state.candidates.iter().enumerate().map(|(i, candidate)| {
+      let line = Line::default().spans(vec![
+        Span::from(match candidate.name == state.active {
+          true => "* ",
+          false => "  ",
+        }),
+        Span::from(format!("{:<width$} ", candidate.name, width = name_width)).bold(),
+        Span::from(format!("{} matched line(s)", candidate.score)).dark_gray(),
+      ]);
+      match i == state.cursor {
+        true => line.style(Style::default().on_light_blue()),
+        false => line,
+      }
+    }));
+    Paragraph::new(lines)
+      .wrap(Wrap { trim: false })
+      .block(
+        Block::bordered()
+          .title("Rules (Enter: switch, E: edit, Esc: close)")
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+  use crate::RuleCandidate;
+
+  use super::{RulePicker, RulePickerState};
+
+  fn press(state: &mut RulePickerState, code: KeyCode) -> bool {
+    RulePicker::handle_key(KeyEvent::from(code), state, || {
+      (
+        ["gcc", "rust: cargo"]
+          .into_iter()
+          .map(|name| RuleCandidate {
+            name: name.to_string(),
+            score: 0,
+          })
+          .collect(),
+        "rust: cargo".to_string(),
+      )
+    })
+  }
+
+  #[test]
+  fn handle_key() {
+    let mut state = RulePickerState::default();
+    assert!(!press(&mut state, KeyCode::Enter));
+    assert!(press(&mut state, KeyCode::Char('R')));
+    assert!(state.is_shown());
+    for code in [KeyCode::Down, KeyCode::Down, KeyCode::Up] {
+      assert!(press(&mut state, code));
+    }
+    assert_eq!(state.cursor(), 0);
+    assert!(press(&mut state, KeyCode::Enter));
+    assert!(!state.is_shown());
+    assert_eq!(state.take_pick(), Some("gcc".to_string()));
+    assert_eq!(state.take_pick(), None);
+    // closing picks nothing
+    assert!(press(&mut state, KeyCode::Char('R')));
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_shown());
+    assert_eq!(state.take_pick(), None);
+    // editing picks nothing either
+    assert!(press(&mut state, KeyCode::Char('R')));
+    assert!(press(&mut state, KeyCode::Down));
+    assert!(press(&mut state, KeyCode::Char('E')));
+    assert!(!state.is_shown());
+    assert_eq!(state.take_pick(), None);
+    assert_eq!(state.take_edit(), Some("rust: cargo".to_string()));
+  }
+}
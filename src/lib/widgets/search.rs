@@ -2,100 +2,285 @@ use std::sync::mpsc::Sender;
 
 use ratatui::{
   crossterm::event::{KeyCode, KeyEvent},
-  text::Text,
+  layout::Rect,
+  style::Style,
+  text::{Line, Span},
   widgets::{StatefulWidget, Widget},
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
   Forward,
   Backward,
 }
 
+impl Direction {
+  /// Retrieve the other direction
+  pub fn reversed(self) -> Self {
+    match self {
+      Self::Forward => Self::Backward,
+      Self::Backward => Self::Forward,
+    }
+  }
+
+  /// Iterate over `0..len` from `start` in this direction, wrapping around once.
+  ///
+  /// A `start` past the end begins with the first index forward, the last one backward
+  pub fn order(self, len: usize, start: usize) -> impl Iterator<Item = usize> {
+    let start = match (start < len, self) {
+      (true, _) => start,
+      (false, Self::Forward) => 0,
+      (false, Self::Backward) => len.saturating_sub(1),
+    };
+    (0..len).map(move |i| match self {
+      Self::Forward => (start + i) % len,
+      Self::Backward => (start + len - i) % len,
+    })
+  }
+}
+
+/// A query submitted to the [`SearchBar`], repeated with `n`/`N`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+  pub query: String,
+  pub direction: Direction,
+}
+
+impl SearchQuery {
+  pub fn new<Q: AsRef<str>>(query: Q, direction: Direction) -> Self {
+    Self {
+      query: query.as_ref().to_string(),
+      direction,
+    }
+  }
+
+  /// Retrieve the same query, searched the other way
+  pub fn reversed(&self) -> Self {
+    Self::new(&self.query, self.direction.reversed())
+  }
+}
+
+/// The edited query of a [`SearchBar`], also used by the [`super::RegexTester`] inputs.
+///
+/// Queries wider than their area scroll horizontally, see [`SearchState::fit`]
 pub struct SearchState {
   prompt: String,
   query: String,
+  /// The byte offset of the cursor in the query, on a char boundary
   cursor: usize,
+  /// The display columns of the query scrolled out on the left
+  offset: usize,
+  direction: Direction,
 }
 
 impl SearchState {
+  /// Construct an empty input displayed after `prompt`
+  pub fn new<P: AsRef<str>>(prompt: P) -> Self {
+    Self {
+      prompt: prompt.as_ref().to_string(),
+      query: String::new(),
+      cursor: 0,
+      offset: 0,
+      direction: Direction::Forward,
+    }
+  }
+
+  /// Define the direction of the search, forward by default
+  pub fn with_direction(mut self, direction: Direction) -> Self {
+    self.direction = direction;
+    self
+  }
+
+  /// Replace the query and move the cursor at its end
+  pub fn with_query<Q: AsRef<str>>(mut self, query: Q) -> Self {
+    self.query = query.as_ref().to_string();
+    self.cursor = self.query.len();
+    self
+  }
+
+  /// Retrieve the prompt
+  pub fn prompt(&self) -> &str {
+    &self.prompt
+  }
+
+  /// Retrieve the current query
+  pub fn query(&self) -> &str {
+    &self.query
+  }
+
+  /// Apply the editing keys (insertion, deletion and cursor moves) to the query
+  pub fn edit(&mut self, key: KeyEvent) -> bool {
+    if key.code == KeyCode::Backspace {
+      self.pop(Direction::Backward);
+    } else if key.code == KeyCode::Delete {
+      self.pop(Direction::Forward);
+    } else if key.code == KeyCode::Left {
+      if let Some(ch) = self.query[..self.cursor].chars().next_back() {
+        self.cursor -= ch.len_utf8();
+      }
+    } else if key.code == KeyCode::Right {
+      if let Some(ch) = self.query[self.cursor..].chars().next() {
+        self.cursor += ch.len_utf8();
+      }
+    } else if key.code == KeyCode::Home {
+      self.cursor = 0;
+    } else if key.code == KeyCode::End {
+      self.cursor = self.query.len();
+    } else if let KeyCode::Char(ch) = key.code {
+      self.push(ch);
+    } else {
+      return false;
+    }
+    true
+  }
+
+  /// Retrieve the display columns of the query scrolled out on the left
+  #[cfg(test)]
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  /// Retrieve the display column of the cursor, from the start of the prompt
   pub fn cursor_position(&self) -> usize {
-    self.prompt.len() + self.cursor
+    (self.prompt.width() + self.query[..self.cursor].width()).saturating_sub(self.offset)
+  }
+
+  /// Retrieve the cursor position in `area`, the one the state was fitted to
+  pub fn cursor_at(&self, area: Rect) -> (u16, u16) {
+    let x = (self.cursor_position() as u16).min(area.width.saturating_sub(1));
+    (area.x + x, area.y)
+  }
+
+  /// Scroll the query so that the cursor stays visible in `width` columns.
+  ///
+  /// A clipped edge displays an ellipsis, the cursor never lands on it
+  pub fn fit(&mut self, width: u16) {
+    let available = (width as usize).saturating_sub(self.prompt.width());
+    // the cursor needs a column after the last char
+    let total = self.query.width() + 1;
+    let cursor = self.query[..self.cursor].width();
+    // a wide char under the cursor stays whole
+    let under = self.query[self.cursor..]
+      .chars()
+      .next()
+      .and_then(|ch| ch.width())
+      .unwrap_or(1)
+      .max(1);
+    if available == 0 || total <= available {
+      self.offset = 0;
+      return;
+    }
+    let mut offset = self.offset.min(total - available);
+    if cursor <= offset && offset > 0 {
+      offset = cursor.saturating_sub(1);
+    }
+    if cursor + under >= offset + available {
+      offset = (cursor + under).saturating_sub(available);
+      // still clipped on the right, the last column is the ellipsis
+      if offset + available < total {
+        offset += 1;
+      }
+    }
+    self.offset = offset.min(total - available);
+  }
+
+  /// Render the prompt and the visible part of the query in `width` columns,
+  /// see [`SearchState::fit`]
+  pub fn line(&self, width: u16, prompt_style: Style) -> Line<'_> {
+    let available = (width as usize).saturating_sub(self.prompt.width());
+    let total = self.query.width() + 1;
+    let clipped_left = self.offset > 0;
+    let clipped_right = self.offset + available < total;
+    let start = self.offset + clipped_left as usize;
+    let end = (self.offset + available).saturating_sub(clipped_right as usize);
+    let mut visible = String::new();
+    let mut column = 0;
+    for ch in self.query.chars() {
+      let ch_width = ch.width().unwrap_or_default();
+      if column >= start && column + ch_width <= end {
+        visible.push(ch);
+      } else if column < end && column + ch_width > start {
+        // a wide char cut by an edge
+        visible.push_str(&" ".repeat(column.max(start).abs_diff((column + ch_width).min(end))));
+      }
+      column += ch_width;
+    }
+    let mut spans = vec![Span::styled(self.prompt.as_str(), prompt_style)];
+    if clipped_left {
+      spans.push(Span::from("…"));
+    }
+    spans.push(Span::from(visible));
+    if clipped_right {
+      spans.push(Span::from("…"));
+    }
+    Line::default().spans(spans)
   }
 
   pub fn pop(&mut self, dir: Direction) -> Option<char> {
     match dir {
       Direction::Backward => {
-        if self.cursor > 0 && !self.query.is_empty() {
-          let ret = Some(self.query.remove(self.cursor - 1));
-          self.cursor -= 1;
-          return ret;
-        }
+        let ch = self.query[..self.cursor].chars().next_back()?;
+        self.cursor -= ch.len_utf8();
+        Some(self.query.remove(self.cursor))
       }
       Direction::Forward => {
-        if self.cursor < self.query.len() {
-          return Some(self.query.remove(self.cursor));
-        }
+        self.query[self.cursor..].chars().next()?;
+        Some(self.query.remove(self.cursor))
       }
     }
-    None
   }
+
   pub fn push(&mut self, ch: char) {
-    if self.cursor != self.query.len() {
-      self.query.insert(self.cursor, ch);
-    } else {
-      self.query.push(ch);
-    }
-    self.cursor += 1;
+    self.query.insert(self.cursor, ch);
+    self.cursor += ch.len_utf8();
   }
 }
 
 pub struct SearchBar;
 
 impl SearchBar {
+  /// Handle the keys of the search: `/` searches forward from the selection, `?` backward.
+  /// Once a query was submitted, `n` repeats the `last` one and `N` reverses it, until `Esc`
+  /// dismisses it and leaves `n` to the notes
   pub fn handle_key(
     key: KeyEvent,
     state: &mut Option<SearchState>,
-    select: Sender<String>,
+    last: &mut Option<SearchQuery>,
+    select: Sender<SearchQuery>,
   ) -> bool {
     if state.is_some() {
       if key.code == KeyCode::Esc {
         *state = None;
-      } else if key.code == KeyCode::Backspace {
-        state.as_mut().unwrap().pop(Direction::Backward);
-      } else if key.code == KeyCode::Delete {
-        state.as_mut().unwrap().pop(Direction::Forward);
-      } else if key.code == KeyCode::Left {
-        let state = state.as_mut().unwrap();
-        state.cursor = state.cursor.saturating_sub(1);
-      } else if key.code == KeyCode::Right {
-        let state = state.as_mut().unwrap();
-        if state.cursor < state.query.len() + state.cursor {
-          state.cursor = state.cursor.saturating_add(1);
-        }
+        *last = None;
       } else if key.code == KeyCode::Enter {
-        let query = state.as_ref().unwrap().query.clone();
-        let _ = select.send(query);
-      } else if let KeyCode::Char(ch) = key.code {
-        state.as_mut().unwrap().push(ch);
+        let state = state.as_ref().unwrap();
+        let _ = select.send(SearchQuery::new(&state.query, state.direction));
+      } else {
+        state.as_mut().unwrap().edit(key);
       }
       return true;
     } else {
-      if key.code == KeyCode::Char('/') {
-        *state = Some(SearchState {
-          query: String::new(),
-          cursor: 0,
-          prompt: String::from("> "),
-        });
-        return true;
+      match (key.code, last.as_ref()) {
+        (KeyCode::Char('/'), _) => *state = Some(SearchState::new("> ")),
+        (KeyCode::Char('?'), _) => {
+          *state = Some(SearchState::new("< ").with_direction(Direction::Backward))
+        }
+        (KeyCode::Char('n'), Some(last)) => {
+          let _ = select.send(last.clone());
+        }
+        (KeyCode::Char('N'), Some(last)) => {
+          let _ = select.send(last.reversed());
+        }
+        // the search results are dismissed, n shows the notes again
+        (KeyCode::Esc, _) => {
+          *last = None;
+          return false;
+        }
+        _ => return false,
       }
+      true
     }
-    return false;
-  }
-
-  pub fn format(state: &Option<SearchState>) -> Option<String> {
-    return state
-      .as_ref()
-      .map(|state| format!("> {}", state.query.as_str()));
   }
 }
 
@@ -108,9 +293,162 @@ impl StatefulWidget for SearchBar {
     buf: &mut ratatui::prelude::Buffer,
     state: &mut Self::State,
   ) {
-    if let Some(text) = Self::format(state) {
-      let text = Text::from(text);
-      text.render(area, buf);
+    if let Some(state) = state.as_mut() {
+      state.fit(area.width);
+      state.line(area.width, Style::default()).render(area, buf);
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    widgets::StatefulWidget,
+  };
+
+  use std::sync::mpsc::channel;
+
+  use super::{Direction, SearchBar, SearchQuery, SearchState};
+
+  fn press(state: &mut SearchState, code: KeyCode, times: usize) {
+    for _ in 0..times {
+      state.edit(KeyEvent::from(code));
+    }
+  }
+
+  fn render(state: SearchState, width: u16) -> (String, u16, SearchState) {
+    let area = Rect::new(0, 0, width, 1);
+    let mut buf = Buffer::empty(area);
+    let mut state = Some(state);
+    SearchBar.render(area, &mut buf, &mut state);
+    let state = state.unwrap();
+    let line = buf.content.iter().map(|cell| cell.symbol()).collect();
+    (line, state.cursor_at(area).0, state)
+  }
+
+  #[test]
+  fn offset() {
+    let query = "abcdefghijklmnopqrst";
+    // short enough
+    let (line, x, state) = render(SearchState::new("> ").with_query("abc"), 12);
+    assert_eq!((line.as_str(), x, state.offset()), ("> abc       ", 5, 0));
+
+    // the cursor at the end shows the tail
+    let (line, x, mut state) = render(SearchState::new("> ").with_query(query), 12);
+    assert_eq!((line.as_str(), x), ("> …mnopqrst ", 11));
+    assert_eq!(state.offset(), 11);
+
+    // moving left keeps the view until the cursor reaches the ellipsis
+    press(&mut state, KeyCode::Left, 8);
+    let (line, x, mut state) = render(state, 12);
+    assert_eq!((line.as_str(), x), ("> …mnopqrst ", 3));
+    press(&mut state, KeyCode::Left, 1);
+    let (line, x, mut state) = render(state, 12);
+    assert_eq!((line.as_str(), x), ("> …lmnopqrs…", 3));
+
+    // the head, clipped on the right
+    press(&mut state, KeyCode::Home, 1);
+    let (line, x, mut state) = render(state, 12);
+    assert_eq!((line.as_str(), x, state.offset()), ("> abcdefghi…", 2, 0));
+    press(&mut state, KeyCode::Right, 8);
+    let (line, x, mut state) = render(state, 12);
+    assert_eq!((line.as_str(), x), ("> abcdefghi…", 10));
+    press(&mut state, KeyCode::Right, 1);
+    let (line, x, _state) = render(state, 12);
+    assert_eq!((line.as_str(), x), ("> …cdefghij…", 10));
+
+    // no room for the query
+    let (_line, x, state) = render(SearchState::new("> ").with_query(query), 2);
+    assert_eq!((x, state.offset()), (1, 0));
+  }
+
+  #[test]
+  fn order() {
+    let order = |direction: Direction, start| direction.order(4, start).collect::<Vec<_>>();
+    assert_eq!(order(Direction::Forward, 2), [2, 3, 0, 1]);
+    assert_eq!(order(Direction::Backward, 2), [2, 1, 0, 3]);
+    // past the end
+    assert_eq!(order(Direction::Forward, 4), [0, 1, 2, 3]);
+    assert_eq!(order(Direction::Backward, usize::MAX), [3, 2, 1, 0]);
+    assert_eq!(Direction::Forward.order(0, 0).count(), 0);
+  }
+
+  #[test]
+  fn repeat() {
+    let (tx, rx) = channel();
+    let mut state = None;
+    let mut last = None;
+    let key = |code| KeyEvent::from(code);
+    // without a previous search, n is left to the notes
+    assert!(!SearchBar::handle_key(
+      key(KeyCode::Char('n')),
+      &mut state,
+      &mut last,
+      tx.clone()
+    ));
+    assert!(SearchBar::handle_key(
+      key(KeyCode::Char('?')),
+      &mut state,
+      &mut last,
+      tx.clone()
+    ));
+    for code in [KeyCode::Char('x'), KeyCode::Enter] {
+      SearchBar::handle_key(key(code), &mut state, &mut last, tx.clone());
+    }
+    last = Some(rx.try_recv().unwrap());
+    assert_eq!(last, Some(SearchQuery::new("x", Direction::Backward)));
+    state = None;
+    for code in [KeyCode::Char('n'), KeyCode::Char('N')] {
+      assert!(SearchBar::handle_key(
+        key(code),
+        &mut state,
+        &mut last,
+        tx.clone()
+      ));
+    }
+    assert_eq!(
+      rx.try_iter().collect::<Vec<_>>(),
+      [
+        SearchQuery::new("x", Direction::Backward),
+        SearchQuery::new("x", Direction::Forward)
+      ]
+    );
+    // dismissed, n is left to the notes again
+    assert!(!SearchBar::handle_key(
+      key(KeyCode::Esc),
+      &mut state,
+      &mut last,
+      tx.clone()
+    ));
+    assert_eq!(last, None);
+    assert!(!SearchBar::handle_key(
+      key(KeyCode::Char('n')),
+      &mut state,
+      &mut last,
+      tx.clone()
+    ));
+  }
+
+  #[test]
+  fn wide_chars() {
+    let mut state = SearchState::new("> ").with_query("日本語のテキスト");
+    assert_eq!(state.cursor_position(), 2 + 16);
+    press(&mut state, KeyCode::Left, 2);
+    press(&mut state, KeyCode::Backspace, 1);
+    assert_eq!(state.query(), "日本語のテスト");
+    press(&mut state, KeyCode::Char('キ'), 1);
+    assert_eq!(state.query(), "日本語のテキスト");
+    // the cursor is on 'ス', kept whole at the right edge
+    let (line, x, mut state) = render(state, 10);
+    assert_eq!(state.offset(), 7);
+    assert_eq!(x, 2 + 12 - 7);
+    assert_eq!(line.replace(' ', ""), ">…テキス…");
+    press(&mut state, KeyCode::Home, 1);
+    let (line, x, _state) = render(state, 10);
+    assert_eq!(x, 2);
+    assert_eq!(line.replace(' ', ""), ">日本語…");
+  }
+}
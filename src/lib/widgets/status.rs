@@ -1,14 +1,26 @@
-use std::{fmt::Display, process::ExitStatus};
+use std::{
+  fmt::Display,
+  fs, io,
+  path::{Path, PathBuf},
+  process::ExitStatus,
+  time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
 
 use ratatui::{
+  layout::Rect,
   style::{Style, Stylize},
   text::{Line, Span},
   widgets::{Paragraph, Widget},
 };
 
-use crate::{BuildEvent};
+use crate::{
+  compact_count, format_count, human_duration, timestamped_line, BuildEvent, BuildTagKind,
+  ExitCause,
+};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct StatusPart([u8; STATUS_MSG_LEN], usize, Style);
 
 impl Default for StatusPart {
@@ -17,6 +29,13 @@ impl Default for StatusPart {
   }
 }
 
+impl StatusPart {
+  /// Retrieve the text of this part
+  pub fn text(&self) -> &str {
+    std::str::from_utf8(&self.0[0..self.1]).unwrap_or_default()
+  }
+}
+
 impl From<(&str, Style)> for StatusPart {
   fn from(value: (&str, Style)) -> Self {
     let mut ret = [0 as u8; STATUS_MSG_LEN];
@@ -55,10 +74,31 @@ impl<'a> Iterator for StatusIter<'a> {
   }
 }
 
+/// The priority of a [`StatusMessage`], see [`StatusBar::push_message_at`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StatusPriority {
+  Low,
+  #[default]
+  Normal,
+  /// Failures, e.g. a search without result
+  High,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct StatusMessage {
   parts: [StatusPart; STATUS_MSG_PARTS],
   len: usize,
+  priority: StatusPriority,
+  ttl: Duration,
+  /// The kind of markers counted by each part, see [`StatusBar::segments`]
+  segments: [Option<BuildTagKind>; STATUS_MSG_PARTS],
+}
+
+impl PartialEq for StatusMessage {
+  /// Compare the displayed parts only
+  fn eq(&self, other: &Self) -> bool {
+    self.parts[0..self.len] == other.parts[0..other.len]
+  }
 }
 
 impl StatusMessage {
@@ -79,7 +119,51 @@ impl StatusMessage {
       }
       len += 1;
     }
-    Self { parts, len }
+    Self {
+      parts,
+      len,
+      ..Default::default()
+    }
+  }
+
+  /// Define the priority, see [`StatusBar::push_message_at`]
+  pub fn with_priority(mut self, priority: StatusPriority) -> Self {
+    self.priority = priority;
+    self
+  }
+
+  /// Define the minimum display duration
+  pub fn with_ttl(mut self, ttl: Duration) -> Self {
+    self.ttl = ttl;
+    self
+  }
+
+  /// Mark the first part displaying `text` as the count of the `kind` markers
+  pub fn with_segment(mut self, text: &str, kind: BuildTagKind) -> Self {
+    if let Some(i) = self.iter().position(|part| part.text() == text) {
+      self.segments[i] = Some(kind);
+    }
+    self
+  }
+
+  /// The kind of markers counted by the part at `index`, if any
+  pub fn segment(&self, index: usize) -> Option<BuildTagKind> {
+    self.segments.get(index).copied().flatten()
+  }
+
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn priority(&self) -> StatusPriority {
+    self.priority
+  }
+
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn ttl(&self) -> Duration {
+    self.ttl
+  }
+
+  /// Retrieve the displayed text, without the styles
+  pub fn text(&self) -> String {
+    self.iter().map(|part| part.text()).collect()
   }
 
   pub fn iter<'a>(&'a self) -> StatusIter<'a> {
@@ -115,6 +199,9 @@ impl Default for StatusMessage {
     Self {
       parts: [StatusPart::default(); STATUS_MSG_PARTS],
       len: 0,
+      priority: Default::default(),
+      ttl: STATUS_MSG_TTL,
+      segments: [None; STATUS_MSG_PARTS],
     }
   }
 }
@@ -139,46 +226,254 @@ impl Display for StatusMessage {
 pub const STATUS_BAR_BUF: usize = 15;
 pub const STATUS_MSG_PARTS: usize = 10;
 pub const STATUS_MSG_LEN: usize = 100;
+/// The default minimum display duration of the messages
+pub const STATUS_MSG_TTL: Duration = Duration::from_secs(3);
+
+/// A message of the [`StatusBar`] history, see [`crate::StatusHistoryList`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatusRecord {
+  id: usize,
+  at: DateTime<Local>,
+  message: StatusMessage,
+  /// The message of a build event, see [`StatusBar::with_event`]
+  event: bool,
+}
+
+impl StatusRecord {
+  /// Retrieve the id of the record, never reused by the bar even once deleted
+  pub fn id(&self) -> usize {
+    self.id
+  }
+
+  /// Retrieve when the message was displayed
+  pub fn at(&self) -> DateTime<Local> {
+    self.at
+  }
+
+  pub fn message(&self) -> &StatusMessage {
+    &self.message
+  }
+
+  /// Check if the message comes from a build event instead of the user's actions
+  pub fn is_event(&self) -> bool {
+    self.event
+  }
+
+  /// Format the record for the exported history: `<date>  build|status <text>`,
+  /// like the lines of the debug log
+  pub fn line(&self) -> String {
+    let source = match self.event {
+      true => "build ",
+      false => "status",
+    };
+    timestamped_line(
+      self.at,
+      &format!("{} {}", source, self.message.text().trim()),
+    )
+  }
+}
 
 /// The status bar widget displays various informations about
 /// the current build status.
+///
+/// Messages are transient: one stays displayed for at least its ttl, unless a message
+/// of the same or a higher priority replaces it. A lower priority message waits for
+/// it to expire, only the latest waiting one is kept. Without message, the
+/// build state and the latest counts are displayed.
 #[derive(Default, Clone, Copy)]
 pub struct StatusBar {
-  messages: [Option<StatusMessage>; STATUS_BAR_BUF],
+  /// The distinct messages, most recent last
+  messages: [Option<StatusRecord>; STATUS_BAR_BUF],
   cursor: usize,
+  /// The id of the next record
+  next_id: usize,
+  build_event: Option<BuildEvent>,
+  /// The displayed message and since when
+  current: Option<(StatusMessage, Instant)>,
+  pending: Option<StatusMessage>,
+  /// The number of builds started so far
+  run: usize,
   num_errors: usize,
   num_warnings: usize,
   num_notes: usize,
+  /// The warnings of the build scripts, see [`crate::BuildScript`]
+  num_script_warnings: usize,
   num_output_lines: usize,
   num_prepared_lines: usize,
+  /// The names of the crates that failed to build, see [`crate::CrateFailure`]
+  failed_crates: Option<StatusPart>,
+  /// The count under the mouse, see [`StatusBar::segment_at`]
+  hovered: Option<BuildTagKind>,
+  /// The time since the build started, until it finished
+  elapsed: Option<Duration>,
+  /// The counts are shortened for the narrow layouts, see [`compact_count`]
+  compact: bool,
 }
 
 impl StatusBar {
   /// Set the build event to be displayed
   pub fn with_event(mut self, evt: BuildEvent) -> Self {
+    if let BuildEvent::BuildStarted = evt {
+      self.run += 1;
+    }
     if let Some(msg) = self.transform(&evt) {
+      self.build_event = Some(evt);
+      self.record(msg, true, Local::now());
+    }
+    self
+  }
+
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn with_message(mut self, msg: StatusMessage) -> Self {
+    self.push_message(msg);
+    self
+  }
+
+  pub fn push_message(&mut self, message: StatusMessage) {
+    self.push_message_at(message, Instant::now());
+  }
+
+  /// Display a message received at `now`, or keep it waiting if the displayed one
+  /// has a higher priority and did not expire yet
+  pub fn push_message_at(&mut self, message: StatusMessage, now: Instant) {
+    self.record(message, false, Local::now());
+    self.tick(now);
+    match self.current.as_ref() {
+      Some((current, _)) if message.priority < current.priority => self.pending = Some(message),
+      _ => {
+        self.current = Some((message, now));
+        // superseded
+        if self
+          .pending
+          .is_some_and(|pending| pending.priority <= message.priority)
+        {
+          self.pending = None;
+        }
+      }
+    }
+  }
+
+  /// Expire the displayed message, showing the waiting one if any
+  pub fn tick(&mut self, now: Instant) {
+    if let Some((current, since)) = self.current {
+      if now.duration_since(since) < current.ttl {
+        return;
+      }
+      self.current = None;
+    }
+    if let Some(pending) = self.pending.take() {
+      self.current = Some((pending, now));
+    }
+  }
+
+  /// Add a message displayed `at` to the history, unless it is the same as the last one
+  fn record(&mut self, message: StatusMessage, event: bool, at: DateTime<Local>) {
+    if self.last_message() == Some(&message) {
+      return;
+    }
+    if self.messages[self.cursor].is_some() {
       if self.cursor < self.messages.len().saturating_sub(1) {
-        self.messages[self.cursor] = Some(msg);
         self.cursor += 1;
       } else {
-        self.messages.rotate_right(1);
-        self.messages[self.cursor] = Some(msg);
+        self.messages.rotate_left(1);
       }
     }
+    self.messages[self.cursor] = Some(StatusRecord {
+      id: self.next_id,
+      at,
+      message,
+      event,
+    });
+    self.next_id += 1;
+  }
+
+  /// Retrieve the distinct messages, most recent last
+  #[cfg(any(test, feature = "test-util"))]
+  pub fn history(&self) -> impl Iterator<Item = &StatusMessage> {
+    self.records().map(|record| &record.message)
+  }
+
+  /// Retrieve the records of the history, most recent last
+  pub fn records(&self) -> impl Iterator<Item = &StatusRecord> {
+    self.messages.iter().flatten()
+  }
+
+  /// Remove the record `id` from the history, the others keep their order and ids.
+  ///
+  /// Returns false if there is no such record
+  pub fn delete(&mut self, id: usize) -> bool {
+    let Some(i) = self
+      .messages
+      .iter()
+      .position(|record| record.is_some_and(|record| record.id == id))
+    else {
+      return false;
+    };
+    self.messages[i] = None;
+    self.messages[i..].rotate_left(1);
+    self.cursor = self.cursor.saturating_sub(1);
+    true
+  }
+
+  /// Format the history, a [`StatusRecord::line`] per record
+  pub fn export(&self) -> String {
     self
+      .records()
+      .map(|record| format!("{}\n", record.line()))
+      .collect()
   }
 
-  pub fn with_message(mut self, msg: StatusMessage) -> Self {
-    self.push_message(msg);
+  /// Write the history [`Self::export`] to `status-<date>.txt` in the directory `dir`,
+  /// e.g. the run directory.
+  ///
+  /// Returns the path of the file
+  pub fn save_history<P: AsRef<Path>>(&self, dir: P, at: DateTime<Local>) -> io::Result<PathBuf> {
+    let path = dir
+      .as_ref()
+      .join(format!("status-{}.txt", at.format("%Y%m%d-%H%M%S")));
+    fs::write(&path, self.export())?;
+    Ok(path)
+  }
+
+  /// Retrieve the message to display
+  pub fn current_message(&self) -> Option<StatusMessage> {
+    match self.current {
+      Some((message, _)) => Some(message),
+      None => self.build_event.and_then(|evt| self.transform(&evt)),
+    }
+  }
+
+  /// Highlight the count of the `kind` markers, e.g. under the mouse
+  pub fn with_hovered(mut self, kind: Option<BuildTagKind>) -> Self {
+    self.hovered = kind;
     self
   }
 
-  pub fn push_message(&mut self, message: StatusMessage) {
-    self.messages.rotate_right(1);
-    self.messages[self.cursor] = Some(message);
-    if self.cursor < self.messages.len().saturating_sub(1) {
-      self.cursor += 1;
+  /// Compute where the counts of the displayed message are rendered in `area`,
+  /// the same way [`Paragraph`] lays out a single line
+  pub fn segments(&self, area: Rect) -> Vec<(BuildTagKind, Rect)> {
+    let Some(msg) = self.current_message() else {
+      return vec![];
+    };
+    let mut ret = vec![];
+    let mut x = area.x;
+    for (i, span) in msg.spans().iter().enumerate() {
+      let end = x.saturating_add(span.width() as u16).min(area.right());
+      if let Some(kind) = msg.segment(i).filter(|_| end > x) {
+        ret.push((kind, Rect::new(x, area.y, end - x, area.height.min(1))));
+      }
+      x = end;
     }
+    ret
+  }
+
+  /// Find the count rendered at the given position, see [`StatusBar::segments`]
+  pub fn segment_at(&self, area: Rect, column: u16, row: u16) -> Option<BuildTagKind> {
+    self
+      .segments(area)
+      .into_iter()
+      .find(|(_, rect)| rect.contains((column, row).into()))
+      .map(|(kind, _)| kind)
   }
 
   /// Set the number of errors
@@ -199,36 +494,153 @@ impl StatusBar {
     self
   }
 
+  /// Set the number of warnings emitted by the build scripts
+  pub fn with_num_script_warnings(mut self, n: usize) -> Self {
+    self.num_script_warnings = n;
+    self
+  }
+
   /// Set the number of output lines
   pub fn with_num_output_lines(mut self, n: usize) -> Self {
     self.num_output_lines = n;
     self
   }
 
+  /// Set the crates that failed to build, e.g. `failed crates: foo, bar`
+  pub fn with_failed_crates<S: AsRef<str>>(mut self, line: Option<S>) -> Self {
+    self.failed_crates = line.map(|line| StatusPart::from((line.as_ref(), Style::default().red())));
+    self
+  }
+
   /// Set the number of processed output lines
   pub fn with_num_prepared_lines(mut self, n: usize) -> Self {
     self.num_prepared_lines = n;
     self
   }
 
+  /// Define the time since the build started, shown while it runs and once finished
+  pub fn with_elapsed(mut self, elapsed: Option<Duration>) -> Self {
+    self.elapsed = elapsed;
+    self
+  }
+
+  /// Shorten the counts, e.g. `12.3k line(s)`, for the narrow layouts
+  pub fn with_compact_counts(mut self, compact: bool) -> Self {
+    self.compact = compact;
+    self
+  }
+
+  /// Format a count, shortened for the narrow layouts
+  fn count(&self, n: usize) -> String {
+    match self.compact {
+      true => compact_count(n),
+      false => format_count(n),
+    }
+  }
+
   fn transform(&self, evt: &BuildEvent) -> Option<StatusMessage> {
     match evt {
-      BuildEvent::BuildError(_) => None,
+      BuildEvent::FirstErrorDetected(_)
+      | BuildEvent::IntakeStats(_)
+      | BuildEvent::BuildProgress(..) => None,
       BuildEvent::BuildFinished(status) => Some(self.transform_build_finished(*status)),
       BuildEvent::BuildStarted => Some(self.transform_build_started()),
+      BuildEvent::BuildFailed(failure) => Some(
+        StatusMessage::new([
+          (" ✗ ".to_string(), Style::default().bold().red()),
+          (failure.to_string(), Style::default()),
+        ])
+        .with_priority(StatusPriority::High),
+      ),
     }
   }
 
+  /// The number of the run, once the build was re-run
+  fn run_part(&self) -> Option<(String, Style)> {
+    (self.run > 1).then(|| (format!(" | run #{}", self.run), Style::default().dim()))
+  }
+
   fn transform_build_started(&self) -> StatusMessage {
-    StatusMessage::new([
-      ("Build ", Style::default()),
-      ("running", Style::default().gray()),
-      ("⌛", Style::default()),
-    ])
+    let mut parts = vec![
+      ("Build ".to_string(), Style::default()),
+      ("running".to_string(), Style::default().gray()),
+      match self.elapsed {
+        Some(elapsed) => (format!("⌛ {}", human_duration(elapsed)), Style::default()),
+        None => ("⌛".to_string(), Style::default()),
+      },
+    ];
+    parts.extend(self.run_part());
+    let mut segments = vec![];
+    for (num, label, style, kind) in [
+      (
+        self.num_errors,
+        "error(s)",
+        Style::default().red(),
+        Some(BuildTagKind::Error),
+      ),
+      (
+        self.num_warnings,
+        "warning(s)",
+        Style::default().yellow(),
+        Some(BuildTagKind::Warning),
+      ),
+      (
+        self.num_script_warnings,
+        "build script warning(s)",
+        Style::default().yellow().dim(),
+        None,
+      ),
+    ] {
+      if num > 0 {
+        let text = format!("{} {}", self.count(num), label);
+        segments.extend(kind.map(|kind| (text.clone(), kind)));
+        parts.push((" | ".to_string(), Style::default()));
+        parts.push((text, style));
+      }
+    }
+    segments
+      .iter()
+      .fold(StatusMessage::new(parts), |msg, (text, kind)| {
+        msg.with_segment(text, *kind)
+      })
   }
 
   fn transform_build_finished(&self, exit: ExitStatus) -> StatusMessage {
-    StatusMessage::new([
+    let errors = match self.num_errors {
+      0 => ("no errors".to_string(), Style::default().dim()),
+      _ => (
+        format!("{} error(s)", self.count(self.num_errors)),
+        Style::default().red(),
+      ),
+    };
+    let warnings = match (self.num_warnings, self.num_script_warnings) {
+      (0, 0) => ("no warnings".to_string(), Style::default().dim()),
+      (_, 0) => (
+        format!("{} warning(s)", self.count(self.num_warnings)),
+        Style::default().yellow(),
+      ),
+      // counted apart from the warnings, in the same part to stay among the kept ones
+      _ => (
+        format!(
+          "{} warning(s), {} from build scripts",
+          self.count(self.num_warnings),
+          self.count(self.num_script_warnings)
+        ),
+        Style::default().yellow(),
+      ),
+    };
+    let notes = match self.num_notes {
+      0 => ("no notes".to_string(), Style::default().dim()),
+      _ => (
+        format!("{} notes(s)", self.count(self.num_notes)),
+        Style::default().blue(),
+      ),
+    };
+    let took = match self.elapsed {
+      Some(elapsed) => format!(", took {}", human_duration(elapsed)),
+      None => String::new(),
+    };
+    let mut parts = vec![
       ("Build ".to_string(), Style::default()),
       ("finished".to_string(), Style::default().bold()),
       match exit.success() {
@@ -236,58 +648,61 @@ impl StatusBar {
         false => (" ✗".to_string(), Style::default().bold().red()),
       },
       (" | ".to_string(), Style::default()),
-      match exit.success() {
-        true => (format!("{}", exit), Style::default().dim()),
-        false => (format!("{}", exit), Style::default()),
-      },
-      (" | ".to_string(), Style::default()),
-      match self.num_errors {
-        0 => ("no errors".to_string(), Style::default().dim()),
-        _ => (
-          format!("{} error(s)", self.num_errors),
-          Style::default().red(),
+      match (exit.success(), ExitCause::from_status(exit)) {
+        (true, _) => (format!("{}{}", exit, took), Style::default().dim()),
+        // killed, unlike a build failing by itself
+        (false, Some(cause)) => (
+          format!("build process {}{}", cause.explain(), took),
+          Style::default().bold().magenta(),
         ),
+        (false, None) => (format!("{}{}", exit, took), Style::default()),
       },
       (" | ".to_string(), Style::default()),
-      match self.num_warnings {
-        0 => ("no warnings".to_string(), Style::default().dim()),
-        _ => (
-          format!("{} warning(s)", self.num_warnings),
-          Style::default().yellow(),
-        ),
-      },
+      errors.clone(),
       (" | ".to_string(), Style::default()),
-      match self.num_notes {
-        0 => ("no notes".to_string(), Style::default().dim()),
-        _ => (
-          format!("{} notes(s)", self.num_notes),
-          Style::default().blue(),
-        ),
-      },
+      warnings.clone(),
+      (" | ".to_string(), Style::default()),
+      notes.clone(),
       (" | ".to_string(), Style::default()),
       match self.num_prepared_lines == self.num_output_lines {
         true => (
-          format!("{} line(s)", self.num_output_lines),
+          format!("{} line(s)", self.count(self.num_output_lines)),
           Style::default().dim(),
         ),
         false => (
           format!(
             "{}/{} line(s) prepared",
-            self.num_prepared_lines, self.num_output_lines
+            self.count(self.num_prepared_lines),
+            self.count(self.num_output_lines)
           ),
           Style::default(),
         ),
       },
-    ])
+    ];
+    // before the counts, only the first parts of a message are kept
+    if let Some(failed_crates) = self.failed_crates.filter(|_| !exit.success()) {
+      parts.insert(5, (" | ".to_string(), Style::default()));
+      parts.insert(6, (failed_crates.text().to_string(), failed_crates.2));
+    }
+    if let Some(run) = self.run_part() {
+      parts.insert(3, run);
+    }
+    [
+      (self.num_errors, errors, BuildTagKind::Error),
+      (self.num_warnings, warnings, BuildTagKind::Warning),
+      (self.num_notes, notes, BuildTagKind::Note),
+    ]
+    .into_iter()
+    .filter(|(num, _, _)| *num > 0)
+    .fold(StatusMessage::new(parts), |msg, (_, (text, _), kind)| {
+      msg.with_segment(&text, kind)
+    })
   }
 
   pub fn last_message(&self) -> Option<&StatusMessage> {
-    for i in (0..=self.cursor).into_iter().rev() {
-      if self.messages[i].is_some() {
-        return self.messages[i].as_ref();
-      }
-    }
-    return None;
+    self.messages[self.cursor]
+      .as_ref()
+      .map(|record| &record.message)
   }
 }
 
@@ -296,9 +711,292 @@ impl Widget for StatusBar {
   where
     Self: Sized,
   {
-    if let Some(msg) = self.last_message() {
-      let para = Paragraph::new(Line::default().spans(msg.spans()));
+    if let Some(msg) = self.current_message() {
+      let spans = msg.spans().into_iter().enumerate().map(|(i, span)| {
+        match msg.segment(i).is_some() && msg.segment(i) == self.hovered {
+          true => span.underlined().bold(),
+          false => span,
+        }
+      });
+      let para = Paragraph::new(Line::default().spans(spans));
       para.render(area, buf);
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs,
+    process::ExitStatus,
+    time::{Duration, Instant},
+  };
+
+  use chrono::{Local, TimeZone};
+  use ratatui::{layout::Rect, style::Style};
+
+  use crate::{BuildEvent, BuildTagKind};
+
+  use super::{StatusBar, StatusMessage, StatusPriority, STATUS_BAR_BUF};
+
+  fn message(text: &str, priority: StatusPriority) -> StatusMessage {
+    StatusMessage::new([(text, Style::default())]).with_priority(priority)
+  }
+
+  fn text(bar: &StatusBar) -> String {
+    bar
+      .current_message()
+      .map(|msg| {
+        msg
+          .spans()
+          .iter()
+          .map(|span| span.content.to_string())
+          .collect::<String>()
+      })
+      .unwrap_or_default()
+  }
+
+  #[test]
+  fn replacement() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut bar = StatusBar::default().with_event(BuildEvent::BuildStarted);
+    assert_eq!(text(&bar), "Build running⌛");
+    bar.push_message_at(message("not found", StatusPriority::High), at(0));
+    // lower priority: waits for the error to expire, the latest one wins
+    bar.push_message_at(message("result 1", StatusPriority::Normal), at(100));
+    bar.push_message_at(message("result 2", StatusPriority::Normal), at(200));
+    bar.tick(at(2900));
+    assert_eq!(text(&bar), "not found");
+    bar.tick(at(3000));
+    assert_eq!(text(&bar), "result 2");
+    // same priority: replaced right away
+    bar.push_message_at(message("saved", StatusPriority::Normal), at(3100));
+    assert_eq!(text(&bar), "saved");
+    // higher priority: replaced right away, dropping the waiting one
+    bar.push_message_at(message("low", StatusPriority::Low), at(3200));
+    bar.push_message_at(message("failed", StatusPriority::High), at(3300));
+    bar.tick(at(6300));
+    assert_eq!(text(&bar), "Build running⌛");
+  }
+
+  #[test]
+  fn counts() {
+    let mut bar = StatusBar::default().with_event(BuildEvent::BuildStarted);
+    bar = bar.with_num_errors(2).with_num_warnings(1);
+    assert_eq!(text(&bar), "Build running⌛ | 2 error(s) | 1 warning(s)");
+    bar = bar.with_num_errors(3);
+    assert_eq!(text(&bar), "Build running⌛ | 3 error(s) | 1 warning(s)");
+    bar = bar.with_num_script_warnings(2);
+    assert_eq!(
+      text(&bar),
+      "Build running⌛ | 3 error(s) | 1 warning(s) | 2 build script warning(s)"
+    );
+    // the count updates are not messages
+    assert_eq!(bar.history().count(), 1);
+    bar = bar.with_event(BuildEvent::BuildFinished(ExitStatus::default()));
+    assert!(text(&bar).contains(" | 3 error(s) | 1 warning(s), 2 from build scripts"));
+  }
+
+  #[test]
+  fn elapsed_and_compact_counts() {
+    let mut bar = StatusBar::default()
+      .with_event(BuildEvent::BuildStarted)
+      .with_elapsed(Some(Duration::from_millis(45_234)))
+      .with_num_errors(12_345);
+    assert_eq!(text(&bar), "Build running⌛ 45.2s | 12,345 error(s)");
+    bar = bar.with_compact_counts(true);
+    assert_eq!(text(&bar), "Build running⌛ 45.2s | 12.3k error(s)");
+    bar = bar
+      .with_compact_counts(false)
+      .with_elapsed(Some(Duration::from_secs(123)))
+      .with_event(BuildEvent::BuildFinished(ExitStatus::default()));
+    assert!(
+      text(&bar).contains("exit status: 0, took 2m 03s | 12,345 error(s)"),
+      "{}",
+      text(&bar)
+    );
+  }
+
+  #[test]
+  fn segments() {
+    let bar = StatusBar::default()
+      .with_event(BuildEvent::BuildStarted)
+      .with_num_errors(2)
+      .with_num_warnings(1);
+    // "Build running⌛ | 2 error(s) | 1 warning(s)", the hourglass is 2 columns wide
+    assert_eq!(
+      bar.segments(Rect::new(0, 5, 80, 1)),
+      vec![
+        (BuildTagKind::Error, Rect::new(18, 5, 10, 1)),
+        (BuildTagKind::Warning, Rect::new(31, 5, 12, 1)),
+      ]
+    );
+    // clipped like the rendered line
+    assert_eq!(
+      bar.segments(Rect::new(0, 5, 20, 1)),
+      vec![(BuildTagKind::Error, Rect::new(18, 5, 2, 1))]
+    );
+    let area = Rect::new(2, 5, 35, 1);
+    assert_eq!(bar.segment_at(area, 20, 5), Some(BuildTagKind::Error));
+    assert_eq!(bar.segment_at(area, 36, 5), Some(BuildTagKind::Warning));
+    assert_eq!(bar.segment_at(area, 37, 5), None);
+    assert_eq!(bar.segment_at(area, 31, 5), None);
+    assert_eq!(bar.segment_at(area, 20, 4), None);
+    // "no errors" is not a segment
+    let finished = bar
+      .with_num_errors(0)
+      .with_event(BuildEvent::BuildFinished(ExitStatus::default()));
+    let kinds = finished
+      .segments(Rect::new(0, 0, 200, 1))
+      .into_iter()
+      .map(|(kind, _)| kind)
+      .collect::<Vec<_>>();
+    assert_eq!(kinds, vec![BuildTagKind::Warning]);
+  }
+
+  #[test]
+  fn run_number() {
+    let mut bar = StatusBar::default().with_event(BuildEvent::BuildStarted);
+    assert_eq!(text(&bar), "Build running⌛");
+    bar = bar.with_event(BuildEvent::BuildStarted);
+    assert_eq!(text(&bar), "Build running⌛ | run #2");
+    bar = bar.with_num_errors(1);
+    assert_eq!(text(&bar), "Build running⌛ | run #2 | 1 error(s)");
+    bar = bar.with_event(BuildEvent::BuildFinished(ExitStatus::default()));
+    assert!(text(&bar).starts_with("Build finished ✓ | run #2 | "));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn failed_crates() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let bar = StatusBar::default()
+      .with_failed_crates(Some("failed crates: foo, bar"))
+      .with_event(BuildEvent::BuildStarted);
+    assert_eq!(text(&bar), "Build running⌛");
+    let failed = bar.with_event(BuildEvent::BuildFinished(ExitStatus::from_raw(101 << 8)));
+    assert!(
+      text(&failed).starts_with("Build finished ✗ | exit status: 101 | failed crates: foo, bar | ")
+    );
+    let succeeded = bar.with_event(BuildEvent::BuildFinished(ExitStatus::default()));
+    assert!(!text(&succeeded).contains("failed crates"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn killed() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let bar = StatusBar::default().with_event(BuildEvent::BuildFinished(ExitStatus::from_raw(
+      libc::SIGKILL,
+    )));
+    assert!(text(&bar)
+      .starts_with("Build finished ✗ | build process killed by SIGKILL — possibly out of memory"));
+  }
+
+  #[test]
+  fn delete() {
+    let mut bar = StatusBar::default();
+    for text in ["a", "b", "c"] {
+      bar.push_message(message(text, StatusPriority::Normal));
+    }
+    let ids = bar.records().map(|record| record.id()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![0, 1, 2]);
+    assert!(bar.delete(1));
+    assert!(!bar.delete(1));
+    let texts = |bar: &StatusBar| bar.history().map(|msg| msg.text()).collect::<Vec<_>>();
+    assert_eq!(texts(&bar), ["a", "c"]);
+    // the ids stay stable, the next record gets a new one
+    bar.push_message(message("d", StatusPriority::Normal));
+    assert_eq!(
+      bar.records().map(|record| record.id()).collect::<Vec<_>>(),
+      vec![0, 2, 3]
+    );
+    // the last message is deleted, the same text gets recorded again
+    assert!(bar.delete(3));
+    assert_eq!(
+      bar.last_message().map(|msg| msg.text()),
+      Some("c".to_string())
+    );
+    bar.push_message(message("d", StatusPriority::Normal));
+    assert_eq!(texts(&bar), ["a", "c", "d"]);
+    for id in [0, 2, 4] {
+      assert!(bar.delete(id));
+    }
+    assert_eq!(bar.history().count(), 0);
+    assert_eq!(bar.last_message(), None);
+    // a full history after deleting
+    for i in 0..STATUS_BAR_BUF + 1 {
+      bar.push_message(message(&i.to_string(), StatusPriority::Normal));
+    }
+    // "0" was dropped, the ids 6 to 20 are kept
+    assert!(bar.delete(10));
+    bar.push_message(message("last", StatusPriority::Normal));
+    assert_eq!(bar.history().count(), STATUS_BAR_BUF);
+    assert_eq!(texts(&bar)[0], "1");
+    assert!(!texts(&bar).contains(&"5".to_string()));
+    assert_eq!(texts(&bar).last().map(String::as_str), Some("last"));
+  }
+
+  #[test]
+  fn export() {
+    let at = |secs| Local.with_ymd_and_hms(2024, 3, 1, 12, 30, secs).unwrap();
+    let mut bar = StatusBar::default();
+    bar.record(
+      StatusMessage::new([("Build ", Style::default()), ("running", Style::default())]),
+      true,
+      at(0),
+    );
+    bar.record(
+      message("No result for \"foo\"", StatusPriority::High),
+      false,
+      at(4),
+    );
+    bar.record(
+      message("Build finished ✗", StatusPriority::Normal),
+      true,
+      at(9),
+    );
+    bar.record(
+      message("Copied target/debug/demo", StatusPriority::Normal),
+      false,
+      at(12),
+    );
+    assert!(bar.delete(3));
+    let expected = "2024-03-01 12:30:00  build  Build running\n\
+      2024-03-01 12:30:04  status No result for \"foo\"\n\
+      2024-03-01 12:30:09  build  Build finished ✗\n";
+    assert_eq!(bar.export(), expected);
+
+    let dir = std::env::temp_dir().join(format!("nbuild-status-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = bar.save_history(&dir, at(30)).unwrap();
+    assert_eq!(path, dir.join("status-20240301-123030.txt"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn distinct_history() {
+    let mut bar = StatusBar::default();
+    for _ in 0..3 {
+      bar.push_message(message("a", StatusPriority::Normal));
+    }
+    bar.push_message(message("b", StatusPriority::Normal));
+    bar.push_message(message("a", StatusPriority::Normal));
+    assert_eq!(bar.history().count(), 3);
+    for i in 0..STATUS_BAR_BUF * 2 {
+      bar.push_message(message(&i.to_string(), StatusPriority::Normal));
+    }
+    assert_eq!(bar.history().count(), STATUS_BAR_BUF);
+    assert_eq!(
+      bar.last_message(),
+      Some(&message(
+        &(STATUS_BAR_BUF * 2 - 1).to_string(),
+        StatusPriority::Low
+      ))
+    );
+  }
+}
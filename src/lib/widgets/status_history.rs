@@ -0,0 +1,211 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::StatusRecord;
+
+use super::help::popup_area;
+
+/// What to do with the status history, applied by the renderer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusHistoryAction {
+  /// Remove the record of this id, see [`crate::StatusBar::delete`]
+  Delete(usize),
+  /// Write the history to the run directory, see [`crate::StatusBar::save_history`]
+  Save,
+}
+
+/// The state of the [`StatusHistoryList`]: the selected record and the pending action
+#[derive(Debug, Clone, Default)]
+pub struct StatusHistoryState {
+  show: bool,
+  cursor: usize,
+  action: Option<StatusHistoryAction>,
+}
+
+impl StatusHistoryState {
+  /// Check if the list is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the position of the selected record
+  #[cfg(test)]
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  /// Take the action chosen by the user
+  pub fn take_action(&mut self) -> Option<StatusHistoryAction> {
+    self.action.take()
+  }
+}
+
+/// The status messages and the build events, oldest first: `d` deletes the selected one
+/// and `s` saves them, e.g. to attach them to a bug report
+pub struct StatusHistoryList<'a> {
+  records: &'a [StatusRecord],
+}
+
+impl<'a> StatusHistoryList<'a> {
+  /// Construct this object from the records of the status bar
+  pub fn new(records: &'a [StatusRecord]) -> Self {
+    Self { records }
+  }
+
+  /// Handle `S` to show/hide the list, the most recent record selected. Once displayed,
+  /// `Up`/`Down` select a record, `d` deletes it, `s` saves the history and `Esc`/`q` closes.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(
+    key: KeyEvent,
+    state: &mut StatusHistoryState,
+    records: &[StatusRecord],
+  ) -> bool {
+    let last = records.len().saturating_sub(1);
+    if !state.show {
+      if key.code != KeyCode::Char('S') {
+        return false;
+      }
+      state.show = true;
+      state.cursor = last;
+      return true;
+    }
+    match key.code {
+      KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('S') => state.show = false,
+      KeyCode::Up | KeyCode::Char('k') => state.cursor = state.cursor.saturating_sub(1),
+      KeyCode::Down | KeyCode::Char('j') => state.cursor = (state.cursor + 1).min(last),
+      KeyCode::Char('d') => {
+        if let Some(record) = records.get(state.cursor) {
+          state.action = Some(StatusHistoryAction::Delete(record.id()));
+          // the next record takes its place, or the previous one for the last
+          state.cursor = state.cursor.min(last.saturating_sub(1));
+        }
+      }
+      KeyCode::Char('s') if !records.is_empty() => state.action = Some(StatusHistoryAction::Save),
+      _ => {}
+    }
+    true
+  }
+}
+
+impl<'a> StatefulWidget for StatusHistoryList<'a> {
+  type State = StatusHistoryState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 80, 50);
+    Clear.render(area, buf);
+    let num_rows = area.height.saturating_sub(2) as usize;
+    let first = (state.cursor + 1).saturating_sub(num_rows);
+    let lines = match self.records.is_empty() {
+      true => vec![Line::from("no status message").dark_gray()],
+      false => self
+        .records
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(num_rows)
+        .map(|(i, record)| {
+          let mut spans = vec![
+            Span::from(format!("{} ", record.at().format("%H:%M:%S"))).dark_gray(),
+            match record.is_event() {
+              true => Span::from("build  ").cyan(),
+              false => Span::from("status ").dark_gray(),
+            },
+          ];
+          spans.extend(record.message().spans());
+          let line = Line::default().spans(spans);
+          match i == state.cursor {
+            true => line.style(Style::default().on_light_blue()),
+            false => line,
+          }
+        })
+        .collect::<Vec<_>>(),
+    };
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title(format!(
+            "Status history ({}, d: delete, s: save to the run directory, Esc: close)",
+            self.records.len()
+          ))
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    style::Style,
+  };
+
+  use crate::{StatusBar, StatusMessage, StatusRecord};
+
+  use super::{StatusHistoryAction, StatusHistoryList, StatusHistoryState};
+
+  fn records(bar: &StatusBar) -> Vec<StatusRecord> {
+    bar.records().copied().collect()
+  }
+
+  #[test]
+  fn handle_key() {
+    let mut bar = StatusBar::default();
+    for text in ["a", "b", "c"] {
+      bar.push_message(StatusMessage::new([(text, Style::default())]));
+    }
+    let mut state = StatusHistoryState::default();
+    let press = |state: &mut StatusHistoryState, bar: &StatusBar, code| {
+      StatusHistoryList::handle_key(KeyEvent::from(code), state, &records(bar))
+    };
+    assert!(!press(&mut state, &bar, KeyCode::Char('d')));
+    assert!(press(&mut state, &bar, KeyCode::Char('S')));
+    assert!(state.is_shown());
+    assert_eq!(state.cursor(), 2);
+    // delete "b"
+    press(&mut state, &bar, KeyCode::Up);
+    assert!(press(&mut state, &bar, KeyCode::Char('d')));
+    let Some(StatusHistoryAction::Delete(id)) = state.take_action() else {
+      panic!("no delete action");
+    };
+    assert!(bar.delete(id));
+    assert_eq!(state.cursor(), 1);
+    assert_eq!(
+      bar
+        .history()
+        .map(|message| message.text())
+        .collect::<Vec<_>>(),
+      ["a", "c"]
+    );
+    // delete the last one, the previous one gets selected
+    press(&mut state, &bar, KeyCode::Char('d'));
+    assert!(matches!(
+      state.take_action(),
+      Some(StatusHistoryAction::Delete(_))
+    ));
+    assert_eq!(state.cursor(), 0);
+    assert!(press(&mut state, &bar, KeyCode::Char('s')));
+    assert_eq!(state.take_action(), Some(StatusHistoryAction::Save));
+    assert!(press(&mut state, &bar, KeyCode::Esc));
+    assert!(!state.is_shown());
+    // nothing to act on
+    let mut state = StatusHistoryState::default();
+    let empty = StatusBar::default();
+    press(&mut state, &empty, KeyCode::Char('S'));
+    press(&mut state, &empty, KeyCode::Char('d'));
+    press(&mut state, &empty, KeyCode::Char('s'));
+    assert_eq!(state.take_action(), None);
+  }
+}
@@ -0,0 +1,276 @@
+use std::{ops::Range, sync::mpsc::Sender};
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+  layout::{Alignment, Constraint, Layout},
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+use regex::Regex;
+
+use crate::BuildTagKind;
+
+use super::{help::popup_area, SearchState};
+
+/// The marker kinds that can be appended from the [`RegexTester`]
+const KINDS: &[BuildTagKind] = &[
+  BuildTagKind::Error,
+  BuildTagKind::Warning,
+  BuildTagKind::Note,
+  BuildTagKind::Hidden,
+];
+
+/// The requests emitted by the [`RegexTester`], handled by the renderer
+#[derive(Debug, Clone)]
+pub enum TesterAction {
+  /// Append a new marker to the active rule and prepare the output again
+  AddMarker(BuildTagKind, Regex),
+  /// Save the modified rule set
+  SaveRules,
+}
+
+/// The input owning the focus inside the [`RegexTester`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TesterField {
+  #[default]
+  Regex,
+  Sample,
+}
+
+/// The state of the [`RegexTester`] popup
+pub struct TesterState {
+  show: bool,
+  focus: TesterField,
+  regex: SearchState,
+  sample: SearchState,
+  kind: usize,
+  cursor_position: Option<(u16, u16)>,
+}
+
+impl Default for TesterState {
+  fn default() -> Self {
+    Self {
+      show: false,
+      focus: TesterField::default(),
+      regex: SearchState::new("regex:  "),
+      sample: SearchState::new("sample: "),
+      kind: 0,
+      cursor_position: None,
+    }
+  }
+}
+
+impl TesterState {
+  /// Check if the popup is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the marker kind that would be appended
+  pub fn kind(&self) -> BuildTagKind {
+    KINDS[self.kind]
+  }
+
+  /// Retrieve the terminal cursor position, computed during the last render
+  pub fn cursor_position(&self) -> Option<(u16, u16)> {
+    self.cursor_position
+  }
+
+  /// Compile the tested regex
+  pub fn compile(&self) -> Result<Regex, regex::Error> {
+    Regex::new(self.regex.query())
+  }
+}
+
+/// The popup used to author marker regexes: it tests a regex against
+/// a sample line, highlighting the matched range and the named capture groups
+pub struct RegexTester;
+
+impl RegexTester {
+  pub fn handle_key(
+    key: KeyEvent,
+    state: &mut TesterState,
+    sample: Option<&String>,
+    action: Sender<TesterAction>,
+  ) -> bool {
+    if key.code == KeyCode::F(2) {
+      state.show = !state.show;
+      if state.show {
+        state.focus = TesterField::Regex;
+        if let Some(sample) = sample {
+          state.sample = SearchState::new(state.sample.prompt()).with_query(sample);
+        }
+      }
+      return true;
+    }
+    if !state.show {
+      return false;
+    }
+    if key.code == KeyCode::Esc {
+      state.show = false;
+    } else if key.code == KeyCode::Tab {
+      state.focus = match state.focus {
+        TesterField::Regex => TesterField::Sample,
+        TesterField::Sample => TesterField::Regex,
+      };
+    } else if key.code == KeyCode::Up {
+      state.kind = state.kind.checked_sub(1).unwrap_or(KINDS.len() - 1);
+    } else if key.code == KeyCode::Down {
+      state.kind = (state.kind + 1) % KINDS.len();
+    } else if key.code == KeyCode::Enter {
+      match state.compile() {
+        Ok(regex) if !regex.as_str().is_empty() => {
+          let _ = action.send(TesterAction::AddMarker(state.kind(), regex));
+        }
+        _ => crate::dbg!("Cannot add marker {:?}", state.regex.query()),
+      }
+    } else if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
+      let _ = action.send(TesterAction::SaveRules);
+    } else {
+      match state.focus {
+        TesterField::Regex => state.regex.edit(key),
+        TesterField::Sample => state.sample.edit(key),
+      };
+    }
+    true
+  }
+
+  /// Split the sample into spans, highlighting the match and its named groups
+  fn highlight<'a>(
+    sample: &'a str,
+    matched: Option<&Range<usize>>,
+    groups: &[(String, Range<usize>)],
+  ) -> Vec<Span<'a>> {
+    let style_at = |pos: usize| {
+      if groups.iter().any(|(_, range)| range.contains(&pos)) {
+        Style::default().black().on_magenta()
+      } else if matched.is_some_and(|range| range.contains(&pos)) {
+        Style::default().black().on_yellow()
+      } else {
+        Style::default()
+      }
+    };
+    let mut spans = vec![];
+    let mut start = 0;
+    for (pos, _) in sample.char_indices().skip(1) {
+      if style_at(pos) != style_at(start) {
+        spans.push(Span::styled(&sample[start..pos], style_at(start)));
+        start = pos;
+      }
+    }
+    if start < sample.len() {
+      spans.push(Span::styled(&sample[start..], style_at(start)));
+    }
+    spans
+  }
+}
+
+impl StatefulWidget for RegexTester {
+  type State = TesterState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 70, 50);
+    Clear.render(area, buf);
+    let block = Block::bordered()
+      .title("Regex tester (F2)")
+      .title_alignment(Alignment::Center);
+    let inner = block.inner(area);
+    block.on_black().render(area, buf);
+    let [regex_area, sample_area, result_area, help_area] = Layout::vertical([
+      Constraint::Length(1),
+      Constraint::Length(1),
+      Constraint::Fill(1),
+      Constraint::Length(1),
+    ])
+    .areas(inner);
+
+    let input_style = |field: TesterField| match state.focus == field {
+      true => Style::default().white().bold(),
+      false => Style::default().gray(),
+    };
+    state.regex.fit(regex_area.width);
+    state
+      .regex
+      .line(regex_area.width, input_style(TesterField::Regex))
+      .render(regex_area, buf);
+    state.sample.fit(sample_area.width);
+    state
+      .sample
+      .line(sample_area.width, input_style(TesterField::Sample))
+      .render(sample_area, buf);
+
+    let sample = state.sample.query();
+    let mut lines = vec![Line::default()];
+    match state.compile() {
+      Err(e) => {
+        lines.push(
+          Line::default().spans([Span::from(" ✗ ").bold().red(), Span::from("invalid regex")]),
+        );
+        lines.extend(
+          e.to_string()
+            .lines()
+            .map(|line| Line::from(format!("   {}", line)).dim()),
+        );
+      }
+      Ok(regex) => {
+        lines
+          .push(Line::default().spans([Span::from(" ✓ ").bold().green(), Span::from("compiles")]));
+        match regex.captures(sample) {
+          None => {
+            lines.push(
+              Line::default().spans([Span::from(" ✗ ").bold().red(), Span::from("no match")]),
+            );
+            lines.push(Line::default().spans(Self::highlight(sample, None, &[])));
+          }
+          Some(captures) => {
+            let matched = captures.get(0).unwrap().range();
+            let groups = regex
+              .capture_names()
+              .flatten()
+              .filter_map(|name| {
+                captures
+                  .name(name)
+                  .map(|group| (name.to_string(), group.range()))
+              })
+              .collect::<Vec<_>>();
+            lines.push(Line::default().spans([
+              Span::from(" ✓ ").bold().green(),
+              Span::from(format!("matches {}..{}", matched.start, matched.end)),
+            ]));
+            lines.push(Line::default().spans(Self::highlight(sample, Some(&matched), &groups)));
+            for (name, range) in &groups {
+              lines.push(Line::default().spans([
+                Span::from(format!("   {} = ", name)).magenta(),
+                Span::from(format!("{:?}", &sample[range.clone()])),
+                Span::from(format!(" ({}..{})", range.start, range.end)).dim(),
+              ]));
+            }
+          }
+        }
+      }
+    }
+    lines.push(Line::default());
+    lines.push(Line::default().spans([
+      Span::from("kind: "),
+      Span::from(format!("< {} >", state.kind())).bold(),
+    ]));
+    Paragraph::new(lines).render(result_area, buf);
+    Line::from(
+      "Tab: switch field | Up/Down: kind | Enter: add marker | Ctrl+S: save rules | Esc: close",
+    )
+    .dim()
+    .render(help_area, buf);
+
+    let (input_area, input) = match state.focus {
+      TesterField::Regex => (regex_area, &state.regex),
+      TesterField::Sample => (sample_area, &state.sample),
+    };
+    state.cursor_position = Some(input.cursor_at(input_area));
+  }
+}
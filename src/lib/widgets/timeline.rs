@@ -0,0 +1,195 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  style::{Style, Stylize},
+  text::Span,
+  widgets::{StatefulWidget, Widget},
+};
+
+use crate::{human_duration, BuildTagKind};
+
+/// The state of the [`Timeline`]: shown or not, and the time cursor
+#[derive(Debug, Clone, Default)]
+pub struct TimelineState {
+  show: bool,
+  /// The offset from the build start
+  cursor: Duration,
+}
+
+impl TimelineState {
+  /// The number of `Left`/`Right` presses to cross the whole timeline
+  pub const STEPS: u32 = 50;
+
+  /// The smallest move of the cursor
+  pub const MIN_STEP: Duration = Duration::from_millis(100);
+
+  /// Check if the timeline is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+
+  /// Retrieve the offset of the cursor from the build start
+  #[cfg(test)]
+  pub fn cursor(&self) -> Duration {
+    self.cursor
+  }
+
+  /// Retrieve the instant the log is clamped to, `None` for the live view
+  pub fn at(&self, started_at: Instant) -> Option<Instant> {
+    match self.show {
+      true => Some(started_at + self.cursor),
+      false => None,
+    }
+  }
+
+  /// Handle `t` to show the timeline with the cursor on `elapsed`, `Left`/`Right`
+  /// to move the cursor, `Home`/`End` to jump to the bounds and `Esc` to go back live.
+  ///
+  /// Returns true if the key was handled
+  pub fn handle_key(key: KeyEvent, state: &mut TimelineState, elapsed: Duration) -> bool {
+    if key.code == KeyCode::Char('t') {
+      state.show = !state.show;
+      state.cursor = elapsed;
+      return true;
+    }
+    if !state.show {
+      return false;
+    }
+    let step = (elapsed / Self::STEPS).max(Self::MIN_STEP);
+    match key.code {
+      KeyCode::Esc => state.show = false,
+      KeyCode::Left => state.cursor = state.cursor.saturating_sub(step),
+      KeyCode::Right => state.cursor = (state.cursor + step).min(elapsed),
+      KeyCode::Home => state.cursor = Duration::ZERO,
+      KeyCode::End => state.cursor = elapsed,
+      _ => return false,
+    }
+    true
+  }
+}
+
+/// A one row bar spanning the build duration, with the markers plotted at their
+/// creation time and the time cursor of the [`TimelineState`]
+pub struct Timeline {
+  elapsed: Duration,
+  markers: Vec<(Duration, BuildTagKind, Style)>,
+}
+
+impl Timeline {
+  /// Construct an empty timeline lasting `elapsed`
+  pub fn new(elapsed: Duration) -> Self {
+    Self {
+      elapsed,
+      markers: vec![],
+    }
+  }
+
+  /// Plot markers as `(offset, kind, style)` tuples
+  pub fn with_markers<I: IntoIterator<Item = (Duration, BuildTagKind, Style)>>(
+    mut self,
+    markers: I,
+  ) -> Self {
+    self.markers.extend(markers);
+    self
+  }
+
+  /// Compute the column of `offset` on a bar of `width` cells
+  pub fn column(offset: Duration, elapsed: Duration, width: u16) -> u16 {
+    if width == 0 || elapsed.is_zero() {
+      return 0;
+    }
+    let column = offset.as_secs_f64() / elapsed.as_secs_f64() * width as f64;
+    (column as u16).min(width - 1)
+  }
+}
+
+impl StatefulWidget for Timeline {
+  type State = TimelineState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let label = format!(
+      " ⏱ {}/{} ",
+      human_duration(state.cursor.min(self.elapsed)),
+      human_duration(self.elapsed)
+    );
+    let label_width = (label.chars().count() as u16).min(area.width);
+    Span::styled(label, Style::default().bold()).render(area, buf);
+    let width = area.width - label_width;
+    let x = area.x + label_width;
+    let cursor = Self::column(state.cursor, self.elapsed, width);
+    for column in 0..width {
+      if let Some(cell) = buf.cell_mut((x + column, area.y)) {
+        cell.set_symbol("─").set_style(match column > cursor {
+          true => Style::default().dark_gray(),
+          false => Style::default(),
+        });
+      }
+    }
+    // the most severe marker wins when several share a column
+    let mut plotted = vec![None::<BuildTagKind>; width as usize];
+    for (offset, kind, style) in self.markers {
+      let column = Self::column(offset, self.elapsed, width);
+      let Some(current) = plotted.get_mut(column as usize) else {
+        continue;
+      };
+      if current.is_some_and(|current| current.severity() >= kind.severity()) {
+        continue;
+      }
+      *current = Some(kind);
+      if let Some(cell) = buf.cell_mut((x + column, area.y)) {
+        cell.set_char(kind.badge()).set_style(style);
+      }
+    }
+    if width > 0 {
+      if let Some(cell) = buf.cell_mut((x + cursor, area.y)) {
+        cell.set_style(Style::default().on_light_blue());
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+  use super::{Timeline, TimelineState};
+
+  #[test]
+  fn column() {
+    let elapsed = Duration::from_secs(10);
+    assert_eq!(Timeline::column(Duration::ZERO, elapsed, 20), 0);
+    assert_eq!(Timeline::column(Duration::from_secs(5), elapsed, 20), 10);
+    assert_eq!(Timeline::column(elapsed, elapsed, 20), 19);
+    assert_eq!(Timeline::column(elapsed, Duration::ZERO, 20), 0);
+  }
+
+  #[test]
+  fn move_cursor() {
+    let elapsed = Duration::from_secs(10);
+    let mut state = TimelineState::default();
+    let press = |state: &mut TimelineState, code| {
+      TimelineState::handle_key(KeyEvent::from(code), state, elapsed)
+    };
+    assert!(!press(&mut state, KeyCode::Left));
+    assert!(press(&mut state, KeyCode::Char('t')));
+    assert_eq!(state.cursor(), elapsed);
+    assert!(press(&mut state, KeyCode::Right));
+    assert_eq!(state.cursor(), elapsed);
+    assert!(press(&mut state, KeyCode::Left));
+    assert_eq!(state.cursor(), Duration::from_millis(9800));
+    assert!(press(&mut state, KeyCode::Home));
+    assert_eq!(state.cursor(), Duration::ZERO);
+    // the log keys still scroll
+    assert!(!press(&mut state, KeyCode::Char('j')));
+    assert!(press(&mut state, KeyCode::Esc));
+    assert!(!state.is_shown());
+  }
+}
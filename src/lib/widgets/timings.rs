@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use ratatui::{
+  layout::Rect,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use crate::{format_count, human_duration, human_size, FrameTimings, IntakeStats};
+
+/// The bar characters of the sparkline, from the lowest to the highest
+const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The overlay displaying the [`FrameTimings`], in the top-right corner
+pub struct PerfOverlay<'a> {
+  timings: &'a FrameTimings,
+  /// The markers disabled for going over their budget, see [`crate::MarkerBudget`]
+  slow_markers: usize,
+  /// The streams of the build read so far
+  intake: &'a [IntakeStats],
+}
+
+impl<'a> PerfOverlay<'a> {
+  /// The height of the overlay, borders included
+  pub const HEIGHT: u16 = 7;
+
+  /// Construct this object
+  pub fn new(timings: &'a FrameTimings) -> Self {
+    Self {
+      timings,
+      slow_markers: 0,
+      intake: &[],
+    }
+  }
+
+  /// Define the number of markers disabled for going over their budget
+  pub fn with_slow_markers(mut self, v: usize) -> Self {
+    self.slow_markers = v;
+    self
+  }
+
+  /// Define the stats of the streams of the build, a line each
+  pub fn with_intake(mut self, intake: &'a [IntakeStats]) -> Self {
+    self.intake = intake;
+    self
+  }
+
+  fn format_duration(d: Duration) -> String {
+    format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+  }
+
+  /// Build the sparkline of the draw times, slow frames are displayed in red
+  fn sparkline(&self, width: usize) -> Line<'static> {
+    let frames = self
+      .timings
+      .iter()
+      .skip(self.timings.len().saturating_sub(width))
+      .collect::<Vec<_>>();
+    let max = frames
+      .iter()
+      .map(|frame| frame.draw)
+      .max()
+      .unwrap_or_default()
+      .max(Duration::from_micros(1));
+    Line::default().spans(frames.into_iter().map(|frame| {
+      let id = (frame.draw.as_secs_f64() / max.as_secs_f64() * (BARS.len() - 1) as f64).round();
+      let bar = Span::from(BARS[id as usize].to_string());
+      match frame.is_slow() {
+        true => bar.red(),
+        false => bar.green(),
+      }
+    }))
+  }
+}
+
+impl<'a> Widget for PerfOverlay<'a> {
+  fn render(self, area: Rect, buf: &mut ratatui::prelude::Buffer) {
+    let width = (self.timings.capacity() as u16 + 2).min(area.width);
+    let area = Rect {
+      x: area.right().saturating_sub(width),
+      y: area.y,
+      width,
+      height: (Self::HEIGHT + self.intake.len() as u16).min(area.height),
+    };
+    let last = self.timings.last().cloned().unwrap_or_default();
+    let total_style = match last.is_slow() {
+      true => Style::default().red().bold(),
+      false => Style::default(),
+    };
+    let mut lines = vec![
+      Line::default().spans([
+        Span::from("pull ").dim(),
+        Span::from(Self::format_duration(last.pull)),
+        Span::from("  prepare ").dim(),
+        Span::from(Self::format_duration(last.prepare)),
+        Span::from("  draw ").dim(),
+        Span::from(Self::format_duration(last.draw)),
+        Span::from("  events ").dim(),
+        Span::from(Self::format_duration(last.events)),
+      ]),
+      Line::default().spans([
+        Span::from("total ").dim(),
+        Span::styled(Self::format_duration(last.total()), total_style),
+        Span::from(format!(
+          "  slow frames (>{}): {}/{}",
+          Self::format_duration(FrameTimings::SLOW_FRAME),
+          self.timings.num_slow(),
+          self.timings.len()
+        ))
+        .dim(),
+      ]),
+      Line::default().spans([
+        Span::from("queue ").dim(),
+        Span::from(Self::format_duration(last.queue_latency)),
+        Span::from(format!(
+          "  max {}",
+          Self::format_duration(self.timings.max_queue_latency())
+        ))
+        .dim(),
+      ]),
+      match self.slow_markers {
+        0 => Line::default(),
+        n => Line::default().spans([
+          Span::from("markers over budget ").dim(),
+          Span::from(n.to_string()).yellow(),
+        ]),
+      },
+    ];
+    // the time blocked waiting for cargo vs the time spent on its lines
+    lines.extend(self.intake.iter().map(|stats| {
+      Line::default().spans([
+        Span::from(format!("{:?} ", stats.origin).to_lowercase()).dim(),
+        Span::from(format!(
+          "{} lines, {}",
+          format_count(stats.lines),
+          human_size(stats.bytes)
+        )),
+        Span::from("  blocked ").dim(),
+        Span::from(human_duration(stats.blocked)),
+        Span::from("  processing ").dim(),
+        Span::from(human_duration(stats.processing)),
+        Span::from("  max gap ").dim(),
+        Span::from(human_duration(stats.max_gap)),
+        Span::from(match stats.dropped {
+          0 => String::new(),
+          n => format!("  dropped {}", format_count(n)),
+        })
+        .dim(),
+      ])
+    }));
+    lines.push(self.sparkline(width.saturating_sub(2) as usize));
+    Clear.render(area, buf);
+    Paragraph::new(lines)
+      .block(Block::bordered().title("Frame timings (F10)"))
+      .on_black()
+      .render(area, buf);
+  }
+}
@@ -0,0 +1,224 @@
+use ratatui::{
+  crossterm::event::{KeyCode, KeyEvent},
+  layout::Alignment,
+  style::{Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Clear, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{BuildTagKind, DeclaredMarker, Markers};
+
+use super::help::popup_area;
+
+/// The state of the [`MarkerToggles`] popup
+#[derive(Default)]
+pub struct TogglesState {
+  show: bool,
+  cursor: usize,
+}
+
+impl TogglesState {
+  /// Check if the popup is displayed
+  pub fn is_shown(&self) -> bool {
+    self.show
+  }
+}
+
+/// The popup enabling or disabling each marker kind of the active rule
+pub struct MarkerToggles {
+  /// The kinds, the style of their first marker, their patterns and their state
+  entries: Vec<(BuildTagKind, Style, String, bool)>,
+}
+
+impl MarkerToggles {
+  /// Construct this object from the declared markers and their current state, one row per kind
+  pub fn new(declared: &[DeclaredMarker], markers: &Markers) -> Self {
+    Self {
+      entries: Self::kinds(declared)
+        .into_iter()
+        .map(|kind| {
+          let of_kind = declared
+            .iter()
+            .filter(|marker| marker.tag == kind)
+            .collect::<Vec<_>>();
+          let patterns = of_kind
+            .iter()
+            .map(|marker| marker.regex.as_str())
+            .collect::<Vec<_>>()
+            .join(" | ");
+          (kind, of_kind[0].style, patterns, markers.is_enabled(kind))
+        })
+        .collect::<Vec<_>>(),
+    }
+  }
+
+  /// Retrieve the kinds of the declared markers once each, in declaration order
+  pub fn kinds(declared: &[DeclaredMarker]) -> Vec<BuildTagKind> {
+    let mut kinds = Vec::new();
+    for marker in declared {
+      if !kinds.contains(&marker.tag) {
+        kinds.push(marker.tag);
+      }
+    }
+    kinds
+  }
+
+  pub fn handle_key(
+    key: KeyEvent,
+    state: &mut TogglesState,
+    kinds: &[BuildTagKind],
+    markers: &mut Markers,
+  ) -> bool {
+    if key.code == KeyCode::Char('K') {
+      state.show = !state.show;
+      state.cursor = 0;
+      return true;
+    }
+    if !state.show {
+      return false;
+    }
+    if key.code == KeyCode::Esc || key.code == KeyCode::Char('q') {
+      state.show = false;
+    } else if key.code == KeyCode::Down {
+      if state.cursor < kinds.len().saturating_sub(1) {
+        state.cursor += 1;
+      }
+    } else if key.code == KeyCode::Up {
+      state.cursor = state.cursor.saturating_sub(1);
+    } else if key.code == KeyCode::Char(' ') || key.code == KeyCode::Enter {
+      if let Some(kind) = kinds.get(state.cursor) {
+        markers.toggle(*kind);
+      }
+    }
+    true
+  }
+}
+
+impl StatefulWidget for MarkerToggles {
+  type State = TogglesState;
+
+  fn render(
+    self,
+    area: ratatui::prelude::Rect,
+    buf: &mut ratatui::prelude::Buffer,
+    state: &mut Self::State,
+  ) {
+    let area = popup_area(area, 40, 40);
+    Clear.render(area, buf);
+    let kind_width = self
+      .entries
+      .iter()
+      .map(|(kind, ..)| kind.to_string().len())
+      .max()
+      .unwrap_or_default();
+    let lines = self
+      .entries
+      .iter()
+      .enumerate()
+      .map(|(id, (kind, style, patterns, enabled))| {
+        let mut line = Line::default().spans([
+          Span::from(match enabled {
+            true => "[x] ",
+            false => "[ ] ",
+          }),
+          Span::styled(
+            format!("{:width$}", kind.to_string(), width = kind_width),
+            *style,
+          ),
+          Span::from(" "),
+          Span::from(patterns.clone()).dim(),
+        ]);
+        if id == state.cursor {
+          line = line.style(Style::default().on_light_blue());
+        }
+        line
+      })
+      .collect::<Vec<_>>();
+    Paragraph::new(lines)
+      .block(
+        Block::bordered()
+          .title("Markers (space: toggle)")
+          .title_alignment(Alignment::Center),
+      )
+      .on_black()
+      .render(area, buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    style::Style,
+    widgets::StatefulWidget,
+  };
+  use regex::Regex;
+
+  use crate::{BuildTagKind, DeclaredMarker, Markers};
+
+  use super::{MarkerToggles, TogglesState};
+
+  fn declared() -> Vec<DeclaredMarker> {
+    [
+      (BuildTagKind::Error, "^error"),
+      (BuildTagKind::Warning, "^warning"),
+      (BuildTagKind::Error, "^fatal"),
+    ]
+    .into_iter()
+    .map(|(kind, regex)| DeclaredMarker::new(kind, Regex::new(regex).unwrap(), Style::default()))
+    .collect()
+  }
+
+  fn rows(declared: &[DeclaredMarker], markers: &Markers, state: &mut TogglesState) -> Vec<String> {
+    let area = Rect::new(0, 0, 100, 20);
+    let mut buf = Buffer::empty(area);
+    MarkerToggles::new(declared, markers).render(area, &mut buf, state);
+    (0..area.height)
+      .map(|y| {
+        (0..area.width)
+          .map(|x| buf[(x, y)].symbol())
+          .collect::<String>()
+      })
+      .filter(|row| row.contains("[x]") || row.contains("[ ]"))
+      .map(|row| row.trim().trim_matches('│').trim().to_string())
+      .collect()
+  }
+
+  #[test]
+  fn one_row_per_kind() {
+    let declared = declared();
+    let kinds = MarkerToggles::kinds(&declared);
+    assert_eq!(kinds, vec![BuildTagKind::Error, BuildTagKind::Warning]);
+
+    let mut markers = Markers::new();
+    let mut state = TogglesState::default();
+    MarkerToggles::handle_key(
+      KeyEvent::from(KeyCode::Char('K')),
+      &mut state,
+      &kinds,
+      &mut markers,
+    );
+    assert_eq!(
+      rows(&declared, &markers, &mut state),
+      vec!["[x] Error   ^error | ^fatal", "[x] Warning ^warning"]
+    );
+
+    // the cursor stops on the last kind
+    for code in [
+      KeyCode::Down,
+      KeyCode::Down,
+      KeyCode::Down,
+      KeyCode::Char(' '),
+    ] {
+      MarkerToggles::handle_key(KeyEvent::from(code), &mut state, &kinds, &mut markers);
+    }
+    assert!(markers.is_enabled(BuildTagKind::Error));
+    assert!(!markers.is_enabled(BuildTagKind::Warning));
+    assert_eq!(
+      rows(&declared, &markers, &mut state),
+      vec!["[x] Error   ^error | ^fatal", "[ ] Warning ^warning"]
+    );
+  }
+}
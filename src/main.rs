@@ -1,15 +1,22 @@
 use std::process::{ExitCode, ExitStatus};
 
-use cargo_nbuild::{App, AppOptions, Result};
+use cargo_nbuild::{prelude::*, Debug};
 
 fn main() -> ExitCode {
   let opt = AppOptions::default().parse();
-  if let Err(e) = App::new(opt).run() {
-    eprintln!("\x1b[0;31mfatal\x1b[0m: {}", e);
-    if let Some(loc) = e.location() {
-      eprintln!("-> \x1b[0;34mat\x1b[0m: {}", loc);
+  let result = App::new(opt).run();
+  Debug::flush();
+  match result {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("\x1b[0;31mfatal\x1b[0m: {}", e);
+      if let Some(snippet) = e.snippet() {
+        eprintln!("{}", snippet);
+      }
+      if let Some(loc) = e.location() {
+        eprintln!("-> \x1b[0;34mat\x1b[0m: {}", loc);
+      }
+      ExitCode::FAILURE
     }
-    return ExitCode::FAILURE;
   }
-  ExitCode::SUCCESS
 }
@@ -0,0 +1,44 @@
+//! The bytes allocated by the display of a snapshot, counted by the global allocator of
+//! this test binary: kept apart from the unit tests running in parallel
+
+use cargo_nbuild::{
+  prelude::*,
+  testing::{allocations_by, CountingAllocator},
+  FoldMap,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn display_allocations() {
+  const NUM_ENTRIES: usize = 10_000;
+  const NUM_FRAMES: usize = 100;
+  let mut build = BuildOutput::from((0..NUM_ENTRIES).map(|i| match i % 4 {
+    0 => format!("error[E0308]: mismatched types #{}", i),
+    1 => format!("  --> src/main.rs:{}:5", i),
+    _ => format!("   | let x: u32 = \"{}\";", i),
+  }));
+  while build.prepare() {}
+  let snapshot = build.snapshot();
+  assert_eq!(snapshot.len(), NUM_ENTRIES);
+  let markers = snapshot.markers().clone();
+  let folds = FoldMap::new(snapshot.folds());
+  let row_size = size_of_val(&snapshot.display(&markers)[0]);
+  let (frames, _) = allocations_by(|| {
+    for _ in 0..NUM_FRAMES {
+      let lines = folds.apply(snapshot.display(&markers));
+      assert_eq!(lines.len(), NUM_ENTRIES);
+    }
+  });
+  // a frame only grows the vecs of the rows borrowing the prepared lines: a few
+  // allocations whatever the number of entries, no line nor tag gets copied
+  let (count, bytes) = (frames.count / NUM_FRAMES, frames.bytes / NUM_FRAMES);
+  assert!(count <= 32, "{} allocations per frame", count);
+  assert!(
+    bytes <= 2 * NUM_ENTRIES * row_size,
+    "{} bytes allocated per frame, for rows of {} bytes",
+    bytes,
+    row_size
+  );
+}
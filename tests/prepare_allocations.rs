@@ -0,0 +1,80 @@
+//! The allocations of [`BuildOutput::prepare`], counted by the global allocator of
+//! this test binary: kept apart from the unit tests running in parallel
+
+use std::thread::spawn;
+
+use cargo_nbuild::{
+  prelude::*,
+  testing::{allocations_by, CountingAllocator},
+  AnsiLine,
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The allocations per entry of [`BLOCK`] before the preparation reused its buffers.
+///
+/// Measured on the parent commit of the one adding this test, counting the allocations
+/// of every thread as [`allocations_by`] does: 35.06. The code it measured is gone, so
+/// measure again there if [`BLOCK`] changes
+const ALLOCATIONS_BEFORE: f64 = 35.06;
+
+/// The lines repeated by the synthetic log: plain, markers, colored and tabulated
+const BLOCK: [&str; 12] = [
+  "   Compiling demo v0.1.0 (/tmp/demo)",
+  "warning: unused variable: `x`",
+  " --> src/main.rs:2:7",
+  "  |",
+  "2 |   let x = 1;",
+  "  |       ^ help: if this is intentional, prefix it with an underscore: `_x`",
+  "  |",
+  "  = note: `#[warn(unused_variables)]` on by default",
+  "",
+  "error[E0308]: mismatched types",
+  "\x1b[1m\x1b[38;5;12m --> \x1b[0msrc/lib.rs:4:9",
+  "\tlet y: u8 = \"a\";   ",
+];
+
+/// The rendering of [`BLOCK`], as prepared before the buffers got reused
+const RENDERED: [&str; 12] = [
+  "\x1b[2m             \x1b[0m    Compiling demo v0.1.0 (/tmp/demo)",
+  "\x1b[1;33mwarning:\x1b[0m  unused variable: `x`",
+  "\x1b[2m             \x1b[0m  --> src/main.rs:2:7",
+  "\x1b[2m             \x1b[0m   |",
+  "\x1b[2m             \x1b[0m 2 |   let x = 1;",
+  "\x1b[2m             \x1b[0m   |       ^ help: if this is intentional, prefix it with an underscore: `_x`",
+  "\x1b[2m             \x1b[0m   |",
+  "\x1b[1;34mnote:\x1b[0m  `#[warn(unused_variables)]` on by default",
+  "\x1b[2m             \x1b[0m ",
+  "\x1b[1;31merror[E0308]:\x1b[0m  mismatched types",
+  "\x1b[2m             \x1b[0m  --> src/lib.rs:4:9",
+  "\x1b[2m             \x1b[0m     let y: u8 = \"a\";",
+];
+
+#[test]
+fn prepare_allocations() {
+  let log = BLOCK.iter().cycle().take(10_000).collect::<Vec<_>>();
+  let mut build = BuildOutput::from(log.iter().copied());
+  // the workers preparing the entries allocate on their own threads
+  let (spawned, _) = allocations_by(|| spawn(|| vec![0u8; 64]).join());
+  assert!(spawned.bytes >= 64, "{:?}", spawned);
+  let (allocations, _) = allocations_by(|| build.prepare());
+  let per_entry = allocations.count as f64 / log.len() as f64;
+  assert!(
+    per_entry <= ALLOCATIONS_BEFORE / 2.0,
+    "{:.2} allocations per entry",
+    per_entry
+  );
+  // the selected error gets highlighted
+  build.markers_mut().unselect();
+  let display = build.display();
+  assert_eq!(display.len(), log.len());
+  for (entry_id, entry) in display.iter().enumerate() {
+    assert_eq!(
+      AnsiLine::render(entry.line()),
+      RENDERED[entry_id % RENDERED.len()],
+      "entry #{}",
+      entry_id
+    );
+  }
+}